@@ -0,0 +1,53 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use raytracer::prelude::*;
+
+// Counting intersection tests sits on the hottest path in the renderer; these
+// compare a plain render, which only checks whether stats are being
+// collected, against one that collects them. Run with
+//     cargo bench --bench stats
+
+fn scene() -> World {
+    let spheres = (0..9)
+        .map(|index| {
+            let [x, y] = [(index % 3) as f64 - 1.0, (index / 3) as f64 - 1.0];
+            Sphere::builder()
+                .set_material(Material::preset())
+                .set_frame_transformation(Transform::from(vec![
+                    TransformKind::Scale(0.4, 0.4, 0.4),
+                    TransformKind::Translate(x, y, 0.0),
+                ]))
+                .build_into()
+        })
+        .collect();
+    let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+    World::new(spheres, vec![light])
+}
+
+fn camera() -> Camera<Native> {
+    Camera::new(Native::new(
+        32,
+        32,
+        Angle::from_degrees(60.0),
+        Orientation::new(
+            Point::new(0.0, 0.0, -5.0),
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        ),
+    ))
+}
+
+fn render(c: &mut Criterion) {
+    let world = scene();
+
+    let mut group = c.benchmark_group("render");
+    group.bench_function("without stats", |b| {
+        b.iter(|| camera().render(black_box(&world)))
+    });
+    group.bench_function("with stats", |b| {
+        b.iter(|| camera().render_with_stats(black_box(&world)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
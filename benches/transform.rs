@@ -0,0 +1,48 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use raytracer::objects::transform::Transformable;
+use raytracer::prelude::*;
+
+// Transform keeps its matrix as a fixed 4x4 array; these compare it against
+// the same work done through the general, heap-allocated Matrix. Run with
+//     cargo bench --bench transform
+
+fn transform() -> Transform {
+    Transform::from(vec![
+        TransformKind::Rotate(Axis::Y, Angle::from_degrees(30.0)),
+        TransformKind::Scale(2.0, 1.0, 0.5),
+        TransformKind::Translate(1.0, -2.0, 3.0),
+    ])
+}
+
+fn transform_point(c: &mut Criterion) {
+    let transform = transform();
+    let matrix = transform.matrix();
+    let point = Point::new(1.0, 2.0, 3.0);
+
+    let mut group = c.benchmark_group("transform point");
+    group.bench_function("Transform", |b| {
+        b.iter(|| black_box(point).transform(black_box(&transform)))
+    });
+    group.bench_function("Matrix", |b| {
+        b.iter(|| Point::from(black_box(matrix.clone()) * &Matrix::from(black_box(point))))
+    });
+    group.finish();
+}
+
+fn compose(c: &mut Criterion) {
+    let transform = transform();
+    let other = Transform::new(TransformKind::Shear(1.0, 0.0, 0.5, 0.0, 0.0, 2.0));
+    let (matrix, other_matrix) = (transform.matrix(), other.matrix());
+
+    let mut group = c.benchmark_group("compose");
+    group.bench_function("Transform", |b| {
+        b.iter(|| black_box(&transform).compose(black_box(&other)))
+    });
+    group.bench_function("Matrix", |b| {
+        b.iter(|| black_box(other_matrix.clone()) * black_box(&matrix))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, transform_point, compose);
+criterion_main!(benches);
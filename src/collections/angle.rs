@@ -1,90 +1,168 @@
-use std::f64::consts::PI as MATH_PI;
+use std::f64::consts::{FRAC_PI_2, PI as MATH_PI, TAU};
+use std::ops::{Add, Mul, Sub};
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+// The angle is kept in both units, so that whichever it was given in reads
+// back exactly, along with its sine and cosine, which rotations would
+// otherwise work out again for every entry of their matrices.
+#[derive(Clone, Copy, Debug)]
 pub struct Angle {
-    degrees: Option<f64>,
-    radians: Option<f64>,
+    degrees: f64,
+    radians: f64,
+    sin: f64,
+    cos: f64,
 }
 
 impl Angle {
+    // sin and cos are not const, so their values are written in as
+    // FRAC_PI_2.sin_cos() and TAU.sin_cos() round them, which keeps these in
+    // agreement with from_degrees rather than exactly 0 and 1
+    pub const RIGHT_ANGLE: Angle = Angle {
+        degrees: 90.0,
+        radians: FRAC_PI_2,
+        sin: 1.0,
+        cos: 6.123_233_995_736_766e-17,
+    };
+
+    pub const FULL_TURN: Angle = Angle {
+        degrees: 360.0,
+        radians: TAU,
+        sin: -2.449_293_598_294_706_4e-16,
+        cos: 1.0,
+    };
+
     pub fn from_degrees(deg: f64) -> Angle {
-        Angle {
-            degrees: Some(deg),
-            radians: None,
-        }
+        Angle::new(deg, deg * (MATH_PI / 180.0))
     }
 
     pub fn from_radians(rad: f64) -> Angle {
+        Angle::new(rad * (180.0 / MATH_PI), rad)
+    }
+
+    fn new(degrees: f64, radians: f64) -> Angle {
+        let (sin, cos) = radians.sin_cos();
         Angle {
-            degrees: None,
-            radians: Some(rad),
+            degrees,
+            radians,
+            sin,
+            cos,
         }
     }
 
-    pub fn degrees(&mut self) -> f64 {
-        match self.degrees {
-            Some(deg) => deg,
-            None => {
-                self.degrees = Some(self.radians.unwrap() * (180.0 / MATH_PI));
-                self.degrees.unwrap()
-            }
-        }
+    pub fn degrees(&self) -> f64 {
+        self.degrees
     }
 
-    pub fn radians(&mut self) -> f64 {
-        match self.radians {
-            Some(rad) => rad,
-            None => {
-                self.radians = Some(self.degrees.unwrap() * (MATH_PI / 180.0));
-                self.radians.unwrap()
-            }
-        }
+    pub fn radians(&self) -> f64 {
+        self.radians
+    }
+
+    // the same as degrees and radians, named as for f64
+    pub fn to_degrees(self) -> f64 {
+        self.degrees
+    }
+
+    pub fn to_radians(self) -> f64 {
+        self.radians
+    }
+
+    pub fn sin(&self) -> f64 {
+        self.sin
+    }
+
+    pub fn cos(&self) -> f64 {
+        self.cos
+    }
+}
+
+// the units are kept together, so comparing radians is enough; the cached
+// sine and cosine follow from them
+impl PartialEq for Angle {
+    fn eq(&self, other: &Angle) -> bool {
+        self.radians == other.radians
+    }
+}
+
+impl Add<Angle> for Angle {
+    type Output = Angle;
+
+    fn add(self, other: Angle) -> Angle {
+        Angle::new(self.degrees + other.degrees, self.radians + other.radians)
+    }
+}
+
+impl Sub<Angle> for Angle {
+    type Output = Angle;
+
+    fn sub(self, other: Angle) -> Angle {
+        Angle::new(self.degrees - other.degrees, self.radians - other.radians)
+    }
+}
+
+impl Mul<f64> for Angle {
+    type Output = Angle;
+
+    fn mul(self, scale: f64) -> Angle {
+        Angle::new(self.degrees * scale, self.radians * scale)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::approx_eq;
 
     #[test]
     fn create_angle_from_degrees() {
         let angle = Angle::from_degrees(145.0);
-        let resulting_angle = Angle {
-            degrees: Some(145.0),
-            radians: None,
-        };
-        assert_eq!(angle, resulting_angle);
+        assert_eq!(angle.degrees(), 145.0);
+        assert_eq!(angle.radians(), 145.0 * (MATH_PI / 180.0));
     }
 
     #[test]
     fn create_angle_from_radians() {
         let angle = Angle::from_radians(2.0);
-        let resulting_angle = Angle {
-            degrees: None,
-            radians: Some(2.0),
-        };
-        assert_eq!(angle, resulting_angle);
+        assert_eq!(angle.radians(), 2.0);
+        assert_eq!(angle.degrees(), 2.0 * (180.0 / MATH_PI));
     }
 
     #[test]
     fn access_angle_from_degrees() {
-        let mut angle = Angle::from_degrees(145.0);
-        let resulting_angle = Angle {
-            degrees: Some(145.0),
-            radians: Some(145.0 * (MATH_PI / 180.0)),
-        };
-        angle.radians();
-        assert_eq!(angle, resulting_angle);
+        let angle = Angle::from_degrees(145.0);
+        assert_eq!(angle.to_radians(), 145.0 * (MATH_PI / 180.0));
+        assert_eq!(angle.sin(), angle.radians().sin());
+        assert_eq!(angle.cos(), angle.radians().cos());
     }
 
     #[test]
     fn access_angle_from_radians() {
-        let mut angle = Angle::from_radians(2.0);
-        let resulting_angle = Angle {
-            degrees: Some(2.0 * (180.0 / MATH_PI)),
-            radians: Some(2.0),
-        };
-        angle.degrees();
-        assert_eq!(angle, resulting_angle);
+        let angle = Angle::from_radians(2.0);
+        assert_eq!(angle.to_degrees(), 2.0 * (180.0 / MATH_PI));
+        assert_eq!(angle.sin(), 2.0_f64.sin());
+    }
+
+    #[test]
+    fn angle_arithmetic() {
+        let angle = Angle::from_degrees(30.0) + Angle::from_degrees(60.0);
+        assert_eq!(angle.degrees(), 90.0);
+        approx_eq!(angle.radians(), Angle::RIGHT_ANGLE.radians());
+        approx_eq!(angle.sin(), 1.0);
+
+        assert_eq!((Angle::RIGHT_ANGLE * 4.0).degrees(), 360.0);
+        assert_eq!(
+            (Angle::FULL_TURN - Angle::RIGHT_ANGLE).degrees(),
+            Angle::from_degrees(270.0).degrees()
+        );
+        approx_eq!((Angle::FULL_TURN * 0.5).cos(), -1.0);
+    }
+
+    #[test]
+    fn named_angles_match_computed_ones() {
+        assert_eq!(Angle::from_degrees(90.0), Angle::RIGHT_ANGLE);
+        assert_eq!(Angle::from_degrees(90.0).sin(), Angle::RIGHT_ANGLE.sin());
+        assert_eq!(Angle::from_degrees(90.0).cos(), Angle::RIGHT_ANGLE.cos());
+        assert_eq!(Angle::from_radians(TAU), Angle::FULL_TURN);
+        assert_eq!(Angle::from_radians(TAU).sin(), Angle::FULL_TURN.sin());
+        assert_eq!(Angle::from_radians(TAU).cos(), Angle::FULL_TURN.cos());
+        assert_ne!(Angle::RIGHT_ANGLE, Angle::FULL_TURN);
     }
 }
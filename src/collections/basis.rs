@@ -0,0 +1,71 @@
+use crate::collections::Vector;
+
+// Three mutually perpendicular unit vectors, with w along a chosen direction
+// (such as a surface normal) and u and v spanning the plane across it. Lets
+// directions be built around w as if w were the z axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrthonormalBasis {
+    pub u: Vector,
+    pub v: Vector,
+    pub w: Vector,
+}
+
+impl OrthonormalBasis {
+    // u and v are picked arbitrarily (but always the same way for the same w)
+    pub fn from_w(w: Vector) -> OrthonormalBasis {
+        let w = w.normalise();
+        // any vector not nearly parallel to w will do
+        let helper = if w.x.abs() < 0.9 {
+            Vector::new(1.0, 0.0, 0.0)
+        } else {
+            Vector::new(0.0, 1.0, 0.0)
+        };
+        let u = w.cross(helper).normalise();
+        let v = w.cross(u);
+        OrthonormalBasis { u, v, w }
+    }
+
+    // the vector with the given components along u, v and w
+    pub fn local_to_world(&self, local: Vector) -> Vector {
+        self.u * local.x + self.v * local.y + self.w * local.z
+    }
+
+    // the components of a vector along u, v and w
+    pub fn world_to_local(&self, world: Vector) -> Vector {
+        Vector::new(world.dot(self.u), world.dot(self.v), world.dot(self.w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::approx_eq;
+
+    #[test]
+    fn basis_is_orthonormal() {
+        for w in [
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.3, -2.0, 0.5),
+        ] {
+            let basis = OrthonormalBasis::from_w(w);
+            assert_eq!(basis.w, w.normalise());
+            for (a, b) in [(basis.u, basis.v), (basis.v, basis.w), (basis.w, basis.u)] {
+                approx_eq!(a.magnitude(), 1.0);
+                approx_eq!(a.dot(b), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn convert_between_local_and_world() {
+        let basis = OrthonormalBasis::from_w(Vector::new(1.0, 1.0, 0.0));
+        let world = basis.local_to_world(Vector::new(0.0, 0.0, 2.0));
+        approx_eq!(world.x, 2.0_f64.sqrt());
+        approx_eq!(world.y, 2.0_f64.sqrt());
+        let local = basis.world_to_local(basis.local_to_world(Vector::new(0.1, -0.2, 0.3)));
+        approx_eq!(local.x, 0.1);
+        approx_eq!(local.y, -0.2);
+        approx_eq!(local.z, 0.3);
+    }
+}
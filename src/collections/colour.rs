@@ -1,4 +1,6 @@
-use std::ops::{Add, Mul, Sub};
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Sub};
+
+use crate::utils::ApproxEq;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Colour {
@@ -8,9 +10,48 @@ pub struct Colour {
 }
 
 impl Colour {
+    pub const BLACK: Colour = Colour {
+        red: 0.0,
+        green: 0.0,
+        blue: 0.0,
+    };
+    pub const WHITE: Colour = Colour {
+        red: 1.0,
+        green: 1.0,
+        blue: 1.0,
+    };
+
     pub fn new(red: f64, green: f64, blue: f64) -> Colour {
         Colour { red, green, blue }
     }
+
+    // relative luminance with Rec. 709 primaries
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+
+    pub fn min(self, other: Colour) -> Colour {
+        Colour::new(
+            self.red.min(other.red),
+            self.green.min(other.green),
+            self.blue.min(other.blue),
+        )
+    }
+
+    pub fn max(self, other: Colour) -> Colour {
+        Colour::new(
+            self.red.max(other.red),
+            self.green.max(other.green),
+            self.blue.max(other.blue),
+        )
+    }
+
+    // false if any channel is infinite or NaN, as a stray sample can be after
+    // dividing by a tiny probability; such samples would spoil a whole
+    // accumulated pixel, so are best checked for and dropped
+    pub fn is_finite(&self) -> bool {
+        self.red.is_finite() && self.green.is_finite() && self.blue.is_finite()
+    }
 }
 
 impl Add<Colour> for Colour {
@@ -69,6 +110,65 @@ impl Mul<Colour> for Colour {
     }
 }
 
+impl Div<f64> for Colour {
+    type Output = Colour;
+
+    fn div(self, other: f64) -> Self::Output {
+        Colour {
+            red: self.red / other,
+            green: self.green / other,
+            blue: self.blue / other,
+        }
+    }
+}
+
+impl Div<Colour> for Colour {
+    type Output = Colour;
+
+    fn div(self, other: Colour) -> Self::Output {
+        Colour {
+            red: self.red / other.red,
+            green: self.green / other.green,
+            blue: self.blue / other.blue,
+        }
+    }
+}
+
+impl AddAssign<Colour> for Colour {
+    fn add_assign(&mut self, other: Colour) {
+        *self = *self + other;
+    }
+}
+
+// adds the scalar to every channel
+impl AddAssign<f64> for Colour {
+    fn add_assign(&mut self, other: f64) {
+        self.red += other;
+        self.green += other;
+        self.blue += other;
+    }
+}
+
+impl MulAssign<f64> for Colour {
+    fn mul_assign(&mut self, other: f64) {
+        *self = *self * other;
+    }
+}
+
+impl MulAssign<Colour> for Colour {
+    fn mul_assign(&mut self, other: Colour) {
+        *self = *self * other;
+    }
+}
+
+impl ApproxEq for Colour {
+    fn approx_eq_within(&self, other: &Colour, tolerance: f64) -> bool {
+        self.red.approx_eq_within(&other.red, tolerance)
+            && self.green.approx_eq_within(&other.green, tolerance)
+            && self.blue.approx_eq_within(&other.blue, tolerance)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,4 +212,53 @@ mod tests {
         let resulting_colour = Colour::new(0.9, 0.2, 0.25);
         assert_eq!(colour1 * colour2, resulting_colour);
     }
+
+    #[test]
+    fn colour_luminance() {
+        assert_eq!(Colour::new(1.0, 1.0, 1.0).luminance(), 1.0);
+        assert_eq!(Colour::new(0.0, 1.0, 0.0).luminance(), 0.7152);
+    }
+
+    #[test]
+    fn div_colours() {
+        let colour = Colour::new(0.4, 0.6, 0.8);
+        assert_eq!(colour / 2.0, Colour::new(0.2, 0.3, 0.4));
+        assert_eq!(
+            colour / Colour::new(0.5, 2.0, 0.8),
+            Colour::new(0.8, 0.3, 1.0)
+        );
+    }
+
+    #[test]
+    fn accumulate_in_place() {
+        let mut colour = Colour::BLACK;
+        colour += Colour::new(0.5, 1.0, 1.5);
+        colour += Colour::WHITE;
+        colour *= 2.0;
+        assert_eq!(colour, Colour::new(3.0, 4.0, 5.0));
+        colour *= Colour::new(0.5, 0.0, 1.0);
+        assert_eq!(colour, Colour::new(1.5, 0.0, 5.0));
+    }
+
+    #[test]
+    fn add_scalar_in_place() {
+        let mut colour = Colour::new(0.5, 1.0, 1.5);
+        colour += 0.25;
+        assert_eq!(colour, Colour::new(0.75, 1.25, 1.75));
+    }
+
+    #[test]
+    fn componentwise_min_and_max() {
+        let colour1 = Colour::new(0.2, 3.0, 0.5);
+        let colour2 = Colour::new(1.0, 0.1, 0.5);
+        assert_eq!(colour1.min(colour2), Colour::new(0.2, 0.1, 0.5));
+        assert_eq!(colour1.max(colour2), Colour::new(1.0, 3.0, 0.5));
+    }
+
+    #[test]
+    fn detect_non_finite_colours() {
+        assert!(Colour::new(1e300, 0.0, 0.0).is_finite());
+        assert!(!(Colour::WHITE / 0.0).is_finite());
+        assert!(!Colour::new(0.0, f64::NAN, 0.0).is_finite());
+    }
 }
@@ -0,0 +1,224 @@
+// Conversions to and from the equivalent types of other maths crates, each
+// behind a feature named after the crate, so that geometry made by other tools
+// can be passed in without copying it out element by element. Single precision
+// types are widened on the way in and narrowed on the way out. Only 4 by 4
+// matrices convert, so converting a Matrix can fail; a Transform always can.
+
+use super::{Matrix, MatrixSizeError, Point, Vector};
+use crate::objects::Transform;
+
+fn rows_of(matrix: &Matrix) -> Result<[[f64; 4]; 4], MatrixSizeError> {
+    matrix.to_matrix4().ok_or(MatrixSizeError {
+        rows: matrix.rows(),
+        cols: matrix.cols(),
+    })
+}
+
+fn matrix_from_rows(rows: [[f64; 4]; 4]) -> Matrix {
+    Matrix::from(&rows.iter().map(|row| row.to_vec()).collect())
+}
+
+#[cfg(feature = "glam")]
+mod glam_conversions {
+    use glam::{DMat4, DVec3, Mat4, Vec3};
+
+    use super::*;
+
+    // glam matrices are stored by column
+    macro_rules! glam_conversions {
+        ($float:ty, $vec3:ty, $mat4:ty) => {
+            impl From<$vec3> for Point {
+                fn from(vector: $vec3) -> Point {
+                    Point::new(vector.x as f64, vector.y as f64, vector.z as f64)
+                }
+            }
+
+            impl From<Point> for $vec3 {
+                fn from(point: Point) -> $vec3 {
+                    <$vec3>::new(point.x as $float, point.y as $float, point.z as $float)
+                }
+            }
+
+            impl From<$vec3> for Vector {
+                fn from(vector: $vec3) -> Vector {
+                    Vector::new(vector.x as f64, vector.y as f64, vector.z as f64)
+                }
+            }
+
+            impl From<Vector> for $vec3 {
+                fn from(vector: Vector) -> $vec3 {
+                    <$vec3>::new(vector.x as $float, vector.y as $float, vector.z as $float)
+                }
+            }
+
+            impl From<$mat4> for Matrix {
+                fn from(matrix: $mat4) -> Matrix {
+                    let columns = matrix.to_cols_array_2d();
+                    matrix_from_rows(std::array::from_fn(|row| {
+                        std::array::from_fn(|column| columns[column][row] as f64)
+                    }))
+                }
+            }
+
+            impl TryFrom<&Matrix> for $mat4 {
+                type Error = MatrixSizeError;
+
+                fn try_from(matrix: &Matrix) -> Result<$mat4, MatrixSizeError> {
+                    let rows = rows_of(matrix)?;
+                    Ok(<$mat4>::from_cols_array_2d(&std::array::from_fn(
+                        |column| std::array::from_fn(|row| rows[row][column] as $float),
+                    )))
+                }
+            }
+
+            impl From<$mat4> for Transform {
+                fn from(matrix: $mat4) -> Transform {
+                    Transform::from(Matrix::from(matrix))
+                }
+            }
+
+            impl From<&Transform> for $mat4 {
+                fn from(transform: &Transform) -> $mat4 {
+                    <$mat4>::try_from(&transform.matrix()).expect("transforms are 4 by 4")
+                }
+            }
+        };
+    }
+
+    glam_conversions!(f64, DVec3, DMat4);
+    glam_conversions!(f32, Vec3, Mat4);
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::objects::TransformKind;
+
+        #[test]
+        fn convert_glam_vectors() {
+            assert_eq!(
+                Point::from(DVec3::new(1.0, 2.0, 3.0)),
+                Point::new(1.0, 2.0, 3.0)
+            );
+            assert_eq!(
+                Vec3::from(Vector::new(1.0, -0.5, 3.0)),
+                Vec3::new(1.0, -0.5, 3.0)
+            );
+        }
+
+        #[test]
+        fn convert_glam_matrices() {
+            let transform = Transform::new(TransformKind::Translate(1.0, 2.0, 3.0));
+            let matrix = DMat4::from(&transform);
+            assert_eq!(matrix, DMat4::from_translation(DVec3::new(1.0, 2.0, 3.0)));
+            assert_eq!(Transform::from(matrix), transform);
+            assert_eq!(Transform::from(Mat4::from(&transform)), transform);
+            assert_eq!(DMat4::try_from(&transform.matrix()), Ok(matrix));
+            assert_eq!(
+                Mat4::try_from(&Matrix::new(3, 3)),
+                Err(MatrixSizeError { rows: 3, cols: 3 })
+            );
+        }
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+mod nalgebra_conversions {
+    use nalgebra::{Matrix4, Point3, Vector3};
+
+    use super::*;
+
+    macro_rules! nalgebra_conversions {
+        ($float:ty) => {
+            impl From<Point3<$float>> for Point {
+                fn from(point: Point3<$float>) -> Point {
+                    Point::new(point.x as f64, point.y as f64, point.z as f64)
+                }
+            }
+
+            impl From<Point> for Point3<$float> {
+                fn from(point: Point) -> Point3<$float> {
+                    Point3::new(point.x as $float, point.y as $float, point.z as $float)
+                }
+            }
+
+            impl From<Vector3<$float>> for Vector {
+                fn from(vector: Vector3<$float>) -> Vector {
+                    Vector::new(vector.x as f64, vector.y as f64, vector.z as f64)
+                }
+            }
+
+            impl From<Vector> for Vector3<$float> {
+                fn from(vector: Vector) -> Vector3<$float> {
+                    Vector3::new(vector.x as $float, vector.y as $float, vector.z as $float)
+                }
+            }
+
+            impl From<Matrix4<$float>> for Matrix {
+                fn from(matrix: Matrix4<$float>) -> Matrix {
+                    matrix_from_rows(std::array::from_fn(|row| {
+                        std::array::from_fn(|column| matrix[(row, column)] as f64)
+                    }))
+                }
+            }
+
+            impl TryFrom<&Matrix> for Matrix4<$float> {
+                type Error = MatrixSizeError;
+
+                fn try_from(matrix: &Matrix) -> Result<Matrix4<$float>, MatrixSizeError> {
+                    let rows = rows_of(matrix)?;
+                    Ok(Matrix4::from_fn(|row, column| rows[row][column] as $float))
+                }
+            }
+
+            impl From<Matrix4<$float>> for Transform {
+                fn from(matrix: Matrix4<$float>) -> Transform {
+                    Transform::from(Matrix::from(matrix))
+                }
+            }
+
+            impl From<&Transform> for Matrix4<$float> {
+                fn from(transform: &Transform) -> Matrix4<$float> {
+                    Matrix4::try_from(&transform.matrix()).expect("transforms are 4 by 4")
+                }
+            }
+        };
+    }
+
+    nalgebra_conversions!(f64);
+    nalgebra_conversions!(f32);
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::objects::TransformKind;
+
+        #[test]
+        fn convert_nalgebra_vectors() {
+            assert_eq!(
+                Point::from(Point3::new(1.0, 2.0, 3.0)),
+                Point::new(1.0, 2.0, 3.0)
+            );
+            assert_eq!(
+                Vector3::<f32>::from(Vector::new(1.0, -0.5, 3.0)),
+                Vector3::new(1.0, -0.5, 3.0)
+            );
+        }
+
+        #[test]
+        fn convert_nalgebra_matrices() {
+            let transform = Transform::new(TransformKind::Translate(1.0, 2.0, 3.0));
+            let matrix = Matrix4::<f64>::from(&transform);
+            assert_eq!(
+                matrix,
+                Matrix4::new_translation(&Vector3::new(1.0, 2.0, 3.0))
+            );
+            assert_eq!(Transform::from(matrix), transform);
+            assert_eq!(Transform::from(Matrix4::<f32>::from(&transform)), transform);
+            assert_eq!(Matrix4::try_from(&transform.matrix()), Ok(matrix));
+            assert_eq!(
+                Matrix4::<f32>::try_from(&Matrix::new(4, 3)),
+                Err(MatrixSizeError { rows: 4, cols: 3 })
+            );
+        }
+    }
+}
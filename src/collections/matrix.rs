@@ -1,5 +1,8 @@
+use std::fmt;
 use std::ops::{Index, IndexMut, Mul};
 
+use super::simd::{self, Matrix4};
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Matrix {
     rows: usize,
@@ -29,8 +32,39 @@ impl Matrix {
     pub fn cols(&self) -> usize {
         self.cols
     }
+
+    pub(crate) fn to_matrix4(&self) -> Option<Matrix4> {
+        if (self.rows, self.cols) != (4, 4) {
+            return None;
+        }
+        let mut matrix4 = [[0.0; 4]; 4];
+        for (row4, row) in matrix4.iter_mut().zip(&self.matrix) {
+            row4.copy_from_slice(row);
+        }
+        Some(matrix4)
+    }
 }
 
+// A matrix of the wrong size for a fixed-size type (such as another crate's
+// 4 by 4 matrix) it was being converted to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatrixSizeError {
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl fmt::Display for MatrixSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a {}x{} matrix does not convert; only 4x4 matrices do",
+            self.rows, self.cols
+        )
+    }
+}
+
+impl std::error::Error for MatrixSizeError {}
+
 impl From<&Vec<Vec<f64>>> for Matrix {
     // does not consume the vector and requires cloning
     fn from(vec2d: &Vec<Vec<f64>>) -> Self {
@@ -71,6 +105,19 @@ impl Mul<&Matrix> for Matrix {
 
     fn mul(self, other: &Matrix) -> Self::Output {
         assert_eq!(self.cols, other.rows);
+        // transforms are 4x4 and are applied to 4x4 transforms and 4x1 tuples,
+        // which have dedicated kernels
+        if let Some(matrix4) = self.to_matrix4() {
+            if let Some(other4) = other.to_matrix4() {
+                let product = simd::mul4x4(&matrix4, &other4);
+                return Matrix::from(&product.map(Vec::from).to_vec());
+            }
+            if other.cols == 1 {
+                let tuple = [0, 1, 2, 3].map(|i| other[[i, 0]]);
+                let product = simd::mul4x4_tuple(&matrix4, tuple);
+                return Matrix::from(&product.map(|x| vec![x]).to_vec());
+            }
+        }
         let mut resulting_matrix = Matrix::new(self.rows, other.cols);
         for i in 0..self.rows {
             for j in 0..other.cols {
@@ -85,6 +132,12 @@ impl Mul<&Matrix> for Matrix {
 
 pub trait Tuple4: Copy + From<Matrix> {
     fn to_tuple4(self) -> [f64; 4];
+
+    // the inverse of to_tuple4, which implementors should override to skip
+    // building a column matrix
+    fn from_tuple4(tuple: [f64; 4]) -> Self {
+        Self::from(Matrix::from(&tuple.map(|x| vec![x]).to_vec()))
+    }
 }
 
 impl<T: Tuple4> From<T> for Matrix {
@@ -111,14 +164,54 @@ impl Matrix {
         assert!(self.rows >= 2);
 
         if self.rows == 2 {
-            self[[0, 0]] * self[[1, 1]] - self[[0, 1]] * self[[1, 0]]
-        } else {
-            let mut det = 0.0;
-            for i in 0..self.rows {
-                det += self[[0, i]] * self.cofactor([0, i]);
+            return self[[0, 0]] * self[[1, 1]] - self[[0, 1]] * self[[1, 0]];
+        }
+        match self.lu_decompose() {
+            Some(lu) => (0..self.rows).fold(lu.sign, |det, i| det * lu.factors[i][i]),
+            None => 0.0,
+        }
+    }
+
+    // Doolittle LU decomposition with partial pivoting, taking the largest
+    // remaining entry of each column as its pivot so that rounding errors are
+    // not magnified by dividing through by small numbers. None if some column
+    // has no non-zero pivot, that is, if the matrix is singular.
+    fn lu_decompose(&self) -> Option<LuDecomposition> {
+        let n = self.rows;
+        let mut factors = self.matrix.clone();
+        let mut permutation: Vec<usize> = (0..n).collect();
+        let mut sign = 1.0;
+
+        for col in 0..n {
+            let pivot_row = (col..n)
+                .max_by(|&a, &b| factors[a][col].abs().total_cmp(&factors[b][col].abs()))
+                .unwrap();
+            let pivot = factors[pivot_row][col];
+            if pivot == 0.0 || !pivot.is_finite() {
+                return None;
+            }
+            if pivot_row != col {
+                factors.swap(pivot_row, col);
+                permutation.swap(pivot_row, col);
+                sign = -sign;
+            }
+
+            let (above, below) = factors.split_at_mut(col + 1);
+            let pivot_entries = &above[col];
+            for row in below {
+                let multiplier = row[col] / pivot;
+                row[col] = multiplier;
+                for (entry, pivot_entry) in row.iter_mut().zip(pivot_entries).skip(col + 1) {
+                    *entry -= multiplier * pivot_entry;
+                }
             }
-            det
         }
+
+        Some(LuDecomposition {
+            factors,
+            permutation,
+            sign,
+        })
     }
 
     pub fn submatrix(&self, [sm_row, sm_col]: Idx) -> Matrix {
@@ -149,29 +242,72 @@ impl Matrix {
         }
     }
 
+    // panics if the matrix is non-square or singular; try_invert reports that
+    // instead
     pub fn invert(&self) -> Matrix {
-        let (rows, cols) = (self.rows, self.cols);
-        // panics if determinant is uncomputable (non-square matrix), checked by .det() method
-        let det = self.det();
-        assert_ne!(det, 0.0);
-
-        let mut inverse_matrix = Matrix::new(rows, cols);
+        self.try_invert()
+            .expect("matrix is not invertible (it is not square or its determinant is zero)")
+    }
 
-        for i in 0..rows {
-            for j in 0..cols {
-                // implicit transpose
-                inverse_matrix[[j, i]] = self.cofactor([i, j]) / det;
+    // None for a non-square or singular matrix, or one with non-finite entries
+    pub fn try_invert(&self) -> Option<Matrix> {
+        if self.rows != self.cols {
+            return None;
+        }
+        let n = self.rows;
+        let LuDecomposition {
+            factors,
+            permutation,
+            ..
+        } = self.lu_decompose()?;
+
+        let mut inverse_matrix = Matrix::new(n, n);
+
+        // each column of the inverse solves the system for the matching column
+        // of the identity, permuted as the rows were: forwards through the
+        // lower triangle, then backwards through the upper
+        for col in 0..n {
+            let mut column: Vec<f64> = permutation
+                .iter()
+                .map(|&row| if row == col { 1.0 } else { 0.0 })
+                .collect();
+            for i in 0..n {
+                for k in 0..i {
+                    column[i] -= factors[i][k] * column[k];
+                }
+            }
+            for i in (0..n).rev() {
+                for k in i + 1..n {
+                    column[i] -= factors[i][k] * column[k];
+                }
+                column[i] /= factors[i][i];
+            }
+            for (row, value) in column.into_iter().enumerate() {
+                if !value.is_finite() {
+                    return None;
+                }
+                inverse_matrix[[row, col]] = value;
             }
         }
 
-        inverse_matrix
+        Some(inverse_matrix)
     }
 }
 
+// the lower and upper factors share one square, the unit diagonal of the lower
+// being left implicit; permutation[i] is the original row now at row i, and
+// sign is the determinant of that permutation
+struct LuDecomposition {
+    factors: Vec<Vec<f64>>,
+    permutation: Vec<usize>,
+    sign: f64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::{Point, Vector};
     use super::*;
+    use crate::utils::{approx_eq, ApproxEq};
 
     #[test]
     fn create_new_matrix() {
@@ -335,11 +471,11 @@ mod tests {
             vec![1.0, 2.0, -9.0, 6.0],
             vec![-6.0, 7.0, 7.0, -9.0],
         ]);
-        assert_eq!(matrix.cofactor([0, 0]), 690.0);
-        assert_eq!(matrix.cofactor([0, 1]), 447.0);
-        assert_eq!(matrix.cofactor([0, 2]), 210.0);
-        assert_eq!(matrix.cofactor([0, 3]), 51.0);
-        assert_eq!(matrix.det(), -4071.0);
+        approx_eq!(matrix.cofactor([0, 0]), 690.0);
+        approx_eq!(matrix.cofactor([0, 1]), 447.0);
+        approx_eq!(matrix.cofactor([0, 2]), 210.0);
+        approx_eq!(matrix.cofactor([0, 3]), 51.0);
+        approx_eq!(matrix.det(), -4071.0);
     }
 
     #[test]
@@ -352,4 +488,73 @@ mod tests {
         ]);
         assert_eq!(matrix.invert(), matrix);
     }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        let matrix = Matrix::from(&vec![
+            vec![-4.0, 2.0, -2.0, -3.0],
+            vec![9.0, 6.0, 2.0, 6.0],
+            vec![0.0, -5.0, 1.0, -5.0],
+            vec![0.0, 0.0, 0.0, 0.0],
+        ]);
+        assert_eq!(matrix.try_invert(), None);
+    }
+
+    #[test]
+    fn non_square_matrix_has_no_inverse() {
+        assert_eq!(Matrix::new(2, 3).try_invert(), None);
+    }
+
+    #[test]
+    fn determinant_needing_row_swaps() {
+        let matrix = Matrix::from(&vec![
+            vec![0.0, 2.0, 0.0, 0.0, 1.0],
+            vec![3.0, 0.0, 0.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0, 4.0, 0.0],
+            vec![0.0, 0.0, 5.0, 0.0, 0.0],
+            vec![0.0, 1.0, 0.0, 0.0, 1.0],
+        ]);
+        approx_eq!(matrix.det(), 60.0);
+    }
+
+    #[test]
+    fn inverse_of_4x4_matrix() {
+        let matrix = Matrix::from(&vec![
+            vec![-5.0, 2.0, 6.0, -8.0],
+            vec![1.0, -5.0, 1.0, 8.0],
+            vec![7.0, 7.0, -6.0, -7.0],
+            vec![1.0, -3.0, 7.0, 4.0],
+        ]);
+        let expected = [
+            [0.21805, 0.45113, 0.24060, -0.04511],
+            [-0.80827, -1.45677, -0.44361, 0.52068],
+            [-0.07895, -0.22368, -0.05263, 0.19737],
+            [-0.52256, -0.81391, -0.30075, 0.30639],
+        ];
+        let inverse = matrix.invert();
+        for (i, row) in expected.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                assert!(inverse[[i, j]].approx_eq_within(&value, 1e-5));
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_of_badly_scaled_matrix() {
+        // a small scale, a quarter turn leaving a zero in the first pivot, and
+        // a large translation, as in a view of a tiny scene from far away
+        let matrix = Matrix::from(&vec![
+            vec![0.0, 0.0, 1.0, 1e6],
+            vec![0.0, 1e-3, 0.0, -2e6],
+            vec![-1e-3, 0.0, 0.0, 3e6],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ]);
+        let product = matrix.invert() * &matrix;
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                approx_eq!(product[[i, j]], expected);
+            }
+        }
+    }
 }
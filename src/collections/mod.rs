@@ -1,21 +1,30 @@
 pub mod angle;
+pub mod basis;
 pub mod colour;
+#[cfg(any(feature = "glam", feature = "nalgebra"))]
+mod interop;
 pub mod matrix;
 pub mod point;
+pub mod quaternion;
+pub(crate) mod simd;
 pub mod vector;
 
 // crate-level re-exports
 pub(crate) use angle::*;
+pub(crate) use basis::*;
 pub(crate) use colour::*;
 pub(crate) use matrix::*;
 pub(crate) use point::*;
+pub(crate) use quaternion::*;
 pub(crate) use vector::*;
 
 // public re-exports (through crate::prelude)
 pub(super) mod prelude {
     pub use super::angle::Angle;
+    pub use super::basis::OrthonormalBasis;
     pub use super::colour::Colour;
-    pub use super::matrix::{Matrix, Tuple4};
+    pub use super::matrix::{Matrix, MatrixSizeError, Tuple4};
     pub use super::point::Point;
+    pub use super::quaternion::Quaternion;
     pub use super::vector::Vector;
 }
@@ -1,6 +1,7 @@
 use std::ops::{Add, Neg, Sub};
 
 use super::{Matrix, Tuple4, Vector};
+use crate::utils::ApproxEq;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Point {
@@ -10,16 +11,36 @@ pub struct Point {
 }
 
 impl Point {
+    pub const ORIGIN: Point = Point {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
     pub fn new(x: f64, y: f64, z: f64) -> Point {
         Point { x, y, z }
     }
 
     pub fn zero() -> Point {
-        Point {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        }
+        Point::ORIGIN
+    }
+
+    // the smaller of each pair of coordinates, as for the near corner of a
+    // box around both points
+    pub fn min(self, other: Point) -> Point {
+        Point::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    pub fn max(self, other: Point) -> Point {
+        Point::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
     }
 
     pub fn at_infinity(&self) -> bool {
@@ -77,10 +98,46 @@ impl Neg for Point {
     }
 }
 
+impl ApproxEq for Point {
+    fn approx_eq_within(&self, other: &Point, tolerance: f64) -> bool {
+        self.x.approx_eq_within(&other.x, tolerance)
+            && self.y.approx_eq_within(&other.y, tolerance)
+            && self.z.approx_eq_within(&other.z, tolerance)
+    }
+}
+
 impl Tuple4 for Point {
     fn to_tuple4(self) -> [f64; 4] {
         [self.x, self.y, self.z, 1.0]
     }
+
+    fn from_tuple4([x, y, z, _]: [f64; 4]) -> Point {
+        Point::new(x, y, z)
+    }
+}
+
+impl From<[f64; 3]> for Point {
+    fn from([x, y, z]: [f64; 3]) -> Self {
+        Point::new(x, y, z)
+    }
+}
+
+impl From<(f64, f64, f64)> for Point {
+    fn from((x, y, z): (f64, f64, f64)) -> Self {
+        Point::new(x, y, z)
+    }
+}
+
+impl From<Point> for [f64; 3] {
+    fn from(point: Point) -> Self {
+        [point.x, point.y, point.z]
+    }
+}
+
+impl From<Point> for (f64, f64, f64) {
+    fn from(point: Point) -> Self {
+        (point.x, point.y, point.z)
+    }
 }
 
 impl From<Matrix> for Point {
@@ -151,4 +208,23 @@ mod tests {
         let matrix = Matrix::from(point);
         assert_eq!(Point::from(matrix), point);
     }
+
+    #[test]
+    fn convert_points_to_and_from_arrays_and_tuples() {
+        let point = Point::new(1.0, -2.0, 3.0);
+        assert_eq!(Point::from([1.0, -2.0, 3.0]), point);
+        assert_eq!(Point::from((1.0, -2.0, 3.0)), point);
+        assert_eq!(<[f64; 3]>::from(point), [1.0, -2.0, 3.0]);
+        let (x, y, z) = point.into();
+        assert_eq!((x, y, z), (1.0, -2.0, 3.0));
+        assert_eq!(Point::ORIGIN, Point::zero());
+    }
+
+    #[test]
+    fn componentwise_min_and_max() {
+        let point1 = Point::new(1.0, 5.0, -3.0);
+        let point2 = Point::new(2.0, -1.0, -4.0);
+        assert_eq!(point1.min(point2), Point::new(1.0, -1.0, -4.0));
+        assert_eq!(point1.max(point2), Point::new(2.0, 5.0, -3.0));
+    }
 }
@@ -0,0 +1,183 @@
+use std::ops::Mul;
+
+use super::{Angle, Vector};
+use crate::utils::ApproxEq;
+
+// Unit quaternions represent rotations; w is the scalar part.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // counterclockwise about the axis when looking back along it
+    pub fn from_axis_angle(axis: Vector, angle: Angle) -> Quaternion {
+        let half = angle.radians() / 2.0;
+        let axis = axis.normalise() * half.sin();
+        Quaternion::new(half.cos(), axis.x, axis.y, axis.z)
+    }
+
+    // the rotation taking the x, y and z axes to the given orthonormal,
+    // right-handed axes
+    pub fn from_axes(x_axis: Vector, y_axis: Vector, z_axis: Vector) -> Quaternion {
+        let trace = x_axis.x + y_axis.y + z_axis.z;
+        // work from the largest component to keep the square root well away
+        // from zero
+        let quaternion = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new(
+                s / 4.0,
+                (y_axis.z - z_axis.y) / s,
+                (z_axis.x - x_axis.z) / s,
+                (x_axis.y - y_axis.x) / s,
+            )
+        } else if x_axis.x > y_axis.y && x_axis.x > z_axis.z {
+            let s = (1.0 + x_axis.x - y_axis.y - z_axis.z).sqrt() * 2.0;
+            Quaternion::new(
+                (y_axis.z - z_axis.y) / s,
+                s / 4.0,
+                (y_axis.x + x_axis.y) / s,
+                (z_axis.x + x_axis.z) / s,
+            )
+        } else if y_axis.y > z_axis.z {
+            let s = (1.0 + y_axis.y - x_axis.x - z_axis.z).sqrt() * 2.0;
+            Quaternion::new(
+                (z_axis.x - x_axis.z) / s,
+                (y_axis.x + x_axis.y) / s,
+                s / 4.0,
+                (z_axis.y + y_axis.z) / s,
+            )
+        } else {
+            let s = (1.0 + z_axis.z - x_axis.x - y_axis.y).sqrt() * 2.0;
+            Quaternion::new(
+                (x_axis.y - y_axis.x) / s,
+                (z_axis.x + x_axis.z) / s,
+                (z_axis.y + y_axis.z) / s,
+                s / 4.0,
+            )
+        };
+        quaternion.normalise()
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn normalise(&self) -> Quaternion {
+        let magnitude = self.dot(self).sqrt();
+        Quaternion::new(
+            self.w / magnitude,
+            self.x / magnitude,
+            self.y / magnitude,
+            self.z / magnitude,
+        )
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn rotate(&self, vector: Vector) -> Vector {
+        let rotated = *self * Quaternion::new(0.0, vector.x, vector.y, vector.z) * self.conjugate();
+        Vector::new(rotated.x, rotated.y, rotated.z)
+    }
+
+    // spherical interpolation at constant angular speed, along the shorter arc
+    pub fn slerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        let mut cos_theta = self.dot(other);
+        let mut other = *other;
+        if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            other = Quaternion::new(-other.w, -other.x, -other.y, -other.z);
+        }
+        // nearly parallel, where linear interpolation is accurate and stable
+        let (from_weight, to_weight) = if cos_theta > 1.0 - 1e-9 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+        Quaternion::new(
+            self.w * from_weight + other.w * to_weight,
+            self.x * from_weight + other.x * to_weight,
+            self.y * from_weight + other.y * to_weight,
+            self.z * from_weight + other.z * to_weight,
+        )
+        .normalise()
+    }
+}
+
+// Hamilton product; (a * b) rotates by b and then by a
+impl Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Self::Output {
+        Quaternion::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+// q and -q are the same rotation
+impl ApproxEq for Quaternion {
+    fn approx_eq_within(&self, other: &Quaternion, tolerance: f64) -> bool {
+        self.dot(other).abs().approx_eq_within(&1.0, tolerance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn rotate_vectors() {
+        let quarter_turn =
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), Angle::from_radians(FRAC_PI_2));
+        let rotated = quarter_turn.rotate(Vector::new(0.0, 0.0, 1.0));
+        assert!(rotated.approx_eq(&Vector::new(1.0, 0.0, 0.0)));
+
+        let from_axes = Quaternion::from_axes(
+            Vector::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+        );
+        assert!(from_axes.approx_eq(&quarter_turn));
+        let half_turn = quarter_turn * quarter_turn;
+        let rotated = half_turn.rotate(Vector::new(0.0, 0.0, 1.0));
+        assert!(rotated.approx_eq(&Vector::new(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn slerp_between_rotations() {
+        let axis = Vector::new(0.0, 0.0, 1.0);
+        let start = Quaternion::identity();
+        let end = Quaternion::from_axis_angle(axis, Angle::from_radians(FRAC_PI_2));
+        let halfway = start.slerp(&end, 0.5);
+        assert!(halfway.approx_eq(&Quaternion::from_axis_angle(
+            axis,
+            Angle::from_radians(FRAC_PI_2 / 2.0)
+        )));
+        assert!(start.slerp(&end, 0.0).approx_eq(&start));
+        assert!(start.slerp(&end, 1.0).approx_eq(&end));
+    }
+}
@@ -0,0 +1,147 @@
+// Kernels behind the hottest vector and matrix operations. With the `simd`
+// feature on x86_64 they are written with SSE2 intrinsics, two f64 lanes at a
+// time; otherwise the plain scalar versions below are used. Both versions must
+// give the same results up to floating point rounding.
+
+pub(crate) type Matrix4 = [[f64; 4]; 4];
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod kernels {
+    use std::arch::x86_64::*;
+
+    use super::Matrix4;
+
+    // SAFETY: SSE2 is part of the x86_64 baseline, so the intrinsics are always
+    // available and no runtime detection is needed
+
+    fn horizontal_sum(pair: __m128d) -> f64 {
+        unsafe { _mm_cvtsd_f64(_mm_add_sd(pair, _mm_unpackhi_pd(pair, pair))) }
+    }
+
+    pub(crate) fn dot3(a: [f64; 3], b: [f64; 3]) -> f64 {
+        unsafe {
+            let xy = _mm_mul_pd(_mm_set_pd(a[1], a[0]), _mm_set_pd(b[1], b[0]));
+            horizontal_sum(xy) + a[2] * b[2]
+        }
+    }
+
+    pub(crate) fn cross3(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+        unsafe {
+            // (a.y, a.z) * (b.z, b.x) - (a.z, a.x) * (b.y, b.z) gives x and y
+            let xy = _mm_sub_pd(
+                _mm_mul_pd(_mm_set_pd(a[2], a[1]), _mm_set_pd(b[0], b[2])),
+                _mm_mul_pd(_mm_set_pd(a[0], a[2]), _mm_set_pd(b[2], b[1])),
+            );
+            [
+                _mm_cvtsd_f64(xy),
+                _mm_cvtsd_f64(_mm_unpackhi_pd(xy, xy)),
+                a[0] * b[1] - a[1] * b[0],
+            ]
+        }
+    }
+
+    pub(crate) fn mul4x4(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+        unsafe {
+            let b_rows: [[__m128d; 2]; 4] =
+                b.map(|row| [_mm_set_pd(row[1], row[0]), _mm_set_pd(row[3], row[2])]);
+            a.map(|row| {
+                let mut low = _mm_setzero_pd();
+                let mut high = _mm_setzero_pd();
+                for (&scalar, b_row) in row.iter().zip(&b_rows) {
+                    let scalar = _mm_set1_pd(scalar);
+                    low = _mm_add_pd(low, _mm_mul_pd(scalar, b_row[0]));
+                    high = _mm_add_pd(high, _mm_mul_pd(scalar, b_row[1]));
+                }
+                [
+                    _mm_cvtsd_f64(low),
+                    _mm_cvtsd_f64(_mm_unpackhi_pd(low, low)),
+                    _mm_cvtsd_f64(high),
+                    _mm_cvtsd_f64(_mm_unpackhi_pd(high, high)),
+                ]
+            })
+        }
+    }
+
+    pub(crate) fn mul4x4_tuple(a: &Matrix4, tuple: [f64; 4]) -> [f64; 4] {
+        unsafe {
+            let low = _mm_set_pd(tuple[1], tuple[0]);
+            let high = _mm_set_pd(tuple[3], tuple[2]);
+            a.map(|row| {
+                horizontal_sum(_mm_add_pd(
+                    _mm_mul_pd(_mm_set_pd(row[1], row[0]), low),
+                    _mm_mul_pd(_mm_set_pd(row[3], row[2]), high),
+                ))
+            })
+        }
+    }
+}
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+mod kernels {
+    use super::Matrix4;
+
+    pub(crate) fn dot3(a: [f64; 3], b: [f64; 3]) -> f64 {
+        a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+    }
+
+    pub(crate) fn cross3(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    }
+
+    pub(crate) fn mul4x4(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+        let mut product = [[0.0; 4]; 4];
+        for (i, row) in a.iter().enumerate() {
+            for j in 0..4 {
+                product[i][j] = (0..4).map(|k| row[k] * b[k][j]).sum();
+            }
+        }
+        product
+    }
+
+    pub(crate) fn mul4x4_tuple(a: &Matrix4, tuple: [f64; 4]) -> [f64; 4] {
+        a.map(|row| (0..4).map(|k| row[k] * tuple[k]).sum())
+    }
+}
+
+pub(crate) use kernels::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: Matrix4 = [
+        [1.0, 2.0, 3.0, 4.0],
+        [5.0, 6.0, 7.0, 8.0],
+        [9.0, 8.0, 7.0, 6.0],
+        [5.0, 4.0, 3.0, 2.0],
+    ];
+    const B: Matrix4 = [
+        [-2.0, 1.0, 2.0, 3.0],
+        [3.0, 2.0, 1.0, -1.0],
+        [4.0, 3.0, 6.0, 5.0],
+        [1.0, 2.0, 7.0, 8.0],
+    ];
+
+    #[test]
+    fn kernels_match_scalar_definitions() {
+        assert_eq!(dot3([1.0, 2.0, 3.0], [2.0, 3.0, 4.0]), 20.0);
+        assert_eq!(cross3([1.0, 2.0, 3.0], [2.0, 3.0, 4.0]), [-1.0, 2.0, -1.0]);
+        assert_eq!(
+            mul4x4(&A, &B),
+            [
+                [20.0, 22.0, 50.0, 48.0],
+                [44.0, 54.0, 114.0, 108.0],
+                [40.0, 58.0, 110.0, 102.0],
+                [16.0, 26.0, 46.0, 42.0],
+            ]
+        );
+        assert_eq!(
+            mul4x4_tuple(&A, [1.0, 2.0, 3.0, 1.0]),
+            [18.0, 46.0, 52.0, 24.0]
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
-use super::{Matrix, Point, Tuple4};
+use super::{simd, Matrix, Point, Tuple4};
+use crate::utils::ApproxEq;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vector {
@@ -101,7 +102,7 @@ impl Div<f64> for Vector {
 
 impl Vector {
     pub fn magnitude(self) -> f64 {
-        (self.x.powf(2.0_f64) + self.y.powf(2.0_f64) + self.z.powf(2.0_f64)).sqrt()
+        self.dot(self).sqrt()
     }
 
     pub fn normalise(self) -> Vector {
@@ -114,26 +115,80 @@ impl Vector {
     }
 
     pub fn dot(self, other: Vector) -> f64 {
-        (self.x * other.x) + (self.y * other.y) + (self.z * other.z)
+        simd::dot3([self.x, self.y, self.z], [other.x, other.y, other.z])
     }
 
     pub fn cross(self, other: Vector) -> Vector {
-        Vector {
-            x: (self.y * other.z) - (self.z * other.y),
-            y: (self.z * other.x) - (self.x * other.z),
-            z: (self.x * other.y) - (self.y * other.x),
-        }
+        let [x, y, z] = simd::cross3([self.x, self.y, self.z], [other.x, other.y, other.z]);
+        Vector { x, y, z }
     }
 
     pub fn reflect(self, normal: Vector) -> Vector {
         self - normal * 2.0 * self.dot(normal)
     }
+
+    // the vector t of the way from this one to the other
+    pub fn lerp(self, other: Vector, t: f64) -> Vector {
+        self + (other - self) * t
+    }
+
+    pub fn min(self, other: Vector) -> Vector {
+        Vector::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    pub fn max(self, other: Vector) -> Vector {
+        Vector::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+}
+
+impl ApproxEq for Vector {
+    fn approx_eq_within(&self, other: &Vector, tolerance: f64) -> bool {
+        self.x.approx_eq_within(&other.x, tolerance)
+            && self.y.approx_eq_within(&other.y, tolerance)
+            && self.z.approx_eq_within(&other.z, tolerance)
+    }
 }
 
 impl Tuple4 for Vector {
     fn to_tuple4(self) -> [f64; 4] {
         [self.x, self.y, self.z, 0.0]
     }
+
+    fn from_tuple4([x, y, z, _]: [f64; 4]) -> Vector {
+        Vector::new(x, y, z)
+    }
+}
+
+impl From<[f64; 3]> for Vector {
+    fn from([x, y, z]: [f64; 3]) -> Self {
+        Vector::new(x, y, z)
+    }
+}
+
+impl From<(f64, f64, f64)> for Vector {
+    fn from((x, y, z): (f64, f64, f64)) -> Self {
+        Vector::new(x, y, z)
+    }
+}
+
+impl From<Vector> for [f64; 3] {
+    fn from(vector: Vector) -> Self {
+        [vector.x, vector.y, vector.z]
+    }
+}
+
+impl From<Vector> for (f64, f64, f64) {
+    fn from(vector: Vector) -> Self {
+        (vector.x, vector.y, vector.z)
+    }
 }
 
 impl From<Matrix> for Vector {
@@ -275,4 +330,22 @@ mod tests {
         approx_eq!(vector2_reflected.y, resulting_vector2.y);
         approx_eq!(vector2_reflected.z, resulting_vector2.z);
     }
+
+    #[test]
+    fn convert_vectors_to_and_from_arrays_and_tuples() {
+        let vector = Vector::new(1.0, -2.0, 3.0);
+        assert_eq!(Vector::from([1.0, -2.0, 3.0]), vector);
+        assert_eq!(Vector::from((1.0, -2.0, 3.0)), vector);
+        assert_eq!(<[f64; 3]>::from(vector), [1.0, -2.0, 3.0]);
+        assert_eq!(<(f64, f64, f64)>::from(vector), (1.0, -2.0, 3.0));
+    }
+
+    #[test]
+    fn lerp_and_componentwise_min_and_max() {
+        let vector1 = Vector::new(0.0, 2.0, -4.0);
+        let vector2 = Vector::new(4.0, 2.0, 0.0);
+        assert_eq!(vector1.lerp(vector2, 0.25), Vector::new(1.0, 2.0, -3.0));
+        assert_eq!(vector1.min(vector2), vector1);
+        assert_eq!(vector1.max(vector2), Vector::new(4.0, 2.0, 0.0));
+    }
 }
@@ -0,0 +1,308 @@
+// Flat C interface for embedding the renderer, laid out for cbindgen. Worlds are
+// opaque handles created by rt_world_new and released with rt_world_free; every
+// other function reports success or failure through RtStatus rather than
+// panicking across the boundary.
+//
+//     RtWorld *world = rt_world_new();
+//     rt_world_add_sphere(world, (RtVec3){0, 0, 0}, 1.0, (RtVec3){1, 0.2, 1});
+//     rt_world_add_light(world, (RtVec3){-10, 10, -10}, (RtVec3){1, 1, 1});
+//     rt_render_rgba(world, &camera, pixels, width * height * 4);
+//     rt_world_free(world);
+
+// safety requirements are set out in plain comments, as elsewhere in the crate
+#![allow(clippy::missing_safety_doc)]
+
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::collections::{Angle, Colour, Point, Vector};
+use crate::objects::{Light, Material, Shape, Sphere, Transform, TransformKind};
+use crate::scenes::{Camera, Native, Orientation, World};
+use crate::utils::{BuildInto, Buildable, ConsumingBuilder};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RtStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidArgument = 2,
+    BufferTooSmall = 3,
+    RenderFailed = 4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RtVec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RtCamera {
+    pub width: usize,
+    pub height: usize,
+    pub field_of_view_degrees: f64,
+    pub from: RtVec3,
+    pub to: RtVec3,
+    pub up: RtVec3,
+}
+
+// Scene description gathered through the C calls; a World is only built when
+// rendering.
+#[derive(Debug, Default)]
+pub struct RtWorld {
+    spheres: Vec<(RtVec3, f64, RtVec3)>,
+    lights: Vec<(RtVec3, RtVec3)>,
+}
+
+impl RtWorld {
+    fn world(&self) -> World {
+        let objects: Vec<Shape> = self
+            .spheres
+            .iter()
+            .map(|&(centre, radius, colour)| {
+                Sphere::builder()
+                    .set_frame_transformation(Transform::from(vec![
+                        TransformKind::Scale(radius, radius, radius),
+                        TransformKind::Translate(centre.x, centre.y, centre.z),
+                    ]))
+                    .set_material(
+                        Material::builder()
+                            .set_colour(Colour::new(colour.x, colour.y, colour.z))
+                            .build(),
+                    )
+                    .build_into()
+            })
+            .collect();
+        let lights = self
+            .lights
+            .iter()
+            .map(|&(position, intensity)| {
+                Light::new(
+                    Point::new(position.x, position.y, position.z),
+                    Colour::new(intensity.x, intensity.y, intensity.z),
+                )
+            })
+            .collect();
+        World::new(objects, lights)
+    }
+}
+
+fn finite(vector: RtVec3) -> bool {
+    vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite()
+}
+
+#[no_mangle]
+pub extern "C" fn rt_world_new() -> *mut RtWorld {
+    Box::into_raw(Box::default())
+}
+
+// # Safety
+// world must be null or a handle from rt_world_new that has not been freed;
+// it must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rt_world_free(world: *mut RtWorld) {
+    if !world.is_null() {
+        drop(unsafe { Box::from_raw(world) });
+    }
+}
+
+// colour components are usually between 0 and 1
+//
+// # Safety
+// world must be null or a live handle from rt_world_new.
+#[no_mangle]
+pub unsafe extern "C" fn rt_world_add_sphere(
+    world: *mut RtWorld,
+    centre: RtVec3,
+    radius: f64,
+    colour: RtVec3,
+) -> RtStatus {
+    let Some(world) = (unsafe { world.as_mut() }) else {
+        return RtStatus::NullPointer;
+    };
+    if !(finite(centre) && finite(colour) && radius.is_finite() && radius > 0.0) {
+        return RtStatus::InvalidArgument;
+    }
+    world.spheres.push((centre, radius, colour));
+    RtStatus::Ok
+}
+
+// # Safety
+// world must be null or a live handle from rt_world_new.
+#[no_mangle]
+pub unsafe extern "C" fn rt_world_add_light(
+    world: *mut RtWorld,
+    position: RtVec3,
+    intensity: RtVec3,
+) -> RtStatus {
+    let Some(world) = (unsafe { world.as_mut() }) else {
+        return RtStatus::NullPointer;
+    };
+    if !(finite(position) && finite(intensity)) {
+        return RtStatus::InvalidArgument;
+    }
+    world.lights.push((position, intensity));
+    RtStatus::Ok
+}
+
+// Renders into a buffer of width * height * 4 bytes, row by row from the top
+// left, as 8-bit RGBA with alpha always 255.
+//
+// # Safety
+// world and camera must be null or valid pointers, and pixels must be null or
+// point to at least buffer_length writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rt_render_rgba(
+    world: *const RtWorld,
+    camera: *const RtCamera,
+    pixels: *mut u8,
+    buffer_length: usize,
+) -> RtStatus {
+    let (Some(world), Some(camera)) = (unsafe { world.as_ref() }, unsafe { camera.as_ref() })
+    else {
+        return RtStatus::NullPointer;
+    };
+    if pixels.is_null() {
+        return RtStatus::NullPointer;
+    }
+    let direction = Vector::new(
+        camera.to.x - camera.from.x,
+        camera.to.y - camera.from.y,
+        camera.to.z - camera.from.z,
+    );
+    let up = Vector::new(camera.up.x, camera.up.y, camera.up.z);
+    if camera.width == 0
+        || camera.height == 0
+        || !(camera.field_of_view_degrees > 0.0 && camera.field_of_view_degrees < 180.0)
+        || !(finite(camera.from) && finite(camera.to) && finite(camera.up))
+        || direction.magnitude() == 0.0
+        || direction.cross(up).magnitude() == 0.0
+    {
+        return RtStatus::InvalidArgument;
+    }
+    let Some(required_length) = camera
+        .width
+        .checked_mul(camera.height)
+        .and_then(|count| count.checked_mul(4))
+    else {
+        return RtStatus::InvalidArgument;
+    };
+    if buffer_length < required_length {
+        return RtStatus::BufferTooSmall;
+    }
+
+    let rendered = panic::catch_unwind(AssertUnwindSafe(|| {
+        let orientation = Orientation::new(
+            Point::new(camera.from.x, camera.from.y, camera.from.z),
+            Point::new(camera.to.x, camera.to.y, camera.to.z),
+            up,
+        );
+        Camera::new(Native::new(
+            camera.width,
+            camera.height,
+            Angle::from_degrees(camera.field_of_view_degrees),
+            orientation,
+        ))
+        .render(&world.world())
+    }));
+    match rendered {
+        Ok(Ok(image)) => {
+            let output = unsafe { std::slice::from_raw_parts_mut(pixels, required_length) };
+            output.copy_from_slice(&image.to_rgba8());
+            RtStatus::Ok
+        }
+        _ => RtStatus::RenderFailed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> RtCamera {
+        RtCamera {
+            width: 5,
+            height: 5,
+            field_of_view_degrees: 60.0,
+            from: RtVec3 {
+                x: 0.0,
+                y: 0.0,
+                z: -5.0,
+            },
+            to: RtVec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            up: RtVec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn render_through_c_interface() {
+        let origin = RtVec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let red = RtVec3 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let light = RtVec3 {
+            x: -10.0,
+            y: 10.0,
+            z: -10.0,
+        };
+        let white = RtVec3 {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        };
+        unsafe {
+            let world = rt_world_new();
+            assert_eq!(rt_world_add_sphere(world, origin, 1.0, red), RtStatus::Ok);
+            assert_eq!(rt_world_add_light(world, light, white), RtStatus::Ok);
+            assert_eq!(
+                rt_world_add_sphere(world, origin, -1.0, red),
+                RtStatus::InvalidArgument
+            );
+
+            let mut pixels = vec![0_u8; 5 * 5 * 4];
+            let status = rt_render_rgba(world, &camera(), pixels.as_mut_ptr(), pixels.len());
+            assert_eq!(status, RtStatus::Ok);
+            let centre = &pixels[(2 * 5 + 2) * 4..(2 * 5 + 3) * 4];
+            assert!(centre[0] > 0 && centre[1] == 0 && centre[2] == 0 && centre[3] == 255);
+            assert_eq!(&pixels[..4], &[0, 0, 0, 255]);
+
+            assert_eq!(
+                rt_render_rgba(world, &camera(), pixels.as_mut_ptr(), 10),
+                RtStatus::BufferTooSmall
+            );
+            rt_world_free(world);
+        }
+    }
+
+    #[test]
+    fn reject_null_handles() {
+        unsafe {
+            let mut pixels = [0_u8; 4];
+            assert_eq!(
+                rt_render_rgba(std::ptr::null(), &camera(), pixels.as_mut_ptr(), 4),
+                RtStatus::NullPointer
+            );
+            assert_eq!(
+                rt_world_add_light(std::ptr::null_mut(), camera().from, camera().up),
+                RtStatus::NullPointer
+            );
+            rt_world_free(std::ptr::null_mut());
+        }
+    }
+}
@@ -1,4 +1,5 @@
 pub mod collections;
+pub mod ffi;
 pub mod objects;
 pub mod scenes;
 pub(crate) mod utils;
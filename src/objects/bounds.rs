@@ -1,7 +1,8 @@
-use std::ops::Add;
+use std::ops::{Add, Range};
 
-use crate::collections::Point;
+use crate::collections::{Point, Vector};
 use crate::objects::{Ray, Transform, Transformable};
+use crate::scenes::stats;
 use crate::utils::EPSILON;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -110,45 +111,107 @@ impl BoundingBox {
             && self.z_range == [f64::NEG_INFINITY, f64::INFINITY])
     }
 
+    // points on the faces count as inside
+    pub fn contains_point(&self, point: Point) -> bool {
+        [
+            (self.x_range, point.x),
+            (self.y_range, point.y),
+            (self.z_range, point.z),
+        ]
+        .into_iter()
+        .all(|([min, max], coordinate)| min <= coordinate && coordinate <= max)
+    }
+
+    pub fn contains_box(&self, other: &BoundingBox) -> bool {
+        [
+            (self.x_range, other.x_range),
+            (self.y_range, other.y_range),
+            (self.z_range, other.z_range),
+        ]
+        .into_iter()
+        .all(|([min, max], [other_min, other_max])| min <= other_min && other_max <= max)
+    }
+
+    // boxes that only touch count as intersecting
+    pub fn intersects_box(&self, other: &BoundingBox) -> bool {
+        [
+            (self.x_range, other.x_range),
+            (self.y_range, other.y_range),
+            (self.z_range, other.z_range),
+        ]
+        .into_iter()
+        .all(|([min, max], [other_min, other_max])| min <= other_max && other_min <= max)
+    }
+
+    // None for boxes that are unbounded along any axis
+    pub fn centroid(&self) -> Option<Point> {
+        let [x, y, z] =
+            [self.x_range, self.y_range, self.z_range].map(|[min, max]| (min + max) / 2.0);
+        [x, y, z]
+            .into_iter()
+            .all(f64::is_finite)
+            .then(|| Point::new(x, y, z))
+    }
+
+    // infinite for boxes that are unbounded along any axis, even flat ones
+    pub fn surface_area(&self) -> f64 {
+        let [x, y, z] = [self.x_range, self.y_range, self.z_range].map(|[min, max]| max - min);
+        if [x, y, z].into_iter().all(f64::is_finite) {
+            2.0 * (x * y + y * z + z * x)
+        } else {
+            f64::INFINITY
+        }
+    }
+
     pub fn intersect_bounds<'world: 'ray, 'ray>(
         &'world self,
         ray: &'ray Ray,
         transform_stack: &Vec<&'ray Transform>,
     ) -> bool {
-        fn check_axis(range: [f64; 2], origin: f64, direction: f64) -> (f64, f64) {
-            assert!(range[0] <= range[1]);
-
-            let [min, max] = range;
-            let tmin_numerator = min - origin;
-            let tmax_numerator = max - origin;
-
-            let tmin;
-            let tmax;
-            if direction.abs() >= EPSILON {
-                tmin = tmin_numerator / direction;
-                tmax = tmax_numerator / direction;
-            } else {
-                tmin = tmin_numerator * f64::INFINITY;
-                tmax = tmax_numerator * f64::INFINITY;
-            }
-
-            if tmin > tmax {
-                (tmax, tmin)
-            } else {
-                (tmin, tmax)
-            }
-        }
-
         let ray = super::shape::transform_through_stack_forwards(*ray, transform_stack);
+        self.clip_ray(&ray).is_some()
+    }
 
-        let (xtmin, xtmax) = check_axis(self.x_range, ray.origin.x, ray.direction.x);
-        let (ytmin, ytmax) = check_axis(self.y_range, ray.origin.y, ray.direction.y);
-        let (ztmin, ztmax) = check_axis(self.z_range, ray.origin.z, ray.direction.z);
+    // whether the ray passes through the box anywhere within the range of t
+    pub fn overlaps_ray(&self, ray: &Ray, t_range: &Range<f64>) -> bool {
+        self.clip_ray(ray)
+            .is_some_and(|(tmin, tmax)| tmin < t_range.end && tmax >= t_range.start)
+    }
+
+    // range of t over which the ray lies inside the box, if it meets it at all
+    pub fn clip_ray(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (xtmin, xtmax) = BoundingBox::check_axis(self.x_range, ray.origin.x, ray.direction.x);
+        let (ytmin, ytmax) = BoundingBox::check_axis(self.y_range, ray.origin.y, ray.direction.y);
+        let (ztmin, ztmax) = BoundingBox::check_axis(self.z_range, ray.origin.z, ray.direction.z);
 
         let tmin = [xtmin, ytmin, ztmin].into_iter().reduce(f64::max).unwrap();
         let tmax = [xtmax, ytmax, ztmax].into_iter().reduce(f64::min).unwrap();
 
-        tmax >= tmin
+        (tmax >= tmin).then_some((tmin, tmax))
+    }
+
+    fn check_axis(range: [f64; 2], origin: f64, direction: f64) -> (f64, f64) {
+        assert!(range[0] <= range[1]);
+
+        let [min, max] = range;
+        let tmin_numerator = min - origin;
+        let tmax_numerator = max - origin;
+
+        let tmin;
+        let tmax;
+        if direction.abs() >= EPSILON {
+            tmin = tmin_numerator / direction;
+            tmax = tmax_numerator / direction;
+        } else {
+            tmin = tmin_numerator * f64::INFINITY;
+            tmax = tmax_numerator * f64::INFINITY;
+        }
+
+        if tmin > tmax {
+            (tmax, tmin)
+        } else {
+            (tmin, tmax)
+        }
     }
 }
 
@@ -185,6 +248,133 @@ impl Transformable for BoundingBox {
     }
 }
 
+impl BoundingBox {
+    fn volume(&self) -> f64 {
+        [self.x_range, self.y_range, self.z_range]
+            .into_iter()
+            .map(|[min, max]| max - min)
+            .product()
+    }
+}
+
+// Sphere enclosing a shape, which unlike a box does not grow when the shape is
+// rotated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    centre: Point,
+    radius: f64,
+}
+
+impl BoundingSphere {
+    pub fn new(centre: Point, radius: f64) -> BoundingSphere {
+        BoundingSphere { centre, radius }
+    }
+
+    // the sphere through the corners of the box
+    pub fn around_box(bounding_box: BoundingBox) -> BoundingSphere {
+        let (x_range, y_range, z_range) = bounding_box.axial_bounds();
+        let [min, max] = [0, 1].map(|end| Point::new(x_range[end], y_range[end], z_range[end]));
+        let half_diagonal = (max - min) * 0.5;
+        BoundingSphere::new(min + half_diagonal, half_diagonal.magnitude())
+    }
+
+    pub fn centre(&self) -> Point {
+        self.centre
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        let radius = Vector::new(self.radius, self.radius, self.radius);
+        BoundingBox::from_anchors(vec![self.centre - radius, self.centre + radius])
+    }
+
+    fn volume(&self) -> f64 {
+        4.0 / 3.0 * std::f64::consts::PI * self.radius.powi(3)
+    }
+
+    pub fn intersect_bounds<'world: 'ray, 'ray>(
+        &'world self,
+        ray: &'ray Ray,
+        transform_stack: &Vec<&'ray Transform>,
+    ) -> bool {
+        let ray = super::shape::transform_through_stack_forwards(*ray, transform_stack);
+        self.clip_ray(&ray).is_some()
+    }
+
+    // range of t over which the ray lies inside the sphere, if it meets it
+    pub fn clip_ray(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let to_origin = ray.origin - self.centre;
+        let a = ray.direction.dot(ray.direction);
+        let b = 2.0 * ray.direction.dot(to_origin);
+        let c = to_origin.dot(to_origin) - self.radius.powi(2);
+        let discriminant = b.powi(2) - 4.0 * a * c;
+        (discriminant >= 0.0).then(|| {
+            let root = discriminant.sqrt();
+            ((-b - root) / (2.0 * a), (-b + root) / (2.0 * a))
+        })
+    }
+}
+
+impl Add for BoundingSphere {
+    type Output = Self;
+
+    // smallest sphere enclosing both
+    fn add(self, rhs: Self) -> Self::Output {
+        let offset = rhs.centre - self.centre;
+        let distance = offset.magnitude();
+        if distance + rhs.radius <= self.radius {
+            return self;
+        }
+        if distance + self.radius <= rhs.radius {
+            return rhs;
+        }
+        let radius = (distance + self.radius + rhs.radius) / 2.0;
+        let centre = self.centre + offset * ((radius - self.radius) / distance);
+        BoundingSphere::new(centre, radius)
+    }
+}
+
+impl Transformable for BoundingSphere {
+    // the radius grows by the most the transform stretches any direction: the
+    // square root of the largest eigenvalue of M^T M for its linear part M
+    fn transform(self, transform: &Transform) -> BoundingSphere {
+        let columns = [
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+        ]
+        .map(|axis| axis.transform(transform));
+        let gram = |i: usize, j: usize| columns[i].dot(columns[j]);
+        let off_diagonal = gram(0, 1).powi(2) + gram(0, 2).powi(2) + gram(1, 2).powi(2);
+        let diagonal = [gram(0, 0), gram(1, 1), gram(2, 2)];
+        let largest_eigenvalue = if off_diagonal == 0.0 {
+            diagonal.into_iter().fold(0.0, f64::max)
+        } else {
+            // closed form for symmetric 3x3 matrices
+            let mean = diagonal.iter().sum::<f64>() / 3.0;
+            let spread = ((diagonal.iter().map(|d| (d - mean).powi(2)).sum::<f64>()
+                + 2.0 * off_diagonal)
+                / 6.0)
+                .sqrt();
+            let b = |i: usize, j: usize| match i == j {
+                true => (gram(i, j) - mean) / spread,
+                false => gram(i, j) / spread,
+            };
+            let determinant = b(0, 0) * (b(1, 1) * b(2, 2) - b(1, 2) * b(2, 1))
+                - b(0, 1) * (b(1, 0) * b(2, 2) - b(1, 2) * b(2, 0))
+                + b(0, 2) * (b(1, 0) * b(2, 1) - b(1, 1) * b(2, 0));
+            let angle = (determinant / 2.0).clamp(-1.0, 1.0).acos() / 3.0;
+            mean + 2.0 * spread * angle.cos()
+        };
+        // a little extra to cover rounding in the eigenvalue
+        let stretch = largest_eigenvalue.sqrt() * (1.0 + 1e-9);
+        BoundingSphere::new(self.centre.transform(transform), self.radius * stretch)
+    }
+}
+
 // Helper enum type for wrapping BoundingBox for ergonomic use. Access to the
 // underlying bounding box is still available via a method, but this type is
 // generally immutable once constructed. It delegates functions for ray-bbox
@@ -193,6 +383,8 @@ impl Transformable for BoundingBox {
 pub enum Bounds {
     Checked(BoundingBox),
     Unchecked(BoundingBox),
+    // always checked
+    Sphere(BoundingSphere),
 }
 
 impl Bounds {
@@ -200,6 +392,15 @@ impl Bounds {
         Bounds::Unchecked(bounding_box)
     }
 
+    // whichever of the box and the sphere encloses less space, checked
+    pub fn tighter(bounding_box: BoundingBox, bounding_sphere: BoundingSphere) -> Bounds {
+        if bounding_sphere.volume() < bounding_box.volume() {
+            Bounds::Sphere(bounding_sphere)
+        } else {
+            Bounds::Checked(bounding_box)
+        }
+    }
+
     pub fn raise(self) -> Bounds {
         if let Bounds::Unchecked(bounding_box) = self {
             Bounds::Checked(bounding_box)
@@ -221,10 +422,17 @@ impl Bounds {
     // to the bbox directly instead of going through Bounds is not idiomatic
     pub fn bounding_box(&self) -> BoundingBox {
         match self {
-            Bounds::Checked(bbox) => bbox,
-            Bounds::Unchecked(bbox) => bbox,
+            Bounds::Checked(bbox) => *bbox,
+            Bounds::Unchecked(bbox) => *bbox,
+            Bounds::Sphere(sphere) => sphere.bounding_box(),
+        }
+    }
+
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        match self {
+            Bounds::Sphere(sphere) => *sphere,
+            _ => BoundingSphere::around_box(self.bounding_box()),
         }
-        .to_owned()
     }
 
     pub fn intersect_bounds<'world: 'ray, 'ray>(
@@ -233,12 +441,28 @@ impl Bounds {
         transform_stack: &Vec<&'ray Transform>,
     ) -> bool {
         match self {
-            Bounds::Checked(bbox) => bbox.intersect_bounds(ray, transform_stack),
+            Bounds::Checked(bbox) => {
+                stats::count_intersection_test();
+                bbox.intersect_bounds(ray, transform_stack)
+            }
             Bounds::Unchecked(_) => true,
+            Bounds::Sphere(sphere) => {
+                stats::count_intersection_test();
+                sphere.intersect_bounds(ray, transform_stack)
+            }
         }
     }
 }
 
+// the smaller of two spheres enclosing the same shapes
+pub(crate) fn tighter_sphere(a: BoundingSphere, b: BoundingSphere) -> BoundingSphere {
+    if b.radius < a.radius {
+        b
+    } else {
+        a
+    }
+}
+
 pub trait Bounded {
     fn bounds(&self) -> &Bounds;
 }
@@ -297,14 +521,39 @@ mod tests {
             z_range,
             ..
         } = bounding_box;
-        approx_eq!(x_range[0], -1.414214);
-        approx_eq!(x_range[1], 1.414214);
+        approx_eq!(x_range[0], -std::f64::consts::SQRT_2);
+        approx_eq!(x_range[1], std::f64::consts::SQRT_2);
         approx_eq!(y_range[0], -1.707107);
         approx_eq!(y_range[1], 1.707107);
         approx_eq!(z_range[0], -1.707107);
         approx_eq!(z_range[1], 1.707107);
     }
 
+    #[test]
+    fn query_bounding_box() {
+        let bounding_box = BoundingBox::from_axial_bounds([-1.0, 1.0], [0.0, 2.0], [0.0, 4.0]);
+        assert!(bounding_box.contains_point(Point::new(0.0, 1.0, 2.0)));
+        assert!(bounding_box.contains_point(Point::new(1.0, 2.0, 4.0)));
+        assert!(!bounding_box.contains_point(Point::new(0.0, 3.0, 2.0)));
+
+        let inner = BoundingBox::from_axial_bounds([0.0, 1.0], [0.5, 1.5], [1.0, 2.0]);
+        let overlapping = BoundingBox::from_axial_bounds([0.5, 3.0], [1.0, 3.0], [1.0, 2.0]);
+        let touching = BoundingBox::from_axial_bounds([1.0, 2.0], [0.0, 2.0], [0.0, 4.0]);
+        let apart = BoundingBox::from_axial_bounds([2.0, 3.0], [0.0, 2.0], [0.0, 4.0]);
+        assert!(bounding_box.contains_box(&inner));
+        assert!(!bounding_box.contains_box(&overlapping));
+        assert!(BoundingBox::new_unbounded().contains_box(&bounding_box));
+        assert!(bounding_box.intersects_box(&overlapping));
+        assert!(bounding_box.intersects_box(&touching));
+        assert!(!bounding_box.intersects_box(&apart));
+
+        assert_eq!(bounding_box.centroid(), Some(Point::new(0.0, 1.0, 2.0)));
+        assert_eq!(bounding_box.surface_area(), 2.0 * (4.0 + 8.0 + 8.0));
+        let slab = bounding_box.bound_in_x_axis([f64::NEG_INFINITY, f64::INFINITY]);
+        assert_eq!(slab.centroid(), None);
+        assert_eq!(slab.surface_area(), f64::INFINITY);
+    }
+
     use crate::collections::Vector;
 
     #[test]
@@ -341,7 +590,7 @@ mod tests {
         ];
         let rays: Vec<Ray> = origins
             .into_iter()
-            .zip(directions.into_iter())
+            .zip(directions)
             .map(|(origin, direction)| Ray::new(origin, direction))
             .collect();
         let results = vec![
@@ -352,9 +601,58 @@ mod tests {
             Point::new(1.0, 1.0, 1.0),
         ]);
 
-        for (ray, result) in rays.into_iter().zip(results.into_iter()) {
+        for (ray, result) in rays.into_iter().zip(results) {
             println!("{:?}, {:?}", ray, result);
             assert_eq!(bounding_box.intersect_bounds(&ray, &vec![]), result);
         }
     }
+
+    #[test]
+    fn transform_bounding_sphere() {
+        let sphere = BoundingSphere::new(Point::new(1.0, 0.0, 0.0), 2.0);
+        let rotated = sphere.transform(&Transform::new(TransformKind::Rotate(
+            Axis::Z,
+            Angle::from_radians(FRAC_PI_4),
+        )));
+        approx_eq!(rotated.radius(), 2.0);
+        approx_eq!(rotated.centre().x, FRAC_PI_4.cos());
+
+        let stretched = sphere.transform(&Transform::from(vec![
+            TransformKind::Scale(1.0, 3.0, 0.5),
+            TransformKind::Rotate(Axis::X, Angle::from_radians(0.3)),
+        ]));
+        approx_eq!(stretched.radius(), 6.0);
+        assert!(stretched.radius() >= 6.0);
+    }
+
+    #[test]
+    fn add_bounding_spheres() {
+        let a = BoundingSphere::new(Point::new(-2.0, 0.0, 0.0), 1.0);
+        let b = BoundingSphere::new(Point::new(2.0, 0.0, 0.0), 1.0);
+        assert_eq!(a + b, BoundingSphere::new(Point::zero(), 3.0));
+        let inner = BoundingSphere::new(Point::new(0.5, 0.0, 0.0), 0.5);
+        assert_eq!(a + b + inner, a + b);
+    }
+
+    #[test]
+    fn rotated_box_is_bounded_by_sphere() {
+        let cube = BoundingBox::from_anchors(vec![
+            Point::new(-1.0, -1.0, -1.0),
+            Point::new(1.0, 1.0, 1.0),
+        ]);
+        let sphere = BoundingSphere::around_box(cube);
+        approx_eq!(sphere.radius(), 3.0_f64.sqrt());
+        assert_eq!(Bounds::tighter(cube, sphere), Bounds::Checked(cube));
+
+        let transform = Transform::from(vec![
+            TransformKind::Rotate(Axis::Y, Angle::from_radians(FRAC_PI_4)),
+            TransformKind::Rotate(Axis::X, Angle::from_radians(FRAC_PI_4)),
+        ]);
+        let rotated = Bounds::tighter(cube.transform(&transform), sphere.transform(&transform));
+        assert!(matches!(rotated, Bounds::Sphere(_)));
+
+        let ray = Ray::new(Point::new(-5.0, 1.5, 1.5), Vector::new(1.0, 0.0, 0.0));
+        assert!(cube.transform(&transform).intersect_bounds(&ray, &vec![]));
+        assert!(!rotated.intersect_bounds(&ray, &vec![]));
+    }
 }
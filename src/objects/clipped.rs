@@ -0,0 +1,330 @@
+use crate::collections::Point;
+use crate::objects::*;
+use crate::utils::{BuildInto, Buildable, EPSILON};
+
+// Keeps only the part of a shape within a region, for section views of shapes
+// (such as meshes) that cannot be cut with CSG. Without a cap the cut is left
+// open, showing the inside of the shape; with one, the cut face is filled in
+// wherever it lies inside the shape, which needs the shape to be closed.
+#[derive(Debug)]
+pub struct ClippedShape {
+    shape: Box<Shape>,
+    region: ClipRegion,
+    transform: Transform,
+    // the region's surface, which is hit only where it caps the shape
+    boundary: Box<Shape>,
+    capped: bool,
+    bounds: Bounds,
+    name: Option<String>,
+}
+
+// Regions are placed by a transform, as primitives are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClipRegion {
+    // the side of the xz plane below y = 0
+    BelowPlane,
+    // the inside of the cube spanning -1 to 1 on each axis
+    InsideCube,
+}
+
+impl ClippedShape {
+    pub fn new(shape: Shape, region: ClipRegion, transform: Transform) -> ClippedShape {
+        let boundary = ClippedShape::boundary(region, transform.clone(), Material::preset());
+        let bounds = ClippedShape::bounds_of(&shape, region, &boundary);
+
+        ClippedShape {
+            shape: Box::new(shape),
+            region,
+            transform,
+            boundary,
+            capped: false,
+            bounds,
+            name: None,
+        }
+    }
+
+    fn bounds_of(shape: &Shape, region: ClipRegion, boundary: &Shape) -> Bounds {
+        let shape_box = shape.bounds().bounding_box();
+        let bounding_box = match region {
+            ClipRegion::BelowPlane => shape_box,
+            ClipRegion::InsideCube => {
+                let (shape_x, shape_y, shape_z) = shape_box.axial_bounds();
+                let (cube_x, cube_y, cube_z) = boundary.bounds().bounding_box().axial_bounds();
+                let overlap = |[min1, max1]: [f64; 2], [min2, max2]: [f64; 2]| {
+                    let [min, max] = [f64::max(min1, min2), f64::min(max1, max2)];
+                    // boxes that miss each other leave an empty slab
+                    [min, f64::max(min, max)]
+                };
+                BoundingBox::from_axial_bounds(
+                    overlap(shape_x, cube_x),
+                    overlap(shape_y, cube_y),
+                    overlap(shape_z, cube_z),
+                )
+            }
+        };
+        Bounds::Checked(bounding_box)
+    }
+
+    // works the bounds out again after the shape is changed in place
+    pub(crate) fn refresh_bounds(&mut self) {
+        self.bounds = ClippedShape::bounds_of(&self.shape, self.region, &self.boundary);
+    }
+
+    // moves the shape together with the region (see Shape::apply_transform)
+    pub fn apply_transform(&mut self, transform: &Transform) {
+        self.shape.apply_transform(transform);
+        self.boundary.apply_transform(transform);
+        self.transform = self.transform.compose(transform);
+        self.refresh_bounds();
+    }
+
+    // fills in the cut face with the given material
+    pub fn with_cap(mut self, material: Material) -> ClippedShape {
+        self.boundary = ClippedShape::boundary(self.region, self.transform.clone(), material);
+        self.capped = true;
+        self
+    }
+
+    pub fn with_name(mut self, name: &str) -> ClippedShape {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn shape(&self) -> &Shape {
+        self.shape.as_ref()
+    }
+
+    pub fn cap(&self) -> Option<&Shape> {
+        self.capped.then_some(self.boundary.as_ref())
+    }
+
+    // the shape, and the cap if there is one
+    pub(crate) fn shapes_mut(&mut self) -> impl Iterator<Item = &mut Shape> {
+        let cap = self.capped.then_some(self.boundary.as_mut());
+        std::iter::once(self.shape.as_mut()).chain(cap)
+    }
+
+    pub fn find_object_mut(&mut self, name: &str) -> Option<&mut Shape> {
+        self.shape.find_mut(name)
+    }
+
+    // whether a point lies inside the part of the shape that is kept (see
+    // Shape::contains_point); with the cut left open, the inside is still
+    // taken to be the shape's
+    pub fn contains_point(&self, point: Point) -> bool {
+        self.contains_point_within(point, &[])
+    }
+
+    pub(crate) fn contains_point_within(
+        &self,
+        point: Point,
+        group_transforms: &[&Transform],
+    ) -> bool {
+        self.keeps(point, group_transforms)
+            && self.shape.contains_point_within(point, group_transforms)
+    }
+
+    // the region's boundary contains just the points the region keeps, so
+    // the two combine as a CSG intersection
+    pub(crate) fn signed_distance_within(
+        &self,
+        point: Point,
+        group_transforms: &[&Transform],
+    ) -> Option<f64> {
+        let shape = self.shape.signed_distance_within(point, group_transforms)?;
+        let region = self
+            .boundary
+            .signed_distance_within(point, group_transforms)?;
+        Some(shape.max(region))
+    }
+
+    fn boundary(region: ClipRegion, transform: Transform, material: Material) -> Box<Shape> {
+        Box::new(match region {
+            ClipRegion::BelowPlane => Plane::builder()
+                .set_frame_transformation(transform)
+                .set_material(material)
+                .build_into(),
+            ClipRegion::InsideCube => Cube::builder()
+                .set_frame_transformation(transform)
+                .set_material(material)
+                .build_into(),
+        })
+    }
+
+    // whether a point (in the space the clipped shape sits in) lies within the
+    // region, allowing for points on its surface
+    fn keeps(&self, point: Point, transform_stack: &[&Transform]) -> bool {
+        self.region_keeps(self.region_space(point, transform_stack))
+    }
+
+    // takes a point or ray into the space the region is placed in
+    fn region_space<T: Transformable>(&self, object: T, transform_stack: &[&Transform]) -> T {
+        transform_through_stack_forwards(object, transform_stack)
+            .transform(self.transform.inverse())
+    }
+
+    // as keeps, for a point already in the region's space
+    fn region_keeps(&self, local_point: Point) -> bool {
+        match self.region {
+            ClipRegion::BelowPlane => local_point.y <= EPSILON,
+            ClipRegion::InsideCube => [local_point.x, local_point.y, local_point.z]
+                .into_iter()
+                .all(|coordinate| coordinate.abs() <= 1.0 + EPSILON),
+        }
+    }
+
+    // whether a hit is on the boundary itself, rather than on the shape
+    fn is_boundary(&self, object: &dyn PrimitiveShape) -> bool {
+        match self.boundary.as_ref() {
+            Shape::Primitive(boundary) => std::ptr::addr_eq(boundary.as_ref(), object),
+            _ => false,
+        }
+    }
+}
+
+impl Intersectable<dyn PrimitiveShape> for ClippedShape {
+    // the shape's intersections are kept where they lie in the region, and the
+    // boundary's where they lie inside the shape, by counting the shape's
+    // surfaces crossed along the ray so far
+    fn intersect_ray_into<'world: 'ray, 'ray>(
+        &'world self,
+        world_ray: &'ray Ray,
+        transform_stack: &mut Vec<&'ray Transform>,
+        hit_register: &mut HitRegister<'ray, dyn PrimitiveShape>,
+    ) {
+        let mut operand_hit_register = hit_register.take_spare();
+        self.shape()
+            .intersect_ray_into(world_ray, transform_stack, &mut operand_hit_register);
+        if self.capped {
+            self.boundary
+                .intersect_ray_into(world_ray, transform_stack, &mut operand_hit_register);
+        }
+
+        // t is the same along the ray in every space, so hits are placed in
+        // the region by taking the ray there once
+        let region_ray = self.region_space(*world_ray, transform_stack);
+        let mut in_shape = false;
+        for hit in operand_hit_register.drain_sorted() {
+            if self.is_boundary(hit.object()) {
+                if in_shape {
+                    hit_register.add_raw_intersect(hit);
+                }
+            } else {
+                in_shape = !in_shape;
+                if self.region_keeps(region_ray.position(hit.t())) {
+                    hit_register.add_raw_intersect(hit);
+                }
+            }
+        }
+        hit_register.return_spare(operand_hit_register);
+    }
+}
+
+impl Bounded for ClippedShape {
+    fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+}
+
+impl From<ClippedShape> for Shape {
+    fn from(clipped: ClippedShape) -> Shape {
+        Shape::Clipped(clipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::Vector;
+
+    fn clipped_sphere() -> ClippedShape {
+        ClippedShape::new(
+            Sphere::builder().build_into(),
+            ClipRegion::BelowPlane,
+            Transform::default(),
+        )
+    }
+
+    fn hit_ts(shape: &Shape, ray: &Ray) -> Vec<f64> {
+        shape
+            .intersect_ray(ray, vec![])
+            .expose()
+            .iter()
+            .map(|hit| hit.t())
+            .collect()
+    }
+
+    #[test]
+    fn open_cut_shows_inside() {
+        let clipped: Shape = clipped_sphere().into();
+        let down = Ray::new(Point::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(hit_ts(&clipped, &down), vec![6.0]);
+
+        // rays missing the kept half miss the shape entirely
+        let above = Ray::new(Point::new(-5.0, 0.5, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(hit_ts(&clipped, &above).is_empty());
+    }
+
+    #[test]
+    fn cap_fills_cut_face() {
+        let clipped: Shape = clipped_sphere().with_cap(Material::preset()).into();
+        let down = Ray::new(Point::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(hit_ts(&clipped, &down), vec![5.0, 6.0]);
+
+        // the cap covers only the cut through the sphere
+        let beside = Ray::new(Point::new(3.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert!(hit_ts(&clipped, &beside).is_empty());
+    }
+
+    #[test]
+    fn clip_to_cube() {
+        let clipped: Shape = ClippedShape::new(
+            Plane::builder().build_into(),
+            ClipRegion::InsideCube,
+            Transform::new(TransformKind::Scale(2.0, 2.0, 2.0)),
+        )
+        .into();
+        let inside = Ray::new(Point::new(1.0, 5.0, 1.0), Vector::new(0.0, -1.0, 0.0));
+        let outside = Ray::new(Point::new(3.0, 5.0, 1.0), Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(hit_ts(&clipped, &inside), vec![5.0]);
+        assert!(hit_ts(&clipped, &outside).is_empty());
+        let (x_range, _, _) = clipped.bounds().bounding_box().axial_bounds();
+        assert_eq!(x_range, [-2.0, 2.0]);
+    }
+
+    #[test]
+    fn shape_matching_boundary_is_not_taken_for_it() {
+        let clipped: Shape = ClippedShape::new(
+            Plane::builder()
+                .set_material(Material::preset())
+                .build_into(),
+            ClipRegion::BelowPlane,
+            Transform::default(),
+        )
+        .into();
+        let down = Ray::new(Point::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(hit_ts(&clipped, &down), vec![5.0]);
+    }
+
+    #[test]
+    fn clip_within_transformed_group() {
+        let group: Shape = Group::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(0.0, 2.0, 0.0)))
+            .set_objects(vec![clipped_sphere().into()])
+            .build_into();
+        let down = Ray::new(Point::new(0.0, 7.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(hit_ts(&group, &down), vec![6.0]);
+    }
+
+    #[test]
+    fn contains_points_in_kept_part() {
+        let clipped = clipped_sphere();
+        assert!(clipped.contains_point(Point::new(0.0, -0.5, 0.0)));
+        assert!(!clipped.contains_point(Point::new(0.0, 0.5, 0.0)));
+        assert!(!clipped.contains_point(Point::new(0.0, -1.5, 0.0)));
+    }
+}
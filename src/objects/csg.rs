@@ -1,3 +1,4 @@
+use crate::collections::Point;
 use crate::objects::*;
 
 #[derive(Debug)]
@@ -6,6 +7,7 @@ pub struct Csg {
     lshape: Box<Shape>,
     rshape: Box<Shape>,
     bounds: Bounds,
+    name: Option<String>,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -17,17 +19,49 @@ pub enum CsgOperation {
 
 impl Csg {
     pub fn new(csg_operation: CsgOperation, lshape: Shape, rshape: Shape) -> Csg {
-        let bounds =
-            Bounds::Checked(lshape.bounds().bounding_box() + rshape.bounds().bounding_box());
+        let bounds = Csg::bounds_of(&lshape, &rshape);
 
         Csg {
             csg_operation,
             lshape: Box::new(lshape),
             rshape: Box::new(rshape),
             bounds,
+            name: None,
         }
     }
 
+    fn bounds_of(lshape: &Shape, rshape: &Shape) -> Bounds {
+        let bounding_box = lshape.bounds().bounding_box() + rshape.bounds().bounding_box();
+        Bounds::tighter(
+            bounding_box,
+            tighter_sphere(
+                lshape.bounds().bounding_sphere() + rshape.bounds().bounding_sphere(),
+                BoundingSphere::around_box(bounding_box),
+            ),
+        )
+    }
+
+    // works the bounds out again after the operands are changed in place
+    pub(crate) fn refresh_bounds(&mut self) {
+        self.bounds = Csg::bounds_of(&self.lshape, &self.rshape);
+    }
+
+    // moves both operands (see Shape::apply_transform)
+    pub fn apply_transform(&mut self, transform: &Transform) {
+        self.lshape.apply_transform(transform);
+        self.rshape.apply_transform(transform);
+        self.refresh_bounds();
+    }
+
+    pub fn with_name(mut self, name: &str) -> Csg {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
     pub fn csg_operation(&self) -> CsgOperation {
         self.csg_operation
     }
@@ -40,24 +74,72 @@ impl Csg {
         self.rshape.as_ref()
     }
 
-    fn evaluate_intersections<'a>(
+    pub(crate) fn shapes_mut(&mut self) -> [&mut Shape; 2] {
+        [self.lshape.as_mut(), self.rshape.as_mut()]
+    }
+
+    pub fn find_object_mut(&mut self, name: &str) -> Option<&mut Shape> {
+        match self.lshape.find_mut(name) {
+            Some(shape) => Some(shape),
+            None => self.rshape.find_mut(name),
+        }
+    }
+
+    // whether a point lies inside the shape the operation leaves (see
+    // Shape::contains_point)
+    pub fn contains_point(&self, point: Point) -> bool {
+        self.contains_point_within(point, &[])
+    }
+
+    pub(crate) fn contains_point_within(
         &self,
-        hit_register: HitRegister<'a, dyn PrimitiveShape>,
-    ) -> HitRegister<'a, dyn PrimitiveShape> {
-        let hits = hit_register.expose();
+        point: Point,
+        group_transforms: &[&Transform],
+    ) -> bool {
+        let in_left = self.lshape().contains_point_within(point, group_transforms);
+        let in_right = self.rshape().contains_point_within(point, group_transforms);
+        match self.csg_operation {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersect => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
 
+    pub(crate) fn signed_distance_within(
+        &self,
+        point: Point,
+        group_transforms: &[&Transform],
+    ) -> Option<f64> {
+        let left = self
+            .lshape()
+            .signed_distance_within(point, group_transforms)?;
+        let right = self
+            .rshape()
+            .signed_distance_within(point, group_transforms)?;
+        Some(match self.csg_operation {
+            CsgOperation::Union => left.min(right),
+            CsgOperation::Intersect => left.max(right),
+            CsgOperation::Difference => left.max(-right),
+        })
+    }
+
+    // moves the operands' intersections that survive the operation into the
+    // register
+    fn evaluate_intersections<'a>(
+        &self,
+        operand_hit_register: &mut HitRegister<'a, dyn PrimitiveShape>,
+        hit_register: &mut HitRegister<'a, dyn PrimitiveShape>,
+    ) {
         let mut in_left = false;
         let mut in_right = false;
 
-        let mut hit_register = HitRegister::empty();
-
         let intersection_evaluator = match self.csg_operation {
             CsgOperation::Union => Csg::union_evaluate_intersection,
             CsgOperation::Intersect => Csg::intersect_evaluate_intersection,
             CsgOperation::Difference => Csg::difference_evaluate_intersection,
         };
 
-        for hit in hits {
+        for hit in operand_hit_register.drain_sorted() {
             let lhit = self.lshape().contains(hit.object());
 
             if intersection_evaluator(lhit, in_left, in_right) {
@@ -70,8 +152,6 @@ impl Csg {
                 in_right = !in_right;
             }
         }
-
-        hit_register
     }
 
     fn union_evaluate_intersection(left_hit: bool, in_left: bool, in_right: bool) -> bool {
@@ -88,20 +168,22 @@ impl Csg {
 }
 
 impl Intersectable<dyn PrimitiveShape> for Csg {
-    fn intersect_ray<'world: 'ray, 'ray>(
+    // the two operands' intersections are gathered separately, since only the
+    // ones that survive the operation belong in the shared register
+    fn intersect_ray_into<'world: 'ray, 'ray>(
         &'world self,
         world_ray: &'ray Ray,
-        transform_stack: Vec<&'ray Transform>,
-    ) -> HitRegister<'ray, dyn PrimitiveShape> {
-        let mut lshape_hit_register = self
-            .lshape()
-            .intersect_ray(world_ray, transform_stack.clone());
-        let rshape_hit_register = self
-            .rshape()
-            .intersect_ray(world_ray, transform_stack.clone());
-        lshape_hit_register.combine_registers(rshape_hit_register);
+        transform_stack: &mut Vec<&'ray Transform>,
+        hit_register: &mut HitRegister<'ray, dyn PrimitiveShape>,
+    ) {
+        let mut operand_hit_register = hit_register.take_spare();
+        self.lshape()
+            .intersect_ray_into(world_ray, transform_stack, &mut operand_hit_register);
+        self.rshape()
+            .intersect_ray_into(world_ray, transform_stack, &mut operand_hit_register);
 
-        self.evaluate_intersections(lshape_hit_register)
+        self.evaluate_intersections(&mut operand_hit_register, hit_register);
+        hit_register.return_spare(operand_hit_register);
     }
 }
 
@@ -205,14 +287,16 @@ mod tests {
             };
             let rshape = rshape.as_ref();
 
-            let hit_register = HitRegister::from(vec![
-                Intersect::new(0.0, lshape, &placeholder_ray, None, vec![]),
-                Intersect::new(1.0, rshape, &placeholder_ray, None, vec![]),
-                Intersect::new(2.0, lshape, &placeholder_ray, None, vec![]),
-                Intersect::new(3.0, rshape, &placeholder_ray, None, vec![]),
+            let mut operand_hit_register = HitRegister::from(vec![
+                Intersect::new(0.0, lshape, &placeholder_ray, None, &[]),
+                Intersect::new(1.0, rshape, &placeholder_ray, None, &[]),
+                Intersect::new(2.0, lshape, &placeholder_ray, None, &[]),
+                Intersect::new(3.0, rshape, &placeholder_ray, None, &[]),
             ]);
 
-            let filtered_intersections = csg.evaluate_intersections(hit_register).expose();
+            let mut hit_register = HitRegister::empty();
+            csg.evaluate_intersections(&mut operand_hit_register, &mut hit_register);
+            let filtered_intersections = hit_register.expose();
             let t_list: Vec<f64> = filtered_intersections.iter().map(|itx| itx.t()).collect();
             assert_eq!(x0, t_list[0]);
             assert_eq!(x1, t_list[1]);
@@ -244,4 +328,52 @@ mod tests {
         assert_eq!(intersections[1].t(), 6.5);
         assert!(c.rshape().contains(intersections[1].object()));
     }
+
+    #[test]
+    fn csg_contains_points_left_by_operation() {
+        let shapes = || -> (Shape, Shape) {
+            (
+                Sphere::builder().build_into(),
+                Cube::builder()
+                    .set_frame_transformation(Transform::new(TransformKind::Translate(
+                        1.5, 0.0, 0.0,
+                    )))
+                    .build_into(),
+            )
+        };
+        let points = [
+            Point::new(-0.5, 0.0, 0.0),
+            Point::new(0.75, 0.0, 0.0),
+            Point::new(2.0, 0.0, 0.0),
+            Point::new(0.0, 3.0, 0.0),
+        ];
+        let test_cases = [
+            (CsgOperation::Union, [true, true, true, false]),
+            (CsgOperation::Intersect, [false, true, false, false]),
+            (CsgOperation::Difference, [true, false, false, false]),
+        ];
+        for (operation, expected) in test_cases {
+            let (sphere, cube) = shapes();
+            let csg = Csg::new(operation, sphere, cube);
+            assert_eq!(points.map(|point| csg.contains_point(point)), expected);
+        }
+    }
+
+    #[test]
+    fn csg_signed_distances() {
+        let left: Shape = Sphere::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Scale(2.0, 2.0, 2.0)))
+            .build_into();
+        let right: Shape = Cube::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(2.0, 0.0, 0.0)))
+            .build_into();
+        let csg = Shape::Csg(Csg::new(CsgOperation::Difference, left, right));
+        // inside the sphere, a unit from the cube's face at x = 1
+        assert_eq!(
+            csg.signed_distance_to(Point::new(0.0, 0.0, 0.0)),
+            Some(-1.0)
+        );
+        assert_eq!(csg.distance_to(Point::new(-3.0, 0.0, 0.0)), Some(1.0));
+        assert_eq!(csg.signed_distance_to(Point::new(2.0, 0.0, 0.0)), Some(1.0));
+    }
 }
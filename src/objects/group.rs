@@ -1,11 +1,18 @@
+use crate::collections::Point;
 use crate::objects::*;
-use crate::utils::{Buildable, ConsumingBuilder};
+use crate::utils::{BuildError, Buildable, ConsumingBuilder};
 
 #[derive(Debug)]
 pub struct Group {
     frame_transformation: Transform,
+    // the frame transformation taken together with those of the groups this
+    // group is built into, so that rays and points are taken into its space in
+    // one step however deeply it is nested (see Group::enter)
+    combined_transformation: Box<Transform>,
+    enclosing_transformation: Option<Box<Transform>>,
     objects: Vec<Shape>,
     bounds: Bounds,
+    name: Option<String>,
 }
 
 impl Group {
@@ -16,23 +23,166 @@ impl Group {
     pub fn objects(&self) -> &Vec<Shape> {
         &self.objects
     }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn find_object(&self, name: &str) -> Option<&Shape> {
+        self.objects.iter().find_map(|object| object.find(name))
+    }
+
+    // changes to the objects' bounds are not taken into the group's
+    pub(crate) fn objects_mut(&mut self) -> impl Iterator<Item = &mut Shape> {
+        self.objects.iter_mut()
+    }
+
+    pub fn find_object_mut(&mut self, name: &str) -> Option<&mut Shape> {
+        self.objects
+            .iter_mut()
+            .find_map(|object| object.find_mut(name))
+    }
+
+    // see Shape::apply_transform
+    pub fn apply_transform(&mut self, transform: &Transform) {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.refresh_bounds();
+        self.place();
+    }
+
+    // works the bounds out again after objects are changed in place
+    pub(crate) fn refresh_bounds(&mut self) {
+        self.bounds = Group::bounds_of(&self.objects, &self.frame_transformation);
+    }
+
+    // places the group inside another, whose combined transformation is given
+    pub(crate) fn place_within(&mut self, enclosing_transformation: &Transform) {
+        self.enclosing_transformation = Some(Box::new(enclosing_transformation.clone()));
+        self.place();
+    }
+
+    fn place(&mut self) {
+        *self.combined_transformation = match &self.enclosing_transformation {
+            Some(enclosing) => self.frame_transformation.compose(enclosing),
+            None => self.frame_transformation.clone(),
+        };
+        for object in &mut self.objects {
+            object.place_within(&self.combined_transformation);
+        }
+    }
+
+    // Takes a stack of transformations into the group's space. Inside the group
+    // it was built into, the group's combined transformation stands in for the
+    // enclosing group's on top of the stack rather than adding to it, so the
+    // stack never holds more than one group's; a group on its own adds its
+    // frame transformation. Returns what was replaced, for Group::leave.
+    pub(crate) fn enter<'a>(
+        &'a self,
+        transform_stack: &mut Vec<&'a Transform>,
+    ) -> Option<&'a Transform> {
+        match (&self.enclosing_transformation, transform_stack.is_empty()) {
+            (Some(_), false) => {
+                let enclosing = transform_stack.pop();
+                transform_stack.push(&self.combined_transformation);
+                enclosing
+            }
+            _ => {
+                transform_stack.push(&self.frame_transformation);
+                None
+            }
+        }
+    }
+
+    // undoes Group::enter
+    pub(crate) fn leave<'a>(
+        &self,
+        transform_stack: &mut Vec<&'a Transform>,
+        replaced: Option<&'a Transform>,
+    ) {
+        transform_stack.pop();
+        transform_stack.extend(replaced);
+    }
+
+    fn bounds_of(objects: &[Shape], frame_transformation: &Transform) -> Bounds {
+        match objects
+            .iter()
+            .map(|objects| objects.bounds().bounding_box())
+            .reduce(|bbox_a, bbox_b| bbox_a + bbox_b)
+        {
+            Some(bbox) => {
+                let sphere = objects
+                    .iter()
+                    .map(|object| object.bounds().bounding_sphere())
+                    .reduce(|sphere_a, sphere_b| sphere_a + sphere_b)
+                    .unwrap();
+                Bounds::tighter(
+                    bbox.transform(frame_transformation),
+                    tighter_sphere(sphere, BoundingSphere::around_box(bbox))
+                        .transform(frame_transformation),
+                )
+            }
+            None => Bounds::Unchecked(BoundingBox::new_unbounded()),
+        }
+    }
+
+    // see Shape::contains_point
+    pub fn contains_point(&self, point: Point) -> bool {
+        self.contains_point_within(point, &[])
+    }
+
+    pub(crate) fn contains_point_within(
+        &self,
+        point: Point,
+        group_transforms: &[&Transform],
+    ) -> bool {
+        self.encloses(point, group_transforms)
+            || self.surface_crossings(&Ray::new(point, CROSSING_DIRECTION), group_transforms) % 2
+                == 1
+    }
+
+    pub(crate) fn encloses(&self, point: Point, group_transforms: &[&Transform]) -> bool {
+        let mut transform_stack = group_transforms.to_vec();
+        self.enter(&mut transform_stack);
+        self.objects
+            .iter()
+            .any(|object| object.encloses(point, &transform_stack))
+    }
+
+    pub(crate) fn distance_within(
+        &self,
+        point: Point,
+        group_transforms: &[&Transform],
+    ) -> Option<f64> {
+        let mut transform_stack = group_transforms.to_vec();
+        self.enter(&mut transform_stack);
+        self.objects
+            .iter()
+            .filter_map(|object| object.distance_within(point, &transform_stack))
+            .reduce(f64::min)
+    }
+
+    pub(crate) fn surface_crossings(&self, ray: &Ray, group_transforms: &[&Transform]) -> usize {
+        let mut transform_stack = group_transforms.to_vec();
+        self.enter(&mut transform_stack);
+        self.objects
+            .iter()
+            .map(|object| object.surface_crossings(ray, &transform_stack))
+            .sum()
+    }
 }
 
 impl Intersectable<dyn PrimitiveShape> for Group {
-    fn intersect_ray<'world: 'ray, 'ray>(
+    fn intersect_ray_into<'world: 'ray, 'ray>(
         &'world self,
         world_ray: &'ray Ray,
-        mut transform_stack: Vec<&'ray Transform>,
-    ) -> HitRegister<'ray, dyn PrimitiveShape> {
-        let mut ray_hit_register = HitRegister::empty();
-        transform_stack.push(self.frame_transformation());
-
+        transform_stack: &mut Vec<&'ray Transform>,
+        hit_register: &mut HitRegister<'ray, dyn PrimitiveShape>,
+    ) {
+        let replaced = self.enter(transform_stack);
         for shape in &self.objects {
-            let shape_hit_register = shape.intersect_ray(world_ray, transform_stack.clone());
-            ray_hit_register.combine_registers(shape_hit_register);
+            shape.intersect_ray_into(world_ray, transform_stack, hit_register);
         }
-
-        ray_hit_register
+        self.leave(transform_stack, replaced);
     }
 }
 
@@ -47,6 +197,7 @@ pub struct GroupBuilder {
     frame_transformation: Option<Transform>,
     material: Option<Material>,
     objects: Option<Vec<Shape>>,
+    name: Option<String>,
 }
 
 impl GroupBuilder {
@@ -60,6 +211,11 @@ impl GroupBuilder {
         self
     }
 
+    pub fn set_name(mut self, name: &str) -> GroupBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+
     pub fn set_objects(mut self, objects: Vec<Shape>) -> GroupBuilder {
         self.objects = Some(objects);
         self
@@ -90,35 +246,39 @@ impl ConsumingBuilder for GroupBuilder {
     fn build(self) -> Self::Built {
         let frame_transformation = self.frame_transformation.unwrap_or_default();
         let objects = self.objects.unwrap_or_default();
-        let bounds = match objects
-            .iter()
-            .map(|objects| objects.bounds().bounding_box())
-            .reduce(|bbox_a, bbox_b| bbox_a + bbox_b)
-        {
-            Some(bbox) => Bounds::Checked(bbox.transform(&frame_transformation)),
-            None => Bounds::Unchecked(BoundingBox::new_unbounded()),
-        };
+        let bounds = Group::bounds_of(&objects, &frame_transformation);
 
-        let group = Group {
+        let mut group = Group {
+            combined_transformation: Box::new(frame_transformation.clone()),
             frame_transformation,
+            enclosing_transformation: None,
             objects,
             bounds,
+            name: self.name,
         };
+        group.place();
         group
     }
+
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        check_frame_transformation(&self.frame_transformation)?;
+        Ok(self.build())
+    }
 }
 
-impl Into<Shape> for Group {
-    fn into(self) -> Shape {
-        Shape::Group(self)
+impl From<Group> for Shape {
+    fn from(group: Group) -> Shape {
+        Shape::Group(group)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use super::*;
-    use crate::collections::{Angle, Point, Vector};
-    use crate::objects::{Axis, Ray, Sphere, TransformKind};
+    use crate::collections::{Angle, Colour, Point, Vector};
+    use crate::objects::{Axis, Material, Ray, Solid, Sphere, Stripe, TransformKind, Triangle};
     use crate::utils::BuildInto;
 
     #[test]
@@ -168,6 +328,124 @@ mod tests {
         assert_eq!(shape, &resulting_shape as &dyn PrimitiveShape);
     }
 
+    #[test]
+    fn move_built_shapes() {
+        let translation = Transform::new(TransformKind::Translate(5.0, 0.0, 0.0));
+        let shape: Shape = Sphere::builder().set_name("ball").build_into();
+        let shape = shape.with_transform(&translation);
+        let ray = Ray::new(Point::new(5.0, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(shape.intersect_ray(&ray, vec![]).finalise_hit().is_some());
+        let (x_range, _, _) = shape.bounds().bounding_box().axial_bounds();
+        assert_eq!(x_range, [4.0, 6.0]);
+        assert!(shape.find("ball").is_some());
+
+        // a group takes the transformation into its own rather than nesting
+        let mut group: Shape = Group::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Scale(2.0, 2.0, 2.0)))
+            .add_object(Sphere::builder().build_into())
+            .build_into();
+        group.apply_transform(&translation);
+        let Shape::Group(ref inner) = group else {
+            panic!("expected a group")
+        };
+        assert!(matches!(inner.objects()[..], [Shape::Primitive(_)]));
+        let (x_range, _, _) = group.bounds().bounding_box().axial_bounds();
+        assert_eq!(x_range, [3.0, 7.0]);
+        assert!(group.contains_point(Point::new(6.5, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn move_shapes_nested_in_csg_and_clipped_shapes() {
+        let translation = Transform::new(TransformKind::Translate(5.0, 0.0, 0.0));
+        let mut shape: Shape = Group::builder()
+            .add_object(Shape::Csg(Csg::new(
+                CsgOperation::Union,
+                Sphere::builder().set_name("left").build_into(),
+                ClippedShape::new(
+                    Cube::builder().build_into(),
+                    ClipRegion::BelowPlane,
+                    Transform::default(),
+                )
+                .with_name("right")
+                .into(),
+            )))
+            .build_into();
+        assert!(shape.apply_transform_to("left", &translation));
+        let (x_range, _, _) = shape.bounds().bounding_box().axial_bounds();
+        assert_eq!(x_range, [-1.0, 6.0]);
+
+        // a clipped shape moves its region along with the shape
+        assert!(shape.apply_transform_to("right", &translation));
+        let (x_range, _, _) = shape.bounds().bounding_box().axial_bounds();
+        assert_eq!(x_range, [4.0, 6.0]);
+        assert!(shape.contains_point(Point::new(5.9, -0.9, 0.9)));
+        assert!(!shape.contains_point(Point::new(5.9, 0.9, 0.9)));
+        assert!(!shape.apply_transform_to("middle", &translation));
+    }
+
+    #[test]
+    fn reskin_nested_shapes() {
+        let clipped = ClippedShape::new(
+            Sphere::builder().build_into(),
+            ClipRegion::BelowPlane,
+            Transform::default(),
+        )
+        .with_cap(Material::preset());
+        let csg = Csg::new(
+            CsgOperation::Difference,
+            Sphere::builder().build_into(),
+            Shape::Clipped(clipped),
+        );
+        let mut group: Shape = Group::builder()
+            .add_object(Sphere::builder().build_into())
+            .add_object(Shape::Csg(csg))
+            .build_into();
+
+        let clay = Material::builder().set_specular(0.0).build();
+        group.set_material(clay);
+        let mut primitives = 0;
+        group.for_each_primitive_mut(&mut |primitive| {
+            assert_eq!(primitive.material().specular, 0.0);
+            primitives += 1;
+        });
+        // two spheres, the clipped sphere and its cap
+        assert_eq!(primitives, 4);
+    }
+
+    #[test]
+    fn edit_shared_material() {
+        let material = Arc::new(Material::preset());
+        let mut sphere1 = Sphere::builder()
+            .set_shared_material(Arc::clone(&material))
+            .build();
+        let sphere2 = Sphere::builder().set_shared_material(material).build();
+        sphere1.material_mut().unwrap().ambient = 0.5;
+        assert_eq!(sphere1.material().ambient, 0.5);
+        assert_eq!(sphere2.material().ambient, Material::preset().ambient);
+    }
+
+    #[test]
+    fn intersect_group_into_shared_register() {
+        let inner: Shape = Group::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Scale(2.0, 2.0, 2.0)))
+            .set_objects(vec![Sphere::builder().build_into()])
+            .build_into();
+        let outer: Shape = Group::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(0.0, 0.0, 1.0)))
+            .set_objects(vec![inner])
+            .build_into();
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+
+        let mut hit_register = HitRegister::empty();
+        let mut transform_stack = vec![];
+        outer.intersect_ray_into(&ray, &mut transform_stack, &mut hit_register);
+        outer.intersect_ray_into(&ray, &mut transform_stack, &mut hit_register);
+        assert!(transform_stack.is_empty());
+
+        let ts: Vec<f64> = hit_register.expose().iter().map(|itx| itx.t()).collect();
+        assert_eq!(ts, vec![4.0, 4.0, 8.0, 8.0]);
+    }
+
     #[test]
     fn transform_stack_propagates_through_groups() {
         let s1 = Sphere::builder()
@@ -196,8 +474,171 @@ mod tests {
         ));
         let t2 = Transform::new(TransformKind::Scale(2.0, 2.0, 2.0));
         let t3 = Transform::new(TransformKind::Translate(5.0, 0.0, 0.0));
-        let resulting_transform_stack = vec![&t1, &t2, &t3];
+        // the inner group's transformation takes in the outer group's
+        let t2_in_t1 = t2.compose(&t1);
+        let resulting_transform_stack = vec![&t2_in_t1, &t3];
 
         assert_eq!(transform_stack, &resulting_transform_stack);
     }
+
+    #[test]
+    fn nested_groups_flatten_transform_stack() {
+        let step = Transform::new(TransformKind::Translate(1.0, 0.0, 0.0));
+        let mut shape: Shape = Sphere::builder().build_into();
+        for name in ["inner", "middle", "outer"] {
+            shape = Group::builder()
+                .set_name(name)
+                .set_frame_transformation(step.clone())
+                .add_object(shape)
+                .build_into();
+        }
+        let ray = Ray::new(Point::new(3.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = shape.intersect_ray(&ray, vec![]).finalise_hit().unwrap();
+        assert_eq!(hit.t(), 4.0);
+        // the three groups' transformations and the sphere's
+        assert_eq!(hit.transform_stack().len(), 2);
+
+        // moving a nested group carries the groups inside it along
+        shape
+            .find_mut("middle")
+            .unwrap()
+            .apply_transform(&Transform::new(TransformKind::Translate(0.0, 0.0, 0.5)));
+        let hit = shape.intersect_ray(&ray, vec![]).finalise_hit().unwrap();
+        assert_eq!(hit.t(), 4.5);
+        assert_eq!(hit.normal(), Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn query_nested_primitive_in_world_space() {
+        let colour = Colour::new(0.2, 0.4, 0.6);
+        let sphere = Sphere::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(5.0, 0.0, 0.0)))
+            .set_material(Material {
+                pattern: Box::new(Solid::new(colour)),
+                ..Material::preset()
+            })
+            .build_into();
+        let inner = Group::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Scale(2.0, 2.0, 2.0)))
+            .set_objects(vec![sphere])
+            .build_into();
+        let outer: Shape = Group::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Rotate(
+                Axis::Y,
+                Angle::from_radians(std::f64::consts::FRAC_PI_2),
+            )))
+            .set_objects(vec![inner])
+            .build_into();
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+
+        let hit = outer.intersect_ray(&ray, vec![]).finalise_hit().unwrap();
+        let sphere = hit.object();
+        let group_transforms = outer.group_transforms_to(sphere).unwrap();
+        // the two groups' transformations come as one
+        assert_eq!(group_transforms.len(), 1);
+        assert_eq!(
+            sphere.world_normal_at(hit.target(), &group_transforms),
+            sphere.normal_at(hit.target(), None, hit.transform_stack())
+        );
+        assert_eq!(
+            sphere.world_colour_at(hit.target(), &group_transforms),
+            colour
+        );
+
+        let elsewhere = Sphere::builder().build();
+        assert!(outer.group_transforms_to(&elsewhere).is_none());
+    }
+
+    #[test]
+    fn pattern_moves_with_group() {
+        let white = Colour::new(1.0, 1.0, 1.0);
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let sphere = Sphere::builder()
+            .set_material(Material {
+                pattern: Box::new(Stripe::new(white, black, Transform::default())),
+                ..Material::preset()
+            })
+            .build_into();
+        let group: Shape = Group::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(1.5, 0.0, 0.0)))
+            .set_objects(vec![sphere])
+            .build_into();
+        let ray = Ray::new(Point::new(1.5, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+
+        // the hit is at x = 1.5 in the world (a black stripe) but x = 0 on the
+        // sphere itself (a white stripe)
+        let hit = group.intersect_ray(&ray, vec![]).finalise_hit().unwrap();
+        assert_eq!(hit.object_point().x, 0.0);
+        let sphere = hit.object();
+        let group_transforms = group.group_transforms_to(sphere).unwrap();
+        assert_eq!(
+            sphere.world_colour_at(hit.target(), &group_transforms),
+            white
+        );
+    }
+
+    #[test]
+    fn closed_mesh_contains_points() {
+        let corners = [
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+            Point::new(0.0, 0.0, 1.0),
+        ];
+        let faces: Vec<Shape> = [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]]
+            .into_iter()
+            .map(|[a, b, c]| {
+                Triangle::builder()
+                    .set_vertices([corners[a], corners[b], corners[c]])
+                    .build_into()
+            })
+            .collect();
+        // faces split between subgroups, as divided meshes are
+        let mut faces = faces.into_iter();
+        let halves: Vec<Shape> = vec![
+            Group::builder()
+                .set_objects(faces.by_ref().take(2).collect())
+                .build_into(),
+            Group::builder().set_objects(faces.collect()).build_into(),
+        ];
+        let mesh = Group::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(5.0, 0.0, 0.0)))
+            .set_objects(halves)
+            .build();
+
+        assert!(mesh.contains_point(Point::new(5.2, 0.2, 0.2)));
+        assert!(!mesh.contains_point(Point::new(0.2, 0.2, 0.2)));
+        assert!(!mesh.contains_point(Point::new(5.6, 0.6, 0.6)));
+        // the triangles alone enclose nothing
+        if let Shape::Group(half) = &mesh.objects()[0] {
+            assert!(!half.encloses(Point::new(5.2, 0.2, 0.2), &[]));
+        }
+    }
+
+    #[test]
+    fn group_contains_points_inside_members() {
+        let group = Group::builder()
+            .add_object(Sphere::builder().build_into())
+            .set_frame_transformation(Transform::new(TransformKind::Scale(2.0, 2.0, 2.0)))
+            .build();
+        assert!(group.contains_point(Point::new(0.0, 1.5, 0.0)));
+        assert!(!group.contains_point(Point::new(0.0, 2.5, 0.0)));
+    }
+
+    #[test]
+    fn only_shapes_without_interior_count_as_crossings() {
+        let card: Shape = Triangle::builder()
+            .set_vertices([
+                Point::new(-1.0, -1.0, 3.0),
+                Point::new(1.0, -1.0, 3.0),
+                Point::new(0.0, 1.0, 3.0),
+            ])
+            .build_into();
+        let group = Group::builder()
+            .add_object(Sphere::builder().build_into())
+            .add_object(card)
+            .build();
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(group.surface_crossings(&ray, &[]), 1);
+    }
 }
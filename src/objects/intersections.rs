@@ -1,12 +1,17 @@
+use std::cell::Cell;
 use std::marker::PhantomData;
+use std::ops::{Deref, Range};
 
 use crate::collections::{Colour, Point, Vector};
-use crate::objects::{PrimitiveShape, Transform};
+use crate::objects::{
+    transform_through_stack_forwards, Backface, Material, PrimitiveShape, Transform, IDENTITY,
+};
 use crate::utils::floats::EPSILON;
 
-use super::Light;
-use super::Ray;
+use super::{HemisphericAmbient, Light};
+use super::{Ray, RayDifferentials};
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Coordinates {
     t: f64,
     uv_coordinates: Option<(f64, f64)>,
@@ -29,7 +34,7 @@ impl Coordinates {
         self,
         object: &'ray S,
         ray: &'ray Ray,
-        transform_stack: Vec<&'ray Transform>,
+        transform_stack: &[&'ray Transform],
     ) -> Intersect<'ray, S, Raw>
     where
         S: PrimitiveShape + ?Sized,
@@ -39,6 +44,51 @@ impl Coordinates {
     }
 }
 
+// The transformations an intersect was found through, outermost first. With
+// groups flattened (see Group::enter) there are seldom more than a group's and
+// the shape's own, which are kept in place rather than in a vector.
+#[derive(Clone, Debug)]
+struct TransformStack<'ray> {
+    inline: [&'ray Transform; 2],
+    len: usize,
+    spilled: Vec<&'ray Transform>,
+}
+
+impl<'ray> From<&[&'ray Transform]> for TransformStack<'ray> {
+    fn from(transforms: &[&'ray Transform]) -> TransformStack<'ray> {
+        match *transforms {
+            [] => TransformStack::inline([&IDENTITY; 2], 0),
+            [transform] => TransformStack::inline([transform, &IDENTITY], 1),
+            [outer, inner] => TransformStack::inline([outer, inner], 2),
+            _ => TransformStack {
+                spilled: transforms.to_vec(),
+                ..TransformStack::inline([&IDENTITY; 2], transforms.len())
+            },
+        }
+    }
+}
+
+impl<'ray> TransformStack<'ray> {
+    fn inline(inline: [&'ray Transform; 2], len: usize) -> TransformStack<'ray> {
+        TransformStack {
+            inline,
+            len,
+            spilled: vec![],
+        }
+    }
+}
+
+impl<'ray> Deref for TransformStack<'ray> {
+    type Target = [&'ray Transform];
+
+    fn deref(&self) -> &Self::Target {
+        match self.len {
+            0..=2 => &self.inline[..self.len],
+            _ => &self.spilled,
+        }
+    }
+}
+
 mod private {
     pub trait Sealed {}
     impl<S: Sealed> super::IntersectState for S {}
@@ -65,8 +115,9 @@ where
     object: &'ray S,
     ray: &'ray Ray,
     uv_coordinates: Option<(f64, f64)>,
-    transform_stack: Vec<&'ray Transform>,
+    transform_stack: TransformStack<'ray>,
     computations: Option<Box<Computations>>,
+    material: Option<&'ray Material>,
 }
 
 impl<'ray, S, ItxState> Intersect<'ray, S, ItxState>
@@ -90,7 +141,7 @@ where
         self.uv_coordinates
     }
 
-    pub fn transform_stack(&self) -> &Vec<&'ray Transform> {
+    pub fn transform_stack(&self) -> &[&'ray Transform] {
         &self.transform_stack
     }
 }
@@ -104,7 +155,7 @@ where
         object: &'ray S,
         ray: &'ray Ray,
         uv_coordinates: Option<(f64, f64)>,
-        transform_stack: Vec<&'ray Transform>,
+        transform_stack: &[&'ray Transform],
     ) -> Intersect<'ray, S, Raw> {
         Intersect {
             state: PhantomData,
@@ -112,12 +163,29 @@ where
             object,
             ray,
             uv_coordinates,
-            transform_stack,
+            transform_stack: TransformStack::from(transform_stack),
             computations: None,
+            material: None,
         }
     }
 
-    fn compute(self, refraction_boundary: (f64, f64)) -> Intersect<'ray, S, Computed> {
+    // whether the ray meets the back of the surface here
+    fn faces_away(&self) -> bool {
+        let target = self.ray.position(self.t);
+        let normal = self
+            .object
+            .normal_at(target, self.uv_coordinates, &self.transform_stack);
+        normal.dot(self.ray.direction) > 0.0
+    }
+
+    // surface_bias is how far the over and under points sit off the surface
+    fn compute_with_material(
+        self,
+        refraction_boundary: (f64, f64),
+        channel_refraction_boundaries: [(f64, f64); 3],
+        material: &'ray Material,
+        surface_bias: f64,
+    ) -> Intersect<'ray, S, Computed> {
         let Intersect {
             t,
             object,
@@ -127,7 +195,8 @@ where
             ..
         } = self;
         let target = self.ray.position(t);
-        let eyev = -self.ray.direction;
+        // shading needs unit vectors whether or not the ray was normalised
+        let eyev = -self.ray.direction.normalise();
         let mut normal = object.normal_at(target, uv_coordinates, &transform_stack);
         let inside = match normal.dot(eyev) {
             _x if _x < 0.0 => {
@@ -137,12 +206,19 @@ where
             _x if _x >= 0.0 => false,
             _ => panic!(),
         };
-        let over_point = target + normal * EPSILON;
-        let under_point = target - normal * EPSILON;
-        let reflected_ray = Ray::new(over_point, ray.direction.reflect(normal));
+        // offset points follow the true surface; only shading sees the bumps
+        let over_point = target + normal * surface_bias;
+        let under_point = target - normal * surface_bias;
+        let object_point = transform_through_stack_forwards(over_point, &transform_stack);
+        if let Some(bump) = &material.bump {
+            normal = bump.perturb(object_point, normal, &transform_stack);
+        }
+        let reflected_ray = Ray::normalised(over_point, ray.direction.reflect(normal));
+        let texture_coordinates = object.texture_coordinates_at(uv_coordinates);
 
         let computations = Some(Box::new(Computations {
             target,
+            object_point,
             eyev,
             normal,
             inside,
@@ -150,6 +226,10 @@ where
             under_point,
             reflected_ray,
             refraction_boundary,
+            channel_refraction_boundaries,
+            texture_coordinates,
+            footprint: None,
+            reflected_differentials: None,
         }));
         Intersect {
             state: PhantomData,
@@ -159,6 +239,7 @@ where
             uv_coordinates,
             transform_stack,
             computations,
+            material: Some(material),
         }
     }
 }
@@ -166,6 +247,8 @@ where
 #[derive(Clone, Copy, Debug)]
 pub struct Computations {
     target: Point,
+    // the over point in the shape's own space, where its pattern is evaluated
+    object_point: Point,
     eyev: Vector,
     normal: Vector,
     inside: bool,
@@ -173,6 +256,16 @@ pub struct Computations {
     under_point: Point,
     reflected_ray: Ray,
     refraction_boundary: (f64, f64),
+    // the refractive indices either side for red, green and blue light, which
+    // differ from the refraction boundary only for dispersive materials
+    channel_refraction_boundaries: [(f64, f64); 3],
+    // where the hit lies on a texture the object carries, such as a mesh's
+    texture_coordinates: Option<(f64, f64)>,
+    // the offsets to the points seen through the neighbouring pixels, in the
+    // shape's own space, and the differentials of the reflected ray; known
+    // only for rays traced with differentials
+    footprint: Option<[Vector; 2]>,
+    reflected_differentials: Option<RayDifferentials>,
 }
 
 impl Computations {
@@ -180,6 +273,10 @@ impl Computations {
         self.target
     }
 
+    pub fn object_point(&self) -> Point {
+        self.object_point
+    }
+
     pub fn eyev(&self) -> Vector {
         self.eyev
     }
@@ -207,6 +304,22 @@ impl Computations {
     pub fn refraction_boundary(&self) -> (f64, f64) {
         self.refraction_boundary
     }
+
+    pub fn channel_refraction_boundaries(&self) -> [(f64, f64); 3] {
+        self.channel_refraction_boundaries
+    }
+
+    pub fn texture_coordinates(&self) -> Option<(f64, f64)> {
+        self.texture_coordinates
+    }
+
+    pub fn footprint(&self) -> Option<[Vector; 2]> {
+        self.footprint
+    }
+
+    pub fn reflected_differentials(&self) -> Option<RayDifferentials> {
+        self.reflected_differentials
+    }
 }
 
 impl<'ray, S> Intersect<'ray, S, Computed>
@@ -217,10 +330,20 @@ where
         self.computations.as_ref().unwrap()
     }
 
+    // material used for shading, which may differ from the object's own
+    // material when the hit was finalised with overrides
+    pub fn material(&self) -> &'ray Material {
+        self.material.unwrap()
+    }
+
     pub fn target(&self) -> Point {
         self.computations().target()
     }
 
+    pub fn object_point(&self) -> Point {
+        self.computations().object_point()
+    }
+
     pub fn eyev(&self) -> Vector {
         self.computations().eyev()
     }
@@ -249,13 +372,132 @@ where
         self.computations().refraction_boundary()
     }
 
+    pub fn channel_refraction_boundaries(&self) -> [(f64, f64); 3] {
+        self.computations().channel_refraction_boundaries()
+    }
+
+    pub fn texture_coordinates(&self) -> Option<(f64, f64)> {
+        self.computations().texture_coordinates()
+    }
+
+    pub fn footprint(&self) -> Option<[Vector; 2]> {
+        self.computations().footprint()
+    }
+
+    pub fn reflected_differentials(&self) -> Option<RayDifferentials> {
+        self.computations().reflected_differentials()
+    }
+
+    // takes in the differentials of the ray that made the hit, so that the
+    // pattern is filtered over the ray's footprint here and the reflected ray
+    // carries differentials on to what it hits
+    pub fn with_differentials(mut self, differentials: &RayDifferentials) -> Self {
+        let computations = self.computations.as_mut().unwrap();
+        let (target, normal) = (computations.target, computations.normal);
+        computations.footprint = differentials.footprint(target, normal).map(|footprint| {
+            footprint.map(|offset| transform_through_stack_forwards(offset, &self.transform_stack))
+        });
+        computations.reflected_differentials = differentials.reflect(target, normal);
+        self
+    }
+
+    // the colour of the material's pattern here, filtered over the footprint
+    // when there is one, or the object's own colour if it has one and the
+    // material shows it; patterns drawn on a texture are looked up at
+    // the object's texture coordinates where it has them
+    pub(crate) fn surface_colour(&self) -> Colour {
+        if self.material().vertex_colours {
+            if let Some(colour) = self.object.vertex_colour(self.uv_coordinates) {
+                return colour;
+            }
+        }
+        let pattern = &self.material().pattern;
+        if let Some(texture_coordinates) = self.texture_coordinates() {
+            let texture_footprint = self
+                .footprint()
+                .and_then(|footprint| self.object.texture_footprint(footprint));
+            if let Some(colour) = pattern.colour_at_texture(texture_coordinates, texture_footprint)
+            {
+                return colour;
+            }
+        }
+        match self.footprint() {
+            Some(footprint) => pattern.colour_at_filtered(self.object_point(), footprint),
+            None => pattern.colour_at(self.object_point()),
+        }
+    }
+
+    fn keeps_backface(&self) -> bool {
+        self.inside() && self.material().backface == Backface::Keep
+    }
+
+    // normal lit by lights, which for backfaces the material keeps as they are
+    // points away from the eye
+    fn shading_normal(&self) -> Vector {
+        match self.keeps_backface() {
+            true => -self.normal(),
+            false => self.normal(),
+        }
+    }
+
+    // point just off the surface on the side of the shading normal, where it
+    // is lit and from where shadows are tested
+    pub(crate) fn lit_point(&self) -> Point {
+        match self.keeps_backface() {
+            true => self.under_point(),
+            false => self.over_point(),
+        }
+    }
+
     pub(crate) fn shade(&self, light: &Light, shadowed: bool) -> Colour {
-        light.shade_phong(
-            self.object().material(),
-            self.over_point(),
+        light.shade(
+            self.material(),
+            self.lit_point(),
+            self.surface_colour(),
             self.eyev(),
-            self.normal(),
+            self.shading_normal(),
+            shadowed,
+        )
+    }
+
+    pub(crate) fn shade_direct(&self, light: &Light, shadowed: bool) -> Colour {
+        light.shade_direct(
+            self.material(),
+            self.lit_point(),
+            self.surface_colour(),
+            self.eyev(),
+            self.shading_normal(),
+            shadowed,
+        )
+    }
+
+    pub(crate) fn shade_hemispheric_ambient(&self, ambient: &HemisphericAmbient) -> Colour {
+        ambient.shade(
+            self.material(),
+            self.surface_colour(),
+            self.shading_normal(),
+        )
+    }
+
+    pub(crate) fn shade_toon(&self, light: &Light, shadowed: bool, bands: usize) -> Colour {
+        light.shade_toon(
+            self.material(),
+            self.lit_point(),
+            self.surface_colour(),
+            self.shading_normal(),
             shadowed,
+            bands,
+        )
+    }
+
+    pub(crate) fn shade_toon_direct(&self, light: &Light, shadowed: bool, bands: usize) -> Colour {
+        light.shade_toon_direct(
+            self.material(),
+            self.lit_point(),
+            self.surface_colour(),
+            self.shading_normal(),
+            shadowed,
+            bands,
         )
     }
 
@@ -280,73 +522,216 @@ where
     }
 }
 
+// The intersections of a ray, along with space kept for finding them: the
+// coordinates each shape finds before they become intersects, and the
+// registers CSG and clipped shapes gather their operands' hits in. A register
+// kept from ray to ray (see HitRegister::recycle) stops allocating once it has
+// grown to fit the scene.
 #[derive(Clone, Debug)]
-pub struct HitRegister<'ray, S>(Vec<Intersect<'ray, S, Raw>>)
+pub struct HitRegister<'ray, S>
 where
-    S: PrimitiveShape + ?Sized + PartialEq;
+    S: PrimitiveShape + ?Sized + PartialEq,
+{
+    hits: Vec<Intersect<'ray, S, Raw>>,
+    coordinates: Vec<Coordinates>,
+    // the objects the ray is inside of, for finding refractive indices
+    containers: Vec<&'ray S>,
+    spare_registers: Vec<HitRegister<'ray, S>>,
+}
 
 impl<'ray, S> HitRegister<'ray, S>
 where
     S: PrimitiveShape + ?Sized + PartialEq,
 {
     pub fn empty() -> HitRegister<'ray, S> {
-        HitRegister(vec![])
+        HitRegister::from(vec![])
     }
 
     pub fn add_raw_intersect(&mut self, intersect: Intersect<'ray, S>) {
-        self.0.push(intersect);
+        self.hits.push(intersect);
     }
 
     pub fn combine_registers(&mut self, mut hit_register: HitRegister<'ray, S>) {
-        self.0.append(&mut hit_register.0);
+        self.hits.append(&mut hit_register.hits);
     }
 
-    pub fn finalise_hit(mut self) -> Option<Intersect<'ray, S, Computed>> {
-        self.sort_intersections_by_t();
-        match self.0.iter().position(|itx| itx.t >= 0.0) {
-            Some(idx_hit) => {
-                let refraction_boundary = self.compute_refraction_boundary(idx_hit);
-                Some(self.0.swap_remove(idx_hit).compute(refraction_boundary))
-            }
-            None => None,
+    // adds the intersections a shape finds into the register's coordinates
+    pub(crate) fn add_local_intersections<F>(
+        &mut self,
+        object: &'ray S,
+        ray: &'ray Ray,
+        transform_stack: &[&'ray Transform],
+        local_intersect_into: F,
+    ) where
+        F: FnOnce(&mut Vec<Coordinates>),
+    {
+        let mut coordinates = std::mem::take(&mut self.coordinates);
+        local_intersect_into(&mut coordinates);
+        for coordinate in coordinates.drain(..) {
+            self.add_raw_intersect(coordinate.attach(object, ray, transform_stack));
+        }
+        self.coordinates = coordinates;
+    }
+
+    // an empty register for hits to be sifted before they are added to this
+    // one, such as a CSG shape's operands'; hand it back with return_spare
+    pub(crate) fn take_spare(&mut self) -> HitRegister<'ray, S> {
+        self.spare_registers
+            .pop()
+            .unwrap_or_else(HitRegister::empty)
+    }
+
+    pub(crate) fn return_spare(&mut self, mut spare_register: HitRegister<'ray, S>) {
+        spare_register.hits.clear();
+        self.spare_registers.push(spare_register);
+    }
+
+    // the register emptied, for the intersections of another ray, keeping the
+    // space it has grown
+    pub fn recycle<'other>(self) -> HitRegister<'other, S> {
+        HitRegister {
+            hits: recycle(self.hits),
+            coordinates: self.coordinates,
+            containers: recycle(self.containers),
+            spare_registers: self
+                .spare_registers
+                .into_iter()
+                .map(HitRegister::recycle)
+                .collect(),
         }
     }
 
-    pub fn expose(mut self) -> Vec<Intersect<'ray, S, Raw>> {
+    // removes every intersection, nearest first
+    pub(crate) fn drain_sorted(&mut self) -> impl Iterator<Item = Intersect<'ray, S>> + '_ {
         self.sort_intersections_by_t();
-        self.0
+        self.hits.drain(..)
     }
 
-    fn sort_intersections_by_t(&mut self) {
-        self.0.sort_by(|a, b| a.t().partial_cmp(&b.t()).unwrap());
+    pub fn finalise_hit(self) -> Option<Intersect<'ray, S, Computed>> {
+        self.finalise_hit_with(|object| object.material())
     }
 
-    fn compute_refraction_boundary(&self, idx_hit: usize) -> (f64, f64) {
-        assert!(idx_hit < self.0.len());
+    // as finalise_hit, but with the material of every object looked up through
+    // the given function, for both shading and refraction
+    pub fn finalise_hit_with<F>(self, material_of: F) -> Option<Intersect<'ray, S, Computed>>
+    where
+        F: Fn(&'ray S) -> &'ray Material,
+    {
+        self.finalise_hit_within(0.0..f64::INFINITY, material_of)
+    }
 
-        let mut in_objects: Vec<&S> = vec![];
+    // as finalise_hit_with, but the hit is the nearest intersection whose t
+    // lies in the given range; intersections outside it still count towards
+    // the refractive indices either side of the hit
+    pub fn finalise_hit_within<F>(
+        self,
+        t_range: Range<f64>,
+        material_of: F,
+    ) -> Option<Intersect<'ray, S, Computed>>
+    where
+        F: Fn(&'ray S) -> &'ray Material,
+    {
+        self.finalise_hit_biased(t_range, EPSILON, material_of)
+    }
 
-        for (idx_current_intersect, current_intersect) in self.0.iter().enumerate() {
-            if idx_current_intersect == idx_hit {
-                let n1 = match in_objects.last() {
-                    Some(last_object) => last_object.material().refractive_index,
+    // as finalise_hit_within, with the over and under points of the hit offset
+    // from the surface by surface_bias rather than EPSILON
+    pub fn finalise_hit_biased<F>(
+        mut self,
+        t_range: Range<f64>,
+        surface_bias: f64,
+        material_of: F,
+    ) -> Option<Intersect<'ray, S, Computed>>
+    where
+        F: Fn(&'ray S) -> &'ray Material,
+    {
+        self.take_hit(t_range, surface_bias, material_of)
+    }
+
+    // finalise_hit_biased for a register to be recycled afterwards, which is
+    // left holding the other intersections
+    pub(crate) fn take_hit<F>(
+        &mut self,
+        t_range: Range<f64>,
+        surface_bias: f64,
+        material_of: F,
+    ) -> Option<Intersect<'ray, S, Computed>>
+    where
+        F: Fn(&'ray S) -> &'ray Material,
+    {
+        self.sort_intersections_by_t();
+        let culled = |itx: &Intersect<'ray, S, Raw>| {
+            material_of(itx.object()).backface == Backface::Cull && itx.faces_away()
+        };
+        match self
+            .hits
+            .iter()
+            .position(|itx| t_range.contains(&itx.t) && !culled(itx))
+        {
+            Some(idx_hit) => {
+                let (outside, inside) = self.boundary_objects(idx_hit);
+                let index_of = |object: Option<&'ray S>, channel: Option<usize>| match object {
+                    Some(object) => match channel {
+                        Some(channel) => material_of(object).channel_refractive_indices()[channel],
+                        None => material_of(object).refractive_index,
+                    },
                     None => 1.0,
                 };
+                let refraction_boundary = (index_of(outside, None), index_of(inside, None));
+                let channel_refraction_boundaries = [0, 1, 2].map(|channel| {
+                    (
+                        index_of(outside, Some(channel)),
+                        index_of(inside, Some(channel)),
+                    )
+                });
+                let hit = self.hits.swap_remove(idx_hit);
+                let material = material_of(hit.object());
+                Some(hit.compute_with_material(
+                    refraction_boundary,
+                    channel_refraction_boundaries,
+                    material,
+                    surface_bias,
+                ))
+            }
+            None => None,
+        }
+    }
 
-                HitRegister::update_containers(&mut in_objects, current_intersect);
+    // drops every intersection whose t lies outside the range
+    pub fn clip(mut self, t_range: Range<f64>) -> HitRegister<'ray, S> {
+        self.hits.retain(|itx| t_range.contains(&itx.t));
+        self
+    }
 
-                let n2 = match in_objects.last() {
-                    Some(last_object) => last_object.material().refractive_index,
-                    None => 1.0,
-                };
+    pub fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
 
-                return (n1, n2);
-            } else {
-                HitRegister::update_containers(&mut in_objects, current_intersect);
-            }
+    pub fn expose(mut self) -> Vec<Intersect<'ray, S, Raw>> {
+        self.sort_intersections_by_t();
+        self.hits
+    }
+
+    fn sort_intersections_by_t(&mut self) {
+        self.hits.sort_by(|a, b| a.t().partial_cmp(&b.t()).unwrap());
+    }
+
+    // the innermost objects containing the ray just before and just after the
+    // hit, if any, whose materials give the refractive indices either side
+    fn boundary_objects(&mut self, idx_hit: usize) -> (Option<&'ray S>, Option<&'ray S>) {
+        assert!(idx_hit < self.hits.len());
+
+        let mut in_objects = std::mem::take(&mut self.containers);
+        for current_intersect in &self.hits[..idx_hit] {
+            HitRegister::update_containers(&mut in_objects, current_intersect);
         }
+        let outside = in_objects.last().copied();
+        HitRegister::update_containers(&mut in_objects, &self.hits[idx_hit]);
+        let inside = in_objects.last().copied();
 
-        panic!();
+        in_objects.clear();
+        self.containers = in_objects;
+        (outside, inside)
     }
 
     fn update_containers<'tmp>(
@@ -373,11 +758,54 @@ impl<'ray, S> From<Vec<Intersect<'ray, S>>> for HitRegister<'ray, S>
 where
     S: PrimitiveShape + ?Sized + PartialEq,
 {
-    fn from(value: Vec<Intersect<'ray, S>>) -> HitRegister<'ray, S> {
-        HitRegister(value)
+    fn from(hits: Vec<Intersect<'ray, S>>) -> HitRegister<'ray, S> {
+        HitRegister {
+            hits,
+            coordinates: vec![],
+            containers: vec![],
+            spare_registers: vec![],
+        }
     }
 }
 
+thread_local! {
+    // the register and transform stack the last ray traced on each thread was
+    // intersected with, kept so that tracing allocates nothing once they have
+    // grown to fit the scene
+    static KEPT_HIT_REGISTER: Cell<Option<HitRegister<'static, dyn PrimitiveShape>>> =
+        const { Cell::new(None) };
+    static KEPT_TRANSFORM_STACK: Cell<Vec<&'static Transform>> = const { Cell::new(Vec::new()) };
+}
+
+impl<'ray> HitRegister<'ray, dyn PrimitiveShape> {
+    // the register kept on this thread by HitRegister::keep, or a new one
+    pub(crate) fn kept() -> HitRegister<'ray, dyn PrimitiveShape> {
+        KEPT_HIT_REGISTER
+            .take()
+            .map_or_else(HitRegister::empty, HitRegister::recycle)
+    }
+
+    pub(crate) fn keep(self) {
+        KEPT_HIT_REGISTER.set(Some(self.recycle()));
+    }
+}
+
+// as HitRegister::kept, for the transform stack
+pub(crate) fn kept_transform_stack<'ray>() -> Vec<&'ray Transform> {
+    recycle(KEPT_TRANSFORM_STACK.take())
+}
+
+pub(crate) fn keep_transform_stack(transform_stack: Vec<&Transform>) {
+    KEPT_TRANSFORM_STACK.set(recycle(transform_stack));
+}
+
+// an emptied vector for items borrowing for another lifetime; collecting in
+// place keeps the allocation, as the items are laid out alike
+fn recycle<T, U>(mut items: Vec<T>) -> Vec<U> {
+    items.clear();
+    items.into_iter().map(|_| unreachable!()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -389,8 +817,10 @@ mod tests {
     fn compute_intersect_ray_outside() {
         let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let shape = Sphere::builder().build();
-        let raw_intersect = Intersect::new(4.0, &shape, &ray, None, vec![]);
-        let computed_intersect = raw_intersect.compute((0.0, 0.0));
+        let raw_intersect = Intersect::new(4.0, &shape, &ray, None, &[]);
+        let computed_intersect = HitRegister::from(vec![raw_intersect])
+            .finalise_hit()
+            .unwrap();
         assert_eq!(computed_intersect.target(), Point::new(0.0, 0.0, -1.0));
         assert_eq!(computed_intersect.eyev(), Vector::new(0.0, 0.0, -1.0));
         assert_eq!(computed_intersect.normal(), Vector::new(0.0, 0.0, -1.0));
@@ -408,8 +838,10 @@ mod tests {
             .set_material(Material::preset())
             .build();
         let transform = Transform::new(TransformKind::Translate(0.0, 0.0, 1.0));
-        let raw_intersect = Intersect::new(5.0, &shape, &ray, None, vec![&transform]);
-        let computed_intersect = raw_intersect.compute((0.0, 0.0));
+        let raw_intersect = Intersect::new(5.0, &shape, &ray, None, &[&transform]);
+        let computed_intersect = HitRegister::from(vec![raw_intersect])
+            .finalise_hit()
+            .unwrap();
         assert!(computed_intersect.over_point().z < -EPSILON / 2.0);
         assert!(computed_intersect.target().z > computed_intersect.over_point().z);
         assert!(computed_intersect.under_point().z > -EPSILON / 2.0);
@@ -420,8 +852,10 @@ mod tests {
     fn compute_intersect_ray_inside() {
         let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
         let shape = Sphere::builder().build();
-        let raw_intersect = Intersect::new(1.0, &shape, &ray, None, vec![]);
-        let computed_intersect = raw_intersect.compute((0.0, 0.0));
+        let raw_intersect = Intersect::new(1.0, &shape, &ray, None, &[]);
+        let computed_intersect = HitRegister::from(vec![raw_intersect])
+            .finalise_hit()
+            .unwrap();
         assert_eq!(computed_intersect.target(), Point::new(0.0, 0.0, 1.0));
         assert_eq!(computed_intersect.eyev(), Vector::new(0.0, 0.0, -1.0));
         assert!(computed_intersect.inside());
@@ -430,13 +864,16 @@ mod tests {
 
     #[test]
     fn precompute_reflection_vector() {
-        let plane = Plane::builder().set_material(Material::preset()).build();
+        let plane: Box<dyn PrimitiveShape> =
+            Box::new(Plane::builder().set_material(Material::preset()).build());
         let ray = Ray::new(
             Point::new(0.0, 1.0, -1.0),
             Vector::new(0.0, -(2.0_f64.sqrt()) / 2.0, 2.0_f64.sqrt() / 2.0),
         );
-        let raw_intersect = Intersect::new(2.0_f64.sqrt() / 2.0, &plane, &ray, None, vec![]);
-        let computed_intersect = raw_intersect.compute((0.0, 0.0));
+        let raw_intersect = Intersect::new(2.0_f64.sqrt() / 2.0, plane.as_ref(), &ray, None, &[]);
+        let computed_intersect = HitRegister::from(vec![raw_intersect])
+            .finalise_hit()
+            .unwrap();
         assert_eq!(
             computed_intersect.reflected_ray().direction,
             Vector::new(0.0, 2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0)
@@ -447,9 +884,9 @@ mod tests {
     fn hit_register_finalises_hit() {
         let sphere = Sphere::builder().build();
         let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
-        let intersect1 = Intersect::new(-1.0, &sphere, &ray, None, vec![]);
-        let intersect2 = Intersect::new(2.0, &sphere, &ray, None, vec![]);
-        let intersect3 = Intersect::new(3.0, &sphere, &ray, None, vec![]);
+        let intersect1 = Intersect::new(-1.0, &sphere, &ray, None, &[]);
+        let intersect2 = Intersect::new(2.0, &sphere, &ray, None, &[]);
+        let intersect3 = Intersect::new(3.0, &sphere, &ray, None, &[]);
         let hit_register = HitRegister::from(vec![intersect1, intersect2, intersect3]);
         let hit = hit_register.finalise_hit().unwrap();
         assert_eq!(hit.t(), 2.0);
@@ -484,8 +921,12 @@ mod tests {
             .build_into();
         let world = World::new(vec![s1, s2, s3], vec![]);
         let ray = Ray::new(Point::new(0.0, 0.0, -4.0), Vector::new(0.0, 0.0, 1.0));
-        let mut hit_register = world.intersect_ray(&ray);
-        hit_register.sort_intersections_by_t();
+        let ts: Vec<f64> = world
+            .intersect_ray(&ray)
+            .expose()
+            .iter()
+            .map(|intersect| intersect.t())
+            .collect();
 
         let test_cases: [(usize, f64, f64); 6] = [
             (0, 1.0, 1.5),
@@ -496,7 +937,11 @@ mod tests {
             (5, 1.5, 1.0),
         ];
         for (idx, n1, n2) in test_cases {
-            let refraction_boundary = hit_register.compute_refraction_boundary(idx);
+            let refraction_boundary = world
+                .intersect_ray(&ray)
+                .finalise_hit_within(ts[idx]..f64::INFINITY, |object| object.material())
+                .unwrap()
+                .refraction_boundary();
             assert_eq!(refraction_boundary, (n1, n2), "{}", idx);
         }
     }
@@ -1,11 +1,28 @@
+use std::collections::BTreeSet;
+use std::sync::{Mutex, PoisonError};
+
 use crate::collections::{Colour, Point, Vector};
 
-use super::Material;
+use super::{Material, ShadingModel};
+
+// How a light's intensity drops off with distance. Without falloff it is
+// the colour delivered everywhere, as the brightness of a far-off source; with
+// inverse-square falloff it is the radiant intensity of a point source, so it
+// is delivered as is one unit away and lights are given in the units of the
+// scene rather than kept at or below 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Falloff {
+    #[default]
+    None,
+    InverseSquare,
+}
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Light {
     pub position: Point,
     pub intensity: Colour,
+    pub falloff: Falloff,
+    pub name: Option<&'static str>,
 }
 
 impl Light {
@@ -13,24 +30,113 @@ impl Light {
         Light {
             position,
             intensity,
+            falloff: Falloff::None,
+            name: None,
+        }
+    }
+
+    pub fn with_falloff(mut self, falloff: Falloff) -> Light {
+        self.falloff = falloff;
+        self
+    }
+
+    // light arriving at a point, before any shadowing
+    pub fn intensity_at(&self, point: Point) -> Colour {
+        match self.falloff {
+            Falloff::None => self.intensity,
+            Falloff::InverseSquare => {
+                let distance_squared = (self.position - point).magnitude().powi(2);
+                self.intensity * (1.0 / distance_squared)
+            }
         }
     }
 
-    pub(crate) fn shade_phong(
+    // names are interned, so that lights stay Copy; each distinct name is
+    // kept for the rest of the program
+    pub fn with_name(mut self, name: &str) -> Light {
+        self.name = Some(intern(name));
+        self
+    }
+
+    // shades a surface with the material's own shading model; the target is
+    // where the surface is lit and the surface colour that of the material's
+    // pattern there
+    pub(crate) fn shade(
         &self,
         material: &Material,
         target: Point,
+        surface_colour: Colour,
         eyev: Vector,
         normal: Vector,
         shadowed: bool,
     ) -> Colour {
-        let effective_colour = material.pattern.colour_at(target) * self.intensity;
-        let lightv = (self.position - target).normalise();
-        let ambient = effective_colour * material.ambient;
-        if shadowed {
-            return ambient;
+        let ambient = self.shade_ambient(material, surface_colour, target);
+        match shadowed {
+            true => ambient,
+            false => self.shade_over(ambient, material, surface_colour, target, eyev, normal),
         }
+    }
 
+    // diffuse and specular terms only, for when ambient light is provided by
+    // the world rather than by each light
+    pub(crate) fn shade_direct(
+        &self,
+        material: &Material,
+        target: Point,
+        surface_colour: Colour,
+        eyev: Vector,
+        normal: Vector,
+        shadowed: bool,
+    ) -> Colour {
+        let ambient = Colour::new(0.0, 0.0, 0.0);
+        match shadowed {
+            true => ambient,
+            false => self.shade_over(ambient, material, surface_colour, target, eyev, normal),
+        }
+    }
+
+    fn shade_over(
+        &self,
+        ambient: Colour,
+        material: &Material,
+        surface_colour: Colour,
+        target: Point,
+        eyev: Vector,
+        normal: Vector,
+    ) -> Colour {
+        match material.shading_model {
+            ShadingModel::Phong | ShadingModel::NormalisedPhong => {
+                self.shade_phong_over(ambient, material, surface_colour, target, eyev, normal)
+            }
+            ShadingModel::MetallicRoughness { metallic } => {
+                ambient
+                    + self.shade_metallic_roughness(
+                        material,
+                        metallic,
+                        surface_colour,
+                        target,
+                        eyev,
+                        normal,
+                    )
+            }
+        }
+    }
+
+    fn shade_ambient(&self, material: &Material, surface_colour: Colour, target: Point) -> Colour {
+        surface_colour * self.intensity_at(target) * material.ambient
+    }
+
+    fn shade_phong_over(
+        &self,
+        ambient: Colour,
+        material: &Material,
+        surface_colour: Colour,
+        target: Point,
+        eyev: Vector,
+        normal: Vector,
+    ) -> Colour {
+        let effective_colour = surface_colour * self.intensity_at(target);
+        let lightv = (self.position - target).normalise();
         let light_dot_normal = lightv.dot(normal);
         let diffuse;
         let specular;
@@ -44,17 +150,165 @@ impl Light {
             if reflect_dot_eye <= 0.0 {
                 specular = Colour::new(0.0, 0.0, 0.0);
             } else {
-                let factor = reflect_dot_eye.powf(material.shininess);
-                specular = self.intensity * material.specular * factor;
+                let mut factor = reflect_dot_eye.powf(material.shininess);
+                if material.shading_model == ShadingModel::NormalisedPhong {
+                    factor *= (material.shininess + 2.0) / 2.0;
+                }
+                specular = self.intensity_at(target) * material.specular * factor;
             }
         }
         ambient + diffuse + specular
     }
+
+    // Cook-Torrance microfacet shading with a GGX distribution, Smith-Schlick
+    // geometry term and Schlick's Fresnel approximation. Lights are taken to
+    // deliver pi times their intensity so that a white dielectric facing a light
+    // is about as bright as under Phong with full diffuse.
+    fn shade_metallic_roughness(
+        &self,
+        material: &Material,
+        metallic: f64,
+        base_colour: Colour,
+        target: Point,
+        eyev: Vector,
+        normal: Vector,
+    ) -> Colour {
+        let lightv = (self.position - target).normalise();
+        let light_dot_normal = lightv.dot(normal);
+        let eye_dot_normal = eyev.dot(normal);
+        if light_dot_normal <= 0.0 || eye_dot_normal <= 0.0 {
+            return Colour::new(0.0, 0.0, 0.0);
+        }
+
+        let halfway = (lightv + eyev).normalise();
+        let halfway_dot_normal = f64::max(halfway.dot(normal), 0.0);
+        let eye_dot_halfway = f64::max(eyev.dot(halfway), 0.0);
+
+        // perfectly smooth surfaces would give an infinitely small highlight
+        let roughness = material.roughness.clamp(0.04, 1.0);
+        let alpha2 = roughness.powi(4);
+        let distribution = alpha2
+            / (std::f64::consts::PI * (halfway_dot_normal.powi(2) * (alpha2 - 1.0) + 1.0).powi(2));
+
+        let k = (roughness + 1.0).powi(2) / 8.0;
+        let geometry = (light_dot_normal / (light_dot_normal * (1.0 - k) + k))
+            * (eye_dot_normal / (eye_dot_normal * (1.0 - k) + k));
+
+        let dielectric_reflectance = Colour::new(0.04, 0.04, 0.04);
+        let f0 = dielectric_reflectance * (1.0 - metallic) + base_colour * metallic;
+        let fresnel = f0 + (Colour::new(1.0, 1.0, 1.0) - f0) * (1.0 - eye_dot_halfway).powi(5);
+
+        let specular =
+            fresnel * (distribution * geometry / (4.0 * light_dot_normal * eye_dot_normal));
+        let diffuse = (Colour::new(1.0, 1.0, 1.0) - fresnel)
+            * base_colour
+            * ((1.0 - metallic) * material.diffuse / std::f64::consts::PI);
+
+        (diffuse + specular) * self.intensity_at(target) * (std::f64::consts::PI * light_dot_normal)
+    }
+
+    // cartoon-style shading: diffuse lighting is quantised into a fixed number
+    // of flat bands and specular highlights are dropped entirely
+    pub(crate) fn shade_toon(
+        &self,
+        material: &Material,
+        target: Point,
+        surface_colour: Colour,
+        normal: Vector,
+        shadowed: bool,
+        bands: usize,
+    ) -> Colour {
+        let ambient = self.shade_ambient(material, surface_colour, target);
+        match shadowed {
+            true => ambient,
+            false => self.shade_toon_over(ambient, material, surface_colour, target, normal, bands),
+        }
+    }
+
+    pub(crate) fn shade_toon_direct(
+        &self,
+        material: &Material,
+        target: Point,
+        surface_colour: Colour,
+        normal: Vector,
+        shadowed: bool,
+        bands: usize,
+    ) -> Colour {
+        let ambient = Colour::new(0.0, 0.0, 0.0);
+        match shadowed {
+            true => ambient,
+            false => self.shade_toon_over(ambient, material, surface_colour, target, normal, bands),
+        }
+    }
+
+    fn shade_toon_over(
+        &self,
+        ambient: Colour,
+        material: &Material,
+        surface_colour: Colour,
+        target: Point,
+        normal: Vector,
+        bands: usize,
+    ) -> Colour {
+        let lightv = (self.position - target).normalise();
+        let light_dot_normal = lightv.dot(normal);
+        if light_dot_normal < 0.0 {
+            return ambient;
+        }
+
+        let effective_colour = surface_colour * self.intensity_at(target);
+        let bands = usize::max(bands, 1) as f64;
+        let banded_dot_normal = (light_dot_normal * bands).ceil() / bands;
+        ambient + effective_colour * material.diffuse * banded_dot_normal
+    }
+}
+
+// Names are leaked with Box::leak so that Light can stay Copy; each distinct
+// name leaks once and lives for the rest of the program.
+fn intern(name: &str) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut names = NAMES.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(&interned) = names.get(name) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.into());
+    names.insert(interned);
+    interned
+}
+
+// Ambient light arriving from a sky above and the ground below, blended by how
+// far the surface normal points upwards. Replaces the flat per-light ambient
+// term when set on a world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HemisphericAmbient {
+    pub sky: Colour,
+    pub ground: Colour,
+}
+
+impl HemisphericAmbient {
+    pub fn new(sky: Colour, ground: Colour) -> HemisphericAmbient {
+        HemisphericAmbient { sky, ground }
+    }
+
+    pub fn colour_at(&self, normal: Vector) -> Colour {
+        let sky_weight = (normal.y + 1.0) / 2.0;
+        self.sky * sky_weight + self.ground * (1.0 - sky_weight)
+    }
+
+    pub(crate) fn shade(
+        &self,
+        material: &Material,
+        surface_colour: Colour,
+        normal: Vector,
+    ) -> Colour {
+        surface_colour * self.colour_at(normal) * material.ambient
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::utils::floats::approx_eq;
+    use crate::utils::{Buildable, ConsumingBuilder};
 
     use super::*;
 
@@ -67,7 +321,14 @@ mod tests {
         let light = Light::new(Point::new(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
         let resulting_colour = Colour::new(1.9, 1.9, 1.9);
         assert_eq!(
-            light.shade_phong(&material, position, eyev, normal, false),
+            light.shade(
+                &material,
+                position,
+                material.pattern.colour_at(position),
+                eyev,
+                normal,
+                false
+            ),
             resulting_colour
         );
     }
@@ -81,7 +342,14 @@ mod tests {
         let light = Light::new(Point::new(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
         let resulting_colour = Colour::new(1.0, 1.0, 1.0);
         assert_eq!(
-            light.shade_phong(&material, position, eyev, normal, false),
+            light.shade(
+                &material,
+                position,
+                material.pattern.colour_at(position),
+                eyev,
+                normal,
+                false
+            ),
             resulting_colour
         );
     }
@@ -93,7 +361,14 @@ mod tests {
         let eyev = Vector::new(0.0, 0.0, -1.0);
         let normal = Vector::new(0.0, 0.0, -1.0);
         let light = Light::new(Point::new(0.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
-        let colour = light.shade_phong(&material, position, eyev, normal, false);
+        let colour = light.shade(
+            &material,
+            position,
+            material.pattern.colour_at(position),
+            eyev,
+            normal,
+            false,
+        );
         let resulting_colour = Colour::new(0.736396, 0.736396, 0.736396);
         approx_eq!(colour.red, resulting_colour.red);
         approx_eq!(colour.green, resulting_colour.green);
@@ -107,7 +382,14 @@ mod tests {
         let eyev = Vector::new(0.0, -2.0_f64.sqrt() / 2.0, -2.0_f64.sqrt() / 2.0);
         let normal = Vector::new(0.0, 0.0, -1.0);
         let light = Light::new(Point::new(0.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
-        let colour = light.shade_phong(&material, position, eyev, normal, false);
+        let colour = light.shade(
+            &material,
+            position,
+            material.pattern.colour_at(position),
+            eyev,
+            normal,
+            false,
+        );
         let resulting_colour = Colour::new(1.636396, 1.636396, 1.636396);
         approx_eq!(colour.red, resulting_colour.red);
         approx_eq!(colour.green, resulting_colour.green);
@@ -123,11 +405,39 @@ mod tests {
         let light = Light::new(Point::new(0.0, 0.0, 10.0), Colour::new(1.0, 1.0, 1.0));
         let resulting_colour = Colour::new(0.1, 0.1, 0.1);
         assert_eq!(
-            light.shade_phong(&material, position, eyev, normal, false),
+            light.shade(
+                &material,
+                position,
+                material.pattern.colour_at(position),
+                eyev,
+                normal,
+                false
+            ),
             resulting_colour
         );
     }
 
+    #[test]
+    fn toon_shading_quantises_diffuse() {
+        let material = Material::preset();
+        let position = Point::zero();
+        let normal = Vector::new(0.0, 0.0, -1.0);
+        // light_dot_normal = cos(45deg) ~ 0.707, which rounds up to 0.75 with 4 bands
+        let light = Light::new(Point::new(0.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let colour = light.shade_toon(
+            &material,
+            position,
+            material.pattern.colour_at(position),
+            normal,
+            false,
+            4,
+        );
+        let resulting_colour = Colour::new(0.775, 0.775, 0.775);
+        approx_eq!(colour.red, resulting_colour.red);
+        approx_eq!(colour.green, resulting_colour.green);
+        approx_eq!(colour.blue, resulting_colour.blue);
+    }
+
     #[test]
     fn light_in_shadow() {
         let material = Material::preset();
@@ -137,8 +447,193 @@ mod tests {
         let light = Light::new(Point::new(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
         let resulting_colour = Colour::new(0.1, 0.1, 0.1);
         assert_eq!(
-            light.shade_phong(&material, position, eyev, normal, true),
+            light.shade(
+                &material,
+                position,
+                material.pattern.colour_at(position),
+                eyev,
+                normal,
+                true
+            ),
             resulting_colour
         );
     }
+
+    #[test]
+    fn hemispheric_ambient_blends_by_normal() {
+        let ambient =
+            HemisphericAmbient::new(Colour::new(0.0, 0.0, 1.0), Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(
+            ambient.colour_at(Vector::new(0.0, 1.0, 0.0)),
+            Colour::new(0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            ambient.colour_at(Vector::new(0.0, -1.0, 0.0)),
+            Colour::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            ambient.colour_at(Vector::new(1.0, 0.0, 0.0)),
+            Colour::new(0.5, 0.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn hemispheric_ambient_scales_with_material() {
+        let material = Material::preset();
+        let ambient =
+            HemisphericAmbient::new(Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+        let colour = ambient.shade(
+            &material,
+            material.pattern.colour_at(Point::zero()),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        assert_eq!(colour, Colour::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn metallic_roughness_dielectric_facing_light() {
+        let material = Material::builder()
+            .set_diffuse(1.0)
+            .set_ambient(0.0)
+            .set_roughness(1.0)
+            .set_shading_model(ShadingModel::MetallicRoughness { metallic: 0.0 })
+            .build();
+        let eyev = Vector::new(0.0, 0.0, -1.0);
+        let normal = Vector::new(0.0, 0.0, -1.0);
+        let light = Light::new(Point::new(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let colour = light.shade(
+            &material,
+            Point::zero(),
+            material.pattern.colour_at(Point::zero()),
+            eyev,
+            normal,
+            false,
+        );
+        // 96% diffuse plus a faint rough highlight
+        assert!(colour.red > 0.96 && colour.red < 1.0);
+        assert_eq!(colour.red, colour.blue);
+    }
+
+    #[test]
+    fn metallic_roughness_metal_tints_highlight() {
+        let gold = Material::builder()
+            .set_colour(Colour::new(1.0, 0.8, 0.3))
+            .set_ambient(0.0)
+            .set_roughness(0.3)
+            .set_shading_model(ShadingModel::MetallicRoughness { metallic: 1.0 })
+            .build();
+        let eyev = Vector::new(0.0, 0.0, -1.0);
+        let normal = Vector::new(0.0, 0.0, -1.0);
+        let light = Light::new(Point::new(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let colour = light.shade(
+            &gold,
+            Point::zero(),
+            gold.pattern.colour_at(Point::zero()),
+            eyev,
+            normal,
+            false,
+        );
+        // no diffuse term for metals, and the highlight takes on the base colour
+        assert!(colour.red > colour.green && colour.green > colour.blue);
+        approx_eq!(colour.green / colour.red, 0.8);
+
+        let in_shadow = light.shade(
+            &gold,
+            Point::zero(),
+            gold.pattern.colour_at(Point::zero()),
+            eyev,
+            normal,
+            true,
+        );
+        assert_eq!(in_shadow, Colour::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn normalised_phong_keeps_highlight_energy() {
+        let eyev = Vector::new(0.0, -2.0_f64.sqrt() / 2.0, -2.0_f64.sqrt() / 2.0);
+        let normal = Vector::new(0.0, 0.0, -1.0);
+        let light = Light::new(Point::new(0.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let peak = |shading_model, shininess| {
+            let material = Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                shininess,
+                shading_model,
+                ..Material::preset()
+            };
+            light
+                .shade(
+                    &material,
+                    Point::zero(),
+                    material.pattern.colour_at(Point::zero()),
+                    eyev,
+                    normal,
+                    false,
+                )
+                .red
+        };
+        // legacy Phong highlights peak at the specular strength at any shininess
+        assert_eq!(peak(ShadingModel::Phong, 10.0), 0.9);
+        assert_eq!(peak(ShadingModel::Phong, 200.0), 0.9);
+        approx_eq!(peak(ShadingModel::NormalisedPhong, 10.0), 0.9 * 6.0);
+        approx_eq!(peak(ShadingModel::NormalisedPhong, 200.0), 0.9 * 101.0);
+    }
+
+    #[test]
+    fn named_lights_are_copied() {
+        let key = Light::new(Point::zero(), Colour::new(1.0, 1.0, 1.0)).with_name("key");
+        let copy = key;
+        assert_eq!(copy, key);
+        assert_eq!(key.name, Some("key"));
+        // a name is only stored once, however often it is given
+        let again =
+            Light::new(Point::zero(), Colour::new(1.0, 1.0, 1.0)).with_name(&String::from("key"));
+        assert!(std::ptr::eq(again.name.unwrap(), key.name.unwrap()));
+    }
+
+    #[test]
+    fn inverse_square_falloff() {
+        let light = Light::new(
+            Point::new(0.0, 0.0, -10.0),
+            Colour::new(200.0, 200.0, 200.0),
+        )
+        .with_falloff(Falloff::InverseSquare);
+        assert_eq!(
+            light.intensity_at(Point::zero()),
+            Colour::new(2.0, 2.0, 2.0)
+        );
+        assert_eq!(
+            light.intensity_at(Point::new(0.0, 0.0, 10.0)),
+            Colour::new(0.5, 0.5, 0.5)
+        );
+
+        let material = Material::preset();
+        let eyev = Vector::new(0.0, 0.0, -1.0);
+        let normal = Vector::new(0.0, 0.0, -1.0);
+        let near = Light::new(
+            Point::new(0.0, 0.0, -10.0),
+            Colour::new(100.0, 100.0, 100.0),
+        )
+        .with_falloff(Falloff::InverseSquare);
+        let unit = Light::new(Point::new(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let colour = near.shade(
+            &material,
+            Point::zero(),
+            material.pattern.colour_at(Point::zero()),
+            eyev,
+            normal,
+            false,
+        );
+        let expected = unit.shade(
+            &material,
+            Point::zero(),
+            material.pattern.colour_at(Point::zero()),
+            eyev,
+            normal,
+            false,
+        );
+        approx_eq!(colour.red, expected.red);
+        approx_eq!(colour.green, expected.green);
+        approx_eq!(colour.blue, expected.blue);
+    }
 }
@@ -1,6 +1,8 @@
-use crate::objects::{Pattern, Solid};
+use crate::collections::{Colour, Point, Vector};
+use crate::objects::{transform_through_stack_backwards, Pattern, Solid, Transform};
+use crate::utils::{BuildError, Buildable, ConsumingBuilder};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Material {
     pub pattern: Box<dyn Pattern>,
     pub ambient: f64,
@@ -10,6 +12,162 @@ pub struct Material {
     pub reflectance: f64,
     pub transparency: f64,
     pub refractive_index: f64,
+    // separate refractive indices for red, green and blue light, so that
+    // refraction splits white light into colours as a prism does; reflectance
+    // still follows refractive_index
+    pub dispersion: Option<[f64; 3]>,
+    // light given off by the surface itself, added regardless of any lights
+    pub emission: Colour,
+    // fraction of light absorbed per unit distance travelled through the
+    // material, per channel; only meaningful for transparent materials
+    pub absorption: Colour,
+    // spread of reflected and refracted rays around the ideal direction; zero
+    // gives perfectly sharp mirrors and glass
+    pub roughness: f64,
+    pub shading_model: ShadingModel,
+    // perturbs shading normals to fake surface relief
+    pub bump: Option<BumpMap>,
+    // thin coating whose reflections interfere, tinting them by viewing angle;
+    // when set it takes the place of Schlick's approximation
+    pub thin_film: Option<ThinFilm>,
+    pub backface: Backface,
+    // caps on how many further bounces reflected or refracted rays leaving
+    // this surface may make, beneath the world's own limit
+    pub max_reflection_depth: Option<u32>,
+    pub max_refraction_depth: Option<u32>,
+    // whether shapes that carry colours of their own (such as meshes with
+    // vertex colours) show them in place of the pattern
+    pub vertex_colours: bool,
+}
+
+// How direct light is turned into colour at a surface. Phong matches the book;
+// metallic-roughness is a physically based model with GGX highlights whose
+// width follows the material's roughness.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ShadingModel {
+    #[default]
+    Phong,
+    // Phong with highlights scaled by (shininess + 2) / 2, so a highlight
+    // reflects the same total light however tight it is; raising shininess
+    // makes it smaller and brighter rather than dimmer
+    NormalisedPhong,
+    // metallic is the blend in [0, 1] from a dielectric to a bare metal
+    MetallicRoughness {
+        metallic: f64,
+    },
+}
+
+// What happens where a ray meets the back of a surface, as when looking into
+// an open mesh or at the far side of a flat card.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backface {
+    // shaded as if it were the front, with the normal turned towards the eye
+    #[default]
+    Flip,
+    // shaded with its own outward normal, so it shows the light falling on
+    // the front, as a thin leaf seen against the sun does
+    Keep,
+    // not seen at all: camera, reflected and refracted rays pass through it,
+    // though it still casts shadows
+    Cull,
+}
+
+// Height field for bump mapping. The height at a point is the luminance of the
+// pattern there, multiplied by depth; shading normals tilt away from uphill.
+#[derive(Clone, Debug)]
+pub struct BumpMap {
+    pub height: Box<dyn Pattern>,
+    pub depth: f64,
+}
+
+impl PartialEq for BumpMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.height.as_ref() == other.height.as_ref() && self.depth == other.depth
+    }
+}
+
+impl BumpMap {
+    // distance either side of the target at which the height is sampled
+    const SAMPLE_STEP: f64 = 1e-4;
+
+    pub fn new(height: Box<dyn Pattern>, depth: f64) -> BumpMap {
+        BumpMap { height, depth }
+    }
+
+    fn height_at(&self, point: Point) -> f64 {
+        self.height.colour_at(point).luminance() * self.depth
+    }
+
+    // central-difference gradient of the height field, taken in the object's
+    // own space (where its patterns live) and carried out to world space as a
+    // normal is, with the component along the normal removed so only tilt
+    // across the surface remains
+    pub fn perturb(
+        &self,
+        object_point: Point,
+        normal: Vector,
+        transform_stack: &[&Transform],
+    ) -> Vector {
+        let step = BumpMap::SAMPLE_STEP;
+        let slope = |offset: Vector| {
+            (self.height_at(object_point + offset * step)
+                - self.height_at(object_point - offset * step))
+                / (2.0 * step)
+        };
+        let object_gradient = Vector::new(
+            slope(Vector::new(1.0, 0.0, 0.0)),
+            slope(Vector::new(0.0, 1.0, 0.0)),
+            slope(Vector::new(0.0, 0.0, 1.0)),
+        );
+        let gradient = transform_through_stack_backwards(object_gradient, transform_stack);
+        let tangential = gradient - normal * gradient.dot(normal);
+        (normal - tangential).normalise()
+    }
+}
+
+// A transparent coating, such as the wall of a soap bubble or a layer of oil
+// on water, given by its thickness in nanometres and its refractive index.
+// Light reflected off its top and bottom interferes, so how much is reflected
+// depends on wavelength and angle, giving shifting bands of colour.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThinFilm {
+    pub thickness: f64,
+    pub refractive_index: f64,
+}
+
+impl ThinFilm {
+    // wavelengths in nanometres taken for red, green and blue light
+    const WAVELENGTHS: [f64; 3] = [650.0, 510.0, 475.0];
+
+    pub fn new(thickness: f64, refractive_index: f64) -> ThinFilm {
+        ThinFilm {
+            thickness,
+            refractive_index,
+        }
+    }
+
+    // fraction of light reflected in each channel, for light arriving at the
+    // given cosine to the normal from a medium of index n1 above the film and
+    // a surface of index n2 beneath it (Airy's formula for a single layer,
+    // with amplitudes taken at normal incidence)
+    pub fn reflectance(&self, cos_i: f64, n1: f64, n2: f64) -> Colour {
+        let nf = self.refractive_index;
+        let sin2_film = (n1 / nf).powi(2) * (1.0 - cos_i.powi(2));
+        if sin2_film > 1.0 {
+            return Colour::new(1.0, 1.0, 1.0);
+        }
+        let cos_film = (1.0 - sin2_film).sqrt();
+
+        let r12 = (n1 - nf) / (n1 + nf);
+        let r23 = (nf - n2) / (nf + n2);
+        let [red, green, blue] = ThinFilm::WAVELENGTHS.map(|wavelength| {
+            let cos_phase =
+                (4.0 * std::f64::consts::PI * nf * self.thickness * cos_film / wavelength).cos();
+            (r12.powi(2) + r23.powi(2) + 2.0 * r12 * r23 * cos_phase)
+                / (1.0 + (r12 * r23).powi(2) + 2.0 * r12 * r23 * cos_phase)
+        });
+        Colour::new(red, green, blue)
+    }
 }
 
 impl PartialEq for Material {
@@ -22,6 +180,17 @@ impl PartialEq for Material {
             && self.reflectance == other.reflectance
             && self.transparency == other.transparency
             && self.refractive_index == other.refractive_index
+            && self.dispersion == other.dispersion
+            && self.emission == other.emission
+            && self.absorption == other.absorption
+            && self.roughness == other.roughness
+            && self.shading_model == other.shading_model
+            && self.bump == other.bump
+            && self.thin_film == other.thin_film
+            && self.backface == other.backface
+            && self.max_reflection_depth == other.max_reflection_depth
+            && self.max_refraction_depth == other.max_refraction_depth
+            && self.vertex_colours == other.vertex_colours
     }
 }
 
@@ -36,11 +205,27 @@ impl Default for Material {
             reflectance: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            dispersion: None,
+            emission: Colour::new(0.0, 0.0, 0.0),
+            absorption: Colour::new(0.0, 0.0, 0.0),
+            roughness: 0.0,
+            shading_model: ShadingModel::Phong,
+            bump: None,
+            thin_film: None,
+            backface: Backface::Flip,
+            max_reflection_depth: None,
+            max_refraction_depth: None,
+            vertex_colours: false,
         }
     }
 }
 
 impl Material {
+    // refractive indices for red, green and blue light
+    pub fn channel_refractive_indices(&self) -> [f64; 3] {
+        self.dispersion.unwrap_or([self.refractive_index; 3])
+    }
+
     pub fn preset() -> Material {
         Material {
             pattern: Box::new(Solid::preset()),
@@ -51,6 +236,417 @@ impl Material {
             reflectance: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            dispersion: None,
+            emission: Colour::new(0.0, 0.0, 0.0),
+            absorption: Colour::new(0.0, 0.0, 0.0),
+            roughness: 0.0,
+            shading_model: ShadingModel::Phong,
+            bump: None,
+            thin_film: None,
+            backface: Backface::Flip,
+            max_reflection_depth: None,
+            max_refraction_depth: None,
+            vertex_colours: true,
+        }
+    }
+}
+
+// Starts from the preset material rather than the all-zero default, so only
+// the properties that differ from a plain white surface need to be set.
+#[derive(Debug, Default)]
+pub struct MaterialBuilder {
+    pattern: Option<Box<dyn Pattern>>,
+    ambient: Option<f64>,
+    diffuse: Option<f64>,
+    specular: Option<f64>,
+    shininess: Option<f64>,
+    reflectance: Option<f64>,
+    transparency: Option<f64>,
+    refractive_index: Option<f64>,
+    dispersion: Option<[f64; 3]>,
+    emission: Option<Colour>,
+    absorption: Option<Colour>,
+    roughness: Option<f64>,
+    shading_model: Option<ShadingModel>,
+    bump: Option<BumpMap>,
+    thin_film: Option<ThinFilm>,
+    backface: Option<Backface>,
+    max_reflection_depth: Option<u32>,
+    max_refraction_depth: Option<u32>,
+    vertex_colours: Option<bool>,
+}
+
+impl MaterialBuilder {
+    pub fn set_pattern(mut self, pattern: Box<dyn Pattern>) -> MaterialBuilder {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    pub fn set_colour(mut self, colour: Colour) -> MaterialBuilder {
+        self.pattern = Some(Box::new(Solid::new(colour)));
+        self
+    }
+
+    pub fn set_ambient(mut self, ambient: f64) -> MaterialBuilder {
+        self.ambient = Some(ambient);
+        self
+    }
+
+    pub fn set_diffuse(mut self, diffuse: f64) -> MaterialBuilder {
+        self.diffuse = Some(diffuse);
+        self
+    }
+
+    pub fn set_specular(mut self, specular: f64) -> MaterialBuilder {
+        self.specular = Some(specular);
+        self
+    }
+
+    pub fn set_shininess(mut self, shininess: f64) -> MaterialBuilder {
+        self.shininess = Some(shininess);
+        self
+    }
+
+    pub fn set_reflectance(mut self, reflectance: f64) -> MaterialBuilder {
+        self.reflectance = Some(reflectance);
+        self
+    }
+
+    pub fn set_transparency(mut self, transparency: f64) -> MaterialBuilder {
+        self.transparency = Some(transparency);
+        self
+    }
+
+    pub fn set_refractive_index(mut self, refractive_index: f64) -> MaterialBuilder {
+        self.refractive_index = Some(refractive_index);
+        self
+    }
+
+    pub fn set_thin_film(mut self, thin_film: ThinFilm) -> MaterialBuilder {
+        self.thin_film = Some(thin_film);
+        self
+    }
+
+    pub fn set_backface(mut self, backface: Backface) -> MaterialBuilder {
+        self.backface = Some(backface);
+        self
+    }
+
+    pub fn set_dispersion(mut self, red: f64, green: f64, blue: f64) -> MaterialBuilder {
+        self.dispersion = Some([red, green, blue]);
+        self
+    }
+
+    pub fn set_emission(mut self, emission: Colour) -> MaterialBuilder {
+        self.emission = Some(emission);
+        self
+    }
+
+    pub fn set_absorption(mut self, absorption: Colour) -> MaterialBuilder {
+        self.absorption = Some(absorption);
+        self
+    }
+
+    pub fn set_roughness(mut self, roughness: f64) -> MaterialBuilder {
+        self.roughness = Some(roughness);
+        self
+    }
+
+    pub fn set_shading_model(mut self, shading_model: ShadingModel) -> MaterialBuilder {
+        self.shading_model = Some(shading_model);
+        self
+    }
+
+    pub fn set_bump(mut self, bump: BumpMap) -> MaterialBuilder {
+        self.bump = Some(bump);
+        self
+    }
+
+    pub fn set_max_reflection_depth(mut self, depth: u32) -> MaterialBuilder {
+        self.max_reflection_depth = Some(depth);
+        self
+    }
+
+    pub fn set_max_refraction_depth(mut self, depth: u32) -> MaterialBuilder {
+        self.max_refraction_depth = Some(depth);
+        self
+    }
+
+    // vertex colours show unless a pattern or colour is set
+    pub fn set_vertex_colours(mut self, vertex_colours: bool) -> MaterialBuilder {
+        self.vertex_colours = Some(vertex_colours);
+        self
+    }
+}
+
+impl Buildable for Material {
+    type Builder = MaterialBuilder;
+
+    fn builder() -> Self::Builder {
+        MaterialBuilder::default()
+    }
+}
+
+impl MaterialBuilder {
+    // the first property set outside of its physically meaningful range; the
+    // preset's own values are all valid
+    fn check(&self) -> Result<(), BuildError> {
+        let invalid = |field, reason: &str| {
+            Err(BuildError::InvalidValue {
+                field,
+                reason: reason.to_string(),
+            })
+        };
+        let positive = |value: f64| value > 0.0;
+        let non_negative = [
+            ("ambient", self.ambient),
+            ("diffuse", self.diffuse),
+            ("specular", self.specular),
+            ("shininess", self.shininess),
+            ("roughness", self.roughness),
+        ];
+        for (field, value) in non_negative {
+            if value.is_some_and(|value| !(0.0..).contains(&value)) {
+                return invalid(field, "must not be negative");
+            }
+        }
+        if let Some(ShadingModel::MetallicRoughness { metallic }) = self.shading_model {
+            if !(0.0..=1.0).contains(&metallic) {
+                return invalid("metallic", "must lie within [0, 1]");
+            }
+        }
+        let unit = [
+            ("reflectance", self.reflectance),
+            ("transparency", self.transparency),
+        ];
+        for (field, value) in unit {
+            if value.is_some_and(|value| !(0.0..=1.0).contains(&value)) {
+                return invalid(field, "must lie within [0, 1]");
+            }
+        }
+        let colours = [("emission", self.emission), ("absorption", self.absorption)];
+        for (field, colour) in colours {
+            if colour.is_some_and(|colour| {
+                ![colour.red, colour.green, colour.blue]
+                    .iter()
+                    .all(|channel| (0.0..).contains(channel))
+            }) {
+                return invalid(field, "must not be negative");
+            }
+        }
+        if self
+            .refractive_index
+            .is_some_and(|refractive_index| !positive(refractive_index))
+        {
+            return invalid("refractive_index", "must be positive");
+        }
+        if self
+            .dispersion
+            .is_some_and(|indices| !indices.into_iter().all(positive))
+        {
+            return invalid("dispersion", "refractive indices must be positive");
+        }
+        if let Some(thin_film) = self.thin_film {
+            if !(0.0..).contains(&thin_film.thickness) {
+                return invalid("thin_film", "thickness must not be negative");
+            }
+            if !positive(thin_film.refractive_index) {
+                return invalid("thin_film", "refractive index must be positive");
+            }
         }
+        Ok(())
+    }
+}
+
+impl ConsumingBuilder for MaterialBuilder {
+    type Built = Material;
+
+    // panics if any property is outside of its physically meaningful range;
+    // try_build reports it instead
+    fn build(self) -> Self::Built {
+        self.try_build().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        self.check()?;
+        let preset = Material::preset();
+        let vertex_colours = self.vertex_colours.unwrap_or(self.pattern.is_none());
+        Ok(Material {
+            pattern: self.pattern.unwrap_or(preset.pattern),
+            ambient: self.ambient.unwrap_or(preset.ambient),
+            diffuse: self.diffuse.unwrap_or(preset.diffuse),
+            specular: self.specular.unwrap_or(preset.specular),
+            shininess: self.shininess.unwrap_or(preset.shininess),
+            reflectance: self.reflectance.unwrap_or(preset.reflectance),
+            transparency: self.transparency.unwrap_or(preset.transparency),
+            refractive_index: self.refractive_index.unwrap_or(preset.refractive_index),
+            dispersion: self.dispersion,
+            emission: self.emission.unwrap_or(preset.emission),
+            absorption: self.absorption.unwrap_or(preset.absorption),
+            roughness: self.roughness.unwrap_or(preset.roughness),
+            shading_model: self.shading_model.unwrap_or(preset.shading_model),
+            bump: self.bump,
+            thin_film: self.thin_film,
+            backface: self.backface.unwrap_or(preset.backface),
+            max_reflection_depth: self.max_reflection_depth,
+            max_refraction_depth: self.max_refraction_depth,
+            vertex_colours,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::{Gradient, TransformKind};
+    use crate::utils::approx_eq;
+
+    use super::*;
+
+    #[test]
+    fn build_material_from_preset() {
+        assert_eq!(Material::builder().build(), Material::preset());
+    }
+
+    #[test]
+    fn build_material_with_properties() {
+        let material = Material::builder()
+            .set_colour(Colour::new(1.0, 0.0, 0.0))
+            .set_diffuse(0.7)
+            .set_reflectance(0.3)
+            .build();
+        assert_eq!(
+            material,
+            Material {
+                pattern: Box::new(Solid::new(Colour::new(1.0, 0.0, 0.0))),
+                diffuse: 0.7,
+                reflectance: 0.3,
+                vertex_colours: false,
+                ..Material::preset()
+            }
+        );
+    }
+
+    #[test]
+    fn copy_and_tweak_material() {
+        let gradient = Gradient::new(
+            Colour::new(1.0, 0.0, 0.0),
+            Colour::new(0.0, 0.0, 1.0),
+            Transform::default(),
+        );
+        let original = Material::builder()
+            .set_pattern(Box::new(gradient))
+            .set_reflectance(0.3)
+            .build();
+        let copy = Material {
+            reflectance: 0.6,
+            ..original.clone()
+        };
+        assert!(copy.pattern.as_ref() == original.pattern.as_ref());
+        assert_eq!(original.reflectance, 0.3);
+        assert_eq!(original.clone(), original);
+    }
+
+    #[test]
+    #[should_panic]
+    fn build_material_with_invalid_transparency() {
+        Material::builder().set_transparency(1.5).build();
+    }
+
+    #[test]
+    fn try_build_material_reports_invalid_property() {
+        assert_eq!(
+            Material::builder().set_transparency(1.5).try_build(),
+            Err(BuildError::InvalidValue {
+                field: "transparency",
+                reason: "must lie within [0, 1]".to_string()
+            })
+        );
+        assert!(Material::builder()
+            .set_diffuse(f64::NAN)
+            .try_build()
+            .is_err());
+        assert!(Material::builder()
+            .set_dispersion(1.4, 0.0, 1.6)
+            .try_build()
+            .is_err());
+        assert_eq!(
+            Material::builder()
+                .set_shading_model(ShadingModel::MetallicRoughness { metallic: 1.5 })
+                .try_build(),
+            Err(BuildError::InvalidValue {
+                field: "metallic",
+                reason: "must lie within [0, 1]".to_string()
+            })
+        );
+        assert!(Material::builder().set_diffuse(0.2).try_build().is_ok());
+    }
+
+    #[test]
+    fn try_build_checks_every_property() {
+        let field_of = |builder: MaterialBuilder| match builder.try_build() {
+            Err(BuildError::InvalidValue { field, .. }) => field,
+            other => panic!("expected an invalid value, got {:?}", other),
+        };
+        let builder = Material::builder;
+        assert_eq!(field_of(builder().set_ambient(-0.1)), "ambient");
+        assert_eq!(field_of(builder().set_specular(-0.1)), "specular");
+        assert_eq!(field_of(builder().set_shininess(-1.0)), "shininess");
+        assert_eq!(field_of(builder().set_roughness(-0.1)), "roughness");
+        assert_eq!(field_of(builder().set_reflectance(1.1)), "reflectance");
+        assert_eq!(
+            field_of(builder().set_refractive_index(0.0)),
+            "refractive_index"
+        );
+        let negative = Colour::new(0.0, -1.0, 0.0);
+        assert_eq!(field_of(builder().set_emission(negative)), "emission");
+        assert_eq!(field_of(builder().set_absorption(negative)), "absorption");
+    }
+
+    #[test]
+    fn bump_map_tilts_normal_away_from_uphill() {
+        // height rises from 0 to 1 along x
+        let bump = BumpMap::new(
+            Box::new(Gradient::new(
+                Colour::new(0.0, 0.0, 0.0),
+                Colour::new(1.0, 1.0, 1.0),
+                Transform::default(),
+            )),
+            1.0,
+        );
+        let normal = bump.perturb(Point::new(0.5, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), &[]);
+        approx_eq!(normal.x, -f64::sqrt(0.5));
+        approx_eq!(normal.y, f64::sqrt(0.5));
+        approx_eq!(normal.z, 0.0);
+
+        // stretched to twice its width, the object's slope halves
+        let stretch = Transform::new(TransformKind::Scale(2.0, 1.0, 1.0));
+        let normal = bump.perturb(
+            Point::new(0.5, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            &[&stretch],
+        );
+        approx_eq!(normal.x, -1.0 / f64::sqrt(5.0));
+        approx_eq!(normal.y, 2.0 / f64::sqrt(5.0));
+    }
+
+    #[test]
+    fn flat_bump_map_keeps_normal() {
+        let bump = BumpMap::new(Box::new(Solid::new(Colour::new(0.5, 0.5, 0.5))), 2.0);
+        let normal = Vector::new(0.0, 0.0, -1.0);
+        assert_eq!(bump.perturb(Point::zero(), normal, &[]), normal);
+    }
+
+    #[test]
+    fn thin_film_reflectance() {
+        let soap = ThinFilm::new(0.0, 1.33);
+        // a film far thinner than light's wavelength cancels its reflections
+        assert_eq!(soap.reflectance(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+
+        // a quarter wave thick for green, where the reflections reinforce
+        let r: f64 = (1.0 - 1.33) / (1.0 + 1.33);
+        let soap = ThinFilm::new(510.0 / (4.0 * 1.33), 1.33);
+        let reflectance = soap.reflectance(1.0, 1.0, 1.0);
+        approx_eq!(reflectance.green, (2.0 * r / (1.0 + r * r)).powi(2));
+        assert!(reflectance.red < reflectance.green && reflectance.blue < reflectance.green);
     }
 }
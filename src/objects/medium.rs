@@ -0,0 +1,119 @@
+use std::ops::Range;
+
+use crate::collections::Colour;
+use crate::objects::*;
+
+// Evenly spread particles that dim whatever lies behind them, passing on
+// e^(-density * distance) of its light, and show their own colour in its
+// place. Unlit media glow evenly with their colour; lit ones instead scatter
+// the light reaching points sampled along the ray, so objects shadowing the
+// medium cast visible shafts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Medium {
+    pub colour: Colour,
+    pub density: f64,
+    pub light_samples: Option<usize>,
+}
+
+impl Medium {
+    pub fn new(colour: Colour, density: f64) -> Medium {
+        Medium {
+            colour,
+            density,
+            light_samples: None,
+        }
+    }
+
+    pub fn with_light_samples(mut self, light_samples: usize) -> Medium {
+        self.light_samples = Some(light_samples);
+        self
+    }
+
+    // fraction of light passing through the given distance of the medium
+    pub fn transmittance(&self, distance: f64) -> f64 {
+        match self.density {
+            density if density > 0.0 => (-density * distance).exp(),
+            _ => 1.0,
+        }
+    }
+
+    // distance beyond which less than 1/1000 of the light gets through, where
+    // lit media stop sampling rays that never hit anything
+    pub(crate) fn visible_distance(&self) -> f64 {
+        match self.density {
+            density if density > 0.0 => 1000.0_f64.ln() / density,
+            _ => 0.0,
+        }
+    }
+}
+
+// A medium filling the inside of a closed boundary shape. The boundary itself
+// is never drawn; rays pass straight through it.
+#[derive(Debug)]
+pub struct ConstantMedium {
+    boundary: Shape,
+    medium: Medium,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Shape, medium: Medium) -> ConstantMedium {
+        ConstantMedium { boundary, medium }
+    }
+
+    pub fn boundary(&self) -> &Shape {
+        &self.boundary
+    }
+
+    pub fn medium(&self) -> &Medium {
+        &self.medium
+    }
+
+    // the stretches of the ray (in t, within the range) inside the boundary,
+    // pairing up its intersections as entries and exits
+    pub fn segments(&self, ray: &Ray, t_range: Range<f64>) -> Vec<Range<f64>> {
+        let ts: Vec<f64> = self
+            .boundary
+            .intersect_ray(ray, vec![])
+            .expose()
+            .iter()
+            .map(|intersect| intersect.t())
+            .collect();
+        ts.chunks_exact(2)
+            .map(|pair| pair[0].max(t_range.start)..pair[1].min(t_range.end))
+            .filter(|segment| segment.start < segment.end)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::{Point, Vector};
+    use crate::utils::{approx_eq, BuildInto, Buildable};
+
+    #[test]
+    fn transmittance_falls_off_exponentially() {
+        let medium = Medium::new(Colour::new(1.0, 1.0, 1.0), 0.5);
+        assert_eq!(medium.transmittance(0.0), 1.0);
+        approx_eq!(medium.transmittance(2.0), (-1.0_f64).exp());
+        approx_eq!(medium.transmittance(medium.visible_distance()), 0.001);
+    }
+
+    #[test]
+    fn segments_inside_boundary() {
+        let boundary = Sphere::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Scale(2.0, 2.0, 2.0)))
+            .build_into();
+        let medium = ConstantMedium::new(boundary, Medium::new(Colour::new(1.0, 1.0, 1.0), 1.0));
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(medium.segments(&ray, 0.0..f64::INFINITY), vec![3.0..7.0]);
+        assert_eq!(medium.segments(&ray, 4.0..5.0), vec![4.0..5.0]);
+        assert!(medium.segments(&ray, 0.0..2.0).is_empty());
+
+        let from_inside = Ray::new(Point::zero(), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            medium.segments(&from_inside, 0.0..f64::INFINITY),
+            vec![0.0..2.0]
+        );
+    }
+}
@@ -1,21 +1,26 @@
 pub mod bounds;
+pub mod clipped;
 pub mod csg;
 pub mod group;
 pub mod intersections;
 pub mod light;
 pub mod material;
+pub mod medium;
 pub mod patterns;
+pub mod presets;
 pub mod ray;
 pub mod shapes;
 pub mod transform;
 
 // crate-level re-exports
 pub(crate) use bounds::*;
+pub(crate) use clipped::*;
 pub(crate) use csg::*;
 pub(crate) use group::*;
 pub(crate) use intersections::*;
 pub(crate) use light::*;
 pub(crate) use material::*;
+pub(crate) use medium::*;
 pub(crate) use patterns::*;
 pub(crate) use ray::*;
 pub(crate) use shapes::*;
@@ -26,10 +31,15 @@ pub(super) mod prelude {
     pub use super::patterns::prelude::*;
     pub use super::shapes::prelude::*;
 
+    pub use super::clipped::{ClipRegion, ClippedShape};
     pub use super::group::Group;
     pub use super::intersections::{Coordinates, HitRegister, Intersect};
-    pub use super::light::Light;
-    pub use super::material::Material;
-    pub use super::ray::Ray;
+    pub use super::light::{Falloff, HemisphericAmbient, Light};
+    pub use super::material::{
+        Backface, BumpMap, Material, MaterialBuilder, ShadingModel, ThinFilm,
+    };
+    pub use super::medium::{ConstantMedium, Medium};
+    pub use super::presets;
+    pub use super::ray::{Ray, RayDifferentials};
     pub use super::transform::{Axis, Transform, TransformKind};
 }
@@ -1,11 +1,36 @@
-use crate::collections::{Colour, Point};
-use crate::objects::{Pattern, Transform};
+use crate::collections::{Colour, Point, Vector};
+use crate::objects::{filtered_even_squares, mix, Pattern, Transform};
+use crate::utils::EPSILON;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Checker {
     pub colour1: Colour,
     pub colour2: Colour,
     pub transform: Transform,
+    pub flooring: Flooring,
+}
+
+// How a coordinate is rounded down to find its square.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Flooring {
+    #[default]
+    Exact,
+    // coordinates within EPSILON of a whole number are taken to be on it, so
+    // surfaces lying along a boundary (such as a floor at y = 0) get one
+    // colour rather than speckling between both
+    Robust,
+}
+
+impl Flooring {
+    pub fn floor(&self, x: f64) -> f64 {
+        match self {
+            Flooring::Exact => x.floor(),
+            Flooring::Robust => match x.round() {
+                nearest if (x - nearest).abs() < EPSILON => nearest,
+                _ => x.floor(),
+            },
+        }
+    }
 }
 
 impl Checker {
@@ -14,8 +39,14 @@ impl Checker {
             colour1,
             colour2,
             transform,
+            flooring: Flooring::default(),
         }
     }
+
+    pub fn with_flooring(mut self, flooring: Flooring) -> Checker {
+        self.flooring = flooring;
+        self
+    }
 }
 
 impl Pattern for Checker {
@@ -24,14 +55,30 @@ impl Pattern for Checker {
     }
 
     fn local_colour_at(&self, pattern_point: Point) -> Colour {
+        let floor = |x| self.flooring.floor(x);
         let floored_sum_of_lengths =
-            (pattern_point.x.floor() + pattern_point.y.floor() + pattern_point.z.floor()) as i32;
+            (floor(pattern_point.x) + floor(pattern_point.y) + floor(pattern_point.z)) as i32;
         match floored_sum_of_lengths.rem_euclid(2) {
-            x if x == 0 => self.colour1,
-            x if x == 1 => self.colour2,
+            0 => self.colour1,
+            1 => self.colour2,
             _ => panic!(),
         }
     }
+
+    // box filtered along each axis over the footprint's extent on that axis
+    fn local_colour_filtered(&self, pattern_point: Point, [dx, dy]: [Vector; 2]) -> Colour {
+        let filter_widths = [
+            dx.x.abs() + dy.x.abs(),
+            dx.y.abs() + dy.y.abs(),
+            dx.z.abs() + dy.z.abs(),
+        ];
+        if filter_widths.iter().all(|&width| width < EPSILON) {
+            return self.local_colour_at(pattern_point);
+        }
+        let coordinates = [pattern_point.x, pattern_point.y, pattern_point.z];
+        let even = filtered_even_squares(&coordinates, &filter_widths);
+        mix(self.colour1, self.colour2, even)
+    }
 }
 
 #[cfg(test)]
@@ -47,6 +94,7 @@ mod tests {
             colour1,
             colour2,
             transform: Transform::default(),
+            flooring: Flooring::Exact,
         };
         assert_eq!(checker_pattern, resulting_checker_pattern);
     }
@@ -107,4 +155,40 @@ mod tests {
             colour2
         );
     }
+
+    #[test]
+    fn robust_flooring_ignores_boundary_noise() {
+        let colour1 = Colour::new(1.0, 1.0, 1.0);
+        let colour2 = Colour::new(0.0, 0.0, 0.0);
+        let floor_point = Point::new(0.5, -1e-12, 0.5);
+        let exact = Checker::new(colour1, colour2, Transform::default());
+        assert_eq!(exact.colour_at(floor_point), colour2);
+        let robust = exact.with_flooring(Flooring::Robust);
+        assert_eq!(robust.colour_at(floor_point), colour1);
+        assert_eq!(robust.colour_at(Point::new(1.01, 0.0, 0.0)), colour2);
+    }
+
+    #[test]
+    fn filtered_checker_blends_over_footprint() {
+        let colour1 = Colour::new(1.0, 1.0, 1.0);
+        let colour2 = Colour::new(0.0, 0.0, 0.0);
+        let checker_pattern = Checker::new(colour1, colour2, Transform::default());
+        let point = Point::new(0.25, 0.5, 0.5);
+        let small = [Vector::new(0.1, 0.0, 0.0), Vector::new(0.0, 0.0, 0.1)];
+        assert_eq!(checker_pattern.colour_at_filtered(point, small), colour1);
+
+        // a footprint straddling the edge at x = 1 takes a share of each
+        let straddling = [Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0)];
+        let point = Point::new(1.25, 0.5, 0.5);
+        assert_eq!(
+            checker_pattern.colour_at_filtered(point, straddling),
+            Colour::new(0.25, 0.25, 0.25)
+        );
+        // and one spanning many squares averages out to grey
+        let wide = [Vector::new(20.0, 0.0, 0.0), Vector::new(0.0, 0.0, 20.0)];
+        assert_eq!(
+            checker_pattern.colour_at_filtered(point, wide),
+            Colour::new(0.5, 0.5, 0.5)
+        );
+    }
 }
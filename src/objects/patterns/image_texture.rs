@@ -0,0 +1,187 @@
+use std::sync::Arc;
+
+use crate::collections::{Colour, Point, Vector};
+use crate::objects::{Pattern, Transform, UvMapping};
+use crate::scenes::{Canvas, Height, Width};
+
+// An image wrapped onto shapes through a (u, v) mapping, with the top of the
+// image at v = 1. The image is kept as a chain of mip levels, each half the
+// size of the one before down to a single pixel, so that filtered lookups can
+// read from a level whose pixels are about the size of the footprint instead
+// of skipping over detail. Levels are shared between clones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageTexture {
+    levels: Arc<Vec<Canvas>>,
+    pub mapping: UvMapping,
+    pub transform: Transform,
+}
+
+impl ImageTexture {
+    pub fn new(image: Canvas, transform: Transform) -> ImageTexture {
+        let mut levels = vec![image];
+        while let Some(level) = levels.last().and_then(ImageTexture::downsample) {
+            levels.push(level);
+        }
+        ImageTexture {
+            levels: Arc::new(levels),
+            mapping: UvMapping::default(),
+            transform,
+        }
+    }
+
+    pub fn with_mapping(mut self, mapping: UvMapping) -> ImageTexture {
+        self.mapping = mapping;
+        self
+    }
+
+    // the full size image first
+    pub fn levels(&self) -> &[Canvas] {
+        &self.levels
+    }
+
+    // averages each two by two block of pixels (fewer along an odd edge); None
+    // once the image is a single pixel (or empty)
+    fn downsample(image: &Canvas) -> Option<Canvas> {
+        let (width, height) = (image.width(), image.height());
+        if width * height <= 1 {
+            return None;
+        }
+        let mut level = Canvas::new(Width(width.div_ceil(2)), Height(height.div_ceil(2)));
+        for row in 0..level.height() {
+            for column in 0..level.width() {
+                let block: Vec<Colour> = [[0, 0], [1, 0], [0, 1], [1, 1]]
+                    .into_iter()
+                    .filter_map(|[x, y]| image.get_pixel([2 * column + x, 2 * row + y]))
+                    .map(|pixel| pixel.colour())
+                    .collect();
+                let sum = block
+                    .iter()
+                    .fold(Colour::new(0.0, 0.0, 0.0), |sum, &colour| sum + colour);
+                level
+                    .paint_colour_replace(column, row, sum * (1.0 / block.len() as f64))
+                    .expect("pixels lie within the level");
+            }
+        }
+        Some(level)
+    }
+
+    // blends the two levels either side of the one whose pixels match the
+    // longer side of the footprint on the image
+    fn filtered_sample(&self, uv: (f64, f64), uv_footprint: [(f64, f64); 2]) -> Colour {
+        let (width, height) = (self.levels[0].width(), self.levels[0].height());
+        let footprint_pixels = uv_footprint
+            .into_iter()
+            .map(|(du, dv)| (du * width as f64).hypot(dv * height as f64))
+            .fold(0.0, f64::max);
+
+        let finest = self.levels.len() - 1;
+        let level = footprint_pixels.max(1.0).log2().min(finest as f64);
+        let lower = level.floor() as usize;
+        let upper = usize::min(lower + 1, finest);
+        let blend = level - lower as f64;
+        ImageTexture::sample(&self.levels[lower], uv) * (1.0 - blend)
+            + ImageTexture::sample(&self.levels[upper], uv) * blend
+    }
+
+    // bilinear lookup in a level, repeating the image in u and v
+    fn sample(level: &Canvas, (u, v): (f64, f64)) -> Colour {
+        let (width, height) = (level.width(), level.height());
+        if width == 0 || height == 0 {
+            return Colour::new(0.0, 0.0, 0.0);
+        }
+        // pixel centres sit at half-pixel positions
+        let x = u * width as f64 - 0.5;
+        let y = (1.0 - v) * height as f64 - 0.5;
+        let [x0, y0] = [x.floor(), y.floor()];
+        let [fx, fy] = [x - x0, y - y0];
+        let pixel = |x: f64, y: f64| {
+            let column = (x as i64).rem_euclid(width as i64) as usize;
+            let row = (y as i64).rem_euclid(height as i64) as usize;
+            level[[column, row]].colour()
+        };
+        let top = pixel(x0, y0) * (1.0 - fx) + pixel(x0 + 1.0, y0) * fx;
+        let bottom = pixel(x0, y0 + 1.0) * (1.0 - fx) + pixel(x0 + 1.0, y0 + 1.0) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+impl Pattern for ImageTexture {
+    fn frame_transformation(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn local_colour_at(&self, pattern_point: Point) -> Colour {
+        ImageTexture::sample(&self.levels[0], self.mapping.uv(pattern_point))
+    }
+
+    fn local_colour_filtered(&self, pattern_point: Point, footprint: [Vector; 2]) -> Colour {
+        self.filtered_sample(
+            self.mapping.uv(pattern_point),
+            self.mapping.uv_footprint(pattern_point, footprint),
+        )
+    }
+
+    fn colour_at_texture(
+        &self,
+        texture_coordinates: (f64, f64),
+        texture_footprint: Option<[(f64, f64); 2]>,
+    ) -> Option<Colour> {
+        Some(match texture_footprint {
+            Some(footprint) => self.filtered_sample(texture_coordinates, footprint),
+            None => ImageTexture::sample(&self.levels[0], texture_coordinates),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // stripes one pixel wide, alternating white and black across
+    fn striped_image(width: usize, height: usize) -> Canvas {
+        let mut image = Canvas::new(Width(width), Height(height));
+        for row in 0..height {
+            for column in (0..width).step_by(2) {
+                image
+                    .paint_colour_replace(column, row, Colour::new(1.0, 1.0, 1.0))
+                    .unwrap();
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn mip_levels_halve_down_to_one_pixel() {
+        let texture = ImageTexture::new(striped_image(8, 3), Transform::default());
+        let sizes: Vec<[usize; 2]> = texture
+            .levels()
+            .iter()
+            .map(|level| [level.width(), level.height()])
+            .collect();
+        assert_eq!(sizes, vec![[8, 3], [4, 2], [2, 1], [1, 1]]);
+        for level in &texture.levels()[1..] {
+            assert_eq!(level[[0, 0]].colour(), Colour::new(0.5, 0.5, 0.5));
+        }
+    }
+
+    #[test]
+    fn filtered_lookup_reads_coarser_levels() {
+        let texture = ImageTexture::new(striped_image(8, 8), Transform::default());
+        // the centre of the top left pixel, which is white
+        let point = Point::new(1.0 / 16.0, 0.0, 1.0 - 1.0 / 16.0);
+        assert_eq!(texture.colour_at(point), Colour::new(1.0, 1.0, 1.0));
+
+        let pixel = [Vector::new(0.125, 0.0, 0.0), Vector::new(0.0, 0.0, 0.125)];
+        assert_eq!(
+            texture.colour_at_filtered(point, pixel),
+            Colour::new(1.0, 1.0, 1.0)
+        );
+        // a footprint of many pixels sees their average rather than whichever
+        // stripe the point falls on
+        let wide = [Vector::new(0.5, 0.0, 0.0), Vector::new(0.0, 0.0, 0.5)];
+        assert_eq!(
+            texture.colour_at_filtered(point, wide),
+            Colour::new(0.5, 0.5, 0.5)
+        );
+    }
+}
@@ -1,24 +1,39 @@
 pub mod checker;
 pub mod gradient;
+pub mod image_texture;
 pub mod pattern;
 pub mod ring;
+pub mod sky;
 pub mod solid;
+pub mod starfield;
 pub mod stripe;
+pub mod uv_checker;
+pub mod uv_mapping;
 
 // crate-level re-exports
 pub use checker::*;
 pub use gradient::*;
+pub use image_texture::*;
 pub use pattern::*;
 pub use ring::*;
+pub use sky::*;
 pub use solid::*;
+pub use starfield::*;
 pub use stripe::*;
+pub use uv_checker::*;
+pub use uv_mapping::*;
 
 // public re-exports (through crate::prelude)
 pub mod prelude {
-    pub use super::checker::Checker;
+    pub use super::checker::{Checker, Flooring};
     pub use super::gradient::Gradient;
-    pub use super::pattern::Pattern;
+    pub use super::image_texture::ImageTexture;
+    pub use super::pattern::{Pattern, PatternClone};
     pub use super::ring::Ring;
+    pub use super::sky::Sky;
     pub use super::solid::Solid;
+    pub use super::starfield::{MilkyWay, Starfield};
     pub use super::stripe::Stripe;
+    pub use super::uv_checker::UvChecker;
+    pub use super::uv_mapping::UvMapping;
 }
@@ -1,16 +1,119 @@
 use std::fmt::Debug;
 
-use crate::collections::{Colour, Point};
-use crate::objects::{Transform, Transformable};
+use crate::collections::{Colour, Point, Vector};
+use crate::objects::{transform_through_stack_forwards, Transform, Transformable};
+use crate::utils::EPSILON;
 
-pub trait Pattern: Debug {
+pub trait Pattern: Debug + Send + Sync + PatternClone {
     fn colour_at(&self, shape_point: Point) -> Colour {
-        let pattern_point = shape_point.transform(&self.frame_transformation().invert());
+        let pattern_point = shape_point.transform(self.frame_transformation().inverse());
         self.local_colour_at(pattern_point)
     }
 
+    // colour at a world-space point on a shape placed through the transform
+    // stack (outermost group first, ending with the shape's own), so the
+    // pattern stays fixed to the shape however it and its groups are moved
+    fn colour_at_object(&self, world_point: Point, transform_stack: &[&Transform]) -> Colour {
+        self.colour_at(transform_through_stack_forwards(
+            world_point,
+            transform_stack,
+        ))
+    }
+
+    // colour averaged over a footprint on the shape (the offsets, in the
+    // shape's space, to the points seen through the neighbouring pixels), so
+    // that detail finer than a pixel blends rather than aliasing
+    fn colour_at_filtered(&self, shape_point: Point, footprint: [Vector; 2]) -> Colour {
+        let to_pattern = self.frame_transformation().inverse();
+        let pattern_point = shape_point.transform(to_pattern);
+        self.local_colour_filtered(pattern_point, footprint.map(|v| v.transform(to_pattern)))
+    }
+
     fn frame_transformation(&self) -> &Transform;
     fn local_colour_at(&self, pattern_point: Point) -> Colour;
+
+    // patterns with no fine detail keep the default of point sampling
+    fn local_colour_filtered(&self, pattern_point: Point, _footprint: [Vector; 2]) -> Colour {
+        self.local_colour_at(pattern_point)
+    }
+
+    // colour at texture coordinates carried by the surface (such as a mesh's),
+    // filtered over the changes in them across the footprint when those are
+    // known; patterns drawn on a (u, v) texture are looked up there rather
+    // than through their transform and mapping, and others give None to be
+    // looked up at the point instead
+    fn colour_at_texture(
+        &self,
+        _texture_coordinates: (f64, f64),
+        _texture_footprint: Option<[(f64, f64); 2]>,
+    ) -> Option<Colour> {
+        None
+    }
+}
+
+// Lets boxed patterns be cloned; implemented for every pattern that is Clone.
+pub trait PatternClone {
+    fn clone_box(&self) -> Box<dyn Pattern>;
+}
+
+impl<T: Pattern + Clone + 'static> PatternClone for T {
+    fn clone_box(&self) -> Box<dyn Pattern> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Pattern> {
+    fn clone(&self) -> Box<dyn Pattern> {
+        self.clone_box()
+    }
+}
+
+// whether a coordinate falls in the first colour of bands repeating every two
+// widths, with duty_cycle the fraction of each repeat given to the first colour
+pub(crate) fn in_first_band(coordinate: f64, width: f64, duty_cycle: f64) -> bool {
+    (coordinate / (2.0 * width)).rem_euclid(1.0) < duty_cycle
+}
+
+// the fraction of a box filter of the given width about a coordinate that
+// falls in the first colour of bands as for in_first_band; filters narrower
+// than EPSILON point sample
+pub(crate) fn filtered_first_band(
+    coordinate: f64,
+    width: f64,
+    duty_cycle: f64,
+    filter_width: f64,
+) -> f64 {
+    if filter_width < EPSILON {
+        return match in_first_band(coordinate, width, duty_cycle) {
+            true => 1.0,
+            false => 0.0,
+        };
+    }
+    // length of the first bands from zero up to x
+    let period = 2.0 * width;
+    let first = duty_cycle.clamp(0.0, 1.0) * period;
+    let integral = |x: f64| (x / period).floor() * first + f64::min(x.rem_euclid(period), first);
+    let half = filter_width / 2.0;
+    (integral(coordinate + half) - integral(coordinate - half)) / filter_width
+}
+
+// checker squares one unit across, averaged over filters of the given widths
+// along each axis: the fraction of the filter on squares whose floored
+// coordinates sum to an even number
+pub(crate) fn filtered_even_squares(coordinates: &[f64], filter_widths: &[f64]) -> f64 {
+    // +1 on even squares and -1 on odd, which multiply across the axes
+    let parity: f64 = coordinates
+        .iter()
+        .zip(filter_widths)
+        .map(|(&coordinate, &filter_width)| {
+            2.0 * filtered_first_band(coordinate, 1.0, 0.5, filter_width) - 1.0
+        })
+        .product();
+    (1.0 + parity) / 2.0
+}
+
+pub(crate) fn mix(colour1: Colour, colour2: Colour, weight1: f64) -> Colour {
+    colour1 * weight1 + colour2 * (1.0 - weight1)
 }
 
 impl PartialEq for dyn Pattern {
@@ -1,11 +1,16 @@
 use crate::collections::{Colour, Point};
-use crate::objects::{Pattern, Transform};
+use crate::objects::{in_first_band, Axis, Pattern, Transform};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Ring {
     pub colour1: Colour,
     pub colour2: Colour,
     pub transform: Transform,
+    // rings are centred on the axis
+    pub axis: Axis,
+    pub width: f64,
+    // fraction of each repeat (two widths) given to colour1
+    pub duty_cycle: f64,
 }
 
 impl Ring {
@@ -14,8 +19,26 @@ impl Ring {
             colour1,
             colour2,
             transform,
+            axis: Axis::Y,
+            width: 1.0,
+            duty_cycle: 0.5,
         }
     }
+
+    pub fn with_axis(mut self, axis: Axis) -> Ring {
+        self.axis = axis;
+        self
+    }
+
+    pub fn with_width(mut self, width: f64) -> Ring {
+        self.width = width;
+        self
+    }
+
+    pub fn with_duty_cycle(mut self, duty_cycle: f64) -> Ring {
+        self.duty_cycle = duty_cycle;
+        self
+    }
 }
 
 impl Pattern for Ring {
@@ -24,11 +47,15 @@ impl Pattern for Ring {
     }
 
     fn local_colour_at(&self, pattern_point: Point) -> Colour {
-        let squared_magnitude = pattern_point.x.powi(2) + pattern_point.z.powi(2);
-        match (squared_magnitude.sqrt().floor() as i32).rem_euclid(2) {
-            x if x == 0 => self.colour1,
-            x if x == 1 => self.colour2,
-            _ => panic!(),
+        let [a, b] = match self.axis {
+            Axis::X => [pattern_point.y, pattern_point.z],
+            Axis::Y => [pattern_point.x, pattern_point.z],
+            Axis::Z => [pattern_point.x, pattern_point.y],
+        };
+        let distance = (a.powi(2) + b.powi(2)).sqrt();
+        match in_first_band(distance, self.width, self.duty_cycle) {
+            true => self.colour1,
+            false => self.colour2,
         }
     }
 }
@@ -50,4 +77,16 @@ mod tests {
             colour2
         );
     }
+
+    #[test]
+    fn ring_about_other_axis() {
+        let colour1 = Colour::new(1.0, 1.0, 1.0);
+        let colour2 = Colour::new(0.0, 0.0, 0.0);
+        let ring = Ring::new(colour1, colour2, Transform::default())
+            .with_axis(Axis::Z)
+            .with_width(0.5);
+        assert_eq!(ring.colour_at(Point::new(0.0, 0.4, 7.0)), colour1);
+        assert_eq!(ring.colour_at(Point::new(0.0, 0.6, 7.0)), colour2);
+        assert_eq!(ring.colour_at(Point::new(1.1, 0.0, 0.0)), colour1);
+    }
 }
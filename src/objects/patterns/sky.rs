@@ -0,0 +1,194 @@
+use crate::collections::{Colour, Point, Vector};
+use crate::objects::{Light, Pattern, Transform, Transformable};
+
+// Daylight sky after Preetham, Shirley and Smits (1999), meant to be sampled
+// with ray directions as a background, with y up. Turbidity is the haziness of
+// the air, from about 2 (very clear) to 10 (hazy). The brightness of each
+// direction is given relative to the zenith, scaled by brightness.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sky {
+    pub sun_direction: Vector,
+    pub turbidity: f64,
+    pub brightness: f64,
+    pub transform: Transform,
+}
+
+// coefficients of the Perez distribution, each linear in turbidity
+type PerezCoefficients = [[f64; 2]; 5];
+
+const LUMINANCE_COEFFICIENTS: PerezCoefficients = [
+    [0.1787, -1.4630],
+    [-0.3554, 0.4275],
+    [-0.0227, 5.3251],
+    [0.1206, -2.5771],
+    [-0.0670, 0.3703],
+];
+const X_COEFFICIENTS: PerezCoefficients = [
+    [-0.0193, -0.2592],
+    [-0.0665, 0.0008],
+    [-0.0004, 0.2125],
+    [-0.0641, -0.8989],
+    [-0.0033, 0.0452],
+];
+const Y_COEFFICIENTS: PerezCoefficients = [
+    [-0.0167, -0.2608],
+    [-0.0950, 0.0092],
+    [-0.0079, 0.2102],
+    [-0.0441, -1.6537],
+    [-0.0109, 0.0529],
+];
+
+// zenith chromaticity, as cubics in the sun's zenith angle for each power of
+// turbidity (squared, linear and constant)
+const ZENITH_X: [[f64; 4]; 3] = [
+    [0.00166, -0.00375, 0.00209, 0.0],
+    [-0.02903, 0.06377, -0.03202, 0.00394],
+    [0.11693, -0.21196, 0.06052, 0.25886],
+];
+const ZENITH_Y: [[f64; 4]; 3] = [
+    [0.00275, -0.00610, 0.00317, 0.0],
+    [-0.04214, 0.08970, -0.04153, 0.00516],
+    [0.15346, -0.26756, 0.06670, 0.26688],
+];
+
+impl Sky {
+    // far enough for the sun's light to arrive in parallel across a scene
+    const SUN_DISTANCE: f64 = 1.0e6;
+
+    pub fn new(sun_direction: Vector, turbidity: f64, transform: Transform) -> Sky {
+        Sky {
+            sun_direction,
+            turbidity,
+            brightness: 1.0,
+            transform,
+        }
+    }
+
+    pub fn with_brightness(mut self, brightness: f64) -> Sky {
+        self.brightness = brightness;
+        self
+    }
+
+    // A far-off light standing in for the sun, tinted like the sky around it
+    // and scaled to the given luminance. It sits below the horizon (and so is
+    // blocked by any ground) once the sun has set.
+    pub fn sun_light(&self, luminance: f64) -> Light {
+        let direction = self.sun_direction.normalise();
+        let world_direction = (Point::zero() + direction).transform(&self.transform)
+            - Point::zero().transform(&self.transform);
+        let tint = self.sky_colour(direction);
+        let intensity = match tint.luminance() {
+            tint_luminance if tint_luminance > 0.0 => tint * (luminance / tint_luminance),
+            _ => Colour::new(luminance, luminance, luminance),
+        };
+        Light::new(
+            Point::zero() + world_direction.normalise() * Sky::SUN_DISTANCE,
+            intensity,
+        )
+        .with_name("sun")
+    }
+
+    fn sky_colour(&self, direction: Vector) -> Colour {
+        let sun = self.sun_direction.normalise();
+        let sun_zenith = sun.y.clamp(-1.0, 1.0).acos();
+        // directions below the horizon take the colour of the horizon
+        let cos_zenith = direction.y.max(0.001);
+        let cos_gamma = direction.dot(sun).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+
+        let relative = |coefficients: &PerezCoefficients| {
+            let [a, b, c, d, e] =
+                coefficients.map(|[slope, intercept]| slope * self.turbidity + intercept);
+            let perez = |cos_zenith: f64, gamma: f64, cos_gamma: f64| {
+                (1.0 + a * (b / cos_zenith).exp())
+                    * (1.0 + c * (d * gamma).exp() + e * cos_gamma.powi(2))
+            };
+            perez(cos_zenith, gamma, cos_gamma) / perez(1.0, sun_zenith, sun_zenith.cos())
+        };
+        let zenith = |cubics: &[[f64; 4]; 3]| {
+            let turbidity_powers = [self.turbidity.powi(2), self.turbidity, 1.0];
+            cubics
+                .iter()
+                .zip(turbidity_powers)
+                .map(|([c3, c2, c1, c0], power)| {
+                    power * (((c3 * sun_zenith + c2) * sun_zenith + c1) * sun_zenith + c0)
+                })
+                .sum::<f64>()
+        };
+
+        let luminance = self.brightness * relative(&LUMINANCE_COEFFICIENTS);
+        let x = zenith(&ZENITH_X) * relative(&X_COEFFICIENTS);
+        let y = zenith(&ZENITH_Y) * relative(&Y_COEFFICIENTS);
+        xyy_to_rgb(x, y, luminance)
+    }
+}
+
+// CIE xyY to linear sRGB, dropping colours outside the sRGB gamut to zero
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Colour {
+    if y <= 0.0 {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    Colour::new(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+    )
+}
+
+impl Pattern for Sky {
+    fn frame_transformation(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn local_colour_at(&self, pattern_point: Point) -> Colour {
+        self.sky_colour((pattern_point - Point::zero()).normalise())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::approx_eq;
+
+    fn midday(turbidity: f64) -> Sky {
+        Sky::new(Vector::new(0.3, 1.0, 0.2), turbidity, Transform::default())
+    }
+
+    #[test]
+    fn clear_sky_is_blue() {
+        let zenith = midday(2.5).colour_at(Point::new(0.0, 1.0, 0.0));
+        assert!(zenith.blue > zenith.green && zenith.green > zenith.red);
+        // the sky is scaled so that the zenith has unit luminance
+        assert!((zenith.luminance() - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn sky_brightens_towards_sun() {
+        let sky = midday(3.0);
+        let sun = sky.sun_direction.normalise();
+        let near_sun = sky.colour_at(Point::zero() + sun);
+        let away_from_sun = sky.colour_at(Point::new(-sun.x, sun.y, -sun.z));
+        assert!(near_sun.luminance() > away_from_sun.luminance());
+    }
+
+    #[test]
+    fn haze_whitens_sky() {
+        let saturation = |colour: Colour| (colour.blue - colour.red) / colour.blue;
+        let up = Point::new(0.0, 1.0, 0.0);
+        assert!(saturation(midday(2.0).colour_at(up)) > saturation(midday(8.0).colour_at(up)));
+    }
+
+    #[test]
+    fn sun_light_lies_along_sun_direction() {
+        let sky = midday(3.0);
+        let sun = sky.sun_light(2.0);
+        let direction = (sun.position - Point::zero()).normalise();
+        let expected = sky.sun_direction.normalise();
+        approx_eq!(direction.x, expected.x);
+        approx_eq!(direction.y, expected.y);
+        approx_eq!(direction.z, expected.z);
+        approx_eq!(sun.intensity.luminance(), 2.0);
+    }
+}
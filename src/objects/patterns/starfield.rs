@@ -0,0 +1,181 @@
+use crate::collections::{Colour, Point, Vector};
+use crate::objects::{Pattern, Transform};
+use crate::utils::splitmix64;
+
+// Procedural star background, meant to be sampled with points on the unit
+// sphere (i.e. ray directions). Space is split into a grid of cells and each
+// cell is hashed with the seed to decide whether it holds a star, where in the
+// cell the star sits and how bright it is, so the same seed always produces
+// the same sky.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Starfield {
+    pub seed: u64,
+    // probability that any given grid cell contains a star
+    pub density: f64,
+    // brightness of the brightest possible star
+    pub brightness: f64,
+    // power-law exponent for star brightness; higher values give fewer bright
+    // stars relative to dim ones
+    pub brightness_exponent: f64,
+    // number of grid cells per unit length on the sphere
+    pub resolution: f64,
+    // radius of a star as a fraction of a grid cell
+    pub star_radius: f64,
+    pub band: Option<MilkyWay>,
+    pub transform: Transform,
+}
+
+// Diffuse glowing band across the sky which also raises star density near it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MilkyWay {
+    pub normal: Vector,
+    pub width: f64,
+    pub colour: Colour,
+}
+
+impl Starfield {
+    pub fn new(seed: u64, density: f64, transform: Transform) -> Starfield {
+        Starfield {
+            seed,
+            density,
+            brightness: 1.0,
+            brightness_exponent: 3.0,
+            resolution: 200.0,
+            star_radius: 0.25,
+            band: None,
+            transform,
+        }
+    }
+
+    pub fn with_band(mut self, band: MilkyWay) -> Starfield {
+        self.band = Some(band);
+        self
+    }
+
+    fn hash_cell(&self, cell: [i64; 3], stream: u64) -> f64 {
+        let mut state = self.seed ^ stream.wrapping_mul(0xd1b5_4a32_d192_ed03);
+        for coordinate in cell {
+            state = splitmix64(state ^ coordinate as u64);
+        }
+        // top 53 bits give a uniformly distributed float in [0, 1)
+        (splitmix64(state) >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    fn band_weight(&self, direction: Vector) -> f64 {
+        match &self.band {
+            Some(band) => {
+                let elevation = direction.dot(band.normal.normalise());
+                (-(elevation / band.width).powi(2)).exp()
+            }
+            None => 0.0,
+        }
+    }
+}
+
+impl MilkyWay {
+    pub fn new(normal: Vector, width: f64, colour: Colour) -> MilkyWay {
+        MilkyWay {
+            normal,
+            width,
+            colour,
+        }
+    }
+}
+
+impl Pattern for Starfield {
+    fn frame_transformation(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn local_colour_at(&self, pattern_point: Point) -> Colour {
+        let direction = (pattern_point - Point::zero()).normalise();
+        let band_weight = self.band_weight(direction);
+        let density = f64::min(self.density * (1.0 + 2.0 * band_weight), 1.0);
+
+        let grid_point = [
+            direction.x * self.resolution,
+            direction.y * self.resolution,
+            direction.z * self.resolution,
+        ];
+        let cell = grid_point.map(|coordinate| coordinate.floor() as i64);
+
+        let mut colour = Colour::new(0.0, 0.0, 0.0);
+        if self.hash_cell(cell, 0) < density {
+            let centre = [1, 2, 3].map(|stream| self.hash_cell(cell, stream));
+            let distance = (0..3)
+                .map(|axis| (grid_point[axis] - cell[axis] as f64 - centre[axis]).powi(2))
+                .sum::<f64>()
+                .sqrt();
+            if distance < self.star_radius {
+                let magnitude = self.hash_cell(cell, 4).powf(self.brightness_exponent);
+                let falloff = 1.0 - distance / self.star_radius;
+                let intensity = self.brightness * magnitude * falloff;
+                colour = Colour::new(intensity, intensity, intensity);
+            }
+        }
+
+        match &self.band {
+            Some(band) => colour + band.colour * band_weight,
+            None => colour,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_directions() -> Vec<Point> {
+        let mut points = vec![];
+        for i in 0..50 {
+            for j in 0..50 {
+                let theta = i as f64 / 50.0 * std::f64::consts::PI;
+                let phi = j as f64 / 50.0 * std::f64::consts::TAU;
+                points.push(Point::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                ));
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn starfield_is_deterministic_for_seed() {
+        let starfield1 = Starfield::new(42, 0.5, Transform::default());
+        let starfield2 = Starfield::new(42, 0.5, Transform::default());
+        for point in sample_directions() {
+            assert_eq!(starfield1.colour_at(point), starfield2.colour_at(point));
+        }
+    }
+
+    #[test]
+    fn starfield_differs_between_seeds() {
+        let starfield1 = Starfield::new(1, 0.5, Transform::default());
+        let starfield2 = Starfield::new(2, 0.5, Transform::default());
+        assert!(sample_directions()
+            .into_iter()
+            .any(|point| starfield1.colour_at(point) != starfield2.colour_at(point)));
+    }
+
+    #[test]
+    fn empty_starfield_is_black() {
+        let starfield = Starfield::new(7, 0.0, Transform::default());
+        for point in sample_directions() {
+            assert_eq!(starfield.colour_at(point), Colour::new(0.0, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn milky_way_band_brightens_its_plane() {
+        let band = MilkyWay::new(Vector::new(0.0, 1.0, 0.0), 0.1, Colour::new(0.2, 0.2, 0.3));
+        let starfield = Starfield::new(7, 0.0, Transform::default()).with_band(band);
+        assert_eq!(
+            starfield.colour_at(Point::new(1.0, 0.0, 0.0)),
+            Colour::new(0.2, 0.2, 0.3)
+        );
+        let pole = starfield.colour_at(Point::new(0.0, 1.0, 0.0));
+        assert!(pole.red < 1e-6 && pole.blue < 1e-6);
+    }
+}
@@ -1,5 +1,5 @@
-use crate::collections::Point;
-use crate::objects::{Pattern, Transform};
+use crate::collections::{Point, Vector};
+use crate::objects::{filtered_first_band, in_first_band, mix, Axis, Pattern, Transform};
 use crate::prelude::Colour;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -7,6 +7,11 @@ pub struct Stripe {
     pub colour1: Colour,
     pub colour2: Colour,
     pub transform: Transform,
+    // stripes change colour along the axis
+    pub axis: Axis,
+    pub width: f64,
+    // fraction of each repeat (two widths) given to colour1
+    pub duty_cycle: f64,
 }
 
 impl Stripe {
@@ -15,6 +20,33 @@ impl Stripe {
             colour1,
             colour2,
             transform,
+            axis: Axis::X,
+            width: 1.0,
+            duty_cycle: 0.5,
+        }
+    }
+
+    pub fn with_axis(mut self, axis: Axis) -> Stripe {
+        self.axis = axis;
+        self
+    }
+
+    pub fn with_width(mut self, width: f64) -> Stripe {
+        self.width = width;
+        self
+    }
+
+    pub fn with_duty_cycle(mut self, duty_cycle: f64) -> Stripe {
+        self.duty_cycle = duty_cycle;
+        self
+    }
+
+    // the component along the axis, of a point or footprint offset
+    fn coordinate(&self, [x, y, z]: [f64; 3]) -> f64 {
+        match self.axis {
+            Axis::X => x,
+            Axis::Y => y,
+            Axis::Z => z,
         }
     }
 }
@@ -25,12 +57,26 @@ impl Pattern for Stripe {
     }
 
     fn local_colour_at(&self, pattern_point: Point) -> Colour {
-        match (pattern_point.x.floor() as i32).rem_euclid(2) {
-            x if x == 0 => self.colour1,
-            x if x == 1 => self.colour2,
-            _ => panic!(),
+        let Point { x, y, z } = pattern_point;
+        match in_first_band(self.coordinate([x, y, z]), self.width, self.duty_cycle) {
+            true => self.colour1,
+            false => self.colour2,
         }
     }
+
+    // box filtered along the axis over the footprint's extent on it
+    fn local_colour_filtered(&self, pattern_point: Point, [dx, dy]: [Vector; 2]) -> Colour {
+        let filter_width =
+            self.coordinate([dx.x, dx.y, dx.z]).abs() + self.coordinate([dy.x, dy.y, dy.z]).abs();
+        let Point { x, y, z } = pattern_point;
+        let first = filtered_first_band(
+            self.coordinate([x, y, z]),
+            self.width,
+            self.duty_cycle,
+            filter_width,
+        );
+        mix(self.colour1, self.colour2, first)
+    }
 }
 
 #[cfg(test)]
@@ -46,6 +92,9 @@ mod tests {
             colour1,
             colour2,
             transform: Transform::default(),
+            axis: Axis::X,
+            width: 1.0,
+            duty_cycle: 0.5,
         };
         assert_eq!(stripe_pattern, resulting_stripe_pattern);
     }
@@ -91,4 +140,33 @@ mod tests {
             colour1
         );
     }
+
+    #[test]
+    fn stripe_with_axis_width_and_duty_cycle() {
+        let colour1 = Colour::new(1.0, 1.0, 1.0);
+        let colour2 = Colour::new(0.0, 0.0, 0.0);
+        let stripe_pattern = Stripe::new(colour1, colour2, Transform::default())
+            .with_axis(Axis::Z)
+            .with_width(2.0)
+            .with_duty_cycle(0.25);
+        // repeats every 4 units along z, of which the first 1 is colour1
+        assert_eq!(stripe_pattern.colour_at(Point::new(5.0, 0.0, 0.5)), colour1);
+        assert_eq!(stripe_pattern.colour_at(Point::new(0.0, 0.0, 1.5)), colour2);
+        assert_eq!(stripe_pattern.colour_at(Point::new(0.0, 0.0, 3.5)), colour2);
+        assert_eq!(
+            stripe_pattern.colour_at(Point::new(0.0, 0.0, -3.5)),
+            colour1
+        );
+
+        // filtering over a whole repeat gives each colour its share, and
+        // footprints across the stripes leave them sharp
+        let point = Point::new(0.0, 0.0, 0.5);
+        let along = [Vector::new(0.0, 0.0, 4.0), Vector::new(0.0, 0.0, 0.0)];
+        assert_eq!(
+            stripe_pattern.colour_at_filtered(point, along),
+            Colour::new(0.25, 0.25, 0.25)
+        );
+        let across = [Vector::new(4.0, 0.0, 0.0), Vector::new(0.0, 4.0, 0.0)];
+        assert_eq!(stripe_pattern.colour_at_filtered(point, across), colour1);
+    }
 }
@@ -0,0 +1,124 @@
+use crate::collections::{Colour, Point, Vector};
+use crate::objects::{filtered_even_squares, mix, Pattern, Transform, UvMapping};
+
+// A checker drawn on the (u, v) texture rather than through space, so faces
+// lying on a checker boundary (such as a floor at y = 0) show a clean pattern
+// instead of speckling between the two colours.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UvChecker {
+    pub colour1: Colour,
+    pub colour2: Colour,
+    // squares across the texture in u and in v
+    pub squares: [f64; 2],
+    pub mapping: UvMapping,
+    pub transform: Transform,
+}
+
+impl UvChecker {
+    pub fn new(
+        colour1: Colour,
+        colour2: Colour,
+        squares: [f64; 2],
+        transform: Transform,
+    ) -> UvChecker {
+        UvChecker {
+            colour1,
+            colour2,
+            squares,
+            mapping: UvMapping::default(),
+            transform,
+        }
+    }
+
+    pub fn with_mapping(mut self, mapping: UvMapping) -> UvChecker {
+        self.mapping = mapping;
+        self
+    }
+
+    fn colour_at_uv(&self, (u, v): (f64, f64)) -> Colour {
+        let [u_squares, v_squares] = self.squares;
+        let square = (u * u_squares).floor() as i64 + (v * v_squares).floor() as i64;
+        match square.rem_euclid(2) {
+            0 => self.colour1,
+            _ => self.colour2,
+        }
+    }
+
+    // box filtered in u and v over the footprint's extent on the texture
+    fn filtered_at_uv(
+        &self,
+        (u, v): (f64, f64),
+        [(du_x, dv_x), (du_y, dv_y)]: [(f64, f64); 2],
+    ) -> Colour {
+        let [u_squares, v_squares] = self.squares;
+        let even = filtered_even_squares(
+            &[u * u_squares, v * v_squares],
+            &[
+                (du_x.abs() + du_y.abs()) * u_squares,
+                (dv_x.abs() + dv_y.abs()) * v_squares,
+            ],
+        );
+        mix(self.colour1, self.colour2, even)
+    }
+}
+
+impl Pattern for UvChecker {
+    fn frame_transformation(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn local_colour_at(&self, pattern_point: Point) -> Colour {
+        self.colour_at_uv(self.mapping.uv(pattern_point))
+    }
+
+    fn local_colour_filtered(&self, pattern_point: Point, footprint: [Vector; 2]) -> Colour {
+        self.filtered_at_uv(
+            self.mapping.uv(pattern_point),
+            self.mapping.uv_footprint(pattern_point, footprint),
+        )
+    }
+
+    fn colour_at_texture(
+        &self,
+        texture_coordinates: (f64, f64),
+        texture_footprint: Option<[(f64, f64); 2]>,
+    ) -> Option<Colour> {
+        Some(match texture_footprint {
+            Some(footprint) => self.filtered_at_uv(texture_coordinates, footprint),
+            None => self.colour_at_uv(texture_coordinates),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checker_in_uv_space() {
+        let white = Colour::new(1.0, 1.0, 1.0);
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let checker = UvChecker::new(white, black, [2.0, 2.0], Transform::default());
+        assert_eq!(checker.colour_at(Point::new(0.25, 0.0, 0.25)), white);
+        assert_eq!(checker.colour_at(Point::new(0.75, 0.0, 0.25)), black);
+        assert_eq!(checker.colour_at(Point::new(0.75, 0.0, 0.75)), white);
+        // a floor point fractionally below y = 0 is unaffected
+        assert_eq!(checker.colour_at(Point::new(0.25, -1e-12, 0.25)), white);
+
+        let sphere_checker = checker.clone().with_mapping(UvMapping::Spherical);
+        assert_eq!(sphere_checker.colour_at(Point::new(0.0, 0.0, -1.0)), black);
+        assert_eq!(sphere_checker.colour_at(Point::new(0.0, -0.5, -1.0)), white);
+
+        // a footprint wrapping across the edge of the texture spans only a
+        // sliver of it, rather than blurring across the whole texture
+        let footprint = [Vector::new(0.1, 0.0, 0.0), Vector::new(0.0, 0.0, 0.0)];
+        let point = Point::new(0.95, 0.0, 0.25);
+        assert_eq!(checker.colour_at_filtered(point, footprint), black);
+        let footprint = [Vector::new(0.5, 0.0, 0.0), Vector::new(0.0, 0.0, 0.0)];
+        let point = Point::new(0.5, 0.0, 0.25);
+        assert_eq!(
+            checker.colour_at_filtered(point, footprint),
+            Colour::new(0.5, 0.5, 0.5)
+        );
+    }
+}
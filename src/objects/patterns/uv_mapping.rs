@@ -0,0 +1,78 @@
+use std::f64::consts::PI;
+
+use crate::collections::{Point, Vector};
+
+// Ways of unwrapping a point in pattern space onto a flat (u, v) texture, with
+// both coordinates between 0 and 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UvMapping {
+    // the xz plane, repeating every unit
+    #[default]
+    Planar,
+    // latitude and longitude on the unit sphere about the origin
+    Spherical,
+    // around the y axis, repeating every unit up it
+    Cylindrical,
+}
+
+impl UvMapping {
+    pub fn uv(&self, point: Point) -> (f64, f64) {
+        match self {
+            UvMapping::Planar => (point.x.rem_euclid(1.0), point.z.rem_euclid(1.0)),
+            UvMapping::Spherical => {
+                let radius = (point.x * point.x + point.y * point.y + point.z * point.z).sqrt();
+                let phi = (point.y / radius).clamp(-1.0, 1.0).acos();
+                (UvMapping::azimuth(point), 1.0 - phi / PI)
+            }
+            UvMapping::Cylindrical => (UvMapping::azimuth(point), point.y.rem_euclid(1.0)),
+        }
+    }
+
+    // the changes in (u, v) from the point to each end of a footprint, going
+    // the short way round where the texture wraps
+    pub fn uv_footprint(&self, point: Point, footprint: [Vector; 2]) -> [(f64, f64); 2] {
+        let (u, v) = self.uv(point);
+        footprint.map(|offset| {
+            let (offset_u, offset_v) = self.uv(point + offset);
+            let wrap = |change: f64| change - change.round();
+            (wrap(offset_u - u), wrap(offset_v - v))
+        })
+    }
+
+    // how far round the y axis, from 0 to 1
+    fn azimuth(point: Point) -> f64 {
+        let theta = point.x.atan2(point.z);
+        1.0 - (theta / (2.0 * PI) + 0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::approx_eq;
+
+    #[test]
+    fn map_points_to_uv() {
+        let cases = [
+            (
+                UvMapping::Planar,
+                Point::new(0.25, 0.5, -0.75),
+                (0.25, 0.25),
+            ),
+            (UvMapping::Planar, Point::new(-1.75, 0.0, 1.5), (0.25, 0.5)),
+            (UvMapping::Spherical, Point::new(0.0, 0.0, -1.0), (0.0, 0.5)),
+            (UvMapping::Spherical, Point::new(1.0, 0.0, 0.0), (0.25, 0.5)),
+            (UvMapping::Spherical, Point::new(0.0, 1.0, 0.0), (0.5, 1.0)),
+            (
+                UvMapping::Cylindrical,
+                Point::new(0.0, -0.25, 1.0),
+                (0.5, 0.75),
+            ),
+        ];
+        for (mapping, point, (u, v)) in cases {
+            let uv = mapping.uv(point);
+            approx_eq!(uv.0, u);
+            approx_eq!(uv.1, v);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::collections::Colour;
+use crate::objects::Material;
+use crate::utils::{Buildable, ConsumingBuilder};
+
+// Ready-made materials for common looks. Each returns a fresh material that can
+// be adjusted further through its public fields.
+
+pub fn glass() -> Material {
+    Material::builder()
+        .set_colour(Colour::new(0.0, 0.0, 0.0))
+        .set_ambient(0.0)
+        .set_diffuse(0.1)
+        .set_specular(1.0)
+        .set_shininess(300.0)
+        .set_reflectance(0.9)
+        .set_transparency(0.9)
+        .set_refractive_index(1.5)
+        .build()
+}
+
+pub fn mirror() -> Material {
+    Material::builder()
+        .set_colour(Colour::new(0.0, 0.0, 0.0))
+        .set_ambient(0.0)
+        .set_diffuse(0.0)
+        .set_specular(1.0)
+        .set_shininess(300.0)
+        .set_reflectance(1.0)
+        .build()
+}
+
+pub fn matte(colour: Colour) -> Material {
+    Material::builder()
+        .set_colour(colour)
+        .set_specular(0.0)
+        .build()
+}
+
+pub fn metal(colour: Colour) -> Material {
+    Material::builder()
+        .set_colour(colour)
+        .set_ambient(0.1)
+        .set_diffuse(0.3)
+        .set_specular(0.8)
+        .set_shininess(100.0)
+        .set_reflectance(0.6)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Pattern, Solid};
+
+    #[test]
+    fn glass_is_transparent_and_refracts() {
+        let material = glass();
+        assert_eq!(material.transparency, 0.9);
+        assert_eq!(material.refractive_index, 1.5);
+    }
+
+    #[test]
+    fn matte_has_no_highlights() {
+        let material = matte(Colour::new(0.2, 0.4, 0.6));
+        assert_eq!(material.specular, 0.0);
+        assert_eq!(
+            material.pattern.as_ref(),
+            &Solid::new(Colour::new(0.2, 0.4, 0.6)) as &dyn Pattern
+        );
+    }
+}
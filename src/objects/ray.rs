@@ -1,6 +1,7 @@
 use crate::collections::{Point, Vector};
 
 use super::{Transform, Transformable};
+use crate::utils::EPSILON;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Ray {
@@ -8,14 +9,105 @@ pub struct Ray {
     pub direction: Vector,
 }
 
+// Rays need not have a direction of unit length, as rays carried into an
+// object's space keep their t so that hits can be compared across shapes. t
+// is then a distance only for normalised rays; shading converts between the
+// two with distance_at and t_at wherever it measures along a ray.
 impl Ray {
+    // the direction is kept as given
     pub fn new(origin: Point, direction: Vector) -> Ray {
         Ray { origin, direction }
     }
 
+    // a ray whose direction is scaled to unit length, so that t is distance
+    pub fn normalised(origin: Point, direction: Vector) -> Ray {
+        Ray::new(origin, direction.normalise())
+    }
+
+    // as normalised, for directions already known to be of unit length; this
+    // is checked only in debug builds
+    pub fn normalised_unchecked(origin: Point, direction: Vector) -> Ray {
+        debug_assert!(
+            (direction.magnitude() - 1.0).abs() < EPSILON,
+            "direction {direction:?} is not normalised"
+        );
+        Ray::new(origin, direction)
+    }
+
+    pub fn origin(&self) -> Point {
+        self.origin
+    }
+
+    pub fn direction(&self) -> Vector {
+        self.direction
+    }
+
+    pub fn is_normalised(&self) -> bool {
+        (self.direction.magnitude() - 1.0).abs() < EPSILON
+    }
+
     pub fn position(&self, t: f64) -> Point {
         self.origin + t * self.direction
     }
+
+    // the same as position
+    pub fn at(&self, t: f64) -> Point {
+        self.position(t)
+    }
+
+    // distance travelled from the origin by t
+    pub fn distance_at(&self, t: f64) -> f64 {
+        t * self.direction.magnitude()
+    }
+
+    // the t at which the ray has travelled the distance
+    pub fn t_at(&self, distance: f64) -> f64 {
+        distance / self.direction.magnitude()
+    }
+}
+
+// The rays through the neighbouring pixels (one to the right and one below),
+// carried alongside a camera ray and its mirror reflections so that the area
+// of a surface seen through a pixel (its footprint) can be estimated, and
+// patterns averaged over it instead of aliasing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayDifferentials {
+    pub dx: Ray,
+    pub dy: Ray,
+}
+
+impl RayDifferentials {
+    pub fn new(dx: Ray, dy: Ray) -> RayDifferentials {
+        RayDifferentials { dx, dy }
+    }
+
+    // where each neighbouring ray meets the plane touching the surface at the
+    // target; None if either runs along the plane
+    fn plane_hits(&self, target: Point, normal: Vector) -> Option<[Point; 2]> {
+        let hit = |ray: Ray| {
+            let facing = normal.dot(ray.direction);
+            (facing.abs() > EPSILON).then(|| ray.position(normal.dot(target - ray.origin) / facing))
+        };
+        Some([hit(self.dx)?, hit(self.dy)?])
+    }
+
+    // offsets from the target to where the neighbouring rays meet the surface,
+    // taken to be flat about the target
+    pub fn footprint(&self, target: Point, normal: Vector) -> Option<[Vector; 2]> {
+        let [dx, dy] = self.plane_hits(target, normal)?;
+        Some([dx - target, dy - target])
+    }
+
+    // the differentials of the ray mirrored about the normal at the target;
+    // the surface is again taken to be flat, so curved mirrors are filtered
+    // less than they should be
+    pub fn reflect(&self, target: Point, normal: Vector) -> Option<RayDifferentials> {
+        let [dx, dy] = self.plane_hits(target, normal)?;
+        Some(RayDifferentials::new(
+            Ray::new(dx, self.dx.direction.reflect(normal)),
+            Ray::new(dy, self.dy.direction.reflect(normal)),
+        ))
+    }
 }
 
 impl Transformable for Ray {
@@ -51,4 +143,49 @@ mod tests {
         assert_eq!(ray.position(-1.0), Point::new(1.0, 3.0, 4.0));
         assert_eq!(ray.position(2.5), Point::new(4.5, 3.0, 4.0));
     }
+
+    #[test]
+    fn normalised_rays_measure_distance_in_t() {
+        let origin = Point::new(1.0, 0.0, 0.0);
+        let ray = Ray::normalised(origin, Vector::new(0.0, 3.0, 4.0));
+        assert!(ray.is_normalised());
+        assert_eq!(ray.direction(), Vector::new(0.0, 0.6, 0.8));
+        assert_eq!(ray.at(5.0), Point::new(1.0, 3.0, 4.0));
+        assert_eq!(ray.distance_at(5.0), 5.0);
+
+        let unnormalised = Ray::new(origin, Vector::new(0.0, 3.0, 4.0));
+        assert!(!unnormalised.is_normalised());
+        assert_eq!(unnormalised.distance_at(1.0), 5.0);
+        assert_eq!(unnormalised.t_at(10.0), 2.0);
+        assert_eq!(unnormalised.at(unnormalised.t_at(5.0)), ray.at(5.0));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn unchecked_rays_must_be_normalised_in_debug_builds() {
+        Ray::normalised_unchecked(Point::zero(), Vector::new(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn differentials_on_surface() {
+        let differentials = RayDifferentials::new(
+            Ray::new(Point::new(0.5, 0.0, 0.0), Vector::new(0.0, -1.0, 1.0)),
+            Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, -1.0, 1.5)),
+        );
+        let target = Point::new(0.0, -2.0, 2.0);
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        assert_eq!(
+            differentials.footprint(target, normal),
+            Some([Vector::new(0.5, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0)])
+        );
+
+        let reflected = differentials.reflect(target, normal).unwrap();
+        assert_eq!(
+            reflected.dx,
+            Ray::new(Point::new(0.5, -2.0, 2.0), Vector::new(0.0, 1.0, 1.0))
+        );
+        let along = Vector::new(0.0, 0.0, 1.0);
+        assert_eq!(differentials.footprint(target, along.cross(normal)), None);
+    }
 }
@@ -0,0 +1,419 @@
+use std::sync::Arc;
+
+use crate::collections::{Point, Vector};
+use crate::objects::*;
+use crate::utils::floats::EPSILON;
+use crate::utils::{BuildError, Buildable, ConsumingBuilder};
+
+// Box spanning two opposite corners in object space, so that boxes of any
+// proportions can be placed without scaling a unit Cube by hand.
+#[derive(Debug)]
+pub struct AxisAlignedBox {
+    minimum: Point,
+    maximum: Point,
+    frame_transformation: Transform,
+    material: Arc<Material>,
+    name: Option<String>,
+    bounds: Bounds,
+}
+
+impl AxisAlignedBox {
+    pub fn minimum(&self) -> Point {
+        self.minimum
+    }
+
+    pub fn maximum(&self) -> Point {
+        self.maximum
+    }
+
+    // point of the box spanning the corners nearest to a point; points
+    // inside are nearest the face they are closest to
+    pub(crate) fn closest_point(point: Point, minimum: Point, maximum: Point) -> Point {
+        let axes = [
+            [minimum.x, maximum.x],
+            [minimum.y, maximum.y],
+            [minimum.z, maximum.z],
+        ];
+        let mut closest = [point.x, point.y, point.z];
+        let inside = closest
+            .iter()
+            .zip(axes)
+            .all(|(coordinate, [min, max])| (min..=max).contains(coordinate));
+        if inside {
+            let (axis, face) = (0..3)
+                .flat_map(|axis| axes[axis].map(|face| (axis, face)))
+                .min_by(|&(a, f), &(b, g)| {
+                    (closest[a] - f).abs().total_cmp(&(closest[b] - g).abs())
+                })
+                .expect("a box has faces");
+            closest[axis] = face;
+        } else {
+            for (coordinate, [min, max]) in closest.iter_mut().zip(axes) {
+                *coordinate = coordinate.clamp(min, max);
+            }
+        }
+        Point::new(closest[0], closest[1], closest[2])
+    }
+
+    // the minimum and maximum corners of the box spanning two corners given
+    // in any order
+    pub(crate) fn span(corner1: Point, corner2: Point) -> (Point, Point) {
+        let minimum = Point::new(
+            f64::min(corner1.x, corner2.x),
+            f64::min(corner1.y, corner2.y),
+            f64::min(corner1.z, corner2.z),
+        );
+        let maximum = Point::new(
+            f64::max(corner1.x, corner2.x),
+            f64::max(corner1.y, corner2.y),
+            f64::max(corner1.z, corner2.z),
+        );
+        (minimum, maximum)
+    }
+
+    // where a ray enters and leaves the box spanning the corners, for any
+    // box shape (such as Cube) in its own space
+    pub(crate) fn intersect_span(
+        local_ray: &Ray,
+        minimum: Point,
+        maximum: Point,
+        intersections: &mut Vec<Coordinates>,
+    ) {
+        let (origin, direction) = (local_ray.origin, local_ray.direction);
+        let (xtmin, xtmax) =
+            AxisAlignedBox::check_axis(origin.x, direction.x, minimum.x, maximum.x);
+        let (ytmin, ytmax) =
+            AxisAlignedBox::check_axis(origin.y, direction.y, minimum.y, maximum.y);
+        let (ztmin, ztmax) =
+            AxisAlignedBox::check_axis(origin.z, direction.z, minimum.z, maximum.z);
+
+        let tmin = [xtmin, ytmin, ztmin].into_iter().reduce(f64::max).unwrap();
+        let tmax = [xtmax, ytmax, ztmax].into_iter().reduce(f64::min).unwrap();
+
+        if tmin <= tmax {
+            intersections.extend([tmin, tmax].map(|t| Coordinates::new(t, None)));
+        }
+    }
+
+    fn check_axis(origin: f64, direction: f64, minimum: f64, maximum: f64) -> (f64, f64) {
+        if direction.abs() < EPSILON {
+            // a ray running along the slab is within it everywhere or
+            // nowhere; scaling the distances to the faces by infinity instead
+            // gives NaN for a ray lying in a face, as it may on a flat box
+            return match (minimum..=maximum).contains(&origin) {
+                true => (f64::NEG_INFINITY, f64::INFINITY),
+                false => (f64::INFINITY, f64::NEG_INFINITY),
+            };
+        }
+
+        let tmin = (minimum - origin) / direction;
+        let tmax = (maximum - origin) / direction;
+        if tmin > tmax {
+            (tmax, tmin)
+        } else {
+            (tmin, tmax)
+        }
+    }
+}
+
+impl PrimitiveShape for AxisAlignedBox {
+    fn frame_transformation(&self) -> &Transform {
+        &self.frame_transformation
+    }
+
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.bounds = Bounds::new(
+            BoundingBox::from_anchors(vec![self.minimum, self.maximum])
+                .transform(&self.frame_transformation),
+        );
+        true
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn has_interior(&self) -> bool {
+        true
+    }
+
+    fn local_contains_point(&self, local_point: Point) -> Option<bool> {
+        let axes = [
+            (local_point.x, self.minimum.x, self.maximum.x),
+            (local_point.y, self.minimum.y, self.maximum.y),
+            (local_point.z, self.minimum.z, self.maximum.z),
+        ];
+        Some(
+            axes.into_iter().all(|(coordinate, min, max)| {
+                (min - EPSILON..=max + EPSILON).contains(&coordinate)
+            }),
+        )
+    }
+
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        Some(AxisAlignedBox::closest_point(
+            local_point,
+            self.minimum,
+            self.maximum,
+        ))
+    }
+
+    // normal of whichever face the point lies closest to
+    fn local_normal_at(&self, local_point: Point, _: Option<(f64, f64)>) -> Vector {
+        let faces = [
+            (local_point.x - self.minimum.x, Vector::new(-1.0, 0.0, 0.0)),
+            (self.maximum.x - local_point.x, Vector::new(1.0, 0.0, 0.0)),
+            (local_point.y - self.minimum.y, Vector::new(0.0, -1.0, 0.0)),
+            (self.maximum.y - local_point.y, Vector::new(0.0, 1.0, 0.0)),
+            (local_point.z - self.minimum.z, Vector::new(0.0, 0.0, -1.0)),
+            (self.maximum.z - local_point.z, Vector::new(0.0, 0.0, 1.0)),
+        ];
+        faces
+            .into_iter()
+            .reduce(|closest, face| {
+                if face.0.abs() < closest.0.abs() {
+                    face
+                } else {
+                    closest
+                }
+            })
+            .unwrap()
+            .1
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
+        collect_local_intersections(self, local_ray)
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
+        AxisAlignedBox::intersect_span(local_ray, self.minimum, self.maximum, intersections);
+    }
+}
+
+impl Bounded for AxisAlignedBox {
+    fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AxisAlignedBoxBuilder {
+    corners: Option<(Point, Point)>,
+    frame_transformation: Option<Transform>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
+}
+
+impl AxisAlignedBoxBuilder {
+    // the corners may be given in any order
+    pub fn set_corners(mut self, corner1: Point, corner2: Point) -> AxisAlignedBoxBuilder {
+        self.corners = Some((corner1, corner2));
+        self
+    }
+
+    pub fn set_centre(mut self, centre: Point, half_extents: Vector) -> AxisAlignedBoxBuilder {
+        self.corners = Some((centre - half_extents, centre + half_extents));
+        self
+    }
+
+    pub fn set_frame_transformation(
+        mut self,
+        frame_transformation: Transform,
+    ) -> AxisAlignedBoxBuilder {
+        self.frame_transformation = Some(frame_transformation);
+        self
+    }
+
+    pub fn set_material(mut self, material: Material) -> AxisAlignedBoxBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> AxisAlignedBoxBuilder {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn set_name(mut self, name: &str) -> AxisAlignedBoxBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+}
+
+impl Buildable for AxisAlignedBox {
+    type Builder = AxisAlignedBoxBuilder;
+
+    fn builder() -> Self::Builder {
+        AxisAlignedBoxBuilder::default()
+    }
+}
+
+impl ConsumingBuilder for AxisAlignedBoxBuilder {
+    type Built = AxisAlignedBox;
+
+    // without corners, the box matches a unit Cube
+    fn build(self) -> Self::Built {
+        let (corner1, corner2) = self
+            .corners
+            .unwrap_or((Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0)));
+        let (minimum, maximum) = AxisAlignedBox::span(corner1, corner2);
+
+        let frame_transformation = self.frame_transformation.unwrap_or_default();
+        let material = self.material.unwrap_or_default();
+        let bounds = Bounds::new(
+            BoundingBox::from_anchors(vec![minimum, maximum]).transform(&frame_transformation),
+        );
+
+        AxisAlignedBox {
+            minimum,
+            maximum,
+            frame_transformation,
+            material,
+            name: self.name,
+            bounds,
+        }
+    }
+
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        check_frame_transformation(&self.frame_transformation)?;
+        Ok(self.build())
+    }
+}
+
+impl From<AxisAlignedBox> for Shape {
+    fn from(axis_aligned_box: AxisAlignedBox) -> Shape {
+        Shape::Primitive(Box::new(axis_aligned_box))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_box_from_corners_in_any_order() {
+        let aabb = AxisAlignedBox::builder()
+            .set_corners(Point::new(2.0, -1.0, 5.0), Point::new(-3.0, 4.0, 1.0))
+            .build();
+        assert_eq!(aabb.minimum(), Point::new(-3.0, -1.0, 1.0));
+        assert_eq!(aabb.maximum(), Point::new(2.0, 4.0, 5.0));
+
+        let (x_range, y_range, z_range) = aabb.bounds().bounding_box().axial_bounds();
+        assert_eq!(x_range, [-3.0, 2.0]);
+        assert_eq!(y_range, [-1.0, 4.0]);
+        assert_eq!(z_range, [1.0, 5.0]);
+    }
+
+    #[test]
+    fn build_box_from_centre_and_half_extents() {
+        let aabb = AxisAlignedBox::builder()
+            .set_centre(Point::new(1.0, 2.0, 3.0), Vector::new(0.5, 1.0, 2.0))
+            .build();
+        assert_eq!(aabb.minimum(), Point::new(0.5, 1.0, 1.0));
+        assert_eq!(aabb.maximum(), Point::new(1.5, 3.0, 5.0));
+    }
+
+    #[test]
+    fn ray_intersects_box() {
+        let aabb = AxisAlignedBox::builder()
+            .set_corners(Point::new(1.0, 0.0, 0.0), Point::new(3.0, 2.0, 4.0))
+            .build();
+        let test_cases: [(Point, Vector, f64, f64); 3] = [
+            (
+                Point::new(-5.0, 1.0, 2.0),
+                Vector::new(1.0, 0.0, 0.0),
+                6.0,
+                8.0,
+            ),
+            (
+                Point::new(2.0, 10.0, 1.0),
+                Vector::new(0.0, -1.0, 0.0),
+                8.0,
+                10.0,
+            ),
+            (
+                Point::new(2.0, 1.0, 2.0),
+                Vector::new(0.0, 0.0, 1.0),
+                -2.0,
+                2.0,
+            ),
+        ];
+        for (origin, direction, t1, t2) in test_cases {
+            let intersections = aabb.local_intersect(&Ray::new(origin, direction));
+            assert_eq!(intersections.len(), 2);
+            assert_eq!(intersections[0].t(), t1);
+            assert_eq!(intersections[1].t(), t2);
+        }
+
+        let miss = Ray::new(Point::new(0.0, 1.0, 2.0), Vector::new(0.0, 1.0, 0.0));
+        assert!(aabb.local_intersect(&miss).is_empty());
+    }
+
+    #[test]
+    fn ray_intersects_flat_box() {
+        let card = AxisAlignedBox::builder()
+            .set_corners(Point::new(-1.0, 0.0, -1.0), Point::new(1.0, 0.0, 1.0))
+            .build();
+        let through = Ray::new(Point::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let ts: Vec<f64> = card
+            .local_intersect(&through)
+            .iter()
+            .map(|hit| hit.t())
+            .collect();
+        assert_eq!(ts, vec![5.0, 5.0]);
+
+        // rays lying in the card's plane, which once gave NaN
+        let along = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let ts: Vec<f64> = card
+            .local_intersect(&along)
+            .iter()
+            .map(|hit| hit.t())
+            .collect();
+        assert_eq!(ts, vec![4.0, 6.0]);
+        let beside = Ray::new(Point::new(-5.0, 0.5, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(card.local_intersect(&beside).is_empty());
+    }
+
+    #[test]
+    fn normal_on_box() {
+        let aabb = AxisAlignedBox::builder()
+            .set_corners(Point::new(0.0, 0.0, 0.0), Point::new(4.0, 1.0, 2.0))
+            .build();
+        let test_cases: [(Point, Vector); 6] = [
+            (Point::new(0.0, 0.5, 1.0), Vector::new(-1.0, 0.0, 0.0)),
+            (Point::new(4.0, 0.5, 1.0), Vector::new(1.0, 0.0, 0.0)),
+            (Point::new(2.0, 0.0, 1.0), Vector::new(0.0, -1.0, 0.0)),
+            (Point::new(2.0, 1.0, 1.0), Vector::new(0.0, 1.0, 0.0)),
+            (Point::new(2.0, 0.5, 0.0), Vector::new(0.0, 0.0, -1.0)),
+            (Point::new(3.5, 0.5, 2.0), Vector::new(0.0, 0.0, 1.0)),
+        ];
+        for (point, normal) in test_cases {
+            assert_eq!(aabb.local_normal_at(point, None), normal);
+        }
+    }
+
+    #[test]
+    fn closest_point_on_box() {
+        let aabb = AxisAlignedBox::builder()
+            .set_corners(Point::new(-1.0, 0.0, 0.0), Point::new(1.0, 4.0, 2.0))
+            .build();
+        let test_cases = [
+            (Point::new(3.0, 5.0, 1.0), Point::new(1.0, 4.0, 1.0)),
+            (Point::new(0.0, -2.0, 1.0), Point::new(0.0, 0.0, 1.0)),
+            // inside, nearest the face at x = 1
+            (Point::new(0.5, 2.0, 1.0), Point::new(1.0, 2.0, 1.0)),
+        ];
+        for (point, closest) in test_cases {
+            assert_eq!(aabb.local_closest_point(point), Some(closest));
+        }
+    }
+}
@@ -1,11 +1,14 @@
+use std::sync::Arc;
+
 use crate::collections::{Point, Vector};
 use crate::objects::*;
-use crate::utils::{Buildable, ConsumingBuilder, EPSILON};
+use crate::utils::{BuildError, Buildable, ConsumingBuilder, EPSILON};
 
 #[derive(Debug)]
 pub struct Cone {
     frame_transformation: Transform,
-    material: Material,
+    material: Arc<Material>,
+    name: Option<String>,
     y_minimum: f64,
     closed_bot: bool,
     y_maximum: f64,
@@ -16,23 +19,33 @@ pub struct Cone {
 impl Cone {
     const PRIMITIVE_BOUNDING_BOX: BoundingBox = BoundingBox::new_unbounded();
 
-    pub fn y_minimum(&mut self) -> Option<f64> {
-        if self.closed_bot {
-            None
-        } else {
-            Some(self.y_minimum)
-        }
+    // as wide as the cone is at whichever end lies further from its tip
+    fn local_bounding_box(y_minimum: f64, y_maximum: f64) -> BoundingBox {
+        let limit = f64::max(y_minimum.abs(), y_maximum.abs());
+        Cone::PRIMITIVE_BOUNDING_BOX
+            .bound_in_x_axis([-limit, limit])
+            .bound_in_y_axis([y_minimum, y_maximum])
+            .bound_in_z_axis([-limit, limit])
     }
 
-    pub fn y_maximum(&mut self) -> Option<f64> {
-        if self.closed_bot {
-            None
-        } else {
-            Some(self.y_maximum)
-        }
+    // None when the shape extends forever in that direction
+    pub fn y_minimum(&self) -> Option<f64> {
+        self.y_minimum.is_finite().then_some(self.y_minimum)
+    }
+
+    pub fn y_maximum(&self) -> Option<f64> {
+        self.y_maximum.is_finite().then_some(self.y_maximum)
+    }
+
+    pub fn closed_bot(&self) -> bool {
+        self.closed_bot
     }
 
-    fn intersect_walls(&self, local_ray: &Ray) -> Vec<f64> {
+    pub fn closed_top(&self) -> bool {
+        self.closed_top
+    }
+
+    fn intersect_walls(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
         let &Ray { origin, direction } = local_ray;
         let Point {
             x: origin_x,
@@ -50,37 +63,32 @@ impl Cone {
         let c = origin_x.powi(2) - origin_y.powi(2) + origin_z.powi(2);
 
         if a.abs() < EPSILON {
-            return if b.abs() < EPSILON {
-                vec![]
-            } else {
-                vec![-c / (2.0 * b)]
-            };
+            if b.abs() >= EPSILON {
+                intersections.push(Coordinates::new(-c / (2.0 * b), None));
+            }
+            return;
         }
 
         let disc = b.powi(2) - 4.0 * a * c;
 
         if disc < 0.0 {
-            return vec![];
+            return;
         }
 
-        let mut t_values = vec![];
-
         let t0 = (-b - disc.sqrt()) / (2.0 * a);
         let y0 = local_ray.position(t0).y;
         if (self.y_minimum < y0) && (y0 < self.y_maximum) {
-            t_values.push(t0);
+            intersections.push(Coordinates::new(t0, None));
         }
 
         let t1 = (-b + disc.sqrt()) / (2.0 * a);
         let y1 = local_ray.position(t1).y;
         if (self.y_minimum < y1) && (y1 < self.y_maximum) {
-            t_values.push(t1);
+            intersections.push(Coordinates::new(t1, None));
         }
-
-        t_values
     }
 
-    fn intersect_caps(&self, local_ray: &Ray) -> Vec<f64> {
+    fn intersect_caps(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
         fn check_cap(local_ray: &Ray, t: f64, y: f64) -> bool {
             let position = local_ray.position(t);
 
@@ -88,26 +96,22 @@ impl Cone {
         }
 
         if local_ray.direction.y.abs() < EPSILON {
-            return vec![];
+            return;
         }
 
-        let mut t_values = vec![];
-
         if self.closed_bot {
             let t = (self.y_minimum - local_ray.origin.y) / local_ray.direction.y;
             if check_cap(local_ray, t, self.y_minimum) {
-                t_values.push(t);
+                intersections.push(Coordinates::new(t, None));
             }
         }
 
         if self.closed_top {
             let t = (self.y_maximum - local_ray.origin.y) / local_ray.direction.y;
             if check_cap(local_ray, t, self.y_maximum) {
-                t_values.push(t);
+                intersections.push(Coordinates::new(t, None));
             }
         }
-
-        t_values
     }
 }
 
@@ -116,17 +120,90 @@ impl PrimitiveShape for Cone {
         &self.frame_transformation
     }
 
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.bounds = Bounds::new(
+            Cone::local_bounding_box(self.y_minimum, self.y_maximum)
+                .transform(&self.frame_transformation),
+        );
+        true
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
 
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn has_interior(&self) -> bool {
+        true
+    }
+
+    // open ends are treated as if capped, as for cylinders
+    fn local_contains_point(&self, local_point: Point) -> Option<bool> {
+        let Point { x, y, z } = local_point;
+        Some(
+            x.powi(2) + z.powi(2) <= y.powi(2) + EPSILON
+                && (self.y_minimum - EPSILON..=self.y_maximum + EPSILON).contains(&y),
+        )
+    }
+
+    // nearest of the two nappes and any caps, found in the (r, y) plane
+    // through the point, where the nappes are the lines r = y and r = -y
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        let Point { x, y, z } = local_point;
+        let radius = x.hypot(z);
+        // any direction out from points on the axis
+        let (cos, sin) = match radius < EPSILON {
+            true => (1.0, 0.0),
+            false => (x / radius, z / radius),
+        };
+        let mut candidates = vec![];
+        let [upper_min, upper_max] = [self.y_minimum.max(0.0), self.y_maximum];
+        if upper_min <= upper_max {
+            let t = ((radius + y) / 2.0).clamp(upper_min, upper_max);
+            candidates.push((t, t));
+        }
+        let [lower_min, lower_max] = [self.y_minimum, self.y_maximum.min(0.0)];
+        if lower_min <= lower_max {
+            let t = ((y - radius) / 2.0).clamp(lower_min, lower_max);
+            candidates.push((-t, t));
+        }
+        for (closed, cap_y) in [
+            (self.closed_bot, self.y_minimum),
+            (self.closed_top, self.y_maximum),
+        ] {
+            if closed {
+                candidates.push((radius.min(cap_y.abs()), cap_y));
+            }
+        }
+        candidates
+            .into_iter()
+            .min_by(|&(r0, y0), &(r1, y1)| {
+                (r0 - radius)
+                    .hypot(y0 - y)
+                    .total_cmp(&(r1 - radius).hypot(y1 - y))
+            })
+            .map(|(r, y)| Point::new(cos * r, y, sin * r))
+    }
+
     fn local_normal_at(&self, local_point: Point, _: Option<(f64, f64)>) -> Vector {
         let dist = local_point.x.powi(2) + local_point.z.powi(2);
 
         if dist < f64::abs(local_point.y) {
             match local_point.y {
-                y if y >= self.y_maximum - EPSILON => return Vector::new(0.0, 1.0, 0.0),
-                y if y <= self.y_minimum + EPSILON => return Vector::new(0.0, -1.0, 0.0),
+                y if self.closed_top && y >= self.y_maximum - EPSILON => {
+                    return Vector::new(0.0, 1.0, 0.0)
+                }
+                y if self.closed_bot && y <= self.y_minimum + EPSILON => {
+                    return Vector::new(0.0, -1.0, 0.0)
+                }
                 _ => (),
             }
         }
@@ -141,15 +218,12 @@ impl PrimitiveShape for Cone {
     }
 
     fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
-        let mut t_values = vec![];
-
-        t_values.extend_from_slice(&self.intersect_walls(local_ray));
-        t_values.extend_from_slice(&self.intersect_caps(local_ray));
+        collect_local_intersections(self, local_ray)
+    }
 
-        t_values
-            .iter()
-            .map(|&t| Coordinates::new(t, None))
-            .collect()
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
+        self.intersect_walls(local_ray, intersections);
+        self.intersect_caps(local_ray, intersections);
     }
 }
 
@@ -162,9 +236,12 @@ impl Bounded for Cone {
 #[derive(Debug, Default)]
 pub struct ConeBuilder {
     frame_transformation: Option<Transform>,
-    material: Option<Material>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
     y_minimum: Option<f64>,
     y_maximum: Option<f64>,
+    closed_bot: Option<bool>,
+    closed_top: Option<bool>,
 }
 
 impl ConeBuilder {
@@ -174,10 +251,20 @@ impl ConeBuilder {
     }
 
     pub fn set_material(mut self, material: Material) -> ConeBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> ConeBuilder {
         self.material = Some(material);
         self
     }
 
+    pub fn set_name(mut self, name: &str) -> ConeBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+
     pub fn set_y_minimum(mut self, y_minimum: f64) -> ConeBuilder {
         self.y_minimum = Some(y_minimum);
         self
@@ -187,6 +274,18 @@ impl ConeBuilder {
         self.y_maximum = Some(y_maximum);
         self
     }
+
+    // truncated ends are capped unless opened here; an end that is not
+    // truncated has nothing to cap, so closing it has no effect
+    pub fn set_closed_bot(mut self, closed_bot: bool) -> ConeBuilder {
+        self.closed_bot = Some(closed_bot);
+        self
+    }
+
+    pub fn set_closed_top(mut self, closed_top: bool) -> ConeBuilder {
+        self.closed_top = Some(closed_top);
+        self
+    }
 }
 
 impl Buildable for Cone {
@@ -204,44 +303,45 @@ impl ConsumingBuilder for ConeBuilder {
         let frame_transformation = self.frame_transformation.unwrap_or_default();
         let material = self.material.unwrap_or_default();
         let (y_minimum, closed_bot) = match self.y_minimum {
-            Some(y_minimum) => (y_minimum, true),
+            Some(y_minimum) => (y_minimum, self.closed_bot.unwrap_or(true)),
             None => (f64::NEG_INFINITY, false),
         };
         let (y_maximum, closed_top) = match self.y_maximum {
-            Some(y_maximum) => (y_maximum, true),
+            Some(y_maximum) => (y_maximum, self.closed_top.unwrap_or(true)),
             None => (f64::INFINITY, false),
         };
-        let limit = f64::max(y_minimum.abs(), y_maximum.abs());
         let bounds = Bounds::new(
-            Cone::PRIMITIVE_BOUNDING_BOX
-                .bound_in_x_axis([-limit, limit])
-                .bound_in_y_axis([y_minimum, y_maximum])
-                .bound_in_z_axis([-limit, limit])
-                .transform(&frame_transformation),
+            Cone::local_bounding_box(y_minimum, y_maximum).transform(&frame_transformation),
         );
-        let cone = Cone {
+
+        Cone {
             frame_transformation,
             material,
+            name: self.name,
             y_minimum,
             closed_bot,
             y_maximum,
             closed_top,
             bounds,
-        };
-        cone
+        }
+    }
+
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        check_frame_transformation(&self.frame_transformation)?;
+        Ok(self.build())
     }
 }
 
-impl Into<Shape> for Cone {
-    fn into(self) -> Shape {
-        Shape::Primitive(Box::new(self))
+impl From<Cone> for Shape {
+    fn from(cone: Cone) -> Shape {
+        Shape::Primitive(Box::new(cone))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::utils::approx_eq;
+    use crate::utils::{approx_eq, ApproxEq};
 
     #[test]
     fn ray_intersects_cone() {
@@ -342,4 +442,71 @@ mod tests {
         assert_eq!(y_range, [-5.0, 3.0]);
         assert_eq!(z_range, [-5.0, 5.0]);
     }
+
+    #[test]
+    fn lamp_shade_cone_is_open_at_bottom() {
+        let shade = Cone::builder()
+            .set_y_minimum(0.5)
+            .set_y_maximum(1.0)
+            .set_closed_bot(false)
+            .build();
+        assert_eq!(shade.y_minimum(), Some(0.5));
+        assert_eq!(shade.y_maximum(), Some(1.0));
+        assert!(!shade.closed_bot() && shade.closed_top());
+
+        let ray = Ray::new(Point::new(0.0, 2.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let intersections = shade.local_intersect(&ray);
+        assert_eq!(intersections.len(), 1);
+        assert_eq!(intersections[0].t(), 1.0);
+
+        let capped = Cone::builder()
+            .set_y_minimum(0.5)
+            .set_y_maximum(1.0)
+            .build();
+        assert_eq!(capped.local_intersect(&ray).len(), 2);
+    }
+
+    #[test]
+    fn points_inside_cone() {
+        let cone = Cone::builder()
+            .set_y_minimum(-1.0)
+            .set_y_maximum(2.0)
+            .build();
+        assert_eq!(
+            cone.local_contains_point(Point::new(0.5, 1.0, 0.0)),
+            Some(true)
+        );
+        assert_eq!(
+            cone.local_contains_point(Point::new(0.0, -1.0, 1.0)),
+            Some(true)
+        );
+        assert_eq!(
+            cone.local_contains_point(Point::new(1.5, 1.0, 0.0)),
+            Some(false)
+        );
+        // beyond the ends, though they are open
+        assert_eq!(
+            cone.local_contains_point(Point::new(0.0, 2.5, 0.0)),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn closest_point_on_cone() {
+        let cone = Cone::builder()
+            .set_y_minimum(-1.0)
+            .set_y_maximum(1.0)
+            .set_closed_bot(false)
+            .build();
+        let test_cases = [
+            (Point::new(2.0, 0.5, 0.0), Point::new(1.0, 1.0, 0.0)),
+            (Point::new(0.0, -1.0, 0.0), Point::new(0.5, -0.5, 0.0)),
+            (Point::new(0.0, -2.0, -1.0), Point::new(0.0, -1.0, -1.0)),
+            (Point::new(0.0, 3.0, 0.5), Point::new(0.0, 1.0, 0.5)),
+        ];
+        for (point, closest) in test_cases {
+            let found = cone.local_closest_point(point).unwrap();
+            assert!(found.approx_eq(&closest), "{found:?} for {point:?}");
+        }
+    }
 }
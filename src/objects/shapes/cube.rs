@@ -1,39 +1,21 @@
+use std::sync::Arc;
+
 use crate::collections::{Point, Vector};
 use crate::objects::*;
 use crate::utils::floats::EPSILON;
-use crate::utils::{Buildable, ConsumingBuilder};
+use crate::utils::{BuildError, Buildable, ConsumingBuilder};
 
 #[derive(Debug)]
 pub struct Cube {
     frame_transformation: Transform,
-    material: Material,
+    material: Arc<Material>,
+    name: Option<String>,
     bounds: Bounds,
 }
 
 impl Cube {
     const PRIMITIVE_BOUNDING_BOX: BoundingBox =
         BoundingBox::from_axial_bounds([-1.0, 1.0], [-1.0, 1.0], [-1.0, 1.0]);
-
-    fn check_axis(origin: f64, direction: f64) -> (f64, f64) {
-        let tmin_numerator = -1.0 - origin;
-        let tmax_numerator = 1.0 - origin;
-
-        let tmin;
-        let tmax;
-        if direction.abs() >= EPSILON {
-            tmin = tmin_numerator / direction;
-            tmax = tmax_numerator / direction;
-        } else {
-            tmin = tmin_numerator * f64::INFINITY;
-            tmax = tmax_numerator * f64::INFINITY;
-        }
-
-        if tmin > tmax {
-            (tmax, tmin)
-        } else {
-            (tmin, tmax)
-        }
-    }
 }
 
 impl PrimitiveShape for Cube {
@@ -41,10 +23,46 @@ impl PrimitiveShape for Cube {
         &self.frame_transformation
     }
 
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.bounds =
+            Bounds::new(Cube::PRIMITIVE_BOUNDING_BOX.transform(&self.frame_transformation));
+        true
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
 
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn has_interior(&self) -> bool {
+        true
+    }
+
+    fn local_contains_point(&self, local_point: Point) -> Option<bool> {
+        let Point { x, y, z } = local_point;
+        Some(
+            [x, y, z]
+                .into_iter()
+                .all(|coordinate| coordinate.abs() <= 1.0 + EPSILON),
+        )
+    }
+
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        Some(AxisAlignedBox::closest_point(
+            local_point,
+            Point::new(-1.0, -1.0, -1.0),
+            Point::new(1.0, 1.0, 1.0),
+        ))
+    }
+
     fn local_normal_at(&self, local_point: Point, _: Option<(f64, f64)>) -> Vector {
         let maxc = [
             local_point.x.abs(),
@@ -64,21 +82,16 @@ impl PrimitiveShape for Cube {
     }
 
     fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
-        let (xtmin, xtmax) = Cube::check_axis(local_ray.origin.x, local_ray.direction.x);
-        let (ytmin, ytmax) = Cube::check_axis(local_ray.origin.y, local_ray.direction.y);
-        let (ztmin, ztmax) = Cube::check_axis(local_ray.origin.z, local_ray.direction.z);
-
-        let tmin = [xtmin, ytmin, ztmin].into_iter().reduce(f64::max).unwrap();
-        let tmax = [xtmax, ytmax, ztmax].into_iter().reduce(f64::min).unwrap();
-
-        if tmin > tmax {
-            vec![]
-        } else {
-            vec![tmin, tmax]
-                .iter()
-                .map(|&t| Coordinates::new(t, None))
-                .collect()
-        }
+        collect_local_intersections(self, local_ray)
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
+        AxisAlignedBox::intersect_span(
+            local_ray,
+            Point::new(-1.0, -1.0, -1.0),
+            Point::new(1.0, 1.0, 1.0),
+            intersections,
+        );
     }
 }
 
@@ -91,7 +104,8 @@ impl Bounded for Cube {
 #[derive(Debug, Default)]
 pub struct CubeBuilder {
     frame_transformation: Option<Transform>,
-    material: Option<Material>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
 }
 
 impl CubeBuilder {
@@ -101,9 +115,19 @@ impl CubeBuilder {
     }
 
     pub fn set_material(mut self, material: Material) -> CubeBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> CubeBuilder {
         self.material = Some(material);
         self
     }
+
+    pub fn set_name(mut self, name: &str) -> CubeBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
 }
 
 impl Buildable for Cube {
@@ -122,18 +146,23 @@ impl ConsumingBuilder for CubeBuilder {
         let material = self.material.unwrap_or_default();
         let bounds = Bounds::new(Cube::PRIMITIVE_BOUNDING_BOX.transform(&frame_transformation));
 
-        let cube = Cube {
+        Cube {
             frame_transformation,
             material,
+            name: self.name,
             bounds,
-        };
-        cube
+        }
+    }
+
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        check_frame_transformation(&self.frame_transformation)?;
+        Ok(self.build())
     }
 }
 
-impl Into<Shape> for Cube {
-    fn into(self) -> Shape {
-        Shape::Primitive(Box::new(self))
+impl From<Cube> for Shape {
+    fn from(cube: Cube) -> Shape {
+        Shape::Primitive(Box::new(cube))
     }
 }
 
@@ -141,6 +170,7 @@ impl Into<Shape> for Cube {
 mod tests {
     use super::*;
     use crate::collections::{Point, Vector};
+    use crate::objects::TransformKind;
 
     #[test]
     fn ray_intersects_cube() {
@@ -241,4 +271,27 @@ mod tests {
             assert_eq!(cube.local_normal_at(point, None), normal);
         }
     }
+
+    #[test]
+    fn primitive_cube_bounds() {
+        let cube = Cube::builder().build();
+        let (x_range, y_range, z_range) = cube.bounds().bounding_box().axial_bounds();
+        assert_eq!(x_range, [-1.0, 1.0]);
+        assert_eq!(y_range, [-1.0, 1.0]);
+        assert_eq!(z_range, [-1.0, 1.0]);
+    }
+
+    #[test]
+    fn transformed_cube_bounds() {
+        let cube = Cube::builder()
+            .set_frame_transformation(Transform::from(vec![
+                TransformKind::Scale(2.0, 0.5, 1.0),
+                TransformKind::Translate(1.0, 0.0, -3.0),
+            ]))
+            .build();
+        let (x_range, y_range, z_range) = cube.bounds().bounding_box().axial_bounds();
+        assert_eq!(x_range, [-1.0, 3.0]);
+        assert_eq!(y_range, [-0.5, 0.5]);
+        assert_eq!(z_range, [-4.0, -2.0]);
+    }
 }
@@ -1,11 +1,14 @@
+use std::sync::Arc;
+
 use crate::collections::{Point, Vector};
 use crate::objects::*;
-use crate::utils::{Buildable, ConsumingBuilder, EPSILON};
+use crate::utils::{BuildError, Buildable, ConsumingBuilder, EPSILON};
 
 #[derive(Debug)]
 pub struct Cylinder {
     frame_transformation: Transform,
-    material: Material,
+    material: Arc<Material>,
+    name: Option<String>,
     y_minimum: f64,
     closed_bot: bool,
     y_maximum: f64,
@@ -20,23 +23,24 @@ impl Cylinder {
         [-1.0, 1.0],
     );
 
-    pub fn y_minimum(&mut self) -> Option<f64> {
-        if self.closed_bot {
-            None
-        } else {
-            Some(self.y_minimum)
-        }
+    // None when the shape extends forever in that direction
+    pub fn y_minimum(&self) -> Option<f64> {
+        self.y_minimum.is_finite().then_some(self.y_minimum)
     }
 
-    pub fn y_maximum(&mut self) -> Option<f64> {
-        if self.closed_bot {
-            None
-        } else {
-            Some(self.y_maximum)
-        }
+    pub fn y_maximum(&self) -> Option<f64> {
+        self.y_maximum.is_finite().then_some(self.y_maximum)
+    }
+
+    pub fn closed_bot(&self) -> bool {
+        self.closed_bot
     }
 
-    fn intersect_walls(&self, local_ray: &Ray) -> Vec<f64> {
+    pub fn closed_top(&self) -> bool {
+        self.closed_top
+    }
+
+    fn intersect_walls(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
         let &Ray { origin, direction } = local_ray;
         let Point {
             x: origin_x,
@@ -52,7 +56,7 @@ impl Cylinder {
         let a = dir_x.powi(2) + dir_z.powi(2);
 
         if a.abs() < EPSILON {
-            return vec![];
+            return;
         }
 
         let b = (2.0 * origin_x * dir_x) + (2.0 * origin_z * dir_z);
@@ -61,24 +65,20 @@ impl Cylinder {
         let disc = b.powi(2) - 4.0 * a * c;
 
         if disc < 0.0 {
-            return vec![];
+            return;
         }
 
-        let mut t_values = vec![];
-
         let t0 = (-b - disc.sqrt()) / (2.0 * a);
         let y0 = local_ray.position(t0).y;
         if (self.y_minimum < y0) && (y0 < self.y_maximum) {
-            t_values.push(t0);
+            intersections.push(Coordinates::new(t0, None));
         }
 
         let t1 = (-b + disc.sqrt()) / (2.0 * a);
         let y1 = local_ray.position(t1).y;
         if (self.y_minimum < y1) && (y1 < self.y_maximum) {
-            t_values.push(t1);
+            intersections.push(Coordinates::new(t1, None));
         }
-
-        t_values
     }
 
     fn check_cap(local_ray: &Ray, t: f64) -> bool {
@@ -87,28 +87,24 @@ impl Cylinder {
         (position.x.powi(2) + position.z.powi(2)) <= 1.0
     }
 
-    fn intersect_caps(&self, local_ray: &Ray) -> Vec<f64> {
+    fn intersect_caps(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
         if local_ray.direction.y.abs() < EPSILON {
-            return vec![];
+            return;
         }
 
-        let mut t_values = vec![];
-
         if self.closed_bot {
             let t = (self.y_minimum - local_ray.origin.y) / local_ray.direction.y;
             if Self::check_cap(local_ray, t) {
-                t_values.push(t);
+                intersections.push(Coordinates::new(t, None));
             }
         }
 
         if self.closed_top {
             let t = (self.y_maximum - local_ray.origin.y) / local_ray.direction.y;
             if Self::check_cap(local_ray, t) {
-                t_values.push(t);
+                intersections.push(Coordinates::new(t, None));
             }
         }
-
-        t_values
     }
 }
 
@@ -117,17 +113,75 @@ impl PrimitiveShape for Cylinder {
         &self.frame_transformation
     }
 
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.bounds =
+            Bounds::new(Cylinder::PRIMITIVE_BOUNDING_BOX.transform(&self.frame_transformation));
+        true
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
 
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn has_interior(&self) -> bool {
+        true
+    }
+
+    // open ends are treated as if capped, so that a tube holds the points
+    // within it
+    fn local_contains_point(&self, local_point: Point) -> Option<bool> {
+        let Point { x, y, z } = local_point;
+        Some(
+            x.powi(2) + z.powi(2) <= 1.0 + EPSILON
+                && (self.y_minimum - EPSILON..=self.y_maximum + EPSILON).contains(&y),
+        )
+    }
+
+    // nearest of the wall and any caps; open ends are left open here, so
+    // points beyond them are nearest the rim
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        let Point { x, y, z } = local_point;
+        let radius = x.hypot(z);
+        // any direction out from points on the axis
+        let (cos, sin) = match radius < EPSILON {
+            true => (1.0, 0.0),
+            false => (x / radius, z / radius),
+        };
+        let wall = Point::new(cos, y.clamp(self.y_minimum, self.y_maximum), sin);
+        let caps = [
+            (self.closed_bot, self.y_minimum),
+            (self.closed_top, self.y_maximum),
+        ]
+        .into_iter()
+        .filter(|&(closed, _)| closed)
+        .map(|(_, cap_y)| Point::new(cos * radius.min(1.0), cap_y, sin * radius.min(1.0)));
+        std::iter::once(wall).chain(caps).min_by(|a, b| {
+            (*a - local_point)
+                .magnitude()
+                .total_cmp(&(*b - local_point).magnitude())
+        })
+    }
+
     fn local_normal_at(&self, local_point: Point, _: Option<(f64, f64)>) -> Vector {
         let dist = local_point.x.powi(2) + local_point.z.powi(2);
 
         if dist < 1.0 {
             match local_point.y {
-                y if y >= self.y_maximum - EPSILON => return Vector::new(0.0, 1.0, 0.0),
-                y if y <= self.y_minimum + EPSILON => return Vector::new(0.0, -1.0, 0.0),
+                y if self.closed_top && y >= self.y_maximum - EPSILON => {
+                    return Vector::new(0.0, 1.0, 0.0)
+                }
+                y if self.closed_bot && y <= self.y_minimum + EPSILON => {
+                    return Vector::new(0.0, -1.0, 0.0)
+                }
                 _ => (),
             }
         }
@@ -136,15 +190,12 @@ impl PrimitiveShape for Cylinder {
     }
 
     fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
-        let mut t_values = vec![];
-
-        t_values.extend_from_slice(&self.intersect_walls(local_ray));
-        t_values.extend_from_slice(&self.intersect_caps(local_ray));
+        collect_local_intersections(self, local_ray)
+    }
 
-        t_values
-            .iter()
-            .map(|&t| Coordinates::new(t, None))
-            .collect()
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
+        self.intersect_walls(local_ray, intersections);
+        self.intersect_caps(local_ray, intersections);
     }
 }
 
@@ -157,9 +208,12 @@ impl Bounded for Cylinder {
 #[derive(Debug, Default)]
 pub struct CylinderBuilder {
     frame_transformation: Option<Transform>,
-    material: Option<Material>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
     y_minimum: Option<f64>,
     y_maximum: Option<f64>,
+    closed_bot: Option<bool>,
+    closed_top: Option<bool>,
 }
 
 impl CylinderBuilder {
@@ -169,10 +223,20 @@ impl CylinderBuilder {
     }
 
     pub fn set_material(mut self, material: Material) -> CylinderBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> CylinderBuilder {
         self.material = Some(material);
         self
     }
 
+    pub fn set_name(mut self, name: &str) -> CylinderBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+
     pub fn set_y_minimum(mut self, y_minimum: f64) -> CylinderBuilder {
         self.y_minimum = Some(y_minimum);
         self
@@ -182,6 +246,18 @@ impl CylinderBuilder {
         self.y_maximum = Some(y_maximum);
         self
     }
+
+    // truncated ends are capped unless opened here; an end that is not
+    // truncated has nothing to cap, so closing it has no effect
+    pub fn set_closed_bot(mut self, closed_bot: bool) -> CylinderBuilder {
+        self.closed_bot = Some(closed_bot);
+        self
+    }
+
+    pub fn set_closed_top(mut self, closed_top: bool) -> CylinderBuilder {
+        self.closed_top = Some(closed_top);
+        self
+    }
 }
 
 impl Buildable for Cylinder {
@@ -199,31 +275,36 @@ impl ConsumingBuilder for CylinderBuilder {
         let frame_transformation = self.frame_transformation.unwrap_or_default();
         let material = self.material.unwrap_or_default();
         let (y_minimum, closed_bot) = match self.y_minimum {
-            Some(y_minimum) => (y_minimum, true),
+            Some(y_minimum) => (y_minimum, self.closed_bot.unwrap_or(true)),
             None => (f64::NEG_INFINITY, false),
         };
         let (y_maximum, closed_top) = match self.y_maximum {
-            Some(y_maximum) => (y_maximum, true),
+            Some(y_maximum) => (y_maximum, self.closed_top.unwrap_or(true)),
             None => (f64::INFINITY, false),
         };
         let bounds = Bounds::new(Cylinder::PRIMITIVE_BOUNDING_BOX.transform(&frame_transformation));
 
-        let cylinder = Cylinder {
+        Cylinder {
             frame_transformation,
             material,
+            name: self.name,
             y_minimum,
             closed_bot,
             y_maximum,
             closed_top,
             bounds,
-        };
-        cylinder
+        }
+    }
+
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        check_frame_transformation(&self.frame_transformation)?;
+        Ok(self.build())
     }
 }
 
-impl Into<Shape> for Cylinder {
-    fn into(self) -> Shape {
-        Shape::Primitive(Box::new(self))
+impl From<Cylinder> for Shape {
+    fn from(cylinder: Cylinder) -> Shape {
+        Shape::Primitive(Box::new(cylinder))
     }
 }
 
@@ -348,4 +429,42 @@ mod tests {
             assert_eq!(cylinder.local_normal_at(point, None), normal);
         }
     }
+
+    #[test]
+    fn open_ended_cylinder_has_no_caps() {
+        let tube = Cylinder::builder()
+            .set_y_minimum(1.0)
+            .set_y_maximum(2.0)
+            .set_closed_bot(false)
+            .set_closed_top(false)
+            .build();
+        assert_eq!(tube.y_minimum(), Some(1.0));
+        assert_eq!(tube.y_maximum(), Some(2.0));
+        assert!(!tube.closed_bot() && !tube.closed_top());
+        let ray = Ray::new(Point::new(0.0, 3.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert!(tube.local_intersect(&ray).is_empty());
+
+        let cup = Cylinder::builder()
+            .set_y_minimum(1.0)
+            .set_y_maximum(2.0)
+            .set_closed_top(false)
+            .build();
+        assert!(cup.closed_bot() && !cup.closed_top());
+        let intersections = cup.local_intersect(&ray);
+        assert_eq!(intersections.len(), 1);
+        approx_eq!(intersections[0].t(), 2.0);
+        // the rim of an open end belongs to the wall
+        assert_eq!(
+            cup.local_normal_at(Point::new(0.0, 2.0, 0.5), None),
+            Vector::new(0.0, 0.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn untruncated_cylinder_accessors() {
+        let cylinder = Cylinder::builder().set_closed_bot(true).build();
+        assert_eq!(cylinder.y_minimum(), None);
+        assert_eq!(cylinder.y_maximum(), None);
+        assert!(!cylinder.closed_bot());
+    }
 }
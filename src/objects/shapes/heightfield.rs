@@ -0,0 +1,461 @@
+use std::sync::Arc;
+
+use crate::collections::{Point, Vector};
+use crate::objects::*;
+use crate::scenes::Canvas;
+use crate::utils::{BuildError, Buildable, ConsumingBuilder, EPSILON};
+
+// Terrain from a regular grid of heights. In object space the grid covers the
+// unit square from x = 0 to 1 and z = 0 to 1, with the first row of heights
+// along z = 0 and the first column along x = 0, and heights taken as y. Each
+// grid cell is split into two triangles, and rays walk the cells they pass
+// over in order (2D DDA) so only those triangles are ever tested.
+#[derive(Debug)]
+pub struct Heightfield {
+    heights: Vec<Vec<f64>>,
+    height_range: [f64; 2],
+    frame_transformation: Transform,
+    material: Arc<Material>,
+    name: Option<String>,
+    bounds: Bounds,
+}
+
+impl Heightfield {
+    // heights from the luminance of each pixel of an image, so that white is
+    // a height of 1; any image the canvas can decode will do
+    pub fn heights_from_canvas(image: &Canvas) -> Vec<Vec<f64>> {
+        (0..image.height())
+            .map(|row| {
+                (0..image.width())
+                    .map(|column| match image.get_pixel([column, row]) {
+                        Some(pixel) => pixel.colour().luminance(),
+                        None => 0.0,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn local_bounding_box(&self) -> BoundingBox {
+        BoundingBox::from_axial_bounds([0.0, 1.0], self.height_range, [0.0, 1.0])
+    }
+
+    pub fn heights(&self) -> &Vec<Vec<f64>> {
+        &self.heights
+    }
+
+    fn columns(&self) -> usize {
+        self.heights[0].len()
+    }
+
+    fn rows(&self) -> usize {
+        self.heights.len()
+    }
+
+    fn vertex(&self, column: usize, row: usize) -> Point {
+        Point::new(
+            column as f64 / (self.columns() - 1) as f64,
+            self.heights[row][column],
+            row as f64 / (self.rows() - 1) as f64,
+        )
+    }
+
+    // the two triangles of a cell, split along the diagonal from its corner
+    // nearest the origin to the opposite one
+    fn cell_triangles(&self, column: usize, row: usize) -> [[Point; 3]; 2] {
+        let p00 = self.vertex(column, row);
+        let p10 = self.vertex(column + 1, row);
+        let p01 = self.vertex(column, row + 1);
+        let p11 = self.vertex(column + 1, row + 1);
+        [[p00, p10, p11], [p00, p11, p01]]
+    }
+
+    fn cell_at(&self, grid_x: f64, grid_z: f64) -> (usize, usize) {
+        (
+            (grid_x.floor().max(0.0) as usize).min(self.columns() - 2),
+            (grid_z.floor().max(0.0) as usize).min(self.rows() - 2),
+        )
+    }
+
+    fn intersect_triangle(local_ray: &Ray, [p1, p2, p3]: [Point; 3]) -> Option<f64> {
+        let edge1 = p2 - p1;
+        let edge2 = p3 - p1;
+        let dir_cross_e2 = local_ray.direction.cross(edge2);
+        let det = edge1.dot(dir_cross_e2);
+        if det.abs() < EPSILON {
+            return None;
+        }
+
+        let f = 1.0 / det;
+        let p1_to_origin = local_ray.origin - p1;
+        let u = f * p1_to_origin.dot(dir_cross_e2);
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let origin_cross_e1 = p1_to_origin.cross(edge1);
+        let v = f * local_ray.direction.dot(origin_cross_e1);
+        if v < 0.0 || (u + v) > 1.0 {
+            return None;
+        }
+
+        Some(f * edge2.dot(origin_cross_e1))
+    }
+}
+
+impl PrimitiveShape for Heightfield {
+    fn frame_transformation(&self) -> &Transform {
+        &self.frame_transformation
+    }
+
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.bounds = Bounds::new(
+            self.local_bounding_box()
+                .transform(&self.frame_transformation),
+        );
+        true
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    // tries every triangle of the grid, so is slow for large heightfields
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        (0..self.rows() - 1)
+            .flat_map(|row| (0..self.columns() - 1).map(move |column| (column, row)))
+            .flat_map(|(column, row)| self.cell_triangles(column, row))
+            .map(|vertices| Triangle::closest_point(vertices, local_point))
+            .min_by(|a, b| {
+                (*a - local_point)
+                    .magnitude()
+                    .total_cmp(&(*b - local_point).magnitude())
+            })
+    }
+
+    // face normal of the triangle beneath the point, always pointing up
+    fn local_normal_at(&self, local_point: Point, _: Option<(f64, f64)>) -> Vector {
+        let grid_x = local_point.x * (self.columns() - 1) as f64;
+        let grid_z = local_point.z * (self.rows() - 1) as f64;
+        let (column, row) = self.cell_at(grid_x, grid_z);
+        let [upper, lower] = self.cell_triangles(column, row);
+        let [p1, p2, p3] = if grid_x - column as f64 >= grid_z - row as f64 {
+            upper
+        } else {
+            lower
+        };
+        let normal = (p3 - p1).cross(p2 - p1);
+        match normal.y < 0.0 {
+            true => -normal,
+            false => normal,
+        }
+    }
+
+    // the terrain is a sheet, so every crossing along the ray is reported
+    fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
+        collect_local_intersections(self, local_ray)
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
+        let Some((t_entry, t_exit)) = self.local_bounding_box().clip_ray(local_ray) else {
+            return;
+        };
+
+        let cell_width = 1.0 / (self.columns() - 1) as f64;
+        let cell_depth = 1.0 / (self.rows() - 1) as f64;
+        let entry = local_ray.position(t_entry);
+        let (mut column, mut row) = self.cell_at(entry.x / cell_width, entry.z / cell_depth);
+
+        // distance in t to the next cell boundary along each axis, and between
+        // successive boundaries
+        let axis_walk = |origin: f64, direction: f64, cell: usize, size: f64| {
+            if direction.abs() < EPSILON {
+                return (0, f64::INFINITY, f64::INFINITY);
+            }
+            let step: isize = if direction > 0.0 { 1 } else { -1 };
+            let boundary = (cell as f64 + if step > 0 { 1.0 } else { 0.0 }) * size;
+            (
+                step,
+                (boundary - origin) / direction,
+                size / direction.abs(),
+            )
+        };
+        let (step_x, mut t_next_x, t_delta_x) = axis_walk(
+            local_ray.origin.x,
+            local_ray.direction.x,
+            column,
+            cell_width,
+        );
+        let (step_z, mut t_next_z, t_delta_z) =
+            axis_walk(local_ray.origin.z, local_ray.direction.z, row, cell_depth);
+
+        let mut last_hit = None;
+        loop {
+            let mut hits: Vec<f64> = self
+                .cell_triangles(column, row)
+                .into_iter()
+                .filter_map(|triangle| Heightfield::intersect_triangle(local_ray, triangle))
+                .filter(|&t| t >= t_entry - EPSILON && t <= t_exit + EPSILON)
+                .collect();
+            hits.sort_by(f64::total_cmp);
+            // cells are walked in order along the ray, so a crossing on an edge
+            // shared with the last triangle is the one just reported
+            for t in hits {
+                if last_hit.is_none_or(|last: f64| (t - last).abs() >= EPSILON) {
+                    intersections.push(Coordinates::new(t, None));
+                    last_hit = Some(t);
+                }
+            }
+
+            if t_exit < t_entry || t_next_x.min(t_next_z) > t_exit {
+                return;
+            }
+            if t_next_x < t_next_z {
+                match column.checked_add_signed(step_x) {
+                    Some(next) if next < self.columns() - 1 => column = next,
+                    _ => return,
+                }
+                t_next_x += t_delta_x;
+            } else {
+                match row.checked_add_signed(step_z) {
+                    Some(next) if next < self.rows() - 1 => row = next,
+                    _ => return,
+                }
+                t_next_z += t_delta_z;
+            }
+        }
+    }
+}
+
+impl Bounded for Heightfield {
+    fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct HeightfieldBuilder {
+    heights: Option<Vec<Vec<f64>>>,
+    frame_transformation: Option<Transform>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
+}
+
+impl HeightfieldBuilder {
+    pub fn set_heights(mut self, heights: Vec<Vec<f64>>) -> HeightfieldBuilder {
+        self.heights = Some(heights);
+        self
+    }
+
+    pub fn set_frame_transformation(
+        mut self,
+        frame_transformation: Transform,
+    ) -> HeightfieldBuilder {
+        self.frame_transformation = Some(frame_transformation);
+        self
+    }
+
+    pub fn set_material(mut self, material: Material) -> HeightfieldBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> HeightfieldBuilder {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn set_name(mut self, name: &str) -> HeightfieldBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+}
+
+impl Buildable for Heightfield {
+    type Builder = HeightfieldBuilder;
+
+    fn builder() -> Self::Builder {
+        HeightfieldBuilder::default()
+    }
+}
+
+impl ConsumingBuilder for HeightfieldBuilder {
+    type Built = Heightfield;
+
+    fn build(self) -> Self::Built {
+        self.try_build().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // the heights must form a rectangular grid of at least 2 by 2; without
+    // heights, the heightfield is a flat unit square
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        let heights = self.heights.unwrap_or(vec![vec![0.0; 2]; 2]);
+        if heights.len() < 2 || heights[0].len() < 2 {
+            return Err(BuildError::InvalidValue {
+                field: "heights",
+                reason: "needs at least 2 rows and 2 columns".to_string(),
+            });
+        }
+        if heights.iter().any(|row| row.len() != heights[0].len()) {
+            return Err(BuildError::InvalidValue {
+                field: "heights",
+                reason: "every row must have the same length".to_string(),
+            });
+        }
+        check_frame_transformation(&self.frame_transformation)?;
+
+        let (lowest, highest) = heights
+            .iter()
+            .flatten()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &h| {
+                (low.min(h), high.max(h))
+            });
+        let frame_transformation = self.frame_transformation.unwrap_or_default();
+        let material = self.material.unwrap_or_default();
+        let bounds = Bounds::new(
+            BoundingBox::from_axial_bounds([0.0, 1.0], [lowest, highest], [0.0, 1.0])
+                .transform(&frame_transformation),
+        );
+
+        Ok(Heightfield {
+            heights,
+            height_range: [lowest, highest],
+            frame_transformation,
+            material,
+            name: self.name,
+            bounds,
+        })
+    }
+}
+
+impl From<Heightfield> for Shape {
+    fn from(heightfield: Heightfield) -> Shape {
+        Shape::Primitive(Box::new(heightfield))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::approx_eq;
+
+    #[test]
+    fn ray_hits_flat_heightfield() {
+        let heightfield = Heightfield::builder().build();
+        let ray = Ray::new(Point::new(0.3, 5.0, 0.7), Vector::new(0.0, -1.0, 0.0));
+        let intersections = heightfield.local_intersect(&ray);
+        assert_eq!(intersections.len(), 1);
+        approx_eq!(intersections[0].t(), 5.0);
+        assert_eq!(
+            heightfield.local_normal_at(Point::new(0.3, 0.0, 0.7), None),
+            Vector::new(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn ray_hits_sloped_heightfield() {
+        // rises from y = 0 along z = 0 to y = 1 along z = 1
+        let heightfield = Heightfield::builder()
+            .set_heights(vec![vec![0.0, 0.0], vec![1.0, 1.0]])
+            .build();
+        let ray = Ray::new(Point::new(0.5, 5.0, 0.25), Vector::new(0.0, -1.0, 0.0));
+        approx_eq!(heightfield.local_intersect(&ray)[0].t(), 4.75);
+        // starting inside the bounds but above the terrain
+        let ray = Ray::new(Point::new(0.5, 0.9, 0.25), Vector::new(0.0, -1.0, 0.0));
+        approx_eq!(heightfield.local_intersect(&ray)[0].t(), 0.65);
+
+        let normal = heightfield
+            .local_normal_at(Point::new(0.5, 0.25, 0.25), None)
+            .normalise();
+        approx_eq!(normal.x, 0.0);
+        approx_eq!(normal.y, f64::sqrt(0.5));
+        approx_eq!(normal.z, -f64::sqrt(0.5));
+    }
+
+    #[test]
+    fn ray_walks_cells_to_distant_hit() {
+        let heightfield = Heightfield::builder()
+            .set_heights(vec![
+                vec![0.0, 0.0, 0.0],
+                vec![0.0, 1.0, 0.0],
+                vec![0.0, 0.0, 0.0],
+            ])
+            .build();
+        // the ridge along z = 0.5 climbs from y = 0 at x = 0 to y = 1 at x = 0.5
+        let ray = Ray::new(Point::new(-1.0, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0));
+        approx_eq!(heightfield.local_intersect(&ray)[0].t(), 1.25);
+        let ray = Ray::new(Point::new(2.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0));
+        approx_eq!(heightfield.local_intersect(&ray)[0].t(), 1.25);
+
+        let over = Ray::new(Point::new(-1.0, 2.0, 0.5), Vector::new(1.0, 0.0, 0.0));
+        assert!(heightfield.local_intersect(&over).is_empty());
+        let beside = Ray::new(Point::new(-1.0, 0.0, 0.1), Vector::new(1.0, 1.0, 0.0));
+        assert!(heightfield.local_intersect(&beside).is_empty());
+    }
+
+    #[test]
+    fn ray_reports_every_crossing() {
+        let heightfield = Heightfield::builder()
+            .set_heights(vec![
+                vec![0.0, 0.0, 0.0],
+                vec![0.0, 1.0, 0.0],
+                vec![0.0, 0.0, 0.0],
+            ])
+            .build();
+        // up one side of the ridge and down the other
+        let ray = Ray::new(Point::new(-1.0, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0));
+        let intersections = heightfield.local_intersect(&ray);
+        assert_eq!(intersections.len(), 2);
+        approx_eq!(intersections[0].t(), 1.25);
+        approx_eq!(intersections[1].t(), 1.75);
+
+        // crossings behind the origin are kept too
+        let flat = Heightfield::builder().build();
+        let ray = Ray::new(Point::new(0.3, 0.5, 0.7), Vector::new(0.0, 1.0, 0.0));
+        let intersections = flat.local_intersect(&ray);
+        assert_eq!(intersections.len(), 1);
+        approx_eq!(intersections[0].t(), -0.5);
+    }
+
+    #[test]
+    fn load_heights_from_image() {
+        let ppm = b"P3\n# heights\n2 2\n255\n255 255 255  0 0 0\n0 0 0  255 255 255\n";
+        let heights = Heightfield::heights_from_canvas(&Canvas::from_ppm(ppm).unwrap());
+        assert_eq!(heights.len(), 2);
+        approx_eq!(heights[0][0], 1.0);
+        approx_eq!(heights[0][1], 0.0);
+        approx_eq!(heights[1][0], 0.0);
+        approx_eq!(heights[1][1], 1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn build_heightfield_from_single_row() {
+        Heightfield::builder()
+            .set_heights(vec![vec![0.0, 1.0]])
+            .build();
+    }
+
+    #[test]
+    fn try_build_reports_invalid_grids() {
+        let field_of = |heights: Vec<Vec<f64>>| match Heightfield::builder()
+            .set_heights(heights)
+            .try_build()
+        {
+            Err(BuildError::InvalidValue { field, .. }) => field,
+            other => panic!("expected an invalid value, got {:?}", other),
+        };
+        assert_eq!(field_of(vec![vec![0.0, 1.0]]), "heights");
+        assert_eq!(field_of(vec![vec![0.0, 1.0], vec![0.0]]), "heights");
+        assert!(Heightfield::builder().try_build().is_ok());
+    }
+}
@@ -0,0 +1,436 @@
+use std::sync::Arc;
+
+use crate::collections::{Point, Vector};
+use crate::objects::*;
+use crate::utils::{BuildError, Buildable, ConsumingBuilder, EPSILON};
+
+// Surface swept by revolving a profile of (r, y) points around the y axis.
+// Each pair of consecutive points sweeps out a band of a cone, a cylinder or
+// (when both share a y) a flat ring. Normals face to the right of the
+// direction of travel along the profile in the (r, y) plane, so a profile
+// that starts on the axis at the bottom and ends on the axis at the top
+// outlines a solid whose normals point outwards.
+#[derive(Debug)]
+pub struct Lathe {
+    profile: Vec<(f64, f64)>,
+    frame_transformation: Transform,
+    material: Arc<Material>,
+    name: Option<String>,
+    bounds: Bounds,
+}
+
+impl Lathe {
+    pub fn profile(&self) -> &Vec<(f64, f64)> {
+        &self.profile
+    }
+
+    // as wide as the widest point of the profile, all the way round
+    fn local_bounding_box(profile: &[(f64, f64)]) -> BoundingBox {
+        let max_radius = profile.iter().map(|&(r, _)| r).fold(0.0, f64::max);
+        let y_range = profile.iter().fold(
+            [f64::INFINITY, f64::NEG_INFINITY],
+            |[low, high], &(_, y)| [low.min(y), high.max(y)],
+        );
+        BoundingBox::from_axial_bounds(
+            [-max_radius, max_radius],
+            y_range,
+            [-max_radius, max_radius],
+        )
+    }
+
+    fn segments(&self) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+        self.profile.windows(2).map(|pair| (pair[0], pair[1]))
+    }
+
+    fn intersect_segment(local_ray: &Ray, (r0, y0): (f64, f64), (r1, y1): (f64, f64)) -> Vec<f64> {
+        let &Ray { origin, direction } = local_ray;
+
+        // flat ring between two radii
+        if (y1 - y0).abs() < EPSILON {
+            if direction.y.abs() < EPSILON {
+                return vec![];
+            }
+            let t = (y0 - origin.y) / direction.y;
+            let position = local_ray.position(t);
+            let radius = (position.x.powi(2) + position.z.powi(2)).sqrt();
+            return match f64::min(r0, r1) <= radius && radius <= f64::max(r0, r1) {
+                true => vec![t],
+                false => vec![],
+            };
+        }
+
+        // band where the radius varies linearly with height, r = a + b * y
+        let b = (r1 - r0) / (y1 - y0);
+        let a = r0 - b * y0;
+        let radius_at_origin = a + b * origin.y;
+        let qa = direction.x.powi(2) + direction.z.powi(2) - (b * direction.y).powi(2);
+        let qb = 2.0 * (origin.x * direction.x + origin.z * direction.z)
+            - 2.0 * b * direction.y * radius_at_origin;
+        let qc = origin.x.powi(2) + origin.z.powi(2) - radius_at_origin.powi(2);
+
+        let roots = if qa.abs() < EPSILON {
+            if qb.abs() < EPSILON {
+                return vec![];
+            }
+            vec![-qc / qb]
+        } else {
+            let disc = qb.powi(2) - 4.0 * qa * qc;
+            if disc < 0.0 {
+                return vec![];
+            }
+            vec![
+                (-qb - disc.sqrt()) / (2.0 * qa),
+                (-qb + disc.sqrt()) / (2.0 * qa),
+            ]
+        };
+
+        // keep hits within the band's height, on the nappe with positive radius
+        let (y_low, y_high) = (f64::min(y0, y1), f64::max(y0, y1));
+        roots
+            .into_iter()
+            .filter(|&t| {
+                let y = origin.y + t * direction.y;
+                y_low <= y && y <= y_high && a + b * y >= -EPSILON
+            })
+            .collect()
+    }
+
+    // point of a profile segment nearest to a point in the (r, y) plane
+    fn closest_on_segment(
+        (r, y): (f64, f64),
+        (r0, y0): (f64, f64),
+        (r1, y1): (f64, f64),
+    ) -> (f64, f64) {
+        let (dr, dy) = (r1 - r0, y1 - y0);
+        let length = dr.powi(2) + dy.powi(2);
+        let s = match length < EPSILON {
+            true => 0.0,
+            false => (((r - r0) * dr + (y - y0) * dy) / length).clamp(0.0, 1.0),
+        };
+        (r0 + s * dr, y0 + s * dy)
+    }
+
+    // squared distance in the (r, y) plane from a point to a profile segment
+    fn distance_to_segment((r, y): (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+        let (closest_r, closest_y) = Lathe::closest_on_segment((r, y), start, end);
+        (r - closest_r).powi(2) + (y - closest_y).powi(2)
+    }
+}
+
+impl PrimitiveShape for Lathe {
+    fn frame_transformation(&self) -> &Transform {
+        &self.frame_transformation
+    }
+
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.bounds = Bounds::new(
+            Lathe::local_bounding_box(&self.profile).transform(&self.frame_transformation),
+        );
+        true
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn has_interior(&self) -> bool {
+        true
+    }
+
+    // counts the profile segments crossed going outwards from the point in
+    // the (r, y) plane, so a profile that starts and ends on the axis
+    // encloses the points an odd number of crossings away from the outside
+    fn local_contains_point(&self, local_point: Point) -> Option<bool> {
+        let (r, y) = (local_point.x.hypot(local_point.z), local_point.y);
+        if self
+            .segments()
+            .any(|(start, end)| Lathe::distance_to_segment((r, y), start, end) <= EPSILON.powi(2))
+        {
+            return Some(true);
+        }
+        let crossings = self
+            .segments()
+            .filter(|&((r0, y0), (r1, y1))| {
+                (y0 > y) != (y1 > y) && r < r0 + (y - y0) / (y1 - y0) * (r1 - r0)
+            })
+            .count();
+        Some(crossings % 2 == 1)
+    }
+
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        let radius = local_point.x.hypot(local_point.z);
+        let (cos, sin) = match radius < EPSILON {
+            true => (1.0, 0.0),
+            false => (local_point.x / radius, local_point.z / radius),
+        };
+        self.segments()
+            .map(|(start, end)| Lathe::closest_on_segment((radius, local_point.y), start, end))
+            .min_by(|&(r0, y0), &(r1, y1)| {
+                (r0 - radius)
+                    .hypot(y0 - local_point.y)
+                    .total_cmp(&(r1 - radius).hypot(y1 - local_point.y))
+            })
+            .map(|(r, y)| Point::new(cos * r, y, sin * r))
+    }
+
+    // normal of the profile segment nearest the point, swept to the point's
+    // angle around the axis
+    fn local_normal_at(&self, local_point: Point, _: Option<(f64, f64)>) -> Vector {
+        let radius = (local_point.x.powi(2) + local_point.z.powi(2)).sqrt();
+        let ((r0, y0), (r1, y1)) = self
+            .segments()
+            .map(|(start, end)| {
+                let distance = Lathe::distance_to_segment((radius, local_point.y), start, end);
+                (distance, (start, end))
+            })
+            .reduce(|nearest, segment| match segment.0 < nearest.0 {
+                true => segment,
+                false => nearest,
+            })
+            .unwrap()
+            .1;
+
+        let (normal_r, normal_y) = (y1 - y0, r0 - r1);
+        if radius < EPSILON {
+            return Vector::new(0.0, normal_y.signum(), 0.0);
+        }
+        Vector::new(
+            local_point.x / radius * normal_r,
+            normal_y,
+            local_point.z / radius * normal_r,
+        )
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
+        collect_local_intersections(self, local_ray)
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
+        intersections.extend(
+            self.segments()
+                .flat_map(|(start, end)| Lathe::intersect_segment(local_ray, start, end))
+                .map(|t| Coordinates::new(t, None)),
+        );
+    }
+}
+
+impl Bounded for Lathe {
+    fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LatheBuilder {
+    profile: Option<Vec<(f64, f64)>>,
+    frame_transformation: Option<Transform>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
+}
+
+impl LatheBuilder {
+    // points are (radius, height) pairs
+    pub fn set_profile(mut self, profile: Vec<(f64, f64)>) -> LatheBuilder {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn set_frame_transformation(mut self, frame_transformation: Transform) -> LatheBuilder {
+        self.frame_transformation = Some(frame_transformation);
+        self
+    }
+
+    pub fn set_material(mut self, material: Material) -> LatheBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> LatheBuilder {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn set_name(mut self, name: &str) -> LatheBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+}
+
+impl Buildable for Lathe {
+    type Builder = LatheBuilder;
+
+    fn builder() -> Self::Builder {
+        LatheBuilder::default()
+    }
+}
+
+impl ConsumingBuilder for LatheBuilder {
+    type Built = Lathe;
+
+    fn build(self) -> Self::Built {
+        self.try_build().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // the profile must have at least two points, none with a negative radius
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        let profile = self.profile.ok_or(BuildError::MissingField("profile"))?;
+        if profile.len() < 2 {
+            return Err(BuildError::InvalidValue {
+                field: "profile",
+                reason: "needs at least 2 points".to_string(),
+            });
+        }
+        if profile.iter().any(|&(r, _)| r < 0.0) {
+            return Err(BuildError::InvalidValue {
+                field: "profile",
+                reason: "radii must not be negative".to_string(),
+            });
+        }
+        check_frame_transformation(&self.frame_transformation)?;
+
+        let frame_transformation = self.frame_transformation.unwrap_or_default();
+        let material = self.material.unwrap_or_default();
+        let bounds =
+            Bounds::new(Lathe::local_bounding_box(&profile).transform(&frame_transformation));
+
+        Ok(Lathe {
+            profile,
+            frame_transformation,
+            material,
+            name: self.name,
+            bounds,
+        })
+    }
+}
+
+impl From<Lathe> for Shape {
+    fn from(lathe: Lathe) -> Shape {
+        Shape::Primitive(Box::new(lathe))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::approx_eq;
+
+    // closed cylinder of radius 1 from y = 0 to y = 2
+    fn drum() -> Lathe {
+        Lathe::builder()
+            .set_profile(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 2.0), (0.0, 2.0)])
+            .build()
+    }
+
+    fn sorted_ts(lathe: &Lathe, ray: &Ray) -> Vec<f64> {
+        let mut ts: Vec<f64> = lathe
+            .local_intersect(ray)
+            .iter()
+            .map(|coordinates| coordinates.t())
+            .collect();
+        ts.sort_by(f64::total_cmp);
+        ts
+    }
+
+    #[test]
+    fn ray_intersects_drum_walls_and_caps() {
+        let drum = drum();
+        let through_walls = Ray::new(Point::new(0.0, 1.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(sorted_ts(&drum, &through_walls), vec![4.0, 6.0]);
+        let through_caps = Ray::new(Point::new(0.5, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(sorted_ts(&drum, &through_caps), vec![3.0, 5.0]);
+        let over = Ray::new(Point::new(0.0, 3.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(drum.local_intersect(&over).is_empty());
+    }
+
+    #[test]
+    fn ray_intersects_cone_band() {
+        // widens from radius 0 at y = 0 to radius 1 at y = 1
+        let funnel = Lathe::builder()
+            .set_profile(vec![(0.0, 0.0), (1.0, 1.0)])
+            .build();
+        let ray = Ray::new(Point::new(0.0, 0.5, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let ts = sorted_ts(&funnel, &ray);
+        assert_eq!(ts.len(), 2);
+        approx_eq!(ts[0], 4.5);
+        approx_eq!(ts[1], 5.5);
+        // the mirrored nappe below the apex is not part of the surface
+        let below = Ray::new(Point::new(0.0, -0.5, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(funnel.local_intersect(&below).is_empty());
+    }
+
+    #[test]
+    fn normals_on_drum() {
+        let drum = drum();
+        let test_cases: [(Point, Vector); 4] = [
+            (Point::new(1.0, 1.0, 0.0), Vector::new(2.0, 0.0, 0.0)),
+            (Point::new(0.0, 1.5, -1.0), Vector::new(0.0, 0.0, -2.0)),
+            (Point::new(0.5, 2.0, 0.0), Vector::new(0.0, 1.0, 0.0)),
+            (Point::new(0.0, 0.0, 0.5), Vector::new(0.0, -1.0, 0.0)),
+        ];
+        for (point, normal) in test_cases {
+            assert_eq!(drum.local_normal_at(point, None), normal);
+        }
+    }
+
+    #[test]
+    fn lathe_bounds() {
+        let vase = Lathe::builder()
+            .set_profile(vec![(0.0, -1.0), (2.0, 0.0), (0.5, 3.0)])
+            .build();
+        let (x_range, y_range, z_range) = vase.bounds().bounding_box().axial_bounds();
+        assert_eq!(x_range, [-2.0, 2.0]);
+        assert_eq!(y_range, [-1.0, 3.0]);
+        assert_eq!(z_range, [-2.0, 2.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn build_lathe_with_single_point() {
+        Lathe::builder().set_profile(vec![(1.0, 0.0)]).build();
+    }
+
+    #[test]
+    fn try_build_reports_invalid_profiles() {
+        assert_eq!(
+            Lathe::builder().try_build().err(),
+            Some(BuildError::MissingField("profile"))
+        );
+        let field_of =
+            |profile: Vec<(f64, f64)>| match Lathe::builder().set_profile(profile).try_build() {
+                Err(BuildError::InvalidValue { field, .. }) => field,
+                other => panic!("expected an invalid value, got {:?}", other),
+            };
+        assert_eq!(field_of(vec![(1.0, 0.0)]), "profile");
+        assert_eq!(field_of(vec![(1.0, 0.0), (-1.0, 1.0)]), "profile");
+    }
+
+    #[test]
+    fn points_inside_drum() {
+        let drum = drum();
+        assert_eq!(
+            drum.local_contains_point(Point::new(0.5, 1.0, 0.5)),
+            Some(true)
+        );
+        assert_eq!(
+            drum.local_contains_point(Point::new(0.0, 2.0, 0.0)),
+            Some(true)
+        );
+        assert_eq!(
+            drum.local_contains_point(Point::new(0.8, 1.0, 0.8)),
+            Some(false)
+        );
+        assert_eq!(
+            drum.local_contains_point(Point::new(0.0, -0.5, 0.0)),
+            Some(false)
+        );
+    }
+}
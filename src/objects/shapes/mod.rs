@@ -1,16 +1,19 @@
+pub mod axis_aligned_box;
 pub mod cone;
 pub mod cube;
 pub mod cylinder;
+pub mod heightfield;
+pub mod lathe;
 pub mod plane;
+pub mod sdf_shape;
 pub mod shape;
 pub mod smooth_triangle;
 pub mod sphere;
 pub mod triangle;
 
 // crate-level re-exports
-pub(crate) use cone::*;
+pub(crate) use axis_aligned_box::*;
 pub(crate) use cube::*;
-pub(crate) use cylinder::*;
 pub(crate) use plane::*;
 pub(crate) use shape::*;
 pub(crate) use smooth_triangle::*;
@@ -19,11 +22,15 @@ pub(crate) use triangle::*;
 
 // public re-exports (through crate::prelude)
 pub(super) mod prelude {
+    pub use super::axis_aligned_box::AxisAlignedBox;
     pub use super::cone::Cone;
     pub use super::cube::Cube;
     pub use super::cylinder::Cylinder;
+    pub use super::heightfield::Heightfield;
+    pub use super::lathe::Lathe;
     pub use super::plane::Plane;
-    pub use super::shape::Shape;
+    pub use super::sdf_shape::SdfShape;
+    pub use super::shape::{PrimitiveShape, Shape};
     pub use super::smooth_triangle::SmoothTriangle;
     pub use super::sphere::Sphere;
     pub use super::triangle::Triangle;
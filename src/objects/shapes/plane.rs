@@ -1,11 +1,14 @@
+use std::sync::Arc;
+
 use crate::collections::{Point, Vector};
 use crate::objects::*;
-use crate::utils::{Buildable, ConsumingBuilder, EPSILON};
+use crate::utils::{BuildError, Buildable, ConsumingBuilder, EPSILON};
 
 #[derive(Debug)]
 pub struct Plane {
     frame_transformation: Transform,
-    material: Material,
+    material: Arc<Material>,
+    name: Option<String>,
     bounds: Bounds,
 }
 
@@ -22,21 +25,53 @@ impl PrimitiveShape for Plane {
         &self.frame_transformation
     }
 
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.bounds =
+            Bounds::new(Plane::PRIMITIVE_BOUNDING_BOX.transform(&self.frame_transformation));
+        true
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
 
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn has_interior(&self) -> bool {
+        true
+    }
+
+    // the half-space below the plane, away from its normal
+    fn local_contains_point(&self, local_point: Point) -> Option<bool> {
+        Some(local_point.y <= EPSILON)
+    }
+
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        Some(Point::new(local_point.x, 0.0, local_point.z))
+    }
+
     fn local_normal_at(&self, _local_point: Point, _: Option<(f64, f64)>) -> Vector {
         Vector::new(0.0, 1.0, 0.0)
     }
 
     fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
+        collect_local_intersections(self, local_ray)
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
         if local_ray.direction.y.abs() < EPSILON {
-            return vec![];
+            return;
         }
 
         let t = -local_ray.origin.y / local_ray.direction.y;
-        vec![t].iter().map(|&t| Coordinates::new(t, None)).collect()
+        intersections.push(Coordinates::new(t, None));
     }
 }
 
@@ -49,7 +84,8 @@ impl Bounded for Plane {
 #[derive(Debug, Default)]
 pub struct PlaneBuilder {
     frame_transformation: Option<Transform>,
-    material: Option<Material>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
 }
 
 impl PlaneBuilder {
@@ -59,9 +95,19 @@ impl PlaneBuilder {
     }
 
     pub fn set_material(mut self, material: Material) -> PlaneBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> PlaneBuilder {
         self.material = Some(material);
         self
     }
+
+    pub fn set_name(mut self, name: &str) -> PlaneBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
 }
 
 impl Buildable for Plane {
@@ -80,18 +126,23 @@ impl ConsumingBuilder for PlaneBuilder {
         let material = self.material.unwrap_or_default();
         let bounds = Bounds::new(Plane::PRIMITIVE_BOUNDING_BOX.transform(&frame_transformation));
 
-        let plane = Plane {
+        Plane {
             frame_transformation,
             material,
+            name: self.name,
             bounds,
-        };
-        plane
+        }
+    }
+
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        check_frame_transformation(&self.frame_transformation)?;
+        Ok(self.build())
     }
 }
 
-impl Into<Shape> for Plane {
-    fn into(self) -> Shape {
-        Shape::Primitive(Box::new(self))
+impl From<Plane> for Shape {
+    fn from(plane: Plane) -> Shape {
+        Shape::Primitive(Box::new(plane))
     }
 }
 
@@ -105,9 +156,9 @@ mod tests {
     #[test]
     fn normal_of_plane() {
         let default_plane = Plane::builder().build();
-        let normal1 = default_plane.normal_at(Point::new(0.0, 0.0, 0.0), None, &vec![]);
-        let normal2 = default_plane.normal_at(Point::new(10.0, 0.0, -10.0), None, &vec![]);
-        let normal3 = default_plane.normal_at(Point::new(-5.0, 0.0, 150.0), None, &vec![]);
+        let normal1 = default_plane.normal_at(Point::new(0.0, 0.0, 0.0), None, &[]);
+        let normal2 = default_plane.normal_at(Point::new(10.0, 0.0, -10.0), None, &[]);
+        let normal3 = default_plane.normal_at(Point::new(-5.0, 0.0, 150.0), None, &[]);
         let resulting_vector = Vector::new(0.0, 1.0, 0.0);
         assert_eq!(normal1, resulting_vector);
         assert_eq!(normal2, resulting_vector);
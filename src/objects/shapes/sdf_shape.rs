@@ -0,0 +1,362 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::collections::{Point, Vector};
+use crate::objects::*;
+use crate::scenes::stats;
+use crate::utils::floats::EPSILON;
+use crate::utils::{BuildError, Buildable, ConsumingBuilder};
+
+pub type DistanceFunction = Arc<dyn Fn(Point) -> f64 + Send + Sync>;
+
+// Shape defined implicitly by a signed distance function: negative inside,
+// positive outside and zero on the surface. Rays are intersected by sphere
+// tracing within a bounding box in object space, which the surface must lie
+// inside. The function must never overestimate the distance to the surface or
+// marching can step straight through it. Rays that use up their steps before
+// leaving the box are taken to miss, and are counted in the render stats.
+pub struct SdfShape {
+    distance: DistanceFunction,
+    max_steps: usize,
+    minimum: Point,
+    maximum: Point,
+    frame_transformation: Transform,
+    material: Arc<Material>,
+    name: Option<String>,
+    bounds: Bounds,
+}
+
+impl SdfShape {
+    const DEFAULT_MAX_STEPS: usize = 512;
+    // tighter than the offset used for shadow and reflection rays, so those
+    // rays do not immediately find the surface they start from
+    const HIT_DISTANCE: f64 = EPSILON / 10.0;
+    // bounds the step off a surface met almost edge on
+    const MIN_COS: f64 = 1e-3;
+
+    pub fn distance_at(&self, local_point: Point) -> f64 {
+        (self.distance)(local_point)
+    }
+}
+
+impl fmt::Debug for SdfShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SdfShape")
+            .field("max_steps", &self.max_steps)
+            .field("minimum", &self.minimum)
+            .field("maximum", &self.maximum)
+            .field("frame_transformation", &self.frame_transformation)
+            .field("material", &self.material)
+            .field("name", &self.name)
+            .field("bounds", &self.bounds)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PrimitiveShape for SdfShape {
+    fn frame_transformation(&self) -> &Transform {
+        &self.frame_transformation
+    }
+
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.bounds = Bounds::new(
+            BoundingBox::from_anchors(vec![self.minimum, self.maximum])
+                .transform(&self.frame_transformation),
+        );
+        true
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn has_interior(&self) -> bool {
+        true
+    }
+
+    fn local_contains_point(&self, local_point: Point) -> Option<bool> {
+        Some(self.distance_at(local_point) <= EPSILON)
+    }
+
+    // one step back along the gradient by the distance, which lands on the
+    // surface wherever the function gives the exact distance
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        Some(local_point - self.local_normal_at(local_point, None) * self.distance_at(local_point))
+    }
+
+    // gradient of the distance field by central differences
+    fn local_normal_at(&self, local_point: Point, _: Option<(f64, f64)>) -> Vector {
+        let slope = |offset: Vector| {
+            self.distance_at(local_point + offset * EPSILON)
+                - self.distance_at(local_point - offset * EPSILON)
+        };
+        Vector::new(
+            slope(Vector::new(1.0, 0.0, 0.0)),
+            slope(Vector::new(0.0, 1.0, 0.0)),
+            slope(Vector::new(0.0, 0.0, 1.0)),
+        )
+        .normalise()
+    }
+
+    // every surface crossing within the bounding box, found by stepping along
+    // the ray by the distance to the nearest surface
+    fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
+        collect_local_intersections(self, local_ray)
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
+        let marching_box = BoundingBox::from_axial_bounds(
+            [self.minimum.x, self.maximum.x],
+            [self.minimum.y, self.maximum.y],
+            [self.minimum.z, self.maximum.z],
+        );
+        let Some((tmin, tmax)) = marching_box.clip_ray(local_ray) else {
+            return;
+        };
+        // distances are measured along the ray, so steps must be scaled back
+        // into t when the direction is not a unit vector
+        let speed = local_ray.direction.magnitude();
+
+        let direction = local_ray.direction / speed;
+
+        // once on the surface, no further hit is reported until the ray is
+        // clear of it again, so rays grazing the surface report it once
+        let mut on_surface = false;
+        let mut t = tmin;
+        for _ in 0..self.max_steps {
+            if t > tmax {
+                return;
+            }
+            let point = local_ray.position(t);
+            let distance = self.distance_at(point).abs();
+            if distance < SdfShape::HIT_DISTANCE && !on_surface {
+                intersections.push(Coordinates::new(t, None));
+                on_surface = true;
+            } else if distance >= 2.0 * SdfShape::HIT_DISTANCE {
+                on_surface = false;
+            }
+            let step = match on_surface {
+                // on the surface the distance makes no headway, so the ray
+                // steps by as far as it must go to clear it, which is further
+                // the more obliquely it meets the surface
+                true => {
+                    let cos = self.local_normal_at(point, None).dot(direction).abs();
+                    2.0 * SdfShape::HIT_DISTANCE / f64::max(cos, SdfShape::MIN_COS)
+                }
+                false => distance,
+            };
+            t += step / speed;
+        }
+        if t <= tmax {
+            stats::count_march_limit();
+        }
+    }
+}
+
+impl Bounded for SdfShape {
+    fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+}
+
+#[derive(Default)]
+pub struct SdfShapeBuilder {
+    distance: Option<DistanceFunction>,
+    max_steps: Option<usize>,
+    corners: Option<(Point, Point)>,
+    frame_transformation: Option<Transform>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
+}
+
+impl SdfShapeBuilder {
+    pub fn set_distance_function(
+        mut self,
+        distance: impl Fn(Point) -> f64 + Send + Sync + 'static,
+    ) -> SdfShapeBuilder {
+        self.distance = Some(Arc::new(distance));
+        self
+    }
+
+    // most steps a ray is marched before it is taken to miss; defaults to 512
+    pub fn set_max_steps(mut self, max_steps: usize) -> SdfShapeBuilder {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    // box in object space that rays are marched through; defaults to the
+    // cube spanning -1 to 1 on each axis
+    pub fn set_bounding_corners(mut self, corner1: Point, corner2: Point) -> SdfShapeBuilder {
+        self.corners = Some((corner1, corner2));
+        self
+    }
+
+    pub fn set_frame_transformation(mut self, frame_transformation: Transform) -> SdfShapeBuilder {
+        self.frame_transformation = Some(frame_transformation);
+        self
+    }
+
+    pub fn set_material(mut self, material: Material) -> SdfShapeBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> SdfShapeBuilder {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn set_name(mut self, name: &str) -> SdfShapeBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+}
+
+impl Buildable for SdfShape {
+    type Builder = SdfShapeBuilder;
+
+    fn builder() -> Self::Builder {
+        SdfShapeBuilder::default()
+    }
+}
+
+impl ConsumingBuilder for SdfShapeBuilder {
+    type Built = SdfShape;
+
+    fn build(self) -> Self::Built {
+        self.try_build().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // a distance function must be set
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        let distance = self
+            .distance
+            .ok_or(BuildError::MissingField("distance function"))?;
+        check_frame_transformation(&self.frame_transformation)?;
+        let (corner1, corner2) = self
+            .corners
+            .unwrap_or((Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0)));
+        let (minimum, maximum) = AxisAlignedBox::span(corner1, corner2);
+
+        let frame_transformation = self.frame_transformation.unwrap_or_default();
+        let material = self.material.unwrap_or_default();
+        let bounds = Bounds::new(
+            BoundingBox::from_anchors(vec![minimum, maximum]).transform(&frame_transformation),
+        );
+
+        Ok(SdfShape {
+            distance,
+            max_steps: self.max_steps.unwrap_or(SdfShape::DEFAULT_MAX_STEPS),
+            minimum,
+            maximum,
+            frame_transformation,
+            material,
+            name: self.name,
+            bounds,
+        })
+    }
+}
+
+impl From<SdfShape> for Shape {
+    fn from(sdf_shape: SdfShape) -> Shape {
+        Shape::Primitive(Box::new(sdf_shape))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{approx_eq, ApproxEq};
+
+    fn unit_sphere() -> SdfShape {
+        SdfShape::builder()
+            .set_distance_function(|point| Vector::new(point.x, point.y, point.z).magnitude() - 1.0)
+            .build()
+    }
+
+    #[test]
+    fn ray_marches_through_sphere() {
+        let sphere = unit_sphere();
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let intersections = sphere.local_intersect(&ray);
+        assert_eq!(intersections.len(), 2);
+        approx_eq!(intersections[0].t(), 4.0);
+        approx_eq!(intersections[1].t(), 6.0);
+    }
+
+    #[test]
+    fn ray_misses_sphere() {
+        let sphere = unit_sphere();
+        let ray = Ray::new(Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(sphere.local_intersect(&ray).is_empty());
+        let outside_bounds = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 1.0, 0.0));
+        assert!(sphere.local_intersect(&outside_bounds).is_empty());
+    }
+
+    #[test]
+    fn normal_on_sphere_from_distance_gradient() {
+        let sphere = unit_sphere();
+        let normal = sphere.local_normal_at(Point::new(0.0, 1.0, 0.0), None);
+        approx_eq!(normal.x, 0.0);
+        approx_eq!(normal.y, 1.0);
+        approx_eq!(normal.z, 0.0);
+
+        let value = f64::sqrt(3.0) / 3.0;
+        let normal = sphere.local_normal_at(Point::new(value, value, value), None);
+        approx_eq!(normal.x, value);
+        approx_eq!(normal.y, value);
+        approx_eq!(normal.z, value);
+    }
+
+    #[test]
+    #[should_panic]
+    fn build_sdf_without_distance_function() {
+        SdfShape::builder().build();
+    }
+
+    #[test]
+    fn try_build_reports_missing_distance_function() {
+        assert!(matches!(
+            SdfShape::builder().try_build(),
+            Err(BuildError::MissingField("distance function"))
+        ));
+    }
+
+    #[test]
+    fn shallow_crossing_reports_surface_once() {
+        let ground = SdfShape::builder()
+            .set_distance_function(|point| point.y)
+            .set_max_steps(5000)
+            .build();
+        // the ray stays within the hit distance of the ground for many times
+        // the old step off the surface
+        let ray = Ray::new(Point::new(-1.0, 0.02, 0.0), Vector::new(1.0, -0.02, 0.0));
+        let intersections = ground.local_intersect(&ray);
+        assert_eq!(intersections.len(), 1);
+        assert!(intersections[0].t().approx_eq_within(&1.0, 1e-4));
+    }
+
+    #[test]
+    fn step_limit_is_counted() {
+        let sphere = SdfShape::builder()
+            .set_distance_function(|point| Vector::new(point.x, point.y, point.z).magnitude() - 1.0)
+            .set_max_steps(2)
+            .build();
+        let ray = Ray::new(Point::new(0.0, 0.5, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let (intersections, stats) = stats::collect(|| sphere.local_intersect(&ray));
+        assert!(intersections.is_empty());
+        assert_eq!(stats.march_limit_count, 1);
+
+        let (_, stats) = stats::collect(|| unit_sphere().local_intersect(&ray));
+        assert_eq!(stats.march_limit_count, 0);
+    }
+}
@@ -1,16 +1,173 @@
 use std::fmt::Debug;
+use std::sync::Arc;
 
-use crate::collections::{Point, Vector};
+use crate::collections::{Colour, Point, Vector};
 use crate::objects::*;
+use crate::scenes::stats;
+use crate::utils::{Buildable, ConsumingBuilder};
 
 #[derive(Debug)]
 pub enum Shape {
     Primitive(Box<dyn PrimitiveShape>),
     Group(Group),
     Csg(Csg),
+    Clipped(ClippedShape),
 }
 
 impl Shape {
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Shape::Primitive(shape) => shape.name(),
+            Shape::Group(group) => group.name(),
+            Shape::Csg(csg) => csg.name(),
+            Shape::Clipped(clipped) => clipped.name(),
+        }
+    }
+
+    // depth-first search through this shape and any shapes nested inside it
+    pub fn find(&self, name: &str) -> Option<&Shape> {
+        if self.name() == Some(name) {
+            return Some(self);
+        }
+        match self {
+            Shape::Primitive(_) => None,
+            Shape::Group(group) => group.find_object(name),
+            Shape::Csg(csg) => csg.lshape().find(name).or_else(|| csg.rshape().find(name)),
+            Shape::Clipped(clipped) => clipped.shape().find(name),
+        }
+    }
+
+    // bounds of enclosing groups and CSG shapes are not recomputed, so replacing
+    // a nested shape should not move it outside of its original bounds (use
+    // apply_transform_to to move one). A group moved here from inside another
+    // should be built into a new group first, as it still takes in the
+    // transformations of the groups it left.
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Shape> {
+        if self.name() == Some(name) {
+            return Some(self);
+        }
+        match self {
+            Shape::Primitive(_) => None,
+            Shape::Group(group) => group.find_object_mut(name),
+            Shape::Csg(csg) => csg.find_object_mut(name),
+            Shape::Clipped(clipped) => clipped.find_object_mut(name),
+        }
+    }
+
+    // Moves the built shape by a transformation applied after its own, as if
+    // it had been built inside a group with that frame transformation. Groups
+    // and primitives take the transformation into their own, and CSG and
+    // clipped shapes pass it on to their parts, so the shape keeps its place
+    // in the tree and its bounds are worked out again. Only primitives that
+    // cannot be moved in place are put inside a group, which takes their name
+    // so that moving them again moves the same group. As with find_mut, the
+    // bounds of any enclosing groups and CSG shapes are left alone (see
+    // apply_transform_to), and a world holding the shape needs its
+    // accelerator refitting.
+    pub fn apply_transform(&mut self, transform: &Transform) {
+        match self {
+            Shape::Primitive(primitive) => {
+                if !primitive.apply_transform(transform) {
+                    let placeholder = Shape::Group(Group::builder().build());
+                    let shape = std::mem::replace(self, placeholder);
+                    let mut wrapper = Group::builder().set_frame_transformation(transform.clone());
+                    if let Some(name) = shape.name() {
+                        wrapper = wrapper.set_name(name);
+                    }
+                    *self = wrapper.add_object(shape).build().into();
+                }
+            }
+            Shape::Group(group) => group.apply_transform(transform),
+            Shape::Csg(csg) => csg.apply_transform(transform),
+            Shape::Clipped(clipped) => clipped.apply_transform(transform),
+        }
+    }
+
+    // Moves the shape with the given name, wherever it is nested in this one
+    // (see find_mut), and works out again the bounds of every group, CSG
+    // shape and clipped shape on the way to it. Returns whether the shape was
+    // found.
+    pub fn apply_transform_to(&mut self, name: &str, transform: &Transform) -> bool {
+        if self.name() == Some(name) {
+            self.apply_transform(transform);
+            return true;
+        }
+        let found = match self {
+            Shape::Primitive(_) => false,
+            Shape::Group(group) => group
+                .objects_mut()
+                .any(|object| object.apply_transform_to(name, transform)),
+            Shape::Csg(csg) => csg
+                .shapes_mut()
+                .into_iter()
+                .any(|shape| shape.apply_transform_to(name, transform)),
+            Shape::Clipped(clipped) => clipped
+                .shapes_mut()
+                .any(|shape| shape.apply_transform_to(name, transform)),
+        };
+        if found {
+            match self {
+                Shape::Primitive(_) => (),
+                Shape::Group(group) => group.refresh_bounds(),
+                Shape::Csg(csg) => csg.refresh_bounds(),
+                Shape::Clipped(clipped) => clipped.refresh_bounds(),
+            }
+        }
+        found
+    }
+
+    pub fn with_transform(mut self, transform: &Transform) -> Shape {
+        self.apply_transform(transform);
+        self
+    }
+
+    // places the groups in this shape inside a group with the given combined
+    // transformation (see Group::enter)
+    pub(crate) fn place_within(&mut self, enclosing_transformation: &Transform) {
+        match self {
+            Shape::Primitive(_) => (),
+            Shape::Group(group) => group.place_within(enclosing_transformation),
+            Shape::Csg(csg) => csg
+                .shapes_mut()
+                .into_iter()
+                .for_each(|shape| shape.place_within(enclosing_transformation)),
+            Shape::Clipped(clipped) => clipped
+                .shapes_mut()
+                .for_each(|shape| shape.place_within(enclosing_transformation)),
+        }
+    }
+
+    // Re-skins every primitive in this shape, including those nested in
+    // groups, CSG shapes and clipped shapes (and the cap of a capped one),
+    // all of them sharing the one material.
+    pub fn set_material(&mut self, material: Material) {
+        self.set_shared_material(Arc::new(material));
+    }
+
+    pub fn set_shared_material(&mut self, material: Arc<Material>) {
+        self.for_each_primitive_mut(&mut |primitive| {
+            primitive.set_shared_material(Arc::clone(&material))
+        });
+    }
+
+    // for edits that depend on the primitive, such as changing only the
+    // colour of each material
+    pub fn for_each_primitive_mut(&mut self, f: &mut dyn FnMut(&mut dyn PrimitiveShape)) {
+        match self {
+            Shape::Primitive(shape) => f(shape.as_mut()),
+            Shape::Group(group) => group
+                .objects_mut()
+                .for_each(|object| object.for_each_primitive_mut(f)),
+            Shape::Csg(csg) => csg
+                .shapes_mut()
+                .into_iter()
+                .for_each(|shape| shape.for_each_primitive_mut(f)),
+            Shape::Clipped(clipped) => clipped
+                .shapes_mut()
+                .for_each(|shape| shape.for_each_primitive_mut(f)),
+        }
+    }
+
     // eventually make this function delegate to underlying object by calling a single method
     pub fn contains<'a, 'b: 'a>(&'a self, primitive_shape: &'b dyn PrimitiveShape) -> bool {
         match self {
@@ -18,37 +175,202 @@ impl Shape {
                 // For some reason, PartialEq does not work here when comparing references directly IF we remove `+ '_` from impl PartialEq for dyn PrimitiveShape + 'a.
                 shape.as_ref() == primitive_shape
             }
-            Shape::Group(group) => {
-                match group
-                    .objects()
-                    .iter()
-                    .position(|object| object.contains(primitive_shape))
-                {
-                    Some(_) => true,
-                    None => false,
-                }
-            }
+            Shape::Group(group) => group
+                .objects()
+                .iter()
+                .position(|object| object.contains(primitive_shape))
+                .is_some(),
             Shape::Csg(csg) => {
                 csg.lshape().contains(primitive_shape) || csg.rshape().contains(primitive_shape)
             }
+            Shape::Clipped(clipped) => {
+                clipped.shape().contains(primitive_shape)
+                    || clipped
+                        .cap()
+                        .is_some_and(|cap| cap.contains(primitive_shape))
+            }
+        }
+    }
+
+    // transformations of the groups enclosing a nested primitive, outermost
+    // first, for the primitive's world_normal_at; None if the primitive is not
+    // part of this shape. Groups built into one another come as one.
+    pub fn group_transforms_to(
+        &self,
+        primitive_shape: &dyn PrimitiveShape,
+    ) -> Option<Vec<&Transform>> {
+        self.group_transforms_within(primitive_shape, &[])
+    }
+
+    fn group_transforms_within<'a>(
+        &'a self,
+        primitive_shape: &dyn PrimitiveShape,
+        group_transforms: &[&'a Transform],
+    ) -> Option<Vec<&'a Transform>> {
+        match self {
+            Shape::Primitive(shape) => {
+                (shape.as_ref() == primitive_shape).then(|| group_transforms.to_vec())
+            }
+            Shape::Group(group) => {
+                let mut transform_stack = group_transforms.to_vec();
+                group.enter(&mut transform_stack);
+                group.objects().iter().find_map(|object| {
+                    object.group_transforms_within(primitive_shape, &transform_stack)
+                })
+            }
+            Shape::Csg(csg) => csg
+                .lshape()
+                .group_transforms_within(primitive_shape, group_transforms)
+                .or_else(|| {
+                    csg.rshape()
+                        .group_transforms_within(primitive_shape, group_transforms)
+                }),
+            Shape::Clipped(clipped) => clipped
+                .shape()
+                .group_transforms_within(primitive_shape, group_transforms)
+                .or_else(|| {
+                    clipped
+                        .cap()?
+                        .group_transforms_within(primitive_shape, group_transforms)
+                }),
+        }
+    }
+}
+
+// Direction of the rays cast to count surface crossings, chosen to lie off
+// the axes so that rays rarely graze the edges of axis-aligned meshes.
+pub(crate) const CROSSING_DIRECTION: Vector = Vector {
+    x: 0.267_261_241_912_424_4,
+    y: 0.534_522_483_824_848_8,
+    z: 0.801_783_725_737_273_2,
+};
+
+impl Shape {
+    // whether a point lies inside the shape or on its surface, such as for
+    // checking that the camera or a light is not buried in geometry. Shapes
+    // with an inside of their own answer for themselves. Surfaces such as mesh
+    // triangles hold the points from which a ray crosses them an odd number of
+    // times, counting every surface gathered in the shape's groups, so a closed
+    // mesh split into subgroups still has an inside.
+    pub fn contains_point(&self, point: Point) -> bool {
+        self.contains_point_within(point, &[])
+    }
+
+    pub(crate) fn contains_point_within(
+        &self,
+        point: Point,
+        group_transforms: &[&Transform],
+    ) -> bool {
+        match self {
+            Shape::Group(group) => group.contains_point_within(point, group_transforms),
+            _ => self.encloses(point, group_transforms),
+        }
+    }
+
+    // distance from a point to the nearest surface of the shape, such as for
+    // approximating ambient occlusion or keeping a camera out of geometry;
+    // None if no primitive in the shape can tell. CSG and clipped shapes
+    // combine the signed distances of their parts as distance fields do,
+    // which may fall short of the true distance.
+    pub fn distance_to(&self, point: Point) -> Option<f64> {
+        self.distance_within(point, &[])
+    }
+
+    // as distance_to, negative where the shape contains the point
+    pub fn signed_distance_to(&self, point: Point) -> Option<f64> {
+        self.signed_distance_within(point, &[])
+    }
+
+    pub(crate) fn distance_within(
+        &self,
+        point: Point,
+        group_transforms: &[&Transform],
+    ) -> Option<f64> {
+        match self {
+            Shape::Primitive(primitive) => primitive.distance_to(point, group_transforms),
+            Shape::Group(group) => group.distance_within(point, group_transforms),
+            Shape::Csg(_) | Shape::Clipped(_) => self
+                .signed_distance_within(point, group_transforms)
+                .map(f64::abs),
+        }
+    }
+
+    pub(crate) fn signed_distance_within(
+        &self,
+        point: Point,
+        group_transforms: &[&Transform],
+    ) -> Option<f64> {
+        match self {
+            Shape::Csg(csg) => csg.signed_distance_within(point, group_transforms),
+            Shape::Clipped(clipped) => clipped.signed_distance_within(point, group_transforms),
+            _ => {
+                let distance = self.distance_within(point, group_transforms)?;
+                Some(match self.contains_point_within(point, group_transforms) {
+                    true => -distance,
+                    false => distance,
+                })
+            }
+        }
+    }
+
+    // contains_point without counting the crossings of loose surfaces
+    pub(crate) fn encloses(&self, point: Point, group_transforms: &[&Transform]) -> bool {
+        match self {
+            Shape::Primitive(primitive) => primitive
+                .contains_point(point, group_transforms)
+                .unwrap_or(false),
+            Shape::Group(group) => group.encloses(point, group_transforms),
+            Shape::Csg(csg) => csg.contains_point_within(point, group_transforms),
+            Shape::Clipped(clipped) => clipped.contains_point_within(point, group_transforms),
+        }
+    }
+
+    // times the ray crosses surfaces with no inside of their own in this shape
+    // and the groups within it; those in CSG and clipped shapes are left to
+    // their own containment tests
+    pub(crate) fn surface_crossings(&self, ray: &Ray, group_transforms: &[&Transform]) -> usize {
+        match self {
+            Shape::Primitive(primitive) => {
+                if primitive.has_interior() {
+                    return 0;
+                }
+                let mut transform_stack = group_transforms.to_vec();
+                let mut hit_register = HitRegister::empty();
+                primitive.intersect_ray_into(ray, &mut transform_stack, &mut hit_register);
+                hit_register
+                    .expose()
+                    .iter()
+                    .filter(|hit| hit.t() > 0.0)
+                    .count()
+            }
+            Shape::Group(group) => group.surface_crossings(ray, group_transforms),
+            Shape::Csg(_) | Shape::Clipped(_) => 0,
         }
     }
 }
 
 impl Intersectable<dyn PrimitiveShape> for Shape {
-    fn intersect_ray<'world: 'ray, 'ray>(
+    fn intersect_ray_into<'world: 'ray, 'ray>(
         &'world self,
         world_ray: &'ray Ray,
-        transform_stack: Vec<&'ray Transform>,
-    ) -> HitRegister<'ray, dyn PrimitiveShape> {
-        if !self.bounds().intersect_bounds(world_ray, &transform_stack) {
-            return HitRegister::empty();
+        transform_stack: &mut Vec<&'ray Transform>,
+        hit_register: &mut HitRegister<'ray, dyn PrimitiveShape>,
+    ) {
+        if !self.bounds().intersect_bounds(world_ray, transform_stack) {
+            return;
         }
 
         match self {
-            Shape::Primitive(primitive) => primitive.intersect_ray(world_ray, transform_stack),
-            Shape::Group(group) => group.intersect_ray(world_ray, transform_stack),
-            Shape::Csg(csg) => csg.intersect_ray(world_ray, transform_stack),
+            Shape::Primitive(primitive) => {
+                primitive.intersect_ray_into(world_ray, transform_stack, hit_register)
+            }
+            Shape::Group(group) => {
+                group.intersect_ray_into(world_ray, transform_stack, hit_register)
+            }
+            Shape::Csg(csg) => csg.intersect_ray_into(world_ray, transform_stack, hit_register),
+            Shape::Clipped(clipped) => {
+                clipped.intersect_ray_into(world_ray, transform_stack, hit_register)
+            }
         }
     }
 }
@@ -59,27 +381,165 @@ impl Bounded for Shape {
             Shape::Primitive(s) => s.bounds(),
             Shape::Group(s) => s.bounds(),
             Shape::Csg(s) => s.bounds(),
+            Shape::Clipped(s) => s.bounds(),
         }
     }
 }
 
-pub trait PrimitiveShape: Debug + Bounded {
+pub trait PrimitiveShape: Debug + Bounded + Send + Sync {
     fn normal_at(
         &self,
         world_point: Point,
         uv_coordinates: Option<(f64, f64)>,
-        transform_stack: &Vec<&Transform>,
+        transform_stack: &[&Transform],
     ) -> Vector {
-        let local_point = transform_through_stack_forwards(world_point, &transform_stack);
+        let local_point = transform_through_stack_forwards(world_point, transform_stack);
         let local_normal = self.local_normal_at(local_point, uv_coordinates);
-        let world_normal = transform_through_stack_backwards(local_normal, &transform_stack);
+        let world_normal = transform_through_stack_backwards(local_normal, transform_stack);
         world_normal.normalise()
     }
 
+    // normal_at for tools outside the renderer, which need only know the
+    // enclosing groups' transformations (see Shape::group_transforms_to)
+    fn world_normal_at(&self, world_point: Point, group_transforms: &[&Transform]) -> Vector {
+        let mut transform_stack = group_transforms.to_vec();
+        transform_stack.push(self.frame_transformation());
+        self.normal_at(world_point, None, &transform_stack)
+    }
+
+    // the colour the shape is shaded with at the point, before lighting
+    fn world_colour_at(&self, world_point: Point, group_transforms: &[&Transform]) -> Colour {
+        let mut transform_stack = group_transforms.to_vec();
+        transform_stack.push(self.frame_transformation());
+        self.material()
+            .pattern
+            .colour_at_object(world_point, &transform_stack)
+    }
+
+    // colour carried by the shape itself at a hit, such as one blended from
+    // the colours at a mesh triangle's corners; shading uses it in place of a
+    // material that has no pattern of its own
+    fn vertex_colour(&self, _uv_coordinates: Option<(f64, f64)>) -> Option<Colour> {
+        None
+    }
+
+    // where a hit lies on a texture laid on the shape itself, such as by the
+    // texture coordinates at a mesh triangle's corners
+    fn texture_coordinates_at(&self, _uv_coordinates: Option<(f64, f64)>) -> Option<(f64, f64)> {
+        None
+    }
+
+    // the changes in texture coordinates across offsets in the shape's own
+    // space (such as a ray footprint), for shapes with texture coordinates
+    fn texture_footprint(&self, _footprint: [Vector; 2]) -> Option<[(f64, f64); 2]> {
+        None
+    }
+
+    // whether a point in the shape's own space lies inside the shape or on its
+    // surface; None for surfaces with no inside of their own, such as mesh
+    // triangles, which enclose space only together (see Shape::contains_point)
+    fn local_contains_point(&self, _local_point: Point) -> Option<bool> {
+        None
+    }
+
+    // whether the shape encloses space of its own, and so answers
+    // local_contains_point everywhere; shapes that do should say so
+    fn has_interior(&self) -> bool {
+        false
+    }
+
+    // local_contains_point for a point in world space
+    fn contains_point(&self, world_point: Point, group_transforms: &[&Transform]) -> Option<bool> {
+        let mut transform_stack = group_transforms.to_vec();
+        transform_stack.push(self.frame_transformation());
+        self.local_contains_point(transform_through_stack_forwards(
+            world_point,
+            &transform_stack,
+        ))
+    }
+
+    // the point of the shape's surface nearest to a point in the shape's own
+    // space; None for shapes that cannot tell
+    fn local_closest_point(&self, _local_point: Point) -> Option<Point> {
+        None
+    }
+
+    // edges drawn by a wireframe debug overlay, in the shape's own space; only
+    // triangles have any
+    fn local_edges(&self) -> Vec<[Point; 2]> {
+        vec![]
+    }
+
+    // distance from a point in world space to the shape's surface, through
+    // the local_closest_point. This is exact for transformations that keep
+    // the shape's proportions; stretching moves the nearest point, so
+    // otherwise it may overestimate.
+    fn distance_to(&self, world_point: Point, group_transforms: &[&Transform]) -> Option<f64> {
+        let mut transform_stack = group_transforms.to_vec();
+        transform_stack.push(self.frame_transformation());
+        let local_point = transform_through_stack_forwards(world_point, &transform_stack);
+        let closest = self.local_closest_point(local_point)?;
+        Some((transform_out_of_stack(closest, &transform_stack) - world_point).magnitude())
+    }
+
     fn frame_transformation(&self) -> &Transform;
+
+    // moves the built shape by a transformation applied after its own,
+    // working out its bounds again; shapes that cannot be moved in place
+    // return false and are left as they were (see Shape::apply_transform)
+    fn apply_transform(&mut self, _transform: &Transform) -> bool {
+        false
+    }
+
     fn material(&self) -> &Material;
+
+    // the material as held, for shapes whose material can be changed after
+    // they are built; shapes that cannot be re-skinned keep theirs through
+    // set_material and the like
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        None
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.set_shared_material(Arc::new(material));
+    }
+
+    fn set_shared_material(&mut self, material: Arc<Material>) {
+        if let Some(shared) = self.shared_material_mut() {
+            *shared = material;
+        }
+    }
+
+    // a material shared with other shapes is copied first, so that editing it
+    // here leaves theirs alone
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        self.shared_material_mut().map(Arc::make_mut)
+    }
+
+    // shapes without a name field are never found by name
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
     fn local_normal_at(&self, local_point: Point, uv_coordinates: Option<(f64, f64)>) -> Vector;
     fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates>;
+
+    // local_intersect adding to a buffer kept from shape to shape, which
+    // shapes override so that no vector is allocated for every ray tested
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
+        intersections.extend(self.local_intersect(local_ray));
+    }
+}
+
+// local_intersect for shapes finding their intersections with
+// local_intersect_into
+pub(crate) fn collect_local_intersections<S>(shape: &S, local_ray: &Ray) -> Vec<Coordinates>
+where
+    S: PrimitiveShape + ?Sized,
+{
+    let mut intersections = vec![];
+    shape.local_intersect_into(local_ray, &mut intersections);
+    intersections
 }
 
 impl PartialEq for dyn PrimitiveShape + '_ {
@@ -92,36 +552,63 @@ pub trait Intersectable<S: PrimitiveShape + PartialEq + ?Sized> {
     fn intersect_ray<'a: 'r, 'r>(
         &'a self,
         world_ray: &'r Ray,
-        transform_stack: Vec<&'r Transform>,
-    ) -> HitRegister<'r, S>;
+        mut transform_stack: Vec<&'r Transform>,
+    ) -> HitRegister<'r, S> {
+        let mut hit_register = HitRegister::empty();
+        self.intersect_ray_into(world_ray, &mut transform_stack, &mut hit_register);
+        hit_register
+    }
+
+    // Adds this shape's intersections to a register shared by everything the
+    // ray is tested against, so that nested shapes neither allocate registers
+    // of their own nor copy the transform stack on the way down. The transform
+    // stack is left as it was found.
+    fn intersect_ray_into<'a: 'r, 'r>(
+        &'a self,
+        world_ray: &'r Ray,
+        transform_stack: &mut Vec<&'r Transform>,
+        hit_register: &mut HitRegister<'r, S>,
+    );
 }
 
 impl<S: PrimitiveShape + PartialEq + ?Sized> Intersectable<S> for S {
-    fn intersect_ray<'a: 'r, 'r>(
+    fn intersect_ray_into<'a: 'r, 'r>(
         &'a self,
         world_ray: &'r Ray,
-        mut transform_stack: Vec<&'r Transform>,
-    ) -> HitRegister<'r, Self> {
-        let mut hit_register = HitRegister::empty();
+        transform_stack: &mut Vec<&'r Transform>,
+        hit_register: &mut HitRegister<'r, Self>,
+    ) {
         transform_stack.push(self.frame_transformation());
-        let local_ray = transform_through_stack_forwards(*world_ray, &transform_stack);
-        let coordinates = self.local_intersect(&local_ray);
+        let local_ray = transform_through_stack_forwards(*world_ray, transform_stack);
 
-        for coordinate in coordinates {
-            let raw_intersect = coordinate.attach(self, world_ray, transform_stack.clone());
-            hit_register.add_raw_intersect(raw_intersect);
-        }
+        stats::count_intersection_test();
+        // each intersect keeps its own copy of the stack for computing normals
+        hit_register.add_local_intersections(self, world_ray, transform_stack, |coordinates| {
+            self.local_intersect_into(&local_ray, coordinates)
+        });
 
-        hit_register
+        transform_stack.pop();
     }
 }
 
 pub(crate) fn transform_through_stack_forwards<T: Transformable>(
     mut object: T,
-    transform_stack: &Vec<&Transform>,
+    transform_stack: &[&Transform],
 ) -> T {
     for &transform in transform_stack {
-        object = object.transform(&transform.invert());
+        object = object.transform(transform.inverse());
+    }
+
+    object
+}
+
+// from the innermost space of the stack back out to world space
+pub(crate) fn transform_out_of_stack<T: Transformable>(
+    mut object: T,
+    transform_stack: &[&Transform],
+) -> T {
+    for &transform in transform_stack.iter().rev() {
+        object = object.transform(transform);
     }
 
     object
@@ -129,11 +616,82 @@ pub(crate) fn transform_through_stack_forwards<T: Transformable>(
 
 pub(crate) fn transform_through_stack_backwards<T: Transformable>(
     mut object: T,
-    transform_stack: &Vec<&Transform>,
+    transform_stack: &[&Transform],
 ) -> T {
     for &transform in transform_stack.iter().rev() {
-        object = object.transform(&transform.invert().transpose());
+        object = object.transform(transform.inverse_transpose());
     }
 
     object
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a shape made outside the crate, implementing only what it must
+    #[derive(Debug)]
+    struct Fixed {
+        transform: Transform,
+        material: Material,
+        bounds: Bounds,
+    }
+
+    impl Bounded for Fixed {
+        fn bounds(&self) -> &Bounds {
+            &self.bounds
+        }
+    }
+
+    impl PrimitiveShape for Fixed {
+        fn frame_transformation(&self) -> &Transform {
+            &self.transform
+        }
+
+        fn material(&self) -> &Material {
+            &self.material
+        }
+
+        fn local_normal_at(&self, _local_point: Point, _: Option<(f64, f64)>) -> Vector {
+            Vector::new(0.0, 1.0, 0.0)
+        }
+
+        fn local_intersect(&self, _local_ray: &Ray) -> Vec<Coordinates> {
+            vec![]
+        }
+    }
+
+    fn fixed() -> Fixed {
+        Fixed {
+            transform: Transform::default(),
+            material: Material::preset(),
+            bounds: Bounds::new(BoundingBox::from_axial_bounds(
+                [-1.0, 1.0],
+                [-1.0, 1.0],
+                [-1.0, 1.0],
+            )),
+        }
+    }
+
+    #[test]
+    fn shapes_without_material_access_keep_theirs() {
+        let mut fixed = fixed();
+        fixed.set_material(Material::default());
+        assert!(fixed.material_mut().is_none());
+        assert_eq!(fixed.material(), &Material::preset());
+    }
+
+    #[test]
+    fn shapes_that_cannot_move_in_place_are_moved_in_one_group() {
+        let translation = Transform::new(TransformKind::Translate(5.0, 0.0, 0.0));
+        let mut shape = Shape::Primitive(Box::new(fixed()));
+        shape.apply_transform(&translation);
+        shape.apply_transform(&translation);
+        let Shape::Group(ref group) = shape else {
+            panic!("expected a group")
+        };
+        assert!(matches!(group.objects()[..], [Shape::Primitive(_)]));
+        let (x_range, _, _) = shape.bounds().bounding_box().axial_bounds();
+        assert_eq!(x_range, [9.0, 11.0]);
+    }
+}
@@ -1,14 +1,20 @@
-use crate::collections::{Point, Vector};
+use std::sync::Arc;
+
+use crate::collections::{Colour, Point, Vector};
 use crate::objects::*;
-use crate::utils::{Buildable, ConsumingBuilder, EPSILON};
+use crate::utils::{BuildError, Buildable, ConsumingBuilder, EPSILON};
 
 #[derive(Debug)]
 pub struct SmoothTriangle {
     frame_transformation: Transform,
-    material: Material,
+    material: Arc<Material>,
+    name: Option<String>,
     vertices: [Point; 3],
     edges: [Vector; 2],
     normals: [Vector; 3],
+    // at each vertex, blended across the face
+    colours: Option<[Colour; 3]>,
+    texture_coordinates: Option<[(f64, f64); 3]>,
     bounds: Bounds,
 }
 
@@ -27,6 +33,14 @@ impl SmoothTriangle {
     pub fn normals(&self) -> [Vector; 3] {
         self.normals
     }
+
+    pub fn colours(&self) -> Option<[Colour; 3]> {
+        self.colours
+    }
+
+    pub fn texture_coordinates(&self) -> Option<[(f64, f64); 3]> {
+        self.texture_coordinates
+    }
 }
 
 impl PrimitiveShape for SmoothTriangle {
@@ -34,10 +48,33 @@ impl PrimitiveShape for SmoothTriangle {
         &self.frame_transformation
     }
 
+    // always unbounded, so only the frame transformation changes
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        true
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
 
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        Some(Triangle::closest_point(self.vertices, local_point))
+    }
+
+    fn local_edges(&self) -> Vec<[Point; 2]> {
+        let [v1, v2, v3] = self.vertices;
+        vec![[v1, v2], [v2, v3], [v3, v1]]
+    }
+
     fn local_normal_at(&self, _local_point: Point, uv_coordinates: Option<(f64, f64)>) -> Vector {
         let [n1, n2, n3] = self.normals;
         let (u, v) = uv_coordinates.unwrap();
@@ -45,30 +82,52 @@ impl PrimitiveShape for SmoothTriangle {
     }
 
     fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
+        collect_local_intersections(self, local_ray)
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
         let dir_cross_e2 = local_ray.direction.cross(self.edges[1]);
         let det = self.edges[0].dot(dir_cross_e2);
         if det.abs() < EPSILON {
-            return vec![];
+            return;
         }
 
         let f = 1.0 / det;
         let p1_to_origin = local_ray.origin - self.vertices[0];
         let u = f * p1_to_origin.dot(dir_cross_e2);
-        if u < 0.0 || u > 1.0 {
-            return vec![];
+        if !(0.0..=1.0).contains(&u) {
+            return;
         }
 
         let origin_cross_e1 = p1_to_origin.cross(self.edges[0]);
         let v = f * local_ray.direction.dot(origin_cross_e1);
         if v < 0.0 || (u + v) > 1.0 {
-            return vec![];
+            return;
         }
 
         let t = f * self.edges[1].dot(origin_cross_e1);
-        vec![(t, Some((u, v)))]
-            .iter()
-            .map(|&(t, uv_coordinates)| Coordinates::new(t, uv_coordinates))
-            .collect()
+        intersections.push(Coordinates::new(t, Some((u, v))));
+    }
+
+    fn vertex_colour(&self, uv_coordinates: Option<(f64, f64)>) -> Option<Colour> {
+        let [c1, c2, c3] = self.colours?;
+        let (u, v) = uv_coordinates?;
+        Some(c2 * u + c3 * v + c1 * (1.0 - u - v))
+    }
+
+    fn texture_coordinates_at(&self, uv_coordinates: Option<(f64, f64)>) -> Option<(f64, f64)> {
+        Some(Triangle::blend_texture(
+            self.texture_coordinates?,
+            uv_coordinates?,
+        ))
+    }
+
+    fn texture_footprint(&self, footprint: [Vector; 2]) -> Option<[(f64, f64); 2]> {
+        Some(Triangle::texture_offsets(
+            self.edges,
+            self.texture_coordinates?,
+            footprint,
+        ))
     }
 }
 
@@ -81,9 +140,12 @@ impl Bounded for SmoothTriangle {
 #[derive(Debug, Default)]
 pub struct SmoothTriangleBuilder {
     frame_transformation: Option<Transform>,
-    material: Option<Material>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
     vertices: Option<[Point; 3]>,
     normals: Option<[Vector; 3]>,
+    colours: Option<[Colour; 3]>,
+    texture_coordinates: Option<[(f64, f64); 3]>,
 }
 
 impl SmoothTriangleBuilder {
@@ -96,10 +158,20 @@ impl SmoothTriangleBuilder {
     }
 
     pub fn set_material(mut self, material: Material) -> SmoothTriangleBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> SmoothTriangleBuilder {
         self.material = Some(material);
         self
     }
 
+    pub fn set_name(mut self, name: &str) -> SmoothTriangleBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+
     pub fn set_vertices(mut self, vertices: [Point; 3]) -> SmoothTriangleBuilder {
         self.vertices = Some(vertices);
         self
@@ -109,6 +181,19 @@ impl SmoothTriangleBuilder {
         self.normals = Some(normals);
         self
     }
+
+    pub fn set_colours(mut self, colours: [Colour; 3]) -> SmoothTriangleBuilder {
+        self.colours = Some(colours);
+        self
+    }
+
+    pub fn set_texture_coordinates(
+        mut self,
+        texture_coordinates: [(f64, f64); 3],
+    ) -> SmoothTriangleBuilder {
+        self.texture_coordinates = Some(texture_coordinates);
+        self
+    }
 }
 
 impl Buildable for SmoothTriangle {
@@ -123,28 +208,37 @@ impl ConsumingBuilder for SmoothTriangleBuilder {
     type Built = SmoothTriangle;
 
     fn build(self) -> Self::Built {
-        let frame_transformation = self.frame_transformation.unwrap_or_default();
-        let material = self.material.unwrap_or_default();
-        let [v1, v2, v3] = self.vertices.unwrap();
-        let normals = self.normals.unwrap();
+        self.try_build().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // a triangle with no area is reported rather than given a NaN normal
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        let [v1, v2, v3] = self.vertices.ok_or(BuildError::MissingField("vertices"))?;
+        let normals = self.normals.ok_or(BuildError::MissingField("normals"))?;
         let e1 = v2 - v1;
         let e2 = v3 - v1;
-        let bounds = Bounds::new(SmoothTriangle::PRIMITIVE_BOUNDING_BOX);
-        let smooth_triangle = SmoothTriangle {
-            frame_transformation,
-            material,
+        if e2.cross(e1).magnitude() == 0.0 {
+            return Err(BuildError::DegenerateTriangle);
+        }
+        check_frame_transformation(&self.frame_transformation)?;
+
+        Ok(SmoothTriangle {
+            frame_transformation: self.frame_transformation.unwrap_or_default(),
+            material: self.material.unwrap_or_default(),
+            name: self.name,
             vertices: [v1, v2, v3],
             edges: [e1, e2],
             normals,
-            bounds,
-        };
-        smooth_triangle
+            colours: self.colours,
+            texture_coordinates: self.texture_coordinates,
+            bounds: Bounds::new(SmoothTriangle::PRIMITIVE_BOUNDING_BOX),
+        })
     }
 }
 
-impl Into<Shape> for SmoothTriangle {
-    fn into(self) -> Shape {
-        Shape::Primitive(Box::new(self))
+impl From<SmoothTriangle> for Shape {
+    fn from(smooth_triangle: SmoothTriangle) -> Shape {
+        Shape::Primitive(Box::new(smooth_triangle))
     }
 }
 
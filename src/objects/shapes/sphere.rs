@@ -1,11 +1,14 @@
+use std::sync::Arc;
+
 use crate::collections::{Point, Vector};
 use crate::objects::*;
-use crate::utils::{Buildable, ConsumingBuilder, EPSILON};
+use crate::utils::{BuildError, Buildable, ConsumingBuilder, EPSILON};
 
 #[derive(Debug, PartialEq)]
 pub struct Sphere {
     frame_transformation: Transform,
-    material: Material,
+    material: Arc<Material>,
+    name: Option<String>,
     bounds: Bounds,
 }
 
@@ -19,15 +22,52 @@ impl PrimitiveShape for Sphere {
         &self.frame_transformation
     }
 
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.bounds =
+            Bounds::new(Sphere::PRIMITIVE_BOUNDING_BOX.transform(&self.frame_transformation));
+        true
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
 
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn has_interior(&self) -> bool {
+        true
+    }
+
+    fn local_contains_point(&self, local_point: Point) -> Option<bool> {
+        let Point { x, y, z } = local_point;
+        Some(x.powi(2) + y.powi(2) + z.powi(2) <= 1.0 + EPSILON)
+    }
+
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        let offset = local_point - Point::zero();
+        // the centre is as near to every point of the surface
+        Some(match offset.magnitude() < EPSILON {
+            true => Point::new(1.0, 0.0, 0.0),
+            false => Point::zero() + offset.normalise(),
+        })
+    }
+
     fn local_normal_at(&self, local_point: Point, _: Option<(f64, f64)>) -> Vector {
         local_point - Point::new(0.0, 0.0, 0.0)
     }
 
     fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
+        collect_local_intersections(self, local_ray)
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
         let sphere_to_ray = local_ray.origin - Point::zero();
         let a = local_ray.direction.dot(local_ray.direction);
         let b = 2.0 * local_ray.direction.dot(sphere_to_ray);
@@ -35,16 +75,12 @@ impl PrimitiveShape for Sphere {
         let discriminant = b.powi(2) - 4.0 * a * c;
 
         if discriminant < 0.0 {
-            vec![]
-        } else {
-            let sqrt_discriminant = discriminant.sqrt();
-            let t1 = (-b - sqrt_discriminant) / (2.0 * a);
-            let t2 = (-b + sqrt_discriminant) / (2.0 * a);
-            vec![t1, t2]
-                .iter()
-                .map(|&t| Coordinates::new(t, None))
-                .collect()
+            return;
         }
+        let sqrt_discriminant = discriminant.sqrt();
+        let t1 = (-b - sqrt_discriminant) / (2.0 * a);
+        let t2 = (-b + sqrt_discriminant) / (2.0 * a);
+        intersections.extend([t1, t2].map(|t| Coordinates::new(t, None)));
     }
 }
 
@@ -57,7 +93,8 @@ impl Bounded for Sphere {
 #[derive(Debug, Default)]
 pub struct SphereBuilder {
     frame_transformation: Option<Transform>,
-    material: Option<Material>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
 }
 
 impl SphereBuilder {
@@ -67,9 +104,19 @@ impl SphereBuilder {
     }
 
     pub fn set_material(mut self, material: Material) -> SphereBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> SphereBuilder {
         self.material = Some(material);
         self
     }
+
+    pub fn set_name(mut self, name: &str) -> SphereBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
 }
 
 impl Buildable for Sphere {
@@ -89,18 +136,23 @@ impl ConsumingBuilder for SphereBuilder {
         let material = self.material.unwrap_or_default();
         let bounds = Bounds::new(Sphere::PRIMITIVE_BOUNDING_BOX.transform(&frame_transformation));
 
-        let sphere = Sphere {
+        Sphere {
             frame_transformation,
             material,
+            name: self.name,
             bounds,
-        };
-        sphere
+        }
+    }
+
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        check_frame_transformation(&self.frame_transformation)?;
+        Ok(self.build())
     }
 }
 
-impl Into<Shape> for Sphere {
-    fn into(self) -> Shape {
-        Shape::Primitive(Box::new(self))
+impl From<Sphere> for Shape {
+    fn from(sphere: Sphere) -> Shape {
+        Shape::Primitive(Box::new(sphere))
     }
 }
 
@@ -130,10 +182,10 @@ mod tests {
             3.0_f64.sqrt() / 3.0,
             3.0_f64.sqrt() / 3.0,
         );
-        assert_eq!(sphere.normal_at(point1, None, &vec![]), normal1);
-        assert_eq!(sphere.normal_at(point2, None, &vec![]), normal2);
-        assert_eq!(sphere.normal_at(point3, None, &vec![]), normal3);
-        assert_eq!(sphere.normal_at(point4, None, &vec![]), normal4);
+        assert_eq!(sphere.normal_at(point1, None, &[]), normal1);
+        assert_eq!(sphere.normal_at(point2, None, &[]), normal2);
+        assert_eq!(sphere.normal_at(point3, None, &[]), normal3);
+        assert_eq!(sphere.normal_at(point4, None, &[]), normal4);
     }
 
     #[test]
@@ -151,8 +203,8 @@ mod tests {
             .build();
         let point1 = Point::new(0.0, 1.0 + 2.0_f64.sqrt() / 2.0, -2.0_f64.sqrt() / 2.0);
         let point2 = Point::new(0.0, 2.0_f64.sqrt() / 2.0, -2.0_f64.sqrt() / 2.0);
-        let normal1 = sphere1.normal_at(point1, None, &vec![sphere1.frame_transformation()]);
-        let normal2 = sphere1.normal_at(point2, None, &vec![sphere2.frame_transformation()]);
+        let normal1 = sphere1.normal_at(point1, None, &[sphere1.frame_transformation()]);
+        let normal2 = sphere1.normal_at(point2, None, &[sphere2.frame_transformation()]);
         let resulting_normal1 = Vector::new(0.0, 2.0_f64.sqrt() / 2.0, -2.0_f64.sqrt() / 2.0);
         let resulting_normal2 = Vector::new(0.0, 0.970143, -0.242535);
         approx_eq!(normal1.x, resulting_normal1.x);
@@ -224,4 +276,13 @@ mod tests {
         let hit_register = sphere.intersect_ray(&ray, vec![]);
         assert!(hit_register.finalise_hit().is_none());
     }
+
+    #[test]
+    fn reject_non_invertible_transform() {
+        let flattened = Sphere::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Scale(1.0, 0.0, 1.0)))
+            .try_build();
+        assert_eq!(flattened.err(), Some(BuildError::NonInvertibleTransform));
+        assert!(Sphere::builder().try_build().is_ok());
+    }
 }
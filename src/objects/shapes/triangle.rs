@@ -1,14 +1,20 @@
-use crate::collections::{Point, Vector};
+use std::sync::Arc;
+
+use crate::collections::{Colour, Point, Vector};
 use crate::objects::*;
-use crate::utils::{Buildable, ConsumingBuilder, EPSILON};
+use crate::utils::{BuildError, Buildable, ConsumingBuilder, EPSILON};
 
 #[derive(Debug)]
 pub struct Triangle {
     frame_transformation: Transform,
-    material: Material,
+    material: Arc<Material>,
+    name: Option<String>,
     vertices: [Point; 3],
     edges: [Vector; 2],
     normal: Vector,
+    // at each vertex, blended across the face
+    colours: Option<[Colour; 3]>,
+    texture_coordinates: Option<[(f64, f64); 3]>,
     bounds: Bounds,
 }
 
@@ -24,6 +30,73 @@ impl Triangle {
     pub fn normal(&self) -> Vector {
         self.normal
     }
+
+    pub fn colours(&self) -> Option<[Colour; 3]> {
+        self.colours
+    }
+
+    pub fn texture_coordinates(&self) -> Option<[(f64, f64); 3]> {
+        self.texture_coordinates
+    }
+
+    // the texture coordinates at the point with the given barycentric (u, v)
+    pub(crate) fn blend_texture(
+        [(s1, t1), (s2, t2), (s3, t3)]: [(f64, f64); 3],
+        (u, v): (f64, f64),
+    ) -> (f64, f64) {
+        let w = 1.0 - u - v;
+        (s2 * u + s3 * v + s1 * w, t2 * u + t3 * v + t1 * w)
+    }
+
+    // the changes in texture coordinates across offsets, after projecting
+    // them onto the plane of the triangle with the given edges
+    pub(crate) fn texture_offsets(
+        [e1, e2]: [Vector; 2],
+        [(s1, t1), (s2, t2), (s3, t3)]: [(f64, f64); 3],
+        offsets: [Vector; 2],
+    ) -> [(f64, f64); 2] {
+        let [g11, g12, g22] = [e1.dot(e1), e1.dot(e2), e2.dot(e2)];
+        let determinant = g11 * g22 - g12 * g12;
+        offsets.map(|offset| {
+            let [r1, r2] = [offset.dot(e1), offset.dot(e2)];
+            // the offset as a u e1 + v e2
+            let u = (g22 * r1 - g12 * r2) / determinant;
+            let v = (g11 * r2 - g12 * r1) / determinant;
+            ((s2 - s1) * u + (s3 - s1) * v, (t2 - t1) * u + (t3 - t1) * v)
+        })
+    }
+
+    // the point of the triangle with the given vertices nearest to a point,
+    // on its face, an edge or a corner depending on where the point lies
+    pub(crate) fn closest_point([a, b, c]: [Point; 3], point: Point) -> Point {
+        let (ab, ac) = (b - a, c - a);
+        let [d1, d2] = [ab.dot(point - a), ac.dot(point - a)];
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return a;
+        }
+        let [d3, d4] = [ab.dot(point - b), ac.dot(point - b)];
+        if d3 >= 0.0 && d4 <= d3 {
+            return b;
+        }
+        let [d5, d6] = [ab.dot(point - c), ac.dot(point - c)];
+        if d6 >= 0.0 && d5 <= d6 {
+            return c;
+        }
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            return a + ab * (d1 / (d1 - d3));
+        }
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            return a + ac * (d2 / (d2 - d6));
+        }
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+            return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+        }
+        let sum = va + vb + vc;
+        a + ab * (vb / sum) + ac * (vc / sum)
+    }
 }
 
 impl PrimitiveShape for Triangle {
@@ -31,36 +104,86 @@ impl PrimitiveShape for Triangle {
         &self.frame_transformation
     }
 
+    fn apply_transform(&mut self, transform: &Transform) -> bool {
+        self.frame_transformation = self.frame_transformation.compose(transform);
+        self.bounds = Bounds::new(
+            BoundingBox::from_anchors(self.vertices.to_vec()).transform(&self.frame_transformation),
+        );
+        true
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
 
+    fn shared_material_mut(&mut self) -> Option<&mut Arc<Material>> {
+        Some(&mut self.material)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn local_closest_point(&self, local_point: Point) -> Option<Point> {
+        Some(Triangle::closest_point(self.vertices, local_point))
+    }
+
+    fn local_edges(&self) -> Vec<[Point; 2]> {
+        let [v1, v2, v3] = self.vertices;
+        vec![[v1, v2], [v2, v3], [v3, v1]]
+    }
+
     fn local_normal_at(&self, _local_point: Point, _: Option<(f64, f64)>) -> Vector {
         self.normal
     }
 
     fn local_intersect(&self, local_ray: &Ray) -> Vec<Coordinates> {
+        collect_local_intersections(self, local_ray)
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, intersections: &mut Vec<Coordinates>) {
         let dir_cross_e2 = local_ray.direction.cross(self.edges[1]);
         let det = self.edges[0].dot(dir_cross_e2);
         if det.abs() < EPSILON {
-            return vec![];
+            return;
         }
 
         let f = 1.0 / det;
         let p1_to_origin = local_ray.origin - self.vertices[0];
         let u = f * p1_to_origin.dot(dir_cross_e2);
-        if u < 0.0 || u > 1.0 {
-            return vec![];
+        if !(0.0..=1.0).contains(&u) {
+            return;
         }
 
         let origin_cross_e1 = p1_to_origin.cross(self.edges[0]);
         let v = f * local_ray.direction.dot(origin_cross_e1);
         if v < 0.0 || (u + v) > 1.0 {
-            return vec![];
+            return;
         }
 
         let t = f * self.edges[1].dot(origin_cross_e1);
-        vec![t].iter().map(|&t| Coordinates::new(t, None)).collect()
+        intersections.push(Coordinates::new(t, Some((u, v))));
+    }
+
+    fn vertex_colour(&self, uv_coordinates: Option<(f64, f64)>) -> Option<Colour> {
+        let [c1, c2, c3] = self.colours?;
+        let (u, v) = uv_coordinates?;
+        Some(c2 * u + c3 * v + c1 * (1.0 - u - v))
+    }
+
+    fn texture_coordinates_at(&self, uv_coordinates: Option<(f64, f64)>) -> Option<(f64, f64)> {
+        Some(Triangle::blend_texture(
+            self.texture_coordinates?,
+            uv_coordinates?,
+        ))
+    }
+
+    fn texture_footprint(&self, footprint: [Vector; 2]) -> Option<[(f64, f64); 2]> {
+        Some(Triangle::texture_offsets(
+            self.edges,
+            self.texture_coordinates?,
+            footprint,
+        ))
     }
 }
 
@@ -73,8 +196,11 @@ impl Bounded for Triangle {
 #[derive(Debug, Default)]
 pub struct TriangleBuilder {
     frame_transformation: Option<Transform>,
-    material: Option<Material>,
+    material: Option<Arc<Material>>,
+    name: Option<String>,
     vertices: Option<[Point; 3]>,
+    colours: Option<[Colour; 3]>,
+    texture_coordinates: Option<[(f64, f64); 3]>,
 }
 
 impl TriangleBuilder {
@@ -84,14 +210,37 @@ impl TriangleBuilder {
     }
 
     pub fn set_material(mut self, material: Material) -> TriangleBuilder {
+        self.material = Some(Arc::new(material));
+        self
+    }
+
+    pub fn set_shared_material(mut self, material: Arc<Material>) -> TriangleBuilder {
         self.material = Some(material);
         self
     }
 
+    pub fn set_name(mut self, name: &str) -> TriangleBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+
     pub fn set_vertices(mut self, vertices: [Point; 3]) -> TriangleBuilder {
         self.vertices = Some(vertices);
         self
     }
+
+    pub fn set_colours(mut self, colours: [Colour; 3]) -> TriangleBuilder {
+        self.colours = Some(colours);
+        self
+    }
+
+    pub fn set_texture_coordinates(
+        mut self,
+        texture_coordinates: [(f64, f64); 3],
+    ) -> TriangleBuilder {
+        self.texture_coordinates = Some(texture_coordinates);
+        self
+    }
 }
 
 impl Buildable for Triangle {
@@ -106,31 +255,41 @@ impl ConsumingBuilder for TriangleBuilder {
     type Built = Triangle;
 
     fn build(self) -> Self::Built {
-        let frame_transformation = self.frame_transformation.unwrap_or_default();
-        let material = self.material.unwrap_or_default();
-        let [v1, v2, v3] = self.vertices.unwrap();
+        self.try_build().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // a triangle with no area is reported rather than given a NaN normal
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        let [v1, v2, v3] = self.vertices.ok_or(BuildError::MissingField("vertices"))?;
         let e1 = v2 - v1;
         let e2 = v3 - v1;
-        let normal = e2.cross(e1).normalise();
+        let normal = e2.cross(e1);
+        if normal.magnitude() == 0.0 {
+            return Err(BuildError::DegenerateTriangle);
+        }
+        check_frame_transformation(&self.frame_transformation)?;
+        let frame_transformation = self.frame_transformation.unwrap_or_default();
         let bounds = Bounds::new(
             BoundingBox::from_anchors(vec![v1, v2, v3]).transform(&frame_transformation),
         );
 
-        let triangle = Triangle {
+        Ok(Triangle {
             frame_transformation,
-            material,
+            material: self.material.unwrap_or_default(),
+            name: self.name,
             vertices: [v1, v2, v3],
             edges: [e1, e2],
-            normal,
+            normal: normal.normalise(),
+            colours: self.colours,
+            texture_coordinates: self.texture_coordinates,
             bounds,
-        };
-        triangle
+        })
     }
 }
 
-impl Into<Shape> for Triangle {
-    fn into(self) -> Shape {
-        Shape::Primitive(Box::new(self))
+impl From<Triangle> for Shape {
+    fn from(triangle: Triangle) -> Shape {
+        Shape::Primitive(Box::new(triangle))
     }
 }
 
@@ -199,4 +358,91 @@ mod tests {
         assert_eq!(t_values.len(), 1);
         assert_eq!(t_values[0].t(), 2.0);
     }
+
+    #[test]
+    fn texture_coordinates_across_triangle() {
+        let triangle = Triangle::builder()
+            .set_vertices([
+                Point::new(0.0, 0.0, 0.0),
+                Point::new(2.0, 0.0, 0.0),
+                Point::new(0.0, 2.0, 0.0),
+            ])
+            .set_texture_coordinates([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)])
+            .build();
+        let ray = Ray::new(Point::new(0.5, 1.0, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let uv_coordinates = triangle.local_intersect(&ray)[0].uv_coordinates();
+        assert_eq!(
+            triangle.texture_coordinates_at(uv_coordinates),
+            Some((0.25, 0.5))
+        );
+        // offsets off the plane of the triangle are projected onto it
+        let footprint = [Vector::new(0.5, 0.0, 1.0), Vector::new(0.0, 1.0, 0.0)];
+        assert_eq!(
+            triangle.texture_footprint(footprint),
+            Some([(0.25, 0.0), (0.0, 0.5)])
+        );
+    }
+
+    #[test]
+    fn triangles_share_material() {
+        let material = Arc::new(Material::preset());
+        let vertices = [
+            Point::new(0.0, 1.0, 0.0),
+            Point::new(-1.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+        ];
+        let triangles: Vec<Triangle> = (0..3)
+            .map(|_| {
+                Triangle::builder()
+                    .set_shared_material(Arc::clone(&material))
+                    .set_vertices(vertices)
+                    .build()
+            })
+            .collect();
+        assert_eq!(Arc::strong_count(&material), 4);
+        assert!(std::ptr::eq(
+            triangles[0].material(),
+            triangles[2].material()
+        ));
+    }
+
+    #[test]
+    fn validate_triangle_builder() {
+        assert_eq!(
+            Triangle::builder().try_build().err(),
+            Some(BuildError::MissingField("vertices"))
+        );
+        let collinear = [
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 1.0, 0.0),
+            Point::new(2.0, 2.0, 0.0),
+        ];
+        assert_eq!(
+            Triangle::builder()
+                .set_vertices(collinear)
+                .try_build()
+                .err(),
+            Some(BuildError::DegenerateTriangle)
+        );
+    }
+
+    #[test]
+    fn closest_point_on_face_edges_and_corners() {
+        let vertices = [
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(2.0, 0.0, 0.0),
+            Point::new(0.0, 2.0, 0.0),
+        ];
+        let test_cases = [
+            (Point::new(0.5, 0.5, 3.0), Point::new(0.5, 0.5, 0.0)),
+            (Point::new(1.0, -1.0, 0.0), Point::new(1.0, 0.0, 0.0)),
+            (Point::new(2.0, 2.0, 1.0), Point::new(1.0, 1.0, 0.0)),
+            (Point::new(-1.0, -1.0, 0.0), Point::new(0.0, 0.0, 0.0)),
+            (Point::new(3.0, -1.0, 0.0), Point::new(2.0, 0.0, 0.0)),
+            (Point::new(-1.0, 4.0, 0.0), Point::new(0.0, 2.0, 0.0)),
+        ];
+        for (point, closest) in test_cases {
+            assert_eq!(Triangle::closest_point(vertices, point), closest);
+        }
+    }
 }
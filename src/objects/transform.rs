@@ -1,9 +1,22 @@
+use std::fmt;
 use std::ops::Mul;
+use std::sync::OnceLock;
 
-use crate::collections::{Angle, Matrix, Tuple4};
+use crate::collections::simd::{self, Matrix4};
+use crate::collections::{Angle, Matrix, Tuple4, Vector};
+use crate::utils::BuildError;
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct Transform(pub Matrix);
+// The matrix is fixed once the transformation is made, so that the inverse
+// kept alongside it always matches; new transformations come from Transform::new
+// or from a Matrix through From. It is kept as a plain 4x4 array so that
+// transforming tuples and composing transformations never allocate.
+#[derive(Clone)]
+pub struct Transform(Matrix4, Inverses);
+
+// the inverse and its transpose, which are needed for every ray meeting a
+// transformed shape, worked out once on first use rather than per ray
+#[derive(Clone, Default)]
+struct Inverses(OnceLock<Box<(Transform, Transform)>>);
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TransformKind {
@@ -11,6 +24,8 @@ pub enum TransformKind {
     Translate(f64, f64, f64),
     Scale(f64, f64, f64),
     Reflect(Axis),
+    // mirrors across the plane of points p with normal · p = offset
+    ReflectAcrossPlane(Vector, f64),
     Rotate(Axis, Angle),
     Shear(f64, f64, f64, f64, f64, f64),
 }
@@ -23,7 +38,7 @@ pub enum Axis {
 }
 
 impl Transform {
-    const IDENTITY: [[f64; 4]; 4] = [
+    const IDENTITY: Matrix4 = [
         [1.0, 0.0, 0.0, 0.0],
         [0.0, 1.0, 0.0, 0.0],
         [0.0, 0.0, 1.0, 0.0],
@@ -40,6 +55,9 @@ impl Transform {
                 Axis::Y => Transform::reflect_in_y_axis(),
                 Axis::Z => Transform::reflect_in_z_axis(),
             },
+            TransformKind::ReflectAcrossPlane(normal, offset) => {
+                Transform::reflect_across_plane(normal, offset)
+            }
             TransformKind::Rotate(axis, angle) => match axis {
                 Axis::X => Transform::rotate_about_x_axis(angle),
                 Axis::Y => Transform::rotate_about_y_axis(angle),
@@ -51,31 +69,119 @@ impl Transform {
         }
     }
 
+    // checks the transformation can be undone, for callers that want to
+    // report a scale by zero (say) before it panics part way through a render
+    pub fn try_new(transform_kind: TransformKind) -> Result<Transform, BuildError> {
+        Transform::new(transform_kind).validated()
+    }
+
+    pub fn try_from_kinds(transform_kinds: Vec<TransformKind>) -> Result<Transform, BuildError> {
+        Transform::from(transform_kinds).validated()
+    }
+
+    pub fn validated(self) -> Result<Transform, BuildError> {
+        match self.is_invertible() {
+            true => Ok(self),
+            false => Err(BuildError::NonInvertibleTransform),
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix {
+        Matrix::from(&self.0.map(Vec::from).to_vec())
+    }
+
+    pub fn is_invertible(&self) -> bool {
+        let det = self.matrix().det();
+        det != 0.0 && det.is_finite()
+    }
+
+    // panics if the transformation is not invertible
     pub fn invert(&self) -> Transform {
-        Transform(self.0.invert())
+        self.inverse().clone()
+    }
+
+    // as invert, without copying the kept inverse
+    pub fn inverse(&self) -> &Transform {
+        &self.inverses().0
+    }
+
+    // the transformation for normals taken into the frame
+    pub fn inverse_transpose(&self) -> &Transform {
+        &self.inverses().1
+    }
+
+    fn inverses(&self) -> &(Transform, Transform) {
+        self.1 .0.get_or_init(|| {
+            let inverse = Transform::from(self.matrix().invert());
+            let inverse_transpose = inverse.transpose();
+            Box::new((inverse, inverse_transpose))
+        })
+    }
+
+    pub fn try_invert(&self) -> Result<Transform, BuildError> {
+        self.matrix()
+            .try_invert()
+            .map(Transform::from)
+            .ok_or(BuildError::NonInvertibleTransform)
     }
 
     pub fn transpose(&self) -> Transform {
-        Transform(self.0.transpose())
+        let matrix = self.0;
+        Transform::from_matrix4([0, 1, 2, 3].map(|i| matrix.map(|row| row[i])))
     }
 
     // transform_a.compose(transform_b) applies transform_a first then transform_b
     // Mul trait not implemented due to potential confusion on the order of application
     pub fn compose(&self, other: &Transform) -> Transform {
-        // clone to prevent moving Matrix out of original Transform
-        Transform(other.0.clone() * &self.0)
+        Transform::from_matrix4(simd::mul4x4(&other.0, &self.0))
+    }
+
+    fn from_matrix4(matrix: Matrix4) -> Transform {
+        Transform(matrix, Inverses::default())
     }
 }
 
+// for builders' try_build; an unset transformation is the identity
+pub(crate) fn check_frame_transformation(
+    frame_transformation: &Option<Transform>,
+) -> Result<(), BuildError> {
+    match frame_transformation {
+        Some(transform) if !transform.is_invertible() => Err(BuildError::NonInvertibleTransform),
+        _ => Ok(()),
+    }
+}
+
+// for references to the identity where no transformation is owned, such as
+// to fill out a TransformStack
+pub(crate) static IDENTITY: Transform = Transform(Transform::IDENTITY, Inverses(OnceLock::new()));
+
 impl Default for Transform {
     fn default() -> Transform {
         Transform::new(TransformKind::Identity)
     }
 }
 
+// panics unless the matrix is 4 by 4
 impl From<Matrix> for Transform {
     fn from(matrix: Matrix) -> Transform {
-        Transform(matrix)
+        let matrix = matrix
+            .to_matrix4()
+            .expect("transformations are 4 by 4 matrices");
+        Transform::from_matrix4(matrix)
+    }
+}
+
+// transformations are told apart by their matrices alone, whether or not
+// their inverses have been worked out
+impl PartialEq for Transform {
+    fn eq(&self, other: &Transform) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl fmt::Debug for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Transform").field(&self.matrix()).finish()
     }
 }
 
@@ -90,85 +196,101 @@ impl From<Vec<TransformKind>> for Transform {
 }
 
 impl Transform {
-    fn base() -> Matrix {
-        Matrix::from(&Transform::IDENTITY.iter().map(|row| row.to_vec()).collect())
+    fn base() -> Matrix4 {
+        Transform::IDENTITY
     }
 
     fn identity() -> Transform {
-        let base_matrix = Transform::base();
-        Transform(base_matrix)
+        Transform::from_matrix4(Transform::base())
     }
 
     fn translate(x: f64, y: f64, z: f64) -> Transform {
         let mut translation_matrix = Transform::base();
-        translation_matrix[[0, 3]] = x;
-        translation_matrix[[1, 3]] = y;
-        translation_matrix[[2, 3]] = z;
-        Transform(translation_matrix)
+        translation_matrix[0][3] = x;
+        translation_matrix[1][3] = y;
+        translation_matrix[2][3] = z;
+        Transform::from_matrix4(translation_matrix)
     }
 
     fn scale(x: f64, y: f64, z: f64) -> Transform {
         let mut scaling_matrix = Transform::base();
-        scaling_matrix[[0, 0]] = x;
-        scaling_matrix[[1, 1]] = y;
-        scaling_matrix[[2, 2]] = z;
-        Transform(scaling_matrix)
+        scaling_matrix[0][0] = x;
+        scaling_matrix[1][1] = y;
+        scaling_matrix[2][2] = z;
+        Transform::from_matrix4(scaling_matrix)
     }
 
     fn reflect_in_x_axis() -> Transform {
         let mut reflection_matrix = Transform::base();
-        reflection_matrix[[0, 0]] = -1.0;
-        Transform(reflection_matrix)
+        reflection_matrix[0][0] = -1.0;
+        Transform::from_matrix4(reflection_matrix)
     }
 
     fn reflect_in_y_axis() -> Transform {
         let mut reflection_matrix = Transform::base();
-        reflection_matrix[[1, 1]] = -1.0;
-        Transform(reflection_matrix)
+        reflection_matrix[1][1] = -1.0;
+        Transform::from_matrix4(reflection_matrix)
     }
 
     fn reflect_in_z_axis() -> Transform {
         let mut reflection_matrix = Transform::base();
-        reflection_matrix[[2, 2]] = -1.0;
-        Transform(reflection_matrix)
+        reflection_matrix[2][2] = -1.0;
+        Transform::from_matrix4(reflection_matrix)
+    }
+
+    // a Householder reflection, moved off the origin to the plane; the normal
+    // need not be unit length, the offset being scaled along with it, but a
+    // zero normal leaves the transformation not invertible
+    fn reflect_across_plane(normal: Vector, offset: f64) -> Transform {
+        let length = normal.magnitude();
+        let unit = [normal.x, normal.y, normal.z].map(|n| n / length);
+        let distance = offset / length;
+        let mut reflection_matrix = Transform::base();
+        for i in 0..3 {
+            for j in 0..3 {
+                reflection_matrix[i][j] -= 2.0 * unit[i] * unit[j];
+            }
+            reflection_matrix[i][3] = 2.0 * distance * unit[i];
+        }
+        Transform::from_matrix4(reflection_matrix)
     }
 
-    fn rotate_about_x_axis(mut angle: Angle) -> Transform {
+    fn rotate_about_x_axis(angle: Angle) -> Transform {
         let mut rotation_matrix = Transform::base();
-        rotation_matrix[[1, 1]] = angle.radians().cos();
-        rotation_matrix[[1, 2]] = -angle.radians().sin();
-        rotation_matrix[[2, 1]] = angle.radians().sin();
-        rotation_matrix[[2, 2]] = angle.radians().cos();
-        Transform(rotation_matrix)
+        rotation_matrix[1][1] = angle.cos();
+        rotation_matrix[1][2] = -angle.sin();
+        rotation_matrix[2][1] = angle.sin();
+        rotation_matrix[2][2] = angle.cos();
+        Transform::from_matrix4(rotation_matrix)
     }
 
-    fn rotate_about_y_axis(mut angle: Angle) -> Transform {
+    fn rotate_about_y_axis(angle: Angle) -> Transform {
         let mut rotation_matrix = Transform::base();
-        rotation_matrix[[0, 0]] = angle.radians().cos();
-        rotation_matrix[[0, 2]] = angle.radians().sin();
-        rotation_matrix[[2, 0]] = -angle.radians().sin();
-        rotation_matrix[[2, 2]] = angle.radians().cos();
-        Transform(rotation_matrix)
+        rotation_matrix[0][0] = angle.cos();
+        rotation_matrix[0][2] = angle.sin();
+        rotation_matrix[2][0] = -angle.sin();
+        rotation_matrix[2][2] = angle.cos();
+        Transform::from_matrix4(rotation_matrix)
     }
 
-    fn rotate_about_z_axis(mut angle: Angle) -> Transform {
+    fn rotate_about_z_axis(angle: Angle) -> Transform {
         let mut rotation_matrix = Transform::base();
-        rotation_matrix[[0, 0]] = angle.radians().cos();
-        rotation_matrix[[0, 1]] = -angle.radians().sin();
-        rotation_matrix[[1, 0]] = angle.radians().sin();
-        rotation_matrix[[1, 1]] = angle.radians().cos();
-        Transform(rotation_matrix)
+        rotation_matrix[0][0] = angle.cos();
+        rotation_matrix[0][1] = -angle.sin();
+        rotation_matrix[1][0] = angle.sin();
+        rotation_matrix[1][1] = angle.cos();
+        Transform::from_matrix4(rotation_matrix)
     }
 
     fn shear(x_y: f64, x_z: f64, y_x: f64, y_z: f64, z_x: f64, z_y: f64) -> Transform {
         let mut shearing_matrix = Transform::base();
-        shearing_matrix[[0, 1]] = x_y;
-        shearing_matrix[[0, 2]] = x_z;
-        shearing_matrix[[1, 0]] = y_x;
-        shearing_matrix[[1, 2]] = y_z;
-        shearing_matrix[[2, 0]] = z_x;
-        shearing_matrix[[2, 1]] = z_y;
-        Transform(shearing_matrix)
+        shearing_matrix[0][1] = x_y;
+        shearing_matrix[0][2] = x_z;
+        shearing_matrix[1][0] = y_x;
+        shearing_matrix[1][2] = y_z;
+        shearing_matrix[2][0] = z_x;
+        shearing_matrix[2][1] = z_y;
+        Transform::from_matrix4(shearing_matrix)
     }
 }
 
@@ -176,7 +298,7 @@ impl Mul<&Matrix> for Transform {
     type Output = Matrix;
 
     fn mul(self, other: &Matrix) -> Self::Output {
-        self.0 * other
+        self.matrix() * other
     }
 }
 
@@ -189,7 +311,7 @@ pub trait Transformable {
 
 impl<T: Tuple4 + From<Matrix>> Transformable for T {
     fn transform(self, transform: &Transform) -> T {
-        T::from(transform.clone() * &Matrix::from(self))
+        T::from_tuple4(simd::mul4x4_tuple(&transform.0, self.to_tuple4()))
     }
 }
 
@@ -205,7 +327,7 @@ mod tests {
     #[test]
     fn create_identity_transform() {
         let transform = Transform::new(TransformKind::Identity);
-        let resulting_transform = Transform(Matrix::from(&vec![
+        let resulting_transform = Transform::from(Matrix::from(&vec![
             vec![1.0, 0.0, 0.0, 0.0],
             vec![0.0, 1.0, 0.0, 0.0],
             vec![0.0, 0.0, 1.0, 0.0],
@@ -231,7 +353,7 @@ mod tests {
     #[test]
     fn create_translation_transform() {
         let transform = Transform::new(TransformKind::Translate(5.0, -3.0, 2.0));
-        let resulting_transform = Transform(Matrix::from(&vec![
+        let resulting_transform = Transform::from(Matrix::from(&vec![
             vec![1.0, 0.0, 0.0, 5.0],
             vec![0.0, 1.0, 0.0, -3.0],
             vec![0.0, 0.0, 1.0, 2.0],
@@ -266,7 +388,7 @@ mod tests {
     #[test]
     fn create_scaling_transform() {
         let transform = Transform::new(TransformKind::Scale(2.0, 3.0, 4.0));
-        let resulting_transform = Transform(Matrix::from(&vec![
+        let resulting_transform = Transform::from(Matrix::from(&vec![
             vec![2.0, 0.0, 0.0, 0.0],
             vec![0.0, 3.0, 0.0, 0.0],
             vec![0.0, 0.0, 4.0, 0.0],
@@ -296,19 +418,19 @@ mod tests {
         let transform_x = Transform::new(TransformKind::Reflect(Axis::X));
         let transform_y = Transform::new(TransformKind::Reflect(Axis::Y));
         let transform_z = Transform::new(TransformKind::Reflect(Axis::Z));
-        let resulting_transform_x = Transform(Matrix::from(&vec![
+        let resulting_transform_x = Transform::from(Matrix::from(&vec![
             vec![-1.0, 0.0, 0.0, 0.0],
             vec![0.0, 1.0, 0.0, 0.0],
             vec![0.0, 0.0, 1.0, 0.0],
             vec![0.0, 0.0, 0.0, 1.0],
         ]));
-        let resulting_transform_y = Transform(Matrix::from(&vec![
+        let resulting_transform_y = Transform::from(Matrix::from(&vec![
             vec![1.0, 0.0, 0.0, 0.0],
             vec![0.0, -1.0, 0.0, 0.0],
             vec![0.0, 0.0, 1.0, 0.0],
             vec![0.0, 0.0, 0.0, 1.0],
         ]));
-        let resulting_transform_z = Transform(Matrix::from(&vec![
+        let resulting_transform_z = Transform::from(Matrix::from(&vec![
             vec![1.0, 0.0, 0.0, 0.0],
             vec![0.0, 1.0, 0.0, 0.0],
             vec![0.0, 0.0, -1.0, 0.0],
@@ -347,27 +469,72 @@ mod tests {
         assert_eq!(vector.transform(&transform_z), resulting_vector_z);
     }
 
+    #[test]
+    fn reflect_across_plane() {
+        // the plane x + y = 2, scaled to show the normal need not be unit length
+        let transform = Transform::new(TransformKind::ReflectAcrossPlane(
+            Vector::new(2.0, 2.0, 0.0),
+            4.0,
+        ));
+        let point = Point::new(0.0, 0.0, 3.0).transform(&transform);
+        approx_eq!(point.x, 2.0);
+        approx_eq!(point.y, 2.0);
+        approx_eq!(point.z, 3.0);
+        let on_plane = Point::new(2.0, 0.0, -1.0).transform(&transform);
+        approx_eq!(on_plane.x, 2.0);
+        approx_eq!(on_plane.y, 0.0);
+        let vector = Vector::new(1.0, 0.0, 5.0).transform(&transform);
+        approx_eq!(vector.x, 0.0);
+        approx_eq!(vector.y, -1.0);
+        approx_eq!(vector.z, 5.0);
+    }
+
+    #[test]
+    fn reflections_across_planes_undo_themselves() {
+        let transform = Transform::new(TransformKind::ReflectAcrossPlane(
+            Vector::new(1.0, -2.0, 3.0),
+            1.5,
+        ));
+        let twice = transform.compose(&transform);
+        let point = Point::new(-4.0, 0.5, 7.0);
+        let returned = point.transform(&twice);
+        approx_eq!(returned.x, point.x);
+        approx_eq!(returned.y, point.y);
+        approx_eq!(returned.z, point.z);
+        // the axis reflections are the planes through the origin
+        assert_eq!(
+            Transform::new(TransformKind::ReflectAcrossPlane(
+                Vector::new(0.0, 1.0, 0.0),
+                0.0
+            )),
+            Transform::new(TransformKind::Reflect(Axis::Y))
+        );
+        assert!(
+            Transform::try_new(TransformKind::ReflectAcrossPlane(Vector::zero(), 1.0)).is_err()
+        );
+    }
+
     #[test]
     fn create_rotation_transform() {
-        let mut r = Angle::from_radians(MATH_FRAC_PI_2);
+        let r = Angle::from_radians(MATH_FRAC_PI_2);
         let transform_x = Transform::new(TransformKind::Rotate(Axis::X, r));
         let transform_y = Transform::new(TransformKind::Rotate(Axis::Y, r));
         let transform_z = Transform::new(TransformKind::Rotate(Axis::Z, r));
-        let sin_r = r.radians().sin();
-        let cos_r = r.radians().cos();
-        let resulting_transform_x = Transform(Matrix::from(&vec![
+        let sin_r = r.sin();
+        let cos_r = r.cos();
+        let resulting_transform_x = Transform::from(Matrix::from(&vec![
             vec![1.0, 0.0, 0.0, 0.0],
             vec![0.0, cos_r, -sin_r, 0.0],
             vec![0.0, sin_r, cos_r, 0.0],
             vec![0.0, 0.0, 0.0, 1.0],
         ]));
-        let resulting_transform_y = Transform(Matrix::from(&vec![
+        let resulting_transform_y = Transform::from(Matrix::from(&vec![
             vec![cos_r, 0.0, sin_r, 0.0],
             vec![0.0, 1.0, 0.0, 0.0],
             vec![-sin_r, 0.0, cos_r, 0.0],
             vec![0.0, 0.0, 0.0, 1.0],
         ]));
-        let resulting_transform_z = Transform(Matrix::from(&vec![
+        let resulting_transform_z = Transform::from(Matrix::from(&vec![
             vec![cos_r, -sin_r, 0.0, 0.0],
             vec![sin_r, cos_r, 0.0, 0.0],
             vec![0.0, 0.0, 1.0, 0.0],
@@ -427,7 +594,7 @@ mod tests {
     #[test]
     fn create_shearing_transform() {
         let transform = Transform::new(TransformKind::Shear(2.0, 3.0, 4.0, 5.0, 6.0, 7.0));
-        let resulting_transform = Transform(Matrix::from(&vec![
+        let resulting_transform = Transform::from(Matrix::from(&vec![
             vec![1.0, 2.0, 3.0, 0.0],
             vec![4.0, 1.0, 5.0, 0.0],
             vec![6.0, 7.0, 1.0, 0.0],
@@ -499,4 +666,37 @@ mod tests {
         ]);
         assert_eq!(chained_transform, resulting_transform);
     }
+
+    #[test]
+    fn detect_non_invertible_transforms() {
+        assert_eq!(
+            Transform::try_new(TransformKind::Scale(2.0, 0.0, 1.0)),
+            Err(BuildError::NonInvertibleTransform)
+        );
+        assert!(Transform::try_from_kinds(vec![
+            TransformKind::Scale(2.0, 2.0, 2.0),
+            TransformKind::Translate(1.0, 0.0, 0.0),
+        ])
+        .is_ok());
+        let flattened = Transform::new(TransformKind::Shear(1.0, 0.0, 1.0, 0.0, 0.0, 0.0));
+        assert!(flattened.try_invert().is_err());
+    }
+
+    #[test]
+    fn inverse_is_kept() {
+        let transform = Transform::from(vec![
+            TransformKind::Rotate(Axis::Y, Angle::from_radians(MATH_FRAC_PI_2)),
+            TransformKind::Translate(1.0, 2.0, 3.0),
+        ]);
+        let unused = transform.clone();
+        let inverse = transform.inverse();
+        assert_eq!(inverse, &Transform::from(transform.matrix().invert()));
+        assert_eq!(transform.inverse_transpose(), &inverse.transpose());
+        // later calls return the kept inverse
+        assert!(std::ptr::eq(inverse, transform.inverse()));
+
+        // keeping the inverse changes neither equality nor the debug output
+        assert_eq!(transform, unused);
+        assert_eq!(format!("{:?}", transform), format!("{:?}", unused));
+    }
 }
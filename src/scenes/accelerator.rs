@@ -0,0 +1,75 @@
+use std::fmt::Debug;
+use std::ops::Range;
+
+use crate::objects::*;
+
+// A structure over a world's objects that narrows down which of them a ray
+// has to be tested against, so that different structures (such as the
+// instance tree and kd-tree) can be swapped in and compared on the same
+// scenes. Structures refer to objects by their place in the list they were
+// built over, and are handed that list again whenever they are used.
+pub trait Accelerator: Debug + Send + Sync {
+    // builds the structure afresh over the objects, keeping its settings
+    fn build(&mut self, objects: &[Shape]);
+
+    // brings the structure up to date after objects have moved, with none
+    // added or removed; structures that cannot do better than rebuilding
+    // keep the default
+    fn refit(&mut self, objects: &[Shape]) {
+        self.build(objects);
+    }
+
+    // adds every intersection of the ray with the objects to the register,
+    // as testing each object in turn would; objects lying wholly outside the
+    // range of t may be left out, so callers still clip the hits to it
+    fn intersect_ray_into<'world: 'ray, 'ray>(
+        &self,
+        objects: &'world [Shape],
+        world_ray: &'ray Ray,
+        t_range: Range<f64>,
+        transform_stack: &mut Vec<&'ray Transform>,
+        hit_register: &mut HitRegister<'ray, dyn PrimitiveShape>,
+    );
+
+    // whether any object lies along the ray within the range; structures
+    // that can stop at the first such object override this
+    fn is_occluded(&self, objects: &[Shape], world_ray: &Ray, t_range: Range<f64>) -> bool {
+        let mut hit_register = HitRegister::kept();
+        let mut transform_stack = kept_transform_stack();
+        self.intersect_ray_into(
+            objects,
+            world_ray,
+            t_range.clone(),
+            &mut transform_stack,
+            &mut hit_register,
+        );
+        keep_transform_stack(transform_stack);
+
+        let hit_register = hit_register.clip(t_range);
+        let occluded = !hit_register.is_empty();
+        hit_register.keep();
+        occluded
+    }
+}
+
+// Tests every object in turn, as a world with no accelerator does, which is
+// quickest for scenes of only a few objects.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinearScan;
+
+impl Accelerator for LinearScan {
+    fn build(&mut self, _objects: &[Shape]) {}
+
+    fn intersect_ray_into<'world: 'ray, 'ray>(
+        &self,
+        objects: &'world [Shape],
+        world_ray: &'ray Ray,
+        _t_range: Range<f64>,
+        transform_stack: &mut Vec<&'ray Transform>,
+        hit_register: &mut HitRegister<'ray, dyn PrimitiveShape>,
+    ) {
+        for object in objects {
+            object.intersect_ray_into(world_ray, transform_stack, hit_register);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::collections::Colour;
+use crate::scenes::{Canvas, Height, Width};
+use crate::utils::RenderError;
+
+// Running sums of the samples landing on each pixel, kept at full precision
+// until the render is resolved into a canvas. Each sample is weighted by how
+// much of the pixel it stands for, and resolving divides by the total weight,
+// so a pixel's colour is the weighted mean of its samples however many there
+// are; further passes can keep adding to the same buffer to refine it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccumulationBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<AccumulatedPixel>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct AccumulatedPixel {
+    colour: Colour,
+    weight: f64,
+    sample_count: u64,
+}
+
+impl AccumulatedPixel {
+    const EMPTY: AccumulatedPixel = AccumulatedPixel {
+        colour: Colour::BLACK,
+        weight: 0.0,
+        sample_count: 0,
+    };
+}
+
+impl AccumulationBuffer {
+    pub fn new(Width(width): Width, Height(height): Height) -> AccumulationBuffer {
+        AccumulationBuffer {
+            width,
+            height,
+            pixels: vec![AccumulatedPixel::EMPTY; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn add_sample(
+        &mut self,
+        [column, row]: [usize; 2],
+        colour: Colour,
+        weight: f64,
+    ) -> Result<(), RenderError> {
+        let pixel = self.pixel_mut([column, row])?;
+        pixel.colour += colour * weight;
+        pixel.weight += weight;
+        pixel.sample_count += 1;
+        Ok(())
+    }
+
+    // adds every sample of another buffer onto this one with its top-left
+    // pixel at the given position
+    pub fn add_buffer(
+        &mut self,
+        column: usize,
+        row: usize,
+        buffer: &AccumulationBuffer,
+    ) -> Result<(), RenderError> {
+        if column + buffer.width > self.width || row + buffer.height > self.height {
+            return Err(self.out_of_bounds([column, row]));
+        }
+
+        for (buffer_row, pixels) in buffer.pixels.chunks(buffer.width.max(1)).enumerate() {
+            let start = (row + buffer_row) * self.width + column;
+            for (pixel, other) in self.pixels[start..start + pixels.len()]
+                .iter_mut()
+                .zip(pixels)
+            {
+                pixel.colour += other.colour;
+                pixel.weight += other.weight;
+                pixel.sample_count += other.sample_count;
+            }
+        }
+        Ok(())
+    }
+
+    // sets the pixel's mean to the colour, keeping the weight of the samples it
+    // replaces (or a weight of one if it had none), as for lines drawn over a
+    // render
+    pub fn replace_colour(
+        &mut self,
+        [column, row]: [usize; 2],
+        colour: Colour,
+    ) -> Result<(), RenderError> {
+        let pixel = self.pixel_mut([column, row])?;
+        if pixel.weight <= 0.0 {
+            pixel.weight = 1.0;
+            pixel.sample_count = 1;
+        }
+        pixel.colour = colour * pixel.weight;
+        Ok(())
+    }
+
+    // the weighted mean of the pixel's samples, if it has any
+    pub fn colour(&self, [column, row]: [usize; 2]) -> Option<Colour> {
+        let pixel = self.pixel([column, row])?;
+        (pixel.weight > 0.0).then(|| pixel.colour / pixel.weight)
+    }
+
+    pub fn weight(&self, [column, row]: [usize; 2]) -> f64 {
+        self.pixel([column, row]).map_or(0.0, |pixel| pixel.weight)
+    }
+
+    pub fn sample_count(&self, [column, row]: [usize; 2]) -> u64 {
+        self.pixel([column, row])
+            .map_or(0, |pixel| pixel.sample_count)
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(AccumulatedPixel::EMPTY);
+    }
+
+    // a canvas of each pixel's weighted mean; pixels without samples are black
+    pub fn resolve(&self) -> Canvas {
+        let mut canvas = Canvas::new(Width(self.width), Height(self.height));
+        for (index, pixel) in canvas.pixels_mut().enumerate() {
+            if let Some(colour) = self.colour([index % self.width, index / self.width]) {
+                pixel.set_colour(colour);
+            }
+        }
+        canvas
+    }
+
+    fn pixel(&self, [column, row]: [usize; 2]) -> Option<&AccumulatedPixel> {
+        (column < self.width && row < self.height).then(|| &self.pixels[row * self.width + column])
+    }
+
+    fn pixel_mut(
+        &mut self,
+        [column, row]: [usize; 2],
+    ) -> Result<&mut AccumulatedPixel, RenderError> {
+        if column >= self.width || row >= self.height {
+            return Err(self.out_of_bounds([column, row]));
+        }
+        Ok(&mut self.pixels[row * self.width + column])
+    }
+
+    fn out_of_bounds(&self, index: [usize; 2]) -> RenderError {
+        RenderError::OutOfBounds {
+            index,
+            size: [self.width, self.height],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_weighted_mean_of_samples() {
+        let mut buffer = AccumulationBuffer::new(Width(2), Height(2));
+        buffer
+            .add_sample([1, 0], Colour::new(1.0, 0.0, 0.0), 0.25)
+            .unwrap();
+        buffer
+            .add_sample([1, 0], Colour::new(0.0, 1.0, 0.0), 0.25)
+            .unwrap();
+        buffer.add_sample([0, 1], Colour::WHITE, 0.5).unwrap();
+        assert_eq!(buffer.sample_count([1, 0]), 2);
+        assert_eq!(buffer.weight([1, 0]), 0.5);
+        assert_eq!(buffer.colour([0, 0]), None);
+
+        // weights need not sum to one over a pixel
+        let canvas = buffer.resolve();
+        assert_eq!(canvas[[1, 0]].colour(), Colour::new(0.5, 0.5, 0.0));
+        assert_eq!(canvas[[0, 1]].colour(), Colour::WHITE);
+        assert_eq!(canvas[[0, 0]].colour(), Colour::BLACK);
+
+        assert!(buffer.add_sample([2, 0], Colour::WHITE, 1.0).is_err());
+    }
+
+    #[test]
+    fn add_buffer_at_offset() {
+        let mut tile = AccumulationBuffer::new(Width(1), Height(2));
+        tile.add_sample([0, 1], Colour::WHITE, 1.0).unwrap();
+        let mut buffer = AccumulationBuffer::new(Width(3), Height(3));
+        buffer.add_sample([2, 2], Colour::BLACK, 1.0).unwrap();
+        buffer.add_buffer(2, 1, &tile).unwrap();
+        assert_eq!(buffer.sample_count([2, 2]), 2);
+        assert_eq!(buffer.colour([2, 2]), Some(Colour::new(0.5, 0.5, 0.5)));
+        assert_eq!(buffer.sample_count([2, 1]), 0);
+        assert!(buffer.add_buffer(2, 2, &tile).is_err());
+
+        buffer.replace_colour([2, 2], Colour::WHITE).unwrap();
+        assert_eq!(buffer.colour([2, 2]), Some(Colour::WHITE));
+        assert_eq!(buffer.weight([2, 2]), 2.0);
+        buffer.replace_colour([0, 0], Colour::WHITE).unwrap();
+        assert_eq!(buffer.colour([0, 0]), Some(Colour::WHITE));
+
+        buffer.clear();
+        assert_eq!(buffer.sample_count([2, 2]), 0);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::collections::{Angle, Colour, Point, Vector};
+use crate::objects::{Transform, TransformKind};
+use crate::scenes::{Camera, Canvas, Orientation, RayGenerator, World};
+use crate::utils::RenderError;
+
+// Shape of the change between one keyframe and the next, mapping the fraction
+// of the way through the segment to the fraction of the way between values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    // keeps the keyframe's value until the next keyframe
+    Hold,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Hold => 0.0,
+        }
+    }
+}
+
+// Values that can be blended, where t = 0 gives self and t = 1 gives other.
+pub trait Interpolate {
+    fn interpolate(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &f64, t: f64) -> f64 {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Point {
+    fn interpolate(&self, other: &Point, t: f64) -> Point {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Interpolate for Vector {
+    fn interpolate(&self, other: &Vector, t: f64) -> Vector {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Interpolate for Colour {
+    fn interpolate(&self, other: &Colour, t: f64) -> Colour {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Interpolate for Angle {
+    fn interpolate(&self, other: &Angle, t: f64) -> Angle {
+        Angle::from_radians(self.radians().interpolate(&other.radians(), t))
+    }
+}
+
+// Parameters of the same kind (and about the same axis) are blended; any
+// other pair switches over halfway.
+impl Interpolate for TransformKind {
+    fn interpolate(&self, other: &TransformKind, t: f64) -> TransformKind {
+        let lerp = |a: f64, b: f64| a.interpolate(&b, t);
+        match (*self, *other) {
+            (TransformKind::Translate(x0, y0, z0), TransformKind::Translate(x1, y1, z1)) => {
+                TransformKind::Translate(lerp(x0, x1), lerp(y0, y1), lerp(z0, z1))
+            }
+            (TransformKind::Scale(x0, y0, z0), TransformKind::Scale(x1, y1, z1)) => {
+                TransformKind::Scale(lerp(x0, x1), lerp(y0, y1), lerp(z0, z1))
+            }
+            (TransformKind::Rotate(axis, a0), TransformKind::Rotate(other_axis, a1))
+                if axis == other_axis =>
+            {
+                TransformKind::Rotate(axis, a0.interpolate(&a1, t))
+            }
+            (
+                TransformKind::Shear(a0, b0, c0, d0, e0, f0),
+                TransformKind::Shear(a1, b1, c1, d1, e1, f1),
+            ) => TransformKind::Shear(
+                lerp(a0, a1),
+                lerp(b0, b1),
+                lerp(c0, c1),
+                lerp(d0, d1),
+                lerp(e0, e1),
+                lerp(f0, f1),
+            ),
+            (from, to) => match t < 0.5 {
+                true => from,
+                false => to,
+            },
+        }
+    }
+}
+
+// A transform keyframed as the list of steps that make it up, blended step by
+// step, so that rotations turn rather than shear as a blended matrix would.
+// Lists of different lengths switch over halfway.
+impl Interpolate for Vec<TransformKind> {
+    fn interpolate(&self, other: &Vec<TransformKind>, t: f64) -> Vec<TransformKind> {
+        match self.len() == other.len() {
+            true => self
+                .iter()
+                .zip(other)
+                .map(|(from, to)| from.interpolate(to, t))
+                .collect(),
+            false => match t < 0.5 {
+                true => self.clone(),
+                false => other.clone(),
+            },
+        }
+    }
+}
+
+// Camera placement as the points an Orientation is built from, which (unlike
+// the view transform itself) blend sensibly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LookAt {
+    pub from: Point,
+    pub to: Point,
+    pub up: Vector,
+}
+
+impl LookAt {
+    pub fn new(from: Point, to: Point, up: Vector) -> LookAt {
+        LookAt { from, to, up }
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        Orientation::new(self.from, self.to, self.up)
+    }
+}
+
+impl Interpolate for LookAt {
+    fn interpolate(&self, other: &LookAt, t: f64) -> LookAt {
+        LookAt {
+            from: self.from.interpolate(&other.from, t),
+            to: self.to.interpolate(&other.to, t),
+            up: self.up.interpolate(&other.up, t),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframe<T> {
+    pub time: f64,
+    pub value: T,
+    // how the value moves from this keyframe towards the next
+    pub easing: Easing,
+}
+
+// Keyframes ordered by time, starting from a value at time 0. Sampling before
+// the first keyframe or after the last gives that keyframe's value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: Interpolate + Clone> Track<T> {
+    pub fn new(value: T) -> Track<T> {
+        Track {
+            keyframes: vec![Keyframe {
+                time: 0.0,
+                value,
+                easing: Easing::default(),
+            }],
+        }
+    }
+
+    // replaces any keyframe already at the same time
+    pub fn with_keyframe(mut self, time: f64, value: T, easing: Easing) -> Track<T> {
+        let keyframe = Keyframe {
+            time,
+            value,
+            easing,
+        };
+        match self
+            .keyframes
+            .binary_search_by(|existing| existing.time.total_cmp(&time))
+        {
+            Ok(index) => self.keyframes[index] = keyframe,
+            Err(index) => self.keyframes.insert(index, keyframe),
+        }
+        self
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    pub fn sample(&self, time: f64) -> T {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        match (next.checked_sub(1), self.keyframes.get(next)) {
+            (Some(previous), Some(to)) => {
+                let from = &self.keyframes[previous];
+                let t = (time - from.time) / (to.time - from.time);
+                from.value.interpolate(&to.value, from.easing.apply(t))
+            }
+            (Some(previous), None) => self.keyframes[previous].value.clone(),
+            (None, _) => self.keyframes[0].value.clone(),
+        }
+    }
+}
+
+impl Track<Vec<TransformKind>> {
+    pub fn transform_at(&self, time: f64) -> Transform {
+        Transform::from(self.sample(time))
+    }
+}
+
+// times of each frame, starting from 0
+pub fn frame_times(frame_rate: f64, frame_count: usize) -> impl Iterator<Item = f64> {
+    (0..frame_count).map(move |frame| frame as f64 / frame_rate)
+}
+
+// Renders one canvas per frame, building the camera and world for each frame's
+// time with scene_at (typically by sampling tracks). Stops at the first frame
+// that fails to render.
+pub fn render_sequence<R: RayGenerator>(
+    frame_rate: f64,
+    frame_count: usize,
+    mut scene_at: impl FnMut(f64) -> (Camera<R>, World),
+) -> Result<Vec<Canvas>, RenderError> {
+    frame_times(frame_rate, frame_count)
+        .map(|time| {
+            let (camera, world) = scene_at(time);
+            camera.render(&world)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::*;
+    use crate::scenes::Native;
+    use crate::utils::{approx_eq, ApproxEq, BuildInto, Buildable};
+
+    #[test]
+    fn easing_curves() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            approx_eq!(easing.apply(0.0), 0.0);
+            approx_eq!(easing.apply(1.0), 1.0);
+        }
+        approx_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        approx_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        approx_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        approx_eq!(Easing::Hold.apply(0.9), 0.0);
+    }
+
+    #[test]
+    fn sample_track_between_keyframes() {
+        let track = Track::new(0.0)
+            .with_keyframe(2.0, 10.0, Easing::EaseIn)
+            .with_keyframe(1.0, 4.0, Easing::Hold)
+            .with_keyframe(3.0, 20.0, Easing::Linear);
+        approx_eq!(track.sample(-1.0), 0.0);
+        approx_eq!(track.sample(0.5), 2.0);
+        approx_eq!(track.sample(1.5), 4.0);
+        approx_eq!(track.sample(2.5), 12.5);
+        approx_eq!(track.sample(3.0), 20.0);
+        approx_eq!(track.sample(5.0), 20.0);
+    }
+
+    #[test]
+    fn keyframed_transform_rotates() {
+        let track = Track::new(vec![TransformKind::Rotate(
+            Axis::Y,
+            Angle::from_radians(0.0),
+        )])
+        .with_keyframe(
+            1.0,
+            vec![TransformKind::Rotate(
+                Axis::Y,
+                Angle::from_radians(std::f64::consts::PI),
+            )],
+            Easing::Linear,
+        );
+        let halfway = Point::new(1.0, 0.0, 0.0).transform(&track.transform_at(0.5));
+        assert!(halfway.approx_eq(&Point::new(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn render_turntable_sequence() {
+        let camera_path = Track::new(LookAt::new(
+            Point::new(0.0, 0.0, -5.0),
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        ))
+        .with_keyframe(
+            1.0,
+            LookAt::new(
+                Point::new(5.0, 0.0, 0.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ),
+            Easing::EaseInOut,
+        );
+        let sphere_offset = Track::new(0.0).with_keyframe(1.0, 10.0, Easing::Linear);
+
+        let frames = render_sequence(2.0, 3, |time| {
+            let sphere = Sphere::builder()
+                .set_material(Material::preset())
+                .set_frame_transformation(Transform::new(TransformKind::Translate(
+                    0.0,
+                    sphere_offset.sample(time),
+                    0.0,
+                )))
+                .build_into();
+            let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+            let camera = Camera::new(Native::new(
+                5,
+                5,
+                Angle::from_degrees(60.0),
+                camera_path.sample(time).orientation(),
+            ));
+            (camera, World::new(vec![sphere], vec![light]))
+        })
+        .unwrap();
+
+        assert_eq!(frames.len(), 3);
+        // the sphere is centred in the first frame and has left by the last
+        assert_ne!(frames[0][[2, 2]].colour(), Colour::new(0.0, 0.0, 0.0));
+        assert_eq!(frames[2][[2, 2]].colour(), Colour::new(0.0, 0.0, 0.0));
+    }
+}
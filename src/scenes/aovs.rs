@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use crate::collections::{Colour, Vector};
+use crate::objects::{PrimitiveShape, Shape};
+use crate::scenes::{Canvas, Height, Width};
+
+// What a primary ray sees of the first surface it hits. Depth is the distance
+// to the hit, the normal is in world space and faces the camera, and the
+// object ID counts primitives depth-first through the world's objects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct SurfaceRecord {
+    pub depth: f64,
+    pub normal: Vector,
+    pub albedo: Colour,
+    pub object_id: Option<usize>,
+}
+
+// IDs of a world's primitives, counted depth-first through groups and both
+// operands of CSG shapes, looked up by each primitive's address so that a hit
+// is tagged without searching the world
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ObjectIds(HashMap<usize, usize>);
+
+impl ObjectIds {
+    pub(crate) fn new(objects: &[Shape]) -> ObjectIds {
+        fn number(shape: &Shape, ids: &mut HashMap<usize, usize>) {
+            match shape {
+                Shape::Primitive(primitive) => {
+                    let id = ids.len();
+                    ids.insert(address(primitive.as_ref()), id);
+                }
+                Shape::Group(group) => group.objects().iter().for_each(|child| number(child, ids)),
+                Shape::Csg(csg) => {
+                    number(csg.lshape(), ids);
+                    number(csg.rshape(), ids);
+                }
+                Shape::Clipped(clipped) => {
+                    number(clipped.shape(), ids);
+                    if let Some(cap) = clipped.cap() {
+                        number(cap, ids);
+                    }
+                }
+            }
+        }
+
+        let mut ids = HashMap::new();
+        objects.iter().for_each(|shape| number(shape, &mut ids));
+        ObjectIds(ids)
+    }
+
+    pub(crate) fn id_of(&self, object: &dyn PrimitiveShape) -> Option<usize> {
+        self.0.get(&address(object)).copied()
+    }
+}
+
+pub(crate) fn address(object: &dyn PrimitiveShape) -> usize {
+    (object as *const dyn PrimitiveShape).cast::<()>() as usize
+}
+
+// Auxiliary render passes (AOVs) recorded alongside the beauty pass, for use by
+// denoisers and compositing. Depth, normal and albedo are blended by the same
+// weights as the beauty pass; a pixel has no surface when most of the rays
+// through it miss. Object IDs cannot be blended, so each pixel keeps the ID of
+// the object covering most of it, by the total weight of the rays hitting each.
+#[derive(Clone, Debug)]
+pub struct AuxiliaryBuffers {
+    width: usize,
+    height: usize,
+    samples: Vec<AovSample>,
+}
+
+#[derive(Clone, Debug)]
+struct AovSample {
+    depth: f64,
+    normal: Vector,
+    albedo: Colour,
+    hit_weight: f64,
+    total_weight: f64,
+    object_weights: Vec<(usize, f64)>,
+}
+
+impl AuxiliaryBuffers {
+    pub(crate) fn new(width: usize, height: usize) -> AuxiliaryBuffers {
+        let sample = AovSample {
+            depth: 0.0,
+            normal: Vector::zero(),
+            albedo: Colour::new(0.0, 0.0, 0.0),
+            hit_weight: 0.0,
+            total_weight: 0.0,
+            object_weights: vec![],
+        };
+        AuxiliaryBuffers {
+            width,
+            height,
+            samples: vec![sample; width * height],
+        }
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        [pos_x, pos_y]: [usize; 2],
+        surface: Option<SurfaceRecord>,
+        blend_weight: f64,
+    ) {
+        let sample = &mut self.samples[pos_y * self.width + pos_x];
+        sample.total_weight += blend_weight;
+        if let Some(surface) = surface {
+            sample.depth += surface.depth * blend_weight;
+            sample.normal = sample.normal + surface.normal * blend_weight;
+            sample.albedo += surface.albedo * blend_weight;
+            sample.hit_weight += blend_weight;
+            if let Some(object_id) = surface.object_id {
+                match sample
+                    .object_weights
+                    .iter_mut()
+                    .find(|(id, _)| *id == object_id)
+                {
+                    Some((_, weight)) => *weight += blend_weight,
+                    None => sample.object_weights.push((object_id, blend_weight)),
+                }
+            }
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn hit_sample(&self, [pos_x, pos_y]: [usize; 2]) -> Option<&AovSample> {
+        let sample = &self.samples[pos_y * self.width + pos_x];
+        match sample.hit_weight == 0.0 || sample.hit_weight < sample.total_weight / 2.0 {
+            true => None,
+            false => Some(sample),
+        }
+    }
+
+    pub fn depth(&self, index: [usize; 2]) -> Option<f64> {
+        self.hit_sample(index)
+            .map(|sample| sample.depth / sample.hit_weight)
+    }
+
+    pub fn normal(&self, index: [usize; 2]) -> Option<Vector> {
+        self.hit_sample(index)
+            .map(|sample| sample.normal.normalise())
+    }
+
+    pub fn albedo(&self, index: [usize; 2]) -> Option<Colour> {
+        self.hit_sample(index)
+            .map(|sample| sample.albedo * (1.0 / sample.hit_weight))
+    }
+
+    pub fn object_id(&self, index: [usize; 2]) -> Option<usize> {
+        self.hit_sample(index).and_then(|sample| {
+            sample
+                .object_weights
+                .iter()
+                .fold(
+                    None,
+                    |best: Option<(usize, f64)>, &(id, weight)| match best {
+                        Some((_, best_weight)) if best_weight >= weight => best,
+                        _ => Some((id, weight)),
+                    },
+                )
+                .map(|(id, _)| id)
+        })
+    }
+
+    // depth scaled so that the furthest surface is white; misses are black
+    pub fn depth_canvas(&self) -> Canvas {
+        let furthest = self
+            .indices()
+            .filter_map(|index| self.depth(index))
+            .fold(0.0, f64::max);
+        self.to_canvas(|index| {
+            let depth = self.depth(index).map_or(0.0, |depth| depth / furthest);
+            Colour::new(depth, depth, depth)
+        })
+    }
+
+    // normals mapped from [-1, 1] to [0, 1] per channel; misses are black
+    pub fn normal_canvas(&self) -> Canvas {
+        self.to_canvas(|index| match self.normal(index) {
+            Some(normal) => Colour::new(
+                (normal.x + 1.0) / 2.0,
+                (normal.y + 1.0) / 2.0,
+                (normal.z + 1.0) / 2.0,
+            ),
+            None => Colour::new(0.0, 0.0, 0.0),
+        })
+    }
+
+    pub fn albedo_canvas(&self) -> Canvas {
+        self.to_canvas(|index| self.albedo(index).unwrap_or(Colour::new(0.0, 0.0, 0.0)))
+    }
+
+    fn indices(&self) -> impl Iterator<Item = [usize; 2]> + '_ {
+        (0..self.height).flat_map(move |pos_y| (0..self.width).map(move |pos_x| [pos_x, pos_y]))
+    }
+
+    fn to_canvas(&self, colour_at: impl Fn([usize; 2]) -> Colour) -> Canvas {
+        let mut canvas = Canvas::new(Width(self.width), Height(self.height));
+        for index @ [pos_x, pos_y] in self.indices() {
+            canvas
+                .paint_colour_replace(pos_x, pos_y, colour_at(index))
+                .expect("index lies within the buffers");
+        }
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_blends_surfaces() {
+        let mut buffers = AuxiliaryBuffers::new(2, 1);
+        let surface = |depth: f64, object_id: usize| SurfaceRecord {
+            depth,
+            normal: Vector::new(0.0, 0.0, -1.0),
+            albedo: Colour::new(1.0, 0.5, 0.0),
+            object_id: Some(object_id),
+        };
+        buffers.record([0, 0], Some(surface(2.0, 3)), 0.25);
+        buffers.record([0, 0], Some(surface(4.0, 7)), 0.75);
+        buffers.record([1, 0], Some(surface(4.0, 7)), 0.25);
+        buffers.record([1, 0], None, 0.75);
+
+        assert_eq!(buffers.depth([0, 0]), Some(3.5));
+        assert_eq!(buffers.normal([0, 0]), Some(Vector::new(0.0, 0.0, -1.0)));
+        assert_eq!(buffers.albedo([0, 0]), Some(Colour::new(1.0, 0.5, 0.0)));
+        assert_eq!(buffers.object_id([0, 0]), Some(7));
+        assert_eq!(buffers.depth([1, 0]), None);
+        assert_eq!(buffers.object_id([1, 0]), None);
+
+        let depth_canvas = buffers.depth_canvas();
+        assert_eq!(depth_canvas[[0, 0]].colour(), Colour::new(1.0, 1.0, 1.0));
+        assert_eq!(depth_canvas[[1, 0]].colour(), Colour::new(0.0, 0.0, 0.0));
+
+        // several small hits on one object outweigh a single larger one
+        let mut buffers = AuxiliaryBuffers::new(1, 1);
+        buffers.record([0, 0], Some(surface(2.0, 3)), 0.3);
+        buffers.record([0, 0], Some(surface(2.0, 3)), 0.3);
+        buffers.record([0, 0], Some(surface(4.0, 7)), 0.4);
+        assert_eq!(buffers.object_id([0, 0]), Some(3));
+    }
+}
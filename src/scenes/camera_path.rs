@@ -0,0 +1,174 @@
+use crate::collections::{Angle, Point, Quaternion, Vector};
+use crate::scenes::{LookAt, Native, Orientation};
+
+// A camera move through a list of look-at keypoints, spaced evenly in time
+// from t = 0 at the first to t = 1 at the last. The camera position follows a
+// Catmull-Rom spline through the keypoints' from points, and the view
+// direction turns by spherical interpolation between the keypoints' rotations,
+// so the camera neither cuts corners nor changes speed abruptly as it passes
+// each keypoint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraPath {
+    keypoints: Vec<LookAt>,
+    rotations: Vec<Quaternion>,
+}
+
+impl CameraPath {
+    // panics if there are no keypoints
+    pub fn new(keypoints: Vec<LookAt>) -> CameraPath {
+        assert!(
+            !keypoints.is_empty(),
+            "camera path needs at least one keypoint"
+        );
+        let rotations = keypoints.iter().map(CameraPath::rotation).collect();
+        CameraPath {
+            keypoints,
+            rotations,
+        }
+    }
+
+    pub fn keypoints(&self) -> &[LookAt] {
+        &self.keypoints
+    }
+
+    // the camera's axes in world space, as set up by Orientation: x to the
+    // right, y up, and z pointing back out of the view
+    fn rotation(look_at: &LookAt) -> Quaternion {
+        let forward = (look_at.to - look_at.from).normalise();
+        let right = forward.cross(look_at.up.normalise()).normalise();
+        let true_up = right.cross(forward);
+        Quaternion::from_axes(right, true_up, -forward)
+    }
+
+    pub fn position_at(&self, t: f64) -> Point {
+        let (segment, local_t) = self.segment(t);
+        let last = self.keypoints.len() - 1;
+        let point = |index: isize| self.keypoints[index.clamp(0, last as isize) as usize].from;
+        let index = segment as isize;
+        catmull_rom(
+            [
+                point(index - 1),
+                point(index),
+                point(index + 1),
+                point(index + 2),
+            ],
+            local_t,
+        )
+    }
+
+    pub fn orientation_at(&self, t: f64) -> Orientation {
+        let (segment, local_t) = self.segment(t);
+        let next = (segment + 1).min(self.rotations.len() - 1);
+        let rotation = self.rotations[segment].slerp(&self.rotations[next], local_t);
+        let from = self.position_at(t);
+        let forward = rotation.rotate(Vector::new(0.0, 0.0, -1.0));
+        Orientation::new(
+            from,
+            from + forward,
+            rotation.rotate(Vector::new(0.0, 1.0, 0.0)),
+        )
+    }
+
+    // one orientation per frame, with the first and last frames on the first
+    // and last keypoints
+    pub fn frame_orientations(&self, frame_count: usize) -> Vec<Orientation> {
+        (0..frame_count)
+            .map(|frame| match frame_count {
+                1 => 0.0,
+                _ => frame as f64 / (frame_count - 1) as f64,
+            })
+            .map(|t| self.orientation_at(t))
+            .collect()
+    }
+
+    // a ray generator for each frame, built from that frame's orientation
+    pub fn ray_generators<R>(
+        &self,
+        frame_count: usize,
+        generator_for: impl FnMut(Orientation) -> R,
+    ) -> Vec<R> {
+        self.frame_orientations(frame_count)
+            .into_iter()
+            .map(generator_for)
+            .collect()
+    }
+
+    pub fn native_ray_generators(
+        &self,
+        frame_count: usize,
+        hsize: usize,
+        vsize: usize,
+        fov: Angle,
+    ) -> Vec<Native> {
+        self.ray_generators(frame_count, |orientation| {
+            Native::new(hsize, vsize, fov, orientation)
+        })
+    }
+
+    // which segment t falls in, and how far along it
+    fn segment(&self, t: f64) -> (usize, f64) {
+        let segments = self.keypoints.len() - 1;
+        if segments == 0 {
+            return (0, 0.0);
+        }
+        let scaled = t.clamp(0.0, 1.0) * segments as f64;
+        let segment = (scaled.floor() as usize).min(segments - 1);
+        (segment, scaled - segment as f64)
+    }
+}
+
+// uniform Catmull-Rom spline through p1 (at t = 0) and p2 (at t = 1)
+fn catmull_rom([p0, p1, p2, p3]: [Point; 4], t: f64) -> Point {
+    let (t2, t3) = (t * t, t * t * t);
+    let tangent_1 = (p2 - p0) * 0.5;
+    let tangent_2 = (p3 - p1) * 0.5;
+    p1 + (p2 - p1) * (3.0 * t2 - 2.0 * t3) + tangent_1 * (t3 - 2.0 * t2 + t) + tangent_2 * (t3 - t2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Transformable;
+    use crate::utils::ApproxEq;
+
+    fn orbit() -> CameraPath {
+        let up = Vector::new(0.0, 1.0, 0.0);
+        let origin = Point::new(0.0, 0.0, 0.0);
+        CameraPath::new(vec![
+            LookAt::new(Point::new(0.0, 0.0, -5.0), origin, up),
+            LookAt::new(Point::new(5.0, 0.0, 0.0), origin, up),
+            LookAt::new(Point::new(0.0, 0.0, 5.0), origin, up),
+        ])
+    }
+
+    #[test]
+    fn path_passes_through_keypoints() {
+        let path = orbit();
+        for (t, keypoint) in [0.0, 0.5, 1.0].iter().zip(path.keypoints()) {
+            assert!(path.position_at(*t).approx_eq(&keypoint.from));
+            let probe = Point::new(1.0, 2.0, 3.0);
+            let expected = probe.transform(keypoint.orientation().frame_transformation());
+            let actual = probe.transform(path.orientation_at(*t).frame_transformation());
+            assert!(actual.approx_eq(&expected));
+        }
+    }
+
+    #[test]
+    fn path_turns_between_keypoints() {
+        let path = orbit();
+        // a quarter of the way round, the camera is off the straight line
+        // between the first two keypoints and looking roughly inwards
+        let position = path.position_at(0.25);
+        assert!(position.x > 2.5 && position.z < -2.5);
+        let view = path.orientation_at(0.25);
+        let forward = Vector::new(0.0, 0.0, -1.0).transform(&view.frame_transformation().invert());
+        let inwards = (Point::new(0.0, 0.0, 0.0) - position).normalise();
+        assert!(forward.normalise().dot(inwards) > 0.9);
+
+        assert_eq!(
+            path.native_ray_generators(5, 4, 4, Angle::from_degrees(60.0))
+                .len(),
+            5
+        );
+    }
+}
@@ -1,8 +1,11 @@
-use std::io::Write;
+use std::io::{self, Write};
+use std::num::NonZeroU16;
 use std::ops::{Add, AddAssign, Index};
 
 use crate::collections::Colour;
-use crate::utils::filehandler;
+use crate::scenes::{AutoExposure, Region};
+use crate::utils::filehandler::{self, DecodedImage};
+use crate::utils::{Error, IoError, ParseError, RenderError};
 
 const PPM_HEADER: &str = "P3";
 const PIXEL_MAX: u64 = 255;
@@ -21,27 +24,37 @@ impl Pixel {
         Pixel { colour }
     }
 
+    pub fn colour(&self) -> Colour {
+        self.colour
+    }
+
+    pub fn set_colour(&mut self, colour: Colour) {
+        self.colour = colour;
+    }
+
     pub fn red(&self) -> u64 {
-        match self.colour.red {
-            x if x > 1.0 => PIXEL_MAX,
-            x if x < 0.0 => 0,
-            x => (x * PIXEL_MAX as f64).round() as u64,
-        }
+        Pixel::quantise(self.colour.red, PIXEL_MAX)
     }
 
     pub fn green(&self) -> u64 {
-        match self.colour.green {
-            x if x > 1.0 => PIXEL_MAX,
-            x if x < 0.0 => 0,
-            x => (x * PIXEL_MAX as f64).round() as u64,
-        }
+        Pixel::quantise(self.colour.green, PIXEL_MAX)
     }
 
     pub fn blue(&self) -> u64 {
-        match self.colour.blue {
-            x if x > 1.0 => PIXEL_MAX,
+        Pixel::quantise(self.colour.blue, PIXEL_MAX)
+    }
+
+    // each channel clamped to between 0 and 1 and scaled to 0..=max_value
+    pub fn channels(&self, max_value: u64) -> [u64; 3] {
+        [self.colour.red, self.colour.green, self.colour.blue]
+            .map(|channel| Pixel::quantise(channel, max_value))
+    }
+
+    fn quantise(channel: f64, max_value: u64) -> u64 {
+        match channel {
+            x if x > 1.0 => max_value,
             x if x < 0.0 => 0,
-            x => (x * PIXEL_MAX as f64).round() as u64,
+            x => (x * max_value as f64).round() as u64,
         }
     }
 }
@@ -62,15 +75,17 @@ impl AddAssign for Pixel {
     }
 }
 
-#[derive(Debug)]
-pub enum WriteError {
-    OutOfBounds,
-}
+#[deprecated(note = "use RenderError")]
+pub type WriteError = RenderError;
 
+// Pixels are opaque unless the canvas has an alpha channel, which is only
+// created once some pixel is given an alpha (or another canvas with alpha is
+// painted onto it).
 #[derive(Clone, Debug, PartialEq)]
 pub struct Canvas {
     size: Size,
     pixels: Vec<Vec<Pixel>>,
+    alpha: Option<Vec<Vec<f64>>>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -92,6 +107,85 @@ impl Canvas {
         Canvas {
             size: Size { width, height },
             pixels: canvas,
+            alpha: None,
+        }
+    }
+
+    pub fn from_ppm(bytes: &[u8]) -> Result<Canvas, ParseError> {
+        Ok(Canvas::from_decoded(filehandler::decode_ppm(bytes)?))
+    }
+
+    // a PNG with transparency loads into a canvas with an alpha channel
+    #[cfg(feature = "png")]
+    pub fn from_png(bytes: &[u8]) -> Result<Canvas, ParseError> {
+        Ok(Canvas::from_decoded(filehandler::decode_png(bytes)?))
+    }
+
+    // reads a PPM or PNG file, telling them apart by their first bytes; PNGs
+    // need the png feature
+    pub fn open(input_path: &str) -> Result<Canvas, Error> {
+        let bytes = filehandler::read_from_file(input_path)?;
+        match bytes.starts_with(b"P") {
+            true => Ok(Canvas::from_ppm(&bytes)?),
+            #[cfg(feature = "png")]
+            false => Ok(Canvas::from_png(&bytes)?),
+            #[cfg(not(feature = "png"))]
+            false => Err(ParseError::from(crate::utils::ParseErrorKind::Unsupported).into()),
+        }
+    }
+
+    fn from_decoded(image: DecodedImage) -> Canvas {
+        let mut canvas = Canvas::new(Width(image.width), Height(image.height));
+        for (row_pixels, colours) in canvas.pixels.iter_mut().zip(image.rows()) {
+            for (pixel, &colour) in row_pixels.iter_mut().zip(colours) {
+                pixel.colour = colour;
+            }
+        }
+        canvas.alpha = image.alpha.map(|alpha| {
+            alpha
+                .chunks(image.width.max(1))
+                .take(image.height)
+                .map(|row| row.to_vec())
+                .collect()
+        });
+        canvas
+    }
+
+    pub fn width(&self) -> usize {
+        self.size.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.size.height
+    }
+
+    // pixels are visited row by row, from the top left
+    pub fn pixels(&self) -> impl Iterator<Item = &Pixel> {
+        self.pixels.iter().flatten()
+    }
+
+    pub fn pixels_mut(&mut self) -> impl Iterator<Item = &mut Pixel> {
+        self.pixels.iter_mut().flatten()
+    }
+
+    // as pixels, with each pixel's [column, row] index
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = ([usize; 2], &Pixel)> {
+        self.pixels.iter().enumerate().flat_map(|(row, pixels)| {
+            pixels
+                .iter()
+                .enumerate()
+                .map(move |(column, pixel)| ([column, row], pixel))
+        })
+    }
+
+    // as indexing, but None instead of a panic outside the canvas
+    pub fn get_pixel(&self, [column, row]: [usize; 2]) -> Option<&Pixel> {
+        self.pixels.get(row).and_then(|pixels| pixels.get(column))
+    }
+
+    pub fn fill(&mut self, colour: Colour) {
+        for pixel in self.pixels_mut() {
+            pixel.set_colour(colour);
         }
     }
 
@@ -100,13 +194,10 @@ impl Canvas {
         column: usize,
         row: usize,
         colour: Colour,
-    ) -> Result<(), WriteError> {
-        match (column, row) {
-            (column, row) if column > self.size.width || row > self.size.height => {
-                return Err(WriteError::OutOfBounds)
-            }
-            _ => (),
-        };
+    ) -> Result<(), RenderError> {
+        if column >= self.size.width || row >= self.size.height {
+            return Err(self.out_of_bounds([column, row]));
+        }
 
         self.pixels[row][column] = Pixel::new(colour);
         Ok(())
@@ -117,48 +208,292 @@ impl Canvas {
         column: usize,
         row: usize,
         colour: Colour,
-    ) -> Result<(), WriteError> {
-        match (column, row) {
-            (column, row) if column > self.size.width || row > self.size.height => {
-                return Err(WriteError::OutOfBounds)
-            }
-            _ => (),
-        };
+    ) -> Result<(), RenderError> {
+        if column >= self.size.width || row >= self.size.height {
+            return Err(self.out_of_bounds([column, row]));
+        }
 
         self.pixels[row][column] += Pixel::new(colour);
         Ok(())
     }
 
-    pub fn write_to_ppm(&self) -> Result<Vec<u8>, std::io::Error> {
-        let mut buffer = Vec::new();
-        writeln!(&mut buffer, "{}", PPM_HEADER)?;
-        writeln!(&mut buffer, "{} {}", self.size.width, self.size.height)?;
-        writeln!(&mut buffer, "{}", PIXEL_MAX)?;
+    // copies another canvas over this one with its top-left pixel at the given
+    // position
+    pub fn paint_canvas_replace(
+        &mut self,
+        column: usize,
+        row: usize,
+        canvas: &Canvas,
+    ) -> Result<(), RenderError> {
+        if column + canvas.size.width > self.size.width
+            || row + canvas.size.height > self.size.height
+        {
+            return Err(self.out_of_bounds([column, row]));
+        }
+
+        for (canvas_row, pixels) in canvas.pixels.iter().enumerate() {
+            self.pixels[row + canvas_row][column..column + pixels.len()].copy_from_slice(pixels);
+        }
+        if canvas.alpha.is_some() || self.alpha.is_some() {
+            for ([canvas_column, canvas_row], _) in canvas.enumerate_pixels() {
+                self.set_alpha_unchecked(
+                    [column + canvas_column, row + canvas_row],
+                    canvas.alpha([canvas_column, canvas_row]),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // adds another canvas's colours onto this one with its top-left pixel at
+    // the given position
+    pub fn paint_canvas_additive(
+        &mut self,
+        column: usize,
+        row: usize,
+        canvas: &Canvas,
+    ) -> Result<(), RenderError> {
+        self.blend_canvas(column, row, canvas, |under, over, _| under + over)
+    }
+
+    // multiplies this canvas's colours by another's, channel by channel
+    pub fn paint_canvas_multiply(
+        &mut self,
+        column: usize,
+        row: usize,
+        canvas: &Canvas,
+    ) -> Result<(), RenderError> {
+        self.blend_canvas(column, row, canvas, |under, over, _| under * over)
+    }
+
+    // lays another canvas over this one with the (straight, not premultiplied)
+    // "over" operator; where this canvas is partly transparent its colour counts
+    // for less, and the combined coverage goes in its alpha channel if it has one
+    pub fn paint_canvas_over(
+        &mut self,
+        column: usize,
+        row: usize,
+        canvas: &Canvas,
+    ) -> Result<(), RenderError> {
+        if column + canvas.size.width > self.size.width
+            || row + canvas.size.height > self.size.height
+        {
+            return Err(self.out_of_bounds([column, row]));
+        }
+
+        for ([canvas_column, canvas_row], pixel) in canvas.enumerate_pixels() {
+            let index = [column + canvas_column, row + canvas_row];
+            let over_alpha = canvas.alpha([canvas_column, canvas_row]);
+            let under_alpha = self.alpha(index) * (1.0 - over_alpha);
+            let alpha = over_alpha + under_alpha;
+            let under = &mut self.pixels[index[1]][index[0]];
+            let colour = if alpha > 0.0 {
+                (pixel.colour() * over_alpha + under.colour() * under_alpha) / alpha
+            } else {
+                Colour::BLACK
+            };
+            under.set_colour(colour);
+            if self.alpha.is_some() {
+                self.set_alpha_unchecked(index, alpha);
+            }
+        }
+        Ok(())
+    }
+
+    fn blend_canvas(
+        &mut self,
+        column: usize,
+        row: usize,
+        canvas: &Canvas,
+        blend: impl Fn(Colour, Colour, f64) -> Colour,
+    ) -> Result<(), RenderError> {
+        if column + canvas.size.width > self.size.width
+            || row + canvas.size.height > self.size.height
+        {
+            return Err(self.out_of_bounds([column, row]));
+        }
+
+        for ([canvas_column, canvas_row], pixel) in canvas.enumerate_pixels() {
+            let under = &mut self.pixels[row + canvas_row][column + canvas_column];
+            let alpha = canvas.alpha([canvas_column, canvas_row]);
+            under.set_colour(blend(under.colour(), pixel.colour(), alpha));
+        }
+        Ok(())
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.alpha.is_some()
+    }
+
+    // 1 (opaque) everywhere without an alpha channel
+    pub fn alpha(&self, [column, row]: [usize; 2]) -> f64 {
+        match &self.alpha {
+            Some(alpha) => alpha[row][column],
+            None => 1.0,
+        }
+    }
+
+    // creates the alpha channel, opaque everywhere, if there is none yet
+    pub fn set_alpha(&mut self, column: usize, row: usize, alpha: f64) -> Result<(), RenderError> {
+        if column >= self.size.width || row >= self.size.height {
+            return Err(self.out_of_bounds([column, row]));
+        }
+        self.set_alpha_unchecked([column, row], alpha);
+        Ok(())
+    }
+
+    fn out_of_bounds(&self, index: [usize; 2]) -> RenderError {
+        RenderError::OutOfBounds {
+            index,
+            size: [self.size.width, self.size.height],
+        }
+    }
+
+    fn set_alpha_unchecked(&mut self, [column, row]: [usize; 2], alpha: f64) {
+        let Size { width, height } = self.size;
+        self.alpha
+            .get_or_insert_with(|| vec![vec![1.0; width]; height])[row][column] = alpha;
+    }
+
+    pub fn scale_brightness(&mut self, factor: f64) {
+        for pixel in self.pixels_mut() {
+            pixel.set_colour(pixel.colour() * factor);
+        }
+    }
+
+    // copy of the pixels (and alpha) within the region
+    pub fn crop(&self, region: Region) -> Result<Canvas, RenderError> {
+        let [x0, x1] = region.x_range();
+        let [y0, y1] = region.y_range();
+        if x1 > self.size.width || y1 > self.size.height {
+            return Err(self.out_of_bounds([x1, y1]));
+        }
+
+        Ok(Canvas {
+            size: Size {
+                width: x1 - x0,
+                height: y1 - y0,
+            },
+            pixels: self.pixels[y0..y1]
+                .iter()
+                .map(|pixels| pixels[x0..x1].to_vec())
+                .collect(),
+            alpha: self.alpha.as_ref().map(|alpha| {
+                alpha[y0..y1]
+                    .iter()
+                    .map(|alphas| alphas[x0..x1].to_vec())
+                    .collect()
+            }),
+        })
+    }
+
+    // scales every pixel so that the key luminance picked by the method lands on
+    // middle grey, returning the scale factor used
+    pub fn auto_expose(&mut self, method: AutoExposure) -> f64 {
+        let region = Region::new(0, 0, self.width(), self.height());
+        self.auto_expose_within(method, region)
+    }
+
+    // as auto_expose, but metering only the pixels in the region (such as the
+    // crop window of a region render, outside which the canvas is black)
+    pub fn auto_expose_within(&mut self, method: AutoExposure, region: Region) -> f64 {
+        let luminances = self
+            .enumerate_pixels()
+            .filter(|(index, _)| region.contains(*index))
+            .map(|(_, pixel)| pixel.colour.luminance())
+            .collect();
+        let scale = method.scale(luminances);
+        for pixel in self.pixels_mut() {
+            pixel.colour *= scale;
+        }
+        scale
+    }
+
+    pub fn write_ppm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let max_value = NonZeroU16::new(PIXEL_MAX as u16).expect("PIXEL_MAX is not zero");
+        self.write_ppm_with_max(writer, max_value)
+    }
+
+    // plain PPM with the given maximum colour value (up to 65535, for 16 bits
+    // per channel)
+    pub fn write_ppm_with_max<W: Write>(
+        &self,
+        writer: &mut W,
+        max_value: NonZeroU16,
+    ) -> io::Result<()> {
+        let max_value = max_value.get();
+        writeln!(writer, "{}", PPM_HEADER)?;
+        writeln!(writer, "{} {}", self.size.width, self.size.height)?;
+        writeln!(writer, "{}", max_value)?;
         for row in &self.pixels {
             let mut row_buffer = String::new();
             for pixel in row {
-                let colour_values: Vec<String> = vec![pixel.red(), pixel.green(), pixel.blue()]
+                let colour_values: Vec<String> = pixel
+                    .channels(max_value as u64)
                     .iter()
                     .map(|cval| cval.to_string())
                     .collect();
                 for colour_value in colour_values {
                     if row_buffer.len() + colour_value.len() + 1 > 70 {
-                        writeln!(buffer, "{}", row_buffer.trim())?;
+                        writeln!(writer, "{}", row_buffer.trim())?;
                         row_buffer = String::new();
                     }
                     row_buffer.push_str(&colour_value[..]);
                     row_buffer.push(' ');
                 }
             }
-            writeln!(buffer, "{}", row_buffer.trim())?;
+            writeln!(writer, "{}", row_buffer.trim())?;
         }
-        Ok(buffer)
+        writer.flush()
     }
 
-    pub fn output_to_ppm(&self, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let buffer = self.write_to_ppm()?;
+    // 8-bit RGB, or RGBA if the canvas has an alpha channel
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut samples = Vec::with_capacity(self.size.width * self.size.height * 4);
+        for (index, pixel) in self.enumerate_pixels() {
+            samples.extend([pixel.red(), pixel.green(), pixel.blue()].map(|value| value as u8));
+            if self.has_alpha() {
+                samples.push((self.alpha(index).clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        filehandler::encode_png(
+            writer,
+            [self.size.width, self.size.height],
+            &samples,
+            self.has_alpha(),
+        )?;
+        writer.flush()
+    }
 
-        filehandler::write_to_file(&buffer, output_path)?;
+    // Radiance HDR (RGBE), keeping colour values above 1 rather than clamping
+    pub fn write_hdr<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let colours: Vec<Colour> = self.pixels().map(|pixel| pixel.colour).collect();
+        filehandler::encode_hdr(writer, [self.size.width, self.size.height], &colours)?;
+        writer.flush()
+    }
+
+    // 8-bit RGBA, row by row from the top left
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let mut samples = Vec::with_capacity(self.size.width * self.size.height * 4);
+        for (index, pixel) in self.enumerate_pixels() {
+            samples.extend(pixel.channels(255).map(|value| value as u8));
+            samples.push((self.alpha(index).clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+        samples
+    }
+
+    pub fn write_to_ppm(&self) -> Result<Vec<u8>, std::io::Error> {
+        let mut buffer = Vec::new();
+        self.write_ppm(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    #[deprecated(note = "use write_ppm with a writer, such as create_file(output_path)")]
+    pub fn output_to_ppm(&self, output_path: &str) -> Result<(), IoError> {
+        let mut file = filehandler::create_file(output_path)?;
+        self.write_ppm(&mut file)
+            .map_err(|error| IoError::at_path(output_path, error))?;
 
         Ok(())
     }
@@ -178,6 +513,7 @@ mod tests {
     use std::io::prelude::*;
 
     use super::*;
+    use crate::utils::approx_eq;
 
     #[test]
     fn create_canvas() {
@@ -192,6 +528,7 @@ mod tests {
                     height: 2,
                 },
                 pixels: resulting_canvas,
+                alpha: None,
             }
         );
     }
@@ -216,6 +553,7 @@ mod tests {
                     height: 3,
                 },
                 pixels: resulting_canvas,
+                alpha: None,
             }
         );
     }
@@ -249,6 +587,7 @@ mod tests {
 
     #[test]
     #[ignore]
+    #[allow(deprecated)]
     fn output_canvas_to_ppm() {
         let mut canvas = Canvas::new(Width(2), Height(2));
         canvas
@@ -272,4 +611,218 @@ mod tests {
         // cleanup
         std::fs::remove_file("test.ppm").unwrap();
     }
+
+    #[test]
+    fn reload_canvas_from_ppm() {
+        let mut canvas = Canvas::new(Width(3), Height(2));
+        canvas
+            .paint_colour_replace(0, 0, Colour::new(1.0, 0.0, 0.0))
+            .unwrap();
+        canvas
+            .paint_colour_replace(2, 1, Colour::new(0.2, 0.4, 0.6))
+            .unwrap();
+        let reloaded = Canvas::from_ppm(&canvas.write_to_ppm().unwrap()).unwrap();
+        assert_eq!(reloaded, canvas);
+        assert!(!reloaded.has_alpha());
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn write_png_round_trip() {
+        assert_eq!(
+            Canvas::from_png(b"P3\n1 1\n255\n0 0 0\n").unwrap_err(),
+            crate::utils::ParseErrorKind::InvalidHeader
+        );
+
+        let mut canvas = Canvas::new(Width(3), Height(2));
+        canvas
+            .paint_colour_replace(1, 0, Colour::new(1.0, 0.2, 0.4))
+            .unwrap();
+        let mut buffer = Vec::new();
+        canvas.write_png(&mut buffer).unwrap();
+        assert_eq!(Canvas::from_png(&buffer).unwrap(), canvas);
+
+        canvas.set_alpha(2, 1, 0.6).unwrap();
+        let mut buffer = Vec::new();
+        canvas.write_png(&mut buffer).unwrap();
+        let reloaded = Canvas::from_png(&buffer).unwrap();
+        assert_eq!(reloaded, canvas);
+        assert_eq!(reloaded.alpha([2, 1]), 0.6);
+    }
+
+    #[test]
+    fn write_sixteen_bit_ppm() {
+        let mut canvas = Canvas::new(Width(2), Height(1));
+        canvas
+            .paint_colour_replace(0, 0, Colour::new(1.5, 0.5, 0.25))
+            .unwrap();
+        let mut buffer = Vec::new();
+        canvas
+            .write_ppm_with_max(&mut buffer, NonZeroU16::MAX)
+            .unwrap();
+        assert_eq!(
+            buffer,
+            b"P3\n2 1\n65535\n65535 32768 16384 0 0 0\n".to_vec()
+        );
+        assert_eq!(
+            Canvas::from_ppm(&buffer).unwrap()[[0, 0]].colour(),
+            Colour::new(1.0, 32768.0 / 65535.0, 16384.0 / 65535.0)
+        );
+    }
+
+    #[test]
+    fn write_hdr_keeps_bright_values() {
+        let mut canvas = Canvas::new(Width(2), Height(1));
+        canvas
+            .paint_colour_replace(0, 0, Colour::new(4.0, 1.0, 0.5))
+            .unwrap();
+        let mut buffer = Vec::new();
+        canvas.write_hdr(&mut buffer).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n";
+        assert!(buffer.starts_with(header));
+        // 4.0 = 0.5 * 2^3, so the mantissas are scaled by 256 / 8
+        assert_eq!(buffer[header.len()..], [128, 32, 16, 131, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn iterate_and_fill_pixels() {
+        let mut canvas = Canvas::new(Width(3), Height(2));
+        canvas.fill(Colour::new(0.5, 0.5, 0.5));
+        assert!(canvas
+            .pixels()
+            .all(|pixel| pixel.colour() == Colour::new(0.5, 0.5, 0.5)));
+
+        for pixel in canvas.pixels_mut().skip(4) {
+            pixel.set_colour(Colour::new(1.0, 0.0, 0.0));
+        }
+        let indices: Vec<[usize; 2]> = canvas.enumerate_pixels().map(|(index, _)| index).collect();
+        assert_eq!(
+            indices,
+            vec![[0, 0], [1, 0], [2, 0], [0, 1], [1, 1], [2, 1]]
+        );
+        assert_eq!(canvas[[1, 1]].colour(), Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(canvas[[0, 1]].colour(), Colour::new(0.5, 0.5, 0.5));
+
+        assert_eq!(canvas.get_pixel([2, 1]), Some(&canvas[[2, 1]]));
+        assert_eq!(canvas.get_pixel([3, 0]), None);
+        assert_eq!(canvas.get_pixel([0, 2]), None);
+    }
+
+    fn filled(width: usize, height: usize, colour: Colour) -> Canvas {
+        let mut canvas = Canvas::new(Width(width), Height(height));
+        canvas.fill(colour);
+        canvas
+    }
+
+    #[test]
+    fn composite_canvases() {
+        let mut canvas = filled(3, 3, Colour::new(0.2, 0.4, 0.6));
+        let layer = filled(2, 2, Colour::new(0.5, 0.5, 0.5));
+        canvas.paint_canvas_additive(1, 1, &layer).unwrap();
+        assert_eq!(canvas[[0, 0]].colour(), Colour::new(0.2, 0.4, 0.6));
+        assert_eq!(canvas[[2, 2]].colour(), Colour::new(0.7, 0.9, 1.1));
+
+        let mut canvas = filled(3, 3, Colour::new(0.2, 0.4, 0.6));
+        canvas.paint_canvas_multiply(0, 0, &layer).unwrap();
+        assert_eq!(canvas[[1, 1]].colour(), Colour::new(0.1, 0.2, 0.3));
+        assert_eq!(canvas[[2, 2]].colour(), Colour::new(0.2, 0.4, 0.6));
+
+        assert_eq!(
+            canvas.paint_canvas_additive(2, 2, &layer),
+            Err(RenderError::OutOfBounds {
+                index: [2, 2],
+                size: [3, 3]
+            })
+        );
+        assert!(canvas
+            .paint_colour_replace(3, 0, Colour::new(1.0, 1.0, 1.0))
+            .is_err());
+    }
+
+    #[test]
+    fn composite_canvas_over_with_alpha() {
+        let mut background = filled(2, 1, Colour::new(0.0, 0.0, 1.0));
+        let mut foreground = filled(2, 1, Colour::new(1.0, 0.0, 0.0));
+        assert!(!foreground.has_alpha());
+        foreground.set_alpha(1, 0, 0.25).unwrap();
+        assert!(foreground.has_alpha());
+        assert_eq!(foreground.alpha([0, 0]), 1.0);
+
+        background.paint_canvas_over(0, 0, &foreground).unwrap();
+        assert_eq!(background[[0, 0]].colour(), Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(background[[1, 0]].colour(), Colour::new(0.25, 0.0, 0.75));
+        assert!(!background.has_alpha());
+
+        // coverage accumulates when the canvas underneath has alpha too
+        let mut transparent = Canvas::new(Width(2), Height(1));
+        transparent.set_alpha(1, 0, 0.0).unwrap();
+        transparent.paint_canvas_over(0, 0, &foreground).unwrap();
+        assert_eq!(transparent.alpha([1, 0]), 0.25);
+        assert_eq!(transparent[[1, 0]].colour(), Colour::new(1.0, 0.0, 0.0));
+
+        // a half-covered canvas underneath counts for half as much
+        let mut half = filled(1, 1, Colour::new(0.0, 0.0, 1.0));
+        half.set_alpha(0, 0, 0.5).unwrap();
+        let mut layer = filled(1, 1, Colour::new(1.0, 0.0, 0.0));
+        layer.set_alpha(0, 0, 0.5).unwrap();
+        half.paint_canvas_over(0, 0, &layer).unwrap();
+        approx_eq!(half.alpha([0, 0]), 0.75);
+        assert_eq!(
+            half[[0, 0]].colour(),
+            Colour::new(2.0 / 3.0, 0.0, 1.0 / 3.0)
+        );
+
+        // nothing over nothing leaves transparent black
+        layer.set_alpha(0, 0, 0.0).unwrap();
+        let mut empty = filled(1, 1, Colour::new(0.0, 0.0, 1.0));
+        empty.set_alpha(0, 0, 0.0).unwrap();
+        empty.paint_canvas_over(0, 0, &layer).unwrap();
+        assert_eq!(empty[[0, 0]].colour(), Colour::BLACK);
+        assert_eq!(empty.alpha([0, 0]), 0.0);
+        assert!(transparent.set_alpha(2, 0, 0.5).is_err());
+    }
+
+    #[test]
+    fn scale_and_crop_canvas() {
+        let mut canvas = Canvas::new(Width(3), Height(2));
+        canvas
+            .paint_colour_replace(2, 1, Colour::new(0.2, 0.4, 0.8))
+            .unwrap();
+        canvas.set_alpha(1, 1, 0.5).unwrap();
+        canvas.scale_brightness(0.5);
+        assert_eq!(canvas[[2, 1]].colour(), Colour::new(0.1, 0.2, 0.4));
+
+        let cropped = canvas.crop(Region::new(1, 1, 3, 2)).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (2, 1));
+        assert_eq!(cropped[[1, 0]].colour(), Colour::new(0.1, 0.2, 0.4));
+        assert_eq!(cropped.alpha([0, 0]), 0.5);
+        assert!(canvas.crop(Region::new(0, 0, 4, 1)).is_err());
+    }
+
+    #[test]
+    fn auto_expose_canvas() {
+        let mut canvas = Canvas::new(Width(2), Height(1));
+        canvas
+            .paint_colour_replace(0, 0, Colour::new(0.9, 0.9, 0.9))
+            .unwrap();
+        canvas
+            .paint_colour_replace(1, 0, Colour::new(2.7, 2.7, 2.7))
+            .unwrap();
+        let scale = canvas.auto_expose(AutoExposure::Average);
+        approx_eq!(scale, 0.1);
+        approx_eq!(canvas[[0, 0]].colour().red, 0.09);
+        approx_eq!(canvas[[1, 0]].colour().red, 0.27);
+    }
+
+    #[test]
+    fn auto_expose_meters_only_region() {
+        let mut canvas = Canvas::new(Width(3), Height(1));
+        canvas
+            .paint_colour_replace(1, 0, Colour::new(0.9, 0.9, 0.9))
+            .unwrap();
+        let scale = canvas.auto_expose_within(AutoExposure::Average, Region::new(1, 0, 2, 1));
+        approx_eq!(scale, 0.2);
+        approx_eq!(canvas[[1, 0]].colour().red, 0.18);
+        assert_eq!(canvas[[0, 0]].colour(), Colour::new(0.0, 0.0, 0.0));
+    }
 }
@@ -0,0 +1,287 @@
+use crate::collections::{Colour, Vector};
+use crate::scenes::{AuxiliaryBuffers, Canvas, Height, Width};
+use crate::utils::EPSILON;
+
+// Cleans up a noisy render using the auxiliary passes recorded with it.
+// External denoisers (such as OpenImageDenoise) can be plugged in through
+// ExternalDenoiser, or by implementing this for a wrapper around them;
+// BilateralFilter is built in.
+pub trait Denoiser {
+    // the image and buffers are the same size as rendered by the camera
+    fn denoise(&self, image: &Canvas, aovs: &AuxiliaryBuffers) -> Canvas;
+}
+
+// Joint bilateral filter: each pixel becomes a weighted average of its
+// neighbours within the radius, where the weights fall off with distance, with
+// difference in colour, and with difference in albedo, normal and depth. The
+// guide terms keep texture and geometric edges sharp while noise in flat
+// regions is smoothed. Pixels with no surface are only averaged with each
+// other. Each sigma sets how quickly weights fall off with that difference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BilateralFilter {
+    pub radius: usize,
+    pub spatial_sigma: f64,
+    pub colour_sigma: f64,
+    pub albedo_sigma: f64,
+    pub normal_sigma: f64,
+    // relative to the nearer of the two depths
+    pub depth_sigma: f64,
+}
+
+impl BilateralFilter {
+    pub fn new(radius: usize) -> BilateralFilter {
+        BilateralFilter {
+            radius,
+            spatial_sigma: f64::max(radius as f64 / 2.0, 0.5),
+            colour_sigma: 0.5,
+            albedo_sigma: 0.1,
+            normal_sigma: 0.2,
+            depth_sigma: 0.1,
+        }
+    }
+
+    fn gaussian(distance_squared: f64, sigma: f64) -> f64 {
+        (-distance_squared / (2.0 * sigma * sigma)).exp()
+    }
+
+    fn colour_distance_squared(a: Colour, b: Colour) -> f64 {
+        (a.red - b.red).powi(2) + (a.green - b.green).powi(2) + (a.blue - b.blue).powi(2)
+    }
+
+    // the depth, normal and albedo at a pixel, if it shows a surface
+    fn guide(aovs: &AuxiliaryBuffers, index: [usize; 2]) -> Option<(f64, Vector, Colour)> {
+        Some((aovs.depth(index)?, aovs.normal(index)?, aovs.albedo(index)?))
+    }
+
+    fn guide_weight(
+        &self,
+        p: Option<(f64, Vector, Colour)>,
+        q: Option<(f64, Vector, Colour)>,
+    ) -> f64 {
+        match (p, q) {
+            (Some((depth_p, normal_p, albedo_p)), Some((depth_q, normal_q, albedo_q))) => {
+                let normal_difference: Vector = normal_p - normal_q;
+                // surfaces at the camera would otherwise divide by zero
+                let nearer = f64::max(f64::min(depth_p, depth_q), EPSILON);
+                let depth_difference = (depth_p - depth_q).abs() / nearer;
+                BilateralFilter::gaussian(
+                    BilateralFilter::colour_distance_squared(albedo_p, albedo_q),
+                    self.albedo_sigma,
+                ) * BilateralFilter::gaussian(
+                    normal_difference.dot(normal_difference),
+                    self.normal_sigma,
+                ) * BilateralFilter::gaussian(depth_difference.powi(2), self.depth_sigma)
+            }
+            (None, None) => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+impl Denoiser for BilateralFilter {
+    // panics if the image and buffers differ in size
+    fn denoise(&self, image: &Canvas, aovs: &AuxiliaryBuffers) -> Canvas {
+        let (width, height) = (image.width(), image.height());
+        assert_eq!(
+            (width, height),
+            (aovs.width(), aovs.height()),
+            "image and auxiliary buffers must be the same size"
+        );
+
+        // looked up once per pixel rather than once per neighbour
+        let guides: Vec<_> = (0..height)
+            .flat_map(|pos_y| (0..width).map(move |pos_x| [pos_x, pos_y]))
+            .map(|index| BilateralFilter::guide(aovs, index))
+            .collect();
+        let guide_at = |pos_x: usize, pos_y: usize| guides[pos_y * width + pos_x];
+
+        let mut denoised = Canvas::new(Width(width), Height(height));
+        for pos_y in 0..height {
+            for pos_x in 0..width {
+                let colour_p = image[[pos_x, pos_y]].colour();
+                let guide_p = guide_at(pos_x, pos_y);
+                let mut total = Colour::new(0.0, 0.0, 0.0);
+                let mut total_weight = 0.0;
+                let window_y =
+                    pos_y.saturating_sub(self.radius)..(pos_y + self.radius + 1).min(height);
+                let window_x =
+                    pos_x.saturating_sub(self.radius)..(pos_x + self.radius + 1).min(width);
+                for q_y in window_y {
+                    for q_x in window_x.clone() {
+                        let colour_q = image[[q_x, q_y]].colour();
+                        let offset_squared =
+                            (q_x.abs_diff(pos_x).pow(2) + q_y.abs_diff(pos_y).pow(2)) as f64;
+                        let weight = BilateralFilter::gaussian(offset_squared, self.spatial_sigma)
+                            * BilateralFilter::gaussian(
+                                BilateralFilter::colour_distance_squared(colour_p, colour_q),
+                                self.colour_sigma,
+                            )
+                            * self.guide_weight(guide_p, guide_at(q_x, q_y));
+                        total += colour_q * weight;
+                        total_weight += weight;
+                    }
+                }
+                // never divides by zero, as the pixel itself always has full weight
+                denoised
+                    .paint_colour_replace(pos_x, pos_y, total * (1.0 / total_weight))
+                    .expect("index lies within the canvas");
+            }
+        }
+        denoised
+    }
+}
+
+// The passes handed to an external denoiser, as flat arrays of red, green and
+// blue f32 values, pixel by pixel and row by row from the top: the layout that
+// libraries such as OpenImageDenoise take. Pixels showing no surface have
+// black albedo and a zero normal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DenoiserInput {
+    pub width: usize,
+    pub height: usize,
+    pub colour: Vec<f32>,
+    pub albedo: Vec<f32>,
+    pub normal: Vec<f32>,
+}
+
+// Plugs in a denoiser from outside the crate, such as OpenImageDenoise through
+// its bindings: the function is given the image and guide passes and returns
+// the denoised colours in the same layout.
+#[derive(Clone, Copy, Debug)]
+pub struct ExternalDenoiser<F> {
+    denoise: F,
+}
+
+impl<F: Fn(DenoiserInput) -> Vec<f32>> ExternalDenoiser<F> {
+    pub fn new(denoise: F) -> ExternalDenoiser<F> {
+        ExternalDenoiser { denoise }
+    }
+}
+
+impl<F: Fn(DenoiserInput) -> Vec<f32>> Denoiser for ExternalDenoiser<F> {
+    // panics if the image and buffers differ in size, or if the function
+    // returns other than a colour for each pixel
+    fn denoise(&self, image: &Canvas, aovs: &AuxiliaryBuffers) -> Canvas {
+        let (width, height) = (image.width(), image.height());
+        assert_eq!(
+            (width, height),
+            (aovs.width(), aovs.height()),
+            "image and auxiliary buffers must be the same size"
+        );
+
+        let indices = || (0..height).flat_map(|pos_y| (0..width).map(move |pos_x| [pos_x, pos_y]));
+        let pass = |value_at: &dyn Fn([usize; 2]) -> [f64; 3]| -> Vec<f32> {
+            indices()
+                .flat_map(value_at)
+                .map(|value| value as f32)
+                .collect()
+        };
+        let rgb = |colour: Colour| [colour.red, colour.green, colour.blue];
+        let input = DenoiserInput {
+            width,
+            height,
+            colour: pass(&|index| rgb(image[index].colour())),
+            albedo: pass(&|index| rgb(aovs.albedo(index).unwrap_or(Colour::BLACK))),
+            normal: pass(&|index| {
+                let normal = aovs.normal(index).unwrap_or(Vector::zero());
+                [normal.x, normal.y, normal.z]
+            }),
+        };
+
+        let output = (self.denoise)(input);
+        assert_eq!(
+            output.len(),
+            width * height * 3,
+            "external denoiser must return a colour for each pixel"
+        );
+        let mut denoised = Canvas::new(Width(width), Height(height));
+        for ([pos_x, pos_y], rgb) in indices().zip(output.chunks_exact(3)) {
+            let colour = Colour::new(rgb[0] as f64, rgb[1] as f64, rgb[2] as f64);
+            denoised
+                .paint_colour_replace(pos_x, pos_y, colour)
+                .expect("index lies within the canvas");
+        }
+        denoised
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes::SurfaceRecord;
+    use crate::utils::approx_eq;
+
+    // a flat wall whose albedo changes halfway across, rendered with noise
+    fn noisy_wall() -> (Canvas, AuxiliaryBuffers) {
+        let (width, height) = (8, 4);
+        let mut image = Canvas::new(Width(width), Height(height));
+        let mut aovs = AuxiliaryBuffers::new(width, height);
+        for pos_y in 0..height {
+            for pos_x in 0..width {
+                let base = if pos_x < width / 2 { 0.2 } else { 0.8 };
+                let noise = if (pos_x + pos_y) % 2 == 0 {
+                    0.05
+                } else {
+                    -0.05
+                };
+                let value = base + noise;
+                image
+                    .paint_colour_replace(pos_x, pos_y, Colour::new(value, value, value))
+                    .unwrap();
+                let surface = SurfaceRecord {
+                    depth: 5.0,
+                    normal: Vector::new(0.0, 0.0, -1.0),
+                    albedo: Colour::new(base, base, base),
+                    object_id: Some(0),
+                };
+                aovs.record([pos_x, pos_y], Some(surface), 1.0);
+            }
+        }
+        (image, aovs)
+    }
+
+    #[test]
+    fn bilateral_filter_smooths_noise_and_keeps_edges() {
+        let (image, aovs) = noisy_wall();
+        let denoised = BilateralFilter::new(2).denoise(&image, &aovs);
+        for pos_y in 0..4 {
+            for pos_x in 0..8 {
+                let base = if pos_x < 4 { 0.2 } else { 0.8 };
+                let noisy = image[[pos_x, pos_y]].colour().red;
+                let smoothed = denoised[[pos_x, pos_y]].colour().red;
+                assert!((smoothed - base).abs() < (noisy - base).abs());
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn bilateral_filter_with_mismatched_buffers() {
+        let (image, _) = noisy_wall();
+        BilateralFilter::new(1).denoise(&image, &AuxiliaryBuffers::new(2, 2));
+    }
+
+    #[test]
+    fn surfaces_at_camera_keep_their_weight() {
+        let filter = BilateralFilter::new(1);
+        let at_camera = Some((0.0, Vector::new(0.0, 0.0, -1.0), Colour::WHITE));
+        assert_eq!(filter.guide_weight(at_camera, at_camera), 1.0);
+        let behind = Some((1.0, Vector::new(0.0, 0.0, -1.0), Colour::WHITE));
+        assert_eq!(filter.guide_weight(at_camera, behind), 0.0);
+    }
+
+    #[test]
+    fn external_denoiser_gets_flat_passes() {
+        let (image, aovs) = noisy_wall();
+        let denoiser = ExternalDenoiser::new(|input: DenoiserInput| {
+            assert_eq!((input.width, input.height), (8, 4));
+            assert_eq!(input.normal[..3], [0.0, 0.0, -1.0]);
+            assert_eq!(input.albedo[21..24], [0.8, 0.8, 0.8]);
+            // stands in for the library, giving back the albedo
+            input.albedo
+        });
+        let denoised = denoiser.denoise(&image, &aovs);
+        approx_eq!(denoised[[7, 3]].colour().green, 0.8);
+        approx_eq!(denoised[[0, 0]].colour().green, 0.2);
+    }
+}
@@ -0,0 +1,85 @@
+// Methods of picking the luminance of a rendered image that auto-exposure maps
+// to middle grey.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutoExposure {
+    // mean luminance over all pixels
+    Average,
+    // luminance below which the given fraction (in [0, 1]) of pixels fall;
+    // high percentiles keep highlights from blowing out
+    Percentile(f64),
+}
+
+impl AutoExposure {
+    pub const MIDDLE_GREY: f64 = 0.18;
+
+    pub(crate) fn key_luminance(&self, mut luminances: Vec<f64>) -> f64 {
+        if luminances.is_empty() {
+            return 0.0;
+        }
+
+        match self {
+            AutoExposure::Average => luminances.iter().sum::<f64>() / luminances.len() as f64,
+            AutoExposure::Percentile(percentile) => {
+                luminances.sort_by(|a, b| a.total_cmp(b));
+                let rank = percentile.clamp(0.0, 1.0) * (luminances.len() - 1) as f64;
+                luminances[rank.round() as usize]
+            }
+        }
+    }
+
+    // scale factor that brings the key luminance to middle grey; images with no
+    // light at all are left as they are
+    pub(crate) fn scale(&self, luminances: Vec<f64>) -> f64 {
+        match self.key_luminance(luminances) {
+            key if key > 0.0 => AutoExposure::MIDDLE_GREY / key,
+            _ => 1.0,
+        }
+    }
+}
+
+// Scale factor for a manual exposure given as an exposure value at ISO 100
+// (in stops, as on a light meter). Luminances up to 1.2 * 2^ev100 are kept
+// below white, so bright daylight scenes lit in real units (with a sun of
+// around 100000) sit at about EV 15, and each step up halves the image.
+pub fn ev100_scale(ev100: f64) -> f64 {
+    1.0 / (1.2 * 2.0_f64.powf(ev100))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::approx_eq;
+
+    #[test]
+    fn average_key_luminance() {
+        let luminances = vec![0.0, 0.5, 1.0, 2.5];
+        assert_eq!(AutoExposure::Average.key_luminance(luminances), 1.0);
+    }
+
+    #[test]
+    fn percentile_key_luminance() {
+        let luminances = vec![4.0, 0.0, 3.0, 1.0, 2.0];
+        assert_eq!(
+            AutoExposure::Percentile(0.5).key_luminance(luminances.clone()),
+            2.0
+        );
+        assert_eq!(
+            AutoExposure::Percentile(1.0).key_luminance(luminances.clone()),
+            4.0
+        );
+        assert_eq!(AutoExposure::Percentile(0.0).key_luminance(luminances), 0.0);
+    }
+
+    #[test]
+    fn black_image_is_not_scaled() {
+        assert_eq!(AutoExposure::Average.scale(vec![0.0, 0.0]), 1.0);
+        assert_eq!(AutoExposure::Average.scale(vec![]), 1.0);
+    }
+
+    #[test]
+    fn each_stop_halves_the_image() {
+        approx_eq!(ev100_scale(0.0), 1.0 / 1.2);
+        approx_eq!(ev100_scale(1.0) * 2.0, ev100_scale(0.0));
+        approx_eq!(ev100_scale(-2.0), 4.0 * ev100_scale(0.0));
+    }
+}
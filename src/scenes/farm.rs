@@ -0,0 +1,348 @@
+use crate::collections::Colour;
+use crate::scenes::{
+    tile_regions, Camera, Canvas, Height, RayGenerator, Region, Tile, Width, World,
+};
+use crate::utils::{ParseError, ParseErrorKind, Position, RenderError};
+
+// A frame split into tiles (work units) so that several machines can render
+// it together. The crate has no way of serialising a world, so every machine
+// builds the scene (and camera) for itself, and only work units and rendered
+// tiles are sent between them, as the byte messages below. Units are handed
+// out with assign, rendered with Camera::render_work_unit and merged back in
+// any order; units lost with a machine are still pending, to be handed out
+// again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderJob {
+    units: Vec<WorkUnit>,
+    merged: Vec<bool>,
+    image: Canvas,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorkUnit {
+    // place of the unit in its job
+    pub index: usize,
+    pub region: Region,
+}
+
+impl RenderJob {
+    pub fn new(width: usize, height: usize, tile_size: usize) -> RenderJob {
+        let units: Vec<WorkUnit> = tile_regions(Region::new(0, 0, width, height), tile_size)
+            .into_iter()
+            .enumerate()
+            .map(|(index, region)| WorkUnit { index, region })
+            .collect();
+        RenderJob {
+            merged: vec![false; units.len()],
+            units,
+            image: Canvas::new(Width(width), Height(height)),
+        }
+    }
+
+    pub fn units(&self) -> &[WorkUnit] {
+        &self.units
+    }
+
+    // units whose tiles have not been merged yet
+    pub fn pending(&self) -> impl Iterator<Item = &WorkUnit> {
+        self.units
+            .iter()
+            .zip(&self.merged)
+            .filter_map(|(unit, &merged)| (!merged).then_some(unit))
+    }
+
+    // deals the pending units out to the given number of machines in turn
+    pub fn assign(&self, machines: usize) -> Vec<Vec<WorkUnit>> {
+        let mut assignments = vec![vec![]; machines];
+        if machines == 0 {
+            return assignments;
+        }
+        for (count, &unit) in self.pending().enumerate() {
+            assignments[count % machines].push(unit);
+        }
+        assignments
+    }
+
+    // paints a returned tile into the frame, giving whether it was the first
+    // tile for its unit; tiles for regions that are not units of the job
+    // (such as from another job) are left out
+    pub fn merge(&mut self, tile: &Tile) -> Result<bool, RenderError> {
+        let Some(unit) = self.units.iter().find(|unit| unit.region == tile.region()) else {
+            return Ok(false);
+        };
+        if self.merged[unit.index] {
+            return Ok(false);
+        }
+        let [x0, x1] = tile.region().x_range();
+        let [y0, y1] = tile.region().y_range();
+        if tile.pixels().width() != x1 - x0 || tile.pixels().height() != y1 - y0 {
+            return Err(RenderError::OutOfBounds {
+                index: [x0, y0],
+                size: [self.image.width(), self.image.height()],
+            });
+        }
+        self.image.paint_canvas_replace(x0, y0, tile.pixels())?;
+        self.merged[unit.index] = true;
+        Ok(true)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        !self.merged.contains(&false)
+    }
+
+    // the merged frame, once every unit's tile is in
+    pub fn finish(self) -> Option<Canvas> {
+        self.is_complete().then_some(self.image)
+    }
+}
+
+impl<R: RayGenerator> Camera<R> {
+    // renders just the unit's region, as a tile for RenderJob::merge. The
+    // camera's exposure is applied to each tile; auto-exposure needs the
+    // whole image, so it should be left off here and applied to the frame.
+    pub fn render_work_unit(self, world: &World, unit: &WorkUnit) -> Result<Tile, RenderError> {
+        let image = self.with_region(unit.region).render(world)?;
+        Ok(Tile::new(unit.region, image.crop(unit.region)?))
+    }
+}
+
+// Messages are a four byte tag followed by little-endian unsigned 64-bit
+// integers and floats.
+impl WorkUnit {
+    const TAG: &'static [u8; 4] = b"RTWU";
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = WorkUnit::TAG.to_vec();
+        write_u64(&mut bytes, self.index);
+        write_region(&mut bytes, self.region);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<WorkUnit, ParseError> {
+        let mut reader = MessageReader::new(bytes, WorkUnit::TAG)?;
+        let unit = WorkUnit {
+            index: reader.read_u64()?,
+            region: reader.read_region()?,
+        };
+        reader.finish()?;
+        Ok(unit)
+    }
+}
+
+impl Tile {
+    const TAG: &'static [u8; 4] = b"RTTL";
+
+    // the region, whether there is an alpha channel (0 or 1), then each
+    // pixel's red, green, blue (and alpha) row by row
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Tile::TAG.to_vec();
+        write_region(&mut bytes, self.region);
+        let has_alpha = self.pixels.has_alpha();
+        bytes.push(has_alpha as u8);
+        for (index, pixel) in self.pixels.enumerate_pixels() {
+            let Colour { red, green, blue } = pixel.colour();
+            for value in [red, green, blue] {
+                bytes.extend(value.to_le_bytes());
+            }
+            if has_alpha {
+                bytes.extend(self.pixels.alpha(index).to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Tile, ParseError> {
+        let mut reader = MessageReader::new(bytes, Tile::TAG)?;
+        let region = reader.read_region()?;
+        let has_alpha = match reader.take(1)? {
+            [0] => false,
+            [1] => true,
+            _ => return Err(reader.error_before(ParseErrorKind::InvalidValue, 1)),
+        };
+        let [x0, x1] = region.x_range();
+        let [y0, y1] = region.y_range();
+        // checked up front so that a corrupt region cannot ask for a huge canvas
+        let channels = if has_alpha { 4 } else { 3 };
+        let size = (x1 - x0)
+            .checked_mul(y1 - y0)
+            .and_then(|count| count.checked_mul(channels * 8));
+        if size.is_none_or(|size| size > reader.bytes.len()) {
+            return Err(ParseErrorKind::MissingData.at(Position::Byte(bytes.len())));
+        }
+        let mut pixels = Canvas::new(Width(x1 - x0), Height(y1 - y0));
+        for row in 0..y1 - y0 {
+            for column in 0..x1 - x0 {
+                let colour =
+                    Colour::new(reader.read_f64()?, reader.read_f64()?, reader.read_f64()?);
+                pixels
+                    .paint_colour_replace(column, row, colour)
+                    .expect("pixels lie within the tile");
+                if has_alpha {
+                    pixels
+                        .set_alpha(column, row, reader.read_f64()?)
+                        .expect("pixels lie within the tile");
+                }
+            }
+        }
+        reader.finish()?;
+        Ok(Tile::new(region, pixels))
+    }
+}
+
+fn write_u64(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend((value as u64).to_le_bytes());
+}
+
+fn write_region(bytes: &mut Vec<u8>, region: Region) {
+    let [x0, x1] = region.x_range();
+    let [y0, y1] = region.y_range();
+    for value in [x0, y0, x1, y1] {
+        write_u64(bytes, value);
+    }
+}
+
+struct MessageReader<'a> {
+    bytes: &'a [u8],
+    // the whole message, for placing errors
+    length: usize,
+}
+
+impl<'a> MessageReader<'a> {
+    fn new(bytes: &'a [u8], tag: &[u8; 4]) -> Result<MessageReader<'a>, ParseError> {
+        match bytes.strip_prefix(tag) {
+            Some(rest) => Ok(MessageReader {
+                bytes: rest,
+                length: bytes.len(),
+            }),
+            None => Err(ParseErrorKind::InvalidHeader.at(Position::Byte(0))),
+        }
+    }
+
+    // an error in the value ending where the reader has got to
+    fn error_before(&self, kind: ParseErrorKind, value_size: usize) -> ParseError {
+        kind.at(Position::Byte(self.length - self.bytes.len() - value_size))
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ParseError> {
+        if self.bytes.len() < count {
+            return Err(ParseErrorKind::MissingData.at(Position::Byte(self.length)));
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn read_u64(&mut self) -> Result<usize, ParseError> {
+        let value = u64::from_le_bytes(self.take(8)?.try_into().expect("took eight bytes"));
+        usize::try_from(value).map_err(|_| self.error_before(ParseErrorKind::InvalidValue, 8))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, ParseError> {
+        Ok(f64::from_le_bytes(
+            self.take(8)?.try_into().expect("took eight bytes"),
+        ))
+    }
+
+    // regions are checked to be well ordered, as Region::new would otherwise
+    // swap the corners of a corrupt one
+    fn read_region(&mut self) -> Result<Region, ParseError> {
+        let [x0, y0, x1, y1] = [
+            self.read_u64()?,
+            self.read_u64()?,
+            self.read_u64()?,
+            self.read_u64()?,
+        ];
+        match x0 <= x1 && y0 <= y1 {
+            true => Ok(Region::new(x0, y0, x1, y1)),
+            false => Err(self.error_before(ParseErrorKind::InvalidValue, 32)),
+        }
+    }
+
+    fn finish(self) -> Result<(), ParseError> {
+        match self.bytes.is_empty() {
+            true => Ok(()),
+            false => Err(self.error_before(ParseErrorKind::InvalidValue, 0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::{Angle, Point, Vector};
+    use crate::objects::*;
+    use crate::scenes::{Native, Orientation};
+    use crate::utils::{BuildInto, Buildable};
+
+    fn camera() -> Camera<Native> {
+        Camera::new(Native::new(
+            8,
+            6,
+            Angle::from_degrees(90.0),
+            Orientation::new(
+                Point::new(0.0, 0.0, -2.5),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ),
+        ))
+    }
+
+    #[test]
+    fn farm_out_frame_and_merge_tiles() {
+        let sphere = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::new(vec![sphere], vec![light]);
+
+        let mut job = RenderJob::new(8, 6, 4);
+        let assignments = job.assign(3);
+        assert_eq!(
+            assignments.iter().map(Vec::len).collect::<Vec<_>>(),
+            [2, 1, 1]
+        );
+
+        // the last machine drops out before returning its tile
+        for unit in assignments[..2].iter().flatten() {
+            let unit = WorkUnit::from_bytes(&unit.to_bytes()).unwrap();
+            let tile = camera().render_work_unit(&world, &unit).unwrap();
+            assert!(job
+                .merge(&Tile::from_bytes(&tile.to_bytes()).unwrap())
+                .unwrap());
+        }
+        assert!(!job.is_complete());
+        let pending: Vec<WorkUnit> = job.pending().copied().collect();
+        assert_eq!(pending, vec![assignments[2][0]]);
+
+        let tile = camera().render_work_unit(&world, &pending[0]).unwrap();
+        assert!(job.merge(&tile).unwrap());
+        assert!(!job.merge(&tile).unwrap());
+        assert_eq!(job.finish(), Some(camera().render(&world).unwrap()));
+    }
+
+    #[test]
+    fn reject_malformed_messages() {
+        let mut pixels = Canvas::new(Width(2), Height(1));
+        pixels.set_alpha(1, 0, 0.5).unwrap();
+        let tile = Tile::new(Region::new(3, 4, 5, 5), pixels);
+        let bytes = tile.to_bytes();
+        assert_eq!(Tile::from_bytes(&bytes), Ok(tile));
+
+        assert_eq!(
+            Tile::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ParseErrorKind::MissingData.at(Position::Byte(bytes.len() - 1)))
+        );
+        assert_eq!(
+            Tile::from_bytes(&bytes[1..]).unwrap_err(),
+            ParseErrorKind::InvalidHeader
+        );
+        let unit = WorkUnit {
+            index: 0,
+            region: Region::new(0, 0, 1, 1),
+        };
+        assert_eq!(
+            WorkUnit::from_bytes(&[unit.to_bytes(), vec![0]].concat()),
+            Err(ParseErrorKind::InvalidValue.at(Position::Byte(44)))
+        );
+    }
+}
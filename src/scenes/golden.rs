@@ -0,0 +1,232 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::collections::Colour;
+use crate::scenes::{Camera, Canvas, Height, RayGenerator, Width, World};
+use crate::utils::{filehandler, Error, RenderError};
+
+// Environment variable that, when set, makes check_golden (re)write the
+// reference image instead of comparing against it.
+pub const UPDATE_GOLDEN_VAR: &str = "RAYTRACER_UPDATE_GOLDEN";
+
+// Result of comparing two canvases of the same size. Channels are compared at
+// 8 bits (as references are stored as PNG), and a pixel differs when any
+// channel is further apart than the tolerance, given as a fraction of full
+// scale. The diff image shows differing pixels in red over a dimmed greyscale
+// copy of the actual image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageDiff {
+    pub differing_pixels: usize,
+    pub max_difference: f64,
+    pub diff: Canvas,
+}
+
+impl ImageDiff {
+    pub fn is_match(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    SizeMismatch {
+        actual: [usize; 2],
+        expected: [usize; 2],
+    },
+    // the diff image is written next to the reference, if that was possible
+    Mismatch {
+        differing_pixels: usize,
+        max_difference: f64,
+        diff_path: Option<String>,
+    },
+    Render(RenderError),
+    Io(Error),
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::SizeMismatch { actual, expected } => write!(
+                f,
+                "image is {}x{} but the reference is {}x{}",
+                actual[0], actual[1], expected[0], expected[1]
+            ),
+            GoldenError::Mismatch {
+                differing_pixels,
+                max_difference,
+                diff_path,
+            } => {
+                write!(
+                    f,
+                    "{} pixels differ from the reference (by up to {:.3})",
+                    differing_pixels, max_difference
+                )?;
+                match diff_path {
+                    Some(path) => write!(f, ", diff written to {}", path),
+                    None => Ok(()),
+                }
+            }
+            GoldenError::Render(error) => write!(f, "render failed: {}", error),
+            GoldenError::Io(error) => write!(f, "could not access reference: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+pub fn compare_canvases(
+    actual: &Canvas,
+    expected: &Canvas,
+    tolerance: f64,
+) -> Result<ImageDiff, GoldenError> {
+    let (actual_size, expected_size) = (
+        [actual.width(), actual.height()],
+        [expected.width(), expected.height()],
+    );
+    if actual_size != expected_size {
+        return Err(GoldenError::SizeMismatch {
+            actual: actual_size,
+            expected: expected_size,
+        });
+    }
+
+    let mut diff = Canvas::new(Width(actual.width()), Height(actual.height()));
+    let (mut differing_pixels, mut max_difference) = (0, 0.0_f64);
+    for ([column, row], pixel) in actual.enumerate_pixels() {
+        let reference = expected[[column, row]];
+        let difference = pixel
+            .channels(255)
+            .iter()
+            .zip(reference.channels(255))
+            .map(|(&a, b)| a.abs_diff(b) as f64 / 255.0)
+            .fold(0.0, f64::max);
+        max_difference = max_difference.max(difference);
+        let diff_colour = match difference > tolerance {
+            true => {
+                differing_pixels += 1;
+                Colour::new(1.0, 0.0, 0.0)
+            }
+            false => {
+                let grey = pixel.colour().luminance().clamp(0.0, 1.0) * 0.25;
+                Colour::new(grey, grey, grey)
+            }
+        };
+        diff.paint_colour_replace(column, row, diff_colour)
+            .expect("index lies within the canvas");
+    }
+
+    Ok(ImageDiff {
+        differing_pixels,
+        max_difference,
+        diff,
+    })
+}
+
+// Compares the canvas against the PNG reference at the path. A missing
+// reference is an error unless the update variable is set, in which case the
+// canvas is written as the new reference. On a mismatch the diff image is
+// written beside the reference as <name>.diff.png.
+pub fn check_golden(
+    actual: &Canvas,
+    reference_path: &str,
+    tolerance: f64,
+) -> Result<(), GoldenError> {
+    if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+        let mut file =
+            filehandler::create_file(reference_path).map_err(|e| GoldenError::Io(e.into()))?;
+        return actual
+            .write_png(&mut file)
+            .map_err(|e| GoldenError::Io(e.into()));
+    }
+
+    let expected = Canvas::open(reference_path).map_err(GoldenError::Io)?;
+    let comparison = compare_canvases(actual, &expected, tolerance)?;
+    if comparison.is_match() {
+        return Ok(());
+    }
+
+    let diff_path = Path::new(reference_path)
+        .with_extension("diff.png")
+        .to_string_lossy()
+        .into_owned();
+    let written = filehandler::create_file(&diff_path)
+        .ok()
+        .and_then(|mut file| comparison.diff.write_png(&mut file).ok());
+    Err(GoldenError::Mismatch {
+        differing_pixels: comparison.differing_pixels,
+        max_difference: comparison.max_difference,
+        diff_path: written.map(|_| diff_path),
+    })
+}
+
+pub fn render_and_check_golden<R: RayGenerator>(
+    camera: Camera<R>,
+    world: &World,
+    reference_path: &str,
+    tolerance: f64,
+) -> Result<(), GoldenError> {
+    let image = camera.render(world).map_err(GoldenError::Render)?;
+    check_golden(&image, reference_path, tolerance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: usize, height: usize) -> Canvas {
+        let mut canvas = Canvas::new(Width(width), Height(height));
+        for row in 0..height {
+            for column in 0..width {
+                let value = (column + row) as f64 / (width + height) as f64;
+                canvas
+                    .paint_colour_replace(column, row, Colour::new(value, value, value))
+                    .unwrap();
+            }
+        }
+        canvas
+    }
+
+    #[test]
+    fn compare_within_tolerance() {
+        let expected = gradient(4, 3);
+        let mut actual = expected.clone();
+        actual
+            .paint_colour_additive(1, 2, Colour::new(0.0, 0.05, 0.0))
+            .unwrap();
+
+        assert!(compare_canvases(&actual, &expected, 0.1)
+            .unwrap()
+            .is_match());
+        let comparison = compare_canvases(&actual, &expected, 0.01).unwrap();
+        assert_eq!(comparison.differing_pixels, 1);
+        assert!(comparison.max_difference > 0.04 && comparison.max_difference < 0.06);
+        assert_eq!(comparison.diff[[1, 2]].colour(), Colour::new(1.0, 0.0, 0.0));
+        assert!(matches!(
+            compare_canvases(&actual, &gradient(3, 4), 0.1),
+            Err(GoldenError::SizeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn check_against_reference_file() {
+        let reference_path = "test_golden_reference.png";
+        let expected = gradient(4, 3);
+        expected
+            .write_png(&mut filehandler::create_file(reference_path).unwrap())
+            .unwrap();
+
+        assert!(check_golden(&expected, reference_path, 0.0).is_ok());
+        let mut actual = expected.clone();
+        actual.fill(Colour::new(1.0, 1.0, 1.0));
+        let error = check_golden(&actual, reference_path, 0.0).unwrap_err();
+        assert!(matches!(
+            error,
+            GoldenError::Mismatch { differing_pixels: 12, diff_path: Some(ref path), .. }
+                if path == "test_golden_reference.diff.png"
+        ));
+
+        // cleanup
+        std::fs::remove_file(reference_path).unwrap();
+        std::fs::remove_file("test_golden_reference.diff.png").unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+use crate::collections::Colour;
+use crate::scenes::RenderStats;
+
+// False-colour image of the work done for each pixel, to show where better
+// bounds or an accelerator would pay off. Each ray's colour is replaced by its
+// cost on a scale running from blue (none) through cyan, green and yellow to
+// red (max_cost and above); the camera's integrator still decides what work
+// the ray does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Heatmap {
+    pub metric: CostMetric,
+    pub max_cost: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CostMetric {
+    // tests of shapes' bounds and of primitives
+    IntersectionTests,
+    // primary, secondary and shadow rays
+    Rays,
+}
+
+impl Heatmap {
+    const SCALE: [Colour; 5] = [
+        Colour {
+            red: 0.0,
+            green: 0.0,
+            blue: 1.0,
+        },
+        Colour {
+            red: 0.0,
+            green: 1.0,
+            blue: 1.0,
+        },
+        Colour {
+            red: 0.0,
+            green: 1.0,
+            blue: 0.0,
+        },
+        Colour {
+            red: 1.0,
+            green: 1.0,
+            blue: 0.0,
+        },
+        Colour {
+            red: 1.0,
+            green: 0.0,
+            blue: 0.0,
+        },
+    ];
+
+    pub fn new(metric: CostMetric, max_cost: f64) -> Heatmap {
+        Heatmap { metric, max_cost }
+    }
+
+    // the colour for the work recorded while tracing a ray
+    pub(crate) fn colour_for(&self, stats: &RenderStats) -> Colour {
+        let cost = match self.metric {
+            CostMetric::IntersectionTests => stats.intersection_test_count,
+            CostMetric::Rays => {
+                stats.primary_ray_count + stats.secondary_ray_count + stats.shadow_ray_count
+            }
+        };
+        let position = (cost as f64 / self.max_cost).clamp(0.0, 1.0) * 4.0;
+        let step = usize::min(position as usize, 3);
+        let [from, to] = [Heatmap::SCALE[step], Heatmap::SCALE[step + 1]];
+        from + (to - from) * (position - step as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colour_costs_on_scale() {
+        let heatmap = Heatmap::new(CostMetric::Rays, 8.0);
+        let stats = |rays: u64| RenderStats {
+            primary_ray_count: 1,
+            shadow_ray_count: rays - 1,
+            ..RenderStats::default()
+        };
+        assert_eq!(heatmap.colour_for(&stats(1)), Colour::new(0.0, 0.5, 1.0));
+        assert_eq!(heatmap.colour_for(&stats(4)), Colour::new(0.0, 1.0, 0.0));
+        assert_eq!(heatmap.colour_for(&stats(8)), Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(heatmap.colour_for(&stats(20)), Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(
+            heatmap.colour_for(&RenderStats::default()),
+            Colour::new(0.0, 0.0, 1.0)
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use std::ops::Range;
+
+use crate::objects::*;
+use crate::scenes::Accelerator;
+use crate::utils::{split_objects, SplitMethod};
+
+// A tree of bounding boxes over the top-level objects of a world (the
+// instances), so that a ray skips whole branches of the scene it misses
+// rather than testing every object's bounds in turn. It is the top level of
+// two: objects that are themselves large (such as meshes) carry trees of
+// their own as nested groups (see divide). The tree refers to objects by
+// their place in the list, so when objects move without being added or
+// removed, the tree need only be refit to their new bounds rather than
+// rebuilt.
+#[derive(Clone, Debug, Default)]
+pub struct InstanceTree {
+    // parents come before their children, with the root first
+    nodes: Vec<Node>,
+    // objects with no bounds, which every ray is tested against
+    unbounded: Vec<usize>,
+    object_count: usize,
+    split_method: SplitMethod,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    bounding_box: BoundingBox,
+    contents: NodeContents,
+}
+
+#[derive(Clone, Debug)]
+enum NodeContents {
+    Leaf(Vec<usize>),
+    Branch([usize; 2]),
+}
+
+impl InstanceTree {
+    const LEAF_SIZE: usize = 2;
+
+    // an empty tree with the given settings, to be built over a world's
+    // objects when it is handed to one
+    pub fn with_split(split_method: SplitMethod) -> InstanceTree {
+        InstanceTree {
+            split_method,
+            ..InstanceTree::default()
+        }
+    }
+
+    pub fn new(objects: &[Shape], split_method: SplitMethod) -> InstanceTree {
+        let boxes: Vec<BoundingBox> = objects
+            .iter()
+            .map(|object| object.bounds().bounding_box())
+            .collect();
+        let (bounded, unbounded): (Vec<usize>, Vec<usize>) =
+            (0..objects.len()).partition(|&index| boxes[index].centroid().is_some());
+
+        let mut tree = InstanceTree {
+            nodes: vec![],
+            unbounded,
+            object_count: objects.len(),
+            split_method,
+        };
+        if !bounded.is_empty() {
+            tree.build_node(bounded, &boxes, split_method);
+        }
+        tree
+    }
+
+    fn build_node(
+        &mut self,
+        indices: Vec<usize>,
+        boxes: &[BoundingBox],
+        split_method: SplitMethod,
+    ) -> usize {
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            bounding_box: InstanceTree::union(indices.iter().map(|&index| boxes[index])),
+            contents: NodeContents::Leaf(vec![]),
+        });
+
+        let split = match indices.len() {
+            count if count <= InstanceTree::LEAF_SIZE => None,
+            _ => {
+                let node_boxes: Vec<BoundingBox> =
+                    indices.iter().map(|&index| boxes[index]).collect();
+                let centres: Vec<[f64; 3]> = node_boxes
+                    .iter()
+                    .filter_map(|bounding_box| bounding_box.centroid())
+                    .map(|centre| [centre.x, centre.y, centre.z])
+                    .collect();
+                split_objects(split_method, &centres, &node_boxes)
+            }
+        };
+        self.nodes[node].contents = match split {
+            Some(below) => {
+                let (lower, upper): (Vec<_>, Vec<_>) = indices
+                    .into_iter()
+                    .zip(below)
+                    .partition(|(_, below)| *below);
+                let children = [lower, upper].map(|half| {
+                    let half = half.into_iter().map(|(index, _)| index).collect();
+                    self.build_node(half, boxes, split_method)
+                });
+                NodeContents::Branch(children)
+            }
+            None => NodeContents::Leaf(indices),
+        };
+        node
+    }
+
+    fn union(boxes: impl Iterator<Item = BoundingBox>) -> BoundingBox {
+        boxes
+            .reduce(|union, bounding_box| union + bounding_box)
+            .expect("every node holds at least one object")
+    }
+
+    pub fn object_count(&self) -> usize {
+        self.object_count
+    }
+
+    // tests the objects in every leaf under the node that the ray passes through
+    // within the range
+    fn intersect_node_into<'world: 'ray, 'ray>(
+        &self,
+        node: usize,
+        objects: &'world [Shape],
+        world_ray: &'ray Ray,
+        t_range: &Range<f64>,
+        transform_stack: &mut Vec<&'ray Transform>,
+        hit_register: &mut HitRegister<'ray, dyn PrimitiveShape>,
+    ) {
+        let Node {
+            bounding_box,
+            contents,
+        } = &self.nodes[node];
+        let ray = transform_through_stack_forwards(*world_ray, transform_stack);
+        if !bounding_box.overlaps_ray(&ray, t_range) {
+            return;
+        }
+        match contents {
+            NodeContents::Leaf(indices) => {
+                for &index in indices {
+                    objects[index].intersect_ray_into(world_ray, transform_stack, hit_register);
+                }
+            }
+            NodeContents::Branch(children) => {
+                for &child in children {
+                    self.intersect_node_into(
+                        child,
+                        objects,
+                        world_ray,
+                        t_range,
+                        transform_stack,
+                        hit_register,
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Accelerator for InstanceTree {
+    fn build(&mut self, objects: &[Shape]) {
+        *self = InstanceTree::new(objects, self.split_method);
+    }
+
+    // Brings the tree's boxes up to date with the objects' bounds, keeping its
+    // shape. Objects far from where they were when the tree was built make
+    // for loose boxes, so a tree whose objects have all moved a long way is
+    // better rebuilt. Panics if objects have been added or removed.
+    fn refit(&mut self, objects: &[Shape]) {
+        assert_eq!(
+            objects.len(),
+            self.object_count,
+            "instance trees can only be refit to the same number of objects"
+        );
+        for node in (0..self.nodes.len()).rev() {
+            let bounding_box = match &self.nodes[node].contents {
+                NodeContents::Leaf(indices) => InstanceTree::union(
+                    indices
+                        .iter()
+                        .map(|&index| objects[index].bounds().bounding_box()),
+                ),
+                NodeContents::Branch([lower, upper]) => {
+                    self.nodes[*lower].bounding_box + self.nodes[*upper].bounding_box
+                }
+            };
+            self.nodes[node].bounding_box = bounding_box;
+        }
+    }
+
+    fn intersect_ray_into<'world: 'ray, 'ray>(
+        &self,
+        objects: &'world [Shape],
+        world_ray: &'ray Ray,
+        t_range: Range<f64>,
+        transform_stack: &mut Vec<&'ray Transform>,
+        hit_register: &mut HitRegister<'ray, dyn PrimitiveShape>,
+    ) {
+        for &index in &self.unbounded {
+            objects[index].intersect_ray_into(world_ray, transform_stack, hit_register);
+        }
+        if !self.nodes.is_empty() {
+            self.intersect_node_into(
+                0,
+                objects,
+                world_ray,
+                &t_range,
+                transform_stack,
+                hit_register,
+            );
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::{Point, Vector};
+    use crate::utils::{BuildInto, Buildable};
+
+    fn sphere_at(x: f64) -> Shape {
+        Sphere::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(x, 0.0, 0.0)))
+            .build_into()
+    }
+
+    fn hit_ts(tree: &InstanceTree, objects: &[Shape], ray: &Ray) -> Vec<f64> {
+        hit_ts_within(tree, objects, ray, f64::NEG_INFINITY..f64::INFINITY)
+    }
+
+    fn hit_ts_within(
+        tree: &InstanceTree,
+        objects: &[Shape],
+        ray: &Ray,
+        t_range: Range<f64>,
+    ) -> Vec<f64> {
+        let mut hit_register = HitRegister::empty();
+        tree.intersect_ray_into(objects, ray, t_range, &mut vec![], &mut hit_register);
+        hit_register.expose().iter().map(|hit| hit.t()).collect()
+    }
+
+    #[test]
+    fn tree_finds_same_hits_as_objects() {
+        let mut objects: Vec<Shape> = (0..9).map(|index| sphere_at(index as f64 * 3.0)).collect();
+        objects.push(Plane::builder().build_into());
+        let tree = InstanceTree::new(&objects, SplitMethod::SURFACE_AREA);
+        assert_eq!(tree.object_count(), 10);
+
+        for ray in [
+            Ray::new(Point::new(6.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0)),
+            Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0)),
+            Ray::new(Point::new(4.5, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0)),
+        ] {
+            let mut expected = HitRegister::empty();
+            for object in &objects {
+                object.intersect_ray_into(&ray, &mut vec![], &mut expected);
+            }
+            let expected: Vec<f64> = expected.expose().iter().map(|hit| hit.t()).collect();
+            assert_eq!(hit_ts(&tree, &objects, &ray), expected);
+        }
+    }
+
+    #[test]
+    fn tree_leaves_out_objects_beyond_range() {
+        let objects: Vec<Shape> = (0..9).map(|index| sphere_at(index as f64 * 3.0)).collect();
+        let tree = InstanceTree::new(&objects, SplitMethod::SURFACE_AREA);
+        let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        // only the first two spheres lie (partly) within reach
+        assert_eq!(
+            hit_ts_within(&tree, &objects, &ray, 0.0..8.5),
+            vec![4.0, 6.0, 7.0, 9.0]
+        );
+    }
+
+    #[test]
+    fn refit_follows_moved_object() {
+        let mut objects: Vec<Shape> = (0..4).map(|index| sphere_at(index as f64 * 3.0)).collect();
+        let mut tree = InstanceTree::new(&objects, SplitMethod::Median);
+        let ray = Ray::new(Point::new(20.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert!(hit_ts(&tree, &objects, &ray).is_empty());
+
+        objects[1] = sphere_at(20.0);
+        // the stale tree still culls the moved sphere
+        assert!(hit_ts(&tree, &objects, &ray).is_empty());
+        tree.refit(&objects);
+        assert_eq!(hit_ts(&tree, &objects, &ray), vec![4.0, 6.0]);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::collections::{Colour, Vector};
+
+// Strategy used by the camera to turn a cast ray into a colour.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Integrator {
+    // recursive ray tracing with Phong shading, reflections and refractions
+    #[default]
+    Whitted,
+    // flat-banded cartoon shading with optional outlines
+    Toon(Toon),
+    // every surface lit as plain grey clay, for checking lighting and form
+    // apart from the materials
+    Clay,
+    // world-space normals of the first surfaces hit, each component mapped
+    // from [-1, 1] to [0, 1]; misses are black
+    Normals,
+    // distance to the first surfaces hit, from white at the camera to black at
+    // the given distance and beyond; misses are black
+    Depth(f64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toon {
+    pub bands: usize,
+    pub outline: Option<Outline>,
+}
+
+impl Toon {
+    pub fn new(bands: usize) -> Toon {
+        Toon {
+            bands,
+            outline: None,
+        }
+    }
+
+    pub fn with_outline(mut self, outline: Outline) -> Toon {
+        self.outline = Some(outline);
+        self
+    }
+}
+
+// Pixels are outlined wherever the surface seen through them differs too much
+// from that of a neighbouring pixel, either in depth (relative difference) or
+// in orientation (dot product of normals falls below the threshold).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outline {
+    pub colour: Colour,
+    pub depth_threshold: f64,
+    pub normal_threshold: f64,
+}
+
+impl Outline {
+    pub fn new(colour: Colour) -> Outline {
+        Outline {
+            colour,
+            depth_threshold: 0.1,
+            normal_threshold: 0.8,
+        }
+    }
+
+    fn separates(&self, a: Option<(f64, Vector)>, b: Option<(f64, Vector)>) -> bool {
+        match (a, b) {
+            (Some((depth_a, normal_a)), Some((depth_b, normal_b))) => {
+                let depth_difference = (depth_a - depth_b).abs() / f64::min(depth_a, depth_b);
+                depth_difference > self.depth_threshold
+                    || normal_a.dot(normal_b) < self.normal_threshold
+            }
+            (None, None) => false,
+            _ => true,
+        }
+    }
+}
+
+// Per-pixel record of the (blended) depth and normal of the surfaces hit by
+// the rays contributing to each pixel.
+#[derive(Clone, Debug)]
+pub(crate) struct SurfaceBuffer {
+    width: usize,
+    height: usize,
+    samples: Vec<SurfaceSample>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct SurfaceSample {
+    depth: f64,
+    normal: Vector,
+    hit_weight: f64,
+    total_weight: f64,
+}
+
+impl SurfaceBuffer {
+    pub(crate) fn new(width: usize, height: usize) -> SurfaceBuffer {
+        let sample = SurfaceSample {
+            depth: 0.0,
+            normal: Vector::zero(),
+            hit_weight: 0.0,
+            total_weight: 0.0,
+        };
+        SurfaceBuffer {
+            width,
+            height,
+            samples: vec![sample; width * height],
+        }
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        [pos_x, pos_y]: [usize; 2],
+        surface: Option<(f64, Vector)>,
+        blend_weight: f64,
+    ) {
+        let sample = &mut self.samples[pos_y * self.width + pos_x];
+        sample.total_weight += blend_weight;
+        if let Some((depth, normal)) = surface {
+            sample.depth += depth * blend_weight;
+            sample.normal = sample.normal + normal * blend_weight;
+            sample.hit_weight += blend_weight;
+        }
+    }
+
+    pub(crate) fn surface(&self, [pos_x, pos_y]: [usize; 2]) -> Option<(f64, Vector)> {
+        let sample = self.samples[pos_y * self.width + pos_x];
+        if sample.hit_weight == 0.0 || sample.hit_weight < sample.total_weight / 2.0 {
+            None
+        } else {
+            Some((sample.depth / sample.hit_weight, sample.normal.normalise()))
+        }
+    }
+
+    // indices of the pixels lying on an outline; only the foreground side of
+    // a discontinuity is marked so that silhouettes hug the object
+    pub(crate) fn outline_pixels(&self, outline: &Outline) -> Vec<[usize; 2]> {
+        let mut edges = vec![];
+        for pos_y in 0..self.height {
+            for pos_x in 0..self.width {
+                let Some((depth, normal)) = self.surface([pos_x, pos_y]) else {
+                    continue;
+                };
+                let neighbours = [
+                    (pos_x > 0).then(|| [pos_x - 1, pos_y]),
+                    (pos_x + 1 < self.width).then(|| [pos_x + 1, pos_y]),
+                    (pos_y > 0).then(|| [pos_x, pos_y - 1]),
+                    (pos_y + 1 < self.height).then(|| [pos_x, pos_y + 1]),
+                ];
+                let on_edge = neighbours.into_iter().flatten().any(|neighbour| {
+                    let neighbour_surface = self.surface(neighbour);
+                    let in_front = match neighbour_surface {
+                        Some((neighbour_depth, _)) => depth <= neighbour_depth,
+                        None => true,
+                    };
+                    in_front && outline.separates(Some((depth, normal)), neighbour_surface)
+                });
+                if on_edge {
+                    edges.push([pos_x, pos_y]);
+                }
+            }
+        }
+        edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outline_separates_hit_and_miss() {
+        let outline = Outline::new(Colour::new(0.0, 0.0, 0.0));
+        let surface = Some((1.0, Vector::new(0.0, 0.0, -1.0)));
+        assert!(outline.separates(surface, None));
+        assert!(!outline.separates(None, None));
+        assert!(!outline.separates(surface, surface));
+    }
+
+    #[test]
+    fn outline_separates_depth_and_normal_discontinuities() {
+        let outline = Outline::new(Colour::new(0.0, 0.0, 0.0));
+        let normal = Vector::new(0.0, 0.0, -1.0);
+        assert!(outline.separates(Some((1.0, normal)), Some((2.0, normal))));
+        assert!(!outline.separates(Some((1.0, normal)), Some((1.05, normal))));
+        assert!(outline.separates(Some((1.0, normal)), Some((1.0, Vector::new(1.0, 0.0, 0.0)))));
+    }
+
+    #[test]
+    fn surface_buffer_finds_outline_pixels() {
+        let outline = Outline::new(Colour::new(0.0, 0.0, 0.0));
+        let normal = Vector::new(0.0, 0.0, -1.0);
+        let mut surface_buffer = SurfaceBuffer::new(4, 1);
+        surface_buffer.record([0, 0], Some((2.0, normal)), 1.0);
+        surface_buffer.record([1, 0], Some((1.0, normal)), 1.0);
+        surface_buffer.record([2, 0], None, 1.0);
+        surface_buffer.record([3, 0], None, 1.0);
+        assert_eq!(surface_buffer.outline_pixels(&outline), vec![[1, 0]]);
+    }
+}
@@ -0,0 +1,415 @@
+use std::ops::Range;
+
+use crate::collections::{Point, Vector};
+use crate::objects::*;
+use crate::scenes::Accelerator;
+
+// A kd-tree over the top-level objects of a world: space is cut in two by
+// planes across the axes until each cell holds only a few objects, with each
+// plane placed by the surface area heuristic (see SplitMethod). Unlike the
+// instance tree the cells never overlap, so rays visit them nearest first
+// and shadow rays can stop at the first object found, but objects lying
+// across a plane are listed on both sides of it, and the tree has to be
+// rebuilt whenever objects move.
+#[derive(Clone, Debug, Default)]
+pub struct KdTree {
+    // the root cell first, with each cell's descendants after it
+    nodes: Vec<KdNode>,
+    // the root cell, if there are any bounded objects
+    bounding_box: Option<BoundingBox>,
+    // objects with no bounds, which every ray is tested against
+    unbounded: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+enum KdNode {
+    Leaf(Vec<usize>),
+    Branch {
+        axis: usize,
+        position: f64,
+        // the cells below and above the plane
+        children: [usize; 2],
+    },
+}
+
+impl KdTree {
+    const LEAF_SIZE: usize = 2;
+    const TRAVERSAL_COST: f64 = 1.0;
+    const INTERSECTION_COST: f64 = 1.0;
+
+    pub fn new(objects: &[Shape]) -> KdTree {
+        let boxes: Vec<BoundingBox> = objects
+            .iter()
+            .map(|object| object.bounds().bounding_box())
+            .collect();
+        let (bounded, unbounded): (Vec<usize>, Vec<usize>) =
+            (0..objects.len()).partition(|&index| boxes[index].centroid().is_some());
+        let bounding_box = bounded
+            .iter()
+            .map(|&index| boxes[index])
+            .reduce(|union, bounding_box| union + bounding_box);
+
+        let mut tree = KdTree {
+            nodes: vec![],
+            bounding_box,
+            unbounded,
+        };
+        if let Some(bounding_box) = bounding_box {
+            // the usual depth limit, beyond which more cells rarely pay off
+            let max_depth = 8 + (1.3 * (bounded.len() as f64).log2()).round() as usize;
+            tree.build_node(bounded, &boxes, bounding_box, max_depth);
+        }
+        tree
+    }
+
+    fn build_node(
+        &mut self,
+        indices: Vec<usize>,
+        boxes: &[BoundingBox],
+        cell: BoundingBox,
+        depth_remaining: usize,
+    ) -> usize {
+        let node = self.nodes.len();
+        self.nodes.push(KdNode::Leaf(vec![]));
+
+        let split = match indices.len() {
+            count if count <= KdTree::LEAF_SIZE || depth_remaining == 0 => None,
+            _ => KdTree::best_plane(&indices, boxes, cell),
+        };
+        self.nodes[node] = match split {
+            Some((axis, position)) => {
+                let range = |bounding_box: &BoundingBox| KdTree::ranges(bounding_box)[axis];
+                // objects lying in the plane go below it
+                let below = indices
+                    .iter()
+                    .copied()
+                    .filter(|&index| {
+                        let [min, max] = range(&boxes[index]);
+                        min < position || max <= position
+                    })
+                    .collect();
+                let above = indices
+                    .into_iter()
+                    .filter(|&index| range(&boxes[index])[1] > position)
+                    .collect();
+                let [min, max] = range(&cell);
+                let children = [
+                    (below, KdTree::with_range(cell, axis, [min, position])),
+                    (above, KdTree::with_range(cell, axis, [position, max])),
+                ]
+                .map(|(half, half_cell)| {
+                    self.build_node(half, boxes, half_cell, depth_remaining - 1)
+                });
+                KdNode::Branch {
+                    axis,
+                    position,
+                    children,
+                }
+            }
+            None => KdNode::Leaf(indices),
+        };
+        node
+    }
+
+    // The plane (as an axis and position along it) through the cell that
+    // makes for the least expected cost of a ray passing through, trying the
+    // faces of each object's box, if it beats testing every object.
+    fn best_plane(
+        indices: &[usize],
+        boxes: &[BoundingBox],
+        cell: BoundingBox,
+    ) -> Option<(usize, f64)> {
+        let count = indices.len();
+        let cell_area = cell.surface_area();
+        let relative = |area: f64| match cell_area {
+            cell_area if cell_area > 0.0 => area / cell_area,
+            _ => 1.0,
+        };
+
+        let (cost, axis, position) = (0..3)
+            .flat_map(|axis| {
+                let [cell_min, cell_max] = KdTree::ranges(&cell)[axis];
+                let mut mins: Vec<f64> = indices
+                    .iter()
+                    .map(|&index| KdTree::ranges(&boxes[index])[axis][0])
+                    .collect();
+                let mut maxes: Vec<f64> = indices
+                    .iter()
+                    .map(|&index| KdTree::ranges(&boxes[index])[axis][1])
+                    .collect();
+                mins.sort_by(f64::total_cmp);
+                maxes.sort_by(f64::total_cmp);
+                let mut positions: Vec<f64> = mins
+                    .iter()
+                    .chain(&maxes)
+                    .copied()
+                    .filter(|&position| cell_min < position && position < cell_max)
+                    .collect();
+                positions.sort_by(f64::total_cmp);
+                positions.dedup();
+
+                positions.into_iter().map(move |position| {
+                    // estimates, counting objects lying in the plane on neither side
+                    let below = mins.partition_point(|&min| min < position);
+                    let above = count - maxes.partition_point(|&max| max <= position);
+                    let [lower, upper] = [[cell_min, position], [position, cell_max]]
+                        .map(|range| KdTree::with_range(cell, axis, range).surface_area());
+                    let cost = KdTree::TRAVERSAL_COST
+                        + KdTree::INTERSECTION_COST
+                            * (relative(lower) * below as f64 + relative(upper) * above as f64);
+                    (cost, axis, position)
+                })
+            })
+            .min_by(|(cost1, _, _), (cost2, _, _)| cost1.total_cmp(cost2))?;
+        (cost < KdTree::INTERSECTION_COST * count as f64).then_some((axis, position))
+    }
+
+    fn ranges(bounding_box: &BoundingBox) -> [[f64; 2]; 3] {
+        let (x_range, y_range, z_range) = bounding_box.axial_bounds();
+        [x_range, y_range, z_range]
+    }
+
+    fn with_range(bounding_box: BoundingBox, axis: usize, range: [f64; 2]) -> BoundingBox {
+        match axis {
+            0 => bounding_box.bound_in_x_axis(range),
+            1 => bounding_box.bound_in_y_axis(range),
+            _ => bounding_box.bound_in_z_axis(range),
+        }
+    }
+
+    // Notes an object as tested, returning whether it had not been yet, so
+    // that objects listed in several leaves are only tested once. The list
+    // (kept sorted) grows only with the objects a ray reaches, rather than
+    // with the size of the scene.
+    fn first_visit(tested: &mut Vec<usize>, index: usize) -> bool {
+        match tested.binary_search(&index) {
+            Ok(_) => false,
+            Err(position) => {
+                tested.insert(position, index);
+                true
+            }
+        }
+    }
+
+    fn along(axis: usize, point: Point, direction: Vector) -> (f64, f64) {
+        match axis {
+            0 => (point.x, direction.x),
+            1 => (point.y, direction.y),
+            _ => (point.z, direction.z),
+        }
+    }
+
+    // Calls visit with the objects of each leaf the ray passes through, and
+    // the range of t over which it does so, nearest first, until visit
+    // returns true. The ray is in the space the tree was built in.
+    fn visit_leaves(&self, ray: &Ray, mut visit: impl FnMut(&[usize], Range<f64>) -> bool) {
+        let Some((tmin, tmax)) = self
+            .bounding_box
+            .and_then(|bounding_box| bounding_box.clip_ray(ray))
+        else {
+            return;
+        };
+
+        let mut pending = vec![(0, tmin, tmax)];
+        while let Some((node, tmin, tmax)) = pending.pop() {
+            match &self.nodes[node] {
+                KdNode::Leaf(indices) => {
+                    if visit(indices, tmin..tmax) {
+                        return;
+                    }
+                }
+                &KdNode::Branch {
+                    axis,
+                    position,
+                    children: [below, above],
+                } => {
+                    let (origin, direction) = KdTree::along(axis, ray.origin, ray.direction);
+                    if direction == 0.0 {
+                        // rays along the plane pass through both cells
+                        if origin >= position {
+                            pending.push((above, tmin, tmax));
+                        }
+                        if origin <= position {
+                            pending.push((below, tmin, tmax));
+                        }
+                        continue;
+                    }
+                    let (near, far) = match direction > 0.0 {
+                        true => (below, above),
+                        false => (above, below),
+                    };
+                    let t_split = (position - origin) / direction;
+                    if t_split > tmax {
+                        pending.push((near, tmin, tmax));
+                    } else if t_split < tmin {
+                        pending.push((far, tmin, tmax));
+                    } else {
+                        pending.push((far, t_split, tmax));
+                        pending.push((near, tmin, t_split));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Accelerator for KdTree {
+    fn build(&mut self, objects: &[Shape]) {
+        *self = KdTree::new(objects);
+    }
+
+    fn intersect_ray_into<'world: 'ray, 'ray>(
+        &self,
+        objects: &'world [Shape],
+        world_ray: &'ray Ray,
+        t_range: Range<f64>,
+        transform_stack: &mut Vec<&'ray Transform>,
+        hit_register: &mut HitRegister<'ray, dyn PrimitiveShape>,
+    ) {
+        let ray = transform_through_stack_forwards(*world_ray, transform_stack);
+        for &index in &self.unbounded {
+            objects[index].intersect_ray_into(world_ray, transform_stack, hit_register);
+        }
+        let mut tested = vec![];
+        self.visit_leaves(&ray, |indices, leaf_range| {
+            if leaf_range.start >= t_range.end {
+                return true;
+            }
+            if leaf_range.end >= t_range.start {
+                for &index in indices {
+                    if KdTree::first_visit(&mut tested, index) {
+                        objects[index].intersect_ray_into(world_ray, transform_stack, hit_register);
+                    }
+                }
+            }
+            false
+        });
+    }
+
+    // tests objects nearest first, stopping at the first hit within the range
+    fn is_occluded(&self, objects: &[Shape], world_ray: &Ray, t_range: Range<f64>) -> bool {
+        let hits_within = |object: &Shape| {
+            !object
+                .intersect_ray(world_ray, vec![])
+                .clip(t_range.clone())
+                .is_empty()
+        };
+        if self
+            .unbounded
+            .iter()
+            .any(|&index| hits_within(&objects[index]))
+        {
+            return true;
+        }
+
+        let mut tested = vec![];
+        let mut occluded = false;
+        self.visit_leaves(world_ray, |indices, leaf_range| {
+            if leaf_range.start > t_range.end {
+                return true;
+            }
+            if leaf_range.end < t_range.start {
+                return false;
+            }
+            occluded = indices.iter().any(|&index| {
+                KdTree::first_visit(&mut tested, index) && hits_within(&objects[index])
+            });
+            occluded
+        });
+        occluded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{approx_eq, BuildInto, Buildable};
+
+    fn sphere_at(x: f64, y: f64) -> Shape {
+        Sphere::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(x, y, 0.0)))
+            .build_into()
+    }
+
+    fn hit_ts(accelerator: &dyn Accelerator, objects: &[Shape], ray: &Ray) -> Vec<f64> {
+        let mut hit_register = HitRegister::empty();
+        accelerator.intersect_ray_into(
+            objects,
+            ray,
+            f64::NEG_INFINITY..f64::INFINITY,
+            &mut vec![],
+            &mut hit_register,
+        );
+        hit_register.expose().iter().map(|hit| hit.t()).collect()
+    }
+
+    #[test]
+    fn kd_tree_finds_same_hits_as_objects() {
+        let mut objects: Vec<Shape> = (0..16)
+            .map(|index| sphere_at((index % 4) as f64 * 3.0, (index / 4) as f64 * 3.0))
+            .collect();
+        // a long box across many cells
+        objects.push(
+            Cube::builder()
+                .set_frame_transformation(Transform::new(TransformKind::Scale(10.0, 0.5, 0.5)))
+                .build_into(),
+        );
+        objects.push(Plane::builder().build_into());
+        let tree = KdTree::new(&objects);
+        assert!(tree.nodes.len() > 1);
+        assert_eq!(tree.unbounded, vec![17]);
+
+        for ray in [
+            Ray::new(Point::new(3.0, 20.0, 0.0), Vector::new(0.0, -1.0, 0.0)),
+            Ray::new(Point::new(-5.0, 3.0, 0.0), Vector::new(1.0, 0.0, 0.0)),
+            Ray::new(Point::new(4.5, 4.5, -5.0), Vector::new(0.0, 0.0, 1.0)),
+            Ray::new(Point::new(20.0, 20.0, 0.0), Vector::new(-1.0, -1.0, 0.0)),
+        ] {
+            let mut expected = HitRegister::empty();
+            for object in &objects {
+                object.intersect_ray_into(&ray, &mut vec![], &mut expected);
+            }
+            let expected: Vec<f64> = expected.expose().iter().map(|hit| hit.t()).collect();
+            assert_eq!(hit_ts(&tree, &objects, &ray), expected);
+        }
+    }
+
+    #[test]
+    fn objects_in_several_leaves_are_tested_once() {
+        let mut objects: Vec<Shape> = (0..8)
+            .map(|index| sphere_at(index as f64 * 3.0, 3.0))
+            .collect();
+        objects.push(
+            Cube::builder()
+                .set_frame_transformation(Transform::new(TransformKind::Scale(12.0, 0.5, 0.5)))
+                .build_into(),
+        );
+        let tree = KdTree::new(&objects);
+        let along = Ray::new(Point::new(-20.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let hits = hit_ts(&tree, &objects, &along);
+        assert_eq!(hits.len(), 2);
+        approx_eq!(hits[0], 8.0);
+        approx_eq!(hits[1], 32.0);
+
+        let mut tested = vec![];
+        assert!(KdTree::first_visit(&mut tested, 5));
+        assert!(KdTree::first_visit(&mut tested, 2));
+        assert!(!KdTree::first_visit(&mut tested, 5));
+        assert_eq!(tested, vec![2, 5]);
+    }
+
+    #[test]
+    fn kd_tree_occlusion_stops_within_range() {
+        let objects: Vec<Shape> = (0..8)
+            .map(|index| sphere_at(index as f64 * 3.0, 0.0))
+            .collect();
+        let tree = KdTree::new(&objects);
+        let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(tree.is_occluded(&objects, &ray, 0.0..f64::INFINITY));
+        assert!(tree.is_occluded(&objects, &ray, 10.0..12.0));
+        // the gap between the second and third spheres
+        assert!(!tree.is_occluded(&objects, &ray, 9.5..9.9));
+        let above = Ray::new(Point::new(-5.0, 2.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(!tree.is_occluded(&objects, &above, 0.0..f64::INFINITY));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::objects::{Light, PrimitiveShape};
+use crate::scenes::overrides::glob_match;
+
+// Light linking: restricts which objects each light illuminates, matching
+// light and object names with the same glob patterns as material overrides. A
+// light with no inclusions lights every object it is not excluded from; once
+// it has any inclusions it only lights objects matching one of them. An
+// exclusion always wins over an inclusion. Unlinked lights cast no shadows on,
+// and add nothing to, the objects they do not illuminate.
+#[derive(Debug, Default)]
+pub struct LightLinks {
+    rules: Vec<LightLink>,
+}
+
+#[derive(Debug)]
+struct LightLink {
+    light_pattern: String,
+    object_pattern: String,
+    included: bool,
+}
+
+impl LightLinks {
+    pub fn new() -> LightLinks {
+        LightLinks::default()
+    }
+
+    pub fn include(mut self, light_pattern: &str, object_pattern: &str) -> LightLinks {
+        self.rules.push(LightLink {
+            light_pattern: light_pattern.to_string(),
+            object_pattern: object_pattern.to_string(),
+            included: true,
+        });
+        self
+    }
+
+    pub fn exclude(mut self, light_pattern: &str, object_pattern: &str) -> LightLinks {
+        self.rules.push(LightLink {
+            light_pattern: light_pattern.to_string(),
+            object_pattern: object_pattern.to_string(),
+            included: false,
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn illuminates(&self, light: &Light, object: &dyn PrimitiveShape) -> bool {
+        let light_name = light.name.unwrap_or("");
+        let object_name = object.name().unwrap_or("");
+        let mut has_inclusions = false;
+        let mut included = false;
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| glob_match(&rule.light_pattern, light_name))
+        {
+            let matches = glob_match(&rule.object_pattern, object_name);
+            if rule.included {
+                has_inclusions = true;
+                included |= matches;
+            } else if matches {
+                return false;
+            }
+        }
+        !has_inclusions || included
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::{Colour, Point};
+    use crate::objects::{Shape, Sphere};
+    use crate::utils::{BuildInto, Buildable};
+
+    fn primitive(name: Option<&str>) -> Box<dyn PrimitiveShape> {
+        let builder = Sphere::builder();
+        let shape: Shape = match name {
+            Some(name) => builder.set_name(name).build_into(),
+            None => builder.build_into(),
+        };
+        match shape {
+            Shape::Primitive(primitive) => primitive,
+            _ => panic!("spheres are primitive shapes"),
+        }
+    }
+
+    #[test]
+    fn lights_illuminate_everything_without_links() {
+        let light = Light::new(Point::zero(), Colour::new(1.0, 1.0, 1.0));
+        assert!(LightLinks::new().illuminates(&light, primitive(None).as_ref()));
+    }
+
+    #[test]
+    fn included_lights_only_illuminate_matching_objects() {
+        let rim = Light::new(Point::zero(), Colour::new(1.0, 1.0, 1.0)).with_name("rim");
+        let key = Light::new(Point::zero(), Colour::new(1.0, 1.0, 1.0)).with_name("key");
+        let links = LightLinks::new().include("rim", "product_*");
+
+        let product = primitive(Some("product_bottle"));
+        let backdrop = primitive(Some("backdrop"));
+        assert!(links.illuminates(&rim, product.as_ref()));
+        assert!(!links.illuminates(&rim, backdrop.as_ref()));
+        assert!(links.illuminates(&key, backdrop.as_ref()));
+    }
+
+    #[test]
+    fn exclusions_win_over_inclusions() {
+        let light = Light::new(Point::zero(), Colour::new(1.0, 1.0, 1.0)).with_name("fill");
+        let links = LightLinks::new().include("*", "*").exclude("fill", "floor");
+        assert!(!links.illuminates(&light, primitive(Some("floor")).as_ref()));
+        assert!(links.illuminates(&light, primitive(Some("wall")).as_ref()));
+    }
+}
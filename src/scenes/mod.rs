@@ -1,19 +1,85 @@
+pub mod accelerator;
+pub mod accumulation;
+pub mod animation;
+pub mod aovs;
+pub mod camera_path;
 pub mod canvas;
+pub mod denoise;
+pub mod exposure;
+pub mod farm;
+#[cfg(any(all(test, feature = "png"), feature = "test-utils"))]
+pub mod golden;
+pub mod heatmap;
+pub mod instances;
+pub mod integrator;
+pub mod kdtree;
+pub mod links;
+pub mod overlay;
+pub mod overrides;
+#[cfg(any(test, feature = "preview"))]
+pub mod preview;
 pub mod raygen;
+pub mod sequence;
+pub mod stats;
+pub mod streaming;
+pub mod tiles;
 pub mod view;
 pub mod world;
 
 // crate-level re-exports
+pub(crate) use accelerator::*;
+pub(crate) use accumulation::*;
+pub(crate) use animation::*;
+pub(crate) use aovs::*;
 pub(crate) use canvas::*;
+pub(crate) use denoise::*;
+pub(crate) use exposure::*;
+pub(crate) use heatmap::*;
+pub(crate) use integrator::*;
+pub(crate) use links::*;
+pub(crate) use overlay::*;
+pub(crate) use overrides::*;
 pub(crate) use raygen::*;
+pub(crate) use stats::RenderStats;
+pub(crate) use tiles::*;
 pub(crate) use view::*;
 pub(crate) use world::*;
 
 // public re-exports (through crate::prelude)
 pub(super) mod prelude {
+    pub use super::accelerator::{Accelerator, LinearScan};
+    pub use super::accumulation::AccumulationBuffer;
+    pub use super::animation::{
+        frame_times, render_sequence, Easing, Interpolate, Keyframe, LookAt, Track,
+    };
+    pub use super::aovs::AuxiliaryBuffers;
+    pub use super::camera_path::CameraPath;
     pub use super::canvas;
     pub use super::canvas::Canvas;
+    pub use super::denoise::{BilateralFilter, Denoiser, DenoiserInput, ExternalDenoiser};
+    pub use super::exposure::{ev100_scale, AutoExposure};
+    pub use super::farm::{RenderJob, WorkUnit};
+    #[cfg(any(all(test, feature = "png"), feature = "test-utils"))]
+    pub use super::golden::{
+        check_golden, compare_canvases, render_and_check_golden, GoldenError, ImageDiff,
+        UPDATE_GOLDEN_VAR,
+    };
+    pub use super::heatmap::{CostMetric, Heatmap};
+    pub use super::instances::InstanceTree;
+    pub use super::integrator::{Integrator, Outline, Toon};
+    pub use super::kdtree::KdTree;
+    pub use super::links::LightLinks;
+    pub use super::overlay::DebugOverlay;
+    pub use super::overrides::MaterialOverrides;
+    #[cfg(feature = "preview")]
+    pub use super::preview::MinifbWindow;
+    #[cfg(any(test, feature = "preview"))]
+    pub use super::preview::{argb_buffer, run_preview, PreviewCommand, PreviewWindow};
     pub use super::raygen::prelude::*;
+    pub use super::sequence::{FrameFormat, SequenceError, SequenceWriter};
+    pub use super::stats::RenderStats;
+    pub use super::streaming::RenderHandle;
+    pub use super::tiles::{Tile, TileEvent};
     pub use super::view::{Camera, Orientation};
-    pub use super::world::World;
+    pub use super::world::{World, WorldBuilder};
 }
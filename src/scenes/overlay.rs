@@ -0,0 +1,220 @@
+use crate::collections::{Colour, Point};
+use crate::objects::*;
+use crate::scenes::World;
+use crate::utils::EPSILON;
+
+// Lines drawn over a render to diagnose culling and placement problems: the
+// bounding boxes of shapes and the edges of triangles. A ray draws a line where
+// it passes within half the line's thickness (in world units) of it, as if the
+// line were a thin rod, and lines are drawn in front of everything, so hidden
+// ones show through the surfaces in front of them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugOverlay {
+    pub colour: Colour,
+    pub thickness: f64,
+    pub bounds: bool,
+    pub wireframe: bool,
+}
+
+impl DebugOverlay {
+    pub fn new(colour: Colour) -> DebugOverlay {
+        DebugOverlay {
+            colour,
+            thickness: 0.02,
+            bounds: false,
+            wireframe: false,
+        }
+    }
+
+    // the bounding box of every shape, nested or not; unbounded boxes (such as
+    // those of planes) are left out
+    pub fn with_bounds(mut self) -> DebugOverlay {
+        self.bounds = true;
+        self
+    }
+
+    pub fn with_wireframe(mut self) -> DebugOverlay {
+        self.wireframe = true;
+        self
+    }
+
+    pub fn with_thickness(mut self, thickness: f64) -> DebugOverlay {
+        self.thickness = thickness;
+        self
+    }
+
+    // the lines of the world in world space, gathered once for a render
+    pub(crate) fn lines(&self, world: &World) -> OverlayLines {
+        let mut segments = vec![];
+        for object in world.objects() {
+            self.gather(object, &mut vec![], &mut segments);
+        }
+        OverlayLines {
+            colour: self.colour,
+            radius: self.thickness / 2.0,
+            segments,
+        }
+    }
+
+    fn gather<'a>(
+        &self,
+        shape: &'a Shape,
+        group_transforms: &mut Vec<&'a Transform>,
+        segments: &mut Vec<[Point; 2]>,
+    ) {
+        // bounds are in the space of the enclosing group
+        if self.bounds {
+            let anchors = shape.bounds().bounding_box().anchors();
+            if !anchors.iter().any(Point::at_infinity) {
+                // anchors are ordered by x, then y, then z, so the ends of each
+                // edge differ in a single bit of their index
+                for (start, end) in (0..8)
+                    .flat_map(|i| [1, 2, 4].map(|bit| (i, i | bit)))
+                    .filter(|(start, end)| start != end)
+                {
+                    segments.push(
+                        [anchors[start], anchors[end]]
+                            .map(|point| transform_out_of_stack(point, group_transforms)),
+                    );
+                }
+            }
+        }
+        match shape {
+            Shape::Primitive(primitive) => {
+                if self.wireframe {
+                    for edge in primitive.local_edges() {
+                        segments.push(edge.map(|point| {
+                            let point = point.transform(primitive.frame_transformation());
+                            transform_out_of_stack(point, group_transforms)
+                        }));
+                    }
+                }
+            }
+            Shape::Group(group) => {
+                let replaced = group.enter(group_transforms);
+                for object in group.objects() {
+                    self.gather(object, group_transforms, segments);
+                }
+                group.leave(group_transforms, replaced);
+            }
+            Shape::Csg(csg) => {
+                self.gather(csg.lshape(), group_transforms, segments);
+                self.gather(csg.rshape(), group_transforms, segments);
+            }
+            Shape::Clipped(clipped) => {
+                self.gather(clipped.shape(), group_transforms, segments);
+                if let Some(cap) = clipped.cap() {
+                    self.gather(cap, group_transforms, segments);
+                }
+            }
+        }
+    }
+}
+
+// A debug overlay ready for rendering. Every ray is tested against every line,
+// so large meshes make for slow renders.
+#[derive(Clone, Debug)]
+pub(crate) struct OverlayLines {
+    colour: Colour,
+    radius: f64,
+    segments: Vec<[Point; 2]>,
+}
+
+impl OverlayLines {
+    // the overlay's colour, if the ray draws any of the lines
+    pub(crate) fn colour_on(&self, ray: &Ray) -> Option<Colour> {
+        self.segments
+            .iter()
+            .any(|&segment| OverlayLines::distance_to(ray, segment) <= self.radius)
+            .then_some(self.colour)
+    }
+
+    // closest approach of the ray (ahead of its origin) to the segment, as for
+    // two segments in Ericson's Real-Time Collision Detection with the ray's
+    // far end unclamped
+    fn distance_to(ray: &Ray, [start, end]: [Point; 2]) -> f64 {
+        let direction = ray.direction;
+        let edge = end - start;
+        let offset = ray.origin - start;
+        let a = direction.dot(direction);
+        let e = edge.dot(edge);
+        let f = edge.dot(offset);
+        let c = direction.dot(offset);
+        let along_ray = |t: f64| f64::max(0.0, (direction.dot(edge) * t - c) / a);
+
+        let (s, t) = if e <= EPSILON {
+            (along_ray(0.0), 0.0)
+        } else {
+            let b = direction.dot(edge);
+            let denominator = a * e - b * b;
+            let s = match denominator > 0.0 {
+                true => f64::max(0.0, (b * f - c * e) / denominator),
+                // parallel, so any point will do
+                false => 0.0,
+            };
+            let t = (b * s + f) / e;
+            if t < 0.0 {
+                (along_ray(0.0), 0.0)
+            } else if t > 1.0 {
+                (along_ray(1.0), 1.0)
+            } else {
+                (s, t)
+            }
+        };
+        (ray.at(s) - (start + edge * t)).magnitude()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::Vector;
+    use crate::utils::{approx_eq, BuildInto, Buildable};
+
+    #[test]
+    fn distance_from_ray_to_segment() {
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 2.0));
+        let across = [Point::new(-1.0, 0.5, 0.0), Point::new(1.0, 0.5, 0.0)];
+        approx_eq!(OverlayLines::distance_to(&ray, across), 0.5);
+        // the nearest point of the segment is an end
+        let aside = [Point::new(2.0, 0.0, 1.0), Point::new(3.0, 0.0, 1.0)];
+        approx_eq!(OverlayLines::distance_to(&ray, aside), 2.0);
+        // the nearest point of the ray is its origin
+        let behind = [Point::new(-1.0, 1.0, -7.0), Point::new(1.0, 1.0, -7.0)];
+        approx_eq!(OverlayLines::distance_to(&ray, behind), 5.0_f64.sqrt());
+        let parallel = [Point::new(0.0, 3.0, 0.0), Point::new(0.0, 3.0, 4.0)];
+        approx_eq!(OverlayLines::distance_to(&ray, parallel), 3.0);
+    }
+
+    #[test]
+    fn gather_bounds_and_wireframe() {
+        let triangle = Triangle::builder()
+            .set_vertices([
+                Point::new(0.0, 1.0, 0.0),
+                Point::new(-1.0, 0.0, 0.0),
+                Point::new(1.0, 0.0, 0.0),
+            ])
+            .build_into();
+        let group: Shape = Group::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(0.0, 0.0, 5.0)))
+            .add_object(triangle)
+            .build_into();
+        let world = World::new(vec![group, Plane::builder().build_into()], vec![]);
+        let colour = Colour::new(1.0, 0.0, 1.0);
+
+        let wireframe = DebugOverlay::new(colour).with_wireframe().lines(&world);
+        assert_eq!(wireframe.segments.len(), 3);
+        assert!(wireframe
+            .segments
+            .contains(&[Point::new(-1.0, 0.0, 5.0), Point::new(1.0, 0.0, 5.0)]));
+
+        // the group's box and the triangle's, but not the plane's
+        let bounds = DebugOverlay::new(colour).with_bounds().lines(&world);
+        assert_eq!(bounds.segments.len(), 24);
+
+        let ray = Ray::new(Point::new(0.0, 0.005, 0.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(wireframe.colour_on(&ray), Some(colour));
+        let ray = Ray::new(Point::new(0.0, 0.5, 0.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(wireframe.colour_on(&ray), None);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use crate::objects::{Material, PrimitiveShape, Shape};
+use crate::scenes::aovs::address;
+
+// Render-time replacement of materials, matched with glob patterns where `*`
+// stands for any run of characters. A pattern matches an object by its own
+// name, or by its path: the names of the groups (and CSG and clipped shapes)
+// around it and its own, joined by `/`. A pattern matching the path of an
+// enclosing group applies to everything inside it. Rules are tried in the
+// order they were added and the first match wins; an unnamed object only
+// matches by its own name if the pattern also matches the empty string.
+//
+// Paths are worked out for the objects of a scene by resolve, which a World
+// does when it is built and when its objects change; until then objects are
+// matched by their own names only.
+#[derive(Debug, Default)]
+pub struct MaterialOverrides {
+    rules: Vec<(String, Material)>,
+    // the rule matching each primitive of the scene, by address
+    resolved: Option<HashMap<usize, usize>>,
+}
+
+impl MaterialOverrides {
+    pub fn new() -> MaterialOverrides {
+        MaterialOverrides::default()
+    }
+
+    pub fn with_override(mut self, pattern: &str, material: Material) -> MaterialOverrides {
+        self.rules.push((pattern.to_string(), material));
+        self.resolved = None;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn material_for<'a>(&'a self, object: &'a dyn PrimitiveShape) -> &'a Material {
+        let rule = match &self.resolved {
+            Some(resolved) => resolved.get(&address(object)).copied(),
+            None => self.rule_for(object.name(), &[]),
+        };
+        rule.map_or(object.material(), |rule| &self.rules[rule].1)
+    }
+
+    // matches every primitive among the objects (and nested inside them) by
+    // its path
+    pub(crate) fn resolve(&mut self, objects: &[Shape]) {
+        fn visit<'a>(
+            shape: &'a Shape,
+            path: &mut Vec<&'a str>,
+            overrides: &MaterialOverrides,
+            resolved: &mut HashMap<usize, usize>,
+        ) {
+            let children: Vec<&Shape> = match shape {
+                Shape::Primitive(primitive) => {
+                    if let Some(rule) = overrides.rule_for(primitive.name(), path) {
+                        resolved.insert(address(primitive.as_ref()), rule);
+                    }
+                    return;
+                }
+                Shape::Group(group) => group.objects().iter().collect(),
+                Shape::Csg(csg) => vec![csg.lshape(), csg.rshape()],
+                Shape::Clipped(clipped) => [Some(clipped.shape()), clipped.cap()]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+            };
+            path.extend(shape.name());
+            for child in children {
+                visit(child, path, overrides, resolved);
+            }
+            if shape.name().is_some() {
+                path.pop();
+            }
+        }
+
+        let mut resolved = HashMap::new();
+        if !self.rules.is_empty() {
+            for object in objects {
+                visit(object, &mut vec![], self, &mut resolved);
+            }
+        }
+        self.resolved = Some(resolved);
+    }
+
+    // the first rule matching an object's name, its path through the named
+    // containers around it, or the path of any of those containers
+    fn rule_for(&self, name: Option<&str>, containers: &[&str]) -> Option<usize> {
+        let mut paths = vec![name.unwrap_or("").to_string()];
+        paths.extend((1..=containers.len()).map(|end| containers[..end].join("/")));
+        if let (Some(name), false) = (name, containers.is_empty()) {
+            paths.push(format!("{}/{}", containers.join("/"), name));
+        }
+        self.rules
+            .iter()
+            .position(|(pattern, _)| paths.iter().any(|path| glob_match(pattern, path)))
+    }
+}
+
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => match name.strip_prefix(prefix) {
+            Some(remainder) => (0..=remainder.len())
+                .filter(|&split| remainder.is_char_boundary(split))
+                .any(|split| glob_match(rest, &remainder[split..])),
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::{Colour, Point};
+    use crate::objects::{Group, Sphere};
+    use crate::utils::{BuildInto, Buildable, ConsumingBuilder};
+
+    #[test]
+    fn match_glob_patterns() {
+        assert!(glob_match("glass_*", "glass_vase"));
+        assert!(glob_match("*_wall", "left_wall"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("glass_*", "vase_glass"));
+        assert!(!glob_match("floor", "floor_tile"));
+    }
+
+    #[test]
+    fn first_matching_override_wins() {
+        let red = Material::builder()
+            .set_colour(Colour::new(1.0, 0.0, 0.0))
+            .build();
+        let blue = Material::builder()
+            .set_colour(Colour::new(0.0, 0.0, 1.0))
+            .build();
+        let overrides = MaterialOverrides::new()
+            .with_override("glass_*", red)
+            .with_override("*", blue);
+
+        let glass: Shape = Sphere::builder().set_name("glass_vase").build_into();
+        let unnamed: Shape = Sphere::builder().build_into();
+        let (Shape::Primitive(glass), Shape::Primitive(unnamed)) = (glass, unnamed) else {
+            panic!("spheres are primitive shapes");
+        };
+        assert_eq!(
+            overrides.material_for(glass.as_ref()),
+            &Material::builder()
+                .set_colour(Colour::new(1.0, 0.0, 0.0))
+                .build()
+        );
+        assert_eq!(
+            overrides.material_for(unnamed.as_ref()),
+            &Material::builder()
+                .set_colour(Colour::new(0.0, 0.0, 1.0))
+                .build()
+        );
+        assert_eq!(
+            MaterialOverrides::new().material_for(unnamed.as_ref()),
+            &Material::default()
+        );
+    }
+
+    #[test]
+    fn match_group_paths() {
+        let red = Material::builder()
+            .set_colour(Colour::new(1.0, 0.0, 0.0))
+            .build();
+        let blue = Material::builder()
+            .set_colour(Colour::new(0.0, 0.0, 1.0))
+            .build();
+        let scene: Vec<Shape> = vec![Group::builder()
+            .set_name("furniture")
+            .add_object(
+                Group::builder()
+                    .set_name("chair")
+                    .add_object(Sphere::builder().set_name("leg").build_into())
+                    .add_object(Sphere::builder().build_into())
+                    .build_into(),
+            )
+            .add_object(Sphere::builder().set_name("lamp").build_into())
+            .build_into()];
+        let mut overrides = MaterialOverrides::new()
+            .with_override("furniture/chair", red)
+            .with_override("*/lamp", blue);
+        overrides.resolve(&scene);
+
+        let primitive = |name: &str| match scene[0].find(name) {
+            Some(Shape::Primitive(primitive)) => primitive.as_ref(),
+            _ => panic!("{} is a primitive", name),
+        };
+        let Some(Shape::Group(chair)) = scene[0].find("chair") else {
+            panic!("chair is a group");
+        };
+        let Shape::Primitive(unnamed) = &chair.objects()[1] else {
+            panic!("spheres are primitive shapes");
+        };
+        let colour_of = |object| {
+            overrides
+                .material_for(object)
+                .pattern
+                .colour_at(Point::zero())
+        };
+        assert_eq!(colour_of(primitive("leg")), Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(colour_of(unnamed.as_ref()), Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(colour_of(primitive("lamp")), Colour::new(0.0, 0.0, 1.0));
+
+        // objects are still matched by their own names
+        let mut by_name = MaterialOverrides::new().with_override("leg", Material::preset());
+        by_name.resolve(&scene);
+        assert_eq!(by_name.material_for(primitive("leg")), &Material::preset());
+        assert_eq!(
+            by_name.material_for(primitive("lamp")),
+            &Material::default()
+        );
+    }
+}
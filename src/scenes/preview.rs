@@ -0,0 +1,233 @@
+#[cfg(feature = "preview")]
+use std::time::Duration;
+
+use crate::scenes::{Camera, Canvas, Height, RayGenerator, TileEvent, Width, World};
+use crate::utils::RenderError;
+
+// What the user asked for since the window was last polled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PreviewCommand {
+    Continue,
+    // stop rendering and close the preview
+    Cancel,
+    // start the render again from scratch, rebuilding the scene
+    Rerender,
+}
+
+// A window that shows a render as it progresses, such as MinifbWindow below
+// or one opened with winit. Implementations turn the canvas into whatever the
+// window displays (argb_buffer gives the 0RGB layout minifb expects) and map
+// key presses to commands, typically Escape to cancel and R to re-render.
+pub trait PreviewWindow {
+    fn show(&mut self, image: &Canvas);
+
+    // called between tiles, so must not block
+    fn poll(&mut self) -> PreviewCommand;
+
+    // called once a render is complete, blocking until the user re-renders or
+    // closes the window (answering Continue or Cancel)
+    fn wait(&mut self) -> PreviewCommand {
+        PreviewCommand::Continue
+    }
+}
+
+// Renders the scene in tiles, showing each tile in the window as it completes.
+// The scene is rebuilt for every render, so edits picked up by the closure
+// (for example from a file the user is changing) appear on re-render. Gives the
+// last complete image, or None if the user cancelled part way through.
+pub fn run_preview<R: RayGenerator>(
+    window: &mut impl PreviewWindow,
+    tile_size: usize,
+    mut scene: impl FnMut() -> (Camera<R>, World),
+) -> Result<Option<Canvas>, RenderError> {
+    loop {
+        let (camera, world) = scene();
+        let (hsize, vsize) = camera.ray_generator().canvas_size();
+        let mut progress = Canvas::new(Width(hsize), Height(vsize));
+        let mut command = PreviewCommand::Continue;
+        let image = camera.render_tiles_with(&world, tile_size, |event| {
+            if let TileEvent::Rendered(tile) = event {
+                let [x0, _] = tile.region().x_range();
+                let [y0, _] = tile.region().y_range();
+                progress
+                    .paint_canvas_replace(x0, y0, tile.pixels())
+                    .expect("tile lies within the canvas");
+                window.show(&progress);
+            }
+            command = window.poll();
+            command == PreviewCommand::Continue
+        })?;
+
+        match (image, command) {
+            (Some(image), _) => {
+                window.show(&image);
+                if window.wait() != PreviewCommand::Rerender {
+                    return Ok(Some(image));
+                }
+            }
+            (None, PreviewCommand::Rerender) => (),
+            (None, _) => return Ok(None),
+        }
+    }
+}
+
+// A minifb window the size of the render. Escape or closing the window
+// cancels, and R re-renders.
+#[cfg(feature = "preview")]
+pub struct MinifbWindow {
+    window: minifb::Window,
+}
+
+#[cfg(feature = "preview")]
+impl MinifbWindow {
+    // how long wait sleeps between checking for key presses
+    const WAIT_INTERVAL: Duration = Duration::from_millis(16);
+
+    pub fn new(title: &str, width: usize, height: usize) -> Result<MinifbWindow, minifb::Error> {
+        let mut window =
+            minifb::Window::new(title, width, height, minifb::WindowOptions::default())?;
+        // polled between tiles, which must not be held up waiting for a frame
+        window.set_target_fps(0);
+        Ok(MinifbWindow { window })
+    }
+
+    fn command(&self) -> PreviewCommand {
+        if !self.window.is_open() || self.window.is_key_down(minifb::Key::Escape) {
+            PreviewCommand::Cancel
+        } else if self
+            .window
+            .is_key_pressed(minifb::Key::R, minifb::KeyRepeat::No)
+        {
+            PreviewCommand::Rerender
+        } else {
+            PreviewCommand::Continue
+        }
+    }
+}
+
+#[cfg(feature = "preview")]
+impl PreviewWindow for MinifbWindow {
+    fn show(&mut self, image: &Canvas) {
+        // a window that fails to draw is simply left as it was
+        let _ = self
+            .window
+            .update_with_buffer(&argb_buffer(image), image.width(), image.height());
+    }
+
+    fn poll(&mut self) -> PreviewCommand {
+        self.window.update();
+        self.command()
+    }
+
+    fn wait(&mut self) -> PreviewCommand {
+        loop {
+            self.window.update();
+            match self.command() {
+                PreviewCommand::Continue => std::thread::sleep(MinifbWindow::WAIT_INTERVAL),
+                command => return command,
+            }
+        }
+    }
+}
+
+// pixels row by row as 0x00RRGGBB
+pub fn argb_buffer(image: &Canvas) -> Vec<u32> {
+    image
+        .pixels()
+        .map(|pixel| {
+            let [red, green, blue] = pixel.channels(255);
+            ((red << 16) | (green << 8) | blue) as u32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::{Angle, Colour, Point, Vector};
+    use crate::objects::*;
+    use crate::scenes::{Native, Orientation};
+    use crate::utils::{BuildInto, Buildable};
+
+    // replays a script of commands, recording what it was shown
+    struct ScriptedWindow {
+        commands: Vec<PreviewCommand>,
+        shown: usize,
+    }
+
+    impl PreviewWindow for ScriptedWindow {
+        fn show(&mut self, _image: &Canvas) {
+            self.shown += 1;
+        }
+
+        fn poll(&mut self) -> PreviewCommand {
+            match self.commands.is_empty() {
+                true => PreviewCommand::Continue,
+                false => self.commands.remove(0),
+            }
+        }
+
+        fn wait(&mut self) -> PreviewCommand {
+            self.poll()
+        }
+    }
+
+    fn scene() -> (Camera<Native>, World) {
+        let sphere = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let camera = Camera::new(Native::new(
+            4,
+            4,
+            Angle::from_degrees(60.0),
+            Orientation::new(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ),
+        ));
+        (camera, World::new(vec![sphere], vec![light]))
+    }
+
+    #[test]
+    fn preview_shows_tiles_and_rerenders() {
+        use PreviewCommand::*;
+        // 4 tiles of 2x2, each reported as scheduled, rendered and merged
+        let mut window = ScriptedWindow {
+            commands: [vec![Continue; 5], vec![Rerender]].concat(),
+            shown: 0,
+        };
+        let mut renders = 0;
+        let image = run_preview(&mut window, 2, || {
+            renders += 1;
+            scene()
+        })
+        .unwrap()
+        .unwrap();
+
+        let (camera, world) = scene();
+        assert_eq!(image, camera.render(&world).unwrap());
+        assert_eq!(renders, 2);
+        // one tile shown before re-rendering, then four tiles and the result
+        assert_eq!(window.shown, 6);
+    }
+
+    #[test]
+    fn cancel_preview() {
+        let mut window = ScriptedWindow {
+            commands: vec![PreviewCommand::Continue, PreviewCommand::Cancel],
+            shown: 0,
+        };
+        assert!(matches!(run_preview(&mut window, 2, scene), Ok(None)));
+    }
+
+    #[test]
+    fn convert_canvas_to_argb() {
+        let mut image = Canvas::new(Width(2), Height(1));
+        image
+            .paint_colour_replace(1, 0, Colour::new(1.0, 0.5, 0.0))
+            .unwrap();
+        assert_eq!(argb_buffer(&image), vec![0, 0x00ff8000]);
+    }
+}
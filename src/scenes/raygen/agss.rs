@@ -1,10 +1,11 @@
 use super::Native;
 use crate::collections::{Angle, Point};
-use crate::objects::{Ray, Transform, Transformable};
+use crate::objects::Transform;
 use crate::scenes::raygen;
 use crate::scenes::raygen::{RayGenerator, TaggedPixel, TaggedRay};
 use crate::scenes::Orientation;
 use crate::utils::floats::EPSILON;
+use crate::utils::Rng;
 
 pub struct Agss {
     render_scale: f64,
@@ -57,6 +58,17 @@ impl Agss {
     pub fn render_scale(&self) -> f64 {
         self.render_scale
     }
+
+    // sends each ray through a random point in its subpixel rather than the
+    // centre, drawn from the camera's random stream
+    pub fn with_jitter(mut self) -> Agss {
+        self.native = self.native.with_jitter();
+        self
+    }
+
+    pub fn jittered(&self) -> bool {
+        self.native.jittered()
+    }
 }
 
 impl IntoIterator for Agss {
@@ -68,7 +80,7 @@ impl IntoIterator for Agss {
         let hsize = f64::ceil(self.hsize() as f64 * render_scale) as usize;
         let vsize = f64::ceil(self.vsize() as f64 * render_scale) as usize;
         let pixel_iterator = Box::new(
-            (0..hsize).flat_map(move |pos_x| std::iter::repeat(pos_x).take(vsize).zip(0..vsize)),
+            (0..hsize).flat_map(move |pos_x| std::iter::repeat_n(pos_x, vsize).zip(0..vsize)),
         );
 
         AgssIterator {
@@ -83,6 +95,10 @@ impl RayGenerator for Agss {
     fn canvas_size(&self) -> (usize, usize) {
         (self.hsize(), self.vsize())
     }
+
+    fn set_rng(&mut self, rng: Rng) {
+        self.native.set_rng(rng);
+    }
 }
 
 pub struct AgssIterator {
@@ -99,9 +115,10 @@ impl Iterator for AgssIterator {
             Some((pos_x, pos_y)) => {
                 // compute ray target coordinate offset from origin (native res)
                 let subpixel_size = self.native.pixel_size() / self.render_scale;
-                let (offset_x, offset_y) = raygen::pixel_offset_from_centre_target(
+                let (offset_x, offset_y) = raygen::pixel_offset_from_target(
                     pos_x,
                     pos_y,
+                    self.native.position_in_pixel([pos_x, pos_y]),
                     subpixel_size,
                     self.native.half_width(),
                     self.native.half_height(),
@@ -109,7 +126,7 @@ impl Iterator for AgssIterator {
                 let ray = raygen::generate_normalised_ray(
                     Point::zero(),
                     Point::new(offset_x, offset_y, -1.0),
-                    &self.native.frame_transformation().invert(),
+                    self.native.frame_transformation().inverse(),
                 );
 
                 // compute subpixel-pixel boundary intersections
@@ -157,7 +174,16 @@ impl Iterator for AgssIterator {
                     }
                 }
 
-                let tagged_ray = TaggedRay::new(ray, tagged_pixels);
+                // differentials span a subpixel, as the samples already cover
+                // the rest of the pixel
+                let differentials = raygen::generate_ray_differentials(
+                    &ray,
+                    (offset_x, offset_y),
+                    subpixel_size,
+                    self.native.frame_transformation().inverse(),
+                );
+                let tagged_ray =
+                    TaggedRay::new(ray, tagged_pixels).with_differentials(differentials);
                 Some(tagged_ray)
             }
             None => None,
@@ -168,6 +194,7 @@ impl Iterator for AgssIterator {
 #[cfg(test)]
 mod tests {
     use crate::collections::Vector;
+    use crate::objects::Ray;
     use crate::utils::approx_eq;
 
     use super::*;
@@ -181,7 +208,7 @@ mod tests {
             Orientation::default(),
             3.0,
         );
-        let tagged_ray = canvas.into_iter().skip(21 * 10 + 10).next().unwrap();
+        let tagged_ray = canvas.into_iter().nth(21 * 10 + 10).unwrap();
         let casted_ray = tagged_ray.ray();
         let resulting_ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
         approx_eq!(casted_ray.origin.x, resulting_ray.origin.x);
@@ -206,7 +233,7 @@ mod tests {
             Orientation::default(),
             1.5,
         );
-        let tagged_ray = canvas.into_iter().skip(21 * 10 + 10).next().unwrap();
+        let tagged_ray = canvas.into_iter().nth(21 * 10 + 10).unwrap();
         let casted_ray = tagged_ray.ray();
         let resulting_ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
         approx_eq!(casted_ray.origin.x, resulting_ray.origin.x);
@@ -1,13 +1,14 @@
 pub mod agss;
 pub mod native;
+#[allow(clippy::module_inception)]
 pub mod raygen;
 
 // crate-level re-exports
-pub(crate) use agss::*;
 pub(crate) use native::*;
 pub(crate) use raygen::*;
 
 pub(super) mod prelude {
     pub use super::agss::Agss;
     pub use super::native::Native;
+    pub use super::raygen::Region;
 }
@@ -1,8 +1,9 @@
 use crate::collections::{Angle, Point};
-use crate::objects::{Ray, Transform, Transformable};
+use crate::objects::Transform;
 use crate::scenes::raygen;
 use crate::scenes::raygen::{RayGenerator, TaggedPixel, TaggedRay};
 use crate::scenes::Orientation;
+use crate::utils::Rng;
 
 pub struct Native {
     hsize: usize,
@@ -12,13 +13,15 @@ pub struct Native {
     half_height: f64,
     half_width: f64,
     pixel_size: f64,
+    jittered: bool,
+    pixel_seed: u64,
 }
 
 impl Native {
     pub fn new(
         hsize: usize,
         vsize: usize,
-        mut fov: Angle,
+        fov: Angle,
         Orientation(frame_transformation): Orientation,
     ) -> Native {
         let half_view = (fov.radians() / 2.0).tan();
@@ -47,9 +50,33 @@ impl Native {
             half_height,
             half_width,
             pixel_size,
+            jittered: false,
+            pixel_seed: 0,
         }
     }
 
+    // sends each ray through a random point in its pixel rather than the
+    // centre, drawn from the camera's random stream
+    pub fn with_jitter(mut self) -> Native {
+        self.jittered = true;
+        self
+    }
+
+    pub fn jittered(&self) -> bool {
+        self.jittered
+    }
+
+    // where the ray for a pixel passes through it, from [0, 0] at one corner
+    // to [1, 1] at the other; each pixel has its own stream, so this does not
+    // depend on the order in which pixels are visited
+    pub(crate) fn position_in_pixel(&self, index: [usize; 2]) -> [f64; 2] {
+        if !self.jittered {
+            return [0.5, 0.5];
+        }
+        let mut rng = Rng::for_pixel(self.pixel_seed, index, 0);
+        [rng.next_f64(), rng.next_f64()]
+    }
+
     pub fn hsize(&self) -> usize {
         self.hsize
     }
@@ -87,7 +114,7 @@ impl IntoIterator for Native {
         let hsize = self.hsize();
         let vsize = self.vsize();
         let pixel_iterator = Box::new(
-            (0..hsize).flat_map(move |pos_x| std::iter::repeat(pos_x).take(vsize).zip(0..vsize)),
+            (0..hsize).flat_map(move |pos_x| std::iter::repeat_n(pos_x, vsize).zip(0..vsize)),
         );
 
         NativeIterator {
@@ -101,6 +128,10 @@ impl RayGenerator for Native {
     fn canvas_size(&self) -> (usize, usize) {
         (self.hsize, self.vsize)
     }
+
+    fn set_rng(&mut self, mut rng: Rng) {
+        self.pixel_seed = rng.next_u64();
+    }
 }
 
 pub struct NativeIterator {
@@ -114,9 +145,10 @@ impl Iterator for NativeIterator {
     fn next(&mut self) -> Option<Self::Item> {
         match self.pixel_iterator.next() {
             Some((pos_x, pos_y)) => {
-                let (offset_x, offset_y) = raygen::pixel_offset_from_centre_target(
+                let (offset_x, offset_y) = raygen::pixel_offset_from_target(
                     pos_x,
                     pos_y,
+                    self.native.position_in_pixel([pos_x, pos_y]),
                     self.native.pixel_size(),
                     self.native.half_width(),
                     self.native.half_height(),
@@ -124,14 +156,22 @@ impl Iterator for NativeIterator {
                 let ray = raygen::generate_normalised_ray(
                     Point::zero(),
                     Point::new(offset_x, offset_y, -1.0),
-                    &self.native.frame_transformation().invert(),
+                    self.native.frame_transformation().inverse(),
+                );
+
+                let differentials = raygen::generate_ray_differentials(
+                    &ray,
+                    (offset_x, offset_y),
+                    self.native.pixel_size(),
+                    self.native.frame_transformation().inverse(),
                 );
 
                 // tag pixel
                 let tagged_pixel = TaggedPixel::new([pos_x, pos_y], 1.0);
 
                 // tag ray
-                let tagged_ray = TaggedRay::new(ray, vec![tagged_pixel]);
+                let tagged_ray =
+                    TaggedRay::new(ray, vec![tagged_pixel]).with_differentials(differentials);
                 Some(tagged_ray)
             }
             None => None,
@@ -176,7 +216,7 @@ mod tests {
             Angle::from_radians(FRAC_PI_2),
             Orientation::default(),
         );
-        let tagged_ray = native.into_iter().skip(101 * 100 + 50).next().unwrap(); // ray for pixel [100, 50]
+        let tagged_ray = native.into_iter().nth(101 * 100 + 50).unwrap(); // ray for pixel [100, 50]
         let casted_ray = tagged_ray.ray();
         let resulting_ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
         approx_eq!(casted_ray.origin.x, resulting_ray.origin.x);
@@ -222,12 +262,7 @@ mod tests {
             Angle::from_radians(FRAC_PI_2),
             Orientation::default().transform(&transform),
         );
-        let casted_ray = native
-            .into_iter()
-            .skip(101 * 100 + 50)
-            .next()
-            .unwrap()
-            .ray();
+        let casted_ray = native.into_iter().nth(101 * 100 + 50).unwrap().ray();
         let resulting_ray = Ray::new(
             Point::new(0.0, 2.0, -5.0),
             Vector::new(2.0_f64.sqrt() / 2.0, 0.0, -2.0_f64.sqrt() / 2.0),
@@ -239,4 +274,33 @@ mod tests {
         approx_eq!(casted_ray.direction.y, resulting_ray.direction.y);
         approx_eq!(casted_ray.direction.z, resulting_ray.direction.z);
     }
+
+    #[test]
+    fn jittered_rays_stay_in_their_pixels() {
+        let jittered = |seed: u64| {
+            let mut native =
+                Native::new(4, 4, Angle::from_radians(FRAC_PI_2), Orientation::default())
+                    .with_jitter();
+            native.set_rng(Rng::new(seed));
+            native
+                .into_iter()
+                .map(|tagged_ray| tagged_ray.ray().direction)
+                .collect::<Vec<Vector>>()
+        };
+        let centred: Vec<Vector> =
+            Native::new(4, 4, Angle::from_radians(FRAC_PI_2), Orientation::default())
+                .into_iter()
+                .map(|tagged_ray| tagged_ray.ray().direction)
+                .collect();
+
+        assert_eq!(jittered(1), jittered(1));
+        assert_ne!(jittered(1), jittered(2));
+        // the view is 2 wide at unit distance, so each pixel is half a unit
+        for (jittered, centred) in jittered(1).into_iter().zip(centred) {
+            let [jittered, centred] = [jittered, centred]
+                .map(|direction| (direction.x / -direction.z, direction.y / -direction.z));
+            assert!((jittered.0 - centred.0).abs() <= 0.25);
+            assert!((jittered.1 - centred.1).abs() <= 0.25);
+        }
+    }
 }
@@ -1,5 +1,6 @@
 use crate::collections::Point;
-use crate::objects::{Ray, Transform, Transformable};
+use crate::objects::{Ray, RayDifferentials, Transform, Transformable};
+use crate::utils::Rng;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct TaggedPixel {
@@ -28,17 +29,32 @@ impl TaggedPixel {
 pub struct TaggedRay {
     pub ray: Ray,
     pub pixels: Vec<TaggedPixel>,
+    // rays through the neighbouring pixels, for filtering patterns
+    pub differentials: Option<RayDifferentials>,
 }
 
 impl TaggedRay {
     pub fn new(ray: Ray, pixels: Vec<TaggedPixel>) -> TaggedRay {
-        TaggedRay { ray, pixels }
+        TaggedRay {
+            ray,
+            pixels,
+            differentials: None,
+        }
+    }
+
+    pub fn with_differentials(mut self, differentials: RayDifferentials) -> TaggedRay {
+        self.differentials = Some(differentials);
+        self
     }
 
     pub fn ray(&self) -> Ray {
         self.ray
     }
 
+    pub fn differentials(&self) -> Option<RayDifferentials> {
+        self.differentials
+    }
+
     pub fn pixels(&self) -> &Vec<TaggedPixel> {
         &self.pixels
     }
@@ -46,6 +62,41 @@ impl TaggedRay {
 
 pub trait RayGenerator: IntoIterator<Item = TaggedRay> {
     fn canvas_size(&self) -> (usize, usize);
+
+    // hands over the camera's random stream, from which generators that place
+    // rays randomly draw; others have no use for it
+    fn set_rng(&mut self, _rng: Rng) {}
+}
+
+// Rectangular window of canvas pixels, spanning columns [x0, x1) and rows
+// [y0, y1). Pixel indices are always those of the full canvas, so a region
+// render lands at the same place as it would in a full render.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    x_range: [usize; 2],
+    y_range: [usize; 2],
+}
+
+impl Region {
+    pub fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> Region {
+        Region {
+            x_range: [usize::min(x0, x1), usize::max(x0, x1)],
+            y_range: [usize::min(y0, y1), usize::max(y0, y1)],
+        }
+    }
+
+    pub fn x_range(&self) -> [usize; 2] {
+        self.x_range
+    }
+
+    pub fn y_range(&self) -> [usize; 2] {
+        self.y_range
+    }
+
+    pub fn contains(&self, [pos_x, pos_y]: [usize; 2]) -> bool {
+        (self.x_range[0]..self.x_range[1]).contains(&pos_x)
+            && (self.y_range[0]..self.y_range[1]).contains(&pos_y)
+    }
 }
 
 pub fn pixel_offset_from_centre_target(
@@ -54,10 +105,30 @@ pub fn pixel_offset_from_centre_target(
     pixel_size: f64,
     half_width: f64,
     half_height: f64,
+) -> (f64, f64) {
+    pixel_offset_from_target(
+        pixel_pos_x,
+        pixel_pos_y,
+        [0.5, 0.5],
+        pixel_size,
+        half_width,
+        half_height,
+    )
+}
+
+// as pixel_offset_from_centre_target, but through the given position within
+// the pixel, from [0, 0] at one corner to [1, 1] at the other
+pub fn pixel_offset_from_target(
+    pixel_pos_x: usize,
+    pixel_pos_y: usize,
+    [within_x, within_y]: [f64; 2],
+    pixel_size: f64,
+    half_width: f64,
+    half_height: f64,
 ) -> (f64, f64) {
     (
-        half_width - ((pixel_pos_x as f64 + 0.5) * pixel_size),
-        half_height - ((pixel_pos_y as f64 + 0.5) * pixel_size),
+        half_width - ((pixel_pos_x as f64 + within_x) * pixel_size),
+        half_height - ((pixel_pos_y as f64 + within_y) * pixel_size),
     )
 }
 
@@ -72,8 +143,28 @@ pub fn generate_normalised_ray(
 ) -> Ray {
     let transformed_ray_origin = ray_origin.transform(frame_transformation);
     let transformed_ray_target = ray_target.transform(frame_transformation);
-    let ray_direction = (transformed_ray_target - transformed_ray_origin).normalise();
-    Ray::new(transformed_ray_origin, ray_direction)
+    Ray::normalised(
+        transformed_ray_origin,
+        transformed_ray_target - transformed_ray_origin,
+    )
+}
+
+// differentials for a ray from generate_normalised_ray, from the rays through
+// targets one pixel further right and further down
+pub fn generate_ray_differentials(
+    ray: &Ray,
+    (offset_x, offset_y): (f64, f64),
+    pixel_size: f64,
+    frame_transformation: &Transform,
+) -> RayDifferentials {
+    let ray_to = |offset_x, offset_y| {
+        let target = Point::new(offset_x, offset_y, -1.0).transform(frame_transformation);
+        Ray::normalised(ray.origin, target - ray.origin)
+    };
+    RayDifferentials::new(
+        ray_to(offset_x - pixel_size, offset_y),
+        ray_to(offset_x, offset_y - pixel_size),
+    )
 }
 
 pub fn section_pixel(
@@ -97,7 +188,7 @@ pub fn section_pixel(
         old_blend_weight * blend_weight_1_ratio,
     );
 
-    let mut tagged_pixel_index_2 = tagged_pixel_index_1.clone();
+    let mut tagged_pixel_index_2 = tagged_pixel_index_1;
     tagged_pixel_index_2[axis_index] += 1;
     let blend_weight_2_ratio = (coordinate_1 - boundary) / old_length;
     let tagged_pixel_2 = TaggedPixel::new(
@@ -149,6 +240,18 @@ mod tests {
         approx_eq!(pixel_offset.1, -0.095);
     }
 
+    #[test]
+    fn region_contains_pixels() {
+        let region = Region::new(4, 3, 1, 1);
+        assert_eq!(region.x_range(), [1, 4]);
+        assert_eq!(region.y_range(), [1, 3]);
+        assert!(region.contains([1, 1]));
+        assert!(region.contains([3, 2]));
+        assert!(!region.contains([4, 2]));
+        assert!(!region.contains([3, 3]));
+        assert!(!region.contains([0, 1]));
+    }
+
     #[test]
     fn section_pixels() {
         let tagged_pixel = TaggedPixel::new([0, 1], 0.5);
@@ -0,0 +1,264 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::scenes::Canvas;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FrameFormat {
+    #[cfg(feature = "png")]
+    #[cfg_attr(feature = "png", default)]
+    Png,
+    #[cfg_attr(not(feature = "png"), default)]
+    Ppm,
+    Hdr,
+}
+
+impl FrameFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "png")]
+            FrameFormat::Png => "png",
+            FrameFormat::Ppm => "ppm",
+            FrameFormat::Hdr => "hdr",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SequenceError {
+    // overwriting is disabled and the file is already there
+    Exists(PathBuf),
+    Io(io::Error),
+}
+
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SequenceError::Exists(path) => write!(f, "{} already exists", path.display()),
+            SequenceError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SequenceError {}
+
+impl From<io::Error> for SequenceError {
+    fn from(error: io::Error) -> SequenceError {
+        SequenceError::Io(error)
+    }
+}
+
+// Writes the frames of an animation as numbered files (out_0001.png, ...) in
+// one directory, plus a manifest describing them, in the layout ffmpeg's image
+// sequence input expects:
+//     ffmpeg -framerate 24 -start_number 1 -i out_%04d.png out.mp4
+// Frame numbers count from the start number, and are zero-padded to the
+// padding width (growing past it if needed). Existing files are overwritten
+// unless overwriting is turned off.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceWriter {
+    directory: PathBuf,
+    prefix: String,
+    padding: usize,
+    start_number: usize,
+    format: FrameFormat,
+    overwrite: bool,
+    frame_rate: f64,
+    settings: Vec<(String, String)>,
+    frames_written: usize,
+    frame_size: Option<[usize; 2]>,
+}
+
+impl SequenceWriter {
+    pub fn new(directory: impl AsRef<Path>, prefix: &str) -> SequenceWriter {
+        SequenceWriter {
+            directory: directory.as_ref().to_path_buf(),
+            prefix: prefix.to_string(),
+            padding: 4,
+            start_number: 1,
+            format: FrameFormat::default(),
+            overwrite: true,
+            frame_rate: 24.0,
+            settings: vec![],
+            frames_written: 0,
+            frame_size: None,
+        }
+    }
+
+    pub fn with_padding(mut self, padding: usize) -> SequenceWriter {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_start_number(mut self, start_number: usize) -> SequenceWriter {
+        self.start_number = start_number;
+        self
+    }
+
+    pub fn with_format(mut self, format: FrameFormat) -> SequenceWriter {
+        self.format = format;
+        self
+    }
+
+    pub fn with_overwrite(mut self, overwrite: bool) -> SequenceWriter {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn with_frame_rate(mut self, frame_rate: f64) -> SequenceWriter {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    // extra render settings recorded in the manifest, such as the seed or
+    // integrator used
+    pub fn with_setting(mut self, key: &str, value: impl ToString) -> SequenceWriter {
+        self.settings.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn frames_written(&self) -> usize {
+        self.frames_written
+    }
+
+    // path of the frame with the given index, counting from 0
+    pub fn frame_path(&self, frame: usize) -> PathBuf {
+        self.directory.join(format!(
+            "{}_{:0width$}.{}",
+            self.prefix,
+            self.start_number + frame,
+            self.format.extension(),
+            width = self.padding
+        ))
+    }
+
+    // printf-style pattern matching every frame, as passed to ffmpeg with -i
+    pub fn frame_pattern(&self) -> String {
+        format!(
+            "{}_%0{}d.{}",
+            self.prefix,
+            self.padding,
+            self.format.extension()
+        )
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        self.directory.join(format!("{}_manifest.txt", self.prefix))
+    }
+
+    pub fn write_frame(&mut self, frame: usize, canvas: &Canvas) -> Result<PathBuf, SequenceError> {
+        let path = self.frame_path(frame);
+        let mut writer = BufWriter::new(self.create(&path)?);
+        match self.format {
+            #[cfg(feature = "png")]
+            FrameFormat::Png => canvas.write_png(&mut writer)?,
+            FrameFormat::Ppm => canvas.write_ppm(&mut writer)?,
+            FrameFormat::Hdr => canvas.write_hdr(&mut writer)?,
+        }
+        self.frames_written = self.frames_written.max(frame + 1);
+        self.frame_size = Some([canvas.width(), canvas.height()]);
+        Ok(path)
+    }
+
+    // writes the frames in order from index 0, then the manifest
+    pub fn write_sequence(&mut self, frames: &[Canvas]) -> Result<Vec<PathBuf>, SequenceError> {
+        let paths = frames
+            .iter()
+            .enumerate()
+            .map(|(frame, canvas)| self.write_frame(frame, canvas))
+            .collect::<Result<Vec<PathBuf>, SequenceError>>()?;
+        self.write_manifest()?;
+        Ok(paths)
+    }
+
+    // key=value lines describing the frames written so far and the settings
+    pub fn write_manifest(&self) -> Result<PathBuf, SequenceError> {
+        let path = self.manifest_path();
+        let mut writer = BufWriter::new(self.create(&path)?);
+        writeln!(writer, "pattern={}", self.frame_pattern())?;
+        writeln!(writer, "start_number={}", self.start_number)?;
+        writeln!(writer, "frame_count={}", self.frames_written)?;
+        writeln!(writer, "frame_rate={}", self.frame_rate)?;
+        if let Some([width, height]) = self.frame_size {
+            writeln!(writer, "width={}", width)?;
+            writeln!(writer, "height={}", height)?;
+        }
+        for (key, value) in &self.settings {
+            writeln!(writer, "{}={}", key, value)?;
+        }
+        writer.flush()?;
+        Ok(path)
+    }
+
+    fn create(&self, path: &Path) -> Result<File, SequenceError> {
+        fs::create_dir_all(&self.directory)?;
+        let mut options = OpenOptions::new();
+        match self.overwrite {
+            true => options.write(true).create(true).truncate(true),
+            false => options.write(true).create_new(true),
+        };
+        options.open(path).map_err(|error| match error.kind() {
+            io::ErrorKind::AlreadyExists => SequenceError::Exists(path.to_path_buf()),
+            _ => SequenceError::Io(error),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::Colour;
+    use crate::scenes::{Height, Width};
+
+    #[test]
+    fn name_frames() {
+        let writer = SequenceWriter::new("renders", "out").with_format(FrameFormat::Ppm);
+        assert_eq!(writer.frame_path(0), Path::new("renders/out_0001.ppm"));
+        assert_eq!(writer.frame_pattern(), "out_%04d.ppm");
+        #[cfg(feature = "png")]
+        assert_eq!(FrameFormat::default(), FrameFormat::Png);
+
+        let writer = SequenceWriter::new("renders", "shot")
+            .with_padding(2)
+            .with_start_number(0)
+            .with_format(FrameFormat::Hdr);
+        assert_eq!(writer.frame_path(7), Path::new("renders/shot_07.hdr"));
+        assert_eq!(writer.frame_path(123), Path::new("renders/shot_123.hdr"));
+        assert_eq!(writer.frame_pattern(), "shot_%02d.hdr");
+    }
+
+    #[test]
+    fn write_sequence_with_manifest() {
+        let directory = "test_sequence_output";
+        let mut frames = vec![Canvas::new(Width(2), Height(1)); 3];
+        frames[1].fill(Colour::new(1.0, 1.0, 1.0));
+        let mut writer = SequenceWriter::new(directory, "out")
+            .with_format(FrameFormat::Ppm)
+            .with_frame_rate(12.0)
+            .with_setting("seed", 7);
+        let paths = writer.write_sequence(&frames).unwrap();
+
+        assert_eq!(paths.len(), 3);
+        let reloaded = Canvas::open(paths[1].to_str().unwrap()).unwrap();
+        assert_eq!(reloaded, frames[1]);
+        let manifest = fs::read_to_string(writer.manifest_path()).unwrap();
+        assert_eq!(
+            manifest,
+            "pattern=out_%04d.ppm\nstart_number=1\nframe_count=3\nframe_rate=12\nwidth=2\nheight=1\nseed=7\n"
+        );
+
+        let mut protected = SequenceWriter::new(directory, "out")
+            .with_format(FrameFormat::Ppm)
+            .with_overwrite(false);
+        assert!(matches!(
+            protected.write_frame(0, &frames[0]),
+            Err(SequenceError::Exists(path)) if path == paths[0]
+        ));
+        assert!(protected.write_frame(3, &frames[0]).is_ok());
+
+        // cleanup
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
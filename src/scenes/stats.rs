@@ -0,0 +1,307 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::ops::AddAssign;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+// Wall-clock time spent in each phase of a render. Shadow, primary and
+// secondary ray times cover intersection testing only; everything else done
+// while tracing (lighting, patterns, recursion bookkeeping) counts as shading.
+// Intersection tests are those of shapes' checked bounds and of primitives
+// against single rays. March limits are rays through distance-field shapes
+// that ran out of steps before finding a surface. Accelerator build covers building and refitting the
+// world's accelerator and dividing objects into trees of groups. Work done on
+// other threads is added in, so phase times may add up to more than the total.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    pub primary_rays: Duration,
+    pub secondary_rays: Duration,
+    pub shadow_rays: Duration,
+    pub shading: Duration,
+    pub canvas_write: Duration,
+    pub accelerator_build: Duration,
+    pub total: Duration,
+    pub primary_ray_count: u64,
+    pub secondary_ray_count: u64,
+    pub shadow_ray_count: u64,
+    pub intersection_test_count: u64,
+    pub march_limit_count: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Phase {
+    PrimaryRays,
+    SecondaryRays,
+    ShadowRays,
+    CanvasWrite,
+    AcceleratorBuild,
+}
+
+// Where threads working for a collection begun elsewhere leave their stats,
+// to be merged in when that collection ends.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StatsSink(Arc<Mutex<RenderStats>>);
+
+impl StatsSink {
+    fn add(&self, stats: &RenderStats) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) += *stats;
+    }
+
+    fn take(&self) -> RenderStats {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+#[derive(Debug)]
+struct Collection {
+    stats: RenderStats,
+    sink: StatsSink,
+}
+
+thread_local! {
+    // only set while a render is being timed
+    static COLLECTOR: RefCell<Option<Collection>> = const { RefCell::new(None) };
+    // whether COLLECTOR is set, so that untimed renders pay only for reading
+    // a flag on the hot paths rather than borrowing the collector
+    static COLLECTING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn collecting() -> bool {
+    COLLECTING.with(Cell::get)
+}
+
+// runs f, adding the time taken to the given phase if stats are being collected
+pub(crate) fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    if !collecting() {
+        return f();
+    }
+
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    COLLECTOR.with(|collector| {
+        if let Some(Collection { stats, .. }) = collector.borrow_mut().as_mut() {
+            match phase {
+                Phase::PrimaryRays => {
+                    stats.primary_rays += elapsed;
+                    stats.primary_ray_count += 1;
+                }
+                Phase::SecondaryRays => {
+                    stats.secondary_rays += elapsed;
+                    stats.secondary_ray_count += 1;
+                }
+                Phase::ShadowRays => {
+                    stats.shadow_rays += elapsed;
+                    stats.shadow_ray_count += 1;
+                }
+                Phase::CanvasWrite => stats.canvas_write += elapsed,
+                Phase::AcceleratorBuild => stats.accelerator_build += elapsed,
+            }
+        }
+    });
+    result
+}
+
+pub(crate) fn count_intersection_test() {
+    if !collecting() {
+        return;
+    }
+    COLLECTOR.with(|collector| {
+        if let Some(collection) = collector.borrow_mut().as_mut() {
+            collection.stats.intersection_test_count += 1;
+        }
+    });
+}
+
+pub(crate) fn count_march_limit() {
+    if !collecting() {
+        return;
+    }
+    COLLECTOR.with(|collector| {
+        if let Some(collection) = collector.borrow_mut().as_mut() {
+            collection.stats.march_limit_count += 1;
+        }
+    });
+}
+
+// runs f with stats collection enabled on the current thread; when already
+// collecting, what f does is also added to the enclosing stats, as is what
+// other threads given this collection's sink did before it ends
+pub(crate) fn collect<T>(f: impl FnOnce() -> T) -> (T, RenderStats) {
+    let collection = Collection {
+        stats: RenderStats::default(),
+        sink: StatsSink::default(),
+    };
+    let previous = COLLECTOR.with(|collector| collector.replace(Some(collection)));
+    let was_collecting = COLLECTING.with(|collecting| collecting.replace(true));
+    let start = Instant::now();
+    let result = f();
+    let total = start.elapsed();
+    COLLECTING.with(|collecting| collecting.set(was_collecting));
+    let mut stats = COLLECTOR
+        .with(|collector| collector.replace(previous))
+        .map(|collection| {
+            let mut stats = collection.stats;
+            stats += collection.sink.take();
+            stats
+        })
+        .unwrap_or_default();
+    COLLECTOR.with(|collector| {
+        if let Some(enclosing) = collector.borrow_mut().as_mut() {
+            enclosing.stats += stats;
+        }
+    });
+
+    stats.total = total;
+    stats.shading = total
+        .saturating_sub(stats.primary_rays)
+        .saturating_sub(stats.secondary_rays)
+        .saturating_sub(stats.shadow_rays)
+        .saturating_sub(stats.canvas_write)
+        .saturating_sub(stats.accelerator_build);
+    (result, stats)
+}
+
+// the sink of the collection running on the current thread, if any, for
+// handing to threads that work on its behalf
+pub(crate) fn sink() -> Option<StatsSink> {
+    COLLECTOR.with(|collector| {
+        collector
+            .borrow()
+            .as_ref()
+            .map(|collection| collection.sink.clone())
+    })
+}
+
+// runs f collecting stats into the given sink, so that work done on another
+// thread is counted by the collection the sink came from
+pub(crate) fn collect_into<T>(sink: Option<StatsSink>, f: impl FnOnce() -> T) -> T {
+    let Some(sink) = sink else {
+        return f();
+    };
+    let (result, stats) = collect(f);
+    sink.add(&stats);
+    result
+}
+
+// adds up the phase times and counts of another set of stats; the total and
+// shading times are left alone, being worked out once collection ends
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, other: Self) {
+        self.primary_rays += other.primary_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.canvas_write += other.canvas_write;
+        self.accelerator_build += other.accelerator_build;
+        self.primary_ray_count += other.primary_ray_count;
+        self.secondary_ray_count += other.secondary_ray_count;
+        self.shadow_ray_count += other.shadow_ray_count;
+        self.intersection_test_count += other.intersection_test_count;
+        self.march_limit_count += other.march_limit_count;
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "primary rays:   {:>10.3?} ({} rays)",
+            self.primary_rays, self.primary_ray_count
+        )?;
+        writeln!(
+            f,
+            "secondary rays: {:>10.3?} ({} rays)",
+            self.secondary_rays, self.secondary_ray_count
+        )?;
+        writeln!(
+            f,
+            "shadow rays:    {:>10.3?} ({} rays)",
+            self.shadow_rays, self.shadow_ray_count
+        )?;
+        writeln!(f, "intersection tests: {}", self.intersection_test_count)?;
+        writeln!(f, "march limits hit:   {}", self.march_limit_count)?;
+        writeln!(f, "shading:        {:>10.3?}", self.shading)?;
+        writeln!(f, "canvas write:   {:>10.3?}", self.canvas_write)?;
+        writeln!(f, "accel build:    {:>10.3?}", self.accelerator_build)?;
+        write!(f, "total:          {:>10.3?}", self.total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_phases_only_while_collecting() {
+        time(Phase::ShadowRays, || ());
+        let ((), stats) = collect(|| {
+            time(Phase::PrimaryRays, || ());
+            time(Phase::PrimaryRays, || ());
+            time(Phase::ShadowRays, || ());
+        });
+        assert_eq!(stats.primary_ray_count, 2);
+        assert_eq!(stats.shadow_ray_count, 1);
+        assert_eq!(stats.secondary_ray_count, 0);
+        assert!(stats.total >= stats.primary_rays + stats.shadow_rays);
+
+        let ((), stats) = collect(|| ());
+        assert_eq!(stats.primary_ray_count, 0);
+    }
+
+    #[test]
+    fn nested_collection_adds_to_enclosing() {
+        let (inner, outer) = collect(|| {
+            time(Phase::PrimaryRays, || ());
+            let ((), inner) = collect(|| {
+                time(Phase::ShadowRays, || ());
+                count_intersection_test();
+                count_intersection_test();
+            });
+            inner
+        });
+        assert_eq!(inner.shadow_ray_count, 1);
+        assert_eq!(inner.primary_ray_count, 0);
+        assert_eq!(inner.intersection_test_count, 2);
+        assert_eq!(outer.primary_ray_count, 1);
+        assert_eq!(outer.shadow_ray_count, 1);
+        assert_eq!(outer.intersection_test_count, 2);
+    }
+
+    #[test]
+    fn stats_from_other_threads_are_merged() {
+        let ((), stats) = collect(|| {
+            time(Phase::PrimaryRays, || ());
+            let workers: Vec<_> = (0..3)
+                .map(|_| {
+                    let sink = sink();
+                    std::thread::spawn(move || {
+                        collect_into(sink, || {
+                            time(Phase::PrimaryRays, || ());
+                            count_intersection_test();
+                        })
+                    })
+                })
+                .collect();
+            for worker in workers {
+                worker.join().unwrap();
+            }
+        });
+        assert_eq!(stats.primary_ray_count, 4);
+        assert_eq!(stats.intersection_test_count, 3);
+
+        assert!(sink().is_none());
+        collect_into(None, || time(Phase::PrimaryRays, || ()));
+    }
+
+    #[test]
+    fn accelerator_build_is_its_own_phase() {
+        let ((), stats) = collect(|| {
+            time(Phase::AcceleratorBuild, || {
+                std::thread::sleep(Duration::from_millis(2))
+            })
+        });
+        assert!(stats.accelerator_build >= Duration::from_millis(2));
+        assert!(stats.shading < stats.accelerator_build);
+        assert_eq!(stats.primary_ray_count, 0);
+    }
+}
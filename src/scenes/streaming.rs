@@ -0,0 +1,140 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::scenes::{stats, Camera, Canvas, RayGenerator, TileEvent, World};
+use crate::utils::RenderError;
+
+// A tiled render running on a thread of its own, so that a caller that must
+// not block for the length of a render (such as a web service on an async
+// runtime) can pass on its tiles as they arrive. The render stops early if
+// the handle is dropped or cancelled.
+#[derive(Debug)]
+pub struct RenderHandle {
+    events: Receiver<TileEvent>,
+    thread: JoinHandle<Result<Option<Canvas>, RenderError>>,
+}
+
+impl RenderHandle {
+    // the next event if there is one yet, without blocking; disconnected
+    // once the render has finished and every event has been taken
+    pub fn try_next(&self) -> Result<TileEvent, TryRecvError> {
+        self.events.try_recv()
+    }
+
+    // blocks for each event in turn until the render finishes
+    pub fn events(&self) -> impl Iterator<Item = TileEvent> + '_ {
+        self.events.iter()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    // blocks until the render finishes, giving the whole image as
+    // Camera::render_tiles would; events not yet taken are dropped
+    pub fn wait(self) -> Result<Canvas, RenderError> {
+        let image = self.join()?;
+        Ok(image.expect("rendering is only stopped by the handle"))
+    }
+
+    // stops the render after the tile in progress, waiting for it to do so
+    pub fn cancel(self) {
+        let RenderHandle { events, thread } = self;
+        drop(events);
+        let _ = RenderHandle::join_thread(thread);
+    }
+
+    fn join(self) -> Result<Option<Canvas>, RenderError> {
+        RenderHandle::join_thread(self.thread)
+    }
+
+    fn join_thread(
+        thread: JoinHandle<Result<Option<Canvas>, RenderError>>,
+    ) -> Result<Option<Canvas>, RenderError> {
+        thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+impl<R: RayGenerator + Send + 'static> Camera<R> {
+    // as render_tiles, rendering on a new thread and reporting events through
+    // the handle it returns; the world is shared so it can outlive the caller
+    pub fn spawn_render_tiles(self, world: Arc<World>, tile_size: usize) -> RenderHandle {
+        let (sender, events) = mpsc::channel();
+        let sink = stats::sink();
+        let thread = thread::spawn(move || {
+            stats::collect_into(sink, || {
+                self.render_tiles_with(&world, tile_size, |event| sender.send(event).is_ok())
+            })
+        });
+        RenderHandle { events, thread }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::{Angle, Colour, Point, Vector};
+    use crate::objects::*;
+    use crate::scenes::{Native, Orientation};
+    use crate::utils::{BuildInto, Buildable};
+
+    fn scene() -> (Camera<Native>, Arc<World>) {
+        let sphere = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let camera = Camera::new(Native::new(
+            8,
+            8,
+            Angle::from_degrees(90.0),
+            Orientation::new(
+                Point::new(0.0, 0.0, -2.5),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ),
+        ));
+        (camera, Arc::new(World::new(vec![sphere], vec![light])))
+    }
+
+    #[test]
+    fn stream_tiles_from_another_thread() {
+        let (camera, world) = scene();
+        let handle = camera.spawn_render_tiles(Arc::clone(&world), 4);
+        let rendered: Vec<_> = handle
+            .events()
+            .filter_map(|event| match event {
+                TileEvent::Rendered(tile) => Some(tile),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(handle.try_next(), Err(TryRecvError::Disconnected));
+        let image = handle.wait().unwrap();
+
+        assert_eq!(rendered.len(), 4);
+        let (camera, _) = scene();
+        assert_eq!(image, camera.render(&world).unwrap());
+        assert_eq!(rendered[3].pixels()[[0, 0]], image[[4, 4]]);
+    }
+
+    #[test]
+    fn stats_count_rays_traced_on_render_thread() {
+        let (camera, world) = scene();
+        let (image, streamed) =
+            stats::collect(|| camera.spawn_render_tiles(Arc::clone(&world), 4).wait());
+        image.unwrap();
+        let (camera, _) = scene();
+        let (_, direct) = stats::collect(|| camera.render(&world));
+        assert_eq!(streamed.primary_ray_count, direct.primary_ray_count);
+        assert!(streamed.primary_ray_count > 0);
+    }
+
+    #[test]
+    fn cancel_stops_render() {
+        let (camera, world) = scene();
+        let handle = camera.spawn_render_tiles(world, 1);
+        handle.cancel();
+    }
+}
@@ -0,0 +1,81 @@
+use crate::scenes::{Canvas, Region};
+
+// Lifecycle of a tile during a tiled render. Every tile is scheduled up front,
+// then each is rendered and merged into the final canvas in turn.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TileEvent {
+    Scheduled(Region),
+    Rendered(Tile),
+    Merged(Region),
+}
+
+// Rendered pixels of a tile; pixel [0, 0] of the buffer is the top-left corner
+// of the tile's region on the full canvas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub region: Region,
+    pub pixels: Canvas,
+}
+
+impl Tile {
+    pub fn new(region: Region, pixels: Canvas) -> Tile {
+        Tile { region, pixels }
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn pixels(&self) -> &Canvas {
+        &self.pixels
+    }
+}
+
+// splits an area into row-major square tiles, with smaller tiles along the
+// right and bottom edges where the area does not divide evenly
+pub(crate) fn tile_regions(area: Region, tile_size: usize) -> Vec<Region> {
+    let [x0, x1] = area.x_range();
+    let [y0, y1] = area.y_range();
+    let tile_size = usize::max(tile_size, 1);
+
+    let mut regions = vec![];
+    for tile_y in (y0..y1).step_by(tile_size) {
+        for tile_x in (x0..x1).step_by(tile_size) {
+            regions.push(Region::new(
+                tile_x,
+                tile_y,
+                usize::min(tile_x + tile_size, x1),
+                usize::min(tile_y + tile_size, y1),
+            ));
+        }
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_area_into_tiles() {
+        let regions = tile_regions(Region::new(0, 0, 5, 3), 2);
+        assert_eq!(
+            regions,
+            vec![
+                Region::new(0, 0, 2, 2),
+                Region::new(2, 0, 4, 2),
+                Region::new(4, 0, 5, 2),
+                Region::new(0, 2, 2, 3),
+                Region::new(2, 2, 4, 3),
+                Region::new(4, 2, 5, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_offset_area_into_tiles() {
+        let regions = tile_regions(Region::new(3, 1, 6, 2), 4);
+        assert_eq!(regions, vec![Region::new(3, 1, 6, 2)]);
+        assert!(tile_regions(Region::new(2, 2, 2, 4), 4).is_empty());
+    }
+}
@@ -1,6 +1,10 @@
+use std::sync::mpsc::Sender;
+
 use crate::collections::{Matrix, Point, Vector};
 use crate::objects::*;
+use crate::scenes::stats;
 use crate::scenes::*;
+use crate::utils::{splitmix64, BuildError, RenderError, Rng};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Orientation(pub Transform);
@@ -10,6 +14,14 @@ impl Orientation {
         Orientation(Orientation::view_transform(from, to, up))
     }
 
+    // fails when from and to coincide or up is parallel to the view, where
+    // there is no sensible camera frame
+    pub fn try_new(from: Point, to: Point, up: Vector) -> Result<Orientation, BuildError> {
+        Orientation::view_transform(from, to, up)
+            .validated()
+            .map(Orientation)
+    }
+
     pub fn frame_transformation(&self) -> &Transform {
         &self.0
     }
@@ -51,27 +63,461 @@ impl Default for Orientation {
 #[derive(Clone, Debug, PartialEq)]
 pub struct Camera<R: RayGenerator> {
     ray_generator: R,
+    region: Option<Region>,
+    integrator: Integrator,
+    debug_overlay: Option<DebugOverlay>,
+    heatmap: Option<Heatmap>,
+    exposure: Option<f64>,
+    auto_exposure: Option<AutoExposure>,
+    seed: u64,
+}
+
+// what each tile needs of the camera to shade its rays
+struct TileShading<'a> {
+    integrator: &'a Integrator,
+    overlay: Option<&'a OverlayLines>,
+    heatmap: Option<Heatmap>,
+    seed: u64,
+    object_ids: Option<&'a ObjectIds>,
 }
 
 impl<R: RayGenerator> Camera<R> {
-    pub fn new(ray_generator: R) -> Camera<R> {
-        Camera { ray_generator }
+    pub fn new(mut ray_generator: R) -> Camera<R> {
+        ray_generator.set_rng(Rng::new(0));
+        Camera {
+            ray_generator,
+            region: None,
+            integrator: Integrator::default(),
+            debug_overlay: None,
+            heatmap: None,
+            exposure: None,
+            auto_exposure: None,
+            seed: 0,
+        }
+    }
+
+    pub fn ray_generator(&self) -> &R {
+        &self.ray_generator
+    }
+
+    pub fn with_integrator(mut self, integrator: Integrator) -> Camera<R> {
+        self.integrator = integrator;
+        self
+    }
+
+    pub fn integrator(&self) -> &Integrator {
+        &self.integrator
+    }
+
+    // draws bounding boxes and wireframes over the image, for diagnosis
+    pub fn with_debug_overlay(mut self, debug_overlay: DebugOverlay) -> Camera<R> {
+        self.debug_overlay = Some(debug_overlay);
+        self
+    }
+
+    pub fn debug_overlay(&self) -> Option<&DebugOverlay> {
+        self.debug_overlay.as_ref()
+    }
+
+    // colours each pixel by the work done for it instead of by what is seen
+    pub fn with_heatmap(mut self, heatmap: Heatmap) -> Camera<R> {
+        self.heatmap = Some(heatmap);
+        self
+    }
+
+    pub fn heatmap(&self) -> Option<Heatmap> {
+        self.heatmap
+    }
+
+    // restricts rendering to a crop window of the canvas; pixels outside the
+    // region are left black
+    pub fn with_region(mut self, region: Region) -> Camera<R> {
+        self.region = Some(region);
+        self
+    }
+
+    pub fn region(&self) -> Option<Region> {
+        self.region
+    }
+
+    // scales the finished image for an exposure value at ISO 100, so lights can
+    // be given in real units rather than kept at or below 1; applied before any
+    // auto-exposure, which rescales the image again
+    pub fn with_exposure(mut self, ev100: f64) -> Camera<R> {
+        self.exposure = Some(ev100);
+        self
+    }
+
+    pub fn exposure(&self) -> Option<f64> {
+        self.exposure
+    }
+
+    // rescales the finished image so that its key luminance is middle grey
+    pub fn with_auto_exposure(mut self, auto_exposure: AutoExposure) -> Camera<R> {
+        self.auto_exposure = Some(auto_exposure);
+        self
+    }
+
+    pub fn auto_exposure(&self) -> Option<AutoExposure> {
+        self.auto_exposure
+    }
+
+    // global seed from which every pixel's random samples are derived, and
+    // which is passed to the ray generator for any jitter; the same seed always
+    // gives the same image
+    pub fn with_seed(mut self, seed: u64) -> Camera<R> {
+        self.seed = seed;
+        self.ray_generator.set_rng(Rng::new(seed));
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn render(self, world: &World) -> Result<Canvas, RenderError> {
+        self.render_resolved(world, None)
     }
 
-    pub fn render(self, world: &World) -> Result<Canvas, WriteError> {
+    // adds the samples of a render (of the camera's region, if it has one) to
+    // the buffer at the canvas's own pixel indices, so that further renders,
+    // say with other seeds, keep refining the same buffer; exposure is left to
+    // whatever resolves it
+    pub fn render_into(
+        self,
+        world: &World,
+        buffer: &mut AccumulationBuffer,
+    ) -> Result<(), RenderError> {
+        self.accumulate(world, buffer, None)
+    }
+
+    // renders as usual, also recording depth, normal, albedo and object ID
+    // passes for the pixels rendered; auto-exposure only affects the image
+    pub fn render_with_aovs(
+        self,
+        world: &World,
+    ) -> Result<(Canvas, AuxiliaryBuffers), RenderError> {
         let (hsize, vsize) = self.ray_generator.canvas_size();
-        let mut image = Canvas::new(Width(hsize), Height(vsize));
+        let mut aovs = AuxiliaryBuffers::new(hsize, vsize);
+        let image = self.render_resolved(world, Some(&mut aovs))?;
+        Ok((image, aovs))
+    }
+
+    // renders with auxiliary passes and cleans the image up with the denoiser
+    pub fn render_denoised(
+        self,
+        world: &World,
+        denoiser: &impl Denoiser,
+    ) -> Result<Canvas, RenderError> {
+        let (image, aovs) = self.render_with_aovs(world)?;
+        Ok(denoiser.denoise(&image, &aovs))
+    }
+
+    fn render_resolved(
+        self,
+        world: &World,
+        aovs: Option<&mut AuxiliaryBuffers>,
+    ) -> Result<Canvas, RenderError> {
+        let (hsize, vsize) = self.ray_generator.canvas_size();
+        let (exposure, auto_exposure) = (self.exposure, self.auto_exposure);
+        let region = self.region;
+        let mut buffer = AccumulationBuffer::new(Width(hsize), Height(vsize));
+        self.accumulate(world, &mut buffer, aovs)?;
+
+        stats::time(stats::Phase::CanvasWrite, || {
+            let mut image = buffer.resolve();
+            Self::expose(&mut image, exposure, auto_exposure, region);
+            Ok(image)
+        })
+    }
+
+    fn accumulate(
+        self,
+        world: &World,
+        buffer: &mut AccumulationBuffer,
+        aovs: Option<&mut AuxiliaryBuffers>,
+    ) -> Result<(), RenderError> {
+        let (hsize, vsize) = self.ray_generator.canvas_size();
+        let area = self.region.unwrap_or(Region::new(0, 0, hsize, vsize));
+        let overlay = self
+            .debug_overlay
+            .as_ref()
+            .map(|overlay| overlay.lines(world));
+        let object_ids = aovs.as_ref().map(|_| world.object_ids());
+        let shading = TileShading {
+            integrator: &self.integrator,
+            overlay: overlay.as_ref(),
+            heatmap: self.heatmap,
+            seed: self.seed,
+            object_ids: object_ids.as_ref(),
+        };
+        let tile = Self::render_tile(
+            &shading,
+            world,
+            area,
+            (hsize, vsize),
+            self.ray_generator,
+            aovs,
+        )?;
+        buffer.add_buffer(area.x_range()[0], area.y_range()[0], &tile)
+    }
+
+    // renders as usual, also timing each phase of the render
+    pub fn render_with_stats(self, world: &World) -> Result<(Canvas, RenderStats), RenderError> {
+        let (image, stats) = stats::collect(|| self.render(world));
+        Ok((image?, stats))
+    }
+
+    // renders the canvas (or the camera's region of it) in square tiles,
+    // reporting each tile through the channel as it is scheduled, rendered and
+    // merged; rendering carries on if the receiving end hangs up. Auto-exposure
+    // needs the whole image, so tiles are reported before it is applied.
+    pub fn render_tiles(
+        self,
+        world: &World,
+        tile_size: usize,
+        events: Sender<TileEvent>,
+    ) -> Result<Canvas, RenderError> {
+        let image = self.render_tiles_with(world, tile_size, |event| {
+            let _ = events.send(event);
+            true
+        })?;
+        Ok(image.expect("rendering is never stopped"))
+    }
+
+    // as render_tiles, but handing each event to a callback, which can stop the
+    // render (giving None) by returning false
+    pub(crate) fn render_tiles_with(
+        self,
+        world: &World,
+        tile_size: usize,
+        mut on_event: impl FnMut(TileEvent) -> bool,
+    ) -> Result<Option<Canvas>, RenderError> {
+        let (hsize, vsize) = self.ray_generator.canvas_size();
+        let area = self.region.unwrap_or(Region::new(0, 0, hsize, vsize));
+        let regions = tile_regions(area, tile_size);
+        for &region in &regions {
+            if !on_event(TileEvent::Scheduled(region)) {
+                return Ok(None);
+            }
+        }
+
+        // rays are bucketed by every tile they contribute to, including tiles
+        // whose outline apron they fall in
+        let outlined = matches!(
+            self.integrator,
+            Integrator::Toon(Toon {
+                outline: Some(_),
+                ..
+            })
+        );
+        let tile_size = usize::max(tile_size, 1);
+        let tiles_per_row = (area.x_range()[1] - area.x_range()[0]).div_ceil(tile_size);
+        let tile_index = |[pos_x, pos_y]: [usize; 2]| {
+            area.contains([pos_x, pos_y]).then(|| {
+                (pos_y - area.y_range()[0]) / tile_size * tiles_per_row
+                    + (pos_x - area.x_range()[0]) / tile_size
+            })
+        };
+        let mut buckets: Vec<Vec<TaggedRay>> = vec![vec![]; regions.len()];
         for tagged_ray in self.ray_generator {
-            let cast_ray = tagged_ray.ray();
-            let colour = world.cast_ray(cast_ray);
-            let tagged_pixels = tagged_ray.pixels();
-            for tagged_pixel in tagged_pixels {
+            let mut indices = vec![];
+            for tagged_pixel in tagged_ray.pixels() {
                 let [pos_x, pos_y] = tagged_pixel.index();
-                let blend_weight = tagged_pixel.blend_weight();
-                image.paint_colour_additive(pos_x, pos_y, colour * blend_weight)?;
+                let mut neighbourhood = vec![[pos_x, pos_y]];
+                if outlined {
+                    neighbourhood.extend([
+                        [pos_x.wrapping_sub(1), pos_y],
+                        [pos_x + 1, pos_y],
+                        [pos_x, pos_y.wrapping_sub(1)],
+                        [pos_x, pos_y + 1],
+                    ]);
+                }
+                for index in neighbourhood.into_iter().filter_map(tile_index) {
+                    if !indices.contains(&index) {
+                        indices.push(index);
+                    }
+                }
+            }
+            for index in indices {
+                buckets[index].push(tagged_ray.clone());
             }
         }
-        Ok(image)
+
+        let overlay = self
+            .debug_overlay
+            .as_ref()
+            .map(|overlay| overlay.lines(world));
+        let shading = TileShading {
+            integrator: &self.integrator,
+            overlay: overlay.as_ref(),
+            heatmap: self.heatmap,
+            seed: self.seed,
+            object_ids: None,
+        };
+        let mut buffer = AccumulationBuffer::new(Width(hsize), Height(vsize));
+        for (region, bucket) in regions.into_iter().zip(buckets) {
+            let tile = Self::render_tile(&shading, world, region, (hsize, vsize), bucket, None)?;
+            if !on_event(TileEvent::Rendered(Tile::new(region, tile.resolve()))) {
+                return Ok(None);
+            }
+            buffer.add_buffer(region.x_range()[0], region.y_range()[0], &tile)?;
+            if !on_event(TileEvent::Merged(region)) {
+                return Ok(None);
+            }
+        }
+
+        let mut image = buffer.resolve();
+        Self::expose(&mut image, self.exposure, self.auto_exposure, self.region);
+        Ok(Some(image))
+    }
+
+    // auto-exposure meters only the region rendered, if there is one
+    fn expose(
+        image: &mut Canvas,
+        exposure: Option<f64>,
+        auto_exposure: Option<AutoExposure>,
+        region: Option<Region>,
+    ) {
+        if let Some(ev100) = exposure {
+            image.scale_brightness(ev100_scale(ev100));
+        }
+        match (auto_exposure, region) {
+            (Some(auto_exposure), Some(region)) => {
+                image.auto_expose_within(auto_exposure, region);
+            }
+            (Some(auto_exposure), None) => {
+                image.auto_expose(auto_exposure);
+            }
+            (None, _) => (),
+        }
+    }
+
+    // renders the pixels of a region into a buffer of the region's size; with
+    // outlines, surfaces are also gathered in a one pixel apron around the
+    // region so that edges along its border are found as in a full render.
+    // Auxiliary passes, when given, are recorded at full canvas indices.
+    fn render_tile(
+        shading: &TileShading,
+        world: &World,
+        region: Region,
+        (hsize, vsize): (usize, usize),
+        tagged_rays: impl IntoIterator<Item = TaggedRay>,
+        mut aovs: Option<&mut AuxiliaryBuffers>,
+    ) -> Result<AccumulationBuffer, RenderError> {
+        let &TileShading {
+            integrator,
+            overlay,
+            heatmap,
+            seed,
+            object_ids,
+        } = shading;
+        let [x0, x1] = region.x_range();
+        let [y0, y1] = region.y_range();
+        let mut samples = AccumulationBuffer::new(Width(x1 - x0), Height(y1 - y0));
+
+        let outline = match integrator {
+            Integrator::Toon(Toon {
+                outline: Some(outline),
+                ..
+            }) => Some(outline),
+            _ => None,
+        };
+        let apron = match outline {
+            Some(_) => Region::new(
+                x0.saturating_sub(1),
+                y0.saturating_sub(1),
+                usize::min(x1 + 1, hsize),
+                usize::min(y1 + 1, vsize),
+            ),
+            None => region,
+        };
+        let [apron_x0, apron_x1] = apron.x_range();
+        let [apron_y0, apron_y1] = apron.y_range();
+        let mut surface_buffer =
+            outline.map(|_| SurfaceBuffer::new(apron_x1 - apron_x0, apron_y1 - apron_y0));
+
+        for tagged_ray in tagged_rays {
+            let tagged_pixels: Vec<&TaggedPixel> = tagged_ray
+                .pixels()
+                .iter()
+                .filter(|tagged_pixel| apron.contains(tagged_pixel.index()))
+                .collect();
+            if tagged_pixels.is_empty() {
+                continue;
+            }
+
+            let cast_ray = tagged_ray.ray();
+            // rays sharing a pixel are told apart by their direction, so that
+            // each gets its own stream however the rays are ordered
+            let ray_key = [
+                cast_ray.direction.x,
+                cast_ray.direction.y,
+                cast_ray.direction.z,
+            ]
+            .into_iter()
+            .fold(0, |key, component| splitmix64(key ^ component.to_bits()));
+            let mut rng = Rng::for_pixel(seed, tagged_ray.pixels()[0].index(), ray_key);
+            let differentials = tagged_ray.differentials();
+            // the surface each primary ray hits is recorded as it is shaded,
+            // for outlines and the auxiliary passes
+            let differentials = differentials.as_ref();
+            let mut shade = || match integrator {
+                Integrator::Whitted => {
+                    world.cast_ray_recorded(cast_ray, differentials, &mut rng, object_ids)
+                }
+                Integrator::Toon(toon) => {
+                    world.cast_ray_toon(cast_ray, differentials, toon.bands, object_ids)
+                }
+                Integrator::Clay => world.cast_ray_clay(cast_ray, differentials, object_ids),
+                Integrator::Normals => world.cast_ray_normals(cast_ray, differentials, object_ids),
+                &Integrator::Depth(max_distance) => {
+                    world.cast_ray_depth(cast_ray, differentials, max_distance, object_ids)
+                }
+            };
+            let (colour, surface) = match heatmap {
+                Some(heatmap) => {
+                    let ((_, surface), cost) = stats::collect(shade);
+                    (heatmap.colour_for(&cost), surface)
+                }
+                None => shade(),
+            };
+            // lines of the debug overlay cover whatever the integrator gave
+            let colour = overlay
+                .and_then(|overlay| overlay.colour_on(&cast_ray))
+                .unwrap_or(colour);
+            stats::time(stats::Phase::CanvasWrite, || {
+                for tagged_pixel in tagged_pixels {
+                    let [pos_x, pos_y] = tagged_pixel.index();
+                    let blend_weight = tagged_pixel.blend_weight();
+                    if region.contains([pos_x, pos_y]) {
+                        samples.add_sample([pos_x - x0, pos_y - y0], colour, blend_weight)?;
+                        if let Some(aovs) = aovs.as_mut() {
+                            aovs.record([pos_x, pos_y], surface, blend_weight);
+                        }
+                    }
+                    if let Some(surface_buffer) = surface_buffer.as_mut() {
+                        surface_buffer.record(
+                            [pos_x - apron_x0, pos_y - apron_y0],
+                            surface.map(|surface| (surface.depth, surface.normal)),
+                            blend_weight,
+                        );
+                    }
+                }
+                Ok::<(), RenderError>(())
+            })?;
+        }
+
+        if let (Some(outline), Some(surface_buffer)) = (outline, &surface_buffer) {
+            for [apron_pos_x, apron_pos_y] in surface_buffer.outline_pixels(outline) {
+                let [pos_x, pos_y] = [apron_pos_x + apron_x0, apron_pos_y + apron_y0];
+                if region.contains([pos_x, pos_y]) {
+                    samples.replace_colour([pos_x - x0, pos_y - y0], outline.colour)?;
+                }
+            }
+        }
+
+        Ok(samples)
     }
 }
 
@@ -80,7 +526,8 @@ mod tests {
     use std::f64::consts::FRAC_PI_2;
 
     use crate::collections::*;
-    use crate::utils::{approx_eq, BuildInto, Buildable};
+    use crate::scenes::raygen::agss::Agss;
+    use crate::utils::{approx_eq, BuildInto, Buildable, ConsumingBuilder};
 
     use super::*;
 
@@ -133,13 +580,25 @@ mod tests {
         for i_row in 0..4 {
             for i_col in 0..4 {
                 approx_eq!(
-                    view_transform.0[[i_row, i_col]],
-                    resulting_transform.0[[i_row, i_col]]
+                    view_transform.matrix()[[i_row, i_col]],
+                    resulting_transform.matrix()[[i_row, i_col]]
                 );
             }
         }
     }
 
+    #[test]
+    fn reject_degenerate_orientation() {
+        let from = Point::new(0.0, 0.0, 8.0);
+        let up = Vector::new(0.0, 1.0, 0.0);
+        assert!(Orientation::try_new(from, Point::new(0.0, 0.0, 0.0), up).is_ok());
+        assert_eq!(
+            Orientation::try_new(from, from, up),
+            Err(BuildError::NonInvertibleTransform)
+        );
+        assert!(Orientation::try_new(from, Point::new(0.0, 1.0, 8.0), up).is_err());
+    }
+
     #[test]
     fn render_world() {
         let s1 = Sphere::builder()
@@ -158,6 +617,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let native_ray_generator = Native::new(
             11,
@@ -177,4 +637,503 @@ mod tests {
         assert_eq!(painted_pixel.green(), resulting_pixel.green());
         assert_eq!(painted_pixel.blue(), resulting_pixel.blue());
     }
+
+    #[test]
+    fn render_world_with_aovs() {
+        let sphere = Sphere::builder()
+            .set_material(Material {
+                pattern: Box::new(Solid::new(Colour::new(0.8, 1.0, 0.6))),
+                ..Material::preset()
+            })
+            .build_into();
+        let backdrop = Plane::builder()
+            .set_frame_transformation(
+                Transform::new(TransformKind::Rotate(
+                    Axis::X,
+                    Angle::from_radians(FRAC_PI_2),
+                ))
+                .compose(&Transform::new(TransformKind::Translate(0.0, 0.0, 10.0))),
+            )
+            .build_into();
+        let world = World {
+            objects: vec![Group::builder().add_object(sphere).build_into(), backdrop],
+            lights: vec![Light::new(
+                Point::new(-10.0, 10.0, -10.0),
+                Colour::new(1.0, 1.0, 1.0),
+            )],
+            ..World::default()
+        };
+        let native_ray_generator = || {
+            Native::new(
+                11,
+                11,
+                Angle::from_radians(FRAC_PI_2),
+                Orientation::new(
+                    Point::new(0.0, 0.0, -5.0),
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                ),
+            )
+        };
+        let beauty = Camera::new(native_ray_generator()).render(&world).unwrap();
+        let (image, aovs) = Camera::new(native_ray_generator())
+            .render_with_aovs(&world)
+            .unwrap();
+        assert_eq!(image[[5, 5]].colour(), beauty[[5, 5]].colour());
+        assert_eq!((aovs.width(), aovs.height()), (11, 11));
+
+        approx_eq!(aovs.depth([5, 5]).unwrap(), 4.0);
+        assert_eq!(aovs.normal([5, 5]), Some(Vector::new(0.0, 0.0, -1.0)));
+        assert_eq!(aovs.albedo([5, 5]), Some(Colour::new(0.8, 1.0, 0.6)));
+        assert_eq!(aovs.object_id([5, 5]), Some(0));
+        assert_eq!(aovs.object_id([0, 0]), Some(1));
+        assert!(aovs.depth([0, 0]).unwrap() > 10.0);
+
+        // the passes are read off the hits the image was shaded from
+        let (_, beauty_stats) =
+            stats::collect(|| Camera::new(native_ray_generator()).render(&world));
+        let (_, aov_stats) =
+            stats::collect(|| Camera::new(native_ray_generator()).render_with_aovs(&world));
+        assert_eq!(aov_stats.primary_ray_count, beauty_stats.primary_ray_count);
+    }
+
+    #[test]
+    fn render_world_with_utility_integrators() {
+        let s1 = Sphere::builder()
+            .set_material(Material {
+                pattern: Box::new(Solid::new(Colour::new(1.0, 0.2, 0.2))),
+                reflectance: 0.5,
+                ..Material::preset()
+            })
+            .build_into();
+        let light = Light::new(Point::new(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::new(vec![s1], vec![light]);
+        let render = |integrator: Integrator| {
+            let native_ray_generator = Native::new(
+                11,
+                11,
+                Angle::from_radians(FRAC_PI_2),
+                Orientation::new(
+                    Point::new(0.0, 0.0, -2.0),
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                ),
+            );
+            Camera::new(native_ray_generator)
+                .with_integrator(integrator)
+                .render(&world)
+                .unwrap()
+        };
+        let black = Pixel::new(Colour::BLACK);
+
+        // the red mirror is lit as grey clay instead
+        let clay = render(Integrator::Clay);
+        let centre = clay[[5, 5]].colour();
+        assert!(centre.red > 0.5);
+        assert_eq!(centre.red, centre.green);
+        assert_eq!(centre.green, centre.blue);
+        assert_eq!(clay[[0, 0]], black);
+
+        let normals = render(Integrator::Normals);
+        assert_eq!(normals[[5, 5]], Pixel::new(Colour::new(0.5, 0.5, 0.0)));
+        assert_eq!(normals[[0, 0]], black);
+
+        // the front of the sphere is 1 from the camera
+        let depth = render(Integrator::Depth(8.0));
+        approx_eq!(depth[[5, 5]].colour().red, 0.875);
+        assert_eq!(depth[[0, 0]], black);
+    }
+
+    #[test]
+    fn render_world_with_debug_overlay() {
+        let s1 = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::new(vec![s1], vec![light]);
+        let camera = || {
+            Camera::new(Native::new(
+                21,
+                21,
+                Angle::from_radians(FRAC_PI_2),
+                Orientation::new(
+                    Point::new(0.0, 0.0, -5.0),
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                ),
+            ))
+        };
+        let beauty = camera().render(&world).unwrap();
+        let overlay_colour = Colour::new(0.0, 1.0, 0.0);
+        let image = camera()
+            .with_debug_overlay(
+                DebugOverlay::new(overlay_colour)
+                    .with_bounds()
+                    .with_thickness(0.2),
+            )
+            .render(&world)
+            .unwrap();
+        // the box around the sphere is drawn and the rest is left alone
+        let mut overlaid = 0;
+        for pos_y in 0..21 {
+            for pos_x in 0..21 {
+                if image[[pos_x, pos_y]] == Pixel::new(overlay_colour) {
+                    overlaid += 1;
+                } else {
+                    assert_eq!(image[[pos_x, pos_y]], beauty[[pos_x, pos_y]]);
+                }
+            }
+        }
+        assert!(overlaid > 0);
+        assert_eq!(image[[10, 10]], beauty[[10, 10]]);
+        assert_eq!(image[[0, 0]], beauty[[0, 0]]);
+    }
+
+    #[test]
+    fn render_world_as_heatmap() {
+        let s1 = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::new(vec![s1], vec![light]);
+        let render = |metric: CostMetric| {
+            let native_ray_generator = Native::new(
+                11,
+                11,
+                Angle::from_radians(FRAC_PI_2),
+                Orientation::new(
+                    Point::new(0.0, 0.0, -5.0),
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                ),
+            );
+            Camera::new(native_ray_generator)
+                .with_heatmap(Heatmap::new(metric, 4.0))
+                .render(&world)
+                .unwrap()
+        };
+
+        // a miss costs the primary ray alone, a hit also its shadow ray
+        let rays = render(CostMetric::Rays);
+        assert_eq!(rays[[0, 0]], Pixel::new(Colour::new(0.0, 1.0, 1.0)));
+        assert_eq!(rays[[5, 5]], Pixel::new(Colour::new(0.0, 1.0, 0.0)));
+
+        // a sphere's bounds are left unchecked, as testing it is as cheap, so
+        // each ray (primary or shadow) makes the one test
+        let tests = render(CostMetric::IntersectionTests);
+        assert_eq!(tests[[0, 0]], Pixel::new(Colour::new(0.0, 1.0, 1.0)));
+        assert_eq!(tests[[5, 5]], Pixel::new(Colour::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn render_world_toon_with_outline() {
+        let s1 = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::new(vec![s1], vec![light]);
+        let native_ray_generator = Native::new(
+            21,
+            21,
+            Angle::from_radians(FRAC_PI_2),
+            Orientation::new(
+                Point::new(0.0, 0.0, -2.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ),
+        );
+        let outline_colour = Colour::new(1.0, 0.0, 0.0);
+        let camera = Camera::new(native_ray_generator).with_integrator(Integrator::Toon(
+            Toon::new(3).with_outline(Outline::new(outline_colour)),
+        ));
+        let image = camera.render(&world).unwrap();
+        // centre of the sphere is banded diffuse, its silhouette is outlined
+        // and the empty corner stays black
+        assert_eq!(image[[10, 10]].red(), image[[10, 10]].green());
+        assert_eq!(image[[4, 10]], Pixel::new(outline_colour));
+        assert_eq!(image[[16, 10]], Pixel::new(outline_colour));
+        assert_eq!(image[[3, 10]], Pixel::new(Colour::new(0.0, 0.0, 0.0)));
+        assert_eq!(image[[0, 0]], Pixel::new(Colour::new(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn render_world_region() {
+        let s1 = Sphere::builder()
+            .set_material(Material {
+                pattern: Box::new(Solid::new(Colour::new(0.8, 1.0, 0.6))),
+                diffuse: 0.7,
+                specular: 0.2,
+                ..Material::preset()
+            })
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World {
+            objects: vec![s1],
+            lights: vec![light],
+            ..World::default()
+        };
+        let native_ray_generator = Native::new(
+            11,
+            11,
+            Angle::from_radians(FRAC_PI_2),
+            Orientation::new(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ),
+        );
+        let camera = Camera::new(native_ray_generator).with_region(Region::new(5, 5, 7, 6));
+        let image = camera.render(&world).unwrap();
+        let painted_pixel = image[[5, 5]];
+        let resulting_pixel = Pixel::new(Colour::new(0.38066, 0.47583, 0.2855));
+        assert_eq!(painted_pixel.red(), resulting_pixel.red());
+        assert_eq!(painted_pixel.green(), resulting_pixel.green());
+        assert_eq!(painted_pixel.blue(), resulting_pixel.blue());
+        assert_ne!(image[[6, 5]], Pixel::new(Colour::new(0.0, 0.0, 0.0)));
+        assert_eq!(image[[4, 5]], Pixel::new(Colour::new(0.0, 0.0, 0.0)));
+        assert_eq!(image[[5, 6]], Pixel::new(Colour::new(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn render_tiles_matches_full_render() {
+        let s1 = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::new(vec![s1], vec![light]);
+        let agss_ray_generator = || {
+            Agss::new(
+                14,
+                14,
+                Angle::from_radians(FRAC_PI_2),
+                Orientation::new(
+                    Point::new(0.0, 0.0, -2.5),
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                ),
+                1.5,
+            )
+        };
+        let integrator =
+            Integrator::Toon(Toon::new(3).with_outline(Outline::new(Colour::new(1.0, 0.0, 0.0))));
+        let camera = Camera::new(agss_ray_generator()).with_integrator(integrator.clone());
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let tiled_image = camera.render_tiles(&world, 4, sender).unwrap();
+        let camera = Camera::new(agss_ray_generator()).with_integrator(integrator);
+        assert_eq!(tiled_image, camera.render(&world).unwrap());
+
+        let events: Vec<TileEvent> = receiver.iter().collect();
+        assert_eq!(events.len(), 48);
+        assert!(events[..16]
+            .iter()
+            .all(|event| matches!(event, TileEvent::Scheduled(_))));
+        match (&events[16], &events[17]) {
+            (TileEvent::Rendered(tile), TileEvent::Merged(region)) => {
+                assert_eq!(tile.region(), Region::new(0, 0, 4, 4));
+                assert_eq!(*region, tile.region());
+                assert_eq!(tile.pixels()[[1, 2]], tiled_image[[1, 2]]);
+            }
+            _ => panic!("expected a rendered tile followed by its merge"),
+        }
+    }
+
+    #[test]
+    fn agss_render_accumulates_blended_samples() {
+        let s1 = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::new(vec![s1], vec![light]);
+        // rays straddle pixel edges, so most pixels blend several rays
+        let camera = || {
+            Camera::new(Agss::new(
+                10,
+                10,
+                Angle::from_radians(FRAC_PI_2),
+                Orientation::new(
+                    Point::new(0.0, 0.0, -2.5),
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                ),
+                1.0 + 1.0 / 3.0,
+            ))
+        };
+
+        let image = camera().render(&world).unwrap();
+        for (index, value) in [
+            ([5, 5], 0.42090177889760316),
+            ([3, 5], 0.6851138335676376),
+            ([6, 4], 0.28031938830534703),
+            ([4, 3], 0.9391905146117187),
+            ([5, 6], 0.15527192857997835),
+        ] {
+            assert_eq!(image[index].colour(), Colour::new(value, value, value));
+        }
+
+        let mut buffer = AccumulationBuffer::new(Width(10), Height(10));
+        camera().render_into(&world, &mut buffer).unwrap();
+        assert_eq!(buffer.resolve(), image);
+        // a second pass of the same samples leaves the mean where it was
+        let sample_count = buffer.sample_count([5, 5]);
+        assert!(sample_count > 1);
+        camera().render_into(&world, &mut buffer).unwrap();
+        assert_eq!(buffer.sample_count([5, 5]), 2 * sample_count);
+        assert_eq!(buffer.resolve(), image);
+    }
+
+    #[test]
+    fn render_world_auto_exposed() {
+        let s1 = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::new(vec![s1], vec![light]);
+        let native_ray_generator = || {
+            Native::new(
+                11,
+                11,
+                Angle::from_radians(FRAC_PI_2),
+                Orientation::new(
+                    Point::new(0.0, 0.0, -5.0),
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                ),
+            )
+        };
+        let image = Camera::new(native_ray_generator()).render(&world).unwrap();
+        let exposed_image = Camera::new(native_ray_generator())
+            .with_auto_exposure(AutoExposure::Percentile(1.0))
+            .render(&world)
+            .unwrap();
+        // the brightest pixel is brought down to middle grey
+        let brightest = (0..11)
+            .flat_map(|pos_y| (0..11).map(move |pos_x| [pos_x, pos_y]))
+            .map(|index| exposed_image[index].colour().luminance())
+            .fold(0.0, f64::max);
+        approx_eq!(brightest, AutoExposure::MIDDLE_GREY);
+        assert_eq!(exposed_image[[0, 0]], image[[0, 0]]);
+    }
+
+    #[test]
+    fn render_world_with_exposure() {
+        let s1 = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        // a light given in real units, a thousand times too bright to show as is
+        let light = Light::new(
+            Point::new(-10.0, 10.0, -10.0),
+            Colour::new(1000.0, 1000.0, 1000.0),
+        );
+        let world = World::new(vec![s1], vec![light]);
+        let ray_generator = Native::new(
+            11,
+            11,
+            Angle::from_radians(FRAC_PI_2),
+            Orientation::new(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ),
+        );
+        let camera = Camera::new(ray_generator).with_exposure(10.0);
+        assert_eq!(camera.exposure(), Some(10.0));
+        let image = camera.render(&world).unwrap();
+        let centre = image[[5, 5]].colour().luminance();
+        assert!(centre > 0.0 && centre < 1.0);
+    }
+
+    #[test]
+    fn render_world_with_stats() {
+        let s1 = Sphere::builder()
+            .set_material(Material::preset())
+            .build_into();
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::new(vec![s1], vec![light]);
+        let native_ray_generator = Native::new(
+            11,
+            11,
+            Angle::from_radians(FRAC_PI_2),
+            Orientation::new(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ),
+        );
+        let (image, stats) = Camera::new(native_ray_generator)
+            .render_with_stats(&world)
+            .unwrap();
+        assert_eq!(image[[0, 0]], Pixel::new(Colour::new(0.0, 0.0, 0.0)));
+        assert_eq!(stats.primary_ray_count, 121);
+        assert_eq!(stats.secondary_ray_count, 0);
+        // one shadow ray per lit pixel
+        assert!(stats.shadow_ray_count > 0 && stats.shadow_ray_count < 121);
+        assert!(stats.total >= stats.primary_rays + stats.shadow_rays + stats.canvas_write);
+    }
+
+    #[test]
+    fn render_is_reproducible_for_seed() {
+        // a rough mirror sphere reflecting a striped background, so that every
+        // pixel on the sphere averages randomly rotated glossy samples
+        let mirror = Material::builder()
+            .set_ambient(0.0)
+            .set_diffuse(0.0)
+            .set_specular(0.0)
+            .set_reflectance(1.0)
+            .set_roughness(0.5)
+            .build();
+        let world = World::builder()
+            .add_object(Sphere::builder().set_material(mirror).build_into())
+            .set_background(Box::new(Stripe::new(
+                Colour::new(0.0, 0.0, 0.0),
+                Colour::new(1.0, 1.0, 1.0),
+                Transform::new(TransformKind::Scale(0.1, 0.1, 0.1)),
+            )))
+            .build();
+        let render = |seed: u64| {
+            let native_ray_generator = Native::new(
+                9,
+                9,
+                Angle::from_radians(FRAC_PI_2),
+                Orientation::new(
+                    Point::new(0.0, 0.0, -5.0),
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                ),
+            );
+            Camera::new(native_ray_generator)
+                .with_seed(seed)
+                .render(&world)
+                .unwrap()
+        };
+        assert_eq!(render(3), render(3));
+        assert_ne!(render(3), render(4));
+    }
+
+    #[test]
+    fn jitter_follows_seed() {
+        // only the background is seen, so any difference comes from where the
+        // rays pass through their pixels
+        let world = World::builder()
+            .set_background(Box::new(Stripe::new(
+                Colour::new(0.0, 0.0, 0.0),
+                Colour::new(1.0, 1.0, 1.0),
+                Transform::new(TransformKind::Scale(0.05, 0.05, 0.05)),
+            )))
+            .build();
+        let render = |seed: u64, jittered: bool| {
+            let agss = Agss::new(
+                6,
+                6,
+                Angle::from_radians(FRAC_PI_2),
+                Orientation::default(),
+                2.0,
+            );
+            let agss = if jittered { agss.with_jitter() } else { agss };
+            Camera::new(agss).with_seed(seed).render(&world).unwrap()
+        };
+        assert_eq!(render(3, false), render(4, false));
+        assert_eq!(render(3, true), render(3, true));
+        assert_ne!(render(3, true), render(4, true));
+    }
 }
@@ -1,95 +1,672 @@
+use std::ops::Range;
+use std::sync::OnceLock;
+
 use crate::collections::*;
 use crate::objects::*;
-use crate::utils::*;
+use crate::scenes::{
+    stats, Accelerator, LightLinks, LinearScan, MaterialOverrides, ObjectIds, SurfaceRecord,
+};
+use crate::utils::{BuildError, Buildable, ConsumingBuilder, Rng, EPSILON};
 
 #[derive(Default, Debug)]
 pub struct World {
     pub objects: Vec<Shape>,
     pub lights: Vec<Light>,
+    // sampled with the direction of rays that miss every object
+    pub background: Option<Box<dyn Pattern>>,
+    // replaces the ambient term of every light when set
+    pub hemispheric_ambient: Option<HemisphericAmbient>,
+    // swaps materials by object name or group path when shading, leaving the
+    // objects as-is. Like the accelerator, it is brought up to date by
+    // refit_accelerator after objects are changed in place.
+    pub material_overrides: MaterialOverrides,
+    // limits which lights reach which objects, by name
+    pub light_links: LightLinks,
+    // distance that secondary rays start off the surface they leave, to avoid
+    // acne; EPSILON when unset, but large scenes may need more
+    pub surface_bias: Option<f64>,
+    // reflected and refracted rays that would make up less than this
+    // fraction of the final colour are not traced; all are when unset
+    pub min_throughput: Option<f64>,
+    // a medium filling all of space
+    pub fog: Option<Medium>,
+    // media filling the insides of shapes, which rays pass through rather
+    // than hit
+    pub media: Vec<ConstantMedium>,
+    // camera rays see only surfaces at distances within this range, so that
+    // whatever lies nearer or further is cut away; other rays see everything
+    pub clipping: Option<Range<f64>>,
+    // finds which objects a ray may meet rather than testing every one, as
+    // happens when unset (see LinearScan). It must be refit (see
+    // refit_accelerator) after objects are changed in place.
+    pub accelerator: Option<Box<dyn Accelerator>>,
 }
 
 impl<'world: 'ray, 'ray> World {
     const MAX_RAYCAST_DEPTH: i32 = 10;
+    const GLOSSY_SAMPLES: usize = 16;
 
     pub fn new(objects: Vec<Shape>, lights: Vec<Light>) -> World {
-        World { objects, lights }
+        World {
+            objects,
+            lights,
+            background: None,
+            hemispheric_ambient: None,
+            material_overrides: MaterialOverrides::default(),
+            light_links: LightLinks::default(),
+            surface_bias: None,
+            min_throughput: None,
+            fog: None,
+            media: vec![],
+            clipping: None,
+            accelerator: None,
+        }
+    }
+
+    pub fn add_object(&mut self, object: Shape) {
+        self.objects.push(object);
+        self.rebuild_accelerator();
+    }
+
+    // panics if the index is out of bounds, like Vec::remove
+    pub fn remove_object(&mut self, index: usize) -> Shape {
+        let object = self.objects.remove(index);
+        self.rebuild_accelerator();
+        object
+    }
+
+    // Puts an object in place of another (such as one that has moved),
+    // returning the one replaced. The accelerator is only refit, so with one
+    // that can be refit (such as InstanceTree) this is the cheap way to move
+    // objects between frames. Panics if the index is out of bounds.
+    pub fn set_object(&mut self, index: usize, object: Shape) -> Shape {
+        let replaced = std::mem::replace(&mut self.objects[index], object);
+        self.refit_accelerator();
+        replaced
+    }
+
+    // brings the accelerator (and the paths material overrides match) up to
+    // date after objects are changed in place
+    pub fn refit_accelerator(&mut self) {
+        self.material_overrides.resolve(&self.objects);
+        if let Some(accelerator) = &mut self.accelerator {
+            stats::time(stats::Phase::AcceleratorBuild, || {
+                accelerator.refit(&self.objects)
+            });
+        }
+    }
+
+    fn rebuild_accelerator(&mut self) {
+        self.material_overrides.resolve(&self.objects);
+        if let Some(accelerator) = &mut self.accelerator {
+            stats::time(stats::Phase::AcceleratorBuild, || {
+                accelerator.build(&self.objects)
+            });
+        }
+    }
+
+    fn accelerator(&self) -> &dyn Accelerator {
+        self.accelerator.as_deref().unwrap_or(&LinearScan)
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &Shape> {
+        self.objects.iter()
+    }
+
+    pub fn objects_mut(&mut self) -> impl Iterator<Item = &mut Shape> {
+        self.objects.iter_mut()
+    }
+
+    pub fn lights(&self) -> impl Iterator<Item = &Light> {
+        self.lights.iter()
+    }
+
+    // searches nested groups and CSG shapes as well as top-level objects
+    pub fn find_object(&self, name: &str) -> Option<&Shape> {
+        self.objects.iter().find_map(|object| object.find(name))
+    }
+
+    pub fn find_object_mut(&mut self, name: &str) -> Option<&mut Shape> {
+        self.objects
+            .iter_mut()
+            .find_map(|object| object.find_mut(name))
+    }
+
+    // moves the object with the given name, however deeply it is nested, and
+    // refits the accelerator (see Shape::apply_transform_to); false if there
+    // is no such object
+    pub fn apply_transform_to(&mut self, name: &str, transform: &Transform) -> bool {
+        let found = self
+            .objects
+            .iter_mut()
+            .any(|object| object.apply_transform_to(name, transform));
+        if found {
+            self.refit_accelerator();
+        }
+        found
+    }
+
+    pub fn find_light(&self, name: &str) -> Option<&Light> {
+        self.lights.iter().find(|light| light.name == Some(name))
+    }
+
+    pub fn find_light_mut(&mut self, name: &str) -> Option<&mut Light> {
+        self.lights
+            .iter_mut()
+            .find(|light| light.name == Some(name))
     }
 
     pub fn cast_ray(&self, ray: Ray) -> Colour {
-        self.shade_ray(&ray, Self::MAX_RAYCAST_DEPTH)
+        self.cast_ray_with(ray, &mut Rng::new(0))
+    }
+
+    // as cast_ray, drawing any random samples needed while shading from the
+    // given generator
+    pub fn cast_ray_with(&self, ray: Ray, rng: &mut Rng) -> Colour {
+        self.cast_ray_recorded(ray, None, rng, None).0
+    }
+
+    // as cast_ray_with, filtering patterns over the footprint the differentials
+    // give the ray on each surface it (or its mirror reflections) hits
+    pub fn cast_ray_differential(
+        &self,
+        ray: Ray,
+        differentials: &RayDifferentials,
+        rng: &mut Rng,
+    ) -> Colour {
+        self.cast_ray_recorded(ray, Some(differentials), rng, None)
+            .0
+    }
+
+    // throughput is the fraction of the ray's colour that will reach the eye,
+    // from the reflectances and transparencies of the surfaces it came off;
+    // a ray parted from the others by dispersion carries only its own channel
+    fn shade_ray(
+        &self,
+        ray: &Ray,
+        differentials: Option<&RayDifferentials>,
+        depth_remaining: i32,
+        throughput: Colour,
+        rng: &mut Rng,
+    ) -> Colour {
+        self.trace_ray(ray, differentials, depth_remaining, throughput, rng)
+            .0
     }
 
-    fn shade_ray(&self, ray: &Ray, depth_remaining: i32) -> Colour {
+    // as cast_ray_with, also giving the first surface hit for the auxiliary
+    // passes when given the IDs to tag it with
+    pub(crate) fn cast_ray_recorded(
+        &self,
+        ray: Ray,
+        differentials: Option<&RayDifferentials>,
+        rng: &mut Rng,
+        object_ids: Option<&ObjectIds>,
+    ) -> (Colour, Option<SurfaceRecord>) {
+        let hit = self.trace_hit(&ray, differentials, stats::Phase::PrimaryRays);
+        let surface = object_ids
+            .zip(hit.as_ref())
+            .map(|(object_ids, hit)| World::surface_record(&ray, hit, Some(object_ids)));
+        let (colour, _) = self.shade_traced(&ray, hit, Self::MAX_RAYCAST_DEPTH, Colour::WHITE, rng);
+        (colour, surface)
+    }
+
+    // shades a ray spawned at a surface and also returns the distance it
+    // travelled before hitting anything (infinite on a miss), which is its t
+    // only if it is normalised
+    fn trace_ray(
+        &self,
+        ray: &Ray,
+        differentials: Option<&RayDifferentials>,
+        depth_remaining: i32,
+        throughput: Colour,
+        rng: &mut Rng,
+    ) -> (Colour, f64) {
         if depth_remaining == 0 {
-            return Colour::new(0.0, 0.0, 0.0);
+            return (Colour::new(0.0, 0.0, 0.0), f64::INFINITY);
         }
 
-        let hit_register = self.intersect_ray(ray);
+        let hit = self.trace_hit(ray, differentials, stats::Phase::SecondaryRays);
+        self.shade_traced(ray, hit, depth_remaining, throughput, rng)
+    }
 
-        if let Some(computed_intersect) = hit_register.finalise_hit() {
+    // the nearest hit along a ray of the given phase; only camera rays are
+    // clipped to the camera's range
+    fn trace_hit(
+        &'world self,
+        ray: &'ray Ray,
+        differentials: Option<&RayDifferentials>,
+        phase: stats::Phase,
+    ) -> Option<Intersect<'ray, dyn PrimitiveShape, Computed>> {
+        let t_range = match phase {
+            stats::Phase::PrimaryRays => self.camera_range(ray),
+            _ => 0.0..f64::INFINITY,
+        };
+        stats::time(phase, || self.find_hit(ray, t_range))
+            .map(|hit| World::attach_differentials(hit, differentials))
+    }
+
+    // the colour found along a ray given what (if anything) it hit, along with
+    // the distance travelled as for trace_ray
+    fn shade_traced(
+        &self,
+        ray: &Ray,
+        hit: Option<Intersect<dyn PrimitiveShape, Computed>>,
+        depth_remaining: i32,
+        throughput: Colour,
+        rng: &mut Rng,
+    ) -> (Colour, f64) {
+        let (colour, t) = if let Some(computed_intersect) = hit {
             let surface = self.shade_surface(&computed_intersect);
-            let reflected = self.shade_reflection(&computed_intersect, depth_remaining);
-            let refracted = self.shade_refraction(&computed_intersect, depth_remaining);
+            let reflected =
+                self.shade_reflection(&computed_intersect, depth_remaining, throughput, rng);
+            let refracted =
+                self.shade_refraction(&computed_intersect, depth_remaining, throughput, rng);
 
-            let material = computed_intersect.object().material();
-            if material.reflectance > 0.0 && material.transparency > 0.0 {
+            let material = computed_intersect.material();
+            let colour = if let Some(thin_film) = material.thin_film {
+                let (n1, n2) = computed_intersect.refraction_boundary();
+                let cos_i = computed_intersect.eyev().dot(computed_intersect.normal());
+                let reflectance = thin_film.reflectance(cos_i, n1, n2);
+                surface
+                    + reflected * reflectance
+                    + refracted * (Colour::new(1.0, 1.0, 1.0) - reflectance)
+            } else if material.reflectance > 0.0 && material.transparency > 0.0 {
                 let reflectance = computed_intersect.schlick_reflectance();
                 surface + reflected * reflectance + refracted * (1.0 - reflectance)
             } else {
                 surface + reflected + refracted
-            }
+            };
+            (colour, computed_intersect.t())
         } else {
-            return Colour::new(0.0, 0.0, 0.0);
+            (self.shade_background(ray), f64::INFINITY)
+        };
+        (self.shade_media(ray, colour, t, rng), ray.distance_at(t))
+    }
+
+    // dims the colour found at t along a ray by the fog and media it passed
+    // through, adding in what they scatter towards the eye; stretches of
+    // media are laid over each other from the far end back
+    fn shade_media(&self, ray: &Ray, colour: Colour, t: f64, rng: &mut Rng) -> Colour {
+        let mut segments: Vec<(Range<f64>, &Medium)> = self
+            .media
+            .iter()
+            .flat_map(|medium| {
+                medium
+                    .segments(ray, 0.0..t)
+                    .into_iter()
+                    .map(move |segment| (segment, medium.medium()))
+            })
+            .collect();
+        if let Some(fog) = &self.fog {
+            segments.push((0.0..t, fog));
+        }
+        if segments.is_empty() {
+            return colour;
+        }
+
+        segments.sort_by(|(segment1, _), (segment2, _)| segment2.end.total_cmp(&segment1.end));
+        segments
+            .into_iter()
+            .fold(colour, |colour, (segment, medium)| {
+                let transmittance =
+                    medium.transmittance(ray.distance_at(segment.end - segment.start));
+                colour * transmittance + self.shade_scattering(ray, segment, medium, rng)
+            })
+    }
+
+    fn shade_scattering(
+        &self,
+        ray: &Ray,
+        segment: Range<f64>,
+        medium: &Medium,
+        rng: &mut Rng,
+    ) -> Colour {
+        let Some(samples) = medium.light_samples else {
+            let transmittance = medium.transmittance(ray.distance_at(segment.end - segment.start));
+            return medium.colour * (1.0 - transmittance);
+        };
+
+        // one jittered sample in each of a run of equal steps, stopping where
+        // too little light would make it back to be seen
+        let samples = samples.max(1);
+        let end = segment
+            .end
+            .min(segment.start + ray.t_at(medium.visible_distance()));
+        let step = (end - segment.start) / samples as f64;
+        let scattered = (0..samples)
+            .map(|sample| {
+                let t = segment.start + (sample as f64 + rng.next_f64()) * step;
+                let point = ray.position(t);
+                let light = self
+                    .lights
+                    .iter()
+                    .filter(|light| !self.is_shadowed_point(light, point))
+                    .fold(Colour::new(0.0, 0.0, 0.0), |sum, light| {
+                        sum + light.intensity_at(point)
+                    });
+                light * medium.transmittance(ray.distance_at(t - segment.start))
+            })
+            .fold(Colour::BLACK, |sum, colour| sum + colour);
+        scattered * medium.colour * (medium.density * ray.distance_at(step))
+    }
+
+    // non-photorealistic counterpart to cast_ray; also returns the surface hit
+    // so that the camera can detect outlines
+    pub(crate) fn cast_ray_toon(
+        &self,
+        ray: Ray,
+        differentials: Option<&RayDifferentials>,
+        bands: usize,
+        object_ids: Option<&ObjectIds>,
+    ) -> (Colour, Option<SurfaceRecord>) {
+        let hit = stats::time(stats::Phase::PrimaryRays, || {
+            self.find_hit(&ray, self.camera_range(&ray))
+        })
+        .map(|hit| World::attach_differentials(hit, differentials));
+        match hit {
+            Some(computed_intersect) => {
+                let mut surface_colour = computed_intersect.material().emission
+                    + match &self.hemispheric_ambient {
+                        Some(ambient) => computed_intersect.shade_hemispheric_ambient(ambient),
+                        None => Colour::new(0.0, 0.0, 0.0),
+                    };
+                for light in self.lights_illuminating(computed_intersect.object()) {
+                    let shadowed = self.is_shadowed_point(light, computed_intersect.lit_point());
+                    surface_colour += match self.hemispheric_ambient {
+                        Some(_) => computed_intersect.shade_toon_direct(light, shadowed, bands),
+                        None => computed_intersect.shade_toon(light, shadowed, bands),
+                    };
+                }
+                let surface = World::surface_record(&ray, &computed_intersect, object_ids);
+                (surface_colour, Some(surface))
+            }
+            None => (self.shade_background(&ray), None),
+        }
+    }
+
+    // Integrator::Clay's counterpart to cast_ray: direct light only, with
+    // shadows, the background and any hemispheric ambient as usual
+    pub(crate) fn cast_ray_clay(
+        &self,
+        ray: Ray,
+        differentials: Option<&RayDifferentials>,
+        object_ids: Option<&ObjectIds>,
+    ) -> (Colour, Option<SurfaceRecord>) {
+        let clay = World::clay();
+        let hit = stats::time(stats::Phase::PrimaryRays, || {
+            self.find_hit_with(&ray, self.camera_range(&ray), |_| clay)
+        })
+        .map(|hit| World::attach_differentials(hit, differentials));
+        match hit {
+            Some(computed_intersect) => {
+                let mut surface_colour = match &self.hemispheric_ambient {
+                    Some(ambient) => computed_intersect.shade_hemispheric_ambient(ambient),
+                    None => Colour::BLACK,
+                };
+                for light in self.lights_illuminating(computed_intersect.object()) {
+                    let shadowed = self.is_shadowed_point(light, computed_intersect.lit_point());
+                    surface_colour += match self.hemispheric_ambient {
+                        Some(_) => computed_intersect.shade_direct(light, shadowed),
+                        None => computed_intersect.shade(light, shadowed),
+                    };
+                }
+                let surface = object_ids.map(|object_ids| {
+                    World::surface_record(&ray, &computed_intersect, Some(object_ids))
+                });
+                (surface_colour, surface)
+            }
+            None => (self.shade_background(&ray), None),
+        }
+    }
+
+    fn clay() -> &'static Material {
+        static CLAY: OnceLock<Material> = OnceLock::new();
+        CLAY.get_or_init(|| {
+            Material::builder()
+                .set_colour(Colour::new(0.8, 0.8, 0.8))
+                .set_specular(0.0)
+                .build()
+        })
+    }
+
+    // Integrator::Normals and Integrator::Depth, read off the first surface
+    pub(crate) fn cast_ray_normals(
+        &self,
+        ray: Ray,
+        differentials: Option<&RayDifferentials>,
+        object_ids: Option<&ObjectIds>,
+    ) -> (Colour, Option<SurfaceRecord>) {
+        let surface = self.cast_ray_surface(ray, differentials, object_ids);
+        let colour = match surface {
+            Some(surface) => {
+                let Vector { x, y, z } = surface.normal;
+                Colour::new(x + 1.0, y + 1.0, z + 1.0) * 0.5
+            }
+            None => Colour::BLACK,
+        };
+        (colour, surface)
+    }
+
+    pub(crate) fn cast_ray_depth(
+        &self,
+        ray: Ray,
+        differentials: Option<&RayDifferentials>,
+        max_distance: f64,
+        object_ids: Option<&ObjectIds>,
+    ) -> (Colour, Option<SurfaceRecord>) {
+        let surface = self.cast_ray_surface(ray, differentials, object_ids);
+        let colour = match surface {
+            Some(surface) => Colour::WHITE * (1.0 - surface.depth / max_distance).clamp(0.0, 1.0),
+            None => Colour::BLACK,
+        };
+        (colour, surface)
+    }
+
+    // the first surface along a primary ray, unshaded
+    fn cast_ray_surface(
+        &self,
+        ray: Ray,
+        differentials: Option<&RayDifferentials>,
+        object_ids: Option<&ObjectIds>,
+    ) -> Option<SurfaceRecord> {
+        let hit = stats::time(stats::Phase::PrimaryRays, || {
+            self.find_hit(&ray, self.camera_range(&ray))
+        })
+        .map(|hit| World::attach_differentials(hit, differentials))?;
+        Some(World::surface_record(&ray, &hit, object_ids))
+    }
+
+    fn surface_record(
+        ray: &Ray,
+        hit: &Intersect<dyn PrimitiveShape, Computed>,
+        object_ids: Option<&ObjectIds>,
+    ) -> SurfaceRecord {
+        SurfaceRecord {
+            depth: ray.distance_at(hit.t()),
+            normal: hit.normal(),
+            albedo: hit.surface_colour(),
+            object_id: object_ids.and_then(|object_ids| object_ids.id_of(hit.object())),
+        }
+    }
+
+    // IDs for every primitive in the world, for tagging surfaces in the
+    // auxiliary passes
+    pub(crate) fn object_ids(&self) -> ObjectIds {
+        ObjectIds::new(&self.objects)
+    }
+
+    fn lights_illuminating(
+        &'world self,
+        object: &'ray dyn PrimitiveShape,
+    ) -> impl Iterator<Item = &'world Light> + 'ray {
+        self.lights
+            .iter()
+            .filter(move |light| self.light_links.illuminates(light, object))
+    }
+
+    fn find_hit(
+        &'world self,
+        ray: &'ray Ray,
+        t_range: Range<f64>,
+    ) -> Option<Intersect<'ray, dyn PrimitiveShape, Computed>> {
+        self.find_hit_with(ray, t_range, |object| {
+            self.material_overrides.material_for(object)
+        })
+    }
+
+    // the nearest hit within the range, shaded with the given materials
+    fn find_hit_with<F>(
+        &'world self,
+        ray: &'ray Ray,
+        t_range: Range<f64>,
+        material_of: F,
+    ) -> Option<Intersect<'ray, dyn PrimitiveShape, Computed>>
+    where
+        F: Fn(&'ray (dyn PrimitiveShape + 'static)) -> &'ray Material,
+    {
+        let mut hit_register = self.intersect_ray_within(ray, t_range.clone());
+        let hit = hit_register.take_hit(t_range, self.surface_bias(), material_of);
+        hit_register.keep();
+        hit
+    }
+
+    fn attach_differentials(
+        hit: Intersect<'ray, dyn PrimitiveShape, Computed>,
+        differentials: Option<&RayDifferentials>,
+    ) -> Intersect<'ray, dyn PrimitiveShape, Computed> {
+        match differentials {
+            Some(differentials) => hit.with_differentials(differentials),
+            None => hit,
+        }
+    }
+
+    // the stretch of a camera ray (in t) along which surfaces can be seen
+    fn camera_range(&self, ray: &Ray) -> Range<f64> {
+        match &self.clipping {
+            Some(clipping) => ray.t_at(clipping.start)..ray.t_at(clipping.end),
+            None => 0.0..f64::INFINITY,
+        }
+    }
+
+    pub fn surface_bias(&self) -> f64 {
+        self.surface_bias.unwrap_or(EPSILON)
+    }
+
+    fn shade_background(&self, ray: &Ray) -> Colour {
+        match &self.background {
+            Some(background) => background.colour_at(Point::zero() + ray.direction.normalise()),
+            None => Colour::new(0.0, 0.0, 0.0),
         }
     }
 
+    // every intersection of the ray with every object, nearest first,
+    // including any behind the ray's origin (negative t)
+    pub fn intersections(
+        &'world self,
+        ray: &'ray Ray,
+    ) -> impl Iterator<Item = Intersect<'ray, dyn PrimitiveShape>> {
+        self.intersect_ray(ray).expose().into_iter()
+    }
+
+    // as intersections, keeping only those with t_range.start <= t < t_range.end
+    pub fn intersections_within(
+        &'world self,
+        ray: &'ray Ray,
+        t_range: Range<f64>,
+    ) -> impl Iterator<Item = Intersect<'ray, dyn PrimitiveShape>> {
+        self.intersect_ray(ray).clip(t_range).expose().into_iter()
+    }
+
+    // nearest intersection within the range, ready for shading
+    pub fn hit_within(
+        &'world self,
+        ray: &'ray Ray,
+        t_range: Range<f64>,
+    ) -> Option<Intersect<'ray, dyn PrimitiveShape, Computed>> {
+        self.find_hit(ray, t_range)
+    }
+
+    // whether anything at all lies along the ray within the range
+    pub fn is_occluded(&self, ray: &Ray, t_range: Range<f64>) -> bool {
+        self.accelerator().is_occluded(&self.objects, ray, t_range)
+    }
+
+    // distance from the point to the nearest surface of any object (see
+    // Shape::distance_to); None if there is nothing to measure to
+    pub fn distance_to(&self, point: Point) -> Option<f64> {
+        self.objects
+            .iter()
+            .filter_map(|object| object.distance_to(point))
+            .reduce(f64::min)
+    }
+
+    // as distance_to, negative inside any object
+    pub fn signed_distance_to(&self, point: Point) -> Option<f64> {
+        self.objects
+            .iter()
+            .filter_map(|object| object.signed_distance_to(point))
+            .reduce(f64::min)
+    }
+
+    // the intersections are found in the register kept on this thread, which
+    // should be handed back with HitRegister::keep once done with
     pub(crate) fn intersect_ray(
         &'world self,
         ray: &'ray Ray,
     ) -> HitRegister<'ray, dyn PrimitiveShape> {
-        let mut ray_hit_register = HitRegister::empty();
+        self.intersect_ray_within(ray, f64::NEG_INFINITY..f64::INFINITY)
+    }
 
-        for shape in &self.objects {
-            let shape_hit_register = shape.intersect_ray(ray, vec![]);
-            ray_hit_register.combine_registers(shape_hit_register);
+    // as intersect_ray, leaving out objects lying wholly outside the range
+    fn intersect_ray_within(
+        &'world self,
+        ray: &'ray Ray,
+        t_range: Range<f64>,
+    ) -> HitRegister<'ray, dyn PrimitiveShape> {
+        let mut ray_hit_register = HitRegister::kept();
+        let mut transform_stack = kept_transform_stack();
 
-            // match shape {
-            //     Shape::Primitive(primitive_shape) => {
-            //         let shape_hit_register = primitive_shape.intersect_ray(ray, vec![]);
-            //         ray_hit_register.combine_registers(shape_hit_register);
-            //     }
-            //     Shape::Group(group) => {
-            //         let shape_hit_register = group.intersect_ray(ray, vec![]);
-            //         ray_hit_register.combine_registers(shape_hit_register);
-            //     }
-            // }
-        }
+        self.accelerator().intersect_ray_into(
+            &self.objects,
+            ray,
+            t_range,
+            &mut transform_stack,
+            &mut ray_hit_register,
+        );
 
+        keep_transform_stack(transform_stack);
         ray_hit_register
     }
 
     fn is_shadowed_point(&self, light: &Light, point: Point) -> bool {
         let vector = light.position - point;
         let distance = vector.magnitude();
-        let direction = vector.normalise();
 
-        let ray = Ray::new(point, direction);
-        let hit_register = self.intersect_ray(&ray);
-
-        matches!(hit_register.finalise_hit(), Some(hit) if hit.t() < distance)
+        let ray = Ray::normalised(point, vector);
+        stats::time(stats::Phase::ShadowRays, || {
+            self.is_occluded(&ray, 0.0..distance)
+        })
     }
 
     fn shade_surface(
         &self,
         computed_intersect: &Intersect<dyn PrimitiveShape, Computed>,
     ) -> Colour {
-        let mut surface_colour = Colour::new(0.0, 0.0, 0.0);
-        for light in &self.lights {
-            surface_colour = surface_colour
-                + computed_intersect.shade(
-                    light,
-                    self.is_shadowed_point(light, computed_intersect.over_point()),
-                );
+        let mut surface_colour = computed_intersect.material().emission
+            + match &self.hemispheric_ambient {
+                Some(ambient) => computed_intersect.shade_hemispheric_ambient(ambient),
+                None => Colour::new(0.0, 0.0, 0.0),
+            };
+        for light in self.lights_illuminating(computed_intersect.object()) {
+            let shadowed = self.is_shadowed_point(light, computed_intersect.lit_point());
+            surface_colour += match self.hemispheric_ambient {
+                Some(_) => computed_intersect.shade_direct(light, shadowed),
+                None => computed_intersect.shade(light, shadowed),
+            };
         }
         surface_colour
     }
@@ -98,38 +675,136 @@ impl<'world: 'ray, 'ray> World {
         &self,
         computed_intersect: &Intersect<dyn PrimitiveShape, Computed>,
         depth_remaining: i32,
+        throughput: Colour,
+        rng: &mut Rng,
     ) -> Colour {
         if depth_remaining == 0 {
             return Colour::new(0.0, 0.0, 0.0);
         }
 
+        let material = computed_intersect.material();
+        let Some(bounce_depth) = Self::bounce_depth(depth_remaining, material.max_reflection_depth)
+        else {
+            return Colour::new(0.0, 0.0, 0.0);
+        };
+
         let reflected_ray = computed_intersect.reflected_ray();
-        let reflectance = computed_intersect.object().material().reflectance;
+        let reflectance = material.reflectance;
 
-        if reflectance == 0.0 {
+        let throughput = throughput * reflectance;
+        if reflectance == 0.0 || self.is_negligible(throughput) {
             return Colour::new(0.0, 0.0, 0.0);
         };
 
-        reflectance * self.shade_ray(&reflected_ray, depth_remaining - 1)
+        if material.roughness == 0.0 {
+            let differentials = computed_intersect.reflected_differentials();
+            return reflectance
+                * self.shade_ray(
+                    &reflected_ray,
+                    differentials.as_ref(),
+                    bounce_depth,
+                    throughput,
+                    rng,
+                );
+        }
+
+        // blurred reflection: average over directions around the mirror
+        // direction, leaving out any that would point into the surface
+        let normal = computed_intersect.normal();
+        let samples = Self::glossy_sample_count(depth_remaining);
+        let rays: Vec<Ray> =
+            glossy_directions(reflected_ray.direction, material.roughness, samples, rng)
+                .into_iter()
+                .filter(|direction| direction.dot(normal) > 0.0)
+                .map(|direction| Ray::new(reflected_ray.origin, direction))
+                .collect();
+        let reflected = match rays.len() {
+            0 => self.shade_ray(&reflected_ray, None, bounce_depth, throughput, rng),
+            count => {
+                rays.iter()
+                    .map(|ray| self.shade_ray(ray, None, bounce_depth, throughput, rng))
+                    .fold(Colour::BLACK, |sum, colour| sum + colour)
+                    / count as f64
+            }
+        };
+        reflectance * reflected
     }
 
     fn shade_refraction(
         &self,
         computed_intersect: &Intersect<dyn PrimitiveShape, Computed>,
         depth_remaining: i32,
+        throughput: Colour,
+        rng: &mut Rng,
     ) -> Colour {
         if depth_remaining == 0 {
             return Colour::new(0.0, 0.0, 0.0);
         }
 
-        let transparency = computed_intersect.object().material().transparency;
+        let material = computed_intersect.material();
+        let Some(bounce_depth) = Self::bounce_depth(depth_remaining, material.max_refraction_depth)
+        else {
+            return Colour::new(0.0, 0.0, 0.0);
+        };
+
+        let transparency = material.transparency;
+        let throughput = throughput * transparency;
 
-        if transparency == 0.0 {
+        if transparency == 0.0 || self.is_negligible(throughput) {
             return Colour::new(0.0, 0.0, 0.0);
         }
 
-        let (n1, n2) = computed_intersect.refraction_boundary();
+        // dispersive materials bend each channel by its own angle, so the
+        // channels part here and each carries on along its own ray; a ray that
+        // already carries a single channel refracts by that channel's index alone
+        let [red, green, blue] = computed_intersect.channel_refraction_boundaries();
+        if red == green && green == blue {
+            return transparency
+                * self.shade_refracted(
+                    computed_intersect,
+                    red,
+                    depth_remaining,
+                    bounce_depth,
+                    throughput,
+                    rng,
+                );
+        }
+        let channels = [
+            (red, Colour::new(1.0, 0.0, 0.0)),
+            (green, Colour::new(0.0, 1.0, 0.0)),
+            (blue, Colour::new(0.0, 0.0, 1.0)),
+        ];
+        let mut refracted = Colour::BLACK;
+        for (refraction_boundary, channel) in channels {
+            let channel_throughput = throughput * channel;
+            if channel_throughput == Colour::BLACK {
+                continue;
+            }
+            refracted += channel
+                * self.shade_refracted(
+                    computed_intersect,
+                    refraction_boundary,
+                    depth_remaining,
+                    bounce_depth,
+                    channel_throughput,
+                    rng,
+                );
+        }
+        transparency * refracted
+    }
 
+    // light arriving along the ray refracted across the given boundary, before
+    // scaling by the material's transparency
+    fn shade_refracted(
+        &self,
+        computed_intersect: &Intersect<dyn PrimitiveShape, Computed>,
+        (n1, n2): (f64, f64),
+        depth_remaining: i32,
+        bounce_depth: i32,
+        throughput: Colour,
+        rng: &mut Rng,
+    ) -> Colour {
+        let material = computed_intersect.material();
         let n_ratio = n1 / n2;
         let cos_i = computed_intersect.eyev().dot(computed_intersect.normal());
         let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
@@ -143,14 +818,297 @@ impl<'world: 'ray, 'ray> World {
             - computed_intersect.eyev() * n_ratio;
         let refracted_ray = Ray::new(computed_intersect.under_point(), refracted_direction);
 
-        transparency * self.shade_ray(&refracted_ray, depth_remaining - 1)
+        if material.roughness == 0.0 {
+            return self.shade_transmission(
+                computed_intersect,
+                &refracted_ray,
+                bounce_depth,
+                throughput,
+                rng,
+            );
+        }
+
+        // blurred refraction, keeping only directions that pass into the surface
+        let normal = computed_intersect.normal();
+        let samples = Self::glossy_sample_count(depth_remaining);
+        let rays: Vec<Ray> =
+            glossy_directions(refracted_ray.direction, material.roughness, samples, rng)
+                .into_iter()
+                .filter(|direction| direction.dot(normal) < 0.0)
+                .map(|direction| Ray::new(refracted_ray.origin, direction))
+                .collect();
+        match rays.len() {
+            0 => self.shade_transmission(
+                computed_intersect,
+                &refracted_ray,
+                bounce_depth,
+                throughput,
+                rng,
+            ),
+            count => {
+                rays.iter()
+                    .map(|ray| {
+                        self.shade_transmission(
+                            computed_intersect,
+                            ray,
+                            bounce_depth,
+                            throughput,
+                            rng,
+                        )
+                    })
+                    .fold(Colour::BLACK, |sum, colour| sum + colour)
+                    / count as f64
+            }
+        }
+    }
+
+    fn shade_transmission(
+        &self,
+        computed_intersect: &Intersect<dyn PrimitiveShape, Computed>,
+        refracted_ray: &Ray,
+        ray_depth: i32,
+        throughput: Colour,
+        rng: &mut Rng,
+    ) -> Colour {
+        // refraction is not followed by the differentials, so what is seen
+        // through transparent surfaces is point sampled
+        let (refracted, distance) = self.trace_ray(refracted_ray, None, ray_depth, throughput, rng);
+
+        // light entering the object is absorbed along the way to the next
+        // surface (Beer's law); the medium on the far side of an exit is not
+        // known, so exiting rays pass through unattenuated
+        let absorption = computed_intersect.material().absorption;
+        if computed_intersect.inside() || absorption == Colour::new(0.0, 0.0, 0.0) {
+            return refracted;
+        }
+        let transmittance = Colour::new(
+            (-absorption.red * distance).exp(),
+            (-absorption.green * distance).exp(),
+            (-absorption.blue * distance).exp(),
+        );
+        refracted * transmittance
+    }
+
+    // whether rays carrying so little of the final colour can be skipped
+    fn is_negligible(&self, throughput: Colour) -> bool {
+        let strongest = throughput.red.max(throughput.green).max(throughput.blue);
+        self.min_throughput
+            .is_some_and(|min_throughput| strongest < min_throughput)
+    }
+
+    // depth remaining after bouncing off a surface, or None if the material's
+    // own bounce limit (if any) has been used up
+    fn bounce_depth(depth_remaining: i32, limit: Option<u32>) -> Option<i32> {
+        match limit.and_then(|limit| i32::try_from(limit).ok()) {
+            Some(limit) => (limit > 0).then(|| i32::min(depth_remaining - 1, limit)),
+            None => Some(depth_remaining - 1),
+        }
+    }
+
+    // glossy rays fan out at every bounce, so fewer are spawned the deeper the
+    // bounce is to keep the total number of rays in check
+    fn glossy_sample_count(depth_remaining: i32) -> usize {
+        let bounce = (Self::MAX_RAYCAST_DEPTH - depth_remaining).clamp(0, 2) as u32;
+        Self::GLOSSY_SAMPLES >> (2 * bounce)
+    }
+}
+
+// Directions spread over a cone around the given direction, laid out on a
+// golden-angle spiral so that they cover the cone evenly. The spiral is turned
+// by a random angle so that neighbouring rays do not share the same pattern.
+// Roughness is the radius of the cone's base at unit distance along the
+// direction.
+fn glossy_directions(
+    direction: Vector,
+    roughness: f64,
+    samples: usize,
+    rng: &mut Rng,
+) -> Vec<Vector> {
+    let basis = OrthonormalBasis::from_w(direction);
+
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0_f64.sqrt());
+    let rotation = rng.next_f64() * std::f64::consts::TAU;
+    (0..samples)
+        .map(|sample| {
+            let radius = roughness * ((sample as f64 + 0.5) / samples as f64).sqrt();
+            let angle = rotation + sample as f64 * golden_angle;
+            (basis.w + basis.u * (radius * angle.cos()) + basis.v * (radius * angle.sin()))
+                .normalise()
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct WorldBuilder {
+    objects: Option<Vec<Shape>>,
+    lights: Option<Vec<Light>>,
+    background: Option<Box<dyn Pattern>>,
+    hemispheric_ambient: Option<HemisphericAmbient>,
+    material_overrides: Option<MaterialOverrides>,
+    light_links: Option<LightLinks>,
+    surface_bias: Option<f64>,
+    min_throughput: Option<f64>,
+    fog: Option<Medium>,
+    media: Option<Vec<ConstantMedium>>,
+    clipping: Option<Range<f64>>,
+    accelerator: Option<Box<dyn Accelerator>>,
+}
+
+impl WorldBuilder {
+    pub fn set_objects(mut self, objects: Vec<Shape>) -> WorldBuilder {
+        self.objects = Some(objects);
+        self
+    }
+
+    pub fn add_object(mut self, object: Shape) -> WorldBuilder {
+        match self.objects {
+            Some(ref mut objects) => {
+                objects.push(object);
+            }
+            None => self.objects = Some(vec![object]),
+        }
+        self
+    }
+
+    pub fn set_lights(mut self, lights: Vec<Light>) -> WorldBuilder {
+        self.lights = Some(lights);
+        self
+    }
+
+    pub fn add_light(mut self, light: Light) -> WorldBuilder {
+        match self.lights {
+            Some(ref mut lights) => {
+                lights.push(light);
+            }
+            None => self.lights = Some(vec![light]),
+        }
+        self
+    }
+
+    pub fn set_background(mut self, background: Box<dyn Pattern>) -> WorldBuilder {
+        self.background = Some(background);
+        self
+    }
+
+    // the sky as the background, with a light for the sun of the given
+    // luminance (see Sky::sun_light)
+    pub fn set_sky(self, sky: Sky, sun_luminance: f64) -> WorldBuilder {
+        let sun = sky.sun_light(sun_luminance);
+        self.set_background(Box::new(sky)).add_light(sun)
+    }
+
+    pub fn set_hemispheric_ambient(mut self, ambient: HemisphericAmbient) -> WorldBuilder {
+        self.hemispheric_ambient = Some(ambient);
+        self
+    }
+
+    pub fn set_material_overrides(mut self, overrides: MaterialOverrides) -> WorldBuilder {
+        self.material_overrides = Some(overrides);
+        self
+    }
+
+    pub fn set_light_links(mut self, links: LightLinks) -> WorldBuilder {
+        self.light_links = Some(links);
+        self
+    }
+
+    pub fn set_surface_bias(mut self, surface_bias: f64) -> WorldBuilder {
+        self.surface_bias = Some(surface_bias);
+        self
+    }
+
+    pub fn set_min_throughput(mut self, min_throughput: f64) -> WorldBuilder {
+        self.min_throughput = Some(min_throughput);
+        self
+    }
+
+    pub fn set_fog(mut self, fog: Medium) -> WorldBuilder {
+        self.fog = Some(fog);
+        self
+    }
+
+    pub fn set_clipping(mut self, near: f64, far: f64) -> WorldBuilder {
+        self.clipping = Some(near..far);
+        self
+    }
+
+    // the accelerator is built over the objects when the world is built, so
+    // it can be handed over empty (as InstanceTree::with_split or
+    // KdTree::default give)
+    pub fn set_accelerator(mut self, accelerator: Box<dyn Accelerator>) -> WorldBuilder {
+        self.accelerator = Some(accelerator);
+        self
+    }
+
+    pub fn add_medium(mut self, medium: ConstantMedium) -> WorldBuilder {
+        match self.media {
+            Some(ref mut media) => {
+                media.push(medium);
+            }
+            None => self.media = Some(vec![medium]),
+        }
+        self
+    }
+}
+
+impl Buildable for World {
+    type Builder = WorldBuilder;
+
+    fn builder() -> Self::Builder {
+        WorldBuilder::default()
+    }
+}
+
+impl ConsumingBuilder for WorldBuilder {
+    type Built = World;
+
+    // panics if the surface bias is not positive; try_build reports it instead
+    fn build(self) -> Self::Built {
+        self.try_build().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    fn try_build(self) -> Result<Self::Built, BuildError> {
+        if self
+            .surface_bias
+            .is_some_and(|surface_bias| surface_bias <= 0.0 || surface_bias.is_nan())
+        {
+            return Err(BuildError::InvalidValue {
+                field: "surface_bias",
+                reason: "must be positive".to_string(),
+            });
+        }
+        let objects = self.objects.unwrap_or_default();
+        let mut material_overrides = self.material_overrides.unwrap_or_default();
+        material_overrides.resolve(&objects);
+        let mut accelerator = self.accelerator;
+        if let Some(accelerator) = &mut accelerator {
+            stats::time(stats::Phase::AcceleratorBuild, || {
+                accelerator.build(&objects)
+            });
+        }
+        Ok(World {
+            objects,
+            lights: self.lights.unwrap_or_default(),
+            background: self.background,
+            hemispheric_ambient: self.hemispheric_ambient,
+            material_overrides,
+            light_links: self.light_links.unwrap_or_default(),
+            surface_bias: self.surface_bias,
+            min_throughput: self.min_throughput,
+            fog: self.fog,
+            media: self.media.unwrap_or_default(),
+            clipping: self.clipping,
+            accelerator,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::utils::approx_eq;
+    use crate::scenes::instances::InstanceTree;
+    use crate::scenes::kdtree::KdTree;
+    use crate::utils::{approx_eq, ApproxEq, BuildInto, SplitMethod};
 
     #[test]
     fn cast_ray() {
@@ -170,6 +1128,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let colour = world.cast_ray(ray);
@@ -179,6 +1138,161 @@ mod tests {
         approx_eq!(colour.blue, resulting_colour.blue);
     }
 
+    #[test]
+    fn clipping_cuts_away_camera_rays() {
+        let world_clipped = |near, far| {
+            World::builder()
+                .add_object(
+                    Sphere::builder()
+                        .set_material(Material::preset())
+                        .build_into(),
+                )
+                .add_light(Light::new(
+                    Point::new(-10.0, 10.0, -10.0),
+                    Colour::new(1.0, 1.0, 1.0),
+                ))
+                .set_clipping(near, far)
+                .build()
+        };
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let cutaway = world_clipped(4.5, f64::INFINITY);
+        // the near side of the sphere is cut away, showing its inside
+        assert_eq!(
+            cutaway
+                .cast_ray_surface(ray, None, None)
+                .map(|surface| surface.depth),
+            Some(6.0)
+        );
+        assert_ne!(cutaway.cast_ray(ray), Colour::new(0.0, 0.0, 0.0));
+        assert_eq!(
+            world_clipped(0.0, 3.0).cast_ray(ray),
+            Colour::new(0.0, 0.0, 0.0)
+        );
+
+        // rays bounced off surfaces see past the clipping range, whatever
+        // depth they were traced from
+        let mirror = Plane::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(0.0, -1.0, 0.0)))
+            .set_material(Material {
+                reflectance: 0.5,
+                ..Material::preset()
+            })
+            .build_into();
+        let world = World {
+            objects: vec![
+                Sphere::builder()
+                    .set_material(Material::preset())
+                    .build_into(),
+                mirror,
+            ],
+            lights: vec![Light::new(
+                Point::new(-10.0, 10.0, -10.0),
+                Colour::new(1.0, 1.0, 1.0),
+            )],
+            clipping: Some(0.0..0.5),
+            ..World::default()
+        };
+        let ray = Ray::new(
+            Point::new(0.0, 0.0, -3.0),
+            Vector::new(0.0, -2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0),
+        );
+        let computed_intersect = world.intersect_ray(&ray).finalise_hit().unwrap();
+        let reflected = |depth| {
+            world.shade_reflection(&computed_intersect, depth, Colour::WHITE, &mut Rng::new(0))
+        };
+        assert_ne!(reflected(10), Colour::BLACK);
+        assert_eq!(reflected(World::MAX_RAYCAST_DEPTH + 1), reflected(10));
+    }
+
+    #[test]
+    fn instance_tree_follows_moved_objects() {
+        let sphere_at = |x: f64| -> Shape {
+            Sphere::builder()
+                .set_material(Material::preset())
+                .set_frame_transformation(Transform::new(TransformKind::Translate(x, 0.0, 0.0)))
+                .build_into()
+        };
+        let mut world = World::builder()
+            .set_objects((0..5).map(|index| sphere_at(index as f64 * 3.0)).collect())
+            .add_object(Plane::builder().build_into())
+            .add_light(Light::new(
+                Point::new(-10.0, 10.0, -10.0),
+                Colour::new(1.0, 1.0, 1.0),
+            ))
+            .set_accelerator(Box::new(InstanceTree::with_split(
+                SplitMethod::SURFACE_AREA,
+            )))
+            .build();
+        let ray = Ray::new(Point::new(30.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let hit_depth = |world: &World| {
+            world
+                .hit_within(&ray, 0.0..f64::INFINITY)
+                .map(|hit| hit.t())
+        };
+        assert_eq!(hit_depth(&world), Some(5.0));
+
+        world.set_object(2, sphere_at(30.0));
+        assert_eq!(hit_depth(&world), Some(4.0));
+        world.remove_object(2);
+        assert_eq!(hit_depth(&world), Some(5.0));
+        world.add_object(sphere_at(30.0));
+        assert_eq!(hit_depth(&world), Some(4.0));
+    }
+
+    #[test]
+    fn accelerators_are_interchangeable() {
+        let world_with = |accelerator: Box<dyn Accelerator>| {
+            let spheres = (0..12).map(|index| {
+                Sphere::builder()
+                    .set_material(Material::preset())
+                    .set_frame_transformation(Transform::new(TransformKind::Translate(
+                        (index % 4) as f64 * 2.5,
+                        1.0 + (index / 4) as f64 * 2.5,
+                        0.0,
+                    )))
+                    .build_into()
+            });
+            World::builder()
+                .set_objects(spheres.collect())
+                .add_object(
+                    Plane::builder()
+                        .set_material(Material::preset())
+                        .build_into(),
+                )
+                .add_light(Light::new(
+                    Point::new(-10.0, 10.0, -10.0),
+                    Colour::new(1.0, 1.0, 1.0),
+                ))
+                .set_accelerator(accelerator)
+                .build()
+        };
+        let scanned = world_with(Box::new(LinearScan));
+        let rays: Vec<Ray> = (0..5)
+            .flat_map(|row| {
+                (0..5).map(move |column| {
+                    let target = Point::new(column as f64 * 2.0, row as f64 * 2.0, 0.0);
+                    let origin = Point::new(1.0, 3.0, -10.0);
+                    Ray::new(origin, (target - origin).normalise())
+                })
+            })
+            .collect();
+
+        for accelerated in [
+            world_with(Box::new(InstanceTree::with_split(
+                SplitMethod::SURFACE_AREA,
+            ))),
+            world_with(Box::<KdTree>::default()),
+        ] {
+            for ray in &rays {
+                assert_eq!(accelerated.cast_ray(*ray), scanned.cast_ray(*ray));
+                assert_eq!(
+                    accelerated.is_occluded(ray, 0.0..10.0),
+                    scanned.is_occluded(ray, 0.0..10.0)
+                );
+            }
+        }
+    }
+
     #[test]
     fn cast_ray_inside() {
         let s1 = Sphere::builder()
@@ -197,6 +1311,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
         let colour = world.cast_ray(ray);
@@ -224,12 +1339,86 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 1.0, 0.0));
         let resulting_colour = Colour::new(0.0, 0.0, 0.0);
         assert_eq!(world.cast_ray(ray), resulting_colour);
     }
 
+    #[test]
+    fn cast_ray_misses_into_background() {
+        let mut world = World::new(vec![], vec![]);
+        world.background = Some(Box::new(Solid::new(Colour::new(0.1, 0.2, 0.3))));
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(world.cast_ray(ray), Colour::new(0.1, 0.2, 0.3));
+    }
+
+    #[test]
+    fn fog_hides_the_background() {
+        let mut world = World::new(vec![], vec![]);
+        world.background = Some(Box::new(Solid::new(Colour::new(0.1, 0.2, 0.3))));
+        world.fog = Some(Medium::new(Colour::new(0.5, 0.5, 0.5), 0.1));
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(world.cast_ray(ray), Colour::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn medium_dims_what_lies_behind() {
+        // the ray crosses two units of the medium, which lets half through
+        let density = 2.0_f64.ln() / 2.0;
+        let world = World::builder()
+            .set_background(Box::new(Solid::new(Colour::new(1.0, 1.0, 1.0))))
+            .add_medium(ConstantMedium::new(
+                Sphere::builder().build_into(),
+                Medium::new(Colour::new(0.0, 0.0, 1.0), density),
+            ))
+            .build();
+        let colour = world.cast_ray(Ray::new(
+            Point::new(0.0, 0.0, -5.0),
+            Vector::new(0.0, 0.0, 1.0),
+        ));
+        approx_eq!(colour.red, 0.5);
+        approx_eq!(colour.blue, 1.0);
+    }
+
+    #[test]
+    fn lit_fog_is_shadowed() {
+        let world = |blocked: bool| {
+            let blocker: Shape = Plane::builder()
+                .set_frame_transformation(Transform::new(TransformKind::Translate(0.0, 5.0, 0.0)))
+                .build_into();
+            World::builder()
+                .set_objects(if blocked { vec![blocker] } else { vec![] })
+                .add_light(Light::new(
+                    Point::new(0.0, 10.0, 0.0),
+                    Colour::new(1.0, 1.0, 1.0),
+                ))
+                .set_fog(Medium::new(Colour::new(1.0, 1.0, 1.0), 0.2).with_light_samples(8))
+                .build()
+        };
+        let ray = Ray::new(Point::zero(), Vector::new(1.0, 0.0, 0.0));
+        assert!(world(false).cast_ray(ray).red > 0.0);
+        assert_eq!(world(true).cast_ray(ray), Colour::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn sky_lights_the_scene() {
+        let sky = Sky::new(Vector::new(0.0, 1.0, -1.0), 3.0, Transform::default());
+        let world = World::builder()
+            .add_object(
+                Plane::builder()
+                    .set_material(Material::preset())
+                    .build_into(),
+            )
+            .set_sky(sky.clone(), 1.0)
+            .build();
+        let up = Ray::new(Point::new(0.0, 1.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(world.cast_ray(up), sky.colour_at(Point::new(0.0, 1.0, 0.0)));
+        let down = Ray::new(Point::new(0.0, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert!(world.cast_ray(down).luminance() > 0.5);
+    }
+
     #[test]
     fn cast_ray_hits() {
         let s1 = Sphere::builder()
@@ -248,6 +1437,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let colour = world.cast_ray(ray);
@@ -257,6 +1447,36 @@ mod tests {
         approx_eq!(colour.blue, resulting_colour.blue);
     }
 
+    #[test]
+    fn differentials_filter_distant_checker_floor() {
+        let floor = Plane::builder()
+            .set_material(Material {
+                pattern: Box::new(Checker::new(
+                    Colour::new(1.0, 1.0, 1.0),
+                    Colour::new(0.0, 0.0, 0.0),
+                    Transform::default(),
+                )),
+                ambient: 1.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                ..Material::preset()
+            })
+            .build_into();
+        let light = Light::new(Point::new(0.0, 10.0, 0.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::new(vec![floor], vec![light]);
+
+        // the neighbouring pixels land two squares across and one further on
+        let origin = Point::new(0.0, 1.0, 0.0);
+        let ray = Ray::new(origin, Vector::new(0.5, -1.0, 100.5));
+        let differentials = RayDifferentials::new(
+            Ray::new(origin, Vector::new(2.5, -1.0, 100.5)),
+            Ray::new(origin, Vector::new(0.5, -0.99, 100.5)),
+        );
+        assert_eq!(world.cast_ray(ray), Colour::new(1.0, 1.0, 1.0));
+        let filtered = world.cast_ray_differential(ray, &differentials, &mut Rng::new(0));
+        assert!((filtered.red - 0.5).abs() < 0.01);
+    }
+
     #[test]
     fn cast_ray_intersects_behind() {
         let s1 = Sphere::builder()
@@ -308,6 +1528,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         assert!(!world.is_shadowed_point(&world.lights[0], Point::new(0.0, 10.0, 0.0)));
     }
@@ -330,6 +1551,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let point = Point::new(0.0, 10.0, 0.0);
         assert!(!world.is_shadowed_point(&world.lights[0], point));
@@ -353,6 +1575,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let point = Point::new(10.0, -10.0, 10.0);
         assert!(world.is_shadowed_point(&world.lights[0], point));
@@ -376,6 +1599,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let point = Point::new(-20.0, 20.0, -20.0);
         assert!(!world.is_shadowed_point(&world.lights[0], point));
@@ -399,6 +1623,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let point = Point::new(-2.0, 2.0, -2.0);
         assert!(!world.is_shadowed_point(&world.lights[0], point));
@@ -448,12 +1673,13 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
         let computed_intersect = world.intersect_ray(&ray).finalise_hit().unwrap();
         let resulting_colour = Colour::new(0.0, 0.0, 0.0);
         assert_eq!(
-            world.shade_reflection(&computed_intersect, 10),
+            world.shade_reflection(&computed_intersect, 10, Colour::WHITE, &mut Rng::new(0)),
             resulting_colour
         );
     }
@@ -483,19 +1709,209 @@ mod tests {
         let world = World {
             objects: vec![s1, s2, s3],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(
             Point::new(0.0, 0.0, -3.0),
             Vector::new(0.0, -2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0),
         );
         let computed_intersect = world.intersect_ray(&ray).finalise_hit().unwrap();
-        let colour = world.shade_reflection(&computed_intersect, 10);
+        let colour =
+            world.shade_reflection(&computed_intersect, 10, Colour::WHITE, &mut Rng::new(0));
         let resulting_colour = Colour::new(0.190331, 0.237913, 0.142748);
         approx_eq!(colour.red, resulting_colour.red);
         approx_eq!(colour.green, resulting_colour.green);
         approx_eq!(colour.blue, resulting_colour.blue);
     }
 
+    #[test]
+    fn negligible_reflections_are_skipped() {
+        let mirror = Plane::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(0.0, -1.0, 0.0)))
+            .set_material(Material {
+                reflectance: 0.5,
+                ..Material::preset()
+            })
+            .build_into();
+        let world = World {
+            objects: vec![
+                Sphere::builder()
+                    .set_material(Material::preset())
+                    .build_into(),
+                mirror,
+            ],
+            lights: vec![Light::new(
+                Point::new(-10.0, 10.0, -10.0),
+                Colour::new(1.0, 1.0, 1.0),
+            )],
+            min_throughput: Some(0.1),
+            ..World::default()
+        };
+        let ray = Ray::new(
+            Point::new(0.0, 0.0, -3.0),
+            Vector::new(0.0, -2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0),
+        );
+        let computed_intersect = world.intersect_ray(&ray).finalise_hit().unwrap();
+
+        let reflected = |throughput| {
+            world.shade_reflection(&computed_intersect, 10, throughput, &mut Rng::new(0))
+        };
+        // a ray arriving at full strength still reflects, but one that would
+        // end up as a twentieth of the image is not worth tracing
+        assert_ne!(reflected(Colour::WHITE), Colour::new(0.0, 0.0, 0.0));
+        assert_eq!(reflected(Colour::WHITE * 0.1), Colour::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn enumerate_all_intersections_in_order() {
+        let world = World::builder()
+            .add_object(
+                Sphere::builder()
+                    .set_name("far")
+                    .set_frame_transformation(Transform::new(TransformKind::Translate(
+                        0.0, 0.0, 5.0,
+                    )))
+                    .build_into(),
+            )
+            .add_object(Sphere::builder().set_name("near").build_into())
+            .build();
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let intersections: Vec<(f64, Option<&str>)> = world
+            .intersections(&ray)
+            .map(|intersect| (intersect.t(), intersect.object().name()))
+            .collect();
+        assert_eq!(
+            intersections,
+            vec![
+                (4.0, Some("near")),
+                (6.0, Some("near")),
+                (9.0, Some("far")),
+                (11.0, Some("far")),
+            ]
+        );
+
+        let inside = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(world.intersections(&inside).next().unwrap().t(), -1.0);
+    }
+
+    #[test]
+    fn query_intersections_within_t_range() {
+        let world = World::builder()
+            .add_object(
+                Sphere::builder()
+                    .set_name("far")
+                    .set_frame_transformation(Transform::new(TransformKind::Translate(
+                        0.0, 0.0, 5.0,
+                    )))
+                    .build_into(),
+            )
+            .add_object(Sphere::builder().set_name("near").build_into())
+            .build();
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+
+        let ts: Vec<f64> = world
+            .intersections_within(&ray, 5.0..11.0)
+            .map(|intersect| intersect.t())
+            .collect();
+        assert_eq!(ts, vec![6.0, 9.0]);
+
+        let hit = world.hit_within(&ray, 7.0..f64::INFINITY).unwrap();
+        assert_eq!(hit.t(), 9.0);
+        assert_eq!(hit.object().name(), Some("far"));
+        assert!(world.hit_within(&ray, 12.0..20.0).is_none());
+
+        assert!(world.is_occluded(&ray, 0.0..5.0));
+        assert!(!world.is_occluded(&ray, 0.0..4.0));
+        assert!(!world.is_occluded(&ray, 6.5..8.5));
+    }
+
+    #[test]
+    fn surface_bias_offsets_hit_points() {
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let default_world = World::builder()
+            .add_object(Sphere::builder().build_into())
+            .build();
+        let hit = default_world.hit_within(&ray, 0.0..f64::INFINITY).unwrap();
+        approx_eq!(hit.over_point().z, -1.0 - EPSILON);
+
+        let biased_world = World::builder()
+            .add_object(Sphere::builder().build_into())
+            .set_surface_bias(0.01)
+            .build();
+        assert_eq!(biased_world.surface_bias(), 0.01);
+        let hit = biased_world.hit_within(&ray, 0.0..f64::INFINITY).unwrap();
+        approx_eq!(hit.over_point().z, -1.01);
+        approx_eq!(hit.under_point().z, -0.99);
+
+        assert!(World::builder().set_surface_bias(0.0).try_build().is_err());
+    }
+
+    #[test]
+    fn material_reflection_depth_limits_bounces() {
+        let world = |limit: u32| {
+            let s1 = Sphere::builder()
+                .set_material(Material::preset())
+                .build_into();
+            let s2 = Plane::builder()
+                .set_frame_transformation(Transform::new(TransformKind::Translate(0.0, -1.0, 0.0)))
+                .set_material(Material {
+                    reflectance: 0.5,
+                    max_reflection_depth: Some(limit),
+                    ..Material::preset()
+                })
+                .build_into();
+            let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+            World {
+                objects: vec![s1, s2],
+                lights: vec![light],
+                ..World::default()
+            }
+        };
+        let ray = Ray::new(
+            Point::new(0.0, 0.0, -3.0),
+            Vector::new(0.0, -2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0),
+        );
+
+        let unreflected = world(0);
+        let computed_intersect = unreflected.intersect_ray(&ray).finalise_hit().unwrap();
+        assert_eq!(
+            unreflected.shade_reflection(&computed_intersect, 10, Colour::WHITE, &mut Rng::new(0)),
+            Colour::new(0.0, 0.0, 0.0)
+        );
+
+        // a single bounce is all this scene needs
+        let once = world(1);
+        let computed_intersect = once.intersect_ray(&ray).finalise_hit().unwrap();
+        assert_ne!(
+            once.shade_reflection(&computed_intersect, 10, Colour::WHITE, &mut Rng::new(0)),
+            Colour::new(0.0, 0.0, 0.0)
+        );
+        assert_eq!(once.cast_ray(ray), world(u32::MAX).cast_ray(ray));
+    }
+
+    #[test]
+    fn unlimited_materials_bounce_to_max_depth() {
+        let mirror = |y: f64| -> Shape {
+            Plane::builder()
+                .set_frame_transformation(Transform::new(TransformKind::Translate(0.0, y, 0.0)))
+                .set_material(Material {
+                    reflectance: 1.0,
+                    ..Material::preset()
+                })
+                .build_into()
+        };
+        let world = World::new(vec![mirror(-1.0), mirror(1.0)], vec![]);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+
+        // the ray bounces between the mirrors until the world's depth runs out
+        let (_, stats) = stats::collect(|| world.cast_ray(ray));
+        assert_eq!(stats.primary_ray_count, 1);
+        assert_eq!(
+            stats.secondary_ray_count,
+            World::MAX_RAYCAST_DEPTH as u64 - 1
+        );
+    }
+
     #[test]
     fn shade_hit_reflective_material() {
         let s1 = Sphere::builder()
@@ -521,6 +1937,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2, s3],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(
             Point::new(0.0, 0.0, -3.0),
@@ -533,6 +1950,59 @@ mod tests {
         approx_eq!(colour.blue, resulting_colour.blue);
     }
 
+    #[test]
+    fn thin_film_tints_reflections() {
+        // a black, film-coated mirror under a white sky, with no lights
+        let plane = Plane::builder()
+            .set_material(Material {
+                pattern: Box::new(Solid::new(Colour::new(0.0, 0.0, 0.0))),
+                reflectance: 1.0,
+                refractive_index: 1.5,
+                thin_film: Some(ThinFilm::new(510.0 / (4.0 * 1.33), 1.33)),
+                ..Material::preset()
+            })
+            .build_into();
+        let world = World {
+            objects: vec![plane],
+            background: Some(Box::new(Solid::new(Colour::new(1.0, 1.0, 1.0)))),
+            ..World::default()
+        };
+        let ray = Ray::new(Point::new(0.0, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let colour = world.cast_ray(ray);
+        assert_eq!(
+            colour,
+            ThinFilm::new(510.0 / (4.0 * 1.33), 1.33).reflectance(1.0, 1.0, 1.5)
+        );
+        assert!(colour.red != colour.green && colour.green != colour.blue);
+    }
+
+    #[test]
+    fn backface_handling() {
+        // seen from below, a card lit from above
+        let world_with = |backface| World {
+            objects: vec![Plane::builder()
+                .set_material(Material {
+                    backface,
+                    ..Material::preset()
+                })
+                .build_into()],
+            lights: vec![Light::new(
+                Point::new(0.0, 10.0, 0.0),
+                Colour::new(1.0, 1.0, 1.0),
+            )],
+            background: Some(Box::new(Solid::new(Colour::new(0.0, 0.0, 1.0)))),
+            ..World::default()
+        };
+        let ray = Ray::new(Point::new(0.0, -1.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        let flipped = world_with(Backface::Flip).cast_ray(ray);
+        let kept = world_with(Backface::Keep).cast_ray(ray);
+        let culled = world_with(Backface::Cull).cast_ray(ray);
+        // turned towards the eye, the card faces away from the light
+        assert_eq!(flipped, Colour::new(0.1, 0.1, 0.1));
+        assert!(kept.red > 0.9);
+        assert_eq!(culled, Colour::new(0.0, 0.0, 1.0));
+    }
+
     #[test]
     fn shade_hit_mutually_reflective_surfaces() {
         let s1 = Plane::builder()
@@ -553,6 +2023,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
         // the following method call should terminate in finite time
@@ -577,12 +2048,13 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let computed_intersect = world.intersect_ray(&ray).finalise_hit().unwrap();
         let resulting_colour = Colour::new(0.0, 0.0, 0.0);
         assert_eq!(
-            world.shade_refraction(&computed_intersect, 10),
+            world.shade_refraction(&computed_intersect, 10, Colour::WHITE, &mut Rng::new(0)),
             resulting_colour
         );
     }
@@ -607,6 +2079,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(
             Point::new(0.0, 0.0, 2.0_f64.sqrt() / 2.0),
@@ -615,12 +2088,12 @@ mod tests {
         let computed_intersect = world.intersect_ray(&ray).finalise_hit().unwrap();
         let resulting_colour = Colour::new(0.0, 0.0, 0.0);
         assert_eq!(
-            world.shade_refraction(&computed_intersect, 10),
+            world.shade_refraction(&computed_intersect, 10, Colour::WHITE, &mut Rng::new(0)),
             resulting_colour
         );
     }
 
-    #[derive(Debug)]
+    #[derive(Clone, Debug)]
     struct TestPattern {
         frame_transformation: Transform,
     }
@@ -667,16 +2140,65 @@ mod tests {
         let world = World {
             objects: vec![s1, s2],
             lights: vec![light],
+            ..World::default()
         };
         let ray = Ray::new(Point::new(0.0, 0.0, 0.1), Vector::new(0.0, 1.0, 0.0));
         let computed_intersect = world.intersect_ray(&ray).finalise_hit().unwrap();
-        let colour = world.shade_refraction(&computed_intersect, 10);
+        let colour =
+            world.shade_refraction(&computed_intersect, 10, Colour::WHITE, &mut Rng::new(0));
         let resulting_colour = Colour::new(0.0, 0.998884, 0.047216);
         approx_eq!(colour.red, resulting_colour.red);
         approx_eq!(colour.green, resulting_colour.green);
         approx_eq!(colour.blue, resulting_colour.blue);
     }
 
+    #[test]
+    fn dispersion_refracts_each_channel_separately() {
+        let world_with = |glass: Material| World {
+            objects: vec![
+                Sphere::builder()
+                    .set_material(Material {
+                        pattern: Box::new(TestPattern::new(Transform::default())),
+                        ambient: 1.0,
+                        ..Material::preset()
+                    })
+                    .build_into(),
+                Sphere::builder()
+                    .set_frame_transformation(Transform::new(TransformKind::Scale(0.5, 0.5, 0.5)))
+                    .set_material(glass)
+                    .build_into(),
+            ],
+            lights: vec![Light::new(
+                Point::new(-10.0, 10.0, -10.0),
+                Colour::new(1.0, 1.0, 1.0),
+            )],
+            ..World::default()
+        };
+        let refracted_carrying = |world: &World, throughput| {
+            let ray = Ray::new(Point::new(0.0, 0.0, 0.1), Vector::new(0.0, 1.0, 0.0));
+            let computed_intersect = world.intersect_ray(&ray).finalise_hit().unwrap();
+            world.shade_refraction(&computed_intersect, 10, throughput, &mut Rng::new(0))
+        };
+        let refracted = |world: &World| refracted_carrying(world, Colour::WHITE);
+        let glass = Material {
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Material::preset()
+        };
+        let plain = refracted(&world_with(glass.clone()));
+        let prism_world = world_with(Material {
+            dispersion: Some([1.4, 1.5, 1.6]),
+            ..glass
+        });
+        let prism = refracted(&prism_world);
+        // green bends as before, while red and blue land elsewhere on the pattern
+        assert_eq!(prism.green, plain.green);
+        assert!((prism.red - plain.red).abs() > 1e-3 || (prism.blue - plain.blue).abs() > 1e-3);
+        // a ray already parted out carries on with its own channel only
+        let red = refracted_carrying(&prism_world, Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(red, Colour::new(prism.red, 0.0, 0.0));
+    }
+
     #[test]
     fn refracted_colour() {
         let s1 = Sphere::builder()
@@ -712,6 +2234,7 @@ mod tests {
         let world = World {
             objects: vec![s1, s2, s3, s4],
             lights: vec![light],
+            ..World::default()
         };
 
         let ray = Ray::new(
@@ -725,6 +2248,44 @@ mod tests {
         approx_eq!(colour.blue, resulting_colour.blue);
     }
 
+    #[test]
+    fn material_refraction_depth_limits_bounces() {
+        let glass = |limit: Option<u32>| {
+            Sphere::builder()
+                .set_material(Material {
+                    transparency: 1.0,
+                    refractive_index: 1.5,
+                    max_refraction_depth: limit,
+                    ..Material::preset()
+                })
+                .build_into()
+        };
+        let world = |limit: Option<u32>| World {
+            objects: vec![glass(limit)],
+            lights: vec![Light::new(
+                Point::new(-10.0, 10.0, -10.0),
+                Colour::new(1.0, 1.0, 1.0),
+            )],
+            background: Some(Box::new(Solid::new(Colour::new(1.0, 1.0, 1.0)))),
+            ..World::default()
+        };
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+
+        let opaque = world(Some(0));
+        let computed_intersect = opaque.intersect_ray(&ray).finalise_hit().unwrap();
+        assert_eq!(
+            opaque.shade_refraction(&computed_intersect, 10, Colour::WHITE, &mut Rng::new(0)),
+            Colour::new(0.0, 0.0, 0.0)
+        );
+        // entering takes one bounce and leaving takes another
+        let clear = world(Some(2));
+        let computed_intersect = clear.intersect_ray(&ray).finalise_hit().unwrap();
+        assert_eq!(
+            clear.shade_refraction(&computed_intersect, 10, Colour::WHITE, &mut Rng::new(0)),
+            world(None).shade_refraction(&computed_intersect, 10, Colour::WHITE, &mut Rng::new(0))
+        );
+    }
+
     #[test]
     fn intersection_retrieves_interpolated_normal() {
         let smooth_triangle = SmoothTriangle::builder()
@@ -747,4 +2308,381 @@ mod tests {
         approx_eq!(normal.y, resulting_normal.y);
         approx_eq!(normal.z, resulting_normal.z);
     }
+
+    #[test]
+    fn build_world() {
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = World::builder()
+            .add_object(Sphere::builder().build_into())
+            .add_object(Plane::builder().build_into())
+            .add_light(light)
+            .build();
+        assert_eq!(world.objects().count(), 2);
+        assert_eq!(world.lights().collect::<Vec<_>>(), vec![&light]);
+        assert!(world.background.is_none());
+    }
+
+    #[test]
+    fn add_and_remove_objects() {
+        let mut world = World::builder().build();
+        assert_eq!(world.objects().count(), 0);
+        world.add_object(Sphere::builder().build_into());
+        world.add_object(Group::builder().build_into());
+        world.add_light(Light::new(Point::zero(), Colour::new(1.0, 1.0, 1.0)));
+        assert!(matches!(world.remove_object(0), Shape::Primitive(_)));
+        assert!(matches!(world.objects().next(), Some(Shape::Group(_))));
+        assert_eq!(world.objects().count(), 1);
+        assert_eq!(world.lights().count(), 1);
+    }
+
+    #[test]
+    fn hemispheric_ambient_lights_unlit_side() {
+        // light from behind the sphere, so only ambient light reaches the eye
+        let light = Light::new(Point::new(0.0, 0.0, 10.0), Colour::new(1.0, 1.0, 1.0));
+        let ambient =
+            HemisphericAmbient::new(Colour::new(0.0, 0.0, 1.0), Colour::new(1.0, 0.0, 0.0));
+        let world = World::builder()
+            .add_object(
+                Sphere::builder()
+                    .set_material(Material::preset())
+                    .build_into(),
+            )
+            .add_light(light)
+            .set_hemispheric_ambient(ambient)
+            .build();
+        let up = world.cast_ray(Ray::new(
+            Point::new(0.0, 10.0, 0.0),
+            Vector::new(0.0, -1.0, 0.0),
+        ));
+        approx_eq!(up.red, 0.0);
+        approx_eq!(up.blue, 0.1);
+        let down = world.cast_ray(Ray::new(
+            Point::new(0.0, -10.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        ));
+        approx_eq!(down.red, 0.1);
+        approx_eq!(down.blue, 0.0);
+    }
+
+    #[test]
+    fn find_named_objects_and_lights() {
+        let mut world = World::builder()
+            .add_object(Plane::builder().set_name("floor").build_into())
+            .add_object(
+                Group::builder()
+                    .set_name("furniture")
+                    .add_object(
+                        Sphere::builder()
+                            .set_name("ball")
+                            .set_material(Material::preset())
+                            .build_into(),
+                    )
+                    .build_into(),
+            )
+            .add_light(Light::new(Point::zero(), Colour::new(1.0, 1.0, 1.0)).with_name("key"))
+            .build();
+        assert!(matches!(
+            world.find_object("furniture"),
+            Some(Shape::Group(_))
+        ));
+        assert_eq!(world.find_object("ball").unwrap().name(), Some("ball"));
+        assert!(world.find_object("ceiling").is_none());
+
+        *world.find_object_mut("floor").unwrap() = Cube::builder().set_name("floor").build_into();
+        let floor = world.find_object("floor").unwrap();
+        assert!(floor.bounds().bounding_box().is_bounded());
+
+        world.find_light_mut("key").unwrap().intensity = Colour::new(0.5, 0.5, 0.5);
+        assert_eq!(
+            world.find_light("key").unwrap().intensity,
+            Colour::new(0.5, 0.5, 0.5)
+        );
+        assert!(world.find_light("fill").is_none());
+    }
+
+    #[test]
+    fn material_overrides_replace_matching_materials() {
+        let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let world = |overrides: MaterialOverrides| {
+            World::builder()
+                .add_object(
+                    Sphere::builder()
+                        .set_name("glass_ball")
+                        .set_material(Material::preset())
+                        .build_into(),
+                )
+                .add_light(light)
+                .set_material_overrides(overrides)
+                .build()
+        };
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let unlit = Material::builder()
+            .set_colour(Colour::new(0.5, 0.0, 0.0))
+            .set_ambient(1.0)
+            .set_diffuse(0.0)
+            .set_specular(0.0)
+            .build();
+
+        let original = world(MaterialOverrides::new()).cast_ray(ray);
+        let overridden = world(MaterialOverrides::new().with_override("glass_*", unlit));
+        assert_eq!(overridden.cast_ray(ray), Colour::new(0.5, 0.0, 0.0));
+        assert_ne!(original, Colour::new(0.5, 0.0, 0.0));
+        // the scene itself keeps its material
+        assert_eq!(
+            overridden
+                .find_object("glass_ball")
+                .map(|object| match object {
+                    Shape::Primitive(primitive) => primitive.material() == &Material::preset(),
+                    _ => false,
+                }),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn material_overrides_apply_to_group_contents() {
+        let unlit = Material::builder()
+            .set_colour(Colour::new(0.5, 0.0, 0.0))
+            .set_ambient(1.0)
+            .set_diffuse(0.0)
+            .set_specular(0.0)
+            .build();
+        let mut world = World::builder()
+            .add_object(
+                Group::builder()
+                    .set_name("glass")
+                    .add_object(Sphere::builder().build_into())
+                    .build_into(),
+            )
+            .add_light(Light::new(
+                Point::new(-10.0, 10.0, -10.0),
+                Colour::new(1.0, 1.0, 1.0),
+            ))
+            .set_material_overrides(MaterialOverrides::new().with_override("glass", unlit))
+            .build();
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(world.cast_ray(ray), Colour::new(0.5, 0.0, 0.0));
+
+        // objects added later are matched too
+        world.remove_object(0);
+        world.add_object(
+            Group::builder()
+                .set_name("glass")
+                .add_object(Sphere::builder().build_into())
+                .build_into(),
+        );
+        assert_eq!(world.cast_ray(ray), Colour::new(0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn move_named_object_twice() {
+        let mut world = World::builder()
+            .add_object(
+                Group::builder()
+                    .set_name("table")
+                    .add_object(Sphere::builder().set_name("ball").build_into())
+                    .build_into(),
+            )
+            .set_accelerator(Box::new(InstanceTree::with_split(SplitMethod::Median)))
+            .build();
+        let step = Transform::new(TransformKind::Translate(5.0, 0.0, 0.0));
+        for x in [5.0, 10.0] {
+            assert!(world.apply_transform_to("ball", &step));
+            // the ball is moved where it is rather than wrapped in a new group
+            let Some(Shape::Group(table)) = world.find_object("table") else {
+                panic!("expected the table group")
+            };
+            assert!(matches!(table.objects()[..], [Shape::Primitive(_)]));
+            assert!(matches!(
+                world.find_object_mut("ball"),
+                Some(Shape::Primitive(_))
+            ));
+
+            let hit = Ray::new(Point::new(x, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+            let miss = Ray::new(Point::new(x - 5.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+            assert!(world.hit_within(&hit, 0.0..f64::INFINITY).is_some());
+            assert!(world.hit_within(&miss, 0.0..f64::INFINITY).is_none());
+        }
+        assert!(!world.apply_transform_to("chair", &step));
+    }
+
+    #[test]
+    fn light_links_skip_unlinked_lights() {
+        let world = |links: LightLinks| {
+            World::builder()
+                .add_object(
+                    Sphere::builder()
+                        .set_name("ball")
+                        .set_material(Material::preset())
+                        .build_into(),
+                )
+                .add_light(
+                    Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0))
+                        .with_name("key"),
+                )
+                .add_light(
+                    Light::new(Point::new(10.0, 10.0, -10.0), Colour::new(0.5, 0.5, 0.5))
+                        .with_name("fill"),
+                )
+                .set_light_links(links)
+                .build()
+        };
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+
+        let both = world(LightLinks::new()).cast_ray(ray);
+        let key_only = world(LightLinks::new().exclude("fill", "ball")).cast_ray(ray);
+        let neither = world(LightLinks::new().include("*", "floor")).cast_ray(ray);
+        assert_eq!(neither, Colour::new(0.0, 0.0, 0.0));
+        assert!(key_only.red > 0.0 && key_only.red < both.red);
+    }
+
+    #[test]
+    fn emissive_surface_glows_without_lights() {
+        let world = World::builder()
+            .add_object(
+                Sphere::builder()
+                    .set_material(
+                        Material::builder()
+                            .set_emission(Colour::new(0.2, 0.8, 0.4))
+                            .build(),
+                    )
+                    .build_into(),
+            )
+            .build();
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(world.cast_ray(ray), Colour::new(0.2, 0.8, 0.4));
+    }
+
+    #[test]
+    fn absorption_attenuates_light_through_medium() {
+        let tinted_glass = Material::builder()
+            .set_ambient(0.0)
+            .set_diffuse(0.0)
+            .set_specular(0.0)
+            .set_transparency(1.0)
+            .set_refractive_index(1.0)
+            .set_absorption(Colour::new(1.0, 0.0, 0.5))
+            .build();
+        let world = World::builder()
+            .add_object(Sphere::builder().set_material(tinted_glass).build_into())
+            .set_background(Box::new(Solid::new(Colour::new(1.0, 1.0, 1.0))))
+            .build();
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let colour = world.cast_ray(ray);
+        // the ray travels through the full diameter of the sphere
+        approx_eq!(colour.red, (-2.0_f64).exp());
+        approx_eq!(colour.green, 1.0);
+        approx_eq!(colour.blue, (-1.0_f64).exp());
+    }
+
+    #[test]
+    fn unnormalised_rays_shade_alike() {
+        let tinted_glass = Material::builder()
+            .set_transparency(1.0)
+            .set_refractive_index(1.5)
+            .set_absorption(Colour::new(1.0, 0.0, 0.5))
+            .build();
+        let world = World::builder()
+            .add_object(Sphere::builder().set_material(tinted_glass).build_into())
+            .add_light(Light::new(
+                Point::new(-10.0, 10.0, -10.0),
+                Colour::new(1.0, 1.0, 1.0),
+            ))
+            .set_background(Box::new(Solid::new(Colour::new(1.0, 1.0, 1.0))))
+            .set_fog(Medium::new(Colour::new(0.5, 0.5, 0.5), 0.1))
+            .set_clipping(1.0, 20.0)
+            .build();
+        let origin = Point::new(0.2, 0.1, -5.0);
+        let direction = Vector::new(0.0, 0.0, 1.0);
+        let normalised = Ray::normalised(origin, direction);
+        let stretched = Ray::new(origin, direction * 4.0);
+
+        let colour = world.cast_ray(normalised);
+        assert!(colour.approx_eq(&world.cast_ray(stretched)));
+        let depth = |ray| world.cast_ray_surface(ray, None, None).unwrap().depth;
+        approx_eq!(depth(stretched), depth(normalised));
+    }
+
+    #[test]
+    fn glossy_directions_stay_within_cone() {
+        let direction = Vector::new(0.0, 0.0, 1.0);
+        let directions = glossy_directions(direction, 0.5, 16, &mut Rng::new(1));
+        assert_eq!(directions.len(), 16);
+        let min_cos = 1.0 / (1.0 + 0.5_f64.powi(2)).sqrt();
+        for glossy_direction in &directions {
+            approx_eq!(glossy_direction.magnitude(), 1.0);
+            assert!(glossy_direction.dot(direction) >= min_cos - 1e-9);
+        }
+        assert_eq!(
+            glossy_directions(direction, 0.5, 16, &mut Rng::new(1)),
+            directions
+        );
+        assert_ne!(
+            glossy_directions(direction, 0.5, 16, &mut Rng::new(2)),
+            directions
+        );
+    }
+
+    #[test]
+    fn glossy_reflection_blurs_mirror_image() {
+        // a mirror floor reflecting a background striped across x = 0, right
+        // where the mirror direction points; a sharp mirror picks one of the two
+        // stripes while a rough one blends them
+        let mirror = |roughness: f64| {
+            Material::builder()
+                .set_colour(Colour::new(0.0, 0.0, 0.0))
+                .set_ambient(0.0)
+                .set_diffuse(0.0)
+                .set_specular(0.0)
+                .set_reflectance(1.0)
+                .set_roughness(roughness)
+                .build()
+        };
+        let background = Stripe::new(
+            Colour::new(0.0, 0.0, 0.0),
+            Colour::new(1.0, 1.0, 1.0),
+            Transform::default(),
+        );
+        let world = |roughness: f64| {
+            World::builder()
+                .add_object(
+                    Plane::builder()
+                        .set_material(mirror(roughness))
+                        .build_into(),
+                )
+                .set_background(Box::new(background.clone()))
+                .build()
+        };
+        let ray = Ray::new(
+            Point::new(0.0, 1.0, -1.0),
+            Vector::new(0.0, -1.0, 1.0).normalise(),
+        );
+        let sharp = world(0.0).cast_ray(ray);
+        let glossy = world(0.5).cast_ray(ray);
+        assert!(sharp.red == 0.0 || sharp.red == 1.0);
+        assert!(glossy.red > 0.0 && glossy.red < 1.0);
+    }
+
+    #[test]
+    fn distance_to_nearest_surface() {
+        assert_eq!(World::new(vec![], vec![]).distance_to(Point::zero()), None);
+
+        let sphere = Sphere::builder()
+            .set_frame_transformation(Transform::new(TransformKind::Translate(0.0, 0.0, 5.0)))
+            .build_into();
+        let triangle = Triangle::builder()
+            .set_vertices([
+                Point::new(-1.0, -1.0, -2.0),
+                Point::new(1.0, -1.0, -2.0),
+                Point::new(0.0, 1.0, -2.0),
+            ])
+            .build_into();
+        let world = World::new(vec![sphere, triangle], vec![]);
+        assert_eq!(world.distance_to(Point::zero()), Some(2.0));
+        assert_eq!(world.distance_to(Point::new(0.0, 0.0, 3.5)), Some(0.5));
+        assert_eq!(
+            world.signed_distance_to(Point::new(0.0, 0.0, 5.5)),
+            Some(-0.5)
+        );
+    }
 }
@@ -1,3 +1,5 @@
+use crate::utils::BuildError;
+
 pub trait Buildable {
     type Builder: ConsumingBuilder<Built = Self>;
 
@@ -8,6 +10,15 @@ pub trait ConsumingBuilder {
     type Built: Buildable<Builder = Self>;
 
     fn build(self) -> Self::Built;
+
+    // checks the settings before building; builders whose settings are always
+    // valid just build
+    fn try_build(self) -> Result<Self::Built, BuildError>
+    where
+        Self: Sized,
+    {
+        Ok(self.build())
+    }
 }
 
 pub trait BuildInto<T>: ConsumingBuilder {
@@ -0,0 +1,262 @@
+use std::fmt;
+use std::io;
+
+// Crate-wide errors. Each area of the crate reports its own error type, and
+// Error gathers them for callers that just want one type to propagate.
+#[derive(Debug)]
+pub enum Error {
+    Render(RenderError),
+    Build(BuildError),
+    Parse(ParseError),
+    Io(IoError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Render(error) => write!(f, "render error: {}", error),
+            Error::Build(error) => write!(f, "build error: {}", error),
+            Error::Parse(error) => write!(f, "parse error: {}", error),
+            Error::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Render(error) => Some(error),
+            Error::Build(error) => Some(error),
+            Error::Parse(error) => Some(error),
+            Error::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<RenderError> for Error {
+    fn from(error: RenderError) -> Error {
+        Error::Render(error)
+    }
+}
+
+impl From<BuildError> for Error {
+    fn from(error: BuildError) -> Error {
+        Error::Build(error)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Error {
+        Error::Parse(error)
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(error: IoError) -> Error {
+        Error::Io(error)
+    }
+}
+
+// Failures while drawing into a canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderError {
+    // a pixel (or the top-left corner of a canvas being painted) lies outside
+    // a canvas of the given size, or a canvas being painted does not fit
+    OutOfBounds { index: [usize; 2], size: [usize; 2] },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::OutOfBounds { index, size } => write!(
+                f,
+                "[{}, {}] is out of bounds for a {}x{} canvas",
+                index[0], index[1], size[0], size[1]
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+// Builder settings that cannot make a valid object.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuildError {
+    MissingField(&'static str),
+    InvalidValue { field: &'static str, reason: String },
+    // the vertices are coincident or collinear, so there is no surface normal
+    DegenerateTriangle,
+    // the frame transformation has no inverse (such as a scale by zero)
+    NonInvertibleTransform,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingField(field) => write!(f, "{} must be set", field),
+            BuildError::InvalidValue { field, reason } => {
+                write!(f, "invalid {}: {}", field, reason)
+            }
+            BuildError::DegenerateTriangle => write!(f, "triangle has no area"),
+            BuildError::NonInvertibleTransform => {
+                write!(f, "frame transformation is not invertible")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+// Input data (such as an image file) that could not be understood, with where
+// in the input the problem was found when that is known.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub position: Option<Position>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParseErrorKind {
+    InvalidHeader,
+    InvalidValue,
+    MissingData,
+    // valid, but uses a feature that is not supported (such as interlacing)
+    Unsupported,
+    // a checksum did not match or compressed data could not be inflated
+    Corrupt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Position {
+    // an offset into binary data, counted from 0
+    Byte(usize),
+    // a line of a text format, counted from 1
+    Line(usize),
+}
+
+impl ParseErrorKind {
+    pub fn at(self, position: Position) -> ParseError {
+        ParseError {
+            kind: self,
+            position: Some(position),
+        }
+    }
+}
+
+impl ParseError {
+    // places the error, unless it was already placed more precisely further in
+    pub fn or_at(self, position: Position) -> ParseError {
+        ParseError {
+            position: self.position.or(Some(position)),
+            ..self
+        }
+    }
+}
+
+impl From<ParseErrorKind> for ParseError {
+    fn from(kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            position: None,
+        }
+    }
+}
+
+// so that an error can be checked for its kind wherever it was found
+impl PartialEq<ParseErrorKind> for ParseError {
+    fn eq(&self, kind: &ParseErrorKind) -> bool {
+        self.kind == *kind
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self.kind {
+            ParseErrorKind::InvalidHeader => "invalid header",
+            ParseErrorKind::InvalidValue => "invalid value in data",
+            ParseErrorKind::MissingData => "data ends early",
+            ParseErrorKind::Unsupported => "unsupported format",
+            ParseErrorKind::Corrupt => "corrupt data",
+        };
+        match self.position {
+            Some(Position::Byte(offset)) => write!(f, "{} at byte {}", message, offset),
+            Some(Position::Line(line)) => write!(f, "{} on line {}", message, line),
+            None => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// An I/O failure, with the path being read or written when there is one.
+#[derive(Debug)]
+pub struct IoError {
+    pub path: Option<String>,
+    pub source: io::Error,
+}
+
+impl IoError {
+    pub fn at_path(path: &str, source: io::Error) -> IoError {
+        IoError {
+            path: Some(path.to_string()),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {}", path, self.source),
+            None => write!(f, "{}", self.source),
+        }
+    }
+}
+
+impl std::error::Error for IoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<io::Error> for IoError {
+    fn from(source: io::Error) -> IoError {
+        IoError { path: None, source }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Error {
+        Error::Io(source.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_describe_their_context() {
+        let error = Error::from(RenderError::OutOfBounds {
+            index: [5, 1],
+            size: [4, 3],
+        });
+        assert_eq!(
+            error.to_string(),
+            "render error: [5, 1] is out of bounds for a 4x3 canvas"
+        );
+
+        let error = Error::from(ParseErrorKind::InvalidValue.at(Position::Line(3)));
+        assert_eq!(
+            error.to_string(),
+            "parse error: invalid value in data on line 3"
+        );
+        let error = ParseError::from(ParseErrorKind::MissingData).or_at(Position::Byte(12));
+        assert_eq!(error.to_string(), "data ends early at byte 12");
+
+        let missing = io::Error::new(io::ErrorKind::NotFound, "not found");
+        let error = Error::from(IoError::at_path("scene.png", missing));
+        assert_eq!(error.to_string(), "scene.png: not found");
+        assert!(std::error::Error::source(&error).is_some());
+    }
+}
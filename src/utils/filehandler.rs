@@ -1,19 +1,268 @@
 use std::fs::File;
-use std::io::Write;
-
-pub fn write_to_file(
-    output_bytes: &[u8],
-    path_string: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut f = File::create(path_string)?;
-    f.write_all(output_bytes)?;
+use std::io::{self, BufWriter, Read, Write};
+
+use crate::collections::Colour;
+use crate::utils::{IoError, ParseError, ParseErrorKind, Position};
+
+#[deprecated(note = "use ParseError")]
+pub type DecodeError = ParseError;
+
+#[cfg(feature = "png")]
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+pub fn write_to_file(output_bytes: &[u8], path_string: &str) -> Result<(), IoError> {
+    File::create(path_string)
+        .and_then(|mut f| f.write_all(output_bytes))
+        .map_err(|error| IoError::at_path(path_string, error))
+}
+
+// buffered, so any io::Write target can be swapped in for a file
+pub fn create_file(path_string: &str) -> Result<BufWriter<File>, IoError> {
+    File::create(path_string)
+        .map(BufWriter::new)
+        .map_err(|error| IoError::at_path(path_string, error))
+}
+
+pub fn read_from_file(path_string: &str) -> Result<Vec<u8>, IoError> {
+    let mut bytes = Vec::new();
+    File::open(path_string)
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .map_err(|error| IoError::at_path(path_string, error))?;
+    Ok(bytes)
+}
+
+// An image decoded from a file, with pixels row by row from the top left and
+// colour channels scaled to between 0 and 1.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DecodedImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Colour>,
+    pub alpha: Option<Vec<f64>>,
+}
+
+impl DecodedImage {
+    pub fn rows(&self) -> impl Iterator<Item = &[Colour]> {
+        self.pixels.chunks(self.width.max(1)).take(self.height)
+    }
+}
+
+// plain (P3) or binary (P6) PPM, with a maximum colour value of up to 65535
+pub(crate) fn decode_ppm(bytes: &[u8]) -> Result<DecodedImage, ParseError> {
+    let mut position = 0;
+    let magic = next_ppm_token(bytes, &mut position)
+        .ok_or(ParseErrorKind::InvalidHeader.at(Position::Byte(0)))?;
+    let binary = match magic {
+        b"P3" => false,
+        b"P6" => true,
+        _ => return Err(ParseErrorKind::InvalidHeader.at(Position::Byte(0))),
+    };
+    let end = Position::Byte(bytes.len());
+    // each number is placed at the byte its token starts at
+    let next_number = |position: &mut usize| -> Result<(u32, Position), ParseError> {
+        let token = next_ppm_token(bytes, position).ok_or(ParseErrorKind::MissingData.at(end))?;
+        let start = Position::Byte(*position - token.len());
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .map(|number| (number, start))
+            .ok_or(ParseErrorKind::InvalidValue.at(start))
+    };
+    let (width, _) = next_number(&mut position)?;
+    let (height, _) = next_number(&mut position)?;
+    let (max_value, max_value_position) = next_number(&mut position)?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(ParseErrorKind::InvalidHeader.at(max_value_position));
+    }
+
+    // every sample takes at least a byte, so a size the rest of the file
+    // cannot hold is rejected before anything is allocated for it
+    let sample_size = if binary && max_value > 255 { 2 } else { 1 };
+    let sample_count = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or(ParseErrorKind::InvalidHeader.at(Position::Byte(position)))?;
+    if sample_count > (bytes.len() - position) / sample_size {
+        return Err(ParseErrorKind::MissingData.at(end));
+    }
+    let mut samples = Vec::with_capacity(sample_count);
+    if binary {
+        // exactly one whitespace byte separates the header from the samples
+        position += 1;
+        let data = bytes
+            .get(position..position + sample_count * sample_size)
+            .ok_or(ParseErrorKind::MissingData.at(end))?;
+        for (index, sample) in data.chunks(sample_size).enumerate() {
+            let value = match sample {
+                [value] => *value as u32,
+                [high, low] => u16::from_be_bytes([*high, *low]) as u32,
+                _ => unreachable!(),
+            };
+            if value > max_value {
+                let offset = position + index * sample_size;
+                return Err(ParseErrorKind::InvalidValue.at(Position::Byte(offset)));
+            }
+            samples.push(value);
+        }
+    } else {
+        for _ in 0..sample_count {
+            let (value, value_position) = next_number(&mut position)?;
+            if value > max_value {
+                return Err(ParseErrorKind::InvalidValue.at(value_position));
+            }
+            samples.push(value);
+        }
+    }
+
+    let scale = 1.0 / max_value as f64;
+    Ok(DecodedImage {
+        width: width as usize,
+        height: height as usize,
+        pixels: samples
+            .chunks(3)
+            .map(|rgb| {
+                Colour::new(
+                    rgb[0] as f64 * scale,
+                    rgb[1] as f64 * scale,
+                    rgb[2] as f64 * scale,
+                )
+            })
+            .collect(),
+        alpha: None,
+    })
+}
+
+// skips whitespace and comments (from # to the end of the line)
+fn next_ppm_token<'a>(bytes: &'a [u8], position: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        match bytes.get(*position)? {
+            byte if byte.is_ascii_whitespace() => *position += 1,
+            b'#' => {
+                while !matches!(bytes.get(*position), Some(b'\n') | Some(b'\r') | None) {
+                    *position += 1;
+                }
+            }
+            _ => break,
+        }
+    }
+    let start = *position;
+    while bytes
+        .get(*position)
+        .is_some_and(|byte| !byte.is_ascii_whitespace() && *byte != b'#')
+    {
+        *position += 1;
+    }
+    Some(&bytes[start..*position])
+}
+
+// 8-bit RGB (or RGBA) samples, row by row from the top left
+#[cfg(feature = "png")]
+pub(crate) fn encode_png<W: Write>(
+    writer: &mut W,
+    [width, height]: [usize; 2],
+    samples: &[u8],
+    has_alpha: bool,
+) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(match has_alpha {
+        true => png::ColorType::Rgba,
+        false => png::ColorType::Rgb,
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut png_writer = encoder.write_header()?;
+    png_writer.write_image_data(samples)?;
+    Ok(png_writer.finish()?)
+}
+
+// Radiance HDR with flat (not run-length encoded) scanlines; each colour is
+// stored as three 8-bit mantissas sharing an exponent, so values far above 1
+// survive. Negative channels are written as 0.
+pub(crate) fn encode_hdr<W: Write>(
+    writer: &mut W,
+    [width, height]: [usize; 2],
+    colours: &[Colour],
+) -> io::Result<()> {
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+    for colour in colours.iter().take(width * height) {
+        writer.write_all(&rgbe(*colour))?;
+    }
     Ok(())
 }
 
+fn rgbe(colour: Colour) -> [u8; 4] {
+    let [red, green, blue] = [colour.red, colour.green, colour.blue].map(|c| c.max(0.0));
+    let brightest = red.max(green).max(blue);
+    if brightest < 1e-32 {
+        return [0; 4];
+    }
+    // brightest = mantissa * 2^exponent, with the mantissa in [0.5, 1)
+    let exponent = brightest.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2_f64.powi(exponent);
+    let [red, green, blue] = [red, green, blue].map(|c| (c * scale).min(255.0) as u8);
+    [red, green, blue, (exponent + 128).clamp(0, 255) as u8]
+}
+
+// PNG in any colour type and bit depth; palettes and transparency are
+// expanded, and 16-bit samples keep their precision
+#[cfg(feature = "png")]
+pub(crate) fn decode_png(bytes: &[u8]) -> Result<DecodedImage, ParseError> {
+    if !bytes.starts_with(&PNG_SIGNATURE) {
+        return Err(ParseErrorKind::InvalidHeader.at(Position::Byte(0)));
+    }
+
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(png_error)?;
+    let mut samples = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut samples).map_err(png_error)?;
+    samples.truncate(info.buffer_size());
+
+    let values: Vec<f64> = match info.bit_depth {
+        png::BitDepth::Sixteen => samples
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as f64 / 65535.0)
+            .collect(),
+        _ => samples.iter().map(|&value| value as f64 / 255.0).collect(),
+    };
+    let channels = info.color_type.samples();
+    let mut pixels = Vec::with_capacity(values.len() / channels);
+    let mut alpha = Vec::with_capacity(values.len() / channels);
+    for pixel in values.chunks(channels) {
+        let (colour, pixel_alpha) = match pixel {
+            [grey] => (Colour::new(*grey, *grey, *grey), 1.0),
+            [grey, alpha] => (Colour::new(*grey, *grey, *grey), *alpha),
+            [red, green, blue] => (Colour::new(*red, *green, *blue), 1.0),
+            [red, green, blue, alpha] => (Colour::new(*red, *green, *blue), *alpha),
+            _ => return Err(ParseErrorKind::Unsupported.into()),
+        };
+        pixels.push(colour);
+        alpha.push(pixel_alpha);
+    }
+
+    Ok(DecodedImage {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+        alpha: matches!(channels, 2 | 4).then_some(alpha),
+    })
+}
+
+#[cfg(feature = "png")]
+fn png_error(error: png::DecodingError) -> ParseError {
+    let kind = match error {
+        png::DecodingError::IoError(_) => ParseErrorKind::MissingData,
+        png::DecodingError::LimitsExceeded => ParseErrorKind::Unsupported,
+        _ => ParseErrorKind::Corrupt,
+    };
+    kind.into()
+}
+
 #[cfg(test)]
 mod tests {
-    use std::io::Read;
-
     use super::*;
 
     #[test]
@@ -32,4 +281,92 @@ mod tests {
         // cleanup
         std::fs::remove_file(path_string).unwrap();
     }
+
+    #[test]
+    fn decode_plain_and_binary_ppm() {
+        let plain = decode_ppm(b"P3\n# a comment\n2 1\n255\n255 0 0 0 51 255\n").unwrap();
+        let binary = decode_ppm(b"P6 2 1 255\n\xff\x00\x00\x00\x33\xff").unwrap();
+        let expected = vec![Colour::new(1.0, 0.0, 0.0), Colour::new(0.0, 0.2, 1.0)];
+        assert_eq!((plain.width, plain.height), (2, 1));
+        assert_eq!(plain.pixels, expected);
+        assert_eq!(binary, plain);
+
+        let wide = decode_ppm(b"P6 1 1 65535\n\xff\xff\x00\x00\x80\x00").unwrap();
+        assert_eq!(wide.pixels, vec![Colour::new(1.0, 0.0, 32768.0 / 65535.0)]);
+
+        assert_eq!(
+            decode_ppm(b"P5 1 1 255\n\x00").unwrap_err(),
+            ParseErrorKind::InvalidHeader
+        );
+        assert_eq!(
+            decode_ppm(b"P3 1 1 255 0 0"),
+            Err(ParseErrorKind::MissingData.at(Position::Byte(14)))
+        );
+        assert_eq!(
+            decode_ppm(b"P3 1 1 255 0 256 0"),
+            Err(ParseErrorKind::InvalidValue.at(Position::Byte(13)))
+        );
+        assert_eq!(
+            decode_ppm(b"P6 1 1 255\n\x00\x00").unwrap_err(),
+            ParseErrorKind::MissingData
+        );
+        assert_eq!(
+            decode_ppm(b"P6 4294967295 4294967295 255\n\x00").unwrap_err(),
+            ParseErrorKind::InvalidHeader
+        );
+        assert_eq!(
+            decode_ppm(b"P3 100000 100000 255 0 0 0").unwrap_err(),
+            ParseErrorKind::MissingData
+        );
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn decode_filtered_png() {
+        // 2x2 RGBA, first row sub filtered and second row Paeth filtered
+        let rgba = [
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x08, 0x06, 0x00, 0x00,
+            0x00, 0x72, 0xb6, 0x0d, 0x24, 0x00, 0x00, 0x00, 0x19, 0x49, 0x44, 0x41, 0x54, 0x78,
+            0x9c, 0x63, 0xfc, 0xcf, 0xc0, 0xf0, 0x9f, 0xf1, 0x3f, 0x43, 0x23, 0x0b, 0x23, 0xc3,
+            0x7f, 0x20, 0x64, 0x68, 0x00, 0x00, 0x3a, 0xb2, 0x06, 0x04, 0x48, 0x54, 0x01, 0x9b,
+            0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+        ];
+        let image = decode_png(&rgba).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(
+            image.pixels,
+            vec![
+                Colour::new(1.0, 0.0, 0.0),
+                Colour::new(0.0, 1.0, 0.0),
+                Colour::new(0.0, 0.0, 1.0),
+                Colour::new(1.0, 1.0, 1.0),
+            ]
+        );
+        assert_eq!(image.alpha, Some(vec![1.0, 128.0 / 255.0, 1.0, 0.0]));
+
+        // 2x1 16-bit greyscale
+        let grey = [
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x10, 0x00, 0x00, 0x00,
+            0x00, 0x81, 0xd9, 0xfc, 0x15, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78,
+            0x9c, 0x63, 0xf8, 0xff, 0xbf, 0x81, 0x01, 0x00, 0x07, 0xfe, 0x02, 0x7f, 0x72, 0xdd,
+            0x80, 0x35, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+        ];
+        let image = decode_png(&grey).unwrap();
+        let half = 32768.0 / 65535.0;
+        assert_eq!(
+            image.pixels,
+            vec![Colour::new(1.0, 1.0, 1.0), Colour::new(half, half, half)]
+        );
+        assert_eq!(image.alpha, None);
+
+        let mut corrupt = grey;
+        corrupt[45] ^= 0xff;
+        assert_eq!(decode_png(&corrupt).unwrap_err(), ParseErrorKind::Corrupt);
+        assert_eq!(
+            decode_png(&grey[1..]).unwrap_err(),
+            ParseErrorKind::InvalidHeader
+        );
+    }
 }
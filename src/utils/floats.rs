@@ -1,5 +1,23 @@
 pub const EPSILON: f64 = 1e-6;
 
+// Equality up to a tolerance, defaulting to the EPSILON the crate itself uses
+// for intersection and shading comparisons. Compound values are equal when
+// every component is.
+pub trait ApproxEq {
+    fn approx_eq_within(&self, other: &Self, tolerance: f64) -> bool;
+
+    fn approx_eq(&self, other: &Self) -> bool {
+        self.approx_eq_within(other, EPSILON)
+    }
+}
+
+impl ApproxEq for f64 {
+    fn approx_eq_within(&self, other: &f64, tolerance: f64) -> bool {
+        (self - other).abs() <= tolerance
+    }
+}
+
+#[cfg(test)]
 macro_rules! approx_eq {
     ($left:expr, $right:expr) => {
         let (left, right) = ($left, $right);
@@ -12,4 +30,26 @@ macro_rules! approx_eq {
     };
 }
 
+#[cfg(test)]
 pub(crate) use approx_eq;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::{Colour, Point, Vector};
+
+    #[test]
+    fn compare_within_tolerance() {
+        assert!(1.0.approx_eq(&(1.0 + EPSILON / 2.0)));
+        assert!(!1.0.approx_eq(&(1.0 + EPSILON * 2.0)));
+        assert!(1.0.approx_eq_within(&1.5, 0.5));
+
+        let point = Point::new(1.0, 2.0, 3.0);
+        assert!(point.approx_eq(&Point::new(1.0, 2.0, 3.0 + EPSILON / 2.0)));
+        assert!(!point.approx_eq(&Point::new(1.0, 2.0 + EPSILON * 2.0, 3.0)));
+        let vector = Vector::new(0.0, 1.0, 0.0);
+        assert!(vector.approx_eq_within(&Vector::new(0.01, 1.0, 0.0), 0.1));
+        let colour = Colour::new(0.2, 0.4, 0.6);
+        assert!(colour.approx_eq(&Colour::new(0.1 + 0.1, 0.4, 0.6)));
+    }
+}
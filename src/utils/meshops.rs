@@ -0,0 +1,1133 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::ops::{Add, AddAssign};
+
+use crate::collections::{Colour, Point, Vector};
+use crate::objects::{Bounded, BoundingBox, Group, Pattern, Shape};
+use crate::scenes::stats;
+use crate::utils::{BuildInto, Buildable, ObjFace, ObjModel, EPSILON};
+
+// how much more an open edge's plane counts than a face's, per unit of area
+const BOUNDARY_WEIGHT: f64 = 100.0;
+
+// Reduces a mesh to at most `target_faces` triangles by collapsing edges, the
+// cheapest first, by the quadric error metric (Garland and Heckbert): each
+// vertex carries the squared distances to the planes of the faces around it,
+// weighted by their areas, and an edge collapses to the point that keeps
+// that sum smallest. Open edges add planes across them so that boundaries
+// keep their outline. A collapse that would turn a face around is rejected,
+// and faces left with no area are dropped. Stops early if no edge can be
+// collapsed. Groups, materials and corner normals are kept on the faces that
+// survive, and vertices no longer used are removed.
+pub fn decimate(model: &ObjModel, target_faces: usize) -> ObjModel {
+    let mut vertices = model.vertices.clone();
+    let mut colours = model.colours.clone();
+    let mut faces = model.faces.clone();
+
+    let mut quadrics = vec![Quadric::default(); vertices.len()];
+    let mut vertex_faces: Vec<Vec<usize>> = vec![vec![]; vertices.len()];
+    let mut edge_faces: HashMap<[usize; 2], Vec<usize>> = HashMap::new();
+    for (index, face) in faces.iter().enumerate() {
+        let [v1, v2, v3] = face.vertices;
+        let plane = Quadric::face(vertices[v1], vertices[v2], vertices[v3]);
+        for vertex in face.vertices {
+            quadrics[vertex] += plane;
+            vertex_faces[vertex].push(index);
+        }
+        for (a, b) in [(v1, v2), (v2, v3), (v3, v1)] {
+            edge_faces
+                .entry([a.min(b), a.max(b)])
+                .or_default()
+                .push(index);
+        }
+    }
+    // in order, so that sums come out the same from run to run
+    let mut edges: Vec<([usize; 2], Vec<usize>)> = edge_faces.into_iter().collect();
+    edges.sort_unstable();
+    for &([a, b], ref around) in &edges {
+        if let [face] = around[..] {
+            let [v1, v2, v3] = faces[face].vertices.map(|vertex| vertices[vertex]);
+            let boundary = Quadric::boundary(vertices[a], vertices[b], face_normal(v1, v2, v3));
+            quadrics[a] += boundary;
+            quadrics[b] += boundary;
+        }
+    }
+
+    let mut versions = vec![0_usize; vertices.len()];
+    let mut removed = vec![false; vertices.len()];
+    let mut alive = vec![true; faces.len()];
+    let mut remaining = faces.len();
+    let mut collapses = BinaryHeap::new();
+    for (edge, _) in edges {
+        collapses.push(Collapse::new(edge, &vertices, &quadrics, &versions));
+    }
+
+    while remaining > target_faces {
+        let Some(Collapse {
+            edge: [a, b],
+            target,
+            versions: edge_versions,
+            ..
+        }) = collapses.pop()
+        else {
+            break;
+        };
+        if removed[a] || removed[b] || edge_versions != [versions[a], versions[b]] {
+            continue;
+        }
+        let mut around: Vec<usize> = vertex_faces[a]
+            .iter()
+            .chain(&vertex_faces[b])
+            .copied()
+            .filter(|&face| alive[face])
+            .collect();
+        around.sort_unstable();
+        around.dedup();
+        let flips = around.iter().any(|&face| {
+            let corners = faces[face].vertices;
+            if corners.contains(&a) && corners.contains(&b) {
+                return false;
+            }
+            let [v1, v2, v3] = corners.map(|vertex| vertices[vertex]);
+            let [m1, m2, m3] = corners.map(|vertex| match vertex == a || vertex == b {
+                true => target,
+                false => vertices[vertex],
+            });
+            face_normal(m1, m2, m3).dot(face_normal(v1, v2, v3)) <= 0.0
+        });
+        if flips || !keeps_manifold(a, b, &around, &faces) {
+            continue;
+        }
+
+        vertices[a] = target;
+        if !colours.is_empty() {
+            colours[a] = (colours[a] + colours[b]) * 0.5;
+        }
+        quadrics[a] = quadrics[a] + quadrics[b];
+        removed[b] = true;
+        versions[a] += 1;
+        for &face in &around {
+            let corners = &mut faces[face].vertices;
+            if corners.contains(&a) && corners.contains(&b) {
+                if alive[face] {
+                    alive[face] = false;
+                    remaining -= 1;
+                }
+                continue;
+            }
+            if let Some(corner) = corners.iter_mut().find(|vertex| **vertex == b) {
+                *corner = a;
+                vertex_faces[a].push(face);
+            }
+        }
+        vertex_faces[a].retain(|&face| alive[face]);
+        vertex_faces[a].sort_unstable();
+        vertex_faces[a].dedup();
+
+        let mut neighbours: Vec<usize> = vertex_faces[a]
+            .iter()
+            .flat_map(|&face| faces[face].vertices)
+            .filter(|&vertex| vertex != a)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        for neighbour in neighbours {
+            let edge = [a.min(neighbour), a.max(neighbour)];
+            collapses.push(Collapse::new(edge, &vertices, &quadrics, &versions));
+        }
+    }
+
+    let mut faces: Vec<ObjFace> = faces
+        .into_iter()
+        .zip(alive)
+        .filter_map(|(face, alive)| alive.then_some(face))
+        .collect();
+    let mut used = vec![false; vertices.len()];
+    for &vertex in faces.iter().flat_map(|face| &face.vertices) {
+        used[vertex] = true;
+    }
+    let mut new_indices = vec![0; vertices.len()];
+    let mut used_vertices = vec![];
+    for (vertex, point) in vertices.into_iter().enumerate() {
+        if used[vertex] {
+            new_indices[vertex] = used_vertices.len();
+            used_vertices.push(point);
+        }
+    }
+    for face in &mut faces {
+        face.vertices = face.vertices.map(|vertex| new_indices[vertex]);
+    }
+    colours = colours
+        .into_iter()
+        .zip(&used)
+        .filter_map(|(colour, &used)| used.then_some(colour))
+        .collect();
+
+    ObjModel {
+        vertices: used_vertices,
+        colours,
+        faces,
+        ..model.clone()
+    }
+}
+
+// an edge can only be collapsed if the vertices next to both its ends are the
+// ones across its faces; otherwise faces on either side would fold together
+fn keeps_manifold(a: usize, b: usize, around: &[usize], faces: &[ObjFace]) -> bool {
+    let neighbours = |end: usize| {
+        let mut neighbours: Vec<usize> = around
+            .iter()
+            .map(|&face| faces[face].vertices)
+            .filter(|corners| corners.contains(&end))
+            .flatten()
+            .filter(|&vertex| vertex != a && vertex != b)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    };
+    let from_b = neighbours(b);
+    let shared = neighbours(a)
+        .into_iter()
+        .filter(|vertex| from_b.binary_search(vertex).is_ok())
+        .count();
+    let edge_faces = around
+        .iter()
+        .filter(|&&face| {
+            let corners = faces[face].vertices;
+            corners.contains(&a) && corners.contains(&b)
+        })
+        .count();
+    shared <= edge_faces
+}
+
+fn face_normal(v1: Point, v2: Point, v3: Point) -> Vector {
+    (v3 - v1).cross(v2 - v1)
+}
+
+// the sum of squared distances to a set of planes, as the symmetric matrix
+// of the plane equations (a, b, c, d) multiplied out, kept as its upper half
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn plane(normal: Vector, point: Point, weight: f64) -> Quadric {
+        let [a, b, c] = [normal.x, normal.y, normal.z];
+        let d = -(a * point.x + b * point.y + c * point.z);
+        Quadric(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|value| value * weight),
+        )
+    }
+
+    // the plane of a face, weighted by its area
+    fn face(v1: Point, v2: Point, v3: Point) -> Quadric {
+        let normal = face_normal(v1, v2, v3);
+        let area = normal.magnitude() * 0.5;
+        match area > 0.0 {
+            true => Quadric::plane(normal.normalise(), v1, area),
+            false => Quadric::default(),
+        }
+    }
+
+    // the plane through an open edge at right angles to its face, weighted
+    // heavily so that the edge is kept in place
+    fn boundary(a: Point, b: Point, face_normal: Vector) -> Quadric {
+        let edge = b - a;
+        let normal = edge.cross(face_normal);
+        match normal.magnitude() > 0.0 {
+            true => Quadric::plane(normal.normalise(), a, BOUNDARY_WEIGHT * edge.dot(edge)),
+            false => Quadric::default(),
+        }
+    }
+
+    fn error(&self, point: Point) -> f64 {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.0;
+        let [x, y, z] = [point.x, point.y, point.z];
+        aa * x * x
+            + 2.0 * ab * x * y
+            + 2.0 * ac * x * z
+            + 2.0 * ad * x
+            + bb * y * y
+            + 2.0 * bc * y * z
+            + 2.0 * bd * y
+            + cc * z * z
+            + 2.0 * cd * z
+            + dd
+    }
+
+    // the point where the error is smallest, if there is just one; otherwise
+    // the better of the edge's ends and midpoint
+    fn best_point(&self, a: Point, b: Point) -> (Point, f64) {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, _] = self.0;
+        let det = |c1: [f64; 3], c2: [f64; 3], c3: [f64; 3]| {
+            c1[0] * (c2[1] * c3[2] - c2[2] * c3[1]) - c2[0] * (c1[1] * c3[2] - c1[2] * c3[1])
+                + c3[0] * (c1[1] * c2[2] - c1[2] * c2[1])
+        };
+        let (col1, col2, col3) = ([aa, ab, ac], [ab, bb, bc], [ac, bc, cc]);
+        let rhs = [-ad, -bd, -cd];
+        let determinant = det(col1, col2, col3);
+        if determinant.abs() > EPSILON {
+            // Cramer's rule
+            let optimum = Point::new(
+                det(rhs, col2, col3) / determinant,
+                det(col1, rhs, col3) / determinant,
+                det(col1, col2, rhs) / determinant,
+            );
+            return (optimum, self.error(optimum));
+        }
+        [a, b, a + (b - a) * 0.5]
+            .into_iter()
+            .map(|point| (point, self.error(point)))
+            .min_by(|(_, error1), (_, error2)| error1.total_cmp(error2))
+            .expect("there are three candidates")
+    }
+}
+
+impl Add for Quadric {
+    type Output = Quadric;
+
+    fn add(self, other: Quadric) -> Quadric {
+        Quadric(std::array::from_fn(|index| self.0[index] + other.0[index]))
+    }
+}
+
+impl AddAssign for Quadric {
+    fn add_assign(&mut self, other: Quadric) {
+        *self = *self + other;
+    }
+}
+
+// an edge waiting to be collapsed, ordered so that the cheapest comes out of
+// the heap first; it is stale once either end has been changed since
+struct Collapse {
+    cost: f64,
+    edge: [usize; 2],
+    target: Point,
+    versions: [usize; 2],
+}
+
+impl Collapse {
+    fn new(
+        edge: [usize; 2],
+        vertices: &[Point],
+        quadrics: &[Quadric],
+        versions: &[usize],
+    ) -> Collapse {
+        let [a, b] = edge;
+        let (target, cost) = (quadrics[a] + quadrics[b]).best_point(vertices[a], vertices[b]);
+        Collapse {
+            cost,
+            edge,
+            target,
+            versions: [versions[a], versions[b]],
+        }
+    }
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Collapse) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Collapse) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then(other.edge.cmp(&self.edge))
+    }
+}
+
+// Moves a model so the box around its vertices is centred on the origin, and
+// scales it evenly so the longest side of that box runs from -1 to 1 (the
+// extent of the unit primitives, such as Cube and Sphere).
+pub fn fit_to_unit_cube(model: &ObjModel) -> ObjModel {
+    let Some(&first) = model.vertices.first() else {
+        return model.clone();
+    };
+    let (min, max) = model
+        .vertices
+        .iter()
+        .fold((first, first), |(min, max), vertex| {
+            (
+                Point::new(
+                    min.x.min(vertex.x),
+                    min.y.min(vertex.y),
+                    min.z.min(vertex.z),
+                ),
+                Point::new(
+                    max.x.max(vertex.x),
+                    max.y.max(vertex.y),
+                    max.z.max(vertex.z),
+                ),
+            )
+        });
+    let centre = min + (max - min) * 0.5;
+    let size = max - min;
+    let longest_side = size.x.max(size.y).max(size.z);
+    // a single point is only moved
+    let scale = if longest_side > 0.0 {
+        2.0 / longest_side
+    } else {
+        1.0
+    };
+
+    ObjModel {
+        vertices: model
+            .vertices
+            .iter()
+            .map(|&vertex| Point::zero() + (vertex - centre) * scale)
+            .collect(),
+        ..model.clone()
+    }
+}
+
+// Splits every face of a mesh into four, through the midpoints of its edges,
+// the given number of times; faces sharing an edge share its midpoint. The
+// surface keeps its shape, so this only adds vertices for other steps (such
+// as displace) to move. Faces keep their groups and materials, but corner
+// normals are dropped and can be regenerated afterwards.
+pub fn subdivide(model: &ObjModel, levels: usize) -> ObjModel {
+    let mut vertices = model.vertices.clone();
+    let mut colours = model.colours.clone();
+    let mut texture_coordinates = model.texture_coordinates.clone();
+    let mut faces = model.faces.clone();
+    for _ in 0..levels {
+        let mut midpoints: HashMap<[usize; 2], usize> = HashMap::new();
+        faces = split_faces(faces, &mut texture_coordinates, |a, b| {
+            *midpoints.entry([a.min(b), a.max(b)]).or_insert_with(|| {
+                vertices.push(vertices[a] + (vertices[b] - vertices[a]) * 0.5);
+                if !colours.is_empty() {
+                    colours.push((colours[a] + colours[b]) * 0.5);
+                }
+                vertices.len() - 1
+            })
+        });
+    }
+    ObjModel {
+        vertices,
+        colours,
+        texture_coordinates,
+        normals: match levels {
+            0 => model.normals.clone(),
+            _ => vec![],
+        },
+        faces,
+        ..model.clone()
+    }
+}
+
+// Subdivides a mesh as subdivide does, but smoothing it with Loop's scheme
+// at each level: new vertices on edges and the old vertices are moved to
+// weighted averages of the vertices around them, so that a coarse cage
+// converges on a smooth surface through (near) it, shrinking a little as it
+// does. Edges with only one face are open boundaries, which are smoothed
+// along the boundary alone so that they stay put in the surface's plane;
+// vertices and edges where more than two faces meet are left as they are.
+pub fn loop_subdivide(model: &ObjModel, levels: usize) -> ObjModel {
+    let mut vertices = model.vertices.clone();
+    let mut colours = model.colours.clone();
+    let mut texture_coordinates = model.texture_coordinates.clone();
+    let mut faces = model.faces.clone();
+    for _ in 0..levels {
+        // each edge with the vertices opposite it, in order of first use
+        let mut edge_indices: HashMap<[usize; 2], usize> = HashMap::new();
+        let mut edges: Vec<([usize; 2], Vec<usize>)> = vec![];
+        for face in &faces {
+            let [v1, v2, v3] = face.vertices;
+            for (a, b, opposite) in [(v1, v2, v3), (v2, v3, v1), (v3, v1, v2)] {
+                let edge = [a.min(b), a.max(b)];
+                let index = *edge_indices.entry(edge).or_insert_with(|| {
+                    edges.push((edge, vec![]));
+                    edges.len() - 1
+                });
+                edges[index].1.push(opposite);
+            }
+        }
+
+        let mut neighbours = vec![vec![]; vertices.len()];
+        let mut boundary_neighbours = vec![vec![]; vertices.len()];
+        let mut irregular = vec![false; vertices.len()];
+        for ([a, b], opposites) in &edges {
+            neighbours[*a].push(*b);
+            neighbours[*b].push(*a);
+            match opposites.len() {
+                1 => {
+                    boundary_neighbours[*a].push(*b);
+                    boundary_neighbours[*b].push(*a);
+                }
+                2 => (),
+                _ => {
+                    irregular[*a] = true;
+                    irregular[*b] = true;
+                }
+            }
+        }
+
+        let offset_sum = |vertex: Point, others: &[usize]| {
+            others.iter().fold(Vector::zero(), |sum, &other| {
+                sum + (vertices[other] - vertex)
+            })
+        };
+        let smoothed = vertices.iter().enumerate().map(|(index, &vertex)| {
+            let count = neighbours[index].len();
+            match boundary_neighbours[index].len() {
+                _ if irregular[index] || count < 3 => vertex,
+                0 => {
+                    let cos = (2.0 * std::f64::consts::PI / count as f64).cos();
+                    let beta = (0.625 - (0.375 + 0.25 * cos).powi(2)) / count as f64;
+                    vertex + offset_sum(vertex, &neighbours[index]) * beta
+                }
+                2 => vertex + offset_sum(vertex, &boundary_neighbours[index]) * 0.125,
+                _ => vertex,
+            }
+        });
+        let edge_points = edges.iter().map(|([a, b], opposites)| {
+            let (start, end) = (vertices[*a], vertices[*b]);
+            match opposites.len() {
+                2 => start + (end - start) * 0.375 + offset_sum(start, opposites) * 0.125,
+                _ => start + (end - start) * 0.5,
+            }
+        });
+        let vertex_count = vertices.len();
+        vertices = smoothed.chain(edge_points).collect();
+        // colours and texture coordinates are blended linearly, without
+        // smoothing
+        if !colours.is_empty() {
+            let edge_colours: Vec<Colour> = edges
+                .iter()
+                .map(|([a, b], _)| (colours[*a] + colours[*b]) * 0.5)
+                .collect();
+            colours.extend(edge_colours);
+        }
+        faces = split_faces(faces, &mut texture_coordinates, |a, b| {
+            vertex_count + edge_indices[&[a.min(b), a.max(b)]]
+        });
+    }
+    ObjModel {
+        vertices,
+        colours,
+        texture_coordinates,
+        normals: match levels {
+            0 => model.normals.clone(),
+            _ => vec![],
+        },
+        faces,
+        ..model.clone()
+    }
+}
+
+// splits each face into four through the vertices that edge_vertex gives for
+// its edges, keeping the faces split from each one together and in order;
+// texture coordinates are split at the midpoints of the faces' corners
+fn split_faces(
+    faces: Vec<ObjFace>,
+    texture_coordinates: &mut Vec<(f64, f64)>,
+    mut edge_vertex: impl FnMut(usize, usize) -> usize,
+) -> Vec<ObjFace> {
+    let mut texture_midpoints: HashMap<[usize; 2], usize> = HashMap::new();
+    let mut texture_midpoint = |a: usize, b: usize| {
+        *texture_midpoints
+            .entry([a.min(b), a.max(b)])
+            .or_insert_with(|| {
+                let [(u1, v1), (u2, v2)] = [texture_coordinates[a], texture_coordinates[b]];
+                texture_coordinates.push(((u1 + u2) * 0.5, (v1 + v2) * 0.5));
+                texture_coordinates.len() - 1
+            })
+    };
+    let split = |[a, b, c]: [usize; 3], [ab, bc, ca]: [usize; 3]| {
+        [[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]
+    };
+    faces
+        .into_iter()
+        .flat_map(|face| {
+            let [v1, v2, v3] = face.vertices;
+            let vertices = split(
+                face.vertices,
+                [
+                    edge_vertex(v1, v2),
+                    edge_vertex(v2, v3),
+                    edge_vertex(v3, v1),
+                ],
+            );
+            let textures = face.texture_coordinates.map(|corners| {
+                let [t1, t2, t3] = corners;
+                split(
+                    corners,
+                    [
+                        texture_midpoint(t1, t2),
+                        texture_midpoint(t2, t3),
+                        texture_midpoint(t3, t1),
+                    ],
+                )
+            });
+            std::array::from_fn::<_, 4, _>(|index| ObjFace {
+                vertices: vertices[index],
+                texture_coordinates: textures.map(|textures| textures[index]),
+                normals: None,
+                ..face
+            })
+        })
+        .collect()
+}
+
+// Moves each vertex of a mesh along its normal (averaged from the faces
+// around it) by the height of a pattern there: the luminance of its colour
+// times depth, as for bump maps. The pattern is evaluated in the model's own
+// space. Displacement moves only the vertices there are, so coarse meshes are
+// best subdivided first. Corner normals no longer fit the moved surface, so
+// they are dropped and can be regenerated afterwards.
+pub fn displace(model: &ObjModel, height: &dyn Pattern, depth: f64) -> ObjModel {
+    let mut normal_sums = vec![Vector::zero(); model.vertices.len()];
+    for face in &model.faces {
+        let [v1, v2, v3] = face.vertices.map(|vertex| model.vertices[vertex]);
+        let face_normal = (v3 - v1).cross(v2 - v1);
+        for vertex in face.vertices {
+            normal_sums[vertex] = normal_sums[vertex] + face_normal;
+        }
+    }
+
+    let vertices = model
+        .vertices
+        .iter()
+        .zip(normal_sums)
+        .map(|(&vertex, normal_sum)| {
+            // vertices with no faces (or only faces with no area) stay put
+            if normal_sum.magnitude() == 0.0 {
+                return vertex;
+            }
+            let height = height.colour_at(vertex).luminance() * depth;
+            vertex + normal_sum.normalise() * height
+        })
+        .collect();
+    ObjModel {
+        vertices,
+        normals: vec![],
+        faces: model
+            .faces
+            .iter()
+            .map(|&face| ObjFace {
+                normals: None,
+                ..face
+            })
+            .collect(),
+        ..model.clone()
+    }
+}
+
+// How `divide_with` chooses where to split a list of objects.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SplitMethod {
+    // at the middle of the spread of object centres along the widest axis
+    #[default]
+    Median,
+    // Where the expected cost of a ray passing through is least, taking the
+    // chance of a ray meeting each half to be in proportion to its surface
+    // area (the surface area heuristic). The costs are those of testing a
+    // ray against a group's bounds and against an object; lists that would
+    // cost more to split than to test in full are left whole, even above
+    // the leaf size.
+    SurfaceArea {
+        traversal_cost: f64,
+        intersection_cost: f64,
+    },
+}
+
+impl SplitMethod {
+    // the surface area heuristic, with bounds tests as dear as object tests
+    pub const SURFACE_AREA: SplitMethod = SplitMethod::SurfaceArea {
+        traversal_cost: 1.0,
+        intersection_cost: 1.0,
+    };
+}
+
+// Splits a long list of objects into a tree of groups holding at most
+// `leaf_size` objects each, so whole branches can be skipped when a ray
+// misses their bounds. Each split is at the middle of the spread of object
+// centres along its widest axis. Unbounded objects (such as planes) are
+// left at the top, as are objects that cannot be told apart by position.
+pub fn divide(objects: Vec<Shape>, leaf_size: usize) -> Vec<Shape> {
+    divide_with(objects, leaf_size, SplitMethod::Median)
+}
+
+// as divide, choosing splits by the given method
+pub fn divide_with(objects: Vec<Shape>, leaf_size: usize, method: SplitMethod) -> Vec<Shape> {
+    stats::time(stats::Phase::AcceleratorBuild, || {
+        divide_tree(objects, leaf_size, method)
+    })
+}
+
+fn divide_tree(objects: Vec<Shape>, leaf_size: usize, method: SplitMethod) -> Vec<Shape> {
+    if objects.len() <= leaf_size.max(1) {
+        return objects;
+    }
+
+    let centre_of = |object: &Shape| {
+        let centroid = object.bounds().bounding_box().centroid()?;
+        Some([centroid.x, centroid.y, centroid.z])
+    };
+    let (bounded, mut unbounded): (Vec<Shape>, Vec<Shape>) = objects
+        .into_iter()
+        .partition(|object| centre_of(object).is_some());
+    let centres: Vec<[f64; 3]> = bounded.iter().filter_map(centre_of).collect();
+    let boxes: Vec<BoundingBox> = bounded
+        .iter()
+        .map(|object| object.bounds().bounding_box())
+        .collect();
+    let split = split_objects(method, &centres, &boxes);
+    let Some(below) = split else {
+        unbounded.extend(bounded);
+        return unbounded;
+    };
+
+    let (below, above): (Vec<_>, Vec<_>) = bounded
+        .into_iter()
+        .zip(below)
+        .partition(|(_, below)| *below);
+    for half in [below, above] {
+        let mut half = divide_tree(
+            half.into_iter().map(|(object, _)| object).collect(),
+            leaf_size,
+            method,
+        );
+        match half.len() {
+            1 => unbounded.append(&mut half),
+            _ => unbounded.push(Group::builder().set_objects(half).build_into()),
+        }
+    }
+    unbounded
+}
+
+// Which objects (given by the centres and boxes of their bounds) go in the
+// lower half of a split by the given method, if they are worth splitting.
+pub(crate) fn split_objects(
+    method: SplitMethod,
+    centres: &[[f64; 3]],
+    boxes: &[BoundingBox],
+) -> Option<Vec<bool>> {
+    match method {
+        SplitMethod::Median => median_split(centres),
+        SplitMethod::SurfaceArea {
+            traversal_cost,
+            intersection_cost,
+        } => surface_area_split(centres, boxes, traversal_cost, intersection_cost),
+    }
+}
+
+// which objects fall below the middle of the widest axis, if the centres
+// are spread at all
+fn median_split(centres: &[[f64; 3]]) -> Option<Vec<bool>> {
+    let (axis, split) = (0..3)
+        .map(|axis| {
+            let (min, max) = centres
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), centre| {
+                    (min.min(centre[axis]), max.max(centre[axis]))
+                });
+            (axis, min, max)
+        })
+        .max_by(|(_, min1, max1), (_, min2, max2)| (max1 - min1).total_cmp(&(max2 - min2)))
+        .filter(|(_, min, max)| max > min)
+        .map(|(axis, min, max)| (axis, (min + max) / 2.0))?;
+    Some(centres.iter().map(|centre| centre[axis] < split).collect())
+}
+
+// Which objects go in the lower half of the cheapest split, trying every
+// split between objects sorted along each axis, if any split beats testing
+// every object.
+fn surface_area_split(
+    centres: &[[f64; 3]],
+    boxes: &[BoundingBox],
+    traversal_cost: f64,
+    intersection_cost: f64,
+) -> Option<Vec<bool>> {
+    let count = centres.len();
+    let parent_area = boxes
+        .iter()
+        .copied()
+        .reduce(|union, bounding_box| union + bounding_box)?
+        .surface_area();
+    // objects with no area between them are all equally likely to be hit
+    let relative = |area: f64| match parent_area {
+        parent_area if parent_area > 0.0 => area / parent_area,
+        _ => 1.0,
+    };
+    let (cost, order, lower_count) = (0..3)
+        .filter_map(|axis| {
+            let mut order: Vec<usize> = (0..count).collect();
+            order.sort_by(|&a, &b| centres[a][axis].total_cmp(&centres[b][axis]));
+            if centres[order[0]][axis] == centres[order[count - 1]][axis] {
+                return None;
+            }
+            let lower_areas = running_areas(boxes, order.iter().copied());
+            let mut upper_areas = running_areas(boxes, order.iter().rev().copied());
+            upper_areas.reverse();
+            let (cost, lower_count) = (1..count)
+                .map(|lower_count| {
+                    let cost = traversal_cost
+                        + intersection_cost
+                            * (relative(lower_areas[lower_count - 1]) * lower_count as f64
+                                + relative(upper_areas[lower_count])
+                                    * (count - lower_count) as f64);
+                    (cost, lower_count)
+                })
+                .min_by(|(cost1, _), (cost2, _)| cost1.total_cmp(cost2))?;
+            Some((cost, order, lower_count))
+        })
+        .min_by(|(cost1, _, _), (cost2, _, _)| cost1.total_cmp(cost2))?;
+    if cost >= intersection_cost * count as f64 {
+        return None;
+    }
+
+    let mut below = vec![false; count];
+    for &index in &order[..lower_count] {
+        below[index] = true;
+    }
+    Some(below)
+}
+
+// surface areas of the boxes around the first one, two, three... objects in
+// the given order
+fn running_areas(boxes: &[BoundingBox], order: impl Iterator<Item = usize>) -> Vec<f64> {
+    order
+        .scan(None, |union: &mut Option<BoundingBox>, index| {
+            let grown = union.map_or(boxes[index], |union| union + boxes[index]);
+            *union = Some(grown);
+            Some(grown.surface_area())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Plane, Solid, Sphere, Stripe, Transform, TransformKind};
+    use crate::utils::{approx_eq, ApproxEq};
+
+    // an n by n grid of squares in the xz plane, each split into two triangles
+    fn grid(n: usize) -> ObjModel {
+        let vertices = (0..=n)
+            .flat_map(|row| (0..=n).map(move |column| Point::new(column as f64, 0.0, row as f64)))
+            .collect();
+        let face = |vertices| ObjFace {
+            vertices,
+            texture_coordinates: None,
+            normals: None,
+            group: None,
+            material: None,
+            line: None,
+        };
+        let faces = (0..n)
+            .flat_map(|row| (0..n).map(move |column| row * (n + 1) + column))
+            .flat_map(|corner| {
+                let [v1, v2, v3, v4] = [corner, corner + 1, corner + n + 2, corner + n + 1];
+                [face([v1, v2, v3]), face([v1, v3, v4])]
+            })
+            .collect();
+        ObjModel {
+            vertices,
+            faces,
+            ..ObjModel::default()
+        }
+    }
+
+    #[test]
+    fn decimate_to_budget() {
+        let dense = grid(8);
+        let decimated = decimate(&dense, 20);
+        assert!(decimated.faces.len() <= 20);
+        assert!(!decimated.faces.is_empty());
+        assert!(decimated.vertices.len() < dense.vertices.len());
+        for face in &decimated.faces {
+            let [v1, v2, v3] = face.vertices;
+            assert!(v1 != v2 && v2 != v3 && v3 != v1);
+            assert!(face
+                .vertices
+                .iter()
+                .all(|&vertex| vertex < decimated.vertices.len()));
+        }
+    }
+
+    #[test]
+    fn decimate_flat_sheet_within_its_outline() {
+        let decimated = decimate(&grid(8), 20);
+        let normals: Vec<Vector> = decimated
+            .faces
+            .iter()
+            .map(|face| {
+                let [v1, v2, v3] = face.vertices.map(|vertex| decimated.vertices[vertex]);
+                face_normal(v1, v2, v3)
+            })
+            .collect();
+        assert!(normals.iter().all(|normal| normal.y > 0.0));
+        for vertex in &decimated.vertices {
+            approx_eq!(vertex.y, 0.0);
+        }
+        for corner in [[0.0, 0.0], [8.0, 0.0], [0.0, 8.0], [8.0, 8.0]] {
+            let corner = Point::new(corner[0], 0.0, corner[1]);
+            assert!(decimated
+                .vertices
+                .iter()
+                .any(|vertex| vertex.approx_eq(&corner)));
+        }
+    }
+
+    #[test]
+    fn decimate_closed_surface_without_turning_faces() {
+        let corners = [
+            [1.0, 1.0, 1.0],
+            [1.0, -1.0, -1.0],
+            [-1.0, 1.0, -1.0],
+            [-1.0, -1.0, 1.0],
+        ];
+        let face = |vertices| ObjFace {
+            vertices,
+            texture_coordinates: None,
+            normals: None,
+            group: None,
+            material: None,
+            line: None,
+        };
+        let tetrahedron = ObjModel {
+            vertices: corners.map(|[x, y, z]| Point::new(x, y, z)).to_vec(),
+            faces: vec![
+                face([0, 2, 1]),
+                face([0, 1, 3]),
+                face([0, 3, 2]),
+                face([1, 2, 3]),
+            ],
+            ..ObjModel::default()
+        };
+        let faces_outward = |model: &ObjModel| {
+            model.faces.iter().all(|face| {
+                let [v1, v2, v3] = face.vertices.map(|vertex| model.vertices[vertex]);
+                // the surface is centred on the origin
+                let centre = (v1 - Point::zero()) + ((v2 - v1) + (v3 - v1)) * (1.0 / 3.0);
+                face_normal(v1, v2, v3).dot(centre) > 0.0
+            })
+        };
+        let smooth = loop_subdivide(&tetrahedron, 3);
+        assert!(faces_outward(&smooth));
+        let decimated = decimate(&smooth, 40);
+        assert!(decimated.faces.len() <= 40);
+        assert!(faces_outward(&decimated));
+        // every edge still has a face on each side
+        let mut edges: HashMap<[usize; 2], usize> = HashMap::new();
+        for face in &decimated.faces {
+            let [v1, v2, v3] = face.vertices;
+            for (a, b) in [(v1, v2), (v2, v3), (v3, v1)] {
+                *edges.entry([a.min(b), a.max(b)]).or_default() += 1;
+            }
+        }
+        assert!(edges.values().all(|&count| count == 2));
+    }
+
+    #[test]
+    fn fit_model_to_unit_cube() {
+        let mut model = grid(2);
+        model.vertices = model
+            .vertices
+            .iter()
+            .map(|vertex| Point::new(vertex.x * 3.0 + 5.0, 1.0, vertex.z - 4.0))
+            .collect();
+        let fitted = fit_to_unit_cube(&model);
+        assert_eq!(fitted.vertices[0], Point::new(-1.0, 0.0, -1.0 / 3.0));
+        assert_eq!(fitted.vertices[8], Point::new(1.0, 0.0, 1.0 / 3.0));
+        assert_eq!(fitted.faces, model.faces);
+    }
+
+    #[test]
+    fn subdivide_through_edge_midpoints() {
+        let square = grid(1);
+        let subdivided = subdivide(&square, 2);
+        assert_eq!(subdivided.faces.len(), 32);
+        // a 4 by 4 grid of points, with no duplicates along shared edges
+        assert_eq!(subdivided.vertices.len(), 25);
+        assert!(subdivided
+            .vertices
+            .iter()
+            .all(|vertex| vertex.y == 0.0
+                && [vertex.x, vertex.z].iter().all(|c| c * 4.0 % 1.0 == 0.0)));
+        assert_eq!(subdivide(&square, 0), square);
+
+        // texture coordinates matching x and z are split to keep matching
+        let mut textured = square.clone();
+        textured.texture_coordinates = textured
+            .vertices
+            .iter()
+            .map(|vertex| (vertex.x, vertex.z))
+            .collect();
+        for face in &mut textured.faces {
+            face.texture_coordinates = Some(face.vertices);
+        }
+        let subdivided = subdivide(&textured, 2);
+        assert_eq!(subdivided.texture_coordinates.len(), 25);
+        for face in &subdivided.faces {
+            let corners = face.texture_coordinates.unwrap();
+            for (vertex, corner) in face.vertices.into_iter().zip(corners) {
+                let vertex = subdivided.vertices[vertex];
+                assert_eq!(subdivided.texture_coordinates[corner], (vertex.x, vertex.z));
+            }
+        }
+    }
+
+    #[test]
+    fn loop_subdivide_smooths_cage() {
+        let corners = [
+            [1.0, 1.0, 1.0],
+            [1.0, -1.0, -1.0],
+            [-1.0, 1.0, -1.0],
+            [-1.0, -1.0, 1.0],
+        ];
+        let face = |vertices| ObjFace {
+            vertices,
+            texture_coordinates: None,
+            normals: None,
+            group: None,
+            material: None,
+            line: None,
+        };
+        let tetrahedron = ObjModel {
+            vertices: corners.map(|[x, y, z]| Point::new(x, y, z)).to_vec(),
+            faces: vec![
+                face([0, 1, 2]),
+                face([0, 3, 1]),
+                face([0, 2, 3]),
+                face([1, 3, 2]),
+            ],
+            ..ObjModel::default()
+        };
+        let smoothed = loop_subdivide(&tetrahedron, 1);
+        assert_eq!(smoothed.faces.len(), 16);
+        assert_eq!(smoothed.vertices.len(), 10);
+        // corners are pulled a quarter of the way in, and edge points to
+        // half the distance of the midpoints
+        assert!(smoothed.vertices[0].approx_eq(&Point::new(0.25, 0.25, 0.25)));
+        for vertex in &smoothed.vertices[4..] {
+            approx_eq!((*vertex - Point::zero()).magnitude(), 0.5);
+        }
+
+        // an open sheet stays flat, with its corners pulled in along its edges
+        let sheet = loop_subdivide(&grid(1), 2);
+        assert_eq!(sheet.faces.len(), 32);
+        assert!(sheet.vertices.iter().all(|vertex| vertex.y == 0.0));
+        assert!(sheet.vertices[0].approx_eq(&Point::new(0.15625, 0.0, 0.15625)));
+    }
+
+    #[test]
+    fn displace_vertices_by_pattern() {
+        let white = Colour::new(1.0, 1.0, 1.0);
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let flat = grid(2);
+        let raised = displace(&flat, &Solid::new(white), 0.5);
+        assert!(raised.vertices.iter().all(|vertex| vertex.y == 0.5));
+
+        // stripes one unit wide raise the columns at x = 0 and x = 2
+        let stripes = Stripe::new(white, black, Transform::default());
+        let ridged = displace(&flat, &stripes, 0.5);
+        for (vertex, displaced) in flat.vertices.iter().zip(&ridged.vertices) {
+            let expected = if vertex.x == 1.0 { 0.0 } else { 0.5 };
+            assert_eq!(*displaced, Point::new(vertex.x, expected, vertex.z));
+        }
+        assert_eq!(ridged.faces, flat.faces);
+    }
+
+    #[test]
+    fn divide_into_leaves() {
+        let spheres = (0..8)
+            .map(|index| {
+                Sphere::builder()
+                    .set_frame_transformation(Transform::new(TransformKind::Translate(
+                        index as f64 * 3.0,
+                        0.0,
+                        0.0,
+                    )))
+                    .build_into()
+            })
+            .collect();
+        let mut objects = divide(spheres, 2);
+        objects.push(Plane::builder().build_into());
+        let divided = divide(objects, 2);
+
+        // the plane stays on top, ahead of the two halves of the row of spheres
+        assert_eq!(divided.len(), 3);
+        let leaf_sizes = |group: &Group| -> Vec<usize> {
+            group
+                .objects()
+                .iter()
+                .map(|quarter| match quarter {
+                    Shape::Group(leaf) => leaf.objects().len(),
+                    _ => 0,
+                })
+                .collect()
+        };
+        for half in &divided[1..] {
+            match half {
+                Shape::Group(half) => assert_eq!(leaf_sizes(half), vec![2, 2]),
+                _ => panic!("expected a group"),
+            }
+        }
+    }
+
+    #[test]
+    fn divide_by_surface_area() {
+        let sphere_at = |x: f64| -> Shape {
+            Sphere::builder()
+                .set_frame_transformation(Transform::new(TransformKind::Translate(x, 0.0, 0.0)))
+                .build_into()
+        };
+        let clusters = || {
+            [0.0, 2.0, 4.0, 6.0, 100.0, 102.0, 104.0, 106.0]
+                .into_iter()
+                .map(sphere_at)
+                .collect::<Vec<_>>()
+        };
+
+        // the two clusters are split apart, each into a leaf of its own
+        let divided = divide_with(clusters(), 4, SplitMethod::SURFACE_AREA);
+        assert_eq!(divided.len(), 2);
+        for (cluster, expected_x) in divided.iter().zip([[-1.0, 7.0], [99.0, 107.0]]) {
+            let Shape::Group(cluster) = cluster else {
+                panic!("expected a group");
+            };
+            assert_eq!(cluster.objects().len(), 4);
+            let (x_range, _, _) = cluster.bounds().bounding_box().axial_bounds();
+            assert_eq!(x_range, expected_x);
+        }
+
+        // splitting is not worth it when groups are dear to test
+        let dear_groups = SplitMethod::SurfaceArea {
+            traversal_cost: 100.0,
+            intersection_cost: 1.0,
+        };
+        assert_eq!(divide_with(clusters(), 1, dear_groups).len(), 8);
+    }
+
+    #[test]
+    fn leave_mesh_within_budget_alone() {
+        let sparse = grid(2);
+        assert_eq!(decimate(&sparse, 8), sparse);
+        assert_eq!(decimate(&sparse, 100), sparse);
+    }
+}
@@ -1,15 +1,42 @@
 pub mod builder;
+pub mod error;
 pub(crate) mod filehandler;
 pub(crate) mod floats;
+pub mod meshops;
 pub mod objparser;
+pub mod plyparser;
+pub mod random;
+pub mod sampling;
 
 // crate-level re-exports
 pub(crate) use builder::*;
+pub(crate) use error::*;
 pub(crate) use filehandler::*;
 pub(crate) use floats::*;
+pub(crate) use meshops::*;
 pub(crate) use objparser::*;
+pub(crate) use plyparser::*;
+pub(crate) use random::*;
 
 // public re-exports (through crate::prelude)
 pub(super) mod prelude {
     pub use super::builder::{BuildInto, Buildable, ConsumingBuilder};
+    pub use super::error::{
+        BuildError, Error, IoError, ParseError, ParseErrorKind, Position, RenderError,
+    };
+    #[allow(deprecated)]
+    pub use super::filehandler::DecodeError;
+    pub use super::filehandler::{create_file, read_from_file, write_to_file};
+    pub use super::floats::{ApproxEq, EPSILON};
+    pub use super::meshops::{
+        decimate, displace, divide, divide_with, fit_to_unit_cube, loop_subdivide, subdivide,
+        SplitMethod,
+    };
+    pub use super::objparser::{parse_obj, ObjFace, ObjImporter, ObjModel};
+    pub use super::plyparser::parse_ply;
+    pub use super::random::Rng;
+    pub use super::sampling::{
+        cosine_hemisphere, cosine_hemisphere_pdf, uniform_hemisphere, uniform_hemisphere_pdf,
+        uniform_sphere, uniform_sphere_pdf,
+    };
 }
@@ -1,195 +1,734 @@
-// use std::cell::RefCell;
-// use std::fs::File;
-// use std::io::Read;
-
-// use crate::collections::{Point, Vector};
-// use crate::objects::{Group, Material, Transform, Triangle};
-
-// type ParsedObjects = (Vec<Point>, Vec<Vector>, Vec<Triangle>);
-
-// pub fn parse_obj(file_path: &str) -> Result<ParsedObjects, Box<dyn std::error::Error>> {
-//     let mut file_contents_as_string = String::new();
-//     File::open(file_path)?.read_to_string(&mut file_contents_as_string)?;
-//     let file_lines: Vec<&str> = file_contents_as_string.split("\n").collect();
-
-//     let mut parsed_vertices = vec![];
-//     let mut parsed_normals = vec![];
-//     let mut parsed_shapes: Vec<Triangle> = vec![];
-//     let mut parsed_groups = vec![];
-
-//     let default_group = Group::builder();
-//     let mut current_group: Option<Rc<RefCell<Group>>> = None;
-
-//     for line in file_lines {
-//         match line.split(" ").collect::<Vec<&str>>() {
-//             vertex if vertex[0] == "v" => {
-//                 if let [x_str, y_str, z_str] = vertex[1..4] {
-//                     let x = x_str.parse()?;
-//                     let y = y_str.parse()?;
-//                     let z = z_str.parse()?;
-
-//                     parsed_vertices.push(Point::new(x, y, z));
-//                 } else {
-//                     continue;
-//                 }
-//             }
-
-//             vertex_normal if vertex_normal[0] == "vn" => {
-//                 if let [x_str, y_str, z_str] = vertex_normal[1..4] {
-//                     let x = x_str.parse()?;
-//                     let y = y_str.parse()?;
-//                     let z = z_str.parse()?;
-
-//                     parsed_normals.push(Vector::new(x, y, z));
-//                 } else {
-//                     continue;
-//                 }
-//             }
-
-//             face if face[0] == "f" => {
-//                 if face.len() >= 4 {
-//                     let vertex_indices_as_str = face[1..].to_vec();
-
-//                     let mut vertices = vec![];
-//                     for vertex_idx_str in vertex_indices_as_str {
-//                         let vertex_idx: usize = vertex_idx_str.parse()?;
-
-//                         // 1-indexed to 0-indexed array indices
-//                         vertices.push(parsed_vertices[vertex_idx - 1]);
-//                     }
-
-//                     let triangles = face_triangulation(vertices);
-
-//                     for mut triangle in triangles {
-//                         if current_group.is_some() {
-//                             current_group
-//                                 .as_mut()
-//                                 .unwrap()
-//                                 .borrow_mut()
-//                                 .add_object(&mut triangle);
-//                         } else {
-//                             default_group.borrow_mut().add_object(&mut triangle);
-//                         }
-
-//                         parsed_shapes.push(triangle);
-//                     }
-//                 } else {
-//                     if let [idx1_str, idx2_str, idx3_str] = face[1..4] {
-//                         let idx1: usize = idx1_str.parse()?;
-//                         let idx2: usize = idx2_str.parse()?;
-//                         let idx3: usize = idx3_str.parse()?;
-
-//                         // 1-indexed to 0-indexed array indices
-//                         let vertex1 = parsed_vertices[idx1 - 1];
-//                         let vertex2 = parsed_vertices[idx2 - 1];
-//                         let vertex3 = parsed_vertices[idx3 - 1];
-
-//                         let mut triangle =
-//                             Triangle::new(Material::default(), [vertex1, vertex2, vertex3]);
-//                         if current_group.is_some() {
-//                             current_group
-//                                 .as_mut()
-//                                 .unwrap()
-//                                 .borrow_mut()
-//                                 .add_object(&mut triangle);
-//                         } else {
-//                             default_group.borrow_mut().add_object(&mut triangle);
-//                         }
-
-//                         parsed_shapes.push(triangle);
-//                     } else {
-//                         continue;
-//                     }
-//                 }
-//             }
-
-//             group if group[0] == "g" => {
-//                 if let Some(old_group) = current_group {
-//                     parsed_groups.push(old_group);
-//                 }
-
-//                 let new_group = Group::new::<Triangle>(Transform::default(), vec![]);
-//                 current_group = Some(new_group);
-//                 current_group
-//                     .as_mut()
-//                     .unwrap()
-//                     .borrow_mut()
-//                     .set_parent(Rc::clone(&default_group));
-//             }
-
-//             _ => continue,
-//         }
-//     }
-
-//     if let Some(old_group) = current_group {
-//         parsed_groups.push(old_group);
-//     }
-
-//     Ok((
-//         parsed_vertices,
-//         parsed_normals,
-//         parsed_shapes,
-//         parsed_groups,
-//     ))
-// }
-
-// fn face_triangulation(vertices: Vec<Point>) -> Vec<Triangle> {
-//     assert!(vertices.len() >= 3);
-
-//     let mut parsed_triangles = vec![];
-
-//     let vertex1 = vertices[0];
-//     for (&vertex2, &vertex3) in vertices[1..].iter().zip(vertices[2..].iter()) {
-//         parsed_triangles.push(Triangle::new(vertex1, vertex2, vertex3));
-//     }
-
-//     parsed_triangles
-// }
-
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-
-//     #[test]
-//     fn objparser_ignores_unrecognised_commands() {
-//         let parsed_objects = parse_obj("./resources/gibberish.obj").unwrap();
-//         let (parsed_vertices, parsed_normals, parsed_triangles, parsed_groups) = parsed_objects;
-//         assert_eq!(parsed_vertices.len(), 0);
-//         assert_eq!(parsed_normals.len(), 0);
-//         assert_eq!(parsed_triangles.len(), 0);
-//         assert_eq!(parsed_groups.len(), 1);
-//     }
-
-//     #[test]
-//     fn objparser_parses_vertex_data() {
-//         let parsed_objects = parse_obj("./resources/vertex.obj").unwrap();
-//         let parsed_vertices = parsed_objects.0;
-//         assert_eq!(parsed_vertices.len(), 4);
-//         assert_eq!(parsed_vertices[0], Point::new(-1.0, 1.0, 0.0));
-//         assert_eq!(parsed_vertices[1], Point::new(-1.0, 0.5, 0.0));
-//         assert_eq!(parsed_vertices[2], Point::new(1.0, 0.0, 0.0));
-//         assert_eq!(parsed_vertices[3], Point::new(1.0, 1.0, 0.0));
-//     }
-
-//     #[test]
-//     fn objparser_parses_triangle_data() {
-//         let parsed_objects = parse_obj("./resources/triangle.obj").unwrap();
-//         let parsed_shapes = parsed_objects.2;
-//         assert_eq!(parsed_shapes.len(), 2);
-//     }
-
-//     #[test]
-//     fn objparser_parses_polygon_data() {
-//         let parsed_objects = parse_obj("./resources/polygon.obj").unwrap();
-//         let parsed_shapes = parsed_objects.2;
-//         assert_eq!(parsed_shapes.len(), 3);
-//     }
-
-//     #[test]
-//     fn objparser_parses_groups() {
-//         let parsed_objects = parse_obj("./resources/group.obj").unwrap();
-//         let (_, _, _, parsed_groups) = parsed_objects;
-
-//         assert_eq!(parsed_groups.len(), 3);
-//     }
-// }
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::collections::{Colour, Point, Vector};
+use crate::objects::{Group, Material, Shape, SmoothTriangle, Triangle};
+use crate::utils::{
+    fit_to_unit_cube, loop_subdivide, meshops, parse_ply, read_from_file, BuildInto, Buildable,
+    ConsumingBuilder, Error, ParseError, ParseErrorKind, Position, SplitMethod,
+};
+
+// A Wavefront OBJ model (or a PLY model, see parse_ply), with polygons split
+// into triangle fans and all indices counted from 0.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjModel {
+    pub vertices: Vec<Point>,
+    // one per vertex when any vertex has a colour (white for those without),
+    // otherwise empty
+    pub colours: Vec<Colour>,
+    // (u, v) positions on a texture, from `vt` lines
+    pub texture_coordinates: Vec<(f64, f64)>,
+    pub normals: Vec<Vector>,
+    pub faces: Vec<ObjFace>,
+    // names from `g` lines, indexed by ObjFace::group
+    pub groups: Vec<String>,
+    // names from `usemtl` lines, indexed by ObjFace::material
+    pub materials: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObjFace {
+    pub vertices: [usize; 3],
+    // each only set when every corner of the face has one
+    pub texture_coordinates: Option<[usize; 3]>,
+    pub normals: Option<[usize; 3]>,
+    pub group: Option<usize>,
+    pub material: Option<usize>,
+    // the `f` line the face was read from, for reporting faces with no area
+    pub line: Option<usize>,
+}
+
+impl ObjModel {
+    // gives each face without normals a normal at every corner, averaged from
+    // the area-weighted normals of those faces around the vertex, so curved
+    // surfaces modelled with flat faces shade smoothly
+    pub fn generate_normals(&mut self) {
+        let mut normal_sums = vec![Vector::zero(); self.vertices.len()];
+        for face in self.faces.iter().filter(|face| face.normals.is_none()) {
+            let [v1, v2, v3] = face.vertices.map(|vertex| self.vertices[vertex]);
+            let face_normal = (v3 - v1).cross(v2 - v1);
+            for vertex in face.vertices {
+                normal_sums[vertex] = normal_sums[vertex] + face_normal;
+            }
+        }
+
+        let mut normal_indices = vec![None; self.vertices.len()];
+        for face in self.faces.iter_mut().filter(|face| face.normals.is_none()) {
+            let corners = face.vertices.map(|vertex| {
+                if normal_sums[vertex].magnitude() == 0.0 {
+                    return None;
+                }
+                Some(*normal_indices[vertex].get_or_insert_with(|| {
+                    self.normals.push(normal_sums[vertex].normalise());
+                    self.normals.len() - 1
+                }))
+            });
+            // a vertex touched only by faces with no area keeps its faces flat
+            if let [Some(n1), Some(n2), Some(n3)] = corners {
+                face.normals = Some([n1, n2, n3]);
+            }
+        }
+    }
+}
+
+// Reads vertices (`v`, optionally followed by a red, green and blue colour
+// from 0 to 1), texture coordinates (`vt`), normals (`vn`), faces (`f`),
+// groups (`g`) and material boundaries (`usemtl`); anything else is ignored.
+pub fn parse_obj(source: &str) -> Result<ObjModel, ParseError> {
+    let mut model = ObjModel::default();
+    let mut colours = vec![];
+    let mut group = None;
+    let mut material = None;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let invalid_line = ParseErrorKind::InvalidValue.at(Position::Line(line_index + 1));
+        let mut tokens = line.split('#').next().unwrap_or("").split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let [x, y, z] = parse_coordinates(&mut tokens).ok_or(invalid_line)?;
+                model.vertices.push(Point::new(x, y, z));
+                // a single value left over is the rarely used w coordinate
+                let rest: Vec<&str> = tokens.collect();
+                colours.push(match rest.len() {
+                    0 | 1 => None,
+                    3 => {
+                        let [red, green, blue] =
+                            parse_coordinates(&mut rest.into_iter()).ok_or(invalid_line)?;
+                        Some(Colour::new(red, green, blue))
+                    }
+                    _ => return Err(invalid_line),
+                });
+            }
+            Some("vt") => {
+                // v (and the rarely used w) may be left out
+                let values: Vec<f64> = tokens
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .ok()
+                    .filter(|values: &Vec<f64>| (1..=3).contains(&values.len()))
+                    .ok_or(invalid_line)?;
+                let v = values.get(1).copied().unwrap_or(0.0);
+                model.texture_coordinates.push((values[0], v));
+            }
+            Some("vn") => {
+                let [x, y, z] = parse_coordinates(&mut tokens).ok_or(invalid_line)?;
+                model.normals.push(Vector::new(x, y, z));
+            }
+            Some("f") => {
+                let corners = tokens
+                    .map(|token| parse_corner(token, &model))
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|corners| corners.len() >= 3)
+                    .ok_or(invalid_line)?;
+                for pair in corners[1..].windows(2) {
+                    let corners = [corners[0], pair[0], pair[1]];
+                    let all = |indices: [Option<usize>; 3]| match indices {
+                        [Some(i1), Some(i2), Some(i3)] => Some([i1, i2, i3]),
+                        _ => None,
+                    };
+                    model.faces.push(ObjFace {
+                        vertices: corners.map(|(vertex, _, _)| vertex),
+                        texture_coordinates: all(corners.map(|(_, texture, _)| texture)),
+                        normals: all(corners.map(|(_, _, normal)| normal)),
+                        group,
+                        material,
+                        line: Some(line_index + 1),
+                    });
+                }
+            }
+            Some("g") => group = tokens.next().map(|name| index_of(&mut model.groups, name)),
+            Some("usemtl") => {
+                let name = tokens.next().ok_or(invalid_line)?;
+                material = Some(index_of(&mut model.materials, name));
+            }
+            _ => continue,
+        }
+    }
+
+    if colours.iter().any(Option::is_some) {
+        model.colours = colours
+            .into_iter()
+            .map(|colour| colour.unwrap_or(Colour::new(1.0, 1.0, 1.0)))
+            .collect();
+    }
+    Ok(model)
+}
+
+fn parse_coordinates<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<[f64; 3]> {
+    let mut next = || tokens.next().and_then(|token| token.parse().ok());
+    Some([next()?, next()?, next()?])
+}
+
+// a face corner is `v`, `v/vt`, `v//vn` or `v/vt/vn`, where negative indices
+// count back from the most recent vertex, texture coordinate or normal;
+// texture coordinates that were never given are ignored
+fn parse_corner(token: &str, model: &ObjModel) -> Option<(usize, Option<usize>, Option<usize>)> {
+    let mut indices = token.split('/');
+    let vertex = resolve_index(indices.next()?, model.vertices.len())?;
+    let texture = indices
+        .next()
+        .and_then(|index| resolve_index(index, model.texture_coordinates.len()));
+    let normal = match indices.next() {
+        Some(index) if !index.is_empty() => Some(resolve_index(index, model.normals.len())?),
+        _ => None,
+    };
+    Some((vertex, texture, normal))
+}
+
+fn resolve_index(index: &str, count: usize) -> Option<usize> {
+    let index: isize = index.parse().ok()?;
+    let resolved = match index {
+        0 => return None,
+        index if index > 0 => index as usize - 1,
+        index => count.checked_sub(index.unsigned_abs())?,
+    };
+    (resolved < count).then_some(resolved)
+}
+
+fn index_of(names: &mut Vec<String>, name: &str) -> usize {
+    names
+        .iter()
+        .position(|existing| existing == name)
+        .unwrap_or_else(|| {
+            names.push(name.to_string());
+            names.len() - 1
+        })
+}
+
+// Turns OBJ models into a Group of triangles. Named OBJ groups become named
+// sub-groups, and faces with normals become smooth triangles. Triangles
+// sharing a material share one copy of it, so a model can carry several
+// materials without being split up by hand.
+#[derive(Debug, Default)]
+pub struct ObjImporter {
+    default_material: Option<Arc<Material>>,
+    named_materials: Vec<(String, Arc<Material>)>,
+    face_materials: Vec<(Range<usize>, Arc<Material>)>,
+    smooth_normals: bool,
+    fit_to_unit_cube: bool,
+    subdivision_levels: usize,
+    leaf_size: Option<usize>,
+    split_method: SplitMethod,
+}
+
+impl ObjImporter {
+    // each level makes four faces of every one, so more than this would not
+    // fit in memory anyway
+    pub const MAX_SUBDIVISION_LEVELS: usize = 12;
+
+    pub fn new() -> ObjImporter {
+        ObjImporter::default()
+    }
+
+    // for faces that no other material applies to
+    pub fn with_default_material(mut self, material: Material) -> ObjImporter {
+        self.default_material = Some(Arc::new(material));
+        self
+    }
+
+    // for faces following a `usemtl` line with this name
+    pub fn with_material(mut self, name: &str, material: Material) -> ObjImporter {
+        self.named_materials
+            .push((name.to_string(), Arc::new(material)));
+        self
+    }
+
+    // for a range of ObjModel::faces (counted after polygons are split into
+    // triangles); takes priority over `usemtl`, and later ranges take
+    // priority over earlier ones
+    pub fn with_face_material(mut self, faces: Range<usize>, material: Material) -> ObjImporter {
+        self.face_materials.push((faces, Arc::new(material)));
+        self
+    }
+
+    // generates normals for faces that have none (see
+    // ObjModel::generate_normals) rather than leaving them flat
+    pub fn with_smooth_normals(mut self, smooth_normals: bool) -> ObjImporter {
+        self.smooth_normals = smooth_normals;
+        self
+    }
+
+    // centres and scales the model to fit the cube from -1 to 1 (see
+    // meshops::fit_to_unit_cube), so any model can stand in for a unit shape
+    pub fn with_fit_to_unit_cube(mut self, fit_to_unit_cube: bool) -> ObjImporter {
+        self.fit_to_unit_cube = fit_to_unit_cube;
+        self
+    }
+
+    // smooths the model with this many levels of Loop subdivision (see
+    // meshops::loop_subdivide), after fitting it to the unit cube; corner
+    // normals are lost, so this is best paired with smooth normals. Face
+    // material ranges still count the faces of the model as parsed. Levels
+    // beyond MAX_SUBDIVISION_LEVELS are capped to it.
+    pub fn with_subdivision(mut self, levels: usize) -> ObjImporter {
+        self.subdivision_levels = levels.min(ObjImporter::MAX_SUBDIVISION_LEVELS);
+        self
+    }
+
+    // splits each group of triangles into a tree of sub-groups with at most
+    // this many objects at the leaves (see meshops::divide)
+    pub fn with_leaf_size(mut self, leaf_size: usize) -> ObjImporter {
+        self.leaf_size = Some(leaf_size);
+        self
+    }
+
+    // how groups are split when a leaf size is given (the median split by
+    // default)
+    pub fn with_split_method(mut self, split_method: SplitMethod) -> ObjImporter {
+        self.split_method = split_method;
+        self
+    }
+
+    pub fn import(&self, source: &str) -> Result<Group, ParseError> {
+        self.try_build(&parse_obj(source)?)
+    }
+
+    pub fn import_ply(&self, bytes: &[u8]) -> Result<Group, ParseError> {
+        self.try_build(&parse_ply(bytes)?)
+    }
+
+    // reads an OBJ or PLY file, telling them apart by their first bytes
+    pub fn open(&self, path: &str) -> Result<Group, Error> {
+        let bytes = read_from_file(path)?;
+        if bytes.starts_with(b"ply") {
+            return Ok(self.import_ply(&bytes)?);
+        }
+        let source = String::from_utf8(bytes).map_err(|error| {
+            ParseErrorKind::InvalidValue.at(Position::Byte(error.utf8_error().valid_up_to()))
+        })?;
+        Ok(self.import(&source)?)
+    }
+
+    pub fn build(&self, model: &ObjModel) -> Group {
+        self.try_build(model)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // a face with no area is reported as an invalid value, placed at the line
+    // it was read from when it came from OBJ source
+    pub fn try_build(&self, model: &ObjModel) -> Result<Group, ParseError> {
+        let mut model = Cow::Borrowed(model);
+        if self.fit_to_unit_cube {
+            model = Cow::Owned(fit_to_unit_cube(&model));
+        }
+        if self.subdivision_levels > 0 {
+            model = Cow::Owned(loop_subdivide(&model, self.subdivision_levels));
+        }
+        if self.smooth_normals {
+            model.to_mut().generate_normals();
+        }
+        let model = model.as_ref();
+
+        let default_material = self.default_material.clone().unwrap_or_default();
+        let usemtl_materials: Vec<Arc<Material>> = model
+            .materials
+            .iter()
+            .map(|name| {
+                self.named_materials
+                    .iter()
+                    .rev()
+                    .find(|(material_name, _)| material_name == name)
+                    .map_or_else(
+                        || Arc::clone(&default_material),
+                        |(_, material)| Arc::clone(material),
+                    )
+            })
+            .collect();
+
+        let mut ungrouped = vec![];
+        let mut grouped: Vec<Vec<Shape>> = model.groups.iter().map(|_| vec![]).collect();
+        // each parsed face is split into this many, which stay together
+        let split_faces = 4_usize.pow(self.subdivision_levels as u32);
+        for (index, face) in model.faces.iter().enumerate() {
+            let material = self
+                .face_materials
+                .iter()
+                .rev()
+                .find(|(faces, _)| faces.contains(&(index / split_faces)))
+                .map(|(_, material)| material)
+                .or_else(|| face.material.map(|material| &usemtl_materials[material]))
+                .unwrap_or(&default_material);
+            let triangle = self.build_face(model, face, Arc::clone(material))?;
+            match face.group {
+                Some(group) => grouped[group].push(triangle),
+                None => ungrouped.push(triangle),
+            }
+        }
+
+        let sub_groups = model
+            .groups
+            .iter()
+            .zip(grouped)
+            .filter(|(_, objects)| !objects.is_empty())
+            .map(|(name, objects)| {
+                Group::builder()
+                    .set_name(name)
+                    .set_objects(self.divide(objects))
+                    .build_into()
+            });
+        let mut objects = self.divide(ungrouped);
+        objects.extend(sub_groups);
+        Ok(Group::builder().set_objects(objects).build())
+    }
+
+    fn divide(&self, objects: Vec<Shape>) -> Vec<Shape> {
+        match self.leaf_size {
+            Some(leaf_size) => meshops::divide_with(objects, leaf_size, self.split_method),
+            None => objects,
+        }
+    }
+
+    fn build_face(
+        &self,
+        model: &ObjModel,
+        face: &ObjFace,
+        material: Arc<Material>,
+    ) -> Result<Shape, ParseError> {
+        let vertices = face.vertices.map(|vertex| model.vertices[vertex]);
+        let colours = match model.colours.is_empty() {
+            true => None,
+            false => Some(face.vertices.map(|vertex| model.colours[vertex])),
+        };
+        let texture_coordinates = face
+            .texture_coordinates
+            .map(|corners| corners.map(|corner| model.texture_coordinates[corner]));
+        let built = match face.normals {
+            Some(normals) => {
+                let mut builder = SmoothTriangle::builder()
+                    .set_vertices(vertices)
+                    .set_normals(normals.map(|normal| model.normals[normal]))
+                    .set_shared_material(material);
+                if let Some(colours) = colours {
+                    builder = builder.set_colours(colours);
+                }
+                if let Some(texture_coordinates) = texture_coordinates {
+                    builder = builder.set_texture_coordinates(texture_coordinates);
+                }
+                builder.try_build().map(Shape::from)
+            }
+            None => {
+                let mut builder = Triangle::builder()
+                    .set_vertices(vertices)
+                    .set_shared_material(material);
+                if let Some(colours) = colours {
+                    builder = builder.set_colours(colours);
+                }
+                if let Some(texture_coordinates) = texture_coordinates {
+                    builder = builder.set_texture_coordinates(texture_coordinates);
+                }
+                builder.try_build().map(Shape::from)
+            }
+        };
+        built.map_err(|_| match face.line {
+            Some(line) => ParseErrorKind::InvalidValue.at(Position::Line(line)),
+            None => ParseErrorKind::InvalidValue.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::Colour;
+    use crate::objects::{
+        Bounded, BoundingBox, Intersectable, PrimitiveShape, Ray, Solid, Transform, UvChecker,
+    };
+
+    fn read_input(name: &str) -> String {
+        let bytes = read_from_file(&format!("./resources/test_inputs/{}", name)).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn primitives(group: &Group) -> Vec<&dyn PrimitiveShape> {
+        group
+            .objects()
+            .iter()
+            .flat_map(|object| match object {
+                Shape::Primitive(primitive) => vec![primitive.as_ref()],
+                Shape::Group(group) => primitives(group),
+                Shape::Csg(_) | Shape::Clipped(_) => vec![],
+            })
+            .collect()
+    }
+
+    fn coloured(red: f64, green: f64, blue: f64) -> Material {
+        Material {
+            pattern: Box::new(Solid::new(Colour::new(red, green, blue))),
+            ..Material::preset()
+        }
+    }
+
+    #[test]
+    fn ignore_unrecognised_lines() {
+        let model = parse_obj(&read_input("gibberish.obj")).unwrap();
+        assert_eq!(model, ObjModel::default());
+    }
+
+    #[test]
+    fn parse_vertices() {
+        let model = parse_obj(&read_input("vertex.obj")).unwrap();
+        assert_eq!(
+            model.vertices,
+            vec![
+                Point::new(-1.0, 1.0, 0.0),
+                Point::new(-1.0, 0.5, 0.0),
+                Point::new(1.0, 0.0, 0.0),
+                Point::new(1.0, 1.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_triangles_and_polygons() {
+        let triangles = parse_obj(&read_input("triangle.obj")).unwrap();
+        assert_eq!(triangles.faces.len(), 2);
+        assert_eq!(triangles.faces[1].vertices, [0, 2, 3]);
+
+        let polygon = parse_obj(&read_input("polygon.obj")).unwrap();
+        let fan: Vec<[usize; 3]> = polygon.faces.iter().map(|face| face.vertices).collect();
+        assert_eq!(fan, vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+    }
+
+    #[test]
+    fn parse_groups() {
+        let model = parse_obj(&read_input("group.obj")).unwrap();
+        assert_eq!(model.groups, vec!["FirstGroup", "SecondGroup"]);
+        assert_eq!(model.faces[0].group, Some(0));
+        assert_eq!(model.faces[1].group, Some(1));
+
+        let group = ObjImporter::new().build(&model);
+        assert_eq!(group.objects().len(), 2);
+        assert!(group.find_object("SecondGroup").is_some());
+    }
+
+    #[test]
+    fn parse_normals_and_relative_indices() {
+        let source = "v 0 1 0\nv -1 0 0\nv 1 0 0\nvn -1 0 0\nvn 1 0 0\nvn 0 1 0\n\
+                      f 1//3 2//1 3//2\nf -3/1/-1 -2/2/-3 -1/3/-2\nf 1/1 2/2 3/3\n";
+        let model = parse_obj(source).unwrap();
+        assert_eq!(model.faces[0].normals, Some([2, 0, 1]));
+        assert_eq!(
+            model.faces[1],
+            ObjFace {
+                line: Some(8),
+                ..model.faces[0]
+            }
+        );
+        assert_eq!(model.faces[2].normals, None);
+    }
+
+    #[test]
+    fn generate_averaged_normals() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nf 1 2 3\nf 1 3 4\n";
+        let mut model = parse_obj(source).unwrap();
+        model.generate_normals();
+        let corner_normals = |face: usize| {
+            model.faces[face]
+                .normals
+                .unwrap()
+                .map(|normal| model.normals[normal])
+        };
+        let fold = Vector::new(-1.0, 0.0, -1.0).normalise();
+        assert_eq!(corner_normals(0), [fold, Vector::new(0.0, 0.0, -1.0), fold]);
+        assert_eq!(corner_normals(1), [fold, fold, Vector::new(-1.0, 0.0, 0.0)]);
+        // shared vertices share a normal
+        assert_eq!(model.normals.len(), 4);
+    }
+
+    #[test]
+    fn import_with_smooth_normals() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nf 1 2 3\nf 1 3 4\n";
+        let ray = Ray::new(Point::new(0.2, 0.2, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let normal_of = |importer: ObjImporter| {
+            let mesh: Shape = importer.import(source).unwrap().into();
+            let hit = mesh.intersect_ray(&ray, vec![]).finalise_hit().unwrap();
+            hit.normal()
+        };
+        assert_eq!(normal_of(ObjImporter::new()), Vector::new(0.0, 0.0, -1.0));
+        assert!(normal_of(ObjImporter::new().with_smooth_normals(true)).x < 0.0);
+    }
+
+    #[test]
+    fn vertex_colours_stand_in_for_pattern() {
+        let source = "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0\nf 1 2 3\n";
+        let model = parse_obj(source).unwrap();
+        assert_eq!(model.colours[2], Colour::new(1.0, 1.0, 1.0));
+        assert!(parse_obj("v 0 0 0 1\n").unwrap().colours.is_empty());
+        assert_eq!(
+            parse_obj("v 0 0 0 1 0\n"),
+            Err(ParseErrorKind::InvalidValue.at(Position::Line(1)))
+        );
+
+        let ray = Ray::new(Point::new(0.25, 0.25, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let colour_of = |material: Material| {
+            let importer = ObjImporter::new().with_default_material(material);
+            let mesh: Shape = importer.build(&model).into();
+            let hit = mesh.intersect_ray(&ray, vec![]).finalise_hit().unwrap();
+            hit.surface_colour()
+        };
+        assert_eq!(colour_of(Material::preset()), Colour::new(0.75, 0.5, 0.25));
+        let blue = Colour::new(0.0, 0.0, 1.0);
+        assert_eq!(
+            colour_of(Material::builder().set_colour(blue).build()),
+            blue
+        );
+        // a pattern set on purpose is kept even when it matches the preset
+        let white = Colour::new(1.0, 1.0, 1.0);
+        assert_eq!(
+            colour_of(Material::builder().set_colour(white).build()),
+            white
+        );
+        let material = Material::builder()
+            .set_colour(blue)
+            .set_vertex_colours(true)
+            .build();
+        assert_eq!(colour_of(material), Colour::new(0.75, 0.5, 0.25));
+    }
+
+    #[test]
+    fn texture_coordinates_place_uv_patterns() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nf 1/1 2/2 3/3\n";
+        let model = parse_obj(source).unwrap();
+        assert_eq!(model.faces[0].texture_coordinates, Some([0, 1, 2]));
+        assert_eq!(
+            parse_obj("vt 0.5 x\n"),
+            Err(ParseErrorKind::InvalidValue.at(Position::Line(1)))
+        );
+
+        let white = Colour::new(1.0, 1.0, 1.0);
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let checker = UvChecker::new(white, black, [2.0, 2.0], Transform::default());
+        let material = Material::builder().set_pattern(Box::new(checker)).build();
+        let mesh: Shape = ObjImporter::new()
+            .with_default_material(material)
+            .build(&model)
+            .into();
+        // a planar mapping would see u = 0.25, v = 0 here, a white square
+        let ray = Ray::new(Point::new(0.25, 0.6, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = mesh.intersect_ray(&ray, vec![]).finalise_hit().unwrap();
+        assert_eq!(hit.texture_coordinates(), Some((0.25, 0.6)));
+        assert_eq!(hit.surface_colour(), black);
+    }
+
+    #[test]
+    fn import_fitted_to_unit_cube() {
+        let source = "v 10 0 0\nv 14 0 0\nv 10 2 1\nf 1 2 3\n";
+        let group = ObjImporter::new()
+            .with_fit_to_unit_cube(true)
+            .import(source)
+            .unwrap();
+        assert_eq!(
+            group.bounds().bounding_box(),
+            BoundingBox::from_axial_bounds([-1.0, 1.0], [-0.5, 0.5], [-0.25, 0.25])
+        );
+    }
+
+    #[test]
+    fn import_divided_into_leaves() {
+        let source: String = (0..16)
+            .map(|index| {
+                let x = index as f64 * 2.0;
+                format!("v {} 0 0\nv {} 0 0\nv {} 1 0\nf -3 -2 -1\n", x, x + 1.0, x)
+            })
+            .collect();
+        let flat = ObjImporter::new().import(&source).unwrap();
+        assert_eq!(flat.objects().len(), 16);
+
+        let divided = ObjImporter::new()
+            .with_leaf_size(4)
+            .import(&source)
+            .unwrap();
+        assert_eq!(divided.objects().len(), 2);
+        assert_eq!(primitives(&divided).len(), 16);
+    }
+
+    #[test]
+    fn report_the_line_of_bad_data() {
+        assert_eq!(
+            parse_obj("v 1 2 3\nv 1 x 3"),
+            Err(ParseErrorKind::InvalidValue.at(Position::Line(2)))
+        );
+        assert_eq!(
+            parse_obj("v 1 2 3\nf 1 1"),
+            Err(ParseErrorKind::InvalidValue.at(Position::Line(2)))
+        );
+        assert_eq!(
+            parse_obj("v 1 2 3\nf 1 1 2"),
+            Err(ParseErrorKind::InvalidValue.at(Position::Line(2)))
+        );
+        assert_eq!(
+            parse_obj("v 1 2 3\nf 0 1 1"),
+            Err(ParseErrorKind::InvalidValue.at(Position::Line(2)))
+        );
+    }
+
+    #[test]
+    fn report_the_line_of_faces_with_no_area() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 2 0 0\nf 1 2 3\nvn 0 0 1\nf 1 2 4\n";
+        let error = ObjImporter::new().import(source).unwrap_err();
+        assert_eq!(error, ParseErrorKind::InvalidValue.at(Position::Line(7)));
+
+        let smooth = "v 0 0 0\nv 1 0 0\nv 2 0 0\nvn 0 0 1\nf 1//1 2//1 3//1\n";
+        let error = ObjImporter::new().import(smooth).unwrap_err();
+        assert_eq!(error, ParseErrorKind::InvalidValue.at(Position::Line(5)));
+    }
+
+    #[test]
+    fn assign_materials_by_usemtl() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\n\
+                      f 1 2 3\nusemtl red\nf 2 4 3\nusemtl blue\nf 1 2 4\nusemtl red\nf 1 4 3\n";
+        let group = ObjImporter::new()
+            .with_default_material(coloured(1.0, 1.0, 1.0))
+            .with_material("red", coloured(1.0, 0.0, 0.0))
+            .import(source)
+            .unwrap();
+        let materials: Vec<&Material> = primitives(&group)
+            .into_iter()
+            .map(|triangle| triangle.material())
+            .collect();
+        assert_eq!(materials[0], &coloured(1.0, 1.0, 1.0));
+        assert_eq!(materials[1], &coloured(1.0, 0.0, 0.0));
+        // a material that was not supplied falls back to the default
+        assert_eq!(materials[2], &coloured(1.0, 1.0, 1.0));
+        assert!(std::ptr::eq(materials[1], materials[3]));
+    }
+
+    #[test]
+    fn assign_materials_by_face_range() {
+        let model = parse_obj(&read_input("polygon.obj")).unwrap();
+        let group = ObjImporter::new()
+            .with_face_material(0..2, coloured(1.0, 0.0, 0.0))
+            .with_face_material(1..3, coloured(0.0, 0.0, 1.0))
+            .build(&model);
+        let materials: Vec<&Material> = primitives(&group)
+            .into_iter()
+            .map(|triangle| triangle.material())
+            .collect();
+        assert_eq!(materials[0], &coloured(1.0, 0.0, 0.0));
+        assert_eq!(materials[1], &coloured(0.0, 0.0, 1.0));
+        assert!(std::ptr::eq(materials[1], materials[2]));
+    }
+
+    #[test]
+    fn import_subdivided() {
+        let model = parse_obj(&read_input("polygon.obj")).unwrap();
+        let group = ObjImporter::new()
+            .with_subdivision(1)
+            .with_face_material(1..2, coloured(1.0, 0.0, 0.0))
+            .build(&model);
+        let triangles = primitives(&group);
+        assert_eq!(triangles.len(), 4 * model.faces.len());
+        // the faces split from the second parsed face keep its material
+        for (index, triangle) in triangles.iter().enumerate() {
+            let in_range = (4..8).contains(&index);
+            assert_eq!(triangle.material() == &coloured(1.0, 0.0, 0.0), in_range);
+        }
+
+        let capped = ObjImporter::new().with_subdivision(usize::MAX);
+        assert_eq!(
+            capped.subdivision_levels,
+            ObjImporter::MAX_SUBDIVISION_LEVELS
+        );
+    }
+}
@@ -0,0 +1,491 @@
+use crate::collections::{Colour, Point, Vector};
+use crate::utils::{ObjFace, ObjModel, ParseError, ParseErrorKind, Position};
+
+// Reads a PLY (Stanford polygon) file, in ASCII or either binary encoding,
+// into the same model OBJ files are read into, so that it can be built with
+// ObjImporter. Vertices may carry normals (`nx`, `ny`, `nz`), texture
+// coordinates (`u`, `v` or the like) and colours (`red`, `green`, `blue`,
+// with integers scaled by the largest value of their type), and faces are
+// split into triangle fans. Other elements and properties are skipped.
+pub fn parse_ply(bytes: &[u8]) -> Result<ObjModel, ParseError> {
+    let (header, body) = split_header(bytes)?;
+    let (format, elements) = parse_header(&header)?;
+    let mut reader = BodyReader {
+        bytes,
+        position: bytes.len() - body.len(),
+        format,
+    };
+
+    let mut model = ObjModel::default();
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut reader, element, &mut model)?,
+            "face" => read_faces(&mut reader, element, &mut model)?,
+            _ => {
+                for _ in 0..element.count {
+                    reader.read_row(element)?;
+                }
+            }
+        }
+    }
+
+    let vertex_count = model.vertices.len();
+    if model
+        .faces
+        .iter()
+        .any(|face| face.vertices.iter().any(|&vertex| vertex >= vertex_count))
+    {
+        return Err(ParseErrorKind::InvalidValue.into());
+    }
+    Ok(model)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    Binary { big_endian: bool },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    // both the original and the sized names are in use
+    fn from_name(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "char" | "int8" => Scalar::Int8,
+            "uchar" | "uint8" => Scalar::Uint8,
+            "short" | "int16" => Scalar::Int16,
+            "ushort" | "uint16" => Scalar::Uint16,
+            "int" | "int32" => Scalar::Int32,
+            "uint" | "uint32" => Scalar::Uint32,
+            "float" | "float32" => Scalar::Float32,
+            "double" | "float64" => Scalar::Float64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::Uint8 => 1,
+            Scalar::Int16 | Scalar::Uint16 => 2,
+            Scalar::Int32 | Scalar::Uint32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+
+    // the value standing for full intensity in a colour channel
+    fn full_intensity(self) -> f64 {
+        match self {
+            Scalar::Int8 => i8::MAX as f64,
+            Scalar::Uint8 => u8::MAX as f64,
+            Scalar::Int16 => i16::MAX as f64,
+            Scalar::Uint16 => u16::MAX as f64,
+            Scalar::Int32 => i32::MAX as f64,
+            Scalar::Uint32 => u32::MAX as f64,
+            Scalar::Float32 | Scalar::Float64 => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PropertyKind {
+    Scalar(Scalar),
+    // a count followed by that many items
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn position(&self, name: &str) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| property.name == name)
+    }
+
+    // the places of several scalar properties, if there are all of them
+    fn scalars<const N: usize>(&self, names: [&str; N]) -> Option<[(usize, Scalar); N]> {
+        let found = names.map(|name| {
+            let position = self.position(name)?;
+            match self.properties[position].kind {
+                PropertyKind::Scalar(scalar) => Some((position, scalar)),
+                PropertyKind::List { .. } => None,
+            }
+        });
+        match found.contains(&None) {
+            true => None,
+            false => Some(found.map(|scalar| scalar.expect("every property was found"))),
+        }
+    }
+}
+
+// the header's lines (up to `end_header`) and the bytes after it
+fn split_header(bytes: &[u8]) -> Result<(Vec<&str>, &[u8]), ParseError> {
+    let mut lines = vec![];
+    let mut rest = bytes;
+    loop {
+        let line_number = Position::Line(lines.len() + 1);
+        let length = rest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or(ParseErrorKind::InvalidHeader.at(line_number))?;
+        let line = std::str::from_utf8(&rest[..length])
+            .map_err(|_| ParseErrorKind::InvalidHeader.at(line_number))?
+            .trim_end();
+        rest = &rest[length + 1..];
+        if line == "end_header" {
+            return Ok((lines, rest));
+        }
+        lines.push(line);
+    }
+}
+
+fn parse_header(lines: &[&str]) -> Result<(Format, Vec<Element>), ParseError> {
+    if lines.first() != Some(&"ply") {
+        return Err(ParseErrorKind::InvalidHeader.at(Position::Line(1)));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for (index, line) in lines.iter().enumerate().skip(1) {
+        parse_header_line(line, &mut format, &mut elements)
+            .map_err(|kind| kind.at(Position::Line(index + 1)))?;
+    }
+    // the format line is missing, which shows at the end of the header
+    let end_header = Position::Line(lines.len() + 1);
+    Ok((
+        format.ok_or(ParseErrorKind::InvalidHeader.at(end_header))?,
+        elements,
+    ))
+}
+
+fn parse_header_line(
+    line: &str,
+    format: &mut Option<Format>,
+    elements: &mut Vec<Element>,
+) -> Result<(), ParseErrorKind> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    match tokens.as_slice() {
+        ["format", name, "1.0"] => {
+            *format = Some(match *name {
+                "ascii" => Format::Ascii,
+                "binary_little_endian" => Format::Binary { big_endian: false },
+                "binary_big_endian" => Format::Binary { big_endian: true },
+                _ => return Err(ParseErrorKind::Unsupported),
+            })
+        }
+        ["element", name, count] => elements.push(Element {
+            name: name.to_string(),
+            count: count.parse().map_err(|_| ParseErrorKind::InvalidHeader)?,
+            properties: vec![],
+        }),
+        ["property", "list", count, item, name] => {
+            let element = elements.last_mut().ok_or(ParseErrorKind::InvalidHeader)?;
+            let [count, item] = [count, item].map(|name| Scalar::from_name(name));
+            element.properties.push(Property {
+                name: name.to_string(),
+                kind: PropertyKind::List {
+                    count: count.ok_or(ParseErrorKind::Unsupported)?,
+                    item: item.ok_or(ParseErrorKind::Unsupported)?,
+                },
+            });
+        }
+        ["property", scalar, name] => {
+            let element = elements.last_mut().ok_or(ParseErrorKind::InvalidHeader)?;
+            element.properties.push(Property {
+                name: name.to_string(),
+                kind: PropertyKind::Scalar(
+                    Scalar::from_name(scalar).ok_or(ParseErrorKind::Unsupported)?,
+                ),
+            });
+        }
+        ["comment", ..] | ["obj_info", ..] | [] => (),
+        _ => return Err(ParseErrorKind::InvalidHeader),
+    }
+    Ok(())
+}
+
+fn read_vertices(
+    reader: &mut BodyReader,
+    element: &Element,
+    model: &mut ObjModel,
+) -> Result<(), ParseError> {
+    let [x, y, z] = element
+        .scalars(["x", "y", "z"])
+        .ok_or(ParseErrorKind::InvalidHeader)?;
+    let normal = element.scalars(["nx", "ny", "nz"]);
+    let colour = element.scalars(["red", "green", "blue"]);
+    // texture coordinates go by several names
+    let texture = [["u", "v"], ["s", "t"], ["texture_u", "texture_v"]]
+        .into_iter()
+        .find_map(|names| element.scalars(names));
+    for _ in 0..element.count {
+        let row = reader.read_row(element)?;
+        let value = |(position, _): (usize, Scalar)| row[position][0];
+        model
+            .vertices
+            .push(Point::new(value(x), value(y), value(z)));
+        if let Some([x, y, z]) = normal {
+            model
+                .normals
+                .push(Vector::new(value(x), value(y), value(z)));
+        }
+        if let Some(channels) = colour {
+            let [red, green, blue] =
+                channels.map(|channel| value(channel) / channel.1.full_intensity());
+            model.colours.push(Colour::new(red, green, blue));
+        }
+        if let Some([u, v]) = texture {
+            model.texture_coordinates.push((value(u), value(v)));
+        }
+    }
+    Ok(())
+}
+
+// each vertex's normal and texture coordinates have the same index as the
+// vertex
+fn read_faces(
+    reader: &mut BodyReader,
+    element: &Element,
+    model: &mut ObjModel,
+) -> Result<(), ParseError> {
+    let indices = element
+        .position("vertex_indices")
+        .or_else(|| element.position("vertex_index"))
+        .ok_or(ParseErrorKind::InvalidHeader)?;
+    for _ in 0..element.count {
+        let row = reader.read_row(element)?;
+        let corners = row[indices]
+            .iter()
+            .map(|&index| (index >= 0.0 && index.fract() == 0.0).then_some(index as usize))
+            .collect::<Option<Vec<usize>>>()
+            .filter(|corners| corners.len() >= 3)
+            .ok_or(reader.error(ParseErrorKind::InvalidValue))?;
+        for pair in corners[1..].windows(2) {
+            let vertices = [corners[0], pair[0], pair[1]];
+            model.faces.push(ObjFace {
+                vertices,
+                texture_coordinates: (!model.texture_coordinates.is_empty()).then_some(vertices),
+                normals: (!model.normals.is_empty()).then_some(vertices),
+                group: None,
+                material: None,
+                line: None,
+            });
+        }
+    }
+    Ok(())
+}
+
+// reads the body of the file from position, which is where an error is
+// placed; a value that cannot be read is not stepped over
+struct BodyReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    format: Format,
+}
+
+impl BodyReader<'_> {
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        kind.at(Position::Byte(self.position))
+    }
+
+    fn read(&mut self, scalar: Scalar) -> Result<f64, ParseError> {
+        let rest = &self.bytes[self.position..];
+        match self.format {
+            Format::Ascii => {
+                let start = rest
+                    .iter()
+                    .position(|byte| !byte.is_ascii_whitespace())
+                    .ok_or(self.error(ParseErrorKind::MissingData))?;
+                self.position += start;
+                let token = &rest[start..];
+                let length = token
+                    .iter()
+                    .position(u8::is_ascii_whitespace)
+                    .unwrap_or(token.len());
+                let value = std::str::from_utf8(&token[..length])
+                    .ok()
+                    .and_then(|token| token.parse().ok())
+                    .ok_or(self.error(ParseErrorKind::InvalidValue))?;
+                self.position += length;
+                Ok(value)
+            }
+            Format::Binary { big_endian } => {
+                let size = scalar.size();
+                let taken = rest
+                    .get(..size)
+                    .ok_or(self.error(ParseErrorKind::MissingData))?;
+                self.position += size;
+                // read as little-endian, whatever the file's order
+                let mut raw = [0; 8];
+                raw[..size].copy_from_slice(taken);
+                if big_endian {
+                    raw[..size].reverse();
+                }
+                let [b0, b1, b2, b3, ..] = raw;
+                Ok(match scalar {
+                    Scalar::Int8 => b0 as i8 as f64,
+                    Scalar::Uint8 => b0 as f64,
+                    Scalar::Int16 => i16::from_le_bytes([b0, b1]) as f64,
+                    Scalar::Uint16 => u16::from_le_bytes([b0, b1]) as f64,
+                    Scalar::Int32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    Scalar::Uint32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    Scalar::Float32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    Scalar::Float64 => f64::from_le_bytes(raw),
+                })
+            }
+        }
+    }
+
+    // the values of each property in turn, with one value for each scalar
+    fn read_row(&mut self, element: &Element) -> Result<Vec<Vec<f64>>, ParseError> {
+        element
+            .properties
+            .iter()
+            .map(|property| match property.kind {
+                PropertyKind::Scalar(scalar) => Ok(vec![self.read(scalar)?]),
+                PropertyKind::List { count, item } => {
+                    let start = self.position;
+                    let count = self.read(count)?;
+                    if count < 0.0 || count.fract() != 0.0 {
+                        return Err(ParseErrorKind::InvalidValue.at(Position::Byte(start)));
+                    }
+                    (0..count as usize).map(|_| self.read(item)).collect()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "ply\nformat {} 1.0\ncomment a coloured square\n\
+                          element vertex 4\nproperty float x\nproperty float y\n\
+                          property float z\nproperty uchar red\nproperty uchar green\n\
+                          property uchar blue\nelement face 1\n\
+                          property list uchar int vertex_indices\nend_header\n";
+
+    fn header(format: &str) -> Vec<u8> {
+        HEADER.replace("{}", format).into_bytes()
+    }
+
+    fn square() -> ObjModel {
+        let source = [
+            header("ascii"),
+            b"0 0 0 255 0 0\n1 0 0 0 255 0\n1 1 0 0 0 255\n0 1 0 255 255 255\n4 0 1 2 3\n".to_vec(),
+        ]
+        .concat();
+        parse_ply(&source).unwrap()
+    }
+
+    #[test]
+    fn read_ascii_vertices_and_faces() {
+        let model = square();
+        assert_eq!(model.vertices[2], Point::new(1.0, 1.0, 0.0));
+        assert_eq!(
+            model.colours,
+            vec![
+                Colour::new(1.0, 0.0, 0.0),
+                Colour::new(0.0, 1.0, 0.0),
+                Colour::new(0.0, 0.0, 1.0),
+                Colour::new(1.0, 1.0, 1.0),
+            ]
+        );
+        let faces: Vec<[usize; 3]> = model.faces.iter().map(|face| face.vertices).collect();
+        assert_eq!(faces, vec![[0, 1, 2], [0, 2, 3]]);
+        assert!(model.normals.is_empty());
+    }
+
+    #[test]
+    fn read_binary_encodings() {
+        let model = square();
+        for (format, big_endian) in [("binary_little_endian", false), ("binary_big_endian", true)] {
+            let mut source = header(format);
+            for (vertex, colour) in model.vertices.iter().zip(&model.colours) {
+                for value in [vertex.x, vertex.y, vertex.z] {
+                    source.extend(match big_endian {
+                        true => (value as f32).to_be_bytes(),
+                        false => (value as f32).to_le_bytes(),
+                    });
+                }
+                source.extend([colour.red, colour.green, colour.blue].map(|c| (c * 255.0) as u8));
+            }
+            source.push(4);
+            for index in 0..4_i32 {
+                source.extend(match big_endian {
+                    true => index.to_be_bytes(),
+                    false => index.to_le_bytes(),
+                });
+            }
+            assert_eq!(parse_ply(&source), Ok(model.clone()));
+            assert_eq!(
+                parse_ply(&source[..source.len() - 1]),
+                Err(ParseErrorKind::MissingData.at(Position::Byte(source.len() - 4)))
+            );
+        }
+    }
+
+    #[test]
+    fn read_texture_coordinates() {
+        let source = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\n\
+                      property float y\nproperty float z\nproperty float s\n\
+                      property float t\nelement face 1\n\
+                      property list uchar uint vertex_index\nend_header\n\
+                      0 0 0 0 0\n1 0 0 1 0\n0 1 0 0 1\n3 0 1 2\n";
+        let model = parse_ply(source.as_bytes()).unwrap();
+        assert_eq!(
+            model.texture_coordinates,
+            vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]
+        );
+        assert_eq!(model.faces[0].texture_coordinates, Some([0, 1, 2]));
+    }
+
+    #[test]
+    fn reject_bad_files() {
+        assert_eq!(
+            parse_ply(b"ply\nformat ascii 1.0\nelement vertex 1\n"),
+            Err(ParseErrorKind::InvalidHeader.at(Position::Line(4)))
+        );
+        assert_eq!(
+            parse_ply(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty half x\nend_header\n"),
+            Err(ParseErrorKind::Unsupported.at(Position::Line(4)))
+        );
+        let bad_value =
+            b"ply\nformat ascii 1.0\nelement weight 1\nproperty float w\nend_header\n x\n";
+        assert_eq!(
+            parse_ply(bad_value),
+            Err(ParseErrorKind::InvalidValue.at(Position::Byte(bad_value.len() - 2)))
+        );
+        let face_past_end = [
+            header("ascii"),
+            b"0 0 0 0 0 0\n".repeat(4),
+            b"3 0 1 4\n".to_vec(),
+        ];
+        assert_eq!(
+            parse_ply(&face_past_end.concat()).unwrap_err(),
+            ParseErrorKind::InvalidValue
+        );
+    }
+}
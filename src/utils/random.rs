@@ -0,0 +1,69 @@
+// Small, fast generator for reproducible sampling. Every stream is fully
+// determined by its seed, so renders and tests give the same output on every
+// run; Rng::for_pixel derives an independent stream per pixel (and per sample
+// within it) from one global seed, so results do not depend on the order in
+// which pixels are rendered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn for_pixel(seed: u64, [pos_x, pos_y]: [usize; 2], sample: u64) -> Rng {
+        let state = [pos_x as u64, pos_y as u64, sample]
+            .into_iter()
+            .fold(seed, |state, value| splitmix64(state ^ value));
+        Rng::new(state)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        splitmix64(self.state)
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+// finaliser of the SplitMix64 generator, which also makes a good 64 bit hash
+pub(crate) fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_is_reproducible() {
+        let mut rng1 = Rng::new(42);
+        let mut rng2 = Rng::new(42);
+        let values1: Vec<u64> = (0..8).map(|_| rng1.next_u64()).collect();
+        let values2: Vec<u64> = (0..8).map(|_| rng2.next_u64()).collect();
+        assert_eq!(values1, values2);
+        assert_ne!(Rng::new(43).next_u64(), values1[0]);
+
+        for _ in 0..1000 {
+            let value = rng1.next_f64();
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn pixel_streams_are_independent() {
+        let first = |seed, index, sample| Rng::for_pixel(seed, index, sample).next_u64();
+        assert_eq!(first(7, [3, 4], 0), first(7, [3, 4], 0));
+        assert_ne!(first(7, [3, 4], 0), first(7, [4, 3], 0));
+        assert_ne!(first(7, [3, 4], 0), first(7, [3, 4], 1));
+        assert_ne!(first(7, [3, 4], 0), first(8, [3, 4], 0));
+    }
+}
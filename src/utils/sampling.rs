@@ -0,0 +1,84 @@
+use std::f64::consts::{PI, TAU};
+
+use crate::collections::Vector;
+use crate::utils::Rng;
+
+// Random directions for Monte Carlo estimates. Hemisphere samples are about
+// the z axis; turn them to face a surface with OrthonormalBasis::from_w and
+// local_to_world. Each sampler has a matching probability density (per unit
+// solid angle) for weighting the samples it draws.
+
+// equally likely to be any direction
+pub fn uniform_sphere(rng: &mut Rng) -> Vector {
+    let z = 1.0 - 2.0 * rng.next_f64();
+    let radius = (1.0 - z * z).max(0.0).sqrt();
+    let angle = TAU * rng.next_f64();
+    Vector::new(radius * angle.cos(), radius * angle.sin(), z)
+}
+
+pub fn uniform_sphere_pdf() -> f64 {
+    1.0 / (4.0 * PI)
+}
+
+// equally likely to be any direction with z >= 0
+pub fn uniform_hemisphere(rng: &mut Rng) -> Vector {
+    let z = rng.next_f64();
+    let radius = (1.0 - z * z).max(0.0).sqrt();
+    let angle = TAU * rng.next_f64();
+    Vector::new(radius * angle.cos(), radius * angle.sin(), z)
+}
+
+pub fn uniform_hemisphere_pdf() -> f64 {
+    1.0 / TAU
+}
+
+// directions with z >= 0, more likely the closer they are to z (in proportion
+// to the cosine of the angle from it), as suits diffuse surfaces
+pub fn cosine_hemisphere(rng: &mut Rng) -> Vector {
+    let radius = rng.next_f64().sqrt();
+    let angle = TAU * rng.next_f64();
+    let (x, y) = (radius * angle.cos(), radius * angle.sin());
+    Vector::new(x, y, (1.0 - x * x - y * y).max(0.0).sqrt())
+}
+
+pub fn cosine_hemisphere_pdf(direction: Vector) -> f64 {
+    direction.z.max(0.0) / PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::approx_eq;
+
+    const SAMPLES: usize = 20000;
+
+    fn mean_z(sampler: fn(&mut Rng) -> Vector) -> f64 {
+        let mut rng = Rng::new(7);
+        (0..SAMPLES)
+            .map(|_| {
+                let direction = sampler(&mut rng);
+                approx_eq!(direction.magnitude(), 1.0);
+                direction.z
+            })
+            .sum::<f64>()
+            / SAMPLES as f64
+    }
+
+    #[test]
+    fn samplers_cover_expected_regions() {
+        // E[z] is 0 over the sphere, 1/2 over the hemisphere and 2/3 when
+        // weighted by cosine
+        assert!(mean_z(uniform_sphere).abs() < 0.02);
+        assert!((mean_z(uniform_hemisphere) - 0.5).abs() < 0.02);
+        assert!((mean_z(cosine_hemisphere) - 2.0 / 3.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn hemisphere_samples_point_up() {
+        let mut rng = Rng::new(3);
+        for _ in 0..1000 {
+            assert!(uniform_hemisphere(&mut rng).z >= 0.0);
+            assert!(cosine_hemisphere(&mut rng).z >= 0.0);
+        }
+    }
+}
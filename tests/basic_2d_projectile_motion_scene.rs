@@ -53,6 +53,6 @@ fn basic_2d_projectile_motion_scene() {
     }
 
     canvas
-        .output_to_ppm("resources/test_outputs/test_output_projmotion.ppm")
+        .write_ppm(&mut create_file("resources/test_outputs/test_output_projmotion.ppm").unwrap())
         .unwrap();
 }
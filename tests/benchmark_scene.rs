@@ -0,0 +1,58 @@
+use raytracer::prelude::*;
+
+// Fixed scene exercising primary, shadow, reflected and refracted rays, for
+// comparing per-phase render times between changes. Run with
+//     cargo test --release --test benchmark_scene -- --ignored --nocapture
+#[test]
+#[ignore]
+fn benchmark_scene() {
+    let floor = Plane::builder()
+        .set_material(
+            Material::builder()
+                .set_pattern(Box::new(Checker::new(
+                    Colour::new(0.9, 0.9, 0.9),
+                    Colour::new(0.1, 0.1, 0.1),
+                    Transform::default(),
+                )))
+                .set_specular(0.0)
+                .set_reflectance(0.2)
+                .build(),
+        )
+        .build_into();
+    let glass_sphere = Sphere::builder()
+        .set_frame_transformation(Transform::new(TransformKind::Translate(-0.5, 1.0, 0.5)))
+        .set_material(presets::glass())
+        .build_into();
+    let metal_sphere = Sphere::builder()
+        .set_frame_transformation(Transform::from(vec![
+            TransformKind::Scale(0.5, 0.5, 0.5),
+            TransformKind::Translate(1.5, 0.5, -0.5),
+        ]))
+        .set_material(presets::metal(Colour::new(0.8, 0.6, 0.2)))
+        .build_into();
+    let matte_cube = Cube::builder()
+        .set_frame_transformation(Transform::from(vec![
+            TransformKind::Scale(0.33, 0.33, 0.33),
+            TransformKind::Translate(-1.5, 0.33, -0.75),
+        ]))
+        .set_material(presets::matte(Colour::new(0.1, 0.4, 1.0)))
+        .build_into();
+    let light = Light::new(Point::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+    let world = World::builder()
+        .set_objects(vec![floor, glass_sphere, metal_sphere, matte_cube])
+        .add_light(light)
+        .build();
+
+    let camera = Camera::new(Native::new(
+        200,
+        100,
+        Angle::from_radians(std::f64::consts::FRAC_PI_3),
+        Orientation::new(
+            Point::new(0.0, 1.5, -5.0),
+            Point::new(0.0, 1.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        ),
+    ));
+    let (_image, stats) = camera.render_with_stats(&world).unwrap();
+    println!("{}", stats);
+}
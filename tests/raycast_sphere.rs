@@ -21,7 +21,7 @@ fn raycast_sphere() {
     camera
         .render(&world)
         .unwrap()
-        .output_to_ppm("resources/test_outputs/test_raycast_sphere.ppm")
+        .write_ppm(&mut create_file("resources/test_outputs/test_raycast_sphere.ppm").unwrap())
         .unwrap();
 }
 
@@ -111,7 +111,10 @@ fn raycast_scene_native_resolution() {
     ));
     let image = camera.render(&world).unwrap();
     image
-        .output_to_ppm("resources/test_outputs/test_output_raycast_scene_native.ppm")
+        .write_ppm(
+            &mut create_file("resources/test_outputs/test_output_raycast_scene_native.ppm")
+                .unwrap(),
+        )
         .unwrap();
 }
 
@@ -202,6 +205,8 @@ fn raycast_scene_agss() {
     ));
     let image = camera.render(&world).unwrap();
     image
-        .output_to_ppm("resources/test_outputs/test_output_raycast_scene_agss.ppm")
+        .write_ppm(
+            &mut create_file("resources/test_outputs/test_output_raycast_scene_agss.ppm").unwrap(),
+        )
         .unwrap();
 }